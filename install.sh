@@ -22,7 +22,9 @@ SCRIPT_DIR="$(cd "$(dirname "${BASH_SOURCE[0]}")" && pwd)"
 
 # --- Preflight checks ---
 missing=0
-for f in bin/appimage-auto systemd/appimage-auto.service config/default.toml assets/icon.png; do
+for f in bin/appimage-auto systemd/appimage-auto.service systemd/appimage-auto.socket \
+    systemd/appimage-auto-scan.service systemd/appimage-auto-scan.timer \
+    config/default.toml assets/icon.png; do
     if [[ ! -f "$SCRIPT_DIR/$f" ]]; then
         error "Missing required file: $f"
         missing=1
@@ -49,12 +51,16 @@ if [[ $HAS_GUI -eq 1 ]]; then
     install -Dm755 "$SCRIPT_DIR/bin/appimage-auto-gui" "$HOME/.local/bin/appimage-auto-gui"
 fi
 
-# --- Install systemd service (fix ExecStart path) ---
-info "Installing systemd service..."
+# --- Install systemd units (fix ExecStart path) ---
+# The socket and scan timer are for on-demand mode and left disabled
+info "Installing systemd units..."
 mkdir -p "$HOME/.local/share/systemd/user"
-sed 's|%h/\.cargo/bin/|%h/.local/bin/|g' \
-    "$SCRIPT_DIR/systemd/appimage-auto.service" \
-    > "$HOME/.local/share/systemd/user/appimage-auto.service"
+for unit in appimage-auto.service appimage-auto.socket \
+    appimage-auto-scan.service appimage-auto-scan.timer; do
+    sed 's|%h/\.cargo/bin/|%h/.local/bin/|g' \
+        "$SCRIPT_DIR/systemd/$unit" \
+        > "$HOME/.local/share/systemd/user/$unit"
+done
 
 # --- Install desktop entry (only if GUI present) ---
 if [[ $HAS_GUI -eq 1 ]]; then
@@ -63,8 +69,17 @@ if [[ $HAS_GUI -eq 1 ]]; then
     sed "s|^Exec=appimage-auto-gui|Exec=$HOME/.local/bin/appimage-auto-gui|" \
         "$SCRIPT_DIR/desktop/appimage-auto-gui.desktop" \
         > "$HOME/.local/share/applications/appimage-auto-gui.desktop"
+
+    info "Registering AppImage MIME types..."
+    "$HOME/.local/bin/appimage-auto" setup mime >/dev/null \
+        || warn "Failed to register AppImage MIME types"
 fi
 
+# --- Install file manager context menus ---
+info "Adding \"Integrate AppImage\" to file manager context menus..."
+"$HOME/.local/bin/appimage-auto" setup file-manager >/dev/null \
+    || warn "Failed to install file manager context menus"
+
 # --- Install icon ---
 info "Installing icon..."
 install -Dm644 "$SCRIPT_DIR/assets/icon.png" \
@@ -101,6 +116,9 @@ printf "  %bInstalled files:%b\n" "$bold" "$reset"
 printf "    %s\n" "$HOME/.local/bin/appimage-auto"
 [[ $HAS_GUI -eq 1 ]] && printf "    %s\n" "$HOME/.local/bin/appimage-auto-gui"
 printf "    %s\n" "$HOME/.local/share/systemd/user/appimage-auto.service"
+printf "    %s\n" "$HOME/.local/share/systemd/user/appimage-auto.socket"
+printf "    %s\n" "$HOME/.local/share/systemd/user/appimage-auto-scan.service"
+printf "    %s\n" "$HOME/.local/share/systemd/user/appimage-auto-scan.timer"
 [[ $HAS_GUI -eq 1 ]] && printf "    %s\n" "$HOME/.local/share/applications/appimage-auto-gui.desktop"
 printf "    %s\n" "$HOME/.local/share/icons/hicolor/256x256/apps/appimage-auto.png"
 printf "    %s\n" "$HOME/.config/appimage-auto/config.toml"
@@ -0,0 +1,281 @@
+//! AppArmor profiles for sandboxed AppImages.
+//!
+//! Apps with `sandbox = true` in their `[apps]` section get a profile
+//! template next to the firejail wrapper: confined to the home directory
+//! (minus SSH and GnuPG keys), with network access as set by `network`. The
+//! templates are only written by the daemon; loading them into the kernel
+//! needs root and is done with `appimage-auto apparmor install`.
+//!
+//! SELinux systems aren't supported: confining a single user binary there
+//! needs a compiled policy module rather than a text profile.
+
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use thiserror::Error;
+use tracing::{debug, info};
+
+/// Kernel parameter that reads `Y` when AppArmor is enabled
+const ENABLED_PARAMETER: &str = "/sys/module/apparmor/parameters/enabled";
+
+#[derive(Error, Debug)]
+pub enum AppArmorError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("No data directory found")]
+    NoDataDir,
+    #[error("AppArmor is not enabled on this system (SELinux is not supported)")]
+    Unavailable,
+    #[error("apparmor_parser failed: {0}")]
+    Parser(String),
+    #[error("Path contains control characters, can't write a profile for it: {0:?}")]
+    UnsafePath(PathBuf),
+}
+
+/// Name of the profile for an AppImage
+pub fn profile_name(identifier: &str) -> String {
+    format!("appimage-auto.{}", identifier)
+}
+
+/// Directory the profile templates are written to
+pub fn profile_dir() -> Result<PathBuf, AppArmorError> {
+    let dirs =
+        directories::ProjectDirs::from("", "", "appimage-auto").ok_or(AppArmorError::NoDataDir)?;
+    Ok(dirs.data_dir().join("apparmor"))
+}
+
+/// Path of the profile template for an AppImage
+pub fn profile_path(identifier: &str) -> Result<PathBuf, AppArmorError> {
+    Ok(profile_dir()?.join(profile_name(identifier)))
+}
+
+/// Whether AppArmor is enabled in the running kernel
+pub fn is_available() -> bool {
+    fs::read_to_string(ENABLED_PARAMETER).is_ok_and(|value| value.trim() == "Y")
+}
+
+/// Render the profile for an AppImage at `appimage_path`.
+///
+/// Control characters would end the comment or the quoted path early and
+/// let the rest of a file name add rules: they're replaced with spaces in
+/// the name, and a path holding any is rejected.
+pub fn render_profile(
+    identifier: &str,
+    name: Option<&str>,
+    appimage_path: &Path,
+    network: bool,
+) -> Result<String, AppArmorError> {
+    if appimage_path
+        .to_string_lossy()
+        .chars()
+        .any(char::is_control)
+    {
+        return Err(AppArmorError::UnsafePath(appimage_path.to_path_buf()));
+    }
+    let name: String = name
+        .unwrap_or("AppImage")
+        .chars()
+        .map(|c| if c.is_control() { ' ' } else { c })
+        .collect();
+    let network_rules = if network {
+        "  include <abstractions/nameservice>\n  \
+         include <abstractions/ssl_certs>\n  \
+         network inet,\n  \
+         network inet6,\n  \
+         network netlink raw,\n"
+    } else {
+        "  deny network inet,\n  deny network inet6,\n"
+    };
+
+    Ok(format!(
+        "\
+# AppArmor profile for {name}, generated by appimage-auto.
+# Load it with `appimage-auto apparmor install {identifier}`.
+abi <abi/3.0>,
+
+include <tunables/global>
+
+profile {profile} \"{path}\" flags=(attach_disconnected) {{
+  include <abstractions/base>
+  include <abstractions/fonts>
+  include <abstractions/X>
+  include <abstractions/wayland>
+  include <abstractions/audio>
+  include <abstractions/dbus-session-strict>
+  include <abstractions/freedesktop.org>
+
+{network_rules}
+  # The AppImage, its FUSE mount and extract-and-run copies
+  \"{path}\" mrix,
+  /{{usr/,}}bin/fusermount{{,3}} Ux,
+  /dev/fuse rw,
+  /tmp/.mount_*/ r,
+  /tmp/.mount_*/** mrix,
+  owner /tmp/appimage_extracted_*/** rwmix,
+
+  /{{usr/,}}bin/** mrix,
+  /{{usr/,}}lib{{,32,64}}/** mr,
+  /usr/share/** r,
+  /etc/** r,
+  @{{PROC}}/** r,
+  /sys/** r,
+  owner /dev/shm/** rwk,
+  owner /tmp/** rwk,
+
+  # Confined to the home directory, without its keys
+  owner @{{HOME}}/ r,
+  owner @{{HOME}}/** rwkl,
+  deny @{{HOME}}/.ssh/** rw,
+  deny @{{HOME}}/.gnupg/** rw,
+}}
+",
+        profile = profile_name(identifier),
+        path = quote_path(appimage_path),
+    ))
+}
+
+/// Write the profile template for an AppImage, returning its path
+pub fn write_template(
+    identifier: &str,
+    name: Option<&str>,
+    appimage_path: &Path,
+    network: bool,
+) -> Result<PathBuf, AppArmorError> {
+    let path = profile_path(identifier)?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(
+        &path,
+        render_profile(identifier, name, appimage_path, network)?,
+    )?;
+    debug!("Wrote AppArmor profile template {:?}", path);
+    Ok(path)
+}
+
+/// Load (or reload) a profile into the kernel with `apparmor_parser`,
+/// through `sudo` unless running as root
+pub fn load(profile: &Path) -> Result<(), AppArmorError> {
+    if !is_available() {
+        return Err(AppArmorError::Unavailable);
+    }
+    parser(&["-r", "--"], profile)?;
+    info!("Loaded AppArmor profile {:?}", profile);
+    Ok(())
+}
+
+/// Unload a profile from the kernel and remove its template
+pub fn unload(profile: &Path) -> Result<(), AppArmorError> {
+    if !is_available() {
+        return Err(AppArmorError::Unavailable);
+    }
+    // The parser only needs the profile's name, so a template that was
+    // removed can still be unloaded from its rendered text
+    if profile.exists() {
+        parser(&["-R", "--"], profile)?;
+        fs::remove_file(profile)?;
+    } else {
+        let name = profile
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        parser_stdin(&["-R"], &format!("profile {} {{}}\n", name))?;
+    }
+    info!("Unloaded AppArmor profile {:?}", profile);
+    Ok(())
+}
+
+/// Quote a path for a profile, escaping AppArmor's glob characters so it
+/// only matches itself
+fn quote_path(path: &Path) -> String {
+    let mut quoted = String::new();
+    for c in path.to_string_lossy().chars() {
+        if matches!(c, '"' | '\\' | '*' | '?' | '[' | ']' | '{' | '}' | '^') {
+            quoted.push('\\');
+        }
+        quoted.push(c);
+    }
+    quoted
+}
+
+/// `apparmor_parser`, run through `sudo` unless running as root
+fn parser_command() -> Command {
+    // SAFETY: geteuid has no preconditions and cannot fail
+    if unsafe { libc::geteuid() } == 0 {
+        Command::new("apparmor_parser")
+    } else {
+        let mut command = Command::new("sudo");
+        command.arg("apparmor_parser");
+        command
+    }
+}
+
+/// Run `apparmor_parser` on a file, turning a failure into an error
+fn parser(args: &[&str], profile: &Path) -> Result<(), AppArmorError> {
+    // sudo may ask for a password
+    let output = parser_command()
+        .args(args)
+        .arg(profile)
+        .stdin(Stdio::inherit())
+        .output()?;
+    check(output)
+}
+
+/// Run `apparmor_parser` on a profile given on stdin
+fn parser_stdin(args: &[&str], profile: &str) -> Result<(), AppArmorError> {
+    let mut child = parser_command()
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(profile.as_bytes())?;
+    }
+    check(child.wait_with_output()?)
+}
+
+/// Turn a failed `apparmor_parser` run into an error with its stderr
+fn check(output: std::process::Output) -> Result<(), AppArmorError> {
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(AppArmorError::Parser(
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_profile() {
+        let path = Path::new("/home/user/Apps/My App [beta].AppImage");
+        let profile = render_profile("0123456789abcdef", Some("My App"), path, true).unwrap();
+        assert!(profile.starts_with("# AppArmor profile for My App,"));
+        assert!(profile.contains(
+            "profile appimage-auto.0123456789abcdef \
+             \"/home/user/Apps/My App \\[beta\\].AppImage\" flags=(attach_disconnected) {"
+        ));
+        assert!(profile.contains("  network inet,\n"));
+        assert!(profile.contains("  /{usr/,}bin/fusermount{,3} Ux,\n"));
+        assert!(profile.trim_end().ends_with('}'));
+
+        let offline = render_profile("0123456789abcdef", None, path, false).unwrap();
+        assert!(offline.contains("  deny network inet,\n"));
+        assert!(!offline.contains("  network inet,\n"));
+
+        // Neither may start a line of their own
+        let name = "App\n  /** rwix,";
+        let profile = render_profile("0123456789abcdef", Some(name), path, false).unwrap();
+        assert!(profile.starts_with("# AppArmor profile for App   /** rwix,"));
+        let path = Path::new("/home/user/App\"\n  /** rwix,\n#.AppImage");
+        assert!(matches!(
+            render_profile("0123456789abcdef", None, path, false),
+            Err(AppArmorError::UnsafePath(_))
+        ));
+    }
+}
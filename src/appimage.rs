@@ -1,9 +1,15 @@
 //! AppImage detection, extraction, and integration logic.
 
+use crate::appstream;
+use crate::flatpak;
+use crate::squashfs::{EntryKind, Squashfs, SquashfsError};
+use std::collections::BTreeMap;
+use std::ffi::OsStr;
 use std::fs::{self, File};
 use std::io::{Read, Seek, SeekFrom};
+use std::os::unix::fs::FileExt;
 use std::path::{Path, PathBuf};
-use std::process::Command;
+use std::process::{Child, Command};
 use thiserror::Error;
 use tracing::{debug, info};
 
@@ -19,6 +25,19 @@ const APPIMAGE_TYPE2_MAGIC: [u8; 3] = [0x41, 0x49, 0x02];
 /// SquashFS superblock magic number (little-endian: "hsqs")
 const SQUASHFS_MAGIC: [u8; 4] = [0x68, 0x73, 0x71, 0x73];
 
+/// Size of the SquashFS superblock
+const SQUASHFS_SUPERBLOCK_SIZE: usize = 96;
+
+/// Size of a 64-bit ELF header (32-bit ones are smaller)
+const ELF64_HEADER_SIZE: usize = 64;
+
+/// Smallest file that can be an AppImage: an ELF header followed by a
+/// SquashFS superblock
+const MIN_APPIMAGE_SIZE: u64 = (ELF64_HEADER_SIZE + SQUASHFS_SUPERBLOCK_SIZE) as u64;
+
+/// Offset of the primary volume descriptor in an ISO 9660 image
+const ISO9660_PVD_OFFSET: u64 = 0x8000;
+
 #[derive(Error, Debug)]
 pub enum AppImageError {
     #[error("IO error: {0}")]
@@ -31,6 +50,29 @@ pub enum AppImageError {
     NoDesktopFile,
     #[error("Failed to parse .desktop file: {0}")]
     DesktopParseError(String),
+    #[error("Linux binary compatibility is not enabled (kldload linux64)")]
+    LinuxAbiUnavailable,
+    #[error("SquashFS error: {0}")]
+    Squashfs(#[from] SquashfsError),
+}
+
+impl AppImageError {
+    /// Whether the file was locked or still being written (`EBUSY` or
+    /// `ETXTBSY`), so trying again later may succeed
+    pub fn is_busy(&self) -> bool {
+        match self {
+            AppImageError::Io(e) | AppImageError::Squashfs(SquashfsError::Io(e)) => is_busy(e),
+            _ => false,
+        }
+    }
+}
+
+/// Whether an I/O error means the file is locked or open for writing
+pub fn is_busy(e: &std::io::Error) -> bool {
+    matches!(
+        e.kind(),
+        std::io::ErrorKind::ResourceBusy | std::io::ErrorKind::ExecutableFileBusy
+    )
 }
 
 /// Represents an AppImage type
@@ -48,6 +90,16 @@ pub struct AppImageInfo {
     pub desktop_file: Option<PathBuf>,
     pub icon_files: Vec<PathBuf>,
     pub name: Option<String>,
+    /// Version from the embedded desktop entry, if declared
+    pub version: Option<String>,
+    /// Project links from the bundled AppStream metainfo, by type
+    pub links: BTreeMap<String, String>,
+    /// App id from the bundled AppStream metainfo
+    pub app_id: Option<String>,
+    /// One-line summary from the bundled AppStream metainfo
+    pub summary: Option<String>,
+    /// Translated names and summaries from the bundled AppStream metainfo
+    pub translations: appstream::Translations,
 }
 
 /// Check if a file is a valid AppImage by examining magic bytes
@@ -86,25 +138,22 @@ fn check_magic_bytes(path: &Path) -> Result<Option<AppImageType>, AppImageError>
     let mut header = [0u8; 16];
 
     let bytes_read = file.read(&mut header)?;
-    if bytes_read < 11 {
-        return Ok(None);
-    }
+    Ok(magic_type(&header[..bytes_read]))
+}
 
-    // Check ELF magic
-    if header[0..4] != ELF_MAGIC {
-        return Ok(None);
+/// AppImage type from the first bytes of a file: the ELF magic, then the
+/// AppImage magic at offset 8
+fn magic_type(header: &[u8]) -> Option<AppImageType> {
+    if header.len() < 11 || header[0..4] != ELF_MAGIC {
+        return None;
     }
-
-    // Check AppImage magic at offset 8
     if header[8..11] == APPIMAGE_TYPE1_MAGIC {
-        return Ok(Some(AppImageType::Type1));
-    }
-
-    if header[8..11] == APPIMAGE_TYPE2_MAGIC {
-        return Ok(Some(AppImageType::Type2));
+        Some(AppImageType::Type1)
+    } else if header[8..11] == APPIMAGE_TYPE2_MAGIC {
+        Some(AppImageType::Type2)
+    } else {
+        None
     }
-
-    Ok(None)
 }
 
 /// Get the AppImage type
@@ -114,9 +163,11 @@ pub fn get_appimage_type(path: &Path) -> Result<AppImageType, AppImageError> {
 
 /// Check if an AppImage file is complete (not a partial download)
 ///
-/// Validates by reading the SquashFS superblock's `bytes_used` field
-/// and comparing against actual file size. This detects incomplete files
-/// that may still be downloading or copying.
+/// Reads the headers without running the file: for type 2 the embedded
+/// SquashFS image starts where the ELF runtime ends (as the runtime itself
+/// computes it), and its superblock's `bytes_used` must fit in the file. For
+/// type 1 the ISO 9660 volume size is compared instead. This detects
+/// incomplete files that may still be downloading or copying.
 ///
 /// # Returns
 /// - `Ok(true)` if the file is structurally complete
@@ -126,84 +177,350 @@ pub fn is_appimage_complete(path: &Path) -> Result<bool, AppImageError> {
     let mut file = File::open(path)?;
     let file_size = file.metadata()?.len();
 
-    // Get squashfs offset using --appimage-offset (requires executable)
-    let offset = match get_squashfs_offset(path) {
-        Ok(o) => o,
-        Err(e) => {
-            debug!("Could not get squashfs offset for {:?}: {}", path, e);
+    let mut header = [0u8; ELF64_HEADER_SIZE];
+    if read_at(&mut file, 0, &mut header)? < header.len() {
+        debug!("File too small for an ELF header: {:?}", path);
+        return Ok(false);
+    }
+
+    let expected_size = if header[8..11] == APPIMAGE_TYPE1_MAGIC {
+        let mut descriptor = [0u8; 136];
+        if read_at(&mut file, ISO9660_PVD_OFFSET, &mut descriptor)? < descriptor.len() {
+            debug!(
+                "File too small for an ISO 9660 volume descriptor: {:?}",
+                path
+            );
             return Ok(false);
         }
+        iso9660_size(&descriptor)
+    } else {
+        let Some(offset) = elf_size(&header) else {
+            debug!("Could not read the ELF section headers of {:?}", path);
+            return Ok(false);
+        };
+        let mut superblock = [0u8; SQUASHFS_SUPERBLOCK_SIZE];
+        if read_at(&mut file, offset, &mut superblock)? < superblock.len() {
+            debug!("File too small to read squashfs superblock: {:?}", path);
+            return Ok(false);
+        }
+        squashfs_size(&superblock).map(|bytes_used| offset + bytes_used)
     };
 
-    // Seek to squashfs superblock
-    if file.seek(SeekFrom::Start(offset)).is_err() {
-        debug!("Could not seek to offset {} in {:?}", offset, path);
+    let Some(expected_size) = expected_size else {
+        debug!("Invalid filesystem image in {:?}", path);
         return Ok(false);
+    };
+    let is_complete = file_size >= expected_size;
+
+    debug!(
+        "AppImage completeness check: file_size={}, expected={}, complete={}",
+        file_size, expected_size, is_complete
+    );
+
+    Ok(is_complete)
+}
+
+/// Size of the ELF runtime, i.e. the end of its section header table, which
+/// is where the filesystem image of a type 2 AppImage starts
+fn elf_size(header: &[u8]) -> Option<u64> {
+    if header.get(0..4)? != ELF_MAGIC {
+        return None;
     }
+    let little_endian = match header[5] {
+        1 => true,
+        2 => false,
+        _ => return None,
+    };
+    let read = |at: usize, len: usize| read_uint(header, at, len, little_endian);
 
-    // Read superblock header (need at least 48 bytes to get bytes_used)
-    let mut superblock = [0u8; 96];
-    let bytes_read = file.read(&mut superblock)?;
-    if bytes_read < 48 {
-        debug!(
-            "File too small to read squashfs superblock: {} bytes read",
-            bytes_read
-        );
-        return Ok(false);
+    // e_shoff, e_shentsize and e_shnum
+    let (shoff, shentsize, shnum) = match header[4] {
+        1 => (read(0x20, 4)?, read(0x2E, 2)?, read(0x30, 2)?),
+        2 => (read(0x28, 8)?, read(0x3A, 2)?, read(0x3C, 2)?),
+        _ => return None,
+    };
+    shoff.checked_add(shentsize * shnum)
+}
+
+/// A section of an AppImage's ELF runtime
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ElfSection {
+    pub name: String,
+    /// Where the section's contents start in the file
+    pub offset: u64,
+    pub size: u64,
+}
+
+/// Offset and size of a named ELF section of an AppImage's runtime, such as
+/// `.sha256_sig` holding its signature
+pub fn elf_section(path: &Path, name: &str) -> Result<Option<(u64, u64)>, AppImageError> {
+    Ok(elf_sections(path)?
+        .into_iter()
+        .find(|section| section.name == name)
+        .map(|section| (section.offset, section.size)))
+}
+
+/// The section table of an AppImage's ELF runtime, in file order. Empty if
+/// the file isn't an ELF file or its section table can't be read.
+pub fn elf_sections(path: &Path) -> Result<Vec<ElfSection>, AppImageError> {
+    let mut file = File::open(path)?;
+    let mut header = [0u8; ELF64_HEADER_SIZE];
+    if read_at(&mut file, 0, &mut header)? < ELF64_HEADER_SIZE || header[0..4] != ELF_MAGIC {
+        return Ok(Vec::new());
     }
+    let little_endian = header[5] == 1;
+    let is_64 = match header[4] {
+        1 => false,
+        2 => true,
+        _ => return Ok(Vec::new()),
+    };
+    let read = |bytes: &[u8], at: usize, len: usize| read_uint(bytes, at, len, little_endian);
 
-    // Verify squashfs magic
-    if superblock[0..4] != SQUASHFS_MAGIC {
-        debug!(
-            "Invalid squashfs magic at offset {}: {:02x?}",
+    // e_shoff, e_shentsize, e_shnum and e_shstrndx
+    let fields = if is_64 {
+        [(0x28, 8), (0x3A, 2), (0x3C, 2), (0x3E, 2)]
+    } else {
+        [(0x20, 4), (0x2E, 2), (0x30, 2), (0x32, 2)]
+    };
+    let [Some(shoff), Some(shentsize), Some(shnum), Some(shstrndx)] =
+        fields.map(|(at, len)| read(&header, at, len))
+    else {
+        return Ok(Vec::new());
+    };
+    if shentsize == 0 || shnum > 4096 {
+        return Ok(Vec::new());
+    }
+    let mut table = vec![0u8; (shentsize * shnum) as usize];
+    if read_at(&mut file, shoff, &mut table)? < table.len() {
+        return Ok(Vec::new());
+    }
+
+    // sh_name, sh_offset and sh_size of a section header
+    let section = |index: u64| -> Option<(u64, u64, u64)> {
+        let entry = table.get((index * shentsize) as usize..)?;
+        let (offset, size) = if is_64 {
+            (read(entry, 0x18, 8)?, read(entry, 0x20, 8)?)
+        } else {
+            (read(entry, 0x10, 4)?, read(entry, 0x14, 4)?)
+        };
+        Some((read(entry, 0, 4)?, offset, size))
+    };
+    let Some((_, names_offset, names_size)) = section(shstrndx) else {
+        return Ok(Vec::new());
+    };
+    if names_size > 1 << 20 {
+        return Ok(Vec::new());
+    }
+    let mut names = vec![0u8; names_size as usize];
+    let read_len = read_at(&mut file, names_offset, &mut names)?;
+    names.truncate(read_len);
+
+    let mut sections = Vec::new();
+    for index in 0..shnum {
+        let Some((name_offset, offset, size)) = section(index) else {
+            continue;
+        };
+        let Some(rest) = names.get(name_offset as usize..) else {
+            continue;
+        };
+        let end = rest.iter().position(|&b| b == 0).unwrap_or(rest.len());
+        sections.push(ElfSection {
+            name: String::from_utf8_lossy(&rest[..end]).into_owned(),
             offset,
-            &superblock[0..4]
-        );
-        return Ok(false);
+            size,
+        });
     }
+    Ok(sections)
+}
 
-    // Read bytes_used (little-endian u64 at offset 40 in superblock)
-    let bytes_used = u64::from_le_bytes(
-        superblock[40..48]
-            .try_into()
-            .expect("slice is exactly 8 bytes"),
-    );
+/// ELF section holding the update information
+pub const UPDATE_INFO_SECTION: &str = ".upd_info";
 
-    // Check if file is complete
-    let expected_size = offset + bytes_used;
-    let is_complete = file_size >= expected_size;
+/// ELF section holding the detached GPG signature
+pub const SIGNATURE_SECTION: &str = ".sha256_sig";
 
-    debug!(
-        "AppImage completeness check: file_size={}, offset={}, bytes_used={}, expected={}, complete={}",
-        file_size, offset, bytes_used, expected_size, is_complete
-    );
+/// ELF section holding the signer's public key
+pub const KEY_SECTION: &str = ".sig_key";
 
-    Ok(is_complete)
+/// ELF section holding the MD5 digest of the file (with the section zeroed)
+pub const MD5_DIGEST_SECTION: &str = ".digest_md5";
+
+/// Sections larger than this aren't read
+const MAX_SECTION_SIZE: u64 = 1 << 20;
+
+/// Contents of a named ELF section of an AppImage's runtime, or `None` if it
+/// has no such section
+pub fn read_elf_section(path: &Path, name: &str) -> Result<Option<Vec<u8>>, AppImageError> {
+    let Some((offset, size)) = elf_section(path, name)? else {
+        return Ok(None);
+    };
+    let mut data = vec![0u8; size.min(MAX_SECTION_SIZE) as usize];
+    let read_len = read_at(&mut File::open(path)?, offset, &mut data)?;
+    data.truncate(read_len);
+    Ok(Some(data))
 }
 
-/// Get squashfs offset by running --appimage-offset
-///
-/// This requires the AppImage to be executable. If it's not, we'll make it
-/// executable first.
-fn get_squashfs_offset(path: &Path) -> Result<u64, AppImageError> {
-    // Ensure the AppImage is executable first
-    make_executable(path)?;
+/// Text of a section, up to its NUL padding, or `None` if it is missing or
+/// empty. `appimagetool` reserves the sections up front, so unset ones are
+/// all zeros.
+fn section_text(path: &Path, name: &str) -> Result<Option<String>, AppImageError> {
+    let Some(data) = read_elf_section(path, name)? else {
+        return Ok(None);
+    };
+    let end = data.iter().position(|&b| b == 0).unwrap_or(data.len());
+    let text = String::from_utf8_lossy(&data[..end]).trim().to_string();
+    Ok((!text.is_empty()).then_some(text))
+}
 
-    let output = Command::new(path).arg("--appimage-offset").output()?;
+/// Where an AppImage looks for updates, from its `.upd_info` section
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UpdateInformation {
+    /// `zsync|<url>`: a `.zsync` file at a fixed URL
+    Zsync { url: String },
+    /// `gh-releases-zsync|<owner>|<repo>|<tag>|<file name>`: a `.zsync`
+    /// file attached to a GitHub release; `<tag>` can be `latest` and the
+    /// file name can contain `*` wildcards
+    GithubReleases {
+        owner: String,
+        repo: String,
+        tag: String,
+        file_name: String,
+    },
+    /// `pling-v1-zsync|<product id>|<file name>`: a file on pling.com
+    /// (the AppImageHub store)
+    Pling {
+        product_id: String,
+        file_name: String,
+    },
+    /// Any other transport, kept verbatim
+    Other(String),
+}
 
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        return Err(AppImageError::ExtractionFailed(format!(
-            "--appimage-offset failed: {}",
-            stderr
-        )));
+impl UpdateInformation {
+    /// Parse the update information string of the AppImage specification
+    pub fn parse(text: &str) -> Self {
+        let fields: Vec<&str> = text.split('|').map(str::trim).collect();
+        match fields.as_slice() {
+            ["zsync", url] => UpdateInformation::Zsync {
+                url: url.to_string(),
+            },
+            ["gh-releases-zsync", owner, repo, tag, file_name] => {
+                UpdateInformation::GithubReleases {
+                    owner: owner.to_string(),
+                    repo: repo.to_string(),
+                    tag: tag.to_string(),
+                    file_name: file_name.to_string(),
+                }
+            }
+            // `ocs-v1-appimagehub-zsync` is the older name of the transport
+            [
+                "pling-v1-zsync" | "ocs-v1-appimagehub-zsync",
+                product_id,
+                file_name,
+            ] => UpdateInformation::Pling {
+                product_id: product_id.to_string(),
+                file_name: file_name.to_string(),
+            },
+            _ => UpdateInformation::Other(text.to_string()),
+        }
+    }
+}
+
+/// The update information embedded in an AppImage, if any
+pub fn update_information(path: &Path) -> Result<Option<UpdateInformation>, AppImageError> {
+    Ok(section_text(path, UPDATE_INFO_SECTION)?.map(|text| UpdateInformation::parse(&text)))
+}
+
+/// The ASCII-armored signature embedded in a signed AppImage
+pub fn embedded_signature(path: &Path) -> Result<Option<String>, AppImageError> {
+    section_text(path, SIGNATURE_SECTION)
+}
+
+/// The ASCII-armored public key embedded in a signed AppImage
+pub fn embedded_signing_key(path: &Path) -> Result<Option<String>, AppImageError> {
+    section_text(path, KEY_SECTION)
+}
+
+/// The MD5 digest embedded in an AppImage, as a hex string, or `None` if the
+/// section is missing or was never filled in
+pub fn embedded_md5(path: &Path) -> Result<Option<String>, AppImageError> {
+    let Some(digest) = read_elf_section(path, MD5_DIGEST_SECTION)? else {
+        return Ok(None);
+    };
+    if digest.len() != 16 || digest.iter().all(|&b| b == 0) {
+        return Ok(None);
+    }
+    Ok(Some(digest.iter().map(|b| format!("{:02x}", b)).collect()))
+}
+
+/// Unsigned integer of `len` bytes at `at`
+fn read_uint(bytes: &[u8], at: usize, len: usize, little_endian: bool) -> Option<u64> {
+    let bytes = bytes.get(at..at.checked_add(len)?)?;
+    let mut buf = [0u8; 8];
+    if little_endian {
+        buf[..len].copy_from_slice(bytes);
+        Some(u64::from_le_bytes(buf))
+    } else {
+        buf[8 - len..].copy_from_slice(bytes);
+        Some(u64::from_be_bytes(buf))
+    }
+}
+
+/// `bytes_used` of a SquashFS 4.x superblock, if it is a valid one
+fn squashfs_size(superblock: &[u8]) -> Option<u64> {
+    if superblock.get(0..4)? != SQUASHFS_MAGIC {
+        return None;
+    }
+    let u16_at = |at: usize| u16::from_le_bytes([superblock[at], superblock[at + 1]]);
+    let block_size = u32::from_le_bytes(superblock[12..16].try_into().ok()?);
+    let block_log = u16_at(22);
+    let major = u16_at(28);
+    if major != 4 || block_log >= 32 || block_size != 1 << block_log {
+        return None;
+    }
+    Some(u64::from_le_bytes(superblock[40..48].try_into().ok()?))
+}
+
+/// Size of an ISO 9660 image from its primary volume descriptor
+fn iso9660_size(descriptor: &[u8]) -> Option<u64> {
+    if descriptor.get(0..6)? != b"\x01CD001" {
+        return None;
+    }
+    // Both-endian fields; the little-endian half comes first
+    let blocks = u32::from_le_bytes(descriptor[80..84].try_into().ok()?);
+    let block_size = u16::from_le_bytes(descriptor[128..130].try_into().ok()?);
+    Some(u64::from(blocks) * u64::from(block_size))
+}
+
+/// Read up to `buf.len()` bytes at `offset`, returning how many were read
+fn read_at(file: &mut File, offset: u64, buf: &mut [u8]) -> std::io::Result<usize> {
+    file.seek(SeekFrom::Start(offset))?;
+    let mut read = 0;
+    while read < buf.len() {
+        match file.read(&mut buf[read..])? {
+            0 => break,
+            n => read += n,
+        }
+    }
+    Ok(read)
+}
+
+/// The file a symlink ultimately points to, or `None` if `path` is not a
+/// symlink or is dangling
+pub fn symlink_target(path: &Path) -> Option<PathBuf> {
+    let metadata = fs::symlink_metadata(path).ok()?;
+    if !metadata.file_type().is_symlink() {
+        return None;
     }
+    fs::canonicalize(path).ok()
+}
 
-    let offset_str = String::from_utf8_lossy(&output.stdout);
-    offset_str
-        .trim()
-        .parse::<u64>()
-        .map_err(|e| AppImageError::ExtractionFailed(format!("Invalid offset '{}': {}", offset_str.trim(), e)))
+/// Device and inode of the file a path reaches, following symlinks
+pub fn file_id(path: &Path) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+
+    let metadata = fs::metadata(path).ok()?;
+    Some((metadata.dev(), metadata.ino()))
 }
 
 /// Make an AppImage executable
@@ -226,9 +543,44 @@ pub fn make_executable(path: &Path) -> Result<(), AppImageError> {
     Ok(())
 }
 
+/// Build the command that launches an AppImage.
+///
+/// Type 2 AppImages mount themselves with FUSE; without it they are told to
+/// extract to a temporary directory and run from there instead.
+pub fn launch_command(path: &Path, appimage_type: AppImageType, fuse_available: bool) -> Command {
+    let env: &[(&str, &str)] = if appimage_type == AppImageType::Type2 && !fuse_available {
+        &[("APPIMAGE_EXTRACT_AND_RUN", "1")]
+    } else {
+        &[]
+    };
+    flatpak::host_command(path, env)
+}
+
+/// Make an AppImage executable and start it, without integrating it
+pub fn run_once<I, S>(path: &Path, args: I) -> Result<Child, AppImageError>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<OsStr>,
+{
+    let appimage_type = get_appimage_type(path)?;
+    if !linux_abi_available() {
+        return Err(AppImageError::LinuxAbiUnavailable);
+    }
+    make_executable(path)?;
+
+    let fuse_available = Path::new("/dev/fuse").exists();
+    if !fuse_available {
+        info!("FUSE not available, extracting {:?} to run it", path);
+    }
+    Ok(launch_command(path, appimage_type, fuse_available)
+        .args(args)
+        .spawn()?)
+}
+
 /// Extract metadata from an AppImage
 ///
 /// Extracts .desktop and icon files to a temporary directory and returns info about them.
+#[tracing::instrument(level = "debug", skip(extract_dir))]
 pub fn extract_metadata(path: &Path, extract_dir: &Path) -> Result<AppImageInfo, AppImageError> {
     let appimage_type = get_appimage_type(path)?;
 
@@ -238,13 +590,30 @@ pub fn extract_metadata(path: &Path, extract_dir: &Path) -> Result<AppImageInfo,
     // Create extraction directory
     fs::create_dir_all(extract_dir)?;
 
-    // Try selective extraction first (faster)
-    let selective_ok = try_selective_extract(path, extract_dir);
+    // Read the metadata files straight from the SquashFS image; the
+    // AppImage's own extraction handles what the reader can't, such as type
+    // 1 images and compressors other than gzip and zstd
+    let extracted = match appimage_type {
+        AppImageType::Type2 => match extract_native(path, extract_dir) {
+            Ok(count) => {
+                debug!("Extracted {} metadata entries from {:?}", count, path);
+                true
+            }
+            Err(e) => {
+                debug!("Reading the SquashFS image failed: {}", e);
+                false
+            }
+        },
+        AppImageType::Type1 => false,
+    };
 
-    // If selective extraction fails, do full extraction
-    if !selective_ok {
-        debug!("Selective extraction failed, trying full extraction");
-        full_extract(path, extract_dir)?;
+    if !extracted {
+        if !linux_abi_available() {
+            return Err(AppImageError::LinuxAbiUnavailable);
+        }
+        if !try_selective_extract(path, extract_dir) {
+            full_extract(path, extract_dir)?;
+        }
     }
 
     // Find extracted files
@@ -255,16 +624,383 @@ pub fn extract_metadata(path: &Path, extract_dir: &Path) -> Result<AppImageInfo,
         .and_then(|p| p.file_stem())
         .map(|s| s.to_string_lossy().to_string());
 
+    let version = desktop_file
+        .as_ref()
+        .and_then(|p| crate::desktop::DesktopEntry::parse(p).ok())
+        .and_then(|entry| entry.version().map(str::to_string));
+
+    let metainfo = find_metainfo(extract_dir).and_then(|p| fs::read_to_string(p).ok());
+    let links = metainfo
+        .as_deref()
+        .map(appstream::parse_urls)
+        .unwrap_or_default();
+    let app_id = metainfo.as_deref().and_then(appstream::parse_app_id);
+    let summary = metainfo.as_deref().and_then(appstream::parse_summary);
+    let translations = metainfo
+        .as_deref()
+        .map(appstream::parse_translations)
+        .unwrap_or_default();
+
     Ok(AppImageInfo {
         path: path.to_path_buf(),
         appimage_type,
         desktop_file,
         icon_files,
         name,
+        version,
+        links,
+        app_id,
+        summary,
+        translations,
     })
 }
 
-/// Try to selectively extract only .desktop and icon files
+/// List the AppImages directly inside a directory (not recursive), sorted.
+///
+/// In-progress browser downloads are skipped. Made for huge folders on slow
+/// disks: files too small to hold an ELF header and a SquashFS superblock
+/// are skipped without being opened, and the others have their magic bytes read with a single
+/// positioned read into one buffer reused for the whole scan.
+pub fn scan_directory(dir: &Path) -> std::io::Result<Vec<PathBuf>> {
+    let mut header = [0u8; 16];
+    let mut paths = Vec::new();
+
+    for entry in fs::read_dir(dir)?.filter_map(|e| e.ok()) {
+        let path = entry.path();
+        // Follows symlinks, so linked AppImages are listed too
+        let Ok(metadata) = fs::metadata(&path) else {
+            continue;
+        };
+        if !metadata.is_file()
+            || metadata.len() < MIN_APPIMAGE_SIZE
+            || crate::watcher::is_partial_download(&path)
+        {
+            continue;
+        }
+        let is_appimage = File::open(&path)
+            .and_then(|file| file.read_exact_at(&mut header, 0))
+            .is_ok_and(|()| magic_type(&header).is_some());
+        if is_appimage {
+            paths.push(path);
+        }
+    }
+
+    paths.sort();
+    Ok(paths)
+}
+
+/// Compute the MD5 hash of an AppImage's contents, as a hex string
+pub fn content_hash(path: &Path) -> Result<String, AppImageError> {
+    let mut file = File::open(path)?;
+    let mut context = md5::Context::new();
+    let mut buffer = vec![0u8; 64 * 1024];
+
+    loop {
+        let n = file.read(&mut buffer)?;
+        if n == 0 {
+            break;
+        }
+        context.consume(&buffer[..n]);
+    }
+
+    Ok(format!("{:x}", context.finalize()))
+}
+
+/// Where a downloaded file came from, as recorded by the browser
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DownloadOrigin {
+    /// URL the file was downloaded from (`user.xdg.origin.url`)
+    pub url: Option<String>,
+    /// Page the download was started from (`user.xdg.referrer.url`)
+    pub referrer: Option<String>,
+}
+
+/// Read the download origin from the extended attributes browsers set on
+/// downloaded files. Missing attributes (or filesystems without xattr
+/// support) give an empty origin.
+pub fn download_origin(path: &Path) -> DownloadOrigin {
+    DownloadOrigin {
+        url: read_xattr(path, "user.xdg.origin.url"),
+        referrer: read_xattr(path, "user.xdg.referrer.url"),
+    }
+}
+
+/// Read an extended attribute as a non-empty UTF-8 string
+fn read_xattr(path: &Path, name: &str) -> Option<String> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let c_path = CString::new(path.as_os_str().as_bytes()).ok()?;
+
+    let size = get_xattr(&c_path, name, &mut []);
+    if size <= 0 {
+        return None;
+    }
+    let mut buf = vec![0u8; size as usize];
+    let len = get_xattr(&c_path, name, &mut buf);
+    if len <= 0 {
+        debug!("Could not read {} of {:?}", name, path);
+        return None;
+    }
+    buf.truncate(len as usize);
+    // Some writers include the terminating NUL
+    while buf.last() == Some(&0) {
+        buf.pop();
+    }
+    String::from_utf8(buf).ok().filter(|s| !s.is_empty())
+}
+
+/// Read the extended attribute `name` into `buf`, or only query its size if
+/// `buf` is empty. Returns the size, or -1 on error.
+#[cfg(target_os = "linux")]
+fn get_xattr(path: &std::ffi::CStr, name: &str, buf: &mut [u8]) -> isize {
+    let Ok(name) = std::ffi::CString::new(name) else {
+        return -1;
+    };
+    let data = if buf.is_empty() {
+        std::ptr::null_mut()
+    } else {
+        buf.as_mut_ptr().cast()
+    };
+    // SAFETY: both strings are NUL-terminated and outlive the call, which
+    // writes at most `buf.len()` bytes
+    unsafe { libc::getxattr(path.as_ptr(), name.as_ptr(), data, buf.len()) }
+}
+
+/// Read the extended attribute `name` into `buf`, or only query its size if
+/// `buf` is empty. Returns the size, or -1 on error.
+///
+/// FreeBSD takes the `user.` namespace as a separate argument.
+#[cfg(target_os = "freebsd")]
+fn get_xattr(path: &std::ffi::CStr, name: &str, buf: &mut [u8]) -> isize {
+    let Some(Ok(name)) = name.strip_prefix("user.").map(std::ffi::CString::new) else {
+        return -1;
+    };
+    let data = if buf.is_empty() {
+        std::ptr::null_mut()
+    } else {
+        buf.as_mut_ptr().cast()
+    };
+    // SAFETY: both strings are NUL-terminated and outlive the call, which
+    // writes at most `buf.len()` bytes
+    unsafe {
+        libc::extattr_get_file(
+            path.as_ptr(),
+            libc::EXTATTR_NAMESPACE_USER,
+            name.as_ptr(),
+            data,
+            buf.len(),
+        )
+    }
+}
+
+/// Extended attributes are only read on Linux and FreeBSD
+#[cfg(not(any(target_os = "linux", target_os = "freebsd")))]
+fn get_xattr(_path: &std::ffi::CStr, _name: &str, _buf: &mut [u8]) -> isize {
+    -1
+}
+
+/// Whether Linux binaries can be run.
+///
+/// AppImages are Linux executables; FreeBSD runs them through its Linux
+/// binary compatibility, which registers the `compat.linux` sysctls.
+#[cfg(target_os = "freebsd")]
+pub fn linux_abi_available() -> bool {
+    let mut len: libc::size_t = 0;
+    // SAFETY: the name is NUL-terminated and only the value's size is queried
+    unsafe {
+        libc::sysctlbyname(
+            c"compat.linux.osrelease".as_ptr(),
+            std::ptr::null_mut(),
+            &mut len,
+            std::ptr::null(),
+            0,
+        ) == 0
+    }
+}
+
+/// Whether Linux binaries can be run.
+#[cfg(not(target_os = "freebsd"))]
+pub fn linux_abi_available() -> bool {
+    true
+}
+
+/// Where the SquashFS image of a type 2 AppImage starts, which is the size
+/// of its ELF runtime
+pub fn squashfs_offset(path: &Path) -> Result<Option<u64>, AppImageError> {
+    let mut file = File::open(path)?;
+    let mut header = [0u8; ELF64_HEADER_SIZE];
+    if read_at(&mut file, 0, &mut header)? < header.len() {
+        return Ok(None);
+    }
+    Ok(elf_size(&header))
+}
+
+/// Runtimes larger than this aren't searched for their kind
+const MAX_RUNTIME_SCAN: u64 = 16 * 1024 * 1024;
+
+/// Which runtime a type 2 AppImage was built with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RuntimeKind {
+    /// The static runtime from AppImage/type2-runtime, which needs no
+    /// libfuse on the host
+    Static,
+    /// The older AppImageKit runtime, linked against libfuse 2
+    Fuse2,
+    Unknown,
+}
+
+impl RuntimeKind {
+    /// Human-readable name of the runtime
+    pub fn label(&self) -> &'static str {
+        match self {
+            RuntimeKind::Static => "static (type2-runtime)",
+            RuntimeKind::Fuse2 => "AppImageKit (libfuse 2)",
+            RuntimeKind::Unknown => "unknown",
+        }
+    }
+}
+
+/// The ELF runtime of a type 2 AppImage
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RuntimeInfo {
+    /// Size of the runtime, which is where the SquashFS image starts
+    pub size: u64,
+    pub kind: RuntimeKind,
+    /// The section table (see [`elf_sections`])
+    pub sections: Vec<ElfSection>,
+}
+
+/// Read the ELF runtime of a type 2 AppImage without running it, or `None`
+/// for other files.
+///
+/// The runtimes only print their build commit when run with
+/// `--appimage-version`, so the kind of runtime is told apart by the strings
+/// each one carries instead.
+pub fn runtime_info(path: &Path) -> Result<Option<RuntimeInfo>, AppImageError> {
+    if check_magic_bytes(path)? != Some(AppImageType::Type2) {
+        return Ok(None);
+    }
+    let Some(size) = squashfs_offset(path)? else {
+        return Ok(None);
+    };
+
+    let kind = if size > MAX_RUNTIME_SCAN {
+        RuntimeKind::Unknown
+    } else {
+        let mut runtime = vec![0u8; size as usize];
+        let read_len = read_at(&mut File::open(path)?, 0, &mut runtime)?;
+        runtime_kind(&runtime[..read_len])
+    };
+    Ok(Some(RuntimeInfo {
+        size,
+        kind,
+        sections: elf_sections(path)?,
+    }))
+}
+
+/// Tell the runtimes apart by their project URL and the libfuse they load
+fn runtime_kind(runtime: &[u8]) -> RuntimeKind {
+    let contains = |needle: &[u8]| runtime.windows(needle.len()).any(|w| w == needle);
+    if contains(b"AppImage/type2-runtime") {
+        RuntimeKind::Static
+    } else if contains(b"libfuse.so.2") || contains(b"AppImageKit") {
+        RuntimeKind::Fuse2
+    } else {
+        RuntimeKind::Unknown
+    }
+}
+
+/// Image directories holding icon themes and AppStream metainfo
+const METADATA_DIRS: [&str; 3] = [
+    "usr/share/icons/",
+    "usr/share/metainfo/",
+    "usr/share/appdata/",
+];
+
+/// Whether an image path holds metadata: a desktop file or `.DirIcon` at the
+/// root, or anything in [`METADATA_DIRS`]
+fn is_metadata_path(path: &str) -> bool {
+    (!path.contains('/') && (path.ends_with(".desktop") || path == ".DirIcon"))
+        || METADATA_DIRS.iter().any(|dir| path.starts_with(dir))
+}
+
+/// Resolve a symlink target inside the image, relative to the link's path
+fn resolve_link(link: &str, target: &str) -> Option<String> {
+    let mut parts: Vec<&str> = link.split('/').collect();
+    parts.pop();
+    if target.starts_with('/') {
+        parts.clear();
+    }
+    for part in target.split('/') {
+        match part {
+            "" | "." => {}
+            ".." => {
+                parts.pop()?;
+            }
+            _ => parts.push(part),
+        }
+    }
+    Some(parts.join("/"))
+}
+
+/// Extract the metadata files of a type 2 AppImage into
+/// `extract_dir/squashfs-root` in one pass over its SquashFS image, along
+/// with what their symlinks point to. Returns how many entries were
+/// written.
+fn extract_native(path: &Path, extract_dir: &Path) -> Result<usize, AppImageError> {
+    let offset = squashfs_offset(path)?
+        .ok_or_else(|| AppImageError::NotAppImage("unreadable ELF header".to_string()))?;
+    let mut image = Squashfs::open(path, offset)?;
+    let entries = image.entries()?;
+    let by_path: BTreeMap<&str, usize> = entries
+        .iter()
+        .enumerate()
+        .map(|(index, entry)| (entry.path.as_str(), index))
+        .collect();
+
+    let mut selected: Vec<usize> = (0..entries.len())
+        .filter(|&index| is_metadata_path(&entries[index].path))
+        .collect();
+    // Without a desktop file at the root, look for the one installed into
+    // the standard location
+    if !selected
+        .iter()
+        .any(|&index| entries[index].path.ends_with(".desktop"))
+    {
+        selected.extend((0..entries.len()).filter(|&index| {
+            let path = &entries[index].path;
+            path.starts_with("usr/share/applications/") && path.ends_with(".desktop")
+        }));
+    }
+    // Symlinks (the root desktop file and .DirIcon usually are) pull in
+    // their targets, following chains of links a few levels deep
+    let mut pending = selected.clone();
+    for _ in 0..8 {
+        let targets: Vec<usize> = pending
+            .iter()
+            .filter_map(|&index| match &entries[index].kind {
+                EntryKind::Symlink(target) => resolve_link(&entries[index].path, target),
+                _ => None,
+            })
+            .filter_map(|target| by_path.get(target.as_str()).copied())
+            .filter(|index| !selected.contains(index))
+            .collect();
+        selected.extend(&targets);
+        pending = targets;
+    }
+
+    let root = extract_dir.join("squashfs-root");
+    for &index in &selected {
+        let entry = &entries[index];
+        image.extract(entry, &root.join(&entry.path))?;
+    }
+    Ok(selected.len())
+}
+
+/// Try to extract only the metadata files with the AppImage's own
+/// `--appimage-extract <pattern>`. Returns whether a desktop file was
+/// extracted.
 fn try_selective_extract(appimage_path: &Path, extract_dir: &Path) -> bool {
     // Try to extract .desktop files
     let desktop_result = Command::new(appimage_path)
@@ -282,10 +1018,18 @@ fn try_selective_extract(appimage_path: &Path, extract_dir: &Path) -> bool {
         .current_dir(extract_dir)
         .output();
 
-    // Try to extract icons (various formats and locations)
-    let icon_patterns = ["*.png", "*.svg", "*.xpm", "usr/share/icons/*", ".DirIcon"];
-
-    for pattern in &icon_patterns {
+    // Try to extract icons (various formats and locations) and metainfo
+    let patterns = [
+        "*.png",
+        "*.svg",
+        "*.xpm",
+        ".DirIcon",
+        "usr/share/icons/*",
+        "usr/share/metainfo/*",
+        "usr/share/appdata/*",
+    ];
+
+    for pattern in &patterns {
         let _ = Command::new(appimage_path)
             .arg("--appimage-extract")
             .arg(pattern)
@@ -337,12 +1081,12 @@ fn find_extracted_files(
             if let Some(ext) = path.extension() {
                 let ext_lower = ext.to_string_lossy().to_lowercase();
                 match ext_lower.as_str() {
-                    "desktop" => {
-                        // Prefer .desktop files in the root of squashfs-root
-                        if desktop_file.is_none() || path.parent() == Some(&search_dir) {
-                            // Resolve symlinks so we point to the actual file
-                            desktop_file = Some(fs::canonicalize(&path).unwrap_or(path));
-                        }
+                    // Prefer .desktop files in the root of squashfs-root
+                    "desktop"
+                        if desktop_file.is_none() || path.parent() == Some(&search_dir) =>
+                    {
+                        // Resolve symlinks so we point to the actual file
+                        desktop_file = Some(fs::canonicalize(&path).unwrap_or(path));
                     }
                     "png" | "svg" | "xpm" => {
                         icon_files.push(path);
@@ -356,6 +1100,21 @@ fn find_extracted_files(
     Ok((desktop_file, icon_files))
 }
 
+/// Find the AppStream metainfo file in the extraction directory
+fn find_metainfo(extract_dir: &Path) -> Option<PathBuf> {
+    let mut files: Vec<PathBuf> = walk_dir(extract_dir)
+        .ok()?
+        .into_iter()
+        .filter(|p| {
+            p.file_name()
+                .is_some_and(|n| appstream::is_metainfo_file(&n.to_string_lossy()))
+        })
+        .collect();
+    // Prefer the current metainfo/ location over the legacy appdata/ one
+    files.sort_by_key(|p| !p.to_string_lossy().contains("/metainfo/"));
+    files.into_iter().next()
+}
+
 /// Recursively walk a directory and collect all file paths
 fn walk_dir(dir: &Path) -> Result<Vec<PathBuf>, std::io::Error> {
     let mut files = Vec::new();
@@ -457,4 +1216,330 @@ mod tests {
         let path = Path::new("/some/path/icon.png");
         assert_eq!(extract_icon_size(path), None);
     }
+
+    #[test]
+    fn test_is_appimage_complete() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("test.AppImage");
+
+        // 64-bit little-endian runtime with one section header at 64..128
+        let mut runtime = vec![0u8; 128];
+        runtime[0..4].copy_from_slice(&ELF_MAGIC);
+        runtime[4] = 2;
+        runtime[5] = 1;
+        runtime[8..11].copy_from_slice(&APPIMAGE_TYPE2_MAGIC);
+        runtime[0x28..0x30].copy_from_slice(&64u64.to_le_bytes());
+        runtime[0x3A..0x3C].copy_from_slice(&64u16.to_le_bytes());
+        runtime[0x3C..0x3E].copy_from_slice(&1u16.to_le_bytes());
+        assert_eq!(elf_size(&runtime), Some(128));
+
+        let mut superblock = vec![0u8; 200];
+        superblock[0..4].copy_from_slice(&SQUASHFS_MAGIC);
+        superblock[12..16].copy_from_slice(&131072u32.to_le_bytes());
+        superblock[22..24].copy_from_slice(&17u16.to_le_bytes());
+        superblock[28..30].copy_from_slice(&4u16.to_le_bytes());
+        superblock[40..48].copy_from_slice(&200u64.to_le_bytes());
+        let full = [runtime, superblock].concat();
+
+        fs::write(&path, &full[..300]).unwrap();
+        assert!(!is_appimage_complete(&path).unwrap());
+        fs::write(&path, &full).unwrap();
+        assert!(is_appimage_complete(&path).unwrap());
+
+        // Not a squashfs superblock where the runtime ends
+        let mut corrupt = full.clone();
+        corrupt[128] = 0;
+        fs::write(&path, &corrupt).unwrap();
+        assert!(!is_appimage_complete(&path).unwrap());
+        fs::write(&path, &full[..100]).unwrap();
+        assert!(!is_appimage_complete(&path).unwrap());
+    }
+
+    #[test]
+    fn test_elf_section() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("test.AppImage");
+
+        // Header, section names at 64, `.sha256_sig` at 96 and three
+        // section headers (null, `.sha256_sig`, `.shstrtab`) at 112
+        let mut elf = vec![0u8; 304];
+        elf[0..4].copy_from_slice(&ELF_MAGIC);
+        elf[4] = 2;
+        elf[5] = 1;
+        elf[0x28..0x30].copy_from_slice(&112u64.to_le_bytes());
+        elf[0x3A..0x3C].copy_from_slice(&64u16.to_le_bytes());
+        elf[0x3C..0x3E].copy_from_slice(&3u16.to_le_bytes());
+        elf[0x3E..0x40].copy_from_slice(&2u16.to_le_bytes());
+        let names = b"\0.sha256_sig\0.shstrtab\0";
+        elf[64..64 + names.len()].copy_from_slice(names);
+        for (index, name, offset, size) in [(1, 1u32, 96u64, 16u64), (2, 13, 64, 23)] {
+            let at = 112 + index * 64;
+            elf[at..at + 4].copy_from_slice(&name.to_le_bytes());
+            elf[at + 0x18..at + 0x20].copy_from_slice(&offset.to_le_bytes());
+            elf[at + 0x20..at + 0x28].copy_from_slice(&size.to_le_bytes());
+        }
+        fs::write(&path, &elf).unwrap();
+
+        assert_eq!(elf_section(&path, ".sha256_sig").unwrap(), Some((96, 16)));
+        assert_eq!(elf_section(&path, ".sig_key").unwrap(), None);
+        let names: Vec<_> = elf_sections(&path)
+            .unwrap()
+            .into_iter()
+            .map(|section| section.name)
+            .collect();
+        assert_eq!(names, ["", ".sha256_sig", ".shstrtab"]);
+        fs::write(&path, b"#!/bin/sh").unwrap();
+        assert_eq!(elf_section(&path, ".sha256_sig").unwrap(), None);
+        assert!(elf_sections(&path).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_embedded_sections() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("test.AppImage");
+
+        // Header, section names at 64, `.upd_info` at 128, `.digest_md5` at
+        // 192 and four section headers (null, `.upd_info`, `.digest_md5`,
+        // `.shstrtab`) at 256
+        let mut elf = vec![0u8; 512];
+        elf[0..4].copy_from_slice(&ELF_MAGIC);
+        elf[4] = 2;
+        elf[5] = 1;
+        elf[0x28..0x30].copy_from_slice(&256u64.to_le_bytes());
+        elf[0x3A..0x3C].copy_from_slice(&64u16.to_le_bytes());
+        elf[0x3C..0x3E].copy_from_slice(&4u16.to_le_bytes());
+        elf[0x3E..0x40].copy_from_slice(&3u16.to_le_bytes());
+        let names = b"\0.upd_info\0.digest_md5\0.shstrtab\0";
+        elf[64..64 + names.len()].copy_from_slice(names);
+        let update_info = b"gh-releases-zsync|krita|krita|latest|krita-*.zsync";
+        elf[128..128 + update_info.len()].copy_from_slice(update_info);
+        for (index, name, offset, size) in [
+            (1, 1u32, 128u64, 64u64),
+            (2, 11, 192, 16),
+            (3, 23, 64, names.len() as u64),
+        ] {
+            let at = 256 + index * 64;
+            elf[at..at + 4].copy_from_slice(&name.to_le_bytes());
+            elf[at + 0x18..at + 0x20].copy_from_slice(&offset.to_le_bytes());
+            elf[at + 0x20..at + 0x28].copy_from_slice(&size.to_le_bytes());
+        }
+        fs::write(&path, &elf).unwrap();
+
+        assert_eq!(
+            update_information(&path).unwrap(),
+            Some(UpdateInformation::GithubReleases {
+                owner: "krita".to_string(),
+                repo: "krita".to_string(),
+                tag: "latest".to_string(),
+                file_name: "krita-*.zsync".to_string(),
+            })
+        );
+        // Reserved but never filled in
+        assert_eq!(embedded_md5(&path).unwrap(), None);
+        assert_eq!(embedded_signature(&path).unwrap(), None);
+
+        elf[192..208].copy_from_slice(&[0xab; 16]);
+        fs::write(&path, &elf).unwrap();
+        assert_eq!(embedded_md5(&path).unwrap(), Some("ab".repeat(16)));
+    }
+
+    #[test]
+    fn test_parse_update_information() {
+        assert_eq!(
+            UpdateInformation::parse("zsync|https://example.com/App.AppImage.zsync"),
+            UpdateInformation::Zsync {
+                url: "https://example.com/App.AppImage.zsync".to_string()
+            }
+        );
+        assert_eq!(
+            UpdateInformation::parse("ocs-v1-appimagehub-zsync|1234|App-*.AppImage"),
+            UpdateInformation::Pling {
+                product_id: "1234".to_string(),
+                file_name: "App-*.AppImage".to_string()
+            }
+        );
+        assert_eq!(
+            UpdateInformation::parse("zsync"),
+            UpdateInformation::Other("zsync".to_string())
+        );
+    }
+
+    #[test]
+    fn test_runtime_info() {
+        let (_dir, path) = crate::testing::AppImageBuilder::new("Test App")
+            .write_temp()
+            .unwrap();
+        let runtime = runtime_info(&path).unwrap().unwrap();
+        assert_eq!(Some(runtime.size), squashfs_offset(&path).unwrap());
+        assert_eq!(runtime.kind, RuntimeKind::Unknown);
+        assert_eq!(runtime.sections.len(), 2);
+
+        assert_eq!(
+            runtime_kind(b"\0https://github.com/AppImage/type2-runtime\0"),
+            RuntimeKind::Static
+        );
+        assert_eq!(runtime_kind(b"\0libfuse.so.2\0"), RuntimeKind::Fuse2);
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let other = temp_dir.path().join("script.sh");
+        fs::write(&other, b"#!/bin/sh\necho hello\n").unwrap();
+        assert_eq!(runtime_info(&other).unwrap(), None);
+    }
+
+    #[test]
+    fn test_scan_directory() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let dir = temp_dir.path();
+        let appimage = crate::testing::AppImageBuilder::new("Test App");
+        appimage.write(&dir.join("B.AppImage")).unwrap();
+        appimage.write(&dir.join("A.AppImage.part")).unwrap();
+        std::os::unix::fs::symlink(dir.join("B.AppImage"), dir.join("A.AppImage")).unwrap();
+        fs::write(dir.join("notes.txt"), vec![b'x'; 4096]).unwrap();
+        // The magic bytes alone don't make an AppImage
+        let mut tiny = ELF_MAGIC.to_vec();
+        tiny.extend([2, 1, 1, 0]);
+        tiny.extend(APPIMAGE_TYPE2_MAGIC);
+        fs::write(dir.join("Tiny.AppImage"), &tiny).unwrap();
+        fs::create_dir(dir.join("Dir.AppImage")).unwrap();
+
+        assert!(is_appimage(&dir.join("Tiny.AppImage")));
+        assert_eq!(
+            scan_directory(dir).unwrap(),
+            vec![dir.join("A.AppImage"), dir.join("B.AppImage")]
+        );
+    }
+
+    #[test]
+    fn test_symlink_target() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let target = temp_dir.path().join("App-1.2.AppImage");
+        let link = temp_dir.path().join("App-latest.AppImage");
+        fs::write(&target, b"").unwrap();
+        std::os::unix::fs::symlink(&target, &link).unwrap();
+
+        assert_eq!(
+            symlink_target(&link),
+            Some(fs::canonicalize(&target).unwrap())
+        );
+        assert_eq!(symlink_target(&target), None);
+    }
+
+    #[test]
+    fn test_is_metadata_path() {
+        assert!(is_metadata_path("app.desktop"));
+        assert!(is_metadata_path(".DirIcon"));
+        assert!(is_metadata_path(
+            "usr/share/icons/hicolor/scalable/apps/app.svg"
+        ));
+        assert!(is_metadata_path("usr/share/metainfo/app.appdata.xml"));
+        assert!(!is_metadata_path("usr/share/applications/app.desktop"));
+        assert!(!is_metadata_path("app.png"));
+        assert!(!is_metadata_path("usr/bin/app"));
+    }
+
+    #[test]
+    fn test_resolve_link() {
+        assert_eq!(
+            resolve_link("app.desktop", "usr/share/applications/app.desktop").as_deref(),
+            Some("usr/share/applications/app.desktop")
+        );
+        assert_eq!(
+            resolve_link("usr/share/icons/a/app.png", "../b/./app.png").as_deref(),
+            Some("usr/share/icons/b/app.png")
+        );
+        assert_eq!(
+            resolve_link("usr/app.png", "/opt/app.png").as_deref(),
+            Some("opt/app.png")
+        );
+        assert_eq!(resolve_link(".DirIcon", "../app.png"), None);
+    }
+
+    #[test]
+    fn test_file_id() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("App.AppImage");
+        let hardlink = temp_dir.path().join("App-hardlink.AppImage");
+        let other = temp_dir.path().join("Other.AppImage");
+        fs::write(&path, b"").unwrap();
+        fs::write(&other, b"").unwrap();
+        fs::hard_link(&path, &hardlink).unwrap();
+
+        assert!(file_id(&path).is_some());
+        assert_eq!(file_id(&hardlink), file_id(&path));
+        assert_ne!(file_id(&other), file_id(&path));
+        assert_eq!(file_id(&temp_dir.path().join("missing")), None);
+    }
+
+    #[test]
+    fn test_content_hash() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("test.AppImage");
+
+        fs::write(&path, b"version one").unwrap();
+        let first = content_hash(&path).unwrap();
+        assert_eq!(first, format!("{:x}", md5::compute(b"version one")));
+
+        fs::write(&path, b"version two").unwrap();
+        assert_ne!(content_hash(&path).unwrap(), first);
+    }
+
+    #[test]
+    fn test_launch_command() {
+        let path = Path::new("/apps/test.AppImage");
+        let extract_and_run = |command: &Command| {
+            command.get_envs().any(|(key, value)| {
+                key == "APPIMAGE_EXTRACT_AND_RUN" && value == Some(OsStr::new("1"))
+            })
+        };
+
+        let command = launch_command(path, AppImageType::Type2, true);
+        assert_eq!(command.get_program(), path.as_os_str());
+        assert!(!extract_and_run(&command));
+        assert!(extract_and_run(&launch_command(
+            path,
+            AppImageType::Type2,
+            false
+        )));
+        assert!(!extract_and_run(&launch_command(
+            path,
+            AppImageType::Type1,
+            false
+        )));
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_download_origin() {
+        use std::ffi::CString;
+        use std::os::unix::ffi::OsStrExt;
+
+        let temp = tempfile::NamedTempFile::new().unwrap();
+        assert_eq!(download_origin(temp.path()), DownloadOrigin::default());
+
+        let path = CString::new(temp.path().as_os_str().as_bytes()).unwrap();
+        let name = CString::new("user.xdg.origin.url").unwrap();
+        let url = b"https://example.com/App.AppImage";
+        // SAFETY: valid NUL-terminated strings and a buffer of the given length
+        let set = unsafe {
+            libc::setxattr(
+                path.as_ptr(),
+                name.as_ptr(),
+                url.as_ptr().cast(),
+                url.len(),
+                0,
+            )
+        };
+        if set != 0 {
+            // The temp filesystem has no user xattrs
+            return;
+        }
+
+        assert_eq!(
+            download_origin(temp.path()),
+            DownloadOrigin {
+                url: Some("https://example.com/App.AppImage".to_string()),
+                referrer: None,
+            }
+        );
+    }
 }
@@ -0,0 +1,332 @@
+//! Project links, summaries and translations from AppStream metainfo.
+//!
+//! AppImages often bundle `usr/share/metainfo/<id>.metainfo.xml` (or the older
+//! `usr/share/appdata/<id>.appdata.xml`), which lists the project's homepage,
+//! issue tracker and other links as `<url type="...">` elements, and a one-line
+//! `<summary>`. Only those, the translated `<name>`s and `<summary>`s and the
+//! app id are read, so a full XML parser isn't needed.
+
+use std::collections::BTreeMap;
+
+/// Link type of the project homepage
+pub const HOMEPAGE: &str = "homepage";
+
+/// Link type of the issue tracker
+pub const BUGTRACKER: &str = "bugtracker";
+
+/// Read the `<url type="...">` links of a metainfo file, keyed by type.
+///
+/// Links inside `<releases>` describe single releases and are skipped; the
+/// first link of each type wins.
+pub fn parse_urls(xml: &str) -> BTreeMap<String, String> {
+    let xml = strip_element(xml, "releases");
+    let mut urls = BTreeMap::new();
+
+    let mut rest = xml.as_str();
+    while let Some(start) = rest.find("<url") {
+        rest = &rest[start + "<url".len()..];
+        let Some(tag_end) = rest.find('>') else {
+            break;
+        };
+        let attrs = &rest[..tag_end];
+        // Skip `<urls>` and the like, and empty `<url/>` elements
+        if !attrs.starts_with(char::is_whitespace) || attrs.ends_with('/') {
+            continue;
+        }
+
+        let body = &rest[tag_end + 1..];
+        let Some(close) = body.find("</url>") else {
+            break;
+        };
+        let url = unescape(body[..close].trim());
+        if let Some(kind) = attribute(attrs, "type")
+            && !url.is_empty()
+        {
+            urls.entry(kind).or_insert(url);
+        }
+        rest = &body[close..];
+    }
+
+    urls
+}
+
+/// The app id a metainfo file declares: the desktop file it launches
+/// (`<launchable type="desktop-id">`), or else its component `<id>`, without
+/// a `.desktop` suffix. GTK and Qt apps use it as their Wayland app id.
+pub fn parse_app_id(xml: &str) -> Option<String> {
+    // Releases and provided ids can contain `<id>`s of their own
+    let xml = strip_element(&strip_element(xml, "releases"), "provides");
+    let id = element_text(&xml, "launchable", |attrs| {
+        attribute(attrs, "type").as_deref() == Some("desktop-id")
+    })
+    .or_else(|| element_text(&xml, "id", |_| true))?;
+    let id = id.strip_suffix(".desktop").unwrap_or(&id);
+    (!id.is_empty()).then(|| id.to_string())
+}
+
+/// The untranslated one-line `<summary>` of a metainfo file
+pub fn parse_summary(xml: &str) -> Option<String> {
+    // Releases can carry descriptions of their own
+    let xml = strip_element(xml, "releases");
+    element_text(&xml, "summary", |attrs| {
+        attribute(attrs, "xml:lang").is_none()
+    })
+    .filter(|summary| !summary.is_empty())
+}
+
+/// Translated names and summaries of a metainfo file, keyed by locale
+/// (the `xml:lang` attribute, e.g. `de` or `pt_BR`)
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Translations {
+    pub names: BTreeMap<String, String>,
+    pub summaries: BTreeMap<String, String>,
+}
+
+/// Read the translated `<name>`s and `<summary>`s of a metainfo file
+pub fn parse_translations(xml: &str) -> Translations {
+    // Releases have descriptions, developers names of their own
+    let xml = strip_element(xml, "releases");
+    let xml = strip_element(&xml, "developer_name");
+    let xml = strip_element(&xml, "developer");
+    Translations {
+        names: localized_texts(&xml, "name"),
+        summaries: localized_texts(&xml, "summary"),
+    }
+}
+
+/// Human-readable label for a link type
+pub fn label(kind: &str) -> &str {
+    match kind {
+        HOMEPAGE => "Website",
+        BUGTRACKER => "Report issue",
+        "help" => "Help",
+        "faq" => "FAQ",
+        "donation" => "Donate",
+        "translate" => "Translate",
+        "contact" => "Contact",
+        "vcs-browser" => "Source code",
+        "contribute" => "Contribute",
+        other => other,
+    }
+}
+
+/// Whether a file name looks like AppStream metainfo
+pub fn is_metainfo_file(file_name: &str) -> bool {
+    file_name.ends_with(".metainfo.xml") || file_name.ends_with(".appdata.xml")
+}
+
+/// Remove every `<name ...>...</name>` element from a document
+fn strip_element(xml: &str, name: &str) -> String {
+    let open = format!("<{}", name);
+    let close = format!("</{}>", name);
+    let mut out = String::with_capacity(xml.len());
+
+    let mut rest = xml;
+    while let Some(start) = rest.find(&open) {
+        out.push_str(&rest[..start]);
+        match rest[start..].find(&close) {
+            Some(end) => rest = &rest[start + end + close.len()..],
+            None => {
+                rest = "";
+                break;
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Text of the first `<name ...>` element whose attributes satisfy `wanted`
+fn element_text(xml: &str, name: &str, wanted: impl Fn(&str) -> bool) -> Option<String> {
+    let open = format!("<{}", name);
+    let close = format!("</{}>", name);
+
+    let mut rest = xml;
+    while let Some(start) = rest.find(&open) {
+        rest = &rest[start + open.len()..];
+        let tag_end = rest.find('>')?;
+        let attrs = &rest[..tag_end];
+        // Skip longer element names sharing the prefix, and empty elements
+        if !(attrs.is_empty() || attrs.starts_with(char::is_whitespace)) || attrs.ends_with('/') {
+            continue;
+        }
+
+        let body = &rest[tag_end + 1..];
+        let end = body.find(&close)?;
+        if wanted(attrs) {
+            return Some(unescape(body[..end].trim()));
+        }
+        rest = &body[end..];
+    }
+    None
+}
+
+/// Texts of the `<name xml:lang="...">` elements, keyed by language. The
+/// first translation of each language wins.
+fn localized_texts(xml: &str, name: &str) -> BTreeMap<String, String> {
+    let open = format!("<{}", name);
+    let close = format!("</{}>", name);
+    let mut texts = BTreeMap::new();
+
+    let mut rest = xml;
+    while let Some(start) = rest.find(&open) {
+        rest = &rest[start + open.len()..];
+        let Some(tag_end) = rest.find('>') else {
+            break;
+        };
+        let attrs = &rest[..tag_end];
+        if !(attrs.is_empty() || attrs.starts_with(char::is_whitespace)) || attrs.ends_with('/') {
+            continue;
+        }
+
+        let body = &rest[tag_end + 1..];
+        let Some(end) = body.find(&close) else {
+            break;
+        };
+        let text = unescape(body[..end].trim());
+        // `x-test` is AppStream's pseudo-language for checking translations
+        if let Some(lang) = attribute(attrs, "xml:lang")
+            && !lang.is_empty()
+            && lang != "x-test"
+            && !text.is_empty()
+        {
+            texts.entry(lang).or_insert(text);
+        }
+        rest = &body[end..];
+    }
+    texts
+}
+
+/// Value of an attribute in the attribute part of a start tag
+fn attribute(attrs: &str, name: &str) -> Option<String> {
+    let mut rest = attrs;
+    while let Some(pos) = rest.find(name) {
+        let before = rest[..pos].chars().next_back();
+        let after = rest[pos + name.len()..].trim_start();
+        rest = &rest[pos + name.len()..];
+
+        if !before.is_some_and(char::is_whitespace) {
+            continue;
+        }
+        let Some(value) = after.strip_prefix('=').map(str::trim_start) else {
+            continue;
+        };
+        let quote = value.chars().next()?;
+        if quote != '"' && quote != '\'' {
+            return None;
+        }
+        let value = &value[1..];
+        return value.find(quote).map(|end| unescape(&value[..end]));
+    }
+    None
+}
+
+/// Replace the predefined XML entities
+fn unescape(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_urls() {
+        let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<component type="desktop-application">
+  <id>org.kde.krita</id>
+  <url type="homepage">https://krita.org/</url>
+  <url type='bugtracker'>
+    https://bugs.kde.org/enter_bug.cgi?product=krita&amp;component=general
+  </url>
+  <url type="homepage">https://example.com/ignored</url>
+  <url>https://example.com/untyped</url>
+  <urls type="homepage">https://example.com/not-a-url-element</urls>
+  <releases>
+    <release version="5.2.0">
+      <url type="details">https://krita.org/release-5.2</url>
+    </release>
+  </releases>
+  <url type="vcs-browser">https://invent.kde.org/graphics/krita</url>
+</component>
+"#;
+        let urls = parse_urls(xml);
+        assert_eq!(
+            urls,
+            BTreeMap::from([
+                (HOMEPAGE.to_string(), "https://krita.org/".to_string()),
+                (
+                    BUGTRACKER.to_string(),
+                    "https://bugs.kde.org/enter_bug.cgi?product=krita&component=general"
+                        .to_string()
+                ),
+                (
+                    "vcs-browser".to_string(),
+                    "https://invent.kde.org/graphics/krita".to_string()
+                ),
+            ])
+        );
+        assert!(parse_urls("<component/>").is_empty());
+        assert_eq!(label("vcs-browser"), "Source code");
+    }
+
+    #[test]
+    fn test_parse_app_id() {
+        let xml = r#"<component type="desktop-application">
+  <id>org.kde.krita</id>
+  <provides><id>krita.desktop</id></provides>
+  <launchable type="desktop-id">org.kde.krita.desktop</launchable>
+</component>"#;
+        assert_eq!(parse_app_id(xml).as_deref(), Some("org.kde.krita"));
+
+        // Legacy ids name the desktop file
+        let xml = "<component><identifier>x</identifier><id>gimp.desktop</id></component>";
+        assert_eq!(parse_app_id(xml).as_deref(), Some("gimp"));
+        assert_eq!(parse_app_id("<component><id></id></component>"), None);
+    }
+
+    #[test]
+    fn test_parse_summary() {
+        let xml = r#"<component type="desktop-application">
+  <summary xml:lang="de">Digitales Malen</summary>
+  <summary>Digital Painting &amp; Sketching</summary>
+</component>"#;
+        assert_eq!(
+            parse_summary(xml).as_deref(),
+            Some("Digital Painting & Sketching")
+        );
+        assert_eq!(parse_summary("<component><summary/></component>"), None);
+    }
+
+    #[test]
+    fn test_parse_translations() {
+        let xml = r#"<component type="desktop-application">
+  <name>Krita</name>
+  <name xml:lang="ru">Крита</name>
+  <name xml:lang="x-test">xxKritaxx</name>
+  <summary>Digital Painting</summary>
+  <summary xml:lang="de">Digitales Malen &amp; Zeichnen</summary>
+  <summary xml:lang="pt_BR">Pintura digital</summary>
+  <summary xml:lang="fr"></summary>
+  <developer id="org.kde"><name xml:lang="de">KDE-Gemeinschaft</name></developer>
+  <developer_name xml:lang="fr">La communauté KDE</developer_name>
+</component>"#;
+        let translations = parse_translations(xml);
+        assert_eq!(
+            translations.names,
+            BTreeMap::from([("ru".to_string(), "Крита".to_string())])
+        );
+        assert_eq!(
+            translations.summaries,
+            BTreeMap::from([
+                ("de".to_string(), "Digitales Malen & Zeichnen".to_string()),
+                ("pt_BR".to_string(), "Pintura digital".to_string()),
+            ])
+        );
+        assert_eq!(parse_translations("<component/>"), Translations::default());
+    }
+}
@@ -0,0 +1,217 @@
+//! AppImages delivered inside archives.
+//!
+//! Many projects publish their AppImage zipped or in a tarball. With
+//! `watch.extract_archives` enabled, a `.zip`, `.tar.gz` or `.tgz` file
+//! landing in a watched directory that contains exactly one AppImage has it
+//! extracted (with `unzip` or `tar`) and integrated. The archive is left in
+//! place.
+
+use crate::appimage;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum ArchiveError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("{0} failed: {1}")]
+    Tool(&'static str, String),
+    #[error("No AppImage in the archive")]
+    NoAppImage,
+    #[error("{0} AppImages in the archive, expected one")]
+    SeveralAppImages(usize),
+    #[error("{0:?} already exists")]
+    Exists(PathBuf),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Zip,
+    TarGz,
+}
+
+impl Format {
+    fn of(path: &Path) -> Option<Self> {
+        let name = path.file_name()?.to_string_lossy().to_lowercase();
+        if name.ends_with(".zip") {
+            Some(Self::Zip)
+        } else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+            Some(Self::TarGz)
+        } else {
+            None
+        }
+    }
+
+    fn tool(self) -> &'static str {
+        match self {
+            Self::Zip => "unzip",
+            Self::TarGz => "tar",
+        }
+    }
+}
+
+/// Whether a file is an archive that may hold an AppImage, by its name
+pub fn is_archive(path: &Path) -> bool {
+    Format::of(path).is_some()
+}
+
+/// Extract the one AppImage in `archive` into `dest_dir`, returning its path.
+///
+/// It is written as `<name>.part` first, which the watcher ignores like a
+/// browser download, and renamed once complete. An existing file is never
+/// overwritten.
+pub fn extract_appimage(archive: &Path, dest_dir: &Path) -> Result<PathBuf, ArchiveError> {
+    let format = Format::of(archive).ok_or(ArchiveError::NoAppImage)?;
+    let names = list(archive, format)?;
+    let member = appimage_member(&names)?;
+    let file_name = Path::new(member)
+        .file_name()
+        .ok_or(ArchiveError::NoAppImage)?;
+    let dest = dest_dir.join(file_name);
+    if dest.exists() {
+        return Err(ArchiveError::Exists(dest));
+    }
+
+    fs::create_dir_all(dest_dir)?;
+    let part = tempfile::Builder::new()
+        .prefix(file_name)
+        .suffix(".part")
+        .tempfile_in(dest_dir)?;
+    let mut command = match format {
+        Format::Zip => {
+            let mut command = Command::new("unzip");
+            command.arg("-p").arg(archive).arg(zip_pattern(member));
+            command
+        }
+        Format::TarGz => {
+            let mut command = Command::new("tar");
+            command.arg("-xzOf").arg(archive).arg("--").arg(member);
+            command
+        }
+    };
+    let output = command
+        .stdout(Stdio::from(part.as_file().try_clone()?))
+        .output()?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(ArchiveError::Tool(format.tool(), stderr.trim().to_string()));
+    }
+    if !appimage::is_appimage(part.path()) {
+        return Err(ArchiveError::NoAppImage);
+    }
+
+    fs::set_permissions(part.path(), fs::Permissions::from_mode(0o755))?;
+    part.persist_noclobber(&dest).map_err(|e| e.error)?;
+    Ok(dest)
+}
+
+/// Names of the files in an archive (directories left out)
+fn list(archive: &Path, format: Format) -> Result<Vec<String>, ArchiveError> {
+    let output = match format {
+        Format::Zip => Command::new("unzip").arg("-Z1").arg(archive).output()?,
+        Format::TarGz => Command::new("tar").arg("-tzf").arg(archive).output()?,
+    };
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(ArchiveError::Tool(format.tool(), stderr.trim().to_string()));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter(|name| !name.is_empty() && !name.ends_with('/'))
+        .map(str::to_string)
+        .collect())
+}
+
+/// The one member named like an AppImage
+fn appimage_member(names: &[String]) -> Result<&str, ArchiveError> {
+    let appimages: Vec<&str> = names
+        .iter()
+        .map(String::as_str)
+        .filter(|name| name.to_lowercase().ends_with(".appimage"))
+        .collect();
+    match appimages.as_slice() {
+        [] => Err(ArchiveError::NoAppImage),
+        [member] => Ok(member),
+        several => Err(ArchiveError::SeveralAppImages(several.len())),
+    }
+}
+
+/// An `unzip` pattern matching exactly `name`
+fn zip_pattern(name: &str) -> String {
+    let mut pattern = String::with_capacity(name.len());
+    for c in name.chars() {
+        if matches!(c, '*' | '?' | '[' | ']' | '\\') {
+            pattern.push('\\');
+        }
+        pattern.push(c);
+    }
+    pattern
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::AppImageBuilder;
+
+    #[test]
+    fn test_appimage_member() {
+        let names = |names: &[&str]| names.iter().map(|n| n.to_string()).collect::<Vec<_>>();
+
+        assert_eq!(
+            appimage_member(&names(&["README.md", "App-1.0/App-1.0-x86_64.AppImage"])).unwrap(),
+            "App-1.0/App-1.0-x86_64.AppImage"
+        );
+        assert!(matches!(
+            appimage_member(&names(&["README.md"])),
+            Err(ArchiveError::NoAppImage)
+        ));
+        assert!(matches!(
+            appimage_member(&names(&["a.AppImage", "b.appimage"])),
+            Err(ArchiveError::SeveralAppImages(2))
+        ));
+        assert_eq!(zip_pattern("App[1]*.AppImage"), "App\\[1\\]\\*.AppImage");
+        assert!(is_archive(Path::new("/dl/App.TAR.GZ")));
+        assert!(!is_archive(Path::new("/dl/App.AppImage")));
+    }
+
+    #[test]
+    fn test_extract_appimage() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let staging = temp_dir.path().join("App-1.0");
+        fs::create_dir(&staging).unwrap();
+        AppImageBuilder::new("App")
+            .write(&staging.join("App-1.0.AppImage"))
+            .unwrap();
+        fs::write(staging.join("README.md"), "read me").unwrap();
+
+        let archive = temp_dir.path().join("App-1.0.tar.gz");
+        let status = Command::new("tar")
+            .arg("-czf")
+            .arg(&archive)
+            .arg("-C")
+            .arg(temp_dir.path())
+            .arg("App-1.0")
+            .status()
+            .unwrap();
+        assert!(status.success());
+
+        let dest_dir = temp_dir.path().join("Applications");
+        let path = extract_appimage(&archive, &dest_dir).unwrap();
+        assert_eq!(path, dest_dir.join("App-1.0.AppImage"));
+        assert!(appimage::is_appimage(&path));
+        assert_eq!(
+            fs::metadata(&path).unwrap().permissions().mode() & 0o777,
+            0o755
+        );
+        assert_eq!(fs::read_dir(&dest_dir).unwrap().count(), 1);
+
+        assert!(matches!(
+            extract_appimage(&archive, &dest_dir),
+            Err(ArchiveError::Exists(_))
+        ));
+    }
+}
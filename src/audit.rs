@@ -0,0 +1,273 @@
+//! Security-oriented overview of integrated AppImages.
+//!
+//! `appimage-auto audit` reports for each integrated app whether its
+//! signature checks out, where it was downloaded from, whether the file
+//! changed since it was integrated, and desktop entry keys or bundled files
+//! that deserve a second look: requests for the discrete GPU, URL scheme
+//! handlers, autostart entries shipped inside the image and those the app
+//! added to `~/.config/autostart`.
+
+use crate::appimage;
+use crate::autostart;
+use crate::compare;
+use crate::desktop::DesktopEntry;
+use crate::signature;
+use crate::squashfs::Squashfs;
+use crate::state::IntegratedAppImage;
+use serde::Serialize;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// Desktop entry keys that change how or when an app is started
+const NOTABLE_KEYS: [&str; 8] = [
+    "X-KDE-RunOnDiscreteGpu",
+    "PrefersNonDefaultGPU",
+    "X-GNOME-Autostart-enabled",
+    "X-GNOME-Autostart-Phase",
+    "X-KDE-autostart-after",
+    "AutostartCondition",
+    "DBusActivatable",
+    "X-KDE-Wayland-Interfaces",
+];
+
+/// Image directories whose desktop entries are started on login when copied
+/// to the host
+const AUTOSTART_DIRS: [&str; 3] = [
+    "etc/xdg/autostart/",
+    "usr/etc/xdg/autostart/",
+    "usr/share/autostart/",
+];
+
+/// Whether an AppImage is still the file that was integrated
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FileStatus {
+    Unchanged,
+    /// Its contents differ from when it was integrated
+    Changed,
+    Missing,
+    /// Integrated before contents were recorded, or unreadable
+    Unknown,
+}
+
+impl fmt::Display for FileStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let label = match self {
+            FileStatus::Unchanged => "unchanged since integration",
+            FileStatus::Changed => "changed since integration",
+            FileStatus::Missing => "missing",
+            FileStatus::Unknown => "unknown",
+        };
+        f.write_str(label)
+    }
+}
+
+/// What the audit found for one integrated app
+#[derive(Debug, Clone, Serialize)]
+pub struct AppAudit {
+    pub identifier: String,
+    pub name: String,
+    pub path: PathBuf,
+    /// Signature status, or why it could not be checked
+    pub signature: String,
+    /// Whether the signature was made by a trusted key
+    pub trusted: bool,
+    /// URL the AppImage was downloaded from, if the browser recorded it
+    pub origin_url: Option<String>,
+    pub file: FileStatus,
+    /// Notable keys of the installed desktop entry, as `Key=value`
+    pub notable_keys: Vec<String>,
+    /// URL schemes the app registers to open (`x-scheme-handler/...`)
+    pub url_handlers: Vec<String>,
+    /// Autostart entries inside the image, or `None` if the image could not
+    /// be listed
+    pub autostart_entries: Option<Vec<String>>,
+    /// Entries in `~/.config/autostart` that start the app
+    pub added_autostart: Vec<AddedAutostart>,
+}
+
+/// An entry in `~/.config/autostart` that starts an integrated app
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct AddedAutostart {
+    pub path: PathBuf,
+    /// Disabled by `integration.block_autostart`
+    pub blocked: bool,
+}
+
+impl AppAudit {
+    /// Findings worth a look, as short sentences; empty if there are none
+    pub fn concerns(&self) -> Vec<String> {
+        let mut concerns = Vec::new();
+        if !self.trusted {
+            concerns.push(format!("signature: {}", self.signature));
+        }
+        if matches!(self.file, FileStatus::Changed | FileStatus::Missing) {
+            concerns.push(format!("file {}", self.file));
+        }
+        for key in &self.notable_keys {
+            concerns.push(format!("desktop entry sets {}", key));
+        }
+        for scheme in &self.url_handlers {
+            concerns.push(format!("opens {}: links", scheme));
+        }
+        for entry in self.autostart_entries.iter().flatten() {
+            concerns.push(format!("ships autostart entry {}", entry));
+        }
+        for entry in self.added_autostart.iter().filter(|e| !e.blocked) {
+            concerns.push(format!("starts on login through {}", entry.path.display()));
+        }
+        concerns
+    }
+}
+
+/// Audit an integrated app. Checks the signature with `gpg` and reads the
+/// whole AppImage to compare its contents, so this takes a while for large
+/// files.
+pub fn audit(app: &IntegratedAppImage) -> AppAudit {
+    let path = &app.appimage_path;
+    let exists = path.exists();
+
+    let (signature, trusted) = if !exists {
+        ("not checked (file missing)".to_string(), false)
+    } else {
+        match signature::verify(path) {
+            Ok(verification) => (
+                compare::signature_label(&verification),
+                matches!(verification, signature::Verification::Trusted { .. }),
+            ),
+            Err(e) => (format!("not checked ({})", e), false),
+        }
+    };
+
+    let file = match (&app.content_hash, exists) {
+        (_, false) => FileStatus::Missing,
+        (None, true) => FileStatus::Unknown,
+        (Some(recorded), true) => match appimage::content_hash(path) {
+            Ok(hash) if hash == *recorded => FileStatus::Unchanged,
+            Ok(_) => FileStatus::Changed,
+            Err(_) => FileStatus::Unknown,
+        },
+    };
+
+    let entry = DesktopEntry::parse(&app.desktop_path).ok();
+    let notable_keys = entry.as_ref().map(notable_keys).unwrap_or_default();
+    let url_handlers = entry.as_ref().map(url_handlers).unwrap_or_default();
+    let added_autostart = autostart::user_dir()
+        .map(|dir| autostart::find_entries(&dir, app))
+        .unwrap_or_default()
+        .into_iter()
+        .map(|path| AddedAutostart {
+            blocked: autostart::is_blocked(&path),
+            path,
+        })
+        .collect();
+
+    AppAudit {
+        identifier: app.identifier.clone(),
+        name: crate::resolve::display_name(app),
+        path: path.clone(),
+        signature,
+        trusted,
+        origin_url: app.origin_url.clone(),
+        file,
+        notable_keys,
+        url_handlers,
+        autostart_entries: exists.then(|| autostart_entries(path)).flatten(),
+        added_autostart,
+    }
+}
+
+/// Notable keys set in a desktop entry, as `Key=value`
+fn notable_keys(entry: &DesktopEntry) -> Vec<String> {
+    NOTABLE_KEYS
+        .iter()
+        .filter_map(|key| {
+            let value = entry.entries.get(*key)?;
+            Some(format!("{}={}", key, value))
+        })
+        .collect()
+}
+
+/// URL schemes a desktop entry registers to open
+fn url_handlers(entry: &DesktopEntry) -> Vec<String> {
+    entry
+        .entries
+        .get("MimeType")
+        .map(|types| {
+            types
+                .split(';')
+                .filter_map(|t| t.trim().strip_prefix("x-scheme-handler/"))
+                .filter(|scheme| !scheme.is_empty())
+                .map(str::to_string)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Desktop entries in the image's autostart directories. `None` for type 1
+/// AppImages and images that can't be listed without extracting them.
+pub fn autostart_entries(path: &Path) -> Option<Vec<String>> {
+    let offset = appimage::squashfs_offset(path).ok()??;
+    let mut image = Squashfs::open(path, offset).ok()?;
+    let entries = image.entries().ok()?;
+    Some(
+        entries
+            .into_iter()
+            .map(|entry| entry.path)
+            .filter(|path| {
+                path.ends_with(".desktop") && AUTOSTART_DIRS.iter().any(|dir| path.starts_with(dir))
+            })
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::create_entry;
+    use crate::testing::AppImageBuilder;
+    use std::fs;
+
+    #[test]
+    fn test_audit() {
+        let (dir, path) = AppImageBuilder::new("App")
+            .file(
+                "etc/xdg/autostart/app-tray.desktop",
+                "[Desktop Entry]\nType=Application\nName=App Tray\n",
+            )
+            .write_temp()
+            .unwrap();
+        let desktop = dir.path().join("appimage-0123.desktop");
+        fs::write(
+            &desktop,
+            "[Desktop Entry]\nType=Application\nName=App\n\
+             PrefersNonDefaultGPU=true\nMimeType=text/plain;x-scheme-handler/app;\n",
+        )
+        .unwrap();
+        let mut app = create_entry(
+            "0123".to_string(),
+            path.clone(),
+            desktop,
+            vec![],
+            Some("App".to_string()),
+        );
+        app.content_hash = Some(appimage::content_hash(&path).unwrap());
+
+        let report = audit(&app);
+        assert_eq!(report.file, FileStatus::Unchanged);
+        assert_eq!(report.notable_keys, ["PrefersNonDefaultGPU=true"]);
+        assert_eq!(report.url_handlers, ["app"]);
+        assert_eq!(
+            report.autostart_entries,
+            Some(vec!["etc/xdg/autostart/app-tray.desktop".to_string()])
+        );
+        assert!(!report.trusted);
+
+        app.content_hash = Some("0".repeat(32));
+        assert_eq!(audit(&app).file, FileStatus::Changed);
+        fs::remove_file(&path).unwrap();
+        let report = audit(&app);
+        assert_eq!(report.file, FileStatus::Missing);
+        assert_eq!(report.autostart_entries, None);
+    }
+}
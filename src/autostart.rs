@@ -0,0 +1,176 @@
+//! Autostart entries that integrated AppImages create for themselves.
+//!
+//! Some apps add a desktop entry to `~/.config/autostart` the first time they
+//! run, so they start on every login. The daemon looks for entries whose
+//! `Exec` runs an integrated AppImage (or that are copies of its launcher),
+//! records them with the app, and with `integration.block_autostart` set
+//! disables them with `Hidden=true`, as the XDG autostart spec allows.
+
+use crate::desktop::{self, DesktopEntry, DesktopError};
+use crate::state::IntegratedAppImage;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Key marking autostart entries that were disabled by appimage-auto
+pub const BLOCKED_KEY: &str = "X-AppImage-Auto-Blocked";
+
+/// The user's autostart directory (`~/.config/autostart`)
+pub fn user_dir() -> Option<PathBuf> {
+    directories::BaseDirs::new().map(|dirs| dirs.config_dir().join("autostart"))
+}
+
+/// Autostart entries in `dir` that start `app`, sorted by path
+pub fn find_entries(dir: &Path, app: &IntegratedAppImage) -> Vec<PathBuf> {
+    read_entries(dir)
+        .into_iter()
+        .filter(|(_, entry)| starts_app(entry, app))
+        .map(|(path, _)| path)
+        .collect()
+}
+
+/// Autostart entries in `dir` by the identifier of the app they start,
+/// reading each entry once
+pub fn scan<'a>(
+    dir: &Path,
+    apps: impl IntoIterator<Item = &'a IntegratedAppImage>,
+) -> HashMap<String, Vec<PathBuf>> {
+    let entries = read_entries(dir);
+    apps.into_iter()
+        .map(|app| {
+            let found: Vec<PathBuf> = entries
+                .iter()
+                .filter(|(_, entry)| starts_app(entry, app))
+                .map(|(path, _)| path.clone())
+                .collect();
+            (app.identifier.clone(), found)
+        })
+        .filter(|(_, found)| !found.is_empty())
+        .collect()
+}
+
+/// The desktop entries in a directory, sorted by path
+fn read_entries(dir: &Path) -> Vec<(PathBuf, DesktopEntry)> {
+    let Ok(read_dir) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut entries: Vec<(PathBuf, DesktopEntry)> = read_dir
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "desktop"))
+        .filter_map(|path| DesktopEntry::parse(&path).ok().map(|entry| (path, entry)))
+        .collect();
+    entries.sort_by(|a, b| a.0.cmp(&b.0));
+    entries
+}
+
+/// Whether a desktop entry launches an integrated AppImage
+fn starts_app(entry: &DesktopEntry, app: &IntegratedAppImage) -> bool {
+    if entry.appimage_identifier() == Some(app.identifier.as_str()) {
+        return true;
+    }
+    let Some(exec) = entry.exec() else {
+        return false;
+    };
+    // Any word, to catch `env VAR=1 app` and `appimage-auto track app`
+    desktop::exec_words(exec).iter().any(|word| {
+        let word = Path::new(word);
+        word == app.appimage_path
+            || app.symlink_target.as_deref() == Some(word)
+            || app.aliases.iter().any(|alias| alias == word)
+    })
+}
+
+/// Whether an autostart entry was disabled by [`block`]
+pub fn is_blocked(path: &Path) -> bool {
+    DesktopEntry::parse(path)
+        .is_ok_and(|entry| entry.entries.get(BLOCKED_KEY).is_some_and(|v| v == "true"))
+}
+
+/// Disable an autostart entry, marking it as done by appimage-auto. Returns
+/// false if it was already hidden.
+pub fn block(path: &Path) -> Result<bool, DesktopError> {
+    let mut entry = DesktopEntry::parse(path)?;
+    if entry.entries.get("Hidden").is_some_and(|v| v == "true") {
+        return Ok(false);
+    }
+    entry
+        .entries
+        .insert("Hidden".to_string(), "true".to_string());
+    entry
+        .entries
+        .insert(BLOCKED_KEY.to_string(), "true".to_string());
+    entry.write(path)?;
+    Ok(true)
+}
+
+/// Enable an autostart entry disabled by [`block`] again. Returns false if
+/// it wasn't blocked by appimage-auto.
+pub fn unblock(path: &Path) -> Result<bool, DesktopError> {
+    let mut entry = DesktopEntry::parse(path)?;
+    if entry.entries.remove(BLOCKED_KEY).is_none() {
+        return Ok(false);
+    }
+    entry.entries.remove("Hidden");
+    entry.write(path)?;
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::create_entry;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_find_and_block_entries() {
+        let dir = TempDir::new().unwrap();
+        let app = create_entry(
+            "0123".to_string(),
+            PathBuf::from("/home/user/Applications/Chat.AppImage"),
+            PathBuf::from("/tmp/appimage-0123.desktop"),
+            vec![],
+            Some("Chat".to_string()),
+        );
+        fs::write(
+            dir.path().join("chat.desktop"),
+            "[Desktop Entry]\nType=Application\nName=Chat\n\
+             Exec=env CHAT_TRAY=1 \"/home/user/Applications/Chat.AppImage\" --hidden\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.path().join("copy.desktop"),
+            "[Desktop Entry]\nType=Application\nName=Chat\nExec=chat\nX-AppImage-Identifier=0123\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.path().join("other.desktop"),
+            "[Desktop Entry]\nType=Application\nName=Other\nExec=/opt/Other.AppImage\n",
+        )
+        .unwrap();
+
+        let found = find_entries(dir.path(), &app);
+        assert_eq!(
+            found,
+            [
+                dir.path().join("chat.desktop"),
+                dir.path().join("copy.desktop")
+            ]
+        );
+        assert_eq!(scan(dir.path(), [&app]).get("0123"), Some(&found));
+
+        let entry = &found[0];
+        assert!(block(entry).unwrap());
+        assert!(is_blocked(entry));
+        assert!(!block(entry).unwrap());
+        assert!(unblock(entry).unwrap());
+        assert!(!is_blocked(entry));
+        assert!(
+            !DesktopEntry::parse(entry)
+                .unwrap()
+                .entries
+                .contains_key("Hidden")
+        );
+        assert!(find_entries(&dir.path().join("missing"), &app).is_empty());
+    }
+}
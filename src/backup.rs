@@ -0,0 +1,416 @@
+//! Backup and restore of integration artifacts.
+//!
+//! A backup is a gzipped tarball (written with the system `tar`) holding:
+//!
+//! ```text
+//! state.json
+//! overrides.toml
+//! applications/appimage-<id>.desktop
+//! desktop-copies/<n>/appimage-<id>.desktop
+//! icons/<size>/apps/appimage-<id>.<ext>
+//! custom-icons/<id>.<ext>
+//! ```
+//!
+//! `desktop-copies/<n>` holds the copies made for the n-th directory in
+//! `integration.desktop_dirs`, and `custom-icons` the icons chosen with
+//! `set-icon`. The AppImages themselves are not included. On restore,
+//! desktop entries and icons go to the configured directories and the state
+//! is updated to point at them, so a backup can be restored on another machine as long as
+//! the AppImages live at the same paths there.
+
+use crate::config::Config;
+use crate::integrity;
+use crate::overrides::{Overrides, OverridesError};
+use crate::state::{State, StateError};
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::process::Command;
+use thiserror::Error;
+use tracing::{info, warn};
+
+/// State file inside a backup
+const STATE_FILE: &str = "state.json";
+
+/// Overrides file inside a backup
+const OVERRIDES_FILE: &str = "overrides.toml";
+
+/// Directory of desktop entries inside a backup
+const DESKTOP_DIR: &str = "applications";
+
+/// Directory of icons inside a backup, laid out like the icon theme
+const ICON_DIR: &str = "icons";
+
+/// Directory of copies of the desktop entries inside a backup, with a
+/// numbered subdirectory per extra desktop directory
+const DESKTOP_COPIES_DIR: &str = "desktop-copies";
+
+/// Directory of the icons users chose inside a backup
+const CUSTOM_ICON_DIR: &str = "custom-icons";
+
+#[derive(Error, Debug)]
+pub enum BackupError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("JSON error: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("State error: {0}")]
+    State(#[from] StateError),
+    #[error("Overrides error: {0}")]
+    Overrides(#[from] OverridesError),
+    #[error("tar failed: {0}")]
+    Tar(String),
+    #[error("Not an appimage-auto backup: {0}")]
+    InvalidArchive(String),
+}
+
+/// What a backup or restore covered
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BackupSummary {
+    /// Integrated AppImages in the backup
+    pub apps: usize,
+    /// Desktop entries and icons copied
+    pub files: usize,
+}
+
+/// Write a backup of the current state, desktop entries, icons and overrides
+pub fn create_backup(archive: &Path, config: &Config) -> Result<BackupSummary, BackupError> {
+    let state = State::load()?;
+    let overrides = Overrides::load()?;
+
+    let staging = tempfile::TempDir::new()?;
+    let summary = stage(&state, &overrides, &config.icon_directory(), staging.path())?;
+    tar(&[
+        "-czf".as_ref(),
+        archive.as_os_str(),
+        "-C".as_ref(),
+        staging.path().as_os_str(),
+        ".".as_ref(),
+    ])?;
+
+    info!("Wrote backup {:?}", archive);
+    Ok(summary)
+}
+
+/// Restore a backup, replacing the current state and overrides.
+///
+/// The previous state file is kept as `state.json.bak`.
+pub fn restore_backup(archive: &Path, config: &Config) -> Result<BackupSummary, BackupError> {
+    let staging = tempfile::TempDir::new()?;
+    tar(&[
+        "-xzf".as_ref(),
+        archive.as_os_str(),
+        "-C".as_ref(),
+        staging.path().as_os_str(),
+    ])?;
+
+    let (mut state, overrides, summary) = unstage(
+        staging.path(),
+        &config.desktop_directory(),
+        &config.extra_desktop_directories(),
+        &config.icon_directory(),
+        &State::custom_icon_dir()?,
+    )?;
+
+    let state_path = State::state_path()?;
+    if state_path.exists() {
+        fs::rename(&state_path, state_path.with_extension("json.bak"))?;
+    }
+    state.save()?;
+    overrides.save()?;
+
+    info!("Restored backup {:?}", archive);
+    Ok(summary)
+}
+
+/// Copy everything a backup holds into `dir`
+pub fn stage(
+    state: &State,
+    overrides: &Overrides,
+    icon_dir: &Path,
+    dir: &Path,
+) -> Result<BackupSummary, BackupError> {
+    fs::write(dir.join(STATE_FILE), serde_json::to_string_pretty(state)?)?;
+    overrides.save_to(&dir.join(OVERRIDES_FILE))?;
+
+    let mut files = 0;
+    for app in state.all() {
+        let artifacts = std::iter::once((&app.desktop_path, desktop_relative(&app.desktop_path)))
+            .chain(
+                app.desktop_copies
+                    .iter()
+                    .enumerate()
+                    .map(|(n, copy)| (copy, desktop_copy_relative(n, copy))),
+            )
+            .chain(
+                app.icon_paths
+                    .iter()
+                    .map(|icon| (icon, icon_relative(icon, icon_dir))),
+            )
+            .chain(
+                app.custom_icon
+                    .iter()
+                    .map(|icon| (icon, custom_icon_relative(icon))),
+            );
+        for (src, relative) in artifacts {
+            let Some(relative) = relative else {
+                continue;
+            };
+            if !src.is_file() {
+                warn!("Not backing up missing file {:?}", src);
+                continue;
+            }
+            let dest = dir.join(relative);
+            if let Some(parent) = dest.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::copy(src, dest)?;
+            files += 1;
+        }
+    }
+
+    Ok(BackupSummary {
+        apps: state.count(),
+        files,
+    })
+}
+
+/// Install the files of an unpacked backup in `dir` into the given
+/// directories, returning the state (pointing at the installed files) and
+/// overrides to save
+pub fn unstage(
+    dir: &Path,
+    desktop_dir: &Path,
+    desktop_copy_dirs: &[PathBuf],
+    icon_dir: &Path,
+    custom_icon_dir: &Path,
+) -> Result<(State, Overrides, BackupSummary), BackupError> {
+    let state_file = dir.join(STATE_FILE);
+    if !state_file.is_file() {
+        return Err(BackupError::InvalidArchive(format!(
+            "{} is missing",
+            STATE_FILE
+        )));
+    }
+    let mut state = State::load_from(&state_file)?;
+    let overrides_file = dir.join(OVERRIDES_FILE);
+    let overrides = if overrides_file.is_file() {
+        Overrides::load_from(&overrides_file)?
+    } else {
+        Overrides::default()
+    };
+
+    let mut files = 0;
+    let mut install = |relative: &Path, dest: PathBuf| -> Result<Option<PathBuf>, BackupError> {
+        let src = dir.join(relative);
+        if !src.is_file() {
+            return Ok(None);
+        }
+        if let Some(parent) = dest.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::copy(&src, &dest)?;
+        files += 1;
+        Ok(Some(dest))
+    };
+
+    for app in state.integrated.values_mut() {
+        if let Some(relative) = desktop_relative(&app.desktop_path)
+            && let Some(dest) = install(
+                &relative,
+                desktop_dir.join(relative.strip_prefix(DESKTOP_DIR).unwrap_or(&relative)),
+            )?
+        {
+            app.desktop_path = dest;
+        }
+
+        let mut desktop_copies = Vec::new();
+        for (n, copy_dir) in desktop_copy_dirs.iter().enumerate() {
+            let Some(file_name) = app.desktop_path.file_name() else {
+                continue;
+            };
+            let Some(relative) = desktop_copy_relative(n, &app.desktop_path) else {
+                continue;
+            };
+            if let Some(dest) = install(&relative, copy_dir.join(file_name))? {
+                desktop_copies.push(dest);
+            }
+        }
+        app.desktop_copies = desktop_copies;
+
+        let mut icon_paths = Vec::new();
+        for icon in &app.icon_paths {
+            let Some(relative) = icon_relative(icon, icon_dir) else {
+                continue;
+            };
+            let dest = icon_dir.join(relative.strip_prefix(ICON_DIR).unwrap_or(&relative));
+            if let Some(dest) = install(&relative, dest)? {
+                icon_paths.push(dest);
+            }
+        }
+        app.icon_paths = icon_paths;
+
+        if let Some(icon) = &app.custom_icon {
+            app.custom_icon = match (custom_icon_relative(icon), icon.file_name()) {
+                (Some(relative), Some(file_name)) => {
+                    install(&relative, custom_icon_dir.join(file_name))?
+                }
+                _ => None,
+            };
+        }
+        app.checksums = integrity::checksums(app);
+    }
+
+    let summary = BackupSummary {
+        apps: state.count(),
+        files,
+    };
+    Ok((state, overrides, summary))
+}
+
+/// Path of a desktop entry inside a backup
+fn desktop_relative(desktop: &Path) -> Option<PathBuf> {
+    Some(Path::new(DESKTOP_DIR).join(desktop.file_name()?))
+}
+
+/// Path inside a backup of a desktop entry copied to the `n`-th extra
+/// desktop directory
+fn desktop_copy_relative(n: usize, copy: &Path) -> Option<PathBuf> {
+    Some(
+        Path::new(DESKTOP_COPIES_DIR)
+            .join(n.to_string())
+            .join(copy.file_name()?),
+    )
+}
+
+/// Path of an icon the user chose inside a backup
+fn custom_icon_relative(icon: &Path) -> Option<PathBuf> {
+    Some(Path::new(CUSTOM_ICON_DIR).join(icon.file_name()?))
+}
+
+/// Path of an icon inside a backup
+fn icon_relative(icon: &Path, icon_dir: &Path) -> Option<PathBuf> {
+    Some(Path::new(ICON_DIR).join(icon_below(icon, icon_dir)?))
+}
+
+/// Path of an icon relative to an icon theme: its path below `icon_dir`, or
+/// `<size>/apps/<file>` if it was installed elsewhere
+pub(crate) fn icon_below(icon: &Path, icon_dir: &Path) -> Option<PathBuf> {
+    let below = match icon.strip_prefix(icon_dir) {
+        Ok(below) => below.to_path_buf(),
+        Err(_) => {
+            let components: Vec<Component> = icon.components().collect();
+            components[components.len().checked_sub(3)?..]
+                .iter()
+                .collect()
+        }
+    };
+    // Never let a crafted state escape the target directory
+    if below
+        .components()
+        .any(|c| !matches!(c, Component::Normal(_)))
+    {
+        return None;
+    }
+    Some(below)
+}
+
+/// Run the system `tar`
+fn tar(args: &[&std::ffi::OsStr]) -> Result<(), BackupError> {
+    let output = Command::new("tar").args(args).output()?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(BackupError::Tar(
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::overrides::DesktopOverrides;
+    use crate::state::create_entry;
+
+    #[test]
+    fn test_stage_and_unstage() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let old = temp.path().join("old");
+        let new = temp.path().join("new");
+        let staging = temp.path().join("staging");
+        fs::create_dir_all(old.join("applications")).unwrap();
+        fs::create_dir_all(old.join("icons/48x48/apps")).unwrap();
+        fs::create_dir_all(&staging).unwrap();
+
+        fs::create_dir_all(old.join("kde")).unwrap();
+        fs::create_dir_all(old.join("custom-icons")).unwrap();
+
+        let desktop = old.join("applications/appimage-abc.desktop");
+        let copy = old.join("kde/appimage-abc.desktop");
+        let icon = old.join("icons/48x48/apps/appimage-abc.png");
+        let custom_icon = old.join("custom-icons/abc.svg");
+        fs::write(&desktop, b"[Desktop Entry]\n").unwrap();
+        fs::write(&copy, b"[Desktop Entry]\n").unwrap();
+        fs::write(&icon, b"png").unwrap();
+        fs::write(&custom_icon, b"svg").unwrap();
+
+        let mut entry = create_entry(
+            "abc".to_string(),
+            PathBuf::from("/apps/App.AppImage"),
+            desktop,
+            vec![icon],
+            Some("App".to_string()),
+        );
+        entry.desktop_copies = vec![copy];
+        entry.custom_icon = Some(custom_icon);
+        let mut state = State::default();
+        state.add(entry);
+        let mut overrides = Overrides::default();
+        overrides.set(
+            "abc",
+            DesktopOverrides {
+                name: Some("Renamed".to_string()),
+                ..Default::default()
+            },
+        );
+
+        let staged = stage(&state, &overrides, &old.join("icons"), &staging).unwrap();
+        assert_eq!(staged, BackupSummary { apps: 1, files: 4 });
+
+        let (restored, overrides, summary) = unstage(
+            &staging,
+            &new.join("applications"),
+            &[new.join("kde")],
+            &new.join("icons"),
+            &new.join("custom-icons"),
+        )
+        .unwrap();
+        assert_eq!(summary, staged);
+        let app = restored.get("abc").unwrap();
+        assert_eq!(
+            app.desktop_path,
+            new.join("applications/appimage-abc.desktop")
+        );
+        assert_eq!(
+            app.icon_paths,
+            [new.join("icons/48x48/apps/appimage-abc.png")]
+        );
+        assert_eq!(fs::read(&app.icon_paths[0]).unwrap(), b"png");
+        assert_eq!(app.desktop_copies, [new.join("kde/appimage-abc.desktop")]);
+        assert!(app.desktop_copies[0].is_file());
+        assert_eq!(app.custom_icon, Some(new.join("custom-icons/abc.svg")));
+        assert_eq!(fs::read(new.join("custom-icons/abc.svg")).unwrap(), b"svg");
+        assert!(restored.is_integrated(Path::new("/apps/App.AppImage")));
+        assert_eq!(
+            overrides.get("abc").and_then(|o| o.name.as_deref()),
+            Some("Renamed")
+        );
+
+        let empty = temp.path().join("empty");
+        fs::create_dir_all(&empty).unwrap();
+        assert!(matches!(
+            unstage(&empty, &new, &[], &new, &new),
+            Err(BackupError::InvalidArchive(_))
+        ));
+    }
+}
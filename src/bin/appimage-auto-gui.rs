@@ -2,11 +2,19 @@
 //!
 //! A GTK4/libadwaita graphical interface for managing AppImage integrations.
 
+use appimage_auto::flatpak;
 use appimage_auto::gui::AppModel;
 use relm4::RelmApp;
+use relm4::gtk::gio;
+use relm4::gtk::prelude::*;
 
 fn main() {
+    // Manage the host's integrations when running as a Flatpak
+    flatpak::use_host_directories();
+
     // Initialize Relm4 with libadwaita
     let app = RelmApp::new("io.github.appimage-auto.settings");
+    // Accept files so AppImages can be opened with the GUI from a file manager
+    relm4::main_application().set_flags(gio::ApplicationFlags::HANDLES_OPEN);
     app.run::<AppModel>(());
 }
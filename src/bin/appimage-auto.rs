@@ -2,12 +2,29 @@
 //!
 //! Main binary for the appimage-auto daemon.
 
+use appimage_auto::appimage::AppImageError;
+use appimage_auto::appstream;
+use appimage_auto::backup;
+use appimage_auto::config::{AppSort, IntegrationMode, SignaturePolicy};
+use appimage_auto::control::{self, ControlCommand};
+use appimage_auto::exit_code::ExitCode;
+use appimage_auto::flatpak;
+use appimage_auto::migrate;
+use appimage_auto::resolve::{self, ResolveError};
+use appimage_auto::state::{self, AppFilter, IntegratedAppImage};
 use appimage_auto::{Config, Daemon, State, daemon};
-use clap::{Parser, Subcommand};
-use std::path::PathBuf;
+use clap::{Parser, Subcommand, ValueEnum};
+use std::ffi::OsString;
+use std::io::{IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
 use std::sync::atomic::Ordering;
 use tracing::{error, info};
-use tracing_subscriber::EnvFilter;
+use tracing_subscriber::prelude::*;
+use tracing_subscriber::{EnvFilter, fmt};
+
+/// File name of the GUI binary
+const GUI_BINARY: &str = "appimage-auto-gui";
 
 #[derive(Parser)]
 #[command(name = "appimage-auto")]
@@ -24,12 +41,27 @@ struct Cli {
     /// Verbose output (can be repeated: -v, -vv, -vvv)
     #[arg(short, long, action = clap::ArgAction::Count, global = true)]
     verbose: u8,
+
+    /// Print nothing; only the exit status tells how it went (3: not an
+    /// AppImage, 4: extraction failed, 5: no desktop entry, 6: state file
+    /// locked, 7: daemon not running, 1: other errors)
+    #[arg(short, long, global = true, conflicts_with = "verbose")]
+    quiet: bool,
 }
 
 #[derive(Subcommand)]
 enum Commands {
     /// Start the daemon in the foreground
-    Daemon,
+    Daemon {
+        /// Exit after this many seconds without watch events or control requests
+        #[arg(long, value_name = "SECS")]
+        exit_idle: Option<u64>,
+
+        /// Watch and decide as usual, but only log what would be done,
+        /// changing no files and not saving the state
+        #[arg(long)]
+        observe: bool,
+    },
 
     /// Scan directories once and exit (no watching)
     Scan,
@@ -37,19 +69,257 @@ enum Commands {
     /// Show daemon status and statistics
     Status,
 
+    /// Show the daemon's log, from the journal or `logging.file`
+    Logs {
+        /// Keep showing lines as they are logged
+        #[arg(short, long)]
+        follow: bool,
+
+        /// Only show lines since a time: a duration back ("30m", "2h", "1d")
+        /// or a local date and time ("2024-05-01 14:30")
+        #[arg(long)]
+        since: Option<String>,
+    },
+
+    /// Replay watcher events recorded with `logging.record_events`,
+    /// logging what the daemon would do without changing anything
+    Replay {
+        /// File with the recorded events
+        file: PathBuf,
+    },
+
     /// List all integrated AppImages
-    List,
+    List {
+        /// List failed integrations instead
+        #[arg(long, conflicts_with_all = ["missing", "dir", "name", "sort", "format"])]
+        failed: bool,
+
+        /// List AppImages awaiting approval instead (approval mode)
+        #[arg(long, conflicts_with_all = ["failed", "missing", "dir", "name", "sort", "format"])]
+        awaiting: bool,
+
+        /// Only AppImages whose file is missing
+        #[arg(long)]
+        missing: bool,
+
+        /// Only AppImages inside this directory
+        #[arg(long, value_name = "PATH")]
+        dir: Option<PathBuf>,
+
+        /// Only AppImages whose name or file name contains this text
+        #[arg(long, value_name = "SUBSTR")]
+        name: Option<String>,
+
+        /// Sort order
+        #[arg(long, value_enum, default_value_t = ListSort::Name)]
+        sort: ListSort,
+
+        /// Output format
+        #[arg(long, value_enum, default_value_t = ListFormat::Table)]
+        format: ListFormat,
+    },
 
     /// Manually integrate a specific AppImage
     Integrate {
         /// Path to the AppImage file
         path: PathBuf,
+
+        /// Integrate again even if already integrated
+        #[arg(long)]
+        force: bool,
     },
 
-    /// Manually remove integration for an AppImage
-    Remove {
+    /// Take over a hand-written desktop entry for an AppImage, so it is
+    /// managed like an integration of its own
+    Adopt {
+        /// Path to the .desktop file
+        desktop_file: PathBuf,
+    },
+
+    /// Run an AppImage once without integrating it
+    RunOnce {
+        /// Path to the AppImage file
+        path: PathBuf,
+
+        /// Arguments passed to the AppImage
+        #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+        args: Vec<OsString>,
+    },
+
+    /// Count a launch and run the AppImage (used by desktop entries when
+    /// `track_launches` is enabled)
+    #[command(hide = true)]
+    Track {
         /// Path to the AppImage file
         path: PathBuf,
+
+        /// Arguments passed to the AppImage
+        #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+        args: Vec<OsString>,
+    },
+
+    /// Show details of an integrated AppImage
+    Info {
+        /// Name, file name, identifier prefix or path of the AppImage
+        app: String,
+    },
+
+    /// Compare the embedded metadata of two AppImages (version, desktop
+    /// entry, icons, signature, size)
+    Diff {
+        /// AppImage to compare from, e.g. the integrated version
+        old: PathBuf,
+
+        /// AppImage to compare with, e.g. a new download
+        new: PathBuf,
+    },
+
+    /// Launch an integrated AppImage
+    Launch {
+        /// Name, file name, identifier prefix or path of the AppImage
+        app: String,
+
+        /// Arguments passed to the AppImage
+        #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+        args: Vec<OsString>,
+    },
+
+    /// Manually remove integration for an AppImage
+    Remove {
+        /// Name, file name, identifier prefix or path of the AppImage
+        app: String,
+
+        /// Also delete the AppImage file
+        #[arg(long)]
+        delete: bool,
+
+        /// Delete without asking for confirmation when the AppImage was
+        /// found by name rather than by its path
+        #[arg(short, long, requires = "delete")]
+        yes: bool,
+    },
+
+    /// Pin integrated AppImages so they are kept when their file goes missing
+    Pin {
+        /// Names, file names, identifier prefixes or paths of the AppImages
+        #[arg(required = true)]
+        apps: Vec<String>,
+    },
+
+    /// Unpin integrated AppImages
+    Unpin {
+        /// Names, file names, identifier prefixes or paths of the AppImages
+        #[arg(required = true)]
+        apps: Vec<String>,
+    },
+
+    /// Use a custom icon for an integrated AppImage, kept across updates and
+    /// repairs
+    SetIcon {
+        /// Name, file name, identifier prefix or path of the AppImage
+        app: String,
+
+        /// PNG, SVG or XPM icon to use (a copy is kept)
+        #[arg(required_unless_present = "reset")]
+        icon: Option<PathBuf>,
+
+        /// Go back to the AppImage's own icon
+        #[arg(long, conflicts_with = "icon")]
+        reset: bool,
+    },
+
+    /// List apps that were not launched or modified for a while
+    Stale {
+        /// Days without a launch or change before an app counts as stale
+        #[arg(long, default_value_t = 90)]
+        days: u64,
+
+        /// Ask for each stale app whether to remove its integration
+        #[arg(long)]
+        remove: bool,
+    },
+
+    /// Show the disk space used by integrated AppImages and their artifacts
+    Du {
+        /// Output format
+        #[arg(long, value_enum, default_value_t = ListFormat::Table)]
+        format: ListFormat,
+    },
+
+    /// Remove desktop entries and icons left behind by lost integrations
+    Prune {
+        /// Remove without asking for confirmation
+        #[arg(short, long)]
+        yes: bool,
+    },
+
+    /// Find desktop entries and icons changed or deleted since they were
+    /// written, e.g. edited by hand or overwritten by another tool
+    Check {
+        /// Name, file name, identifier prefix or path of the AppImage
+        /// (default: all)
+        app: Option<String>,
+
+        /// Re-integrate changed apps, writing their files anew
+        #[arg(long, conflicts_with = "adopt")]
+        repair: bool,
+
+        /// Keep the changes, no longer reporting them
+        #[arg(long)]
+        adopt: bool,
+    },
+
+    /// Review signatures, origins and notable settings of integrated AppImages
+    Audit {
+        /// Name, file name, identifier prefix or path of the AppImage
+        /// (default: all)
+        app: Option<String>,
+
+        /// Output format
+        #[arg(long, value_enum, default_value_t = ListFormat::Table)]
+        format: ListFormat,
+    },
+
+    /// Inspect or repair the state file
+    State {
+        #[command(subcommand)]
+        action: StateAction,
+    },
+
+    /// Save state, desktop entries, icons and overrides to a .tar.gz
+    Backup {
+        /// Archive to write
+        archive: PathBuf,
+    },
+
+    /// Copy the desktop entries and icons of all integrated AppImages below
+    /// a prefix, for sessions that don't share this home directory
+    ExportMenu {
+        /// Prefix to write `share/applications` and `share/icons` to
+        prefix: PathBuf,
+
+        /// Directory the AppImages are found in where the menu is used
+        /// (default: where they are now)
+        #[arg(long)]
+        appimage_dir: Option<PathBuf>,
+    },
+
+    /// Restore a backup made with `backup`, replacing the current state
+    Restore {
+        /// Archive to read
+        archive: PathBuf,
+    },
+
+    /// Manage the keys AppImage signatures are verified against
+    Trust {
+        #[command(subcommand)]
+        action: TrustAction,
+    },
+
+    /// Generate, load and unload AppArmor profiles of AppImages
+    Apparmor {
+        #[command(subcommand)]
+        action: AppArmorAction,
     },
 
     /// Show or modify configuration
@@ -57,6 +327,171 @@ enum Commands {
         #[command(subcommand)]
         action: Option<ConfigAction>,
     },
+
+    /// Manage the systemd user service
+    Service {
+        #[command(subcommand)]
+        action: ServiceAction,
+    },
+
+    /// Set up desktop integration for appimage-auto itself
+    Setup {
+        #[command(subcommand)]
+        action: SetupAction,
+    },
+
+    /// Open the settings GUI (or bring an open one to the front)
+    Gui {
+        /// AppImages to open with the GUI, e.g. to ask whether to integrate them
+        files: Vec<PathBuf>,
+    },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum ListSort {
+    /// Alphabetical by name
+    Name,
+    /// Most recently integrated first
+    Integrated,
+    /// Most recently updated first
+    Updated,
+    /// Largest file first
+    Size,
+    /// Grouped by directory
+    Directory,
+    /// Most recently launched first
+    LastUsed,
+}
+
+impl From<ListSort> for AppSort {
+    fn from(sort: ListSort) -> Self {
+        match sort {
+            ListSort::Name => AppSort::Name,
+            ListSort::Integrated => AppSort::Integrated,
+            ListSort::Updated => AppSort::Updated,
+            ListSort::Size => AppSort::Size,
+            ListSort::Directory => AppSort::Directory,
+            ListSort::LastUsed => AppSort::LastUsed,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum ListFormat {
+    Table,
+    Json,
+    Csv,
+}
+
+#[derive(Subcommand)]
+enum ServiceAction {
+    /// Write the user unit for this binary, then enable and start it
+    Install,
+
+    /// Stop, disable and remove the user unit
+    Uninstall,
+
+    /// Show whether the user unit is installed, enabled and running
+    Status,
+}
+
+#[derive(Subcommand)]
+enum SetupAction {
+    /// Register the AppImage MIME types and open AppImages with the GUI
+    Mime {
+        /// Remove the registration and association instead
+        #[arg(long)]
+        remove: bool,
+    },
+
+    /// Add "Integrate AppImage" to the Dolphin and Nautilus context menus
+    FileManager {
+        /// Remove the context menu entries instead
+        #[arg(long)]
+        remove: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum TrustAction {
+    /// Trust a key: a key ID or fingerprint from your GnuPG keyring, a key
+    /// file, or a signed AppImage (trusting the key embedded in it)
+    Add {
+        /// Key ID, fingerprint or file
+        key: String,
+    },
+
+    /// Stop trusting a key, or forget the key an app was trusted with on
+    /// first use
+    Remove {
+        /// Key ID, fingerprint or app ID
+        key: String,
+    },
+
+    /// List trusted keys
+    List,
+
+    /// Show or change how signatures are checked
+    Policy {
+        /// Whether unverified AppImages are integrated
+        #[arg(long, value_enum)]
+        verify: Option<VerifyPolicy>,
+
+        /// Trust the signing key of an app the first time it is integrated
+        #[arg(long)]
+        trust_on_first_use: bool,
+
+        /// Only trust keys that were added with `trust add`
+        #[arg(long, conflicts_with = "trust_on_first_use")]
+        no_trust_on_first_use: bool,
+    },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum VerifyPolicy {
+    /// Don't check signatures
+    Off,
+    /// Log AppImages with bad or untrusted signatures, but integrate them
+    Warn,
+    /// Only integrate AppImages signed by a trusted key
+    Require,
+}
+
+impl From<VerifyPolicy> for SignaturePolicy {
+    fn from(policy: VerifyPolicy) -> Self {
+        match policy {
+            VerifyPolicy::Off => SignaturePolicy::Off,
+            VerifyPolicy::Warn => SignaturePolicy::Warn,
+            VerifyPolicy::Require => SignaturePolicy::Require,
+        }
+    }
+}
+
+#[derive(Subcommand)]
+enum AppArmorAction {
+    /// Print the profile of an AppImage
+    Show {
+        /// Name, file name, identifier prefix or path of the AppImage
+        app: String,
+    },
+
+    /// Write the profile of an AppImage and load it (needs root, asks via sudo)
+    Install {
+        /// Name, file name, identifier prefix or path of the AppImage
+        app: String,
+    },
+
+    /// Unload the profile of an AppImage and remove it
+    Unload {
+        /// Name, file name, identifier prefix or path of the AppImage
+        app: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum StateAction {
+    /// Rebuild the state file from installed desktop entries
+    Rebuild,
 }
 
 #[derive(Subcommand)]
@@ -64,7 +499,7 @@ enum ConfigAction {
     /// Show current configuration
     Show,
 
-    /// Show configuration file path
+    /// Show configuration file path (and the drop-in directory, if any)
     Path,
 
     /// Add a directory to watch
@@ -81,6 +516,7 @@ enum ConfigAction {
 }
 
 fn main() {
+    flatpak::use_host_directories();
     let cli = Cli::parse();
 
     // Set up logging
@@ -90,12 +526,32 @@ fn main() {
         _ => "trace",
     };
 
-    let filter = EnvFilter::try_from_default_env()
-        .unwrap_or_else(|_| EnvFilter::new(format!("appimage_auto={}", log_level)));
+    let filter = if cli.quiet {
+        silence_stdout();
+        EnvFilter::new("off")
+    } else {
+        EnvFilter::try_from_default_env()
+            .unwrap_or_else(|_| EnvFilter::new(format!("appimage_auto={}", log_level)))
+    };
 
-    tracing_subscriber::fmt()
-        .with_env_filter(filter)
-        .with_target(false)
+    // The daemon also logs to `logging.file`, if set
+    let log_file = match cli.command {
+        Commands::Daemon { .. } => open_log_file(cli.config.as_deref()),
+        _ => None,
+    };
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(
+            fmt::layer()
+                .with_target(false)
+                .with_ansi(std::io::stderr().is_terminal()),
+        )
+        .with(log_file.map(|file| {
+            fmt::layer()
+                .with_target(false)
+                .with_ansi(false)
+                .with_writer(Mutex::new(file))
+        }))
         .init();
 
     // Load config if specified
@@ -113,24 +569,92 @@ fn main() {
 
     // Run the appropriate command
     let result = match cli.command {
-        Commands::Daemon => run_daemon(config),
+        Commands::Daemon { exit_idle, observe } => run_daemon(config, exit_idle, observe),
         Commands::Scan => run_scan(config),
-        Commands::Status => run_status(),
-        Commands::List => run_list(),
-        Commands::Integrate { path } => run_integrate(config, &path),
-        Commands::Remove { path } => run_remove(&path),
+        Commands::Status => run_status(cli.verbose > 0),
+        Commands::Logs { follow, since } => run_logs(config, follow, since.as_deref()),
+        Commands::Replay { file } => run_replay(config, &file),
+        Commands::List { failed: true, .. } => run_list_failed(),
+        Commands::List { awaiting: true, .. } => run_list_awaiting(),
+        Commands::List {
+            missing,
+            dir,
+            name,
+            sort,
+            format,
+            ..
+        } => {
+            // Paths in state are absolute
+            let dir = dir.map(|d| d.canonicalize().unwrap_or(d));
+            let filter = AppFilter { missing, dir, name };
+            run_list(&filter, sort.into(), format)
+        }
+        Commands::Integrate { path, force } => run_integrate(config, &path, force),
+        Commands::Adopt { desktop_file } => run_adopt(config, &desktop_file),
+        Commands::RunOnce { path, args } => run_run_once(&path, &args),
+        Commands::Track { path, args } => {
+            if path.exists() {
+                record_launch(&path);
+            }
+            run_run_once(&path, &args)
+        }
+        Commands::Info { app } => run_info(&app),
+        Commands::Diff { old, new } => run_diff(&old, &new),
+        Commands::Launch { app, args } => run_launch(&app, &args),
+        Commands::Remove { app, delete, yes } => run_remove(&app, delete, yes),
+        Commands::Pin { apps } => run_pin(&apps, true),
+        Commands::Unpin { apps } => run_pin(&apps, false),
+        Commands::SetIcon { app, icon, .. } => run_set_icon(config, &app, icon.as_deref()),
+        Commands::Stale { days, remove } => run_stale(days, remove),
+        Commands::Du { format } => run_du(format),
+        Commands::Prune { yes } => run_prune(config, yes),
+        Commands::Check { app, repair, adopt } => run_check(config, app.as_deref(), repair, adopt),
+        Commands::Audit { app, format } => run_audit(app.as_deref(), format),
+        Commands::State { action } => run_state(config, action),
+        Commands::Backup { archive } => run_backup(config, &archive),
+        Commands::ExportMenu {
+            prefix,
+            appimage_dir,
+        } => run_export_menu(config, &prefix, appimage_dir.as_deref()),
+        Commands::Restore { archive } => run_restore(config, &archive),
+        Commands::Trust { action } => run_trust(action),
+        Commands::Apparmor { action } => run_apparmor(action),
         Commands::Config { action } => run_config(action),
+        Commands::Service { action } => run_service(action),
+        Commands::Setup { action } => run_setup(action),
+        Commands::Gui { files } => run_gui(&files),
     };
 
     if let Err(e) = result {
         error!("Error: {}", e);
-        std::process::exit(1);
+        std::process::exit(ExitCode::of(e.as_ref()).code());
+    }
+}
+
+/// Send standard output to /dev/null, for `--quiet`
+fn silence_stdout() {
+    use std::os::fd::AsRawFd;
+
+    if let Ok(null) = std::fs::OpenOptions::new().write(true).open("/dev/null") {
+        // SAFETY: both descriptors are open; stdout is replaced atomically
+        unsafe {
+            libc::dup2(null.as_raw_fd(), libc::STDOUT_FILENO);
+        }
     }
 }
 
-fn run_daemon(config: Option<Config>) -> Result<(), Box<dyn std::error::Error>> {
+fn run_daemon(
+    config: Option<Config>,
+    exit_idle: Option<u64>,
+    observe: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
     info!("Starting appimage-auto daemon...");
 
+    // Observe mode leaves the state file alone, an old one is read as it is
+    if !observe && let Some(report) = migrate::migrate()? {
+        info!("{}", report.to_string().trim_end());
+    }
+
     let mut daemon = match config {
         Some(c) => Daemon::with_config(c)?,
         None => Daemon::new()?,
@@ -143,6 +667,11 @@ fn run_daemon(config: Option<Config>) -> Result<(), Box<dyn std::error::Error>>
         running.store(false, Ordering::SeqCst);
     })?;
 
+    if observe {
+        info!("Observe mode: only logging what would be done");
+        daemon.set_dry_run(true);
+    }
+    daemon.set_exit_idle(exit_idle.map(std::time::Duration::from_secs));
     daemon.init()?;
     daemon.run()?;
 
@@ -155,14 +684,32 @@ fn run_scan(config: Option<Config>) -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
-fn run_status() -> Result<(), Box<dyn std::error::Error>> {
+fn run_status(verbose: bool) -> Result<(), Box<dyn std::error::Error>> {
+    use appimage_auto::service;
+
     let state = State::load()?;
     let config = Config::load()?;
 
     println!("AppImage Auto-Integration Status");
     println!("=================================");
     println!();
+    // Pinging a socket-activated daemon would start it, so ask systemd
+    // first; a daemon started by hand is pinged when no socket unit listens
+    let on_demand = service::socket_active();
+    let running = service::status().is_ok_and(|status| status.active)
+        || (!on_demand && control::send(ControlCommand::Ping).is_ok());
+    println!(
+        "Daemon: {}",
+        match (running, on_demand) {
+            (true, _) => "running",
+            (false, true) => "not running (starts on demand)",
+            (false, false) => "not running",
+        }
+    );
     println!("Integrated AppImages: {}", state.count());
+    if config.integration.mode == IntegrationMode::Approval {
+        println!("Awaiting approval: {}", state.awaiting().len());
+    }
     println!();
     println!("Watched directories:");
     for dir in &config.watch.directories {
@@ -171,39 +718,251 @@ fn run_status() -> Result<(), Box<dyn std::error::Error>> {
         let status = if exists { "OK" } else { "NOT FOUND" };
         println!("  {} [{}]", dir, status);
     }
+    if verbose {
+        let slowest = state.slowest(5);
+        if !slowest.is_empty() {
+            println!();
+            println!("Slowest integrations:");
+            for app in slowest {
+                let name = app.name.as_deref().unwrap_or("Unknown");
+                if let Some(t) = app.timing {
+                    println!(
+                        "  {:>6}ms  {} (extract {}ms, icon {}ms, desktop {}ms)",
+                        t.total_ms, name, t.extract_ms, t.icon_ms, t.desktop_ms
+                    );
+                }
+            }
+        }
+    }
     println!();
     println!("Config file: {:?}", Config::config_path()?);
     println!("State file:  {:?}", State::state_path()?);
 
+    if !running {
+        std::process::exit(ExitCode::DaemonNotRunning.code());
+    }
+    Ok(())
+}
+
+fn run_logs(
+    config: Option<Config>,
+    follow: bool,
+    since: Option<&str>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    use appimage_auto::logs::{self, LogSource};
+
+    let config = match config {
+        Some(config) => config,
+        None => Config::load()?,
+    };
+    let source = LogSource::from_config(&config);
+    let since = since
+        .map(|spec| logs::parse_since(spec, state::current_timestamp()))
+        .transpose()?;
+
+    let color = std::io::stdout().is_terminal();
+    let mut stdout = std::io::stdout().lock();
+    logs::stream(&source, since, follow, |line| {
+        let line = if color {
+            logs::colorize(line)
+        } else {
+            line.to_string()
+        };
+        // Stop quietly when the reader (e.g. `head`) goes away
+        if writeln!(stdout, "{}", line).is_err() {
+            std::process::exit(0);
+        }
+    })?;
+
+    Ok(())
+}
+
+fn run_replay(config: Option<Config>, file: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    use appimage_auto::replay;
+    use appimage_auto::watcher::ChannelSource;
+
+    let events = replay::read_events(file)?;
+    let config = match config {
+        Some(config) => config,
+        None => Config::load()?,
+    };
+    info!("Replaying {} events from {:?}", events.len(), file);
+
+    let (source, _events) = ChannelSource::new();
+    let mut daemon = Daemon::with_source(config, source)?;
+    daemon.set_dry_run(true);
+    daemon.replay(events)?;
+
+    info!("Replay complete");
     Ok(())
 }
 
-fn run_list() -> Result<(), Box<dyn std::error::Error>> {
+fn run_list(
+    filter: &AppFilter,
+    sort: AppSort,
+    format: ListFormat,
+) -> Result<(), Box<dyn std::error::Error>> {
+    use appimage_auto::format;
+
+    let state = State::load()?;
+    let mut apps: Vec<IntegratedAppImage> = state
+        .all()
+        .filter(|app| filter.matches(app))
+        .cloned()
+        .collect();
+    state::sort_apps(&mut apps, sort);
+
+    match format {
+        ListFormat::Json => println!("{}", serde_json::to_string_pretty(&apps)?),
+
+        ListFormat::Csv => {
+            println!(
+                "{}",
+                format::csv_row(&[
+                    "identifier",
+                    "name",
+                    "version",
+                    "path",
+                    "desktop",
+                    "size",
+                    "missing",
+                    "pinned",
+                    "integrated_at",
+                    "updated_at",
+                    "launch_count",
+                    "last_launched",
+                ])
+            );
+            for app in &apps {
+                println!(
+                    "{}",
+                    format::csv_row(&[
+                        app.identifier.clone(),
+                        app.name.clone().unwrap_or_default(),
+                        app.version.clone().unwrap_or_default(),
+                        app.appimage_path.display().to_string(),
+                        app.desktop_path.display().to_string(),
+                        app.file_size().to_string(),
+                        (!app.appimage_path.exists()).to_string(),
+                        app.pinned.to_string(),
+                        app.integrated_at.to_string(),
+                        app.updated_at.to_string(),
+                        app.launch_count.to_string(),
+                        app.last_launched.map(|t| t.to_string()).unwrap_or_default(),
+                    ])
+                );
+            }
+        }
+
+        ListFormat::Table => {
+            if apps.is_empty() {
+                println!("No integrated AppImages.");
+                return Ok(());
+            }
+
+            let now = state::current_timestamp();
+            let rows: Vec<Vec<String>> = apps
+                .iter()
+                .map(|app| {
+                    let exists = app.appimage_path.exists();
+                    let mut status = Vec::new();
+                    if !exists {
+                        status.push("missing");
+                    }
+                    if app.pinned {
+                        status.push("pinned");
+                    }
+                    if app.native_duplicate.is_some() {
+                        status.push("duplicate");
+                    }
+                    let size = if exists {
+                        format::human_size(app.file_size())
+                    } else {
+                        "-".to_string()
+                    };
+                    vec![
+                        resolve::display_name(app),
+                        app.version.clone().unwrap_or_default(),
+                        size,
+                        format::days_ago(app.integrated_at, now),
+                        app.last_launched
+                            .map_or_else(|| "-".to_string(), |t| format::days_ago(t, now)),
+                        status.join(","),
+                        app.appimage_path.display().to_string(),
+                    ]
+                })
+                .collect();
+            print!(
+                "{}",
+                format::table(
+                    &[
+                        "NAME",
+                        "VERSION",
+                        "SIZE",
+                        "INTEGRATED",
+                        "LAST USED",
+                        "STATUS",
+                        "PATH"
+                    ],
+                    &rows
+                )
+            );
+        }
+    }
+
+    Ok(())
+}
+
+fn run_list_awaiting() -> Result<(), Box<dyn std::error::Error>> {
     let state = State::load()?;
+    let awaiting = state.awaiting();
+    if awaiting.is_empty() {
+        println!("No AppImages awaiting approval.");
+        return Ok(());
+    }
+
+    println!("Awaiting approval (integrate with `appimage-auto integrate <path>`):");
+    println!();
+    for path in awaiting {
+        println!("  {:?}", path);
+    }
 
-    if state.count() == 0 {
-        println!("No integrated AppImages.");
+    Ok(())
+}
+
+fn run_list_failed() -> Result<(), Box<dyn std::error::Error>> {
+    let state = State::load()?;
+    let mut failures: Vec<_> = state.failures().collect();
+    if failures.is_empty() {
+        println!("No failed integrations.");
         return Ok(());
     }
+    failures.sort_by(|a, b| a.appimage_path.cmp(&b.appimage_path));
 
-    println!("Integrated AppImages:");
+    println!("Failed integrations:");
     println!();
 
-    for app in state.all() {
-        let name = app.name.as_deref().unwrap_or("Unknown");
-        let exists = app.appimage_path.exists();
-        let status = if exists { "" } else { " [MISSING]" };
+    let now = appimage_auto::state::current_timestamp();
+    for failure in failures {
+        let retry = match failure.next_retry_at {
+            Some(at) => format!("retry in {}s", at.saturating_sub(now)),
+            None => "gave up".to_string(),
+        };
 
-        println!("  {} ({}){}", name, app.identifier, status);
-        println!("    Path: {:?}", app.appimage_path);
-        println!("    Desktop: {:?}", app.desktop_path);
+        println!("  {:?}", failure.appimage_path);
+        println!("    Reason: {}", failure.reason);
+        println!("    Attempts: {} ({})", failure.attempts, retry);
         println!();
     }
 
     Ok(())
 }
 
-fn run_integrate(config: Option<Config>, path: &PathBuf) -> Result<(), Box<dyn std::error::Error>> {
+fn run_integrate(
+    config: Option<Config>,
+    path: &PathBuf,
+    force: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
     use appimage_auto::appimage;
 
     if !path.exists() {
@@ -211,7 +970,7 @@ fn run_integrate(config: Option<Config>, path: &PathBuf) -> Result<(), Box<dyn s
     }
 
     if !appimage::is_appimage(path) {
-        return Err(format!("Not a valid AppImage: {:?}", path).into());
+        return Err(AppImageError::NotAppImage(path.display().to_string()).into());
     }
 
     let mut daemon = match config {
@@ -219,66 +978,1147 @@ fn run_integrate(config: Option<Config>, path: &PathBuf) -> Result<(), Box<dyn s
         None => Daemon::new()?,
     };
 
-    daemon.integrate(path)?;
+    if force {
+        daemon.reintegrate(path)?;
+    } else {
+        daemon.integrate(path)?;
+    }
     println!("Successfully integrated: {:?}", path);
 
     Ok(())
 }
 
-fn run_remove(path: &PathBuf) -> Result<(), Box<dyn std::error::Error>> {
-    let config = Config::load()?;
-    let mut daemon = Daemon::with_config(config)?;
-
-    if daemon.state().is_integrated(path) {
-        daemon.unintegrate(path)?;
-        println!("Successfully removed integration for: {:?}", path);
-    } else {
-        println!("AppImage not integrated: {:?}", path);
+fn run_adopt(
+    config: Option<Config>,
+    desktop_file: &Path,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if !desktop_file.exists() {
+        return Err(format!("File not found: {:?}", desktop_file).into());
     }
 
+    let mut daemon = match config {
+        Some(c) => Daemon::with_config(c)?,
+        None => Daemon::new()?,
+    };
+    let path = daemon.adopt(desktop_file)?;
+    println!("Adopted {:?} for {:?}", desktop_file, path);
+
     Ok(())
 }
 
-fn run_config(action: Option<ConfigAction>) -> Result<(), Box<dyn std::error::Error>> {
-    match action {
-        None | Some(ConfigAction::Show) => {
-            let config = Config::load()?;
-            let toml = toml::to_string_pretty(&config)?;
-            println!("{}", toml);
-        }
+fn run_run_once(path: &PathBuf, args: &[OsString]) -> Result<(), Box<dyn std::error::Error>> {
+    use appimage_auto::appimage;
 
-        Some(ConfigAction::Path) => {
-            println!("{:?}", Config::config_path()?);
-        }
+    if !path.exists() {
+        return Err(format!("File not found: {:?}", path).into());
+    }
 
-        Some(ConfigAction::AddWatch { directory }) => {
-            let mut config = Config::load()?;
-            let dir_str = directory.to_string_lossy().to_string();
+    if !appimage::is_appimage(path) {
+        return Err(AppImageError::NotAppImage(path.display().to_string()).into());
+    }
 
-            if config.watch.directories.contains(&dir_str) {
-                println!("Directory already in watch list: {}", dir_str);
-            } else {
-                config.watch.directories.push(dir_str.clone());
-                config.save()?;
-                println!("Added watch directory: {}", dir_str);
-            }
-        }
+    // State and the application menu are left untouched
+    let status = appimage::run_once(path, args)?.wait()?;
+    if !status.success() {
+        std::process::exit(status.code().unwrap_or(1));
+    }
 
-        Some(ConfigAction::RemoveWatch { directory }) => {
-            let mut config = Config::load()?;
-            let dir_str = directory.to_string_lossy().to_string();
+    Ok(())
+}
 
-            let original_len = config.watch.directories.len();
-            config.watch.directories.retain(|d| d != &dir_str);
+fn run_info(query: &str) -> Result<(), Box<dyn std::error::Error>> {
+    use appimage_auto::{appimage, format};
 
-            if config.watch.directories.len() < original_len {
-                config.save()?;
-                println!("Removed watch directory: {}", dir_str);
-            } else {
-                println!("Directory not in watch list: {}", dir_str);
-            }
-        }
-    }
+    let state = State::load()?;
+    let app = resolve_app(&state, query)?;
+    let now = appimage_auto::state::current_timestamp();
+
+    println!("{}", resolve::display_name(app));
+    println!("  Identifier: {}", app.identifier);
+    let missing = if app.appimage_path.exists() {
+        ""
+    } else {
+        " [MISSING]"
+    };
+    println!("  Path:       {:?}{}", app.appimage_path, missing);
+    if let Some(version) = &app.version {
+        println!("  Version:    {}", version);
+    }
+    println!("  Size:       {}", format::human_size(app.file_size()));
+    if let Ok(Some(runtime)) = appimage::runtime_info(&app.appimage_path) {
+        println!(
+            "  Runtime:    {}, {}",
+            runtime.kind.label(),
+            format::human_size(runtime.size)
+        );
+    }
+    if let Some(url) = &app.origin_url {
+        println!("  Source:     {}", url);
+    }
+    if let Some(url) = &app.referrer_url {
+        println!("  Referrer:   {}", url);
+    }
+    if let Some(duplicate) = &app.native_duplicate {
+        println!(
+            "  Duplicate:  of {:?} ({})",
+            duplicate.desktop_path,
+            duplicate.reason.label()
+        );
+    }
+    for (kind, url) in &app.links {
+        println!("  {:<11} {}", format!("{}:", appstream::label(kind)), url);
+    }
+    println!("  Desktop:    {:?}", app.desktop_path);
+    for icon in &app.icon_paths {
+        println!("  Icon:       {:?}", icon);
+    }
+    for entry in &app.autostart_entries {
+        let blocked = if appimage_auto::autostart::is_blocked(entry) {
+            " (disabled)"
+        } else {
+            ""
+        };
+        println!("  Autostart:  {:?}{}", entry, blocked);
+    }
+    println!("  Integrated: {}", format::days_ago(app.integrated_at, now));
+    println!("  Updated:    {}", format::days_ago(app.updated_at, now));
+    println!("  Pinned:     {}", if app.pinned { "yes" } else { "no" });
+    println!(
+        "  Usage:      {}",
+        format::launches(app.launch_count, app.last_launched, now)
+    );
+    if let Some(error) = &app.last_error {
+        println!(
+            "  Last error: {} ({})",
+            error.message,
+            format::days_ago(error.at, now)
+        );
+    }
+    if !app.entry_changes.is_empty() {
+        println!("  Desktop entry changes on last update:");
+        for change in &app.entry_changes {
+            println!("    {}", change);
+        }
+    }
+
+    Ok(())
+}
+
+fn run_diff(old: &Path, new: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    use appimage_auto::compare::{self, Metadata};
+    use appimage_auto::format;
+
+    let old = Metadata::read(old)?;
+    let new = Metadata::read(new)?;
+    let differences = compare::compare(&old, &new);
+    if differences.is_empty() {
+        println!("No differences in metadata");
+        return Ok(());
+    }
+
+    println!("--- {}", old.path.display());
+    println!("+++ {}", new.path.display());
+    if let Some((old, new)) = &differences.version {
+        let unknown = "unknown".to_string();
+        println!(
+            "Version:   {} -> {}",
+            old.as_ref().unwrap_or(&unknown),
+            new.as_ref().unwrap_or(&unknown)
+        );
+    }
+    if let Some((old, new)) = differences.size {
+        println!(
+            "Size:      {} -> {}",
+            format::human_size(old),
+            format::human_size(new)
+        );
+    }
+    if let Some((old, new)) = &differences.signature {
+        println!("Signature: {} -> {}", old, new);
+    }
+    if !differences.entry.is_empty() {
+        println!("Desktop entry:");
+        for change in &differences.entry {
+            println!("  {}", change);
+        }
+    }
+    if !differences.icons_removed.is_empty() || !differences.icons_added.is_empty() {
+        println!("Icons:");
+        for icon in &differences.icons_removed {
+            println!("  -{}", icon);
+        }
+        for icon in &differences.icons_added {
+            println!("  +{}", icon);
+        }
+    }
+
+    Ok(())
+}
+
+fn run_launch(query: &str, args: &[OsString]) -> Result<(), Box<dyn std::error::Error>> {
+    use appimage_auto::appimage;
+
+    let state = State::load()?;
+    let app = resolve_app(&state, query)?;
+    appimage::run_once(&app.appimage_path, args)?;
+    println!("Launched {}", resolve::display_name(app));
+    record_launch(&app.appimage_path);
+
+    Ok(())
+}
+
+/// Count a launch in the state; failures only get logged so the app still starts
+fn record_launch(path: &Path) {
+    let result = State::load().and_then(|mut state| {
+        if state.record_launch(path) {
+            state.save()?;
+        }
+        Ok(())
+    });
+    if let Err(e) = result {
+        error!("Failed to record launch of {:?}: {}", path, e);
+    }
+}
+
+fn run_remove(query: &str, delete: bool, yes: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let config = Config::load()?;
+    let mut daemon = Daemon::with_config(config)?;
+
+    // A file that is not integrated can still be deleted by path
+    let path = match resolve_app(daemon.state(), query) {
+        Ok(app) => app.appimage_path.clone(),
+        Err(_) if Path::new(query).exists() => PathBuf::from(query),
+        Err(e) => return Err(e),
+    };
+    let path = &path;
+
+    // Names match loosely, so only a path is taken as meaning that file
+    let exact = Path::new(query).is_file()
+        && state::normalize_path(Path::new(query)) == state::normalize_path(path);
+    if delete && !exact && !yes {
+        if !std::io::stdin().is_terminal() {
+            return Err(
+                "Refusing to delete without confirmation; pass the path or use --yes".into(),
+            );
+        }
+        print!("Delete {:?}? [y/N] ", path);
+        std::io::stdout().flush()?;
+        let mut answer = String::new();
+        std::io::stdin().read_line(&mut answer)?;
+        if !answer.trim().eq_ignore_ascii_case("y") {
+            println!("Nothing removed.");
+            return Ok(());
+        }
+    }
+
+    let integrated = daemon.state().is_integrated(path);
+    if integrated || delete {
+        daemon.remove(path, delete)?;
+    }
+
+    if integrated {
+        println!("Successfully removed integration for: {:?}", path);
+    } else {
+        println!("AppImage not integrated: {:?}", path);
+    }
+    if delete {
+        println!("Deleted file: {:?}", path);
+    }
 
     Ok(())
 }
+
+fn run_pin(queries: &[String], pinned: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let config = Config::load()?;
+    let mut daemon = Daemon::with_config(config)?;
+
+    for query in queries {
+        let path = match resolve_app(daemon.state(), query) {
+            Ok(app) => app.appimage_path.clone(),
+            Err(e) => {
+                println!("{}", e);
+                continue;
+            }
+        };
+        if daemon.set_pinned(&path, pinned)? {
+            let action = if pinned { "Pinned" } else { "Unpinned" };
+            println!("{}: {:?}", action, path);
+        } else {
+            println!("AppImage not integrated: {:?}", path);
+        }
+    }
+
+    Ok(())
+}
+
+fn run_set_icon(
+    config: Option<Config>,
+    query: &str,
+    icon: Option<&Path>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut daemon = match config {
+        Some(c) => Daemon::with_config(c)?,
+        None => Daemon::new()?,
+    };
+    let app = resolve_app(daemon.state(), query)?;
+    let (name, path) = (resolve::display_name(app), app.appimage_path.clone());
+
+    if let Some(icon) = icon
+        && !icon.is_file()
+    {
+        return Err(format!("File not found: {:?}", icon).into());
+    }
+    daemon.set_icon(&path, icon)?;
+    match icon {
+        Some(icon) => println!("Icon of {} set to {:?}", name, icon),
+        None => println!("{} uses its own icon again", name),
+    }
+
+    Ok(())
+}
+
+fn run_stale(days: u64, remove: bool) -> Result<(), Box<dyn std::error::Error>> {
+    use appimage_auto::format;
+
+    let state = State::load()?;
+    let now = state::current_timestamp();
+    let cutoff = now.saturating_sub(days * 24 * 60 * 60);
+    let mut stale: Vec<IntegratedAppImage> = state
+        .all()
+        .filter(|app| app.last_activity() < cutoff)
+        .cloned()
+        .collect();
+    stale.sort_by_key(|app| app.last_activity());
+
+    if stale.is_empty() {
+        println!("No apps unused for {} days.", days);
+        return Ok(());
+    }
+
+    let rows: Vec<Vec<String>> = stale
+        .iter()
+        .map(|app| {
+            vec![
+                resolve::display_name(app),
+                app.last_launched
+                    .map_or_else(|| "never".to_string(), |t| format::days_ago(t, now)),
+                app.file_modified()
+                    .map_or_else(|| "missing".to_string(), |t| format::days_ago(t, now)),
+                app.appimage_path.display().to_string(),
+            ]
+        })
+        .collect();
+    print!(
+        "{}",
+        format::table(&["NAME", "LAST LAUNCHED", "MODIFIED", "PATH"], &rows)
+    );
+    if stale.iter().all(|app| app.last_launched.is_none()) {
+        println!();
+        println!("No launches recorded; enable `track_launches` to count them.");
+    }
+
+    if !remove {
+        return Ok(());
+    }
+    if !std::io::stdin().is_terminal() {
+        return Err("--remove needs an interactive terminal".into());
+    }
+
+    let mut daemon = Daemon::with_config(Config::load()?)?;
+    println!();
+    for app in &stale {
+        print!("Remove {}? [y/N] ", resolve::display_name(app));
+        std::io::stdout().flush()?;
+        let mut answer = String::new();
+        std::io::stdin().read_line(&mut answer)?;
+        if answer.trim().eq_ignore_ascii_case("y") {
+            daemon.remove(&app.appimage_path, false)?;
+            println!("Removed integration for {:?}", app.appimage_path);
+        }
+    }
+
+    Ok(())
+}
+
+fn run_du(format: ListFormat) -> Result<(), Box<dyn std::error::Error>> {
+    use appimage_auto::format;
+
+    let state = State::load()?;
+    let mut apps: Vec<(&IntegratedAppImage, state::DiskUsage)> =
+        state.all().map(|app| (app, app.disk_usage())).collect();
+    apps.sort_by_key(|(app, usage)| (std::cmp::Reverse(usage.total()), resolve::display_name(app)));
+    let total: state::DiskUsage = apps.iter().map(|(_, usage)| *usage).sum();
+
+    match format {
+        ListFormat::Json => {
+            let apps: Vec<serde_json::Value> = apps
+                .iter()
+                .map(|(app, usage)| {
+                    serde_json::json!({
+                        "identifier": app.identifier,
+                        "name": resolve::display_name(app),
+                        "path": app.appimage_path,
+                        "appimage": usage.appimage,
+                        "icons": usage.icons,
+                        "desktop": usage.desktop,
+                        "total": usage.total(),
+                    })
+                })
+                .collect();
+            let report = serde_json::json!({
+                "apps": apps,
+                "appimage": total.appimage,
+                "icons": total.icons,
+                "desktop": total.desktop,
+                "total": total.total(),
+            });
+            println!("{}", serde_json::to_string_pretty(&report)?);
+        }
+
+        ListFormat::Csv => {
+            println!(
+                "{}",
+                format::csv_row(&[
+                    "identifier",
+                    "name",
+                    "path",
+                    "appimage",
+                    "icons",
+                    "desktop",
+                    "total",
+                ])
+            );
+            for (app, usage) in &apps {
+                println!(
+                    "{}",
+                    format::csv_row(&[
+                        app.identifier.clone(),
+                        resolve::display_name(app),
+                        app.appimage_path.display().to_string(),
+                        usage.appimage.to_string(),
+                        usage.icons.to_string(),
+                        usage.desktop.to_string(),
+                        usage.total().to_string(),
+                    ])
+                );
+            }
+        }
+
+        ListFormat::Table => {
+            if apps.is_empty() {
+                println!("No integrated AppImages.");
+                return Ok(());
+            }
+
+            let row = |name: String, usage: &state::DiskUsage| {
+                vec![
+                    name,
+                    format::human_size(usage.appimage),
+                    format::human_size(usage.icons),
+                    format::human_size(usage.desktop),
+                    format::human_size(usage.total()),
+                ]
+            };
+            let mut rows: Vec<Vec<String>> = apps
+                .iter()
+                .map(|(app, usage)| row(resolve::display_name(app), usage))
+                .collect();
+            rows.push(row("TOTAL".to_string(), &total));
+            print!(
+                "{}",
+                format::table(&["NAME", "APPIMAGE", "ICONS", "DESKTOP", "TOTAL"], &rows)
+            );
+        }
+    }
+
+    Ok(())
+}
+
+fn run_prune(config: Option<Config>, yes: bool) -> Result<(), Box<dyn std::error::Error>> {
+    use appimage_auto::{desktop, maintenance};
+
+    let config = match config {
+        Some(c) => c,
+        None => Config::load()?,
+    };
+    let state = State::load()?;
+    let mut desktop_dirs = vec![config.desktop_directory()];
+    desktop_dirs.extend(config.extra_desktop_directories());
+    let mut icon_dirs = vec![config.icon_directory()];
+    icon_dirs.extend(config.extra_icon_directories());
+    let leftovers = maintenance::find_leftovers(&state, &desktop_dirs, &icon_dirs)?;
+
+    if leftovers.is_empty() {
+        println!("No leftover files found.");
+        return Ok(());
+    }
+
+    println!("Files not belonging to any integrated AppImage:");
+    for path in leftovers.paths() {
+        println!("  {:?}", path);
+    }
+
+    if !yes {
+        if !std::io::stdin().is_terminal() {
+            return Err("Refusing to remove files without confirmation; use --yes".into());
+        }
+        print!("Remove {} file(s)? [y/N] ", leftovers.len());
+        std::io::stdout().flush()?;
+        let mut answer = String::new();
+        std::io::stdin().read_line(&mut answer)?;
+        if !answer.trim().eq_ignore_ascii_case("y") {
+            println!("Nothing removed.");
+            return Ok(());
+        }
+    }
+
+    let failed = maintenance::remove_leftovers(&leftovers);
+    for (path, e) in &failed {
+        println!("Failed to remove {:?}: {}", path, e);
+    }
+    if !leftovers.desktop_files.is_empty() && config.integration.update_database {
+        for dir in &desktop_dirs {
+            desktop::update_desktop_database(dir)?;
+        }
+    }
+    println!("Removed {} file(s)", leftovers.len() - failed.len());
+
+    Ok(())
+}
+
+fn run_check(
+    config: Option<Config>,
+    query: Option<&str>,
+    repair: bool,
+    adopt: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    use appimage_auto::integrity;
+
+    let mut daemon = match config {
+        Some(c) => Daemon::with_config(c)?,
+        None => Daemon::new()?,
+    };
+
+    let mut apps: Vec<IntegratedAppImage> = match query {
+        Some(query) => vec![resolve_app(daemon.state(), query)?.clone()],
+        None => daemon.state().all().cloned().collect(),
+    };
+    state::sort_apps(&mut apps, AppSort::Name);
+    let drifted: Vec<(String, PathBuf, Vec<integrity::Drift>)> = apps
+        .iter()
+        .map(|app| {
+            let drift = integrity::check(app);
+            (resolve::display_name(app), app.appimage_path.clone(), drift)
+        })
+        .filter(|(_, _, drift)| !drift.is_empty())
+        .collect();
+
+    if drifted.is_empty() {
+        println!("All desktop entries and icons are as written.");
+        return Ok(());
+    }
+
+    for (name, path, drift) in &drifted {
+        println!("{} ({:?})", name, path);
+        for drift in drift {
+            println!("  {}", drift);
+        }
+    }
+
+    if repair {
+        for (name, path, _) in &drifted {
+            match daemon.reintegrate(path) {
+                Ok(()) => println!("Repaired: {}", name),
+                Err(e) => println!("Failed to repair {}: {}", name, e),
+            }
+        }
+    } else if adopt {
+        for (name, path, _) in &drifted {
+            daemon.adopt_changes(path)?;
+            println!("Adopted changes: {}", name);
+        }
+    } else {
+        println!();
+        println!("Run with --repair to write them anew, or --adopt to keep the changes.");
+        std::process::exit(1);
+    }
+
+    Ok(())
+}
+
+fn run_audit(query: Option<&str>, format: ListFormat) -> Result<(), Box<dyn std::error::Error>> {
+    use appimage_auto::audit;
+    use appimage_auto::format;
+
+    let state = State::load()?;
+    let mut apps: Vec<IntegratedAppImage> = match query {
+        Some(query) => vec![resolve_app(&state, query)?.clone()],
+        None => state.all().cloned().collect(),
+    };
+    state::sort_apps(&mut apps, AppSort::Name);
+    let reports: Vec<audit::AppAudit> = apps.iter().map(audit::audit).collect();
+
+    match format {
+        ListFormat::Json => println!("{}", serde_json::to_string_pretty(&reports)?),
+
+        ListFormat::Csv => {
+            println!(
+                "{}",
+                format::csv_row(&[
+                    "identifier",
+                    "name",
+                    "path",
+                    "signature",
+                    "trusted",
+                    "origin_url",
+                    "file",
+                    "notable_keys",
+                    "url_handlers",
+                    "autostart_entries",
+                    "added_autostart",
+                ])
+            );
+            for report in &reports {
+                println!(
+                    "{}",
+                    format::csv_row(&[
+                        report.identifier.clone(),
+                        report.name.clone(),
+                        report.path.display().to_string(),
+                        report.signature.clone(),
+                        report.trusted.to_string(),
+                        report.origin_url.clone().unwrap_or_default(),
+                        report.file.to_string(),
+                        report.notable_keys.join(" "),
+                        report.url_handlers.join(" "),
+                        report
+                            .autostart_entries
+                            .as_ref()
+                            .map(|entries| entries.join(" "))
+                            .unwrap_or_default(),
+                        report
+                            .added_autostart
+                            .iter()
+                            .map(|entry| entry.path.display().to_string())
+                            .collect::<Vec<_>>()
+                            .join(" "),
+                    ])
+                );
+            }
+        }
+
+        ListFormat::Table => {
+            if reports.is_empty() {
+                println!("No integrated AppImages.");
+                return Ok(());
+            }
+
+            for (i, report) in reports.iter().enumerate() {
+                if i > 0 {
+                    println!();
+                }
+                println!("{} ({:?})", report.name, report.path);
+                println!("  Signature:  {}", report.signature);
+                println!(
+                    "  Origin:     {}",
+                    report.origin_url.as_deref().unwrap_or("unknown")
+                );
+                println!("  File:       {}", report.file);
+                if !report.notable_keys.is_empty() {
+                    println!("  Desktop:    {}", report.notable_keys.join(", "));
+                }
+                if !report.url_handlers.is_empty() {
+                    println!("  Opens:      {} links", report.url_handlers.join(", "));
+                }
+                match &report.autostart_entries {
+                    Some(entries) if entries.is_empty() => {}
+                    Some(entries) => println!("  Autostart:  {}", entries.join(", ")),
+                    None => println!("  Autostart:  not checked (image can't be listed)"),
+                }
+                for entry in &report.added_autostart {
+                    let blocked = if entry.blocked { " (disabled)" } else { "" };
+                    println!("  Added:      {:?}{}", entry.path, blocked);
+                }
+            }
+
+            let flagged = reports.iter().filter(|r| !r.concerns().is_empty()).count();
+            println!();
+            println!(
+                "{} of {} AppImage(s) have findings worth a look.",
+                flagged,
+                reports.len()
+            );
+        }
+    }
+
+    Ok(())
+}
+
+fn run_state(
+    config: Option<Config>,
+    action: StateAction,
+) -> Result<(), Box<dyn std::error::Error>> {
+    use appimage_auto::maintenance;
+
+    let config = match config {
+        Some(c) => c,
+        None => Config::load()?,
+    };
+
+    match action {
+        StateAction::Rebuild => {
+            let mut state =
+                maintenance::rebuild_state(&config.desktop_directory(), &config.icon_directory())?;
+
+            // Keep the old file, it may still hold pins and failures
+            let path = State::state_path()?;
+            if path.exists() {
+                let backup = path.with_extension("json.bak");
+                std::fs::rename(&path, &backup)?;
+                println!("Previous state moved to {:?}", backup);
+            }
+
+            state.save()?;
+            println!(
+                "Rebuilt state with {} integrated AppImage(s): {:?}",
+                state.count(),
+                path
+            );
+        }
+    }
+
+    Ok(())
+}
+
+fn run_backup(config: Option<Config>, archive: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let config = match config {
+        Some(c) => c,
+        None => Config::load()?,
+    };
+
+    let summary = backup::create_backup(archive, &config)?;
+    println!(
+        "Backed up {} integrated AppImage(s) and {} file(s) to {:?}",
+        summary.apps, summary.files, archive
+    );
+    println!("The AppImages themselves are not included.");
+
+    Ok(())
+}
+
+fn run_export_menu(
+    config: Option<Config>,
+    prefix: &Path,
+    appimage_dir: Option<&Path>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    use appimage_auto::export;
+
+    let config = match config {
+        Some(c) => c,
+        None => Config::load()?,
+    };
+
+    let state = State::load()?;
+    let summary = export::export_menu(&state, &config.icon_directory(), prefix, appimage_dir)?;
+    println!(
+        "Exported {} desktop entries and {} icon(s) to {:?}",
+        summary.apps, summary.icons, prefix
+    );
+    println!(
+        "Add {:?} to XDG_DATA_DIRS where the menu is used.",
+        prefix.join("share")
+    );
+
+    Ok(())
+}
+
+fn run_restore(config: Option<Config>, archive: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    use appimage_auto::desktop;
+
+    let config = match config {
+        Some(c) => c,
+        None => Config::load()?,
+    };
+
+    let summary = backup::restore_backup(archive, &config)?;
+    if config.integration.update_database {
+        desktop::update_desktop_database(&config.desktop_directory())?;
+    }
+    println!(
+        "Restored {} integrated AppImage(s) and {} file(s) from {:?}",
+        summary.apps, summary.files, archive
+    );
+
+    let missing = State::load()?
+        .all()
+        .filter(|app| !app.appimage_path.exists())
+        .count();
+    if missing > 0 {
+        println!(
+            "{} AppImage(s) are not present at their recorded paths; copy them over or run 'prune'.",
+            missing
+        );
+    }
+
+    Ok(())
+}
+
+/// Resolve a name, file name, identifier prefix or path to an integrated
+/// AppImage, asking which one was meant when several match
+fn resolve_app<'a>(
+    state: &'a State,
+    query: &str,
+) -> Result<&'a IntegratedAppImage, Box<dyn std::error::Error>> {
+    match resolve::resolve(state, query) {
+        Err(ResolveError::Ambiguous { .. }) if std::io::stdin().is_terminal() => {
+            let apps = resolve::matches(state, query);
+            println!("'{}' matches several AppImages:", query);
+            for (i, app) in apps.iter().enumerate() {
+                println!(
+                    "  {}) {} ({:?})",
+                    i + 1,
+                    resolve::display_name(app),
+                    app.appimage_path
+                );
+            }
+            print!("Choose one [1-{}]: ", apps.len());
+            std::io::stdout().flush()?;
+
+            let mut answer = String::new();
+            std::io::stdin().read_line(&mut answer)?;
+            answer
+                .trim()
+                .parse::<usize>()
+                .ok()
+                .and_then(|n| apps.get(n.wrapping_sub(1)).copied())
+                .ok_or_else(|| "No AppImage chosen".into())
+        }
+        Err(ResolveError::Ambiguous { query, matches }) => {
+            let paths: Vec<String> = matches.iter().map(|p| format!("  {:?}", p)).collect();
+            Err(format!(
+                "'{}' matches several AppImages, be more specific:\n{}",
+                query,
+                paths.join("\n")
+            )
+            .into())
+        }
+        result => Ok(result?),
+    }
+}
+
+fn run_trust(action: TrustAction) -> Result<(), Box<dyn std::error::Error>> {
+    use appimage_auto::appimage;
+    use appimage_auto::signature;
+
+    match action {
+        TrustAction::Add { key } => {
+            let path = Path::new(&key);
+            let fingerprints = if !path.is_file() {
+                signature::import_user_key(&key)?
+            } else if appimage::is_appimage(path) {
+                let embedded = signature::embedded_key(path)?
+                    .ok_or_else(|| format!("{:?} has no embedded signing key", path))?;
+                signature::import_key(embedded.as_bytes())?
+            } else {
+                signature::import_key(&std::fs::read(path)?)?
+            };
+            for fingerprint in fingerprints {
+                println!("Trusted key {}", fingerprint);
+            }
+        }
+
+        TrustAction::Remove { key } => {
+            if signature::forget_app_key(&key)? {
+                println!("Forgot the key trusted for {}", key);
+            } else {
+                let removed = signature::remove_key(&key)?;
+                println!("Removed key {}", removed.fingerprint);
+            }
+        }
+
+        TrustAction::List => {
+            let keys = signature::list_keys()?;
+            if keys.is_empty() {
+                println!("No trusted keys");
+            }
+            for key in keys {
+                println!(
+                    "{}  {}",
+                    key.fingerprint,
+                    key.user_id.as_deref().unwrap_or("")
+                );
+            }
+            let app_keys = signature::app_keys()?;
+            if !app_keys.is_empty() {
+                println!();
+                println!("Trusted on first use, for one app each:");
+                for (app, fingerprint) in app_keys {
+                    println!("{}  {}", fingerprint, app);
+                }
+            }
+        }
+
+        TrustAction::Policy {
+            verify,
+            trust_on_first_use,
+            no_trust_on_first_use,
+        } => {
+            let mut config = Config::load()?;
+            if let Some(verify) = verify {
+                config.signatures.verify = verify.into();
+            }
+            if trust_on_first_use || no_trust_on_first_use {
+                config.signatures.trust_on_first_use = trust_on_first_use;
+            }
+            if verify.is_some() || trust_on_first_use || no_trust_on_first_use {
+                config.save()?;
+            }
+            let policy = match config.signatures.verify {
+                SignaturePolicy::Off => "off",
+                SignaturePolicy::Warn => "warn",
+                SignaturePolicy::Require => "require",
+            };
+            println!("Verify:             {}", policy);
+            println!(
+                "Trust on first use: {}",
+                if config.signatures.trust_on_first_use {
+                    "yes"
+                } else {
+                    "no"
+                }
+            );
+        }
+    }
+
+    Ok(())
+}
+
+fn run_apparmor(action: AppArmorAction) -> Result<(), Box<dyn std::error::Error>> {
+    use appimage_auto::apparmor;
+
+    let state = State::load()?;
+    let config = Config::load()?;
+    let query = match &action {
+        AppArmorAction::Show { app }
+        | AppArmorAction::Install { app }
+        | AppArmorAction::Unload { app } => app,
+    };
+    let app = resolve_app(&state, query)?;
+    let network = config
+        .app_profile(&app.identifier, app.name.as_deref())
+        .is_none_or(|p| p.network);
+
+    match action {
+        AppArmorAction::Show { .. } => {
+            print!(
+                "{}",
+                apparmor::render_profile(
+                    &app.identifier,
+                    app.name.as_deref(),
+                    &app.appimage_path,
+                    network
+                )?
+            );
+        }
+
+        AppArmorAction::Install { .. } => {
+            let path = apparmor::write_template(
+                &app.identifier,
+                app.name.as_deref(),
+                &app.appimage_path,
+                network,
+            )?;
+            apparmor::load(&path)?;
+            println!(
+                "Loaded {} for {}",
+                apparmor::profile_name(&app.identifier),
+                resolve::display_name(app)
+            );
+        }
+
+        AppArmorAction::Unload { .. } => {
+            apparmor::unload(&apparmor::profile_path(&app.identifier)?)?;
+            println!(
+                "Unloaded {} for {}",
+                apparmor::profile_name(&app.identifier),
+                resolve::display_name(app)
+            );
+        }
+    }
+
+    Ok(())
+}
+
+fn run_config(action: Option<ConfigAction>) -> Result<(), Box<dyn std::error::Error>> {
+    match action {
+        None | Some(ConfigAction::Show) => {
+            let config = Config::load()?;
+            let toml = toml::to_string_pretty(&config)?;
+            println!("{}", toml);
+        }
+
+        Some(ConfigAction::Path) => {
+            println!("{:?}", Config::config_path()?);
+            let drop_ins = Config::drop_in_path()?;
+            if drop_ins.is_dir() {
+                println!("{:?}", drop_ins);
+            }
+        }
+
+        Some(ConfigAction::AddWatch { directory }) => {
+            let mut config = Config::load()?;
+            let dir_str = directory.to_string_lossy().to_string();
+
+            if config.watch.directories.contains(&dir_str) {
+                println!("Directory already in watch list: {}", dir_str);
+            } else {
+                config.watch.directories.push(dir_str.clone());
+                config.save()?;
+                println!("Added watch directory: {}", dir_str);
+            }
+        }
+
+        Some(ConfigAction::RemoveWatch { directory }) => {
+            let mut config = Config::load()?;
+            let dir_str = directory.to_string_lossy().to_string();
+
+            let original_len = config.watch.directories.len();
+            config.watch.directories.retain(|d| d != &dir_str);
+
+            if config.watch_directory_from_drop_in(&dir_str) {
+                println!(
+                    "{} is added by a file in {:?}, remove it there",
+                    dir_str,
+                    Config::drop_in_path()?
+                );
+            } else if config.watch.directories.len() < original_len {
+                config.save()?;
+                println!("Removed watch directory: {}", dir_str);
+            } else {
+                println!("Directory not in watch list: {}", dir_str);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn run_service(action: ServiceAction) -> Result<(), Box<dyn std::error::Error>> {
+    use appimage_auto::service;
+
+    match action {
+        ServiceAction::Install => {
+            let exec = std::env::current_exe()?;
+            let path = service::install(&exec)?;
+            println!("Installed and started {} ({:?})", service::UNIT_NAME, path);
+        }
+
+        ServiceAction::Uninstall => {
+            service::uninstall()?;
+            println!("Removed {}", service::UNIT_NAME);
+        }
+
+        ServiceAction::Status => {
+            let status = service::status()?;
+            let yes_no = |b: bool| if b { "yes" } else { "no" };
+            println!("Unit:      {:?}", service::unit_path()?);
+            println!("Installed: {}", yes_no(status.installed));
+            println!("Enabled:   {}", yes_no(status.enabled));
+            println!("Active:    {}", yes_no(status.active));
+        }
+    }
+
+    Ok(())
+}
+
+fn run_setup(action: SetupAction) -> Result<(), Box<dyn std::error::Error>> {
+    use appimage_auto::setup;
+
+    match action {
+        SetupAction::Mime { remove: true } => {
+            setup::uninstall_mime()?;
+            println!("Removed the AppImage MIME registration");
+        }
+
+        SetupAction::Mime { remove: false } => {
+            let gui = gui_binary();
+            let result = setup::install_mime(gui.as_deref())?;
+            println!(
+                "Registered {} ({:?})",
+                setup::MIME_TYPES.join(", "),
+                result.package
+            );
+            if result.associated {
+                println!("AppImages now open with {}", setup::GUI_DESKTOP_ID);
+            } else {
+                println!("appimage-auto-gui not found; AppImages will not open with the GUI");
+            }
+        }
+
+        SetupAction::FileManager { remove: true } => {
+            let removed = setup::uninstall_file_manager()?;
+            if removed.is_empty() {
+                println!("No file manager context menu entries installed");
+            }
+            for path in removed {
+                println!("Removed {:?}", path);
+            }
+        }
+
+        SetupAction::FileManager { remove: false } => {
+            let cli = std::env::current_exe()?;
+            for path in setup::install_file_manager(&cli)? {
+                println!("Installed {:?}", path);
+            }
+            println!(
+                "Right-click an AppImage in Dolphin or Nautilus (Scripts submenu) to integrate it"
+            );
+        }
+    }
+
+    Ok(())
+}
+
+fn run_gui(files: &[PathBuf]) -> Result<(), Box<dyn std::error::Error>> {
+    use std::os::unix::process::CommandExt;
+
+    // GTK hands the files to an already running instance over D-Bus and exits
+    let gui = gui_binary().unwrap_or_else(|| PathBuf::from(GUI_BINARY));
+    let error = std::process::Command::new(&gui).args(files).exec();
+    if error.kind() == std::io::ErrorKind::NotFound {
+        return Err(format!(
+            "{} not found; install it or build with `--features gui`",
+            GUI_BINARY
+        )
+        .into());
+    }
+    Err(format!("Failed to start {:?}: {}", gui, error).into())
+}
+
+/// The GUI binary installed next to the CLI, if there is one
+fn gui_binary() -> Option<PathBuf> {
+    let gui = std::env::current_exe().ok()?.with_file_name(GUI_BINARY);
+    gui.exists().then_some(gui)
+}
+
+/// Open `logging.file` of the config for appending, if one is set
+fn open_log_file(config_path: Option<&Path>) -> Option<std::fs::File> {
+    use appimage_auto::logs::LogSource;
+
+    let config = match config_path {
+        Some(path) => Config::load_from(path).ok()?,
+        None => Config::load().ok()?,
+    };
+    let LogSource::File(path) = LogSource::from_config(&config) else {
+        return None;
+    };
+    if let Some(parent) = path.parent() {
+        let _ = std::fs::create_dir_all(parent);
+    }
+    std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .inspect_err(|e| eprintln!("Failed to open log file {:?}: {}", path, e))
+        .ok()
+}
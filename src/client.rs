@@ -0,0 +1,358 @@
+//! Typed client for the daemon's control socket.
+//!
+//! Wraps the line protocol of [`crate::control`] in async methods for the
+//! GUI and third-party tools. When no daemon is listening, [`Client::connect`]
+//! falls back to direct mode: requests are carried out in-process with the
+//! library, as the CLI does, on a blocking task. Only a running daemon has a
+//! status and history events to subscribe to.
+//!
+//! The mode is checked again when a request fails: a daemon that stopped
+//! since is no longer sent requests, and one that started since is asked for
+//! its status and events.
+
+use crate::control::{self, ControlCommand, ControlError, ControlStatus};
+use crate::daemon::{Daemon, DaemonError};
+use crate::history::HistoryEntry;
+use crate::state::{IntegratedAppImage, State, StateError};
+use serde::de::DeserializeOwned;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, PoisonError};
+use thiserror::Error;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, Lines};
+use tokio::net::UnixStream;
+use tokio::net::unix::OwnedReadHalf;
+
+#[derive(Error, Debug)]
+pub enum ClientError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("JSON error: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("Control socket error: {0}")]
+    Control(#[from] ControlError),
+    #[error("{0}")]
+    Daemon(#[from] DaemonError),
+    #[error("State error: {0}")]
+    State(#[from] StateError),
+    #[error("Daemon error: {0}")]
+    Rejected(String),
+    #[error("The daemon is not running")]
+    NotRunning,
+    #[error("Task failed: {0}")]
+    Task(#[from] tokio::task::JoinError),
+}
+
+/// How a [`Client`] carries out requests
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    /// Sent to the running daemon
+    Daemon,
+    /// Done in this process with the library
+    Direct,
+}
+
+/// Client for the daemon, or the library when the daemon is down
+#[derive(Debug, Clone)]
+pub struct Client {
+    socket: PathBuf,
+    /// Shared by clones, so they all notice the daemon stop or start
+    mode: Arc<Mutex<Mode>>,
+}
+
+impl Client {
+    /// Connect to the daemon at the default socket, falling back to direct
+    /// mode when it isn't running
+    pub async fn connect() -> Result<Self, ClientError> {
+        Ok(Self::connect_to(control::socket_path()?).await)
+    }
+
+    /// Connect to the daemon at `socket`, falling back to direct mode when
+    /// nothing listens there
+    pub async fn connect_to(socket: PathBuf) -> Self {
+        let mode = probe(&socket).await;
+        Self {
+            socket,
+            mode: Arc::new(Mutex::new(mode)),
+        }
+    }
+
+    pub fn mode(&self) -> Mode {
+        *self.mode.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn set_mode(&self, mode: Mode) {
+        *self.mode.lock().unwrap_or_else(PoisonError::into_inner) = mode;
+    }
+
+    /// The daemon's status
+    pub async fn status(&self) -> Result<ControlStatus, ClientError> {
+        self.require_daemon().await?;
+        self.request(ControlCommand::Status).await
+    }
+
+    /// All integrated AppImages
+    pub async fn list(&self) -> Result<Vec<IntegratedAppImage>, ClientError> {
+        if self.mode() == Mode::Daemon {
+            match self.request(ControlCommand::List).await {
+                Err(ClientError::NotRunning) => {}
+                result => return result,
+            }
+        }
+        let state = tokio::task::spawn_blocking(State::load).await??;
+        Ok(state.all().cloned().collect())
+    }
+
+    /// Integrate an AppImage
+    pub async fn integrate(&self, path: &Path) -> Result<(), ClientError> {
+        if self.mode() == Mode::Daemon {
+            match self
+                .acknowledge(ControlCommand::Integrate(path.into()))
+                .await
+            {
+                Err(ClientError::NotRunning) => {}
+                result => return result,
+            }
+        }
+        let path = path.to_path_buf();
+        direct(move |daemon| daemon.integrate(&path)).await
+    }
+
+    /// Integrate an AppImage again from scratch
+    pub async fn reintegrate(&self, path: &Path) -> Result<(), ClientError> {
+        if self.mode() == Mode::Daemon {
+            match self
+                .acknowledge(ControlCommand::Reintegrate(path.into()))
+                .await
+            {
+                Err(ClientError::NotRunning) => {}
+                result => return result,
+            }
+        }
+        let path = path.to_path_buf();
+        direct(move |daemon| daemon.reintegrate(&path)).await
+    }
+
+    /// Remove an AppImage's integration, keeping the file
+    pub async fn remove(&self, path: &Path) -> Result<(), ClientError> {
+        if self.mode() == Mode::Daemon {
+            match self.acknowledge(ControlCommand::Remove(path.into())).await {
+                Err(ClientError::NotRunning) => {}
+                result => return result,
+            }
+        }
+        let path = path.to_path_buf();
+        direct(move |daemon| daemon.remove(&path, false)).await
+    }
+
+    /// Remove an AppImage's integration and delete the file
+    pub async fn remove_and_delete(&self, path: &Path) -> Result<(), ClientError> {
+        if self.mode() == Mode::Daemon {
+            match self
+                .acknowledge(ControlCommand::RemoveAndDelete(path.into()))
+                .await
+            {
+                Err(ClientError::NotRunning) => {}
+                result => return result,
+            }
+        }
+        let path = path.to_path_buf();
+        direct(move |daemon| daemon.remove(&path, true)).await
+    }
+
+    /// Scan the watched directories again. Returns the number of integrated
+    /// AppImages afterwards.
+    pub async fn rescan(&self) -> Result<usize, ClientError> {
+        if self.mode() == Mode::Daemon {
+            match self
+                .request::<serde_json::Value>(ControlCommand::Scan)
+                .await
+            {
+                Err(ClientError::NotRunning) => {}
+                result => return Ok(result?["integrated"].as_u64().unwrap_or_default() as usize),
+            }
+        }
+        tokio::task::spawn_blocking(|| {
+            let mut daemon = Daemon::new()?;
+            daemon.scan_existing()?;
+            Ok::<_, ClientError>(daemon.state().count())
+        })
+        .await?
+    }
+
+    /// Receive the daemon's history events as they happen
+    pub async fn subscribe(&self) -> Result<Subscription, ClientError> {
+        self.require_daemon().await?;
+        let read = self.send(ControlCommand::Subscribe).await?;
+        Ok(Subscription {
+            lines: BufReader::new(read).lines(),
+        })
+    }
+
+    /// Fail with [`ClientError::NotRunning`] in direct mode, unless the
+    /// daemon has started since
+    async fn require_daemon(&self) -> Result<(), ClientError> {
+        if self.mode() == Mode::Direct {
+            self.set_mode(probe(&self.socket).await);
+        }
+        match self.mode() {
+            Mode::Daemon => Ok(()),
+            Mode::Direct => Err(ClientError::NotRunning),
+        }
+    }
+
+    /// Send a command, returning the connection to read the answer from.
+    /// Switches to direct mode and fails with [`ClientError::NotRunning`]
+    /// when the daemon has stopped.
+    async fn send(&self, command: ControlCommand) -> Result<OwnedReadHalf, ClientError> {
+        let stream = match UnixStream::connect(&self.socket).await {
+            Ok(stream) => stream,
+            Err(e) if is_not_listening(&e) => {
+                self.set_mode(Mode::Direct);
+                return Err(ClientError::NotRunning);
+            }
+            Err(e) => return Err(e.into()),
+        };
+        let (read, mut write) = stream.into_split();
+        write
+            .write_all(format!("{}\n", command.to_line()).as_bytes())
+            .await?;
+        Ok(read)
+    }
+
+    /// Send a command and parse the daemon's answer
+    async fn request<T: DeserializeOwned>(
+        &self,
+        command: ControlCommand,
+    ) -> Result<T, ClientError> {
+        let read = self.send(command).await?;
+        let mut lines = BufReader::new(read).lines();
+        let line = tokio::time::timeout(control::REPLY_TIMEOUT, lines.next_line())
+            .await
+            .map_err(|_| std::io::Error::from(std::io::ErrorKind::TimedOut))??
+            .ok_or_else(|| std::io::Error::from(std::io::ErrorKind::UnexpectedEof))?;
+
+        let value: serde_json::Value = serde_json::from_str(&line)?;
+        if let Some(error) = value.get("error").and_then(|e| e.as_str()) {
+            return Err(ClientError::Rejected(error.to_string()));
+        }
+        Ok(serde_json::from_value(value)?)
+    }
+
+    /// Send a command answered with `{"ok": true}` or an error
+    async fn acknowledge(&self, command: ControlCommand) -> Result<(), ClientError> {
+        let _: serde_json::Value = self.request(command).await?;
+        Ok(())
+    }
+}
+
+/// How requests can be carried out, depending on whether a daemon listens
+/// at `socket`
+async fn probe(socket: &Path) -> Mode {
+    match UnixStream::connect(socket).await {
+        Ok(_) => Mode::Daemon,
+        Err(_) => Mode::Direct,
+    }
+}
+
+/// Whether connecting failed because no daemon listens on the socket
+fn is_not_listening(error: &std::io::Error) -> bool {
+    matches!(
+        error.kind(),
+        std::io::ErrorKind::NotFound | std::io::ErrorKind::ConnectionRefused
+    )
+}
+
+/// Run a request against an in-process daemon on a blocking task
+async fn direct<F>(request: F) -> Result<(), ClientError>
+where
+    F: FnOnce(&mut Daemon) -> Result<(), DaemonError> + Send + 'static,
+{
+    tokio::task::spawn_blocking(move || {
+        let mut daemon = Daemon::new()?;
+        request(&mut daemon)?;
+        Ok::<_, ClientError>(())
+    })
+    .await?
+}
+
+/// History events from the daemon, see [`Client::subscribe`]
+pub struct Subscription {
+    lines: Lines<BufReader<OwnedReadHalf>>,
+}
+
+impl Subscription {
+    /// Wait for the next event. `None` when the daemon stopped.
+    pub async fn next(&mut self) -> Result<Option<HistoryEntry>, ClientError> {
+        match self.lines.next_line().await? {
+            Some(line) => Ok(Some(serde_json::from_str(&line)?)),
+            None => Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::history::HistoryKind;
+    use tokio::net::UnixListener;
+
+    #[tokio::test]
+    async fn test_client_round_trip() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let socket = dir.path().join("appimage-auto.sock");
+        assert_eq!(
+            Client::connect_to(socket.clone()).await.mode(),
+            Mode::Direct
+        );
+
+        // Stands in for the daemon: answers `remove` with an error and
+        // sends one event to a subscriber
+        let listener = UnixListener::bind(&socket).unwrap();
+        let server = tokio::spawn(async move {
+            let _probe = listener.accept().await.unwrap();
+            for _ in 0..2 {
+                let (stream, _) = listener.accept().await.unwrap();
+                let (read, mut write) = stream.into_split();
+                let line = BufReader::new(read).lines().next_line().await.unwrap();
+                let answer = match ControlCommand::parse(&line.unwrap()) {
+                    Some(ControlCommand::Remove(_)) => {
+                        serde_json::json!({ "error": "not integrated" })
+                    }
+                    Some(ControlCommand::Subscribe) => serde_json::to_value(HistoryEntry::new(
+                        HistoryKind::Integrated,
+                        "0123",
+                        Some("App"),
+                        Path::new("/apps/App.AppImage"),
+                    ))
+                    .unwrap(),
+                    other => panic!("unexpected command {:?}", other),
+                };
+                write
+                    .write_all(format!("{}\n", answer).as_bytes())
+                    .await
+                    .unwrap();
+            }
+        });
+
+        let client = Client::connect_to(socket).await;
+        assert_eq!(client.mode(), Mode::Daemon);
+        match client.remove(Path::new("/apps/App.AppImage")).await {
+            Err(ClientError::Rejected(error)) => assert_eq!(error, "not integrated"),
+            other => panic!("unexpected answer {:?}", other),
+        }
+
+        let mut events = client.subscribe().await.unwrap();
+        let event = events.next().await.unwrap().unwrap();
+        assert_eq!(event.kind, HistoryKind::Integrated);
+        assert_eq!(event.identifier, "0123");
+        server.await.unwrap();
+        assert!(events.next().await.unwrap().is_none());
+
+        // The daemon stopped: the client notices on the next request
+        assert!(matches!(
+            client.status().await,
+            Err(ClientError::NotRunning)
+        ));
+        assert_eq!(client.mode(), Mode::Direct);
+    }
+}
@@ -0,0 +1,141 @@
+//! Comparing the embedded metadata of two AppImages.
+//!
+//! Used by `appimage-auto diff` to check a new download against the
+//! integrated version before replacing it.
+
+use crate::appimage::{self, AppImageError};
+use crate::desktop::{self, EntryChange};
+use crate::signature::{self, Verification};
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Metadata of one AppImage that is compared
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Metadata {
+    pub path: PathBuf,
+    pub size: u64,
+    pub version: Option<String>,
+    /// Contents of the embedded desktop entry
+    pub desktop_entry: String,
+    /// Icon paths inside the image
+    pub icons: BTreeSet<String>,
+    /// Signature status, or why it could not be checked
+    pub signature: String,
+}
+
+impl Metadata {
+    /// Extract the metadata of an AppImage (into a temporary directory)
+    pub fn read(path: &Path) -> Result<Self, AppImageError> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let info = appimage::extract_metadata(path, temp_dir.path())?;
+        let desktop_entry = match &info.desktop_file {
+            Some(file) => fs::read_to_string(file)?,
+            None => String::new(),
+        };
+        let icons = info
+            .icon_files
+            .iter()
+            .filter_map(|icon| icon.strip_prefix(temp_dir.path()).ok())
+            .map(|icon| icon.to_string_lossy().to_string())
+            .collect();
+        let signature = match signature::verify(path) {
+            Ok(verification) => signature_label(&verification),
+            Err(e) => format!("not checked ({})", e),
+        };
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            size: fs::metadata(path)?.len(),
+            version: info.version,
+            desktop_entry,
+            icons,
+            signature,
+        })
+    }
+}
+
+/// How the metadata of two AppImages differs
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Differences {
+    /// Versions, if different
+    pub version: Option<(Option<String>, Option<String>)>,
+    /// Changed desktop entry keys
+    pub entry: Vec<EntryChange>,
+    /// Icons only in the first AppImage
+    pub icons_removed: Vec<String>,
+    /// Icons only in the second AppImage
+    pub icons_added: Vec<String>,
+    /// Signature statuses, if different
+    pub signature: Option<(String, String)>,
+    /// File sizes, if different
+    pub size: Option<(u64, u64)>,
+}
+
+impl Differences {
+    /// Whether nothing compared differs
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// Compare the metadata of `old` with `new`
+pub fn compare(old: &Metadata, new: &Metadata) -> Differences {
+    Differences {
+        version: (old.version != new.version).then(|| (old.version.clone(), new.version.clone())),
+        entry: desktop::diff_entries(&old.desktop_entry, &new.desktop_entry),
+        icons_removed: old.icons.difference(&new.icons).cloned().collect(),
+        icons_added: new.icons.difference(&old.icons).cloned().collect(),
+        signature: (old.signature != new.signature)
+            .then(|| (old.signature.clone(), new.signature.clone())),
+        size: (old.size != new.size).then_some((old.size, new.size)),
+    }
+}
+
+/// Short description of a signature status
+pub fn signature_label(verification: &Verification) -> String {
+    match verification {
+        Verification::Unsigned => "not signed".to_string(),
+        Verification::Trusted { fingerprint } => format!("trusted key {}", fingerprint),
+        Verification::UnknownKey { key_id } => format!("untrusted key {}", key_id),
+        Verification::ExpiredKey { fingerprint } => format!("expired key {}", fingerprint),
+        Verification::RevokedKey { fingerprint } => format!("revoked key {}", fingerprint),
+        Verification::Bad => "bad signature".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::AppImageBuilder;
+
+    #[test]
+    fn test_compare() {
+        let (_old_dir, old) = AppImageBuilder::new("App")
+            .version("1.0")
+            .write_temp()
+            .unwrap();
+        let (_new_dir, new) = AppImageBuilder::new("App")
+            .version("2.0")
+            .without_icon()
+            .write_temp()
+            .unwrap();
+        let old = Metadata::read(&old).unwrap();
+        let new = Metadata::read(&new).unwrap();
+
+        assert!(compare(&old, &old).is_empty());
+        let differences = compare(&old, &new);
+        assert_eq!(
+            differences.version,
+            Some((Some("1.0".to_string()), Some("2.0".to_string())))
+        );
+        assert!(
+            differences
+                .entry
+                .iter()
+                .any(|change| change.key == "X-AppImage-Version")
+        );
+        assert_eq!(differences.icons_removed.len(), old.icons.len());
+        assert!(differences.icons_added.is_empty());
+    }
+}
@@ -0,0 +1,190 @@
+//! Checks that a new AppImage has been fully written.
+//!
+//! A file that shows up in a watched directory may still be downloading or
+//! copying. Which checks are used is configured with `watch.completeness`;
+//! all of them have to pass.
+
+use crate::appimage::{self, AppImageError};
+use crate::config::CompletenessCheck;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+use tracing::debug;
+
+/// Size and modification time of a file
+type Snapshot = (u64, Option<SystemTime>);
+
+/// Remembers when files were last seen changing, for the `size_stable` check
+#[derive(Debug, Default)]
+pub struct Stability {
+    seen: HashMap<PathBuf, (Snapshot, Instant)>,
+}
+
+impl Stability {
+    /// Record the file's current size and modification time, and whether
+    /// they have been the same for at least `stable_for`
+    pub fn is_stable(
+        &mut self,
+        path: &Path,
+        snapshot: Snapshot,
+        now: Instant,
+        stable_for: Duration,
+    ) -> bool {
+        match self.seen.get(path) {
+            Some((previous, since)) if *previous == snapshot => {
+                now.duration_since(*since) >= stable_for
+            }
+            _ => {
+                self.seen.insert(path.to_path_buf(), (snapshot, now));
+                false
+            }
+        }
+    }
+
+    /// Stop tracking a file
+    pub fn forget(&mut self, path: &Path) {
+        self.seen.remove(path);
+    }
+}
+
+/// Whether the file passes all `checks`
+pub fn is_complete(
+    path: &Path,
+    checks: &[CompletenessCheck],
+    stability: &mut Stability,
+    stable_for: Duration,
+) -> Result<bool, AppImageError> {
+    for check in checks {
+        let passed = match check {
+            CompletenessCheck::Squashfs => appimage::is_appimage_complete(path)?,
+            CompletenessCheck::SizeStable => {
+                let metadata = fs::metadata(path)?;
+                let snapshot = (metadata.len(), metadata.modified().ok());
+                stability.is_stable(path, snapshot, Instant::now(), stable_for)
+            }
+            CompletenessCheck::OpenFiles => !is_open_for_writing(path)?,
+        };
+        if !passed {
+            debug!("{:?} failed the {:?} completeness check", path, check);
+            return Ok(false);
+        }
+    }
+    stability.forget(path);
+    Ok(true)
+}
+
+/// Whether any process (visible to this user) has the file open for writing.
+///
+/// On Linux, looks through `/proc/<pid>/fd` for descriptors pointing at the file and
+/// checks their access mode in `/proc/<pid>/fdinfo`.
+#[cfg(target_os = "linux")]
+pub fn is_open_for_writing(path: &Path) -> std::io::Result<bool> {
+    let target = fs::canonicalize(path)?;
+
+    for process in fs::read_dir("/proc")?.filter_map(|e| e.ok()) {
+        let pid = process.file_name();
+        if !pid.to_string_lossy().bytes().all(|b| b.is_ascii_digit()) {
+            continue;
+        }
+        // Processes of other users can't be read
+        let Ok(fds) = fs::read_dir(process.path().join("fd")) else {
+            continue;
+        };
+        for fd in fds.filter_map(|e| e.ok()) {
+            if fs::read_link(fd.path()).is_ok_and(|link| link == target)
+                && fs::read_to_string(process.path().join("fdinfo").join(fd.file_name()))
+                    .is_ok_and(|info| opened_for_writing(&info))
+            {
+                debug!("{:?} is open for writing by process {:?}", path, pid);
+                return Ok(true);
+            }
+        }
+    }
+    Ok(false)
+}
+
+/// Whether any process (visible to this user) has the file open for writing.
+///
+/// The BSDs don't mount `/proc` by default; asks `fstat(1)` instead.
+#[cfg(not(target_os = "linux"))]
+pub fn is_open_for_writing(path: &Path) -> std::io::Result<bool> {
+    let output = std::process::Command::new("fstat").arg(path).output()?;
+    let writing = fstat_has_writer(&String::from_utf8_lossy(&output.stdout));
+    if writing {
+        debug!("{:?} is open for writing", path);
+    }
+    Ok(writing)
+}
+
+/// Whether `fstat <file>` output lists a descriptor with write access
+/// (`w` in the R/W column)
+#[cfg(not(target_os = "linux"))]
+fn fstat_has_writer(output: &str) -> bool {
+    output
+        .lines()
+        .skip(1)
+        .filter_map(|line| line.split_whitespace().nth(8))
+        .any(|access| access.contains('w'))
+}
+
+/// Whether the `flags:` line of an fdinfo file has `O_WRONLY` or `O_RDWR` set
+#[cfg(target_os = "linux")]
+fn opened_for_writing(fdinfo: &str) -> bool {
+    fdinfo
+        .lines()
+        .find_map(|line| line.strip_prefix("flags:"))
+        .and_then(|flags| u32::from_str_radix(flags.trim(), 8).ok())
+        .is_some_and(|flags| flags & libc::O_ACCMODE as u32 != libc::O_RDONLY as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stability() {
+        let mut stability = Stability::default();
+        let path = Path::new("/apps/App.AppImage");
+        let stable_for = Duration::from_secs(5);
+        let start = Instant::now();
+
+        assert!(!stability.is_stable(path, (10, None), start, stable_for));
+        // Still growing
+        let later = start + Duration::from_secs(6);
+        assert!(!stability.is_stable(path, (20, None), later, stable_for));
+        assert!(!stability.is_stable(path, (20, None), later + Duration::from_secs(4), stable_for));
+        assert!(stability.is_stable(path, (20, None), later + Duration::from_secs(5), stable_for));
+
+        stability.forget(path);
+        assert!(!stability.is_stable(path, (20, None), later + Duration::from_secs(9), stable_for));
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_is_open_for_writing() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let path = temp.path().join("App.AppImage");
+
+        let file = fs::File::create(&path).unwrap();
+        assert!(is_open_for_writing(&path).unwrap());
+        drop(file);
+
+        let _reader = fs::File::open(&path).unwrap();
+        assert!(!is_open_for_writing(&path).unwrap());
+        assert!(opened_for_writing(
+            "pos:\t0\nflags:\t0100002\nmnt_id:\t25\n"
+        ));
+    }
+
+    #[test]
+    #[cfg(not(target_os = "linux"))]
+    fn test_fstat_has_writer() {
+        let header = "USER     CMD          PID   FD MOUNT      INUM MODE         SZ|DV R/W NAME\n";
+        let reader = "user     cat         1234    3 /home    123456 -rw-r--r--  4096  r  /home/user/App.AppImage\n";
+        let writer = "user     curl        1235    5 /home    123456 -rw-r--r--  4096  w  /home/user/App.AppImage\n";
+        assert!(!fstat_has_writer(header));
+        assert!(!fstat_has_writer(&format!("{header}{reader}")));
+        assert!(fstat_has_writer(&format!("{header}{reader}{writer}")));
+    }
+}
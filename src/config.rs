@@ -1,6 +1,13 @@
 //! Configuration file parsing for appimage-auto daemon.
+//!
+//! Besides `config.toml`, files in the `config.toml.d` directory next to it
+//! are read in order of their names and merged over it, so provisioning tools
+//! and per-machine tweaks can add settings without rewriting the user's
+//! file. Tables are merged key by key, lists are extended and other values
+//! replaced. Saving leaves what the drop-ins set out of `config.toml`.
 
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::path::{Path, PathBuf};
 use thiserror::Error;
 
@@ -10,6 +17,8 @@ pub enum ConfigError {
     ReadError(#[from] std::io::Error),
     #[error("Failed to parse config file: {0}")]
     ParseError(#[from] toml::de::Error),
+    #[error("Failed to parse drop-in {0:?}: {1}")]
+    DropInError(PathBuf, toml::de::Error),
     #[error("Failed to serialize config: {0}")]
     SerializeError(#[from] toml::ser::Error),
     #[error("No config directory found")]
@@ -21,10 +30,26 @@ pub enum ConfigError {
 #[serde(default)]
 #[derive(Default)]
 pub struct Config {
+    /// Preset applied on top of the settings below
+    pub profile: Profile,
     pub watch: WatchConfig,
     pub integration: IntegrationConfig,
     pub logging: LoggingConfig,
     pub notifications: NotificationConfig,
+    pub api: ApiConfig,
+    pub signatures: SignaturesConfig,
+    pub gui: GuiConfig,
+    pub categories: CategoriesConfig,
+    /// Behaviors depending on the desktop session
+    pub desktop_environment: DesktopEnvironmentConfig,
+    /// Launch settings per app, keyed by identifier or name
+    pub apps: BTreeMap<String, AppProfile>,
+    /// Settings merged in from drop-in files, left out when saving
+    #[serde(skip)]
+    drop_ins: toml::Table,
+    /// The config file as read, to restore values the drop-ins replaced
+    #[serde(skip)]
+    file: toml::Table,
 }
 
 /// Watch directory configuration
@@ -37,6 +62,32 @@ pub struct WatchConfig {
     pub patterns: Vec<String>,
     /// Debounce delay in milliseconds
     pub debounce_ms: u64,
+    /// Longest time an event may be deferred by repeated debouncing, in milliseconds
+    pub max_debounce_ms: u64,
+    /// Maximum number of files handled per event loop iteration
+    pub batch_size: usize,
+    /// Checks a new file must pass before it is considered fully written
+    pub completeness: Vec<CompletenessCheck>,
+    /// How long size and modification time must stay unchanged for the
+    /// `size_stable` check, in milliseconds
+    pub stable_ms: u64,
+    /// Integrate symlinks to AppImages under the symlink's path (and not
+    /// their targets), or ignore symlinks
+    pub follow_symlinks: bool,
+    /// Extract and integrate the AppImage from `.zip`, `.tar.gz` and `.tgz`
+    /// archives holding exactly one
+    pub extract_archives: bool,
+    /// Directory AppImages are extracted into (next to the archive if unset)
+    pub archive_destination: Option<String>,
+    /// Create missing watch directories (e.g. `~/Applications`) at startup
+    /// and bookmark them in the file manager
+    pub create_directories: bool,
+    /// Seconds between rescans of the watched directories that catch events
+    /// missed while suspended or on watcher overflow (0 disables them)
+    pub rescan_interval: u64,
+    /// Keep the machine from suspending while a batch of at least this many
+    /// AppImages is integrated (0 disables it)
+    pub inhibit_sleep: usize,
 }
 
 impl Default for WatchConfig {
@@ -49,31 +100,118 @@ impl Default for WatchConfig {
             ],
             patterns: vec!["*.AppImage".to_string(), "*.appimage".to_string()],
             debounce_ms: 1000,
+            max_debounce_ms: 30000,
+            batch_size: 10,
+            completeness: vec![CompletenessCheck::Squashfs],
+            stable_ms: 5000,
+            follow_symlinks: true,
+            extract_archives: false,
+            archive_destination: None,
+            create_directories: false,
+            rescan_interval: 0,
+            inhibit_sleep: 5,
         }
     }
 }
 
+/// A way to tell that a new AppImage has been fully written
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CompletenessCheck {
+    /// The embedded squashfs image ends within the file
+    Squashfs,
+    /// Size and modification time haven't changed for `stable_ms`
+    SizeStable,
+    /// No process has the file open for writing (looked up in `/proc`, or with
+    /// `fstat` on FreeBSD)
+    OpenFiles,
+}
+
 /// Integration behavior configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(default)]
 pub struct IntegrationConfig {
     /// Directory for .desktop files
     pub desktop_dir: String,
+    /// Further directories that get a copy of each desktop entry, e.g. a
+    /// menu directory of another desktop environment
+    pub desktop_dirs: Vec<String>,
     /// Directory for icons
     pub icon_dir: String,
+    /// Further icon theme directories that get a copy of each icon, for
+    /// desktops that only look in a custom theme
+    pub icon_dirs: Vec<String>,
     /// Whether to run update-desktop-database after changes
     pub update_database: bool,
+    /// Whether to keep the apps' desktop actions (extra launch entries such
+    /// as "New Window") in their desktop entries
+    pub desktop_actions: bool,
     /// Whether to scan existing AppImages on startup
     pub scan_on_startup: bool,
+    /// How many times a failed integration is attempted before giving up
+    pub max_retries: u32,
+    /// Integrate new AppImages automatically or hold them for approval
+    pub mode: IntegrationMode,
+    /// Directories (e.g. `~/Downloads`) whose AppImages are held until they
+    /// are moved into `applications_dir`, whatever the mode
+    pub quarantine_dirs: Vec<String>,
+    /// Where the "Move & Integrate" notification action moves held AppImages
+    pub applications_dir: String,
+    /// Launch apps through `appimage-auto track` to count launches
+    pub track_launches: bool,
+    /// Disable the autostart entries integrated apps create for themselves
+    /// (they are reported either way)
+    pub block_autostart: bool,
+    /// Look for distro or Flatpak installs of the same app on integration
+    pub detect_duplicates: bool,
+    /// Append " (AppImage)" to the menu name of detected duplicates
+    pub suffix_duplicates: bool,
+    /// Whether to install the icons of integrated AppImages
+    pub install_icons: bool,
+    /// Directory for symlinks named after each integrated app, to start it
+    /// from a shell (e.g. `~/.local/bin`); none are made if unset
+    pub link_dir: Option<String>,
+    /// Extraction directories older than this are left over from an
+    /// interrupted integration and removed
+    pub extract_ttl_secs: u64,
+    /// AppImages are skipped while the cache, icon or desktop entry directory
+    /// has less than this many megabytes free (0 disables the check)
+    pub min_free_space_mb: u64,
+}
+
+/// What happens when a new AppImage is found in a watched directory
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IntegrationMode {
+    /// Integrate it right away
+    #[default]
+    Automatic,
+    /// Notify and wait until the user integrates it
+    Approval,
 }
 
 impl Default for IntegrationConfig {
     fn default() -> Self {
         Self {
             desktop_dir: "~/.local/share/applications".to_string(),
+            desktop_dirs: Vec::new(),
             icon_dir: "~/.local/share/icons/hicolor".to_string(),
+            icon_dirs: Vec::new(),
             update_database: true,
+            desktop_actions: true,
             scan_on_startup: true,
+            max_retries: 5,
+            mode: IntegrationMode::Automatic,
+            quarantine_dirs: Vec::new(),
+            applications_dir: "~/Applications".to_string(),
+            track_launches: false,
+            block_autostart: false,
+            detect_duplicates: true,
+            suffix_duplicates: false,
+            install_icons: true,
+            link_dir: None,
+            extract_ttl_secs: 3600,
+            min_free_space_mb: 100,
         }
     }
 }
@@ -86,6 +224,8 @@ pub struct LoggingConfig {
     pub level: String,
     /// Whether to log to file
     pub file: Option<String>,
+    /// File to record watcher events to, for `appimage-auto replay`
+    pub record_events: Option<String>,
 }
 
 impl Default for LoggingConfig {
@@ -93,6 +233,7 @@ impl Default for LoggingConfig {
         Self {
             level: "info".to_string(),
             file: None,
+            record_events: None,
         }
     }
 }
@@ -107,6 +248,29 @@ pub struct NotificationConfig {
     pub on_integrate: bool,
     /// Notify on unintegration
     pub on_unintegrate: bool,
+    /// How notifications are sent
+    pub backend: NotificationBackend,
+    /// Command run for each notification with the `command` backend, with
+    /// the summary and body appended as arguments
+    pub command: Vec<String>,
+    /// Daily window (`HH:MM-HH:MM`, local time) without notifications
+    pub quiet_hours: Option<String>,
+    /// Send the notifications held back in quiet hours as one summary when
+    /// they end, instead of dropping them
+    pub quiet_hours_summary: bool,
+}
+
+/// How desktop notifications are sent
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NotificationBackend {
+    /// The `notify-rust` crate (needs the `notifications` feature)
+    #[default]
+    NotifyRust,
+    /// Direct `org.freedesktop.Notifications` D-Bus calls through `gdbus`
+    Dbus,
+    /// A command such as `dunstify`
+    Command,
 }
 
 impl Default for NotificationConfig {
@@ -115,6 +279,371 @@ impl Default for NotificationConfig {
             enabled: true,
             on_integrate: true,
             on_unintegrate: true,
+            backend: NotificationBackend::NotifyRust,
+            command: Vec::new(),
+            quiet_hours: None,
+            quiet_hours_summary: true,
+        }
+    }
+}
+
+/// Local HTTP status API configuration (requires the `http-api` feature)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ApiConfig {
+    /// Serve the read-only JSON API on localhost
+    pub enabled: bool,
+    /// TCP port to listen on (bound to 127.0.0.1 only)
+    pub port: u16,
+}
+
+impl Default for ApiConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            port: 7474,
+        }
+    }
+}
+
+/// Signature verification of AppImages, against the trust store
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SignaturesConfig {
+    /// What to do with AppImages whose signature can't be verified
+    pub verify: SignaturePolicy,
+    /// Trust the signing key of an app the first time it is integrated, so
+    /// later releases signed with the same key are accepted
+    pub trust_on_first_use: bool,
+}
+
+/// How signatures are checked before integrating an AppImage
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SignaturePolicy {
+    /// Don't check signatures
+    #[default]
+    Off,
+    /// Check signed AppImages and log failures, but integrate them anyway
+    Warn,
+    /// Only integrate AppImages signed by a trusted key
+    Require,
+}
+
+/// How one app is launched, from an `[apps."<identifier or name>"]` section.
+///
+/// Applied to its desktop entry at integration time.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct AppProfile {
+    /// Run it inside `firejail --appimage` (launches are then not tracked)
+    /// and write an AppArmor profile template for it
+    pub sandbox: bool,
+    /// Allow network access from the sandbox and the AppArmor profile
+    pub network: bool,
+    /// Environment variables to launch it with
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub env: BTreeMap<String, String>,
+    /// Arguments passed before those of its desktop entry
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub args: Vec<String>,
+    /// Extract to a temporary directory and run from there instead of
+    /// mounting with FUSE
+    pub extract_and_run: bool,
+    /// Leave it out of the application menu
+    pub hidden: bool,
+    /// Keep the integration when its file goes missing
+    pub pinned: bool,
+}
+
+impl Default for AppProfile {
+    fn default() -> Self {
+        Self {
+            sandbox: false,
+            network: true,
+            env: BTreeMap::new(),
+            args: Vec::new(),
+            extract_and_run: false,
+            hidden: false,
+            pinned: false,
+        }
+    }
+}
+
+impl AppProfile {
+    /// Whether nothing differs from the defaults
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// Rewrites of the `Categories=` key of integrated desktop entries
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq, Eq)]
+#[serde(default)]
+pub struct CategoriesConfig {
+    /// Rename categories, e.g. `Utility = "Accessories"`; an empty name drops the category
+    pub rename: BTreeMap<String, String>,
+    /// Per-app rules, applied in order after renaming
+    pub rules: Vec<CategoryRule>,
+    /// New AppImages whose bundled desktop entry has one of these categories
+    /// (e.g. `ConsoleOnly`) are not integrated automatically
+    pub exclude: Vec<String>,
+}
+
+/// Categories to set or add for apps matching a pattern
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq, Eq)]
+#[serde(default)]
+pub struct CategoryRule {
+    /// Text the app name or AppImage file name contains (case-insensitive)
+    #[serde(rename = "match")]
+    pub pattern: String,
+    /// Replace the categories with these (semicolon-separated)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub set: Option<String>,
+    /// Add these categories (semicolon-separated)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub add: Option<String>,
+}
+
+impl CategoriesConfig {
+    /// Apply the renames and matching rules to a `Categories=` value of the
+    /// app with the given name and AppImage file name
+    pub fn remap(&self, categories: &str, name: &str, file_name: &str) -> String {
+        let split = |list: &str| -> Vec<String> {
+            list.split(';')
+                .map(str::trim)
+                .filter(|c| !c.is_empty())
+                .map(str::to_string)
+                .collect()
+        };
+
+        let mut result: Vec<String> = Vec::new();
+        let push = |result: &mut Vec<String>, category: String| {
+            if !category.is_empty() && !result.contains(&category) {
+                result.push(category);
+            }
+        };
+
+        for category in split(categories) {
+            let renamed = self.rename.get(&category).cloned().unwrap_or(category);
+            push(&mut result, renamed);
+        }
+
+        let (name, file_name) = (name.to_lowercase(), file_name.to_lowercase());
+        for rule in &self.rules {
+            let pattern = rule.pattern.to_lowercase();
+            if pattern.is_empty() || !(name.contains(&pattern) || file_name.contains(&pattern)) {
+                continue;
+            }
+            if let Some(set) = &rule.set {
+                result.clear();
+                for category in split(set) {
+                    push(&mut result, category);
+                }
+            }
+            for category in rule.add.as_deref().map(split).unwrap_or_default() {
+                push(&mut result, category);
+            }
+        }
+
+        result.iter().map(|c| format!("{};", c)).collect()
+    }
+
+    /// The first category of a `Categories=` value that is excluded from
+    /// automatic integration
+    pub fn excluded<'a>(&self, categories: &'a str) -> Option<&'a str> {
+        categories
+            .split(';')
+            .map(str::trim)
+            .find(|category| !category.is_empty() && self.exclude.iter().any(|e| e == category))
+    }
+}
+
+/// Settings app preferences
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct GuiConfig {
+    /// Sort order of the app list
+    pub sort: AppSort,
+    /// Color scheme of the settings app
+    pub theme: Theme,
+}
+
+/// Preset for where appimage-auto runs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Profile {
+    /// Use the settings as configured
+    #[default]
+    Desktop,
+    /// Servers and containers: no icons, desktop database updates or
+    /// notifications, but AppImages are still tracked and get launch
+    /// symlinks (in `~/.local/bin` unless `link_dir` is set)
+    Headless,
+}
+
+/// Settings that depend on the desktop session, from `[desktop_environment]`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DesktopEnvironmentConfig {
+    /// Session whose settings apply; `auto` detects it from the environment
+    pub session: Session,
+    pub gnome: SessionSettings,
+    pub kde: SessionSettings,
+    pub sway: SessionSettings,
+}
+
+impl DesktopEnvironmentConfig {
+    /// The configured session, or the detected one for `auto`
+    pub fn current_session(&self) -> Option<Session> {
+        match self.session {
+            Session::Auto => Session::detect(),
+            session => Some(session),
+        }
+    }
+
+    /// Settings for a session: the configured ones over the built-in defaults
+    pub fn settings(&self, session: Session) -> SessionSettings {
+        let (configured, defaults) = match session {
+            Session::Gnome => (&self.gnome, SessionSettings::default()),
+            Session::Kde => (&self.kde, SessionSettings::default()),
+            // wofi and the like list every desktop action as an app of its
+            // own, and nothing reads the MIME cache
+            Session::Sway => (
+                &self.sway,
+                SessionSettings {
+                    update_database: Some(false),
+                    desktop_actions: Some(false),
+                },
+            ),
+            Session::Auto | Session::Other => return SessionSettings::default(),
+        };
+        SessionSettings {
+            update_database: configured.update_database.or(defaults.update_database),
+            desktop_actions: configured.desktop_actions.or(defaults.desktop_actions),
+        }
+    }
+}
+
+/// A desktop session with its own integration behaviors
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Session {
+    /// Detect it from `XDG_CURRENT_DESKTOP`
+    #[default]
+    Auto,
+    Gnome,
+    Kde,
+    Sway,
+    /// Any other session, using the `[integration]` settings as they are
+    Other,
+}
+
+impl Session {
+    /// The running session, from `XDG_CURRENT_DESKTOP` (or else
+    /// `XDG_SESSION_DESKTOP` or `DESKTOP_SESSION`)
+    pub fn detect() -> Option<Self> {
+        const VARS: [&str; 3] = [
+            "XDG_CURRENT_DESKTOP",
+            "XDG_SESSION_DESKTOP",
+            "DESKTOP_SESSION",
+        ];
+        VARS.iter()
+            .filter_map(|var| std::env::var(var).ok())
+            .find(|value| !value.is_empty())
+            .map(|value| Self::from_desktop_names(&value))
+    }
+
+    /// The session named in a `:`-separated desktop list such as
+    /// `ubuntu:GNOME`
+    pub fn from_desktop_names(names: &str) -> Self {
+        names
+            .split(':')
+            .find_map(|name| match name.to_lowercase().as_str() {
+                "gnome" | "gnome-classic" | "gnome-xorg" => Some(Self::Gnome),
+                "kde" | "plasma" | "plasmawayland" => Some(Self::Kde),
+                "sway" => Some(Self::Sway),
+                _ => None,
+            })
+            .unwrap_or(Self::Other)
+    }
+}
+
+/// Integration settings overridden in one desktop session; unset ones keep
+/// the session's default
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SessionSettings {
+    /// Overrides `integration.update_database`
+    pub update_database: Option<bool>,
+    /// Overrides `integration.desktop_actions`
+    pub desktop_actions: Option<bool>,
+}
+
+/// Color scheme preference for the settings app
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Theme {
+    /// Follow the desktop's light/dark preference
+    #[default]
+    System,
+    Light,
+    Dark,
+}
+
+impl Theme {
+    /// All themes, in menu order
+    pub const ALL: [Theme; 3] = [Theme::System, Theme::Light, Theme::Dark];
+
+    /// Human-readable label
+    pub fn label(self) -> &'static str {
+        match self {
+            Theme::System => "Follow System",
+            Theme::Light => "Light",
+            Theme::Dark => "Dark",
+        }
+    }
+}
+
+/// Sort order for lists of integrated AppImages
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AppSort {
+    /// Alphabetical by application name
+    #[default]
+    Name,
+    /// Most recently integrated first
+    Integrated,
+    /// Most recently updated first
+    Updated,
+    /// Largest AppImage file first
+    Size,
+    /// Grouped by containing directory
+    Directory,
+    /// Most recently launched first
+    LastUsed,
+}
+
+impl AppSort {
+    /// All sort orders, in menu order
+    pub const ALL: [AppSort; 6] = [
+        AppSort::Name,
+        AppSort::Integrated,
+        AppSort::Updated,
+        AppSort::Size,
+        AppSort::Directory,
+        AppSort::LastUsed,
+    ];
+
+    /// Human-readable label
+    pub fn label(self) -> &'static str {
+        match self {
+            AppSort::Name => "Name",
+            AppSort::Integrated => "Date integrated",
+            AppSort::Updated => "Last updated",
+            AppSort::Size => "File size",
+            AppSort::Directory => "Directory",
+            AppSort::LastUsed => "Last used",
         }
     }
 }
@@ -124,35 +653,62 @@ impl Config {
     pub fn load() -> Result<Self, ConfigError> {
         let config_path = Self::config_path()?;
 
-        if config_path.exists() {
-            Self::load_from(&config_path)
-        } else {
-            let config = Config::default();
-            config.save()?;
-            Ok(config)
+        if !config_path.exists() {
+            Config::default().save()?;
         }
+        Self::load_from(&config_path)
     }
 
-    /// Load configuration from a specific path
+    /// Load configuration from a specific path, merging in the drop-in
+    /// files next to it
     pub fn load_from(path: &Path) -> Result<Self, ConfigError> {
         let content = std::fs::read_to_string(path)?;
-        let config: Config = toml::from_str(&content)?;
+        let file: toml::Table = toml::from_str(&content)?;
+
+        let mut drop_ins = toml::Table::new();
+        for file in drop_in_files(&drop_in_dir(path)) {
+            let content = std::fs::read_to_string(&file)?;
+            let table = toml::from_str(&content).map_err(|e| ConfigError::DropInError(file, e))?;
+            merge(&mut drop_ins, table);
+        }
+        let mut table = file.clone();
+        merge(&mut table, drop_ins.clone());
+
+        let mut config: Config = table.try_into()?;
+        config.drop_ins = drop_ins;
+        config.file = file;
         Ok(config)
     }
 
     /// Save configuration to the default location
     pub fn save(&self) -> Result<(), ConfigError> {
-        let config_path = Self::config_path()?;
+        self.save_to(&Self::config_path()?)
+    }
 
-        if let Some(parent) = config_path.parent() {
+    /// Save configuration to a specific path, without the settings that
+    /// came from drop-in files
+    pub fn save_to(&self, path: &Path) -> Result<(), ConfigError> {
+        if let Some(parent) = path.parent() {
             std::fs::create_dir_all(parent)?;
         }
 
-        let content = toml::to_string_pretty(self)?;
-        std::fs::write(&config_path, content)?;
+        let mut table = toml::Table::try_from(self)?;
+        unmerge(&mut table, &self.drop_ins, &self.file);
+        let content = toml::to_string_pretty(&table)?;
+        std::fs::write(path, content)?;
         Ok(())
     }
 
+    /// Whether a watch directory is added by a drop-in file, so removing
+    /// it from `config.toml` has no effect
+    pub fn watch_directory_from_drop_in(&self, dir: &str) -> bool {
+        self.drop_ins
+            .get("watch")
+            .and_then(|watch| watch.get("directories"))
+            .and_then(|dirs| dirs.as_array())
+            .is_some_and(|dirs| dirs.iter().any(|d| d.as_str() == Some(dir)))
+    }
+
     /// Get the default config file path
     pub fn config_path() -> Result<PathBuf, ConfigError> {
         let dirs = directories::ProjectDirs::from("", "", "appimage-auto")
@@ -160,6 +716,39 @@ impl Config {
         Ok(dirs.config_dir().join("config.toml"))
     }
 
+    /// Get the drop-in directory next to the default config file
+    pub fn drop_in_path() -> Result<PathBuf, ConfigError> {
+        Ok(drop_in_dir(&Self::config_path()?))
+    }
+
+    /// Apply the desktop session's and the profile's overrides to the
+    /// configured settings
+    pub fn with_profile(&self) -> Self {
+        let mut config = self.clone();
+
+        if let Some(session) = config.desktop_environment.current_session() {
+            let settings = config.desktop_environment.settings(session);
+            if let Some(update_database) = settings.update_database {
+                config.integration.update_database = update_database;
+            }
+            if let Some(desktop_actions) = settings.desktop_actions {
+                config.integration.desktop_actions = desktop_actions;
+            }
+        }
+
+        if config.profile == Profile::Headless {
+            config.integration.install_icons = false;
+            config.integration.update_database = false;
+            config.notifications.enabled = false;
+            config
+                .integration
+                .link_dir
+                .get_or_insert_with(|| "~/.local/bin".to_string());
+        }
+
+        config
+    }
+
     /// Expand all paths in the configuration (resolve ~ and environment variables)
     pub fn expand_paths(&self) -> Self {
         let mut config = self.clone();
@@ -170,18 +759,63 @@ impl Config {
             .iter()
             .map(|d| shellexpand::tilde(d).to_string())
             .collect();
+        if let Some(ref dir) = config.watch.archive_destination {
+            config.watch.archive_destination = Some(shellexpand::tilde(dir).to_string());
+        }
 
         config.integration.desktop_dir =
             shellexpand::tilde(&config.integration.desktop_dir).to_string();
+        config.integration.desktop_dirs = config
+            .integration
+            .desktop_dirs
+            .iter()
+            .map(|d| shellexpand::tilde(d).to_string())
+            .collect();
         config.integration.icon_dir = shellexpand::tilde(&config.integration.icon_dir).to_string();
+        config.integration.icon_dirs = config
+            .integration
+            .icon_dirs
+            .iter()
+            .map(|d| shellexpand::tilde(d).to_string())
+            .collect();
+        config.integration.quarantine_dirs = config
+            .integration
+            .quarantine_dirs
+            .iter()
+            .map(|d| shellexpand::tilde(d).to_string())
+            .collect();
+        config.integration.applications_dir =
+            shellexpand::tilde(&config.integration.applications_dir).to_string();
+        if let Some(ref dir) = config.integration.link_dir {
+            config.integration.link_dir = Some(shellexpand::tilde(dir).to_string());
+        }
 
         if let Some(ref file) = config.logging.file {
             config.logging.file = Some(shellexpand::tilde(file).to_string());
         }
+        if let Some(ref file) = config.logging.record_events {
+            config.logging.record_events = Some(shellexpand::tilde(file).to_string());
+        }
 
         config
     }
 
+    /// Launch settings of an app, looked up by identifier and then by name
+    pub fn app_profile(&self, identifier: &str, name: Option<&str>) -> Option<&AppProfile> {
+        self.apps
+            .get(identifier)
+            .or_else(|| name.and_then(|name| self.apps.get(name)))
+    }
+
+    /// Set the launch settings under a key; empty settings remove it
+    pub fn set_app_profile(&mut self, key: &str, profile: AppProfile) {
+        if profile.is_empty() {
+            self.apps.remove(key);
+        } else {
+            self.apps.insert(key.to_string(), profile);
+        }
+    }
+
     /// Get expanded watch directories as PathBufs
     pub fn watch_directories(&self) -> Vec<PathBuf> {
         self.watch
@@ -196,10 +830,98 @@ impl Config {
         PathBuf::from(shellexpand::tilde(&self.integration.desktop_dir).as_ref())
     }
 
+    /// Get expanded directories that get copies of the desktop entries
+    pub fn extra_desktop_directories(&self) -> Vec<PathBuf> {
+        self.integration
+            .desktop_dirs
+            .iter()
+            .map(|d| PathBuf::from(shellexpand::tilde(d).as_ref()))
+            .collect()
+    }
+
     /// Get expanded icon directory
     pub fn icon_directory(&self) -> PathBuf {
         PathBuf::from(shellexpand::tilde(&self.integration.icon_dir).as_ref())
     }
+
+    /// Get expanded icon theme directories that get copies of the icons
+    pub fn extra_icon_directories(&self) -> Vec<PathBuf> {
+        self.integration
+            .icon_dirs
+            .iter()
+            .map(|d| PathBuf::from(shellexpand::tilde(d).as_ref()))
+            .collect()
+    }
+}
+
+/// Drop-in directory of a config file (`config.toml.d`)
+fn drop_in_dir(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".d");
+    path.with_file_name(name)
+}
+
+/// The `.toml` files in a drop-in directory, in the order they are applied
+fn drop_in_files(dir: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut files: Vec<PathBuf> = entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "toml") && path.is_file())
+        .collect();
+    files.sort();
+    files
+}
+
+/// Merge `over` into `base`: tables key by key, lists extended with the
+/// values they don't have yet, other values replaced
+fn merge(base: &mut toml::Table, over: toml::Table) {
+    for (key, value) in over {
+        match (base.get_mut(&key), value) {
+            (Some(toml::Value::Table(base)), toml::Value::Table(over)) => merge(base, over),
+            (Some(toml::Value::Array(base)), toml::Value::Array(over)) => {
+                for value in over {
+                    if !base.contains(&value) {
+                        base.push(value);
+                    }
+                }
+            }
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+/// Take what [`merge`] added from `over` back out of `table`, restoring the
+/// values of `file` it replaced. Values that differ from `over` were changed
+/// since and are kept.
+fn unmerge(table: &mut toml::Table, over: &toml::Table, file: &toml::Table) {
+    for (key, over) in over {
+        let original = file.get(key);
+        match (table.get_mut(key), over) {
+            (Some(toml::Value::Table(table)), toml::Value::Table(over)) => {
+                let empty = toml::Table::new();
+                let original = original.and_then(toml::Value::as_table).unwrap_or(&empty);
+                unmerge(table, over, original);
+            }
+            (Some(toml::Value::Array(values)), toml::Value::Array(over)) => {
+                let original = original.and_then(toml::Value::as_array);
+                values.retain(|value| {
+                    !over.contains(value) || original.is_some_and(|o| o.contains(value))
+                });
+            }
+            (Some(value), over) if *value == *over => match original {
+                Some(original) => *value = original.clone(),
+                None => {
+                    table.remove(key);
+                }
+            },
+            _ => {}
+        }
+    }
 }
 
 #[cfg(test)]
@@ -229,6 +951,60 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_drop_ins() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("config.toml");
+        std::fs::write(
+            &path,
+            "[watch]\ndirectories = [\"~/Applications\"]\ndebounce_ms = 500\n",
+        )
+        .unwrap();
+        let drop_ins = dir.path().join("config.toml.d");
+        std::fs::create_dir(&drop_ins).unwrap();
+        std::fs::write(
+            drop_ins.join("10-site.toml"),
+            "[watch]\ndirectories = [\"/opt/apps\", \"~/Applications\"]\n\
+             [integration]\nscan_on_startup = false\n",
+        )
+        .unwrap();
+        std::fs::write(
+            drop_ins.join("20-machine.toml"),
+            "[watch]\ndebounce_ms = 1000\n",
+        )
+        .unwrap();
+        std::fs::write(drop_ins.join("notes.txt"), "not = toml = at all").unwrap();
+
+        let mut config = Config::load_from(&path).unwrap();
+        assert_eq!(config.watch.directories, ["~/Applications", "/opt/apps"]);
+        assert_eq!(config.watch.debounce_ms, 1000);
+        assert!(!config.integration.scan_on_startup);
+        assert!(config.watch_directory_from_drop_in("/opt/apps"));
+        // Also listed by the drop-in, so it stays when removed from the main file
+        assert!(config.watch_directory_from_drop_in("~/Applications"));
+
+        // Saving keeps the drop-ins' settings out of the main file
+        config.watch.directories.push("~/Games".to_string());
+        assert!(!config.watch_directory_from_drop_in("~/Games"));
+        config.save_to(&path).unwrap();
+        let main: Config = toml::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(main.watch.directories, ["~/Applications", "~/Games"]);
+        assert_eq!(main.watch.debounce_ms, 500);
+        assert!(main.integration.scan_on_startup);
+        let reloaded = Config::load_from(&path).unwrap();
+        assert_eq!(
+            reloaded.watch.directories,
+            ["~/Applications", "~/Games", "/opt/apps"]
+        );
+        assert_eq!(reloaded.watch.debounce_ms, 1000);
+
+        std::fs::write(drop_ins.join("30-broken.toml"), "[watch").unwrap();
+        assert!(matches!(
+            Config::load_from(&path),
+            Err(ConfigError::DropInError(..))
+        ));
+    }
+
     #[test]
     fn test_serialize_deserialize() {
         let config = Config::default();
@@ -237,4 +1013,162 @@ mod tests {
 
         assert_eq!(config.watch.directories, deserialized.watch.directories);
     }
+
+    #[test]
+    fn test_headless_profile() {
+        let config: Config = toml::from_str("profile = \"headless\"").unwrap();
+        let applied = config.with_profile();
+        assert!(!applied.integration.install_icons);
+        assert!(!applied.integration.update_database);
+        assert!(!applied.notifications.enabled);
+        assert_eq!(
+            applied.integration.link_dir.as_deref(),
+            Some("~/.local/bin")
+        );
+
+        // The stored settings stay as they are
+        assert!(config.integration.install_icons);
+        let desktop = Config::default().with_profile();
+        assert!(desktop.integration.install_icons);
+        assert_eq!(desktop.integration.link_dir, None);
+    }
+
+    #[test]
+    fn test_desktop_environment() {
+        assert_eq!(Session::from_desktop_names("ubuntu:GNOME"), Session::Gnome);
+        assert_eq!(Session::from_desktop_names("KDE"), Session::Kde);
+        assert_eq!(Session::from_desktop_names("sway"), Session::Sway);
+        assert_eq!(Session::from_desktop_names("Hyprland"), Session::Other);
+
+        let config: Config = toml::from_str(
+            r#"
+            [desktop_environment]
+            session = "sway"
+            "#,
+        )
+        .unwrap();
+        let applied = config.with_profile();
+        assert!(!applied.integration.update_database);
+        assert!(!applied.integration.desktop_actions);
+
+        let config: Config = toml::from_str(
+            r#"
+            [desktop_environment]
+            session = "sway"
+
+            [desktop_environment.sway]
+            update_database = true
+            "#,
+        )
+        .unwrap();
+        let applied = config.with_profile();
+        assert!(applied.integration.update_database);
+        assert!(!applied.integration.desktop_actions);
+
+        let config: Config = toml::from_str(
+            r#"
+            [desktop_environment]
+            session = "other"
+            "#,
+        )
+        .unwrap();
+        assert!(config.with_profile().integration.desktop_actions);
+    }
+
+    #[test]
+    fn test_app_profiles() {
+        let mut config: Config = toml::from_str(
+            r#"
+            [apps.0123456789abcdef]
+            sandbox = true
+
+            [apps."Visual Studio Code"]
+            args = ["--disable-gpu"]
+            env = { ELECTRON_OZONE_PLATFORM_HINT = "wayland" }
+            "#,
+        )
+        .unwrap();
+
+        assert!(
+            config
+                .app_profile("0123456789abcdef", None)
+                .unwrap()
+                .sandbox
+        );
+        let by_name = config
+            .app_profile("fedcba9876543210", Some("Visual Studio Code"))
+            .unwrap();
+        assert_eq!(by_name.args, ["--disable-gpu"]);
+        assert_eq!(by_name.env["ELECTRON_OZONE_PLATFORM_HINT"], "wayland");
+        assert!(
+            config
+                .app_profile("fedcba9876543210", Some("Other"))
+                .is_none()
+        );
+
+        config.set_app_profile("0123456789abcdef", AppProfile::default());
+        assert_eq!(config.apps.len(), 1);
+        let serialized = toml::to_string_pretty(&config).unwrap();
+        let reloaded: Config = toml::from_str(&serialized).unwrap();
+        assert_eq!(reloaded.apps, config.apps);
+    }
+
+    #[test]
+    fn test_categories_remap() {
+        let config: Config = toml::from_str(
+            r#"
+            [categories.rename]
+            Utility = "Accessories"
+            Qt = ""
+
+            [[categories.rules]]
+            match = "jetbrains"
+            set = "Development;IDE"
+
+            [[categories.rules]]
+            match = "krita"
+            add = "Art;Graphics"
+            "#,
+        )
+        .unwrap();
+        let categories = &config.categories;
+
+        assert_eq!(
+            categories.remap("Utility;Qt;", "Calculator", "calc.AppImage"),
+            "Accessories;"
+        );
+        assert_eq!(
+            categories.remap("Utility;", "IntelliJ IDEA", "JetBrains-IDEA.AppImage"),
+            "Development;IDE;"
+        );
+        assert_eq!(
+            categories.remap("Graphics;", "Krita", "krita-5.2.AppImage"),
+            "Graphics;Art;"
+        );
+        assert_eq!(categories.remap("", "Other", "other.AppImage"), "");
+    }
+
+    #[test]
+    fn test_categories_excluded() {
+        let config: Config =
+            toml::from_str("[categories]\nexclude = [\"ConsoleOnly\", \"Settings\"]\n").unwrap();
+        let categories = &config.categories;
+
+        assert_eq!(
+            categories.excluded("System;Settings;HardwareSettings;"),
+            Some("Settings")
+        );
+        assert_eq!(categories.excluded("Graphics;2DGraphics;"), None);
+        assert_eq!(categories.excluded(""), None);
+    }
+
+    #[test]
+    fn test_gui_sort() {
+        let config: Config = toml::from_str("[gui]\nsort = \"size\"\n").unwrap();
+        assert_eq!(config.gui.sort, AppSort::Size);
+
+        let config: Config = toml::from_str("").unwrap();
+        assert_eq!(config.gui.sort, AppSort::Name);
+        assert_eq!(config.gui.theme, Theme::System);
+    }
 }
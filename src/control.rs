@@ -0,0 +1,323 @@
+//! Local control socket for the daemon.
+//!
+//! A Unix socket at `$XDG_RUNTIME_DIR/appimage-auto.sock` accepting one
+//! command per connection (`ping`, `status`, `list`, `scan`,
+//! `integrate <path>`, `reintegrate <path>`, `remove <path>`,
+//! `delete <path>`) and answering with one JSON line.
+//! After `subscribe` the connection stays open and gets one JSON line per
+//! history event. When started by systemd socket activation the inherited
+//! socket (`LISTEN_FDS`) is used instead of binding a new one; otherwise the
+//! socket file is removed again when the daemon exits.
+//!
+//! Requests are answered by the daemon's main loop, which owns the state; the
+//! listener thread only forwards them over a channel. [`crate::client`] wraps
+//! the protocol in typed methods.
+
+use serde::{Deserialize, Serialize};
+use std::io::{BufRead, BufReader, Write};
+use std::os::fd::FromRawFd;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+use std::time::Duration;
+use thiserror::Error;
+use tracing::{debug, info, warn};
+
+/// First file descriptor passed by systemd socket activation
+const SD_LISTEN_FDS_START: i32 = 3;
+
+/// How long a client waits for the daemon to answer
+pub(crate) const REPLY_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Error, Debug)]
+pub enum ControlError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("JSON error: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("No runtime directory found")]
+    NoRuntimeDir,
+}
+
+/// A command sent to the daemon
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ControlCommand {
+    Ping,
+    Status,
+    /// All integrated AppImages
+    List,
+    Scan,
+    Integrate(PathBuf),
+    /// Integrate again from scratch, like `integrate --force`
+    Reintegrate(PathBuf),
+    /// Remove the integration, keeping the file
+    Remove(PathBuf),
+    /// Remove the integration and delete the file
+    RemoveAndDelete(PathBuf),
+    /// Keep the connection open for history events
+    Subscribe,
+}
+
+impl ControlCommand {
+    /// Parse a command line as sent by a client. Paths are the rest of the
+    /// line, so they may contain spaces.
+    pub fn parse(line: &str) -> Option<Self> {
+        let line = line.trim_end_matches(['\r', '\n']).trim_start();
+        match line.split_once(' ') {
+            Some(("integrate", path)) if !path.is_empty() => Some(Self::Integrate(path.into())),
+            Some(("reintegrate", path)) if !path.is_empty() => Some(Self::Reintegrate(path.into())),
+            Some(("remove", path)) if !path.is_empty() => Some(Self::Remove(path.into())),
+            Some(("delete", path)) if !path.is_empty() => Some(Self::RemoveAndDelete(path.into())),
+            _ => match line.trim_end() {
+                "ping" => Some(Self::Ping),
+                "status" => Some(Self::Status),
+                "list" => Some(Self::List),
+                "scan" => Some(Self::Scan),
+                "subscribe" => Some(Self::Subscribe),
+                _ => None,
+            },
+        }
+    }
+
+    /// The command as sent over the socket, without the newline
+    pub(crate) fn to_line(&self) -> String {
+        match self {
+            Self::Ping => "ping".to_string(),
+            Self::Status => "status".to_string(),
+            Self::List => "list".to_string(),
+            Self::Scan => "scan".to_string(),
+            Self::Integrate(path) => format!("integrate {}", path.display()),
+            Self::Reintegrate(path) => format!("reintegrate {}", path.display()),
+            Self::Remove(path) => format!("remove {}", path.display()),
+            Self::RemoveAndDelete(path) => format!("delete {}", path.display()),
+            Self::Subscribe => "subscribe".to_string(),
+        }
+    }
+}
+
+/// Daemon status returned for the `status` command
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ControlStatus {
+    pub pid: u32,
+    pub integrated: usize,
+    pub failed: usize,
+    pub pending_events: usize,
+    pub watched_directories: Vec<PathBuf>,
+}
+
+/// A request waiting for the daemon loop to answer it
+pub struct ControlRequest {
+    pub command: ControlCommand,
+    reply: Sender<serde_json::Value>,
+}
+
+impl ControlRequest {
+    /// Send the answer back to the waiting client
+    pub fn respond(self, value: serde_json::Value) {
+        let _ = self.reply.send(value);
+    }
+
+    /// Keep the client's connection open: every value sent is written to it
+    /// as a line, until the client hangs up
+    pub fn subscribe(self) -> Sender<serde_json::Value> {
+        self.reply
+    }
+}
+
+/// The daemon's end of the control socket
+pub struct ControlSocket {
+    /// Requests waiting for the daemon loop to answer them
+    pub requests: Receiver<ControlRequest>,
+    /// Socket file bound by this process, removed on drop (systemd owns an
+    /// inherited one)
+    bound: Option<PathBuf>,
+}
+
+impl Drop for ControlSocket {
+    fn drop(&mut self) {
+        if let Some(path) = &self.bound
+            && let Err(e) = std::fs::remove_file(path)
+        {
+            debug!("Failed to remove control socket {:?}: {}", path, e);
+        }
+    }
+}
+
+/// Get the control socket path
+pub fn socket_path() -> Result<PathBuf, ControlError> {
+    let dirs = directories::BaseDirs::new().ok_or(ControlError::NoRuntimeDir)?;
+    let runtime_dir = dirs.runtime_dir().ok_or(ControlError::NoRuntimeDir)?;
+    Ok(runtime_dir.join("appimage-auto.sock"))
+}
+
+/// Take the listening socket passed by systemd, if any
+fn activated_listener() -> Option<UnixListener> {
+    let pid = std::env::var("LISTEN_PID").ok();
+    let fds = std::env::var("LISTEN_FDS").ok();
+    // Meant for this process, not the AppImages and helpers it starts
+    for var in ["LISTEN_PID", "LISTEN_FDS", "LISTEN_FDNAMES"] {
+        // SAFETY: called from the daemon loop before the control thread
+        // starts; no other thread reads or writes the environment meanwhile.
+        unsafe { std::env::remove_var(var) };
+    }
+    let pid: u32 = pid?.parse().ok()?;
+    let fds: i32 = fds?.parse().ok()?;
+    if pid != std::process::id() || fds < 1 {
+        return None;
+    }
+
+    // Keep the socket out of those as well
+    // SAFETY: fcntl on a descriptor number has no memory effects.
+    if unsafe { libc::fcntl(SD_LISTEN_FDS_START, libc::F_SETFD, libc::FD_CLOEXEC) } == -1 {
+        warn!(
+            "Failed to set close-on-exec on the activated socket: {}",
+            std::io::Error::last_os_error()
+        );
+    }
+
+    // SAFETY: systemd guarantees fd 3 is an open listening socket owned by
+    // this process when LISTEN_PID matches, and nothing else claims it.
+    Some(unsafe { UnixListener::from_raw_fd(SD_LISTEN_FDS_START) })
+}
+
+/// Start the control socket listener on a background thread.
+///
+/// Returns the receiving end of the request channel, to be polled by the
+/// daemon loop.
+pub fn spawn() -> Result<ControlSocket, ControlError> {
+    let (listener, bound) = match activated_listener() {
+        Some(listener) => {
+            info!("Using control socket from systemd socket activation");
+            (listener, None)
+        }
+        None => {
+            let path = socket_path()?;
+            // A stale socket from a previous run blocks bind()
+            if path.exists() && UnixStream::connect(&path).is_err() {
+                std::fs::remove_file(&path)?;
+            }
+            let listener = UnixListener::bind(&path)?;
+            info!("Control socket listening on {:?}", path);
+            (listener, Some(path))
+        }
+    };
+
+    let (tx, rx) = mpsc::channel();
+    thread::Builder::new()
+        .name("control".to_string())
+        .spawn(move || {
+            for stream in listener.incoming() {
+                match stream {
+                    Ok(stream) => {
+                        if let Err(e) = handle_connection(stream, &tx) {
+                            debug!("Control connection error: {}", e);
+                        }
+                    }
+                    Err(e) => warn!("Control socket accept failed: {}", e),
+                }
+            }
+        })?;
+    Ok(ControlSocket {
+        requests: rx,
+        bound,
+    })
+}
+
+/// Forward a single client command to the daemon and write back its answer
+fn handle_connection(
+    mut stream: UnixStream,
+    requests: &Sender<ControlRequest>,
+) -> Result<(), ControlError> {
+    stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+
+    let mut line = String::new();
+    BufReader::new(&stream).read_line(&mut line)?;
+
+    let response = match ControlCommand::parse(&line) {
+        Some(ControlCommand::Subscribe) => {
+            let (reply, events) = mpsc::channel();
+            let command = ControlCommand::Subscribe;
+            let _ = requests.send(ControlRequest { command, reply });
+            // Don't hold up other clients while this one listens
+            thread::Builder::new()
+                .name("control-subscriber".to_string())
+                .spawn(move || {
+                    for event in events {
+                        if writeln!(stream, "{}", event).is_err() {
+                            break;
+                        }
+                    }
+                })?;
+            return Ok(());
+        }
+        Some(command) => {
+            let (reply, answer) = mpsc::channel();
+            let _ = requests.send(ControlRequest { command, reply });
+            answer
+                .recv_timeout(REPLY_TIMEOUT)
+                .unwrap_or_else(|_| serde_json::json!({ "error": "daemon did not respond" }))
+        }
+        None => serde_json::json!({ "error": "unknown command" }),
+    };
+
+    writeln!(stream, "{}", response)?;
+    Ok(())
+}
+
+/// Send a command to the running daemon and return its JSON answer
+pub fn send(command: ControlCommand) -> Result<serde_json::Value, ControlError> {
+    let mut stream = UnixStream::connect(socket_path()?)?;
+    stream.set_read_timeout(Some(REPLY_TIMEOUT))?;
+    writeln!(stream, "{}", command.to_line())?;
+
+    let mut line = String::new();
+    BufReader::new(&stream).read_line(&mut line)?;
+    Ok(serde_json::from_str(&line)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_command() {
+        assert_eq!(ControlCommand::parse("ping\n"), Some(ControlCommand::Ping));
+        assert_eq!(ControlCommand::parse(" scan "), Some(ControlCommand::Scan));
+        assert_eq!(ControlCommand::parse("reboot"), None);
+        assert_eq!(
+            ControlCommand::parse("integrate /home/me/My Apps/App.AppImage\n"),
+            Some(ControlCommand::Integrate(PathBuf::from(
+                "/home/me/My Apps/App.AppImage"
+            )))
+        );
+        assert_eq!(ControlCommand::parse("remove "), None);
+        assert_eq!(ControlCommand::parse("ping now"), None);
+
+        for command in [
+            ControlCommand::Remove(PathBuf::from("/apps/App.AppImage")),
+            ControlCommand::RemoveAndDelete(PathBuf::from("/apps/App.AppImage")),
+            ControlCommand::Reintegrate(PathBuf::from("/apps/App.AppImage")),
+        ] {
+            assert_eq!(ControlCommand::parse(&command.to_line()), Some(command));
+        }
+    }
+
+    #[test]
+    fn test_connection_round_trip() {
+        let (tx, rx) = mpsc::channel();
+        let (client, server) = UnixStream::pair().unwrap();
+
+        let handle = thread::spawn(move || handle_connection(server, &tx));
+        writeln!(&client, "ping").unwrap();
+
+        let request = rx.recv().unwrap();
+        assert_eq!(request.command, ControlCommand::Ping);
+        request.respond(serde_json::json!("pong"));
+
+        let mut line = String::new();
+        BufReader::new(&client).read_line(&mut line).unwrap();
+        assert_eq!(line.trim(), "\"pong\"");
+        handle.join().unwrap().unwrap();
+    }
+}
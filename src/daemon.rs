@@ -1,19 +1,38 @@
 //! Main daemon event loop and coordination logic.
 
+use crate::apparmor;
 use crate::appimage;
-use crate::config::Config;
+use crate::archive;
+use crate::autostart;
+use crate::completeness::{self, Stability};
+use crate::config::{AppProfile, Config, IntegrationMode, SignaturePolicy};
+use crate::control::{self, ControlCommand, ControlRequest, ControlSocket, ControlStatus};
 use crate::desktop;
-use crate::state::{self, IntegratedAppImage, State};
-use crate::watcher::{FileEvent, FileWatcher};
+use crate::disk_space::{self, LowSpace};
+use crate::duplicates;
+use crate::extraction;
+use crate::history::{self, HistoryEntry, HistoryKind};
+use crate::inhibit::Inhibitor;
+use crate::integrity;
+use crate::links;
+use crate::notifications::{self, NotificationEvent, QuietHours};
+use crate::overrides::Overrides;
+use crate::replay::{EventRecorder, RecordedEvent};
+use crate::setup;
+use crate::signature::{self, Verification};
+use crate::state::{self, IntegratedAppImage, IntegrationError, IntegrationTiming, State};
+use crate::watcher::{EventSource, FileEvent, FileWatcher};
 use std::collections::HashMap;
+use std::collections::hash_map::RandomState;
 use std::fs;
+use std::hash::{BuildHasher, Hasher};
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::time::{Duration, Instant};
-use tempfile::TempDir;
+use std::sync::mpsc::Sender;
+use std::time::{Duration, Instant, SystemTime};
 use thiserror::Error;
-use tracing::{debug, error, info, warn};
+use tracing::{debug, error, info, info_span, instrument, warn};
 
 #[derive(Error, Debug)]
 pub enum DaemonError {
@@ -27,51 +46,229 @@ pub enum DaemonError {
     AppImage(#[from] crate::appimage::AppImageError),
     #[error("Desktop error: {0}")]
     Desktop(#[from] crate::desktop::DesktopError),
+    #[error("Signature error: {0}")]
+    Signature(#[from] crate::signature::SignatureError),
+    #[error("{0:?} is {1}")]
+    Untrusted(PathBuf, String),
+    #[error("{0:?} is already integrated")]
+    AlreadyIntegrated(PathBuf),
+    #[error("Not enough disk space: {0}")]
+    LowDiskSpace(LowSpace),
     #[error("IO error: {0}")]
     Io(#[from] std::io::Error),
 }
 
+impl DaemonError {
+    /// Whether the AppImage was locked or still being written
+    fn is_busy(&self) -> bool {
+        match self {
+            DaemonError::Io(e) => appimage::is_busy(e),
+            DaemonError::AppImage(e) => e.is_busy(),
+            _ => false,
+        }
+    }
+}
+
+/// How often the run loop checks for failed integrations due for retry
+const RETRY_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Bursts with more notifications than this are coalesced into one summary
+const COALESCE_THRESHOLD: usize = 3;
+
+/// How often the run loop checks watched directories for stale watches
+const WATCH_CHECK_INTERVAL: Duration = Duration::from_secs(10);
+
+/// How often the run loop removes stale extraction directories
+const EXTRACTION_CLEANUP_INTERVAL: Duration = Duration::from_secs(600);
+
+/// How often the end of quiet hours is checked for
+const QUIET_HOURS_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// How often the run loop looks for apps that added themselves to autostart
+const AUTOSTART_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Largest share of `watch.rescan_interval` a rescan is moved earlier or
+/// later by, so daemons started together don't rescan at once
+const RESCAN_JITTER_DIVISOR: u32 = 10;
+
+/// How often a replay checks for debounced events that are ready
+const REPLAY_TICK: Duration = Duration::from_millis(50);
+
+/// Delay before the first retry of a locked or busy AppImage, doubled on
+/// each further attempt up to `BUSY_RETRY_MAX_DELAY`
+const BUSY_RETRY_DELAY: Duration = Duration::from_secs(1);
+
+/// Longest delay between retries of a busy AppImage
+const BUSY_RETRY_MAX_DELAY: Duration = Duration::from_secs(60);
+
+/// Retries of a busy AppImage before it is recorded as a failed integration
+const BUSY_RETRY_LIMIT: u32 = 10;
+
+/// Create a missing watch directory and bookmark it, so users find where
+/// to put AppImages
+fn create_watch_directory(path: &Path) {
+    if let Err(e) = fs::create_dir_all(path) {
+        warn!("Failed to create watch directory {:?}: {}", path, e);
+        return;
+    }
+    info!("Created watch directory: {:?}", path);
+    if let Err(e) = setup::add_bookmark(path) {
+        warn!("Failed to bookmark {:?}: {}", path, e);
+    }
+}
+
+/// Delay until a scheduled rescan: the interval moved by up to a tenth of
+/// it either way, picked by `seed`
+fn rescan_delay(interval: Duration, seed: u64) -> Duration {
+    let jitter = interval / RESCAN_JITTER_DIVISOR;
+    let range = 2 * jitter.as_millis() as u64 + 1;
+    interval - jitter + Duration::from_millis(seed % range)
+}
+
+/// An event waiting for its debounce window to expire
+#[derive(Debug, Clone)]
+struct PendingEvent {
+    event: FileEvent,
+    /// When the first event for this path was queued
+    first_seen: Instant,
+    /// When the latest event for this path was queued
+    last_seen: Instant,
+    /// Not handled before this, while retrying a busy file
+    not_before: Option<Instant>,
+}
+
+impl PendingEvent {
+    fn new(event: FileEvent, now: Instant) -> Self {
+        Self {
+            event,
+            first_seen: now,
+            last_seen: now,
+            not_before: None,
+        }
+    }
+
+    /// Whether the event should be handled now: either quiet for `debounce`,
+    /// or deferred for `max_delay` in total (e.g. a slow download)
+    fn is_ready(&self, now: Instant, debounce: Duration, max_delay: Duration) -> bool {
+        self.not_before.is_none_or(|at| now >= at)
+            && (now.duration_since(self.last_seen) >= debounce
+                || now.duration_since(self.first_seen) >= max_delay)
+    }
+}
+
 /// The main daemon that watches for AppImages and integrates them
 pub struct Daemon {
     config: Config,
     state: State,
-    watcher: FileWatcher,
+    watcher: Box<dyn EventSource>,
     running: Arc<AtomicBool>,
-    /// Pending events for debouncing (path → (event, timestamp))
-    pending_events: HashMap<PathBuf, (FileEvent, Instant)>,
+    /// Pending events for debouncing
+    pending_events: HashMap<PathBuf, PendingEvent>,
+    /// Requests from the control socket (set up by `run`)
+    control: Option<ControlSocket>,
+    /// Requests from the local HTTP status API, when it is running
+    #[cfg(feature = "http-api")]
+    api: Option<std::sync::mpsc::Receiver<crate::http_api::ApiRequest>>,
+    /// Exit after this long without watch events or control requests
+    exit_idle: Option<Duration>,
+    /// Last watch event or control request
+    last_activity: Instant,
+    /// Whether a burst of events is being worked through (notifications are queued)
+    batching: bool,
+    /// Notifications held back until the current burst is done
+    queued_notifications: Vec<NotificationEvent>,
+    /// Window in which notifications are held back (`notifications.quiet_hours`)
+    quiet_hours: Option<QuietHours>,
+    /// Notifications held back until quiet hours end
+    held_notifications: Vec<NotificationEvent>,
+    /// Sizes of new files, for the `size_stable` completeness check
+    stability: Stability,
+    /// Retries so far of AppImages that were locked or still being written
+    busy_retries: HashMap<PathBuf, u32>,
+    /// Log what would be done instead of doing it
+    dry_run: bool,
+    /// Where watcher events are recorded (`logging.record_events`)
+    recorder: Option<EventRecorder>,
+    /// Modification time of the autostart directory at the last check, or
+    /// `None` before the first
+    autostart_modified: Option<Option<SystemTime>>,
+    /// Control socket clients waiting for history events
+    subscribers: Vec<Sender<serde_json::Value>>,
+    /// Held while a large batch is worked through (`watch.inhibit_sleep`)
+    inhibitor: Option<Inhibitor>,
+    /// Whether the user was told about low disk space since there last was
+    /// enough
+    low_space_notified: bool,
 }
 
 impl Daemon {
     /// Create a new daemon instance
     pub fn new() -> Result<Self, DaemonError> {
-        let config = Config::load()?.expand_paths();
-        let state = State::load()?;
-        let watcher = FileWatcher::new()?;
-
-        Ok(Self {
-            config,
-            state,
-            watcher,
-            running: Arc::new(AtomicBool::new(false)),
-            pending_events: HashMap::new(),
-        })
+        Self::with_config(Config::load()?)
     }
 
     /// Create a daemon with a specific config
     pub fn with_config(config: Config) -> Result<Self, DaemonError> {
-        let config = config.expand_paths();
+        Self::with_source(config, FileWatcher::new()?)
+    }
+
+    /// Create a daemon with a specific config that takes its file events
+    /// from `source` instead of watching the file system
+    pub fn with_source(
+        config: Config,
+        source: impl EventSource + 'static,
+    ) -> Result<Self, DaemonError> {
+        let config = config.with_profile().expand_paths();
         let state = State::load()?;
-        let watcher = FileWatcher::new()?;
+        let quiet_hours = config.notifications.quiet_hours.as_deref().and_then(|s| {
+            let quiet_hours = QuietHours::parse(s);
+            if quiet_hours.is_none() {
+                warn!(
+                    "Ignoring quiet hours {:?}, expected e.g. \"22:00-08:00\"",
+                    s
+                );
+            }
+            quiet_hours
+        });
 
         Ok(Self {
             config,
             state,
-            watcher,
+            watcher: Box::new(source),
             running: Arc::new(AtomicBool::new(false)),
             pending_events: HashMap::new(),
+            control: None,
+            #[cfg(feature = "http-api")]
+            api: None,
+            exit_idle: None,
+            last_activity: Instant::now(),
+            batching: false,
+            queued_notifications: Vec::new(),
+            quiet_hours,
+            held_notifications: Vec::new(),
+            stability: Stability::default(),
+            busy_retries: HashMap::new(),
+            dry_run: false,
+            recorder: None,
+            autostart_modified: None,
+            subscribers: Vec::new(),
+            inhibitor: None,
+            low_space_notified: false,
         })
     }
 
+    /// Exit the run loop after `idle` without watch events or control requests
+    pub fn set_exit_idle(&mut self, idle: Option<Duration>) {
+        self.exit_idle = idle;
+    }
+
+    /// Only log integrations, removals, moves and cleanups instead of
+    /// carrying them out, keeping state changes in memory
+    pub fn set_dry_run(&mut self, dry_run: bool) {
+        self.dry_run = dry_run;
+        self.state.set_read_only(dry_run);
+    }
+
     /// Get a handle to the running flag for signal handling
     pub fn running_flag(&self) -> Arc<AtomicBool> {
         Arc::clone(&self.running)
@@ -80,25 +277,47 @@ impl Daemon {
     /// Initialize the daemon: set up watches and optionally scan existing files
     pub fn init(&mut self) -> Result<(), DaemonError> {
         info!("Initializing daemon...");
+        if let Some(session) = self.config.desktop_environment.current_session() {
+            info!("Desktop session: {:?}", session);
+        }
 
         // Ensure directories exist
-        let desktop_dir = self.config.desktop_directory();
-        let icon_dir = self.config.icon_directory();
-        fs::create_dir_all(&desktop_dir)?;
-        fs::create_dir_all(&icon_dir)?;
+        if !self.dry_run {
+            fs::create_dir_all(self.config.desktop_directory())?;
+            fs::create_dir_all(self.config.icon_directory())?;
+        }
 
         // Set up file watches
         for dir in &self.config.watch.directories {
             let path = PathBuf::from(dir);
+            if !path.exists() && self.config.watch.create_directories && !self.dry_run {
+                create_watch_directory(&path);
+            }
             if path.exists() {
                 if let Err(e) = self.watcher.watch(&path) {
                     warn!("Failed to watch {:?}: {}", path, e);
                 }
             } else {
-                warn!("Watch directory does not exist: {:?}", path);
+                warn!(
+                    "Watch directory does not exist, will watch once created: {:?}",
+                    path
+                );
             }
         }
 
+        // Start the local status API if configured
+        #[cfg(feature = "http-api")]
+        if self.config.api.enabled {
+            match crate::http_api::spawn(self.config.api.port) {
+                Ok(requests) => self.api = Some(requests),
+                Err(e) => warn!("Failed to start HTTP status API: {}", e),
+            }
+        }
+        #[cfg(not(feature = "http-api"))]
+        if self.config.api.enabled {
+            warn!("HTTP status API enabled in config but not compiled in (feature `http-api`)");
+        }
+
         // Scan for existing AppImages if configured
         if self.config.integration.scan_on_startup {
             self.scan_existing()?;
@@ -106,52 +325,267 @@ impl Daemon {
 
         // Clean up orphaned entries
         self.cleanup_orphaned()?;
+        self.cleanup_extractions();
+        self.check_autostart();
 
         info!("Daemon initialized");
         Ok(())
     }
 
+    /// When the next scheduled rescan is due, if `watch.rescan_interval` is set
+    fn next_rescan(&self) -> Option<Instant> {
+        let interval = self.config.watch.rescan_interval;
+        (interval > 0).then(|| {
+            let seed = RandomState::new().build_hasher().finish();
+            Instant::now() + rescan_delay(Duration::from_secs(interval), seed)
+        })
+    }
+
+    /// Scan the watched directories again and clean up orphaned entries
+    fn rescan(&mut self) {
+        debug!("Scheduled rescan of watched directories");
+        if let Err(e) = self.scan_existing() {
+            warn!("Scheduled rescan failed: {}", e);
+        }
+        if let Err(e) = self.cleanup_orphaned() {
+            warn!("Failed to clean up orphaned entries: {}", e);
+        }
+    }
+
+    /// Remove extraction directories older than `integration.extract_ttl_secs`
+    fn cleanup_extractions(&self) {
+        if self.dry_run {
+            return;
+        }
+        let ttl = Duration::from_secs(self.config.integration.extract_ttl_secs);
+        let removed = extraction::cleanup_stale(ttl);
+        if removed > 0 {
+            info!("Removed {} stale extraction directories", removed);
+        }
+    }
+
+    /// Record the autostart entries integrated apps created for themselves,
+    /// disabling new ones if `integration.block_autostart` is set. Skipped
+    /// while the autostart directory is unchanged.
+    fn check_autostart(&mut self) {
+        let Some(dir) = autostart::user_dir() else {
+            return;
+        };
+        let modified = fs::metadata(&dir).and_then(|m| m.modified()).ok();
+        if self.autostart_modified == Some(modified) {
+            return;
+        }
+        self.autostart_modified = Some(modified);
+
+        let apps: Vec<IntegratedAppImage> = self.state.all().cloned().collect();
+        let mut found = autostart::scan(&dir, &apps);
+        let mut changed = false;
+        for app in &apps {
+            let entries = found.remove(&app.identifier).unwrap_or_default();
+            let known = &app.autostart_entries;
+            for entry in entries.iter().filter(|e| !known.contains(e)) {
+                warn!(
+                    "{:?} added itself to autostart: {:?}",
+                    app.appimage_path, entry
+                );
+                if !self.config.integration.block_autostart {
+                    continue;
+                }
+                if self.dry_run {
+                    info!("Dry run: would disable autostart entry {:?}", entry);
+                    continue;
+                }
+                match autostart::block(entry) {
+                    Ok(true) => info!("Disabled autostart entry {:?}", entry),
+                    Ok(false) => {}
+                    Err(e) => warn!("Failed to disable autostart entry {:?}: {}", entry, e),
+                }
+            }
+            let path = &app.appimage_path;
+            changed |= self.state.set_autostart_entries(path, entries);
+        }
+
+        if changed
+            && !self.dry_run
+            && let Err(e) = self.state.save()
+        {
+            warn!("Failed to save state: {}", e);
+        }
+    }
+
     /// Scan watched directories for existing AppImages
     pub fn scan_existing(&mut self) -> Result<(), DaemonError> {
         info!("Scanning for existing AppImages...");
+        let started = Instant::now();
+        let mut integrated = 0;
 
+        self.batching = true;
         for dir in self.watcher.watched_directories().to_vec() {
-            if let Ok(entries) = fs::read_dir(&dir) {
-                for entry in entries.filter_map(|e| e.ok()) {
-                    let path = entry.path();
-                    if path.is_file()
-                        && appimage::is_appimage(&path)
-                        && !self.state.is_integrated(&path)
-                    {
-                        info!("Found existing AppImage: {:?}", path);
-                        if let Err(e) = self.integrate(&path) {
-                            warn!("Failed to integrate {:?}: {}", path, e);
-                        }
-                    }
-                }
+            integrated += self.scan_directory(&dir);
+        }
+        self.end_batch();
+
+        if integrated > 0 {
+            info!(
+                "Scan integrated {} AppImages in {}ms",
+                integrated,
+                millis(started.elapsed())
+            );
+            if let Some(slowest) = self.state.slowest(1).first()
+                && let Some(timing) = &slowest.timing
+            {
+                info!(
+                    "Slowest integration: {:?} ({}ms)",
+                    slowest.appimage_path, timing.total_ms
+                );
             }
         }
 
         Ok(())
     }
 
-    /// Clean up orphaned state entries (AppImages that no longer exist)
+    /// Integrate AppImages in a single directory that are not yet integrated.
+    ///
+    /// Returns the number of newly integrated AppImages.
+    fn scan_directory(&mut self, dir: &Path) -> usize {
+        let mut integrated = 0;
+
+        let is_new = |daemon: &Self, path: &Path| {
+            !daemon.state.is_integrated(path) && !daemon.state.is_alias(path)
+        };
+        let paths = self.handled_paths(appimage::scan_directory(dir).unwrap_or_default());
+        if self.batching {
+            let pending = paths.iter().filter(|path| is_new(self, path)).count();
+            self.inhibit_sleep(pending);
+        }
+        for path in paths {
+            // May have been handled along with an earlier one
+            if !is_new(self, &path) {
+                continue;
+            }
+            info!("Found existing AppImage: {:?}", path);
+            match self.discover(&path) {
+                Ok(true) => integrated += 1,
+                Ok(false) => {}
+                Err(e)
+                    if e.is_busy() && self.retry_busy(&path, FileEvent::Created(path.clone())) => {}
+                Err(e) => warn!("Failed to integrate {:?}: {}", path, e),
+            }
+        }
+
+        integrated
+    }
+
+    /// Scan directories once without watching them, reporting progress.
+    ///
+    /// `progress` is called before each AppImage that is not yet integrated
+    /// is handled, with its position, the total and its path. New AppImages
+    /// are integrated or held for approval depending on the integration mode.
+    /// Returns the paths that were integrated.
+    pub fn scan_directories(
+        &mut self,
+        dirs: &[PathBuf],
+        mut progress: impl FnMut(usize, usize, &Path),
+    ) -> Vec<PathBuf> {
+        let paths: Vec<PathBuf> = self
+            .handled_paths(
+                dirs.iter()
+                    .flat_map(|dir| appimage::scan_directory(dir).unwrap_or_default())
+                    .collect(),
+            )
+            .into_iter()
+            .filter(|path| !self.state.is_integrated(path) && !self.state.is_alias(path))
+            .collect();
+
+        let mut integrated = Vec::new();
+        self.batching = true;
+        self.inhibit_sleep(paths.len());
+        for (i, path) in paths.iter().enumerate() {
+            progress(i, paths.len(), path);
+            match self.discover(path) {
+                Ok(true) => integrated.push(path.clone()),
+                Ok(false) => {}
+                Err(e) => warn!("Failed to integrate {:?}: {}", path, e),
+            }
+        }
+        self.end_batch();
+
+        integrated
+    }
+
+    /// Re-arm watches on recreated directories, start watching configured
+    /// directories created since startup, and scan both
+    fn check_watches(&mut self) {
+        for dir in self.watcher.rearm_stale() {
+            let integrated = self.scan_directory(&dir);
+            if integrated > 0 {
+                info!("Integrated {} AppImages in recreated {:?}", integrated, dir);
+            }
+        }
+
+        for dir in self.unwatched_directories() {
+            if !dir.is_dir() {
+                continue;
+            }
+            if let Err(e) = self.watcher.watch(&dir) {
+                warn!("Failed to watch {:?}: {}", dir, e);
+                continue;
+            }
+            info!("Watch directory appeared: {:?}", dir);
+            let integrated = self.scan_directory(&dir);
+            if integrated > 0 {
+                info!("Integrated {} AppImages in new {:?}", integrated, dir);
+            }
+        }
+    }
+
+    /// Configured watch directories that are not currently watched
+    fn unwatched_directories(&self) -> Vec<PathBuf> {
+        let watched = self.watcher.watched_directories();
+        self.config
+            .watch
+            .directories
+            .iter()
+            .map(PathBuf::from)
+            .filter(|dir| !watched.contains(dir))
+            .collect()
+    }
+
+    /// Clean up orphaned state entries (AppImages that no longer exist).
+    ///
+    /// Pinned entries are kept, e.g. for AppImages on removable drives.
     pub fn cleanup_orphaned(&mut self) -> Result<(), DaemonError> {
         let orphaned: Vec<String> = self
             .state
             .find_orphaned()
             .iter()
+            .filter(|info| !info.pinned)
             .map(|info| info.identifier.clone())
             .collect();
 
-        for id in orphaned {
+        for id in &orphaned {
+            if let Some(path) = self.state.get(id).map(|info| info.appimage_path.clone())
+                && let Some(alias) = self.state.promote_alias(&path)
+            {
+                info!("Keeping orphaned entry {} through {:?}", id, alias);
+                self.handle_move(&path, &alias)?;
+                continue;
+            }
+            if self.dry_run {
+                info!("Dry run: would clean up orphaned entry: {}", id);
+                self.state.remove(id);
+                continue;
+            }
             info!("Cleaning up orphaned entry: {}", id);
-            if let Some(info) = self.state.remove(&id) {
+            if let Some(info) = self.state.remove(id) {
                 self.cleanup_integration(&info)?;
             }
         }
 
-        if !self.state.find_orphaned().is_empty() {
+        let awaiting_before = self.state.awaiting_approval.len();
+        self.state.awaiting_approval.retain(|path, _| path.exists());
+
+        if !orphaned.is_empty() || self.state.awaiting_approval.len() != awaiting_before {
             self.state.save()?;
         }
 
@@ -167,31 +601,108 @@ impl Daemon {
             self.config.watch.debounce_ms
         );
 
+        // Left to a daemon that may be running for real
+        if self.dry_run {
+            info!("Dry run: not starting the control socket");
+        } else {
+            match control::spawn() {
+                Ok(socket) => self.control = Some(socket),
+                Err(e) => warn!("Failed to start control socket: {}", e),
+            }
+        }
+        if let Some(idle) = self.exit_idle {
+            info!("Exiting after {}s without activity", idle.as_secs());
+        }
+        let applications_dir = &self.config.integration.applications_dir;
+        if !self.config.integration.quarantine_dirs.is_empty()
+            && !self.config.watch.directories.contains(applications_dir)
+        {
+            warn!(
+                "{} is not watched: AppImages moved there out of quarantine won't be integrated",
+                applications_dir
+            );
+        }
+        if let Some(path) = &self.config.logging.record_events {
+            match EventRecorder::open(Path::new(path)) {
+                Ok(recorder) => {
+                    info!("Recording watcher events to {:?}", path);
+                    self.recorder = Some(recorder);
+                }
+                Err(e) => warn!("Failed to open event recording {:?}: {}", path, e),
+            }
+        }
+
+        let mut last_retry_check = Instant::now();
+        let mut last_watch_check = Instant::now();
+        let mut last_extraction_cleanup = Instant::now();
+        let mut last_quiet_hours_check = Instant::now();
+        let mut last_autostart_check = Instant::now();
+        let mut next_rescan = self.next_rescan();
+        self.last_activity = Instant::now();
+
         while self.running.load(Ordering::SeqCst) {
             // Check for new events
-            match self.watcher.next_event_timeout(Duration::from_millis(100)) {
-                Ok(Some(event)) => {
-                    self.queue_event(event);
-                }
-                Ok(None) => {
-                    // Timeout, continue loop
-                }
-                Err(e) => {
-                    error!("Watcher error: {}", e);
-                    break;
-                }
+            if let Err(e) = self.receive_event(Duration::from_millis(100)) {
+                error!("{}", e);
+                break;
             }
 
+            self.handle_control_requests();
+            #[cfg(feature = "http-api")]
+            self.handle_api_requests();
+
             // Process debounced events that are ready
             if let Err(e) = self.process_pending_events() {
                 error!("Error processing pending events: {}", e);
             }
 
+            // Re-arm stale watches and pick up newly created watch directories
+            if last_watch_check.elapsed() >= WATCH_CHECK_INTERVAL {
+                last_watch_check = Instant::now();
+                self.check_watches();
+            }
+
+            // Retry failed integrations whose backoff has expired
+            if last_retry_check.elapsed() >= RETRY_CHECK_INTERVAL {
+                last_retry_check = Instant::now();
+                self.retry_failed();
+            }
+
+            // Remove extraction directories left by interrupted integrations
+            if last_extraction_cleanup.elapsed() >= EXTRACTION_CLEANUP_INTERVAL {
+                last_extraction_cleanup = Instant::now();
+                self.cleanup_extractions();
+            }
+
+            if last_quiet_hours_check.elapsed() >= QUIET_HOURS_CHECK_INTERVAL {
+                last_quiet_hours_check = Instant::now();
+                self.send_held_notifications();
+            }
+
+            if last_autostart_check.elapsed() >= AUTOSTART_CHECK_INTERVAL {
+                last_autostart_check = Instant::now();
+                self.check_autostart();
+            }
+
+            // Catch events missed while suspended or on watcher overflow
+            if next_rescan.is_some_and(|at| Instant::now() >= at) {
+                self.rescan();
+                next_rescan = self.next_rescan();
+            }
+
             // Reload state if modified externally (e.g., by the GUI)
-            if self.state.modified_externally() {
-                if let Err(e) = self.state.reload() {
-                    warn!("Failed to reload state from disk: {}", e);
-                }
+            if self.state.modified_externally()
+                && let Err(e) = self.state.reload()
+            {
+                warn!("Failed to reload state from disk: {}", e);
+            }
+
+            if let Some(idle) = self.exit_idle
+                && self.pending_events.is_empty()
+                && self.last_activity.elapsed() >= idle
+            {
+                info!("No activity for {}s, exiting", idle.as_secs());
+                break;
             }
         }
 
@@ -199,6 +710,139 @@ impl Daemon {
         Ok(())
     }
 
+    /// Answer any requests waiting on the control socket
+    fn handle_control_requests(&mut self) {
+        let Some(control) = &self.control else {
+            return;
+        };
+        let pending: Vec<ControlRequest> = control.requests.try_iter().collect();
+
+        let done = |result: Result<(), DaemonError>| match result {
+            Ok(()) => serde_json::json!({ "ok": true }),
+            Err(e) => serde_json::json!({ "error": e.to_string() }),
+        };
+        for request in pending {
+            self.last_activity = Instant::now();
+            debug!("Control request: {:?}", request.command);
+            if request.command == ControlCommand::Subscribe {
+                self.subscribers.push(request.subscribe());
+                continue;
+            }
+            let response = match &request.command {
+                ControlCommand::Ping => serde_json::json!("pong"),
+                ControlCommand::Status => serde_json::to_value(ControlStatus {
+                    pid: std::process::id(),
+                    integrated: self.state.count(),
+                    failed: self.state.failures().count(),
+                    pending_events: self.pending_events.len(),
+                    watched_directories: self.watcher.watched_directories().to_vec(),
+                })
+                .unwrap_or_default(),
+                ControlCommand::List => {
+                    serde_json::to_value(self.state.all().collect::<Vec<_>>()).unwrap_or_default()
+                }
+                ControlCommand::Scan => match self.scan_existing() {
+                    Ok(()) => serde_json::json!({ "integrated": self.state.count() }),
+                    Err(e) => serde_json::json!({ "error": e.to_string() }),
+                },
+                ControlCommand::Integrate(path) => done(self.integrate(path)),
+                ControlCommand::Reintegrate(path) => done(self.reintegrate(path)),
+                ControlCommand::Remove(path) => done(self.remove(path, false)),
+                ControlCommand::RemoveAndDelete(path) => done(self.remove(path, true)),
+                ControlCommand::Subscribe => unreachable!("handled above"),
+            };
+            request.respond(response);
+        }
+    }
+
+    /// Answer any requests waiting from the HTTP status API
+    #[cfg(feature = "http-api")]
+    fn handle_api_requests(&mut self) {
+        let Some(requests) = &self.api else {
+            return;
+        };
+        for request in requests.try_iter() {
+            request.respond(&self.config, &self.state);
+        }
+    }
+
+    /// Append an event to the history and pass it on to subscribed clients
+    fn record_history(&mut self, entry: HistoryEntry) {
+        if !self.subscribers.is_empty() {
+            let value = serde_json::to_value(&entry).unwrap_or_default();
+            // Clients that hung up are dropped
+            self.subscribers
+                .retain(|subscriber| subscriber.send(value.clone()).is_ok());
+        }
+        history::record(entry);
+    }
+
+    /// Wait up to `timeout` for an event from the source and queue it.
+    ///
+    /// Returns whether an event arrived.
+    fn receive_event(&mut self, timeout: Duration) -> Result<bool, DaemonError> {
+        match self.watcher.next_event_timeout(timeout)? {
+            Some(event) => {
+                self.last_activity = Instant::now();
+                if let Some(recorder) = &mut self.recorder
+                    && let Err(e) = recorder.record(&event)
+                {
+                    warn!("Failed to record event: {}", e);
+                }
+                self.queue_event(event);
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    /// Feed recorded events through event handling, spaced as they arrived.
+    ///
+    /// Gaps longer than the debounce window are shortened, as the pending
+    /// events are handled during them either way. Meant for dry runs (see
+    /// [`Self::set_dry_run`]), to reproduce how a recording was handled.
+    pub fn replay(&mut self, events: Vec<RecordedEvent>) -> Result<(), DaemonError> {
+        let debounce = Duration::from_millis(self.config.watch.debounce_ms);
+        let max_gap = debounce + 2 * REPLAY_TICK;
+        let mut previous = None;
+
+        for recorded in events {
+            if let Some(previous) = previous {
+                let gap = Duration::from_millis(recorded.at_ms.saturating_sub(previous));
+                self.replay_wait(gap.min(max_gap))?;
+            }
+            previous = Some(recorded.at_ms);
+            info!("Replaying {:?}", recorded.event);
+            self.queue_event(recorded.event);
+            self.process_pending_events()?;
+        }
+
+        // Let the last debounce windows expire; files that stay incomplete
+        // would be re-queued forever
+        let max_delay = Duration::from_millis(self.config.watch.max_debounce_ms);
+        let deadline = Instant::now() + max_gap.max(max_delay);
+        while !self.pending_events.is_empty() && Instant::now() < deadline {
+            self.replay_wait(REPLAY_TICK)?;
+        }
+        for path in self.pending_events.keys() {
+            warn!("Still pending at the end of the replay: {:?}", path);
+        }
+        Ok(())
+    }
+
+    /// Wait for `duration`, handling debounced events as they become ready
+    fn replay_wait(&mut self, duration: Duration) -> Result<(), DaemonError> {
+        let until = Instant::now() + duration;
+        loop {
+            self.process_pending_events()?;
+            let now = Instant::now();
+            if now >= until {
+                return Ok(());
+            }
+            std::thread::sleep(REPLAY_TICK.min(until - now));
+        }
+    }
+
     /// Queue an event for debounced processing
     fn queue_event(&mut self, event: FileEvent) {
         let now = Instant::now();
@@ -207,12 +851,32 @@ impl Daemon {
             // Debounce Created and Modified events
             FileEvent::Created(path) | FileEvent::Modified(path) => {
                 debug!("Queuing event for debounce: {:?}", path);
-                self.pending_events.insert(path.clone(), (event, now));
+                match self.pending_events.get_mut(path) {
+                    Some(pending) => {
+                        pending.last_seen = now;
+                        // A Modified event must not mask a pending Created
+                        if matches!(event, FileEvent::Created(_)) {
+                            pending.event = event;
+                        }
+                    }
+                    None => {
+                        self.pending_events
+                            .insert(path.clone(), PendingEvent::new(event, now));
+                    }
+                }
             }
-            // Process Deleted and Moved immediately (no debounce needed)
-            FileEvent::Deleted(_) | FileEvent::Moved { .. } => {
+            // Process Deleted, Moved and Resync immediately (no debounce needed)
+            FileEvent::Deleted(_) | FileEvent::Moved { .. } | FileEvent::Resync(_) => {
+                let paths = match &event {
+                    FileEvent::Moved { from, to } => vec![to.clone(), from.clone()],
+                    FileEvent::Deleted(path) => vec![path.clone()],
+                    _ => Vec::new(),
+                };
                 if let Err(e) = self.handle_event(event) {
                     error!("Error handling event: {}", e);
+                    if let Some(path) = paths.iter().find(|p| self.state.is_integrated(p)) {
+                        self.record_error(path, &e);
+                    }
                 }
             }
         }
@@ -221,119 +885,740 @@ impl Daemon {
     /// Process pending events that have exceeded the debounce duration
     fn process_pending_events(&mut self) -> Result<(), DaemonError> {
         let debounce_duration = Duration::from_millis(self.config.watch.debounce_ms);
+        let max_delay = Duration::from_millis(self.config.watch.max_debounce_ms);
         let now = Instant::now();
 
-        // Collect ready events (elapsed >= debounce_duration)
-        let ready: Vec<_> = self
+        // Collect ready events (quiet for the debounce window, or deferred too long)
+        let mut ready: Vec<_> = self
             .pending_events
             .iter()
-            .filter(|(_, (_, timestamp))| now.duration_since(*timestamp) >= debounce_duration)
-            .map(|(path, (event, _))| (path.clone(), event.clone()))
+            .filter(|(_, pending)| pending.is_ready(now, debounce_duration, max_delay))
+            .map(|(path, pending)| (pending.first_seen, path.clone(), pending.event.clone()))
             .collect();
+        if ready.is_empty() {
+            // The rest of a batch can vanish, deleted or moved away before
+            // its turn; its inhibitor lock mustn't outlive it
+            if self.batching || self.inhibitor.is_some() {
+                self.end_batch();
+            }
+            return Ok(());
+        }
+
+        // Handle at most one batch per loop iteration, oldest first, so a storm
+        // of new files doesn't starve the watcher and control socket
+        let batch_size = self.config.watch.batch_size.max(1);
+        self.inhibit_sleep(ready.len());
+        let backlog = ready.len().saturating_sub(batch_size);
+        ready.sort_by_key(|(first_seen, _, _)| *first_seen);
+        ready.truncate(batch_size);
+        if backlog > 0 {
+            debug!(
+                "Handling {} events, {} left for later batches",
+                batch_size, backlog
+            );
+        }
 
         // Process and remove ready events
-        for (path, event) in ready {
+        self.batching = true;
+        for (_, path, event) in ready {
             self.pending_events.remove(&path);
             if let Err(e) = self.handle_event(event) {
                 error!("Error handling debounced event for {:?}: {}", path, e);
+                self.record_error(&path, &e);
             }
         }
+        if backlog == 0 {
+            self.end_batch();
+        }
+
+        Ok(())
+    }
+
+    /// Keep the error of an integrated AppImage in the state, so the GUI
+    /// can show it instead of it only living in the log
+    fn record_error(&mut self, path: &Path, e: &DaemonError) {
+        if self.dry_run {
+            return;
+        }
+        let error = IntegrationError::new(e.to_string());
+        if self.state.set_last_error(path, Some(error))
+            && let Err(e) = self.state.save()
+        {
+            warn!("Failed to save state: {}", e);
+        }
+    }
+
+    /// Handle the event for a locked or busy AppImage again after a delay
+    /// growing with each attempt.
+    ///
+    /// Returns false once `BUSY_RETRY_LIMIT` is reached, leaving the failure
+    /// to the usual retries of failed integrations.
+    fn retry_busy(&mut self, path: &Path, event: FileEvent) -> bool {
+        let attempts = self.busy_retries.entry(path.to_path_buf()).or_default();
+        if *attempts >= BUSY_RETRY_LIMIT {
+            warn!("{:?} still busy after {} retries", path, attempts);
+            self.busy_retries.remove(path);
+            return false;
+        }
+        *attempts += 1;
+        let delay = busy_retry_delay(*attempts);
+        info!(
+            "{:?} is locked or still being written, retrying in {}s",
+            path,
+            delay.as_secs()
+        );
+
+        let now = Instant::now();
+        let mut pending = PendingEvent::new(event, now);
+        pending.not_before = Some(now + delay);
+        self.pending_events.insert(path.to_path_buf(), pending);
+        true
+    }
+
+    /// Send a notification, or queue it while a burst is being processed
+    fn notify(&mut self, event: NotificationEvent) {
+        if self.dry_run {
+            debug!("Dry run, not sending notification");
+        } else if self.batching {
+            self.queued_notifications.push(event);
+        } else {
+            self.deliver(event);
+        }
+    }
+
+    /// Keep the machine awake until the current batch ends if `pending`
+    /// AppImages reach `watch.inhibit_sleep`
+    fn inhibit_sleep(&mut self, pending: usize) {
+        let threshold = self.config.watch.inhibit_sleep;
+        if self.inhibitor.is_some() || self.dry_run || threshold == 0 || pending < threshold {
+            return;
+        }
+        let why = format!("Integrating {} AppImages", pending);
+        match Inhibitor::acquire(&why) {
+            Ok(inhibitor) => {
+                debug!("Inhibiting sleep: {}", why);
+                self.inhibitor = Some(inhibitor);
+            }
+            Err(e) => debug!("Could not inhibit sleep: {}", e),
+        }
+    }
+
+    /// Finish a batch: release the sleep inhibitor and send the notifications
+    /// held back during it
+    fn end_batch(&mut self) {
+        self.batching = false;
+        if self.inhibitor.take().is_some() {
+            debug!("Batch done, no longer inhibiting sleep");
+        }
+        self.flush_notifications();
+    }
+
+    /// Send queued notifications, coalescing bursts into a summary
+    fn flush_notifications(&mut self) {
+        let queued = std::mem::take(&mut self.queued_notifications);
+        for event in notifications::coalesce(queued, COALESCE_THRESHOLD) {
+            self.deliver(event);
+        }
+    }
+
+    /// Send a notification, unless it is quiet hours: then it is held for
+    /// the summary sent when they end (or dropped without one)
+    fn deliver(&mut self, event: NotificationEvent) {
+        if !self.quiet_hours.is_some_and(|q| q.is_now()) {
+            notifications::send(event, &self.config.notifications);
+        } else if self.config.notifications.quiet_hours_summary {
+            debug!("Quiet hours, holding notification");
+            self.held_notifications.push(event);
+        } else {
+            debug!("Quiet hours, dropping notification");
+        }
+    }
+
+    /// Send the notifications held back in quiet hours once they are over,
+    /// as one summary
+    fn send_held_notifications(&mut self) {
+        if self.held_notifications.is_empty() || self.quiet_hours.is_some_and(|q| q.is_now()) {
+            return;
+        }
+        info!(
+            "Quiet hours over, sending {} held notifications",
+            self.held_notifications.len()
+        );
+        let held = std::mem::take(&mut self.held_notifications);
+        for event in notifications::coalesce(held, 1) {
+            notifications::send(event, &self.config.notifications);
+        }
+    }
+
+    /// Handle a file system event
+    fn handle_event(&mut self, event: FileEvent) -> Result<(), DaemonError> {
+        match event {
+            FileEvent::Created(ref path) | FileEvent::Modified(ref path) => {
+                let Some(path) = &self.handled_path(path) else {
+                    return Ok(());
+                };
+                let created = matches!(event, FileEvent::Created(_));
+                debug!(
+                    "File {}: {:?}",
+                    if created { "created" } else { "modified" },
+                    path
+                );
+                // Modifications only matter for integrated AppImages (in-place updates)
+                if !created && !self.state.is_integrated(path) {
+                    return Ok(());
+                }
+                if created && self.config.watch.extract_archives && archive::is_archive(path) {
+                    return self.extract_archive(path);
+                }
+                if appimage::is_appimage(path) {
+                    // Check if file is complete before integrating
+                    match completeness::is_complete(
+                        path,
+                        &self.config.watch.completeness,
+                        &mut self.stability,
+                        Duration::from_millis(self.config.watch.stable_ms),
+                    ) {
+                        Ok(true) => {
+                            info!("Complete AppImage detected: {:?}", path);
+                            // Integrates new files, re-integrates replaced ones
+                            match self.refresh(path) {
+                                Err(e) if e.is_busy() && self.retry_busy(path, event.clone()) => {}
+                                result => {
+                                    self.busy_retries.remove(path);
+                                    result?;
+                                }
+                            }
+                        }
+                        Ok(false) => {
+                            debug!("AppImage incomplete, re-queuing: {:?}", path);
+                            // Re-queue for later check
+                            self.pending_events
+                                .insert(path.clone(), PendingEvent::new(event, Instant::now()));
+                        }
+                        Err(e) if e.is_busy() && self.retry_busy(path, event.clone()) => {}
+                        Err(e) => {
+                            warn!("Could not verify completeness for {:?}: {}", path, e);
+                            // Try integration anyway (fallback to previous behavior)
+                            info!("AppImage detected (unverified): {:?}", path);
+                            self.refresh(path)?;
+                        }
+                    }
+                }
+            }
+
+            FileEvent::Deleted(path) => {
+                debug!("File deleted: {:?}", path);
+                self.pending_events.remove(&path);
+                self.stability.forget(&path);
+                self.busy_retries.remove(&path);
+                let failed = self.state.clear_failure(&path).is_some();
+                let alias = self.state.remove_alias(&path).is_some();
+                if self.state.remove_awaiting(&path) || failed || alias {
+                    self.state.save()?;
+                }
+                if self.state.is_integrated(&path) {
+                    if let Some(alias) = self.state.promote_alias(&path) {
+                        info!("Integrated AppImage deleted, keeping {:?}", alias);
+                        self.handle_move(&path, &alias)?;
+                    } else {
+                        info!("Integrated AppImage deleted: {:?}", path);
+                        self.unintegrate(&path)?;
+                    }
+                }
+            }
+
+            FileEvent::Moved { from, to } => {
+                debug!("File moved: {:?} -> {:?}", from, to);
+                self.pending_events.remove(&from);
+                let failed = self.state.clear_failure(&from).is_some();
+                // Moved aliases are found again through `to`
+                let alias = self.state.remove_alias(&from).is_some();
+                if self.state.remove_awaiting(&from) || failed || alias {
+                    self.state.save()?;
+                }
+                if self.state.is_integrated(&from) {
+                    info!("Integrated AppImage moved: {:?} -> {:?}", from, to);
+                    self.handle_move(&from, &to)?;
+                } else if self.config.watch.extract_archives && archive::is_archive(&to) {
+                    self.extract_archive(&to)?;
+                } else if appimage::is_appimage(&to)
+                    && let Some(to) = self.handled_path(&to)
+                {
+                    // Moved in from outside watched dirs
+                    info!("AppImage moved into watched directory: {:?}", to);
+                    // May replace an integrated AppImage (atomic rename over it,
+                    // or a symlink flipped to a new target)
+                    self.refresh(&to)?;
+                }
+            }
+
+            FileEvent::Resync(dirs) => self.resync(&dirs)?,
+        }
+
+        Ok(())
+    }
+
+    /// Scan directories the watcher dropped events for, integrating missed
+    /// AppImages and cleaning up entries of ones deleted meanwhile
+    fn resync(&mut self, dirs: &[PathBuf]) -> Result<(), DaemonError> {
+        info!("Resyncing {} directories after dropped events", dirs.len());
+        let mut integrated = 0;
+        self.batching = true;
+        for dir in dirs {
+            integrated += self.scan_directory(dir);
+        }
+        self.end_batch();
+        if integrated > 0 {
+            info!("Resync integrated {} missed AppImages", integrated);
+        }
+        self.cleanup_orphaned()
+    }
+
+    /// Extract the AppImage from an archive (see [`archive`]) and integrate it
+    fn extract_archive(&mut self, path: &Path) -> Result<(), DaemonError> {
+        let dest_dir = match &self.config.watch.archive_destination {
+            Some(dir) => PathBuf::from(dir),
+            None => path.parent().unwrap_or(Path::new("/")).to_path_buf(),
+        };
+        if self.dry_run {
+            info!("Dry run: would extract {:?} into {:?}", path, dest_dir);
+            return Ok(());
+        }
+        // The AppImage is about as large as the archive, its squashfs being
+        // compressed already
+        let size = fs::metadata(path).map(|m| m.len()).unwrap_or_default();
+        if let Err(e) = self.check_disk_space(path, std::slice::from_ref(&dest_dir), size) {
+            warn!("Not extracting {:?}: {}", path, e);
+            return Ok(());
+        }
+
+        match archive::extract_appimage(path, &dest_dir) {
+            Ok(appimage) => {
+                info!("Extracted {:?} from {:?}", appimage, path);
+                self.refresh(&appimage)
+            }
+            Err(e) => {
+                warn!("Not extracting {:?}: {}", path, e);
+                Ok(())
+            }
+        }
+    }
+
+    /// The path an AppImage is handled under.
+    ///
+    /// With `follow_symlinks`, a file that a symlink points at (an integrated
+    /// one, or one in the same directory) is handled as that symlink, so it
+    /// gets a single launcher. Without, symlinks are skipped.
+    fn handled_path(&self, path: &Path) -> Option<PathBuf> {
+        let is_symlink = fs::symlink_metadata(path).is_ok_and(|m| m.file_type().is_symlink());
+        if is_symlink && !self.config.watch.follow_symlinks {
+            debug!("Ignoring symlink: {:?}", path);
+            return None;
+        }
+        if is_symlink || !self.config.watch.follow_symlinks {
+            return Some(path.to_path_buf());
+        }
+        Some(self.symlink_to(path).unwrap_or_else(|| path.to_path_buf()))
+    }
+
+    /// [`Self::handled_path`] of each path, without duplicates
+    fn handled_paths(&self, paths: Vec<PathBuf>) -> Vec<PathBuf> {
+        let mut handled: Vec<PathBuf> = paths
+            .iter()
+            .filter_map(|path| self.handled_path(path))
+            .collect();
+        handled.sort();
+        handled.dedup();
+        handled
+    }
+
+    /// A symlink pointing at `target`: an integrated one, or one next to it
+    fn symlink_to(&self, target: &Path) -> Option<PathBuf> {
+        let resolved = fs::canonicalize(target).ok()?;
+        if let Some(app) = self
+            .state
+            .all()
+            .find(|app| app.symlink_target.as_ref() == Some(&resolved))
+        {
+            return Some(app.appimage_path.clone());
+        }
+        fs::read_dir(target.parent()?)
+            .ok()?
+            .filter_map(|e| e.ok())
+            .map(|e| e.path())
+            .find(|path| appimage::symlink_target(path).as_ref() == Some(&resolved))
+    }
+
+    /// Handle a newly found AppImage according to the integration mode.
+    ///
+    /// Integrates it in automatic mode; in approval mode it is recorded as
+    /// awaiting approval (with a notification the first time) instead.
+    /// Returns whether it was integrated.
+    fn discover(&mut self, path: &Path) -> Result<bool, DaemonError> {
+        if self.record_alias(path)? {
+            return Ok(false);
+        }
+        match self.excluded_category(path) {
+            Ok(Some(category)) => {
+                info!(
+                    "Not integrating {:?}: category {} is excluded",
+                    path, category
+                );
+                return Ok(false);
+            }
+            Ok(None) => {}
+            // Integrating it fails the same way, and records the failure
+            Err(e) => debug!("Could not read the categories of {:?}: {}", path, e),
+        }
+        let quarantined = self.is_quarantined(path);
+        if self.config.integration.mode == IntegrationMode::Automatic && !quarantined {
+            return self.integrate(path).map(|()| true);
+        }
+
+        if self.state.add_awaiting(path) {
+            self.state.save()?;
+            if quarantined {
+                let destination = PathBuf::from(&self.config.integration.applications_dir);
+                info!("AppImage held until moved to {:?}: {:?}", destination, path);
+                if self.config.notifications.enabled {
+                    self.notify(notifications::quarantined(path, &destination));
+                }
+            } else {
+                info!("AppImage awaiting approval: {:?}", path);
+                if self.config.notifications.enabled {
+                    self.notify(notifications::awaiting_approval(path));
+                }
+            }
+        }
+        Ok(false)
+    }
+
+    /// The category in `categories.exclude` an AppImage's bundled desktop
+    /// entry has, if any. Only extracts the AppImage when categories are
+    /// excluded.
+    fn excluded_category(&self, path: &Path) -> Result<Option<String>, DaemonError> {
+        if self.config.categories.exclude.is_empty() {
+            return Ok(None);
+        }
+        let identifier = appimage::generate_identifier(path);
+        let temp_dir = extraction::extraction_dir(&identifier)?;
+        let info = appimage::extract_metadata(path, temp_dir.path())?;
+        let Some(desktop_file) = &info.desktop_file else {
+            return Ok(None);
+        };
+        let entry = desktop::DesktopEntry::parse(desktop_file)?;
+        let categories = entry.entries.get("Categories").map(String::as_str);
+        Ok(categories
+            .and_then(|c| self.config.categories.excluded(c))
+            .map(str::to_string))
+    }
+
+    /// Whether an AppImage is in one of the quarantine directories, and only
+    /// integrated once moved out of it
+    fn is_quarantined(&self, path: &Path) -> bool {
+        let applications_dir = Path::new(&self.config.integration.applications_dir);
+        !path.starts_with(applications_dir)
+            && self
+                .config
+                .integration
+                .quarantine_dirs
+                .iter()
+                .any(|dir| path.starts_with(dir))
+    }
+
+    /// Record `path` as an alias if it reaches the same file (device and
+    /// inode) as an integrated AppImage, so it doesn't get a second launcher.
+    ///
+    /// With `follow_symlinks`, symlinks take over the integration of their
+    /// target instead (see `try_integrate`). Returns whether it is an alias.
+    fn record_alias(&mut self, path: &Path) -> Result<bool, DaemonError> {
+        let is_symlink = fs::symlink_metadata(path).is_ok_and(|m| m.file_type().is_symlink());
+        let same_file = match appimage::file_id(path) {
+            Some(id) if !(is_symlink && self.config.watch.follow_symlinks) => self
+                .state
+                .all()
+                .find(|app| {
+                    app.appimage_path != path && appimage::file_id(&app.appimage_path) == Some(id)
+                })
+                .map(|app| app.identifier.clone()),
+            _ => None,
+        };
+
+        let Some(identifier) = same_file else {
+            // An alias replaced by a different file
+            if self.state.remove_alias(path).is_some() {
+                self.state.save()?;
+            }
+            return Ok(false);
+        };
+        if self.state.add_alias(&identifier, path) {
+            info!("{:?} is another link to integrated {}", path, identifier);
+            self.state.save()?;
+        }
+        Ok(true)
+    }
+
+    /// Integrate an AppImage, recording failures for automatic retry
+    pub fn integrate(&mut self, path: &Path) -> Result<(), DaemonError> {
+        if self.dry_run {
+            info!("Dry run: would integrate {:?}", path);
+            return Ok(());
+        }
+        let result = self.try_integrate(path, None);
+        self.track_result(path, result)
+    }
+
+    /// Re-integrate an integrated AppImage whose file was replaced in place.
+    ///
+    /// Does nothing if the contents are unchanged; treats it as newly found
+    /// (see `discover`) if it is not integrated yet.
+    pub fn refresh(&mut self, path: &Path) -> Result<(), DaemonError> {
+        let Some(existing) = self.state.get_by_path(path).cloned() else {
+            return self.discover(path).map(|_| ());
+        };
+
+        let target = appimage::symlink_target(path);
+        if existing.symlink_target != target {
+            info!("Symlink {:?} now points to {:?}", path, target);
+            return self.reintegrate(path);
+        }
+
+        // Hashing reads the whole file, only done when it may have changed
+        if !existing.changed_on_disk() {
+            debug!("AppImage unchanged: {:?}", path);
+            return Ok(());
+        }
+        let mtime_ns = state::modified_ns(path);
+        let hash = appimage::content_hash(path)?;
+        if existing.content_hash.as_deref() == Some(hash.as_str()) {
+            debug!("AppImage touched, but unchanged: {:?}", path);
+            if self.state.set_mtime(path, mtime_ns) {
+                self.state.save()?;
+            }
+            return Ok(());
+        }
+
+        info!("AppImage replaced in place: {:?}", path);
+        self.reintegrate(path)
+    }
+
+    /// Integrate an AppImage again from scratch, keeping its identifier,
+    /// integration date and pin. Integrates it as new if not yet integrated.
+    pub fn reintegrate(&mut self, path: &Path) -> Result<(), DaemonError> {
+        let Some(existing) = self.state.get_by_path(path).cloned() else {
+            return self.integrate(path);
+        };
+        if self.dry_run {
+            info!("Dry run: would re-integrate {:?}", path);
+            return Ok(());
+        }
+
+        // The new integration overwrites the files of the old one, which
+        // stays in place if it fails
+        let old_entry = fs::read_to_string(&existing.desktop_path).ok();
+        let result = self.try_integrate(path, Some(existing.clone()));
+        if result.is_ok() {
+            self.cleanup_replaced(&existing);
+        }
+        self.track_result(path, result)?;
+        if let Some(old_entry) = old_entry {
+            self.record_entry_changes(path, &old_entry)?;
+        }
+        Ok(())
+    }
+
+    /// Log and record how re-integrating changed an AppImage's desktop entry
+    fn record_entry_changes(&mut self, path: &Path, old_entry: &str) -> Result<(), DaemonError> {
+        let Some(desktop_path) = self.state.get_by_path(path).map(|a| a.desktop_path.clone())
+        else {
+            return Ok(());
+        };
+        let new_entry = fs::read_to_string(&desktop_path).unwrap_or_default();
+        let changes = desktop::diff_entries(old_entry, &new_entry);
+        if changes.is_empty() {
+            debug!("Desktop entry unchanged: {:?}", desktop_path);
+        }
+        for change in &changes {
+            info!("Desktop entry changed: {}", change);
+        }
+        if self.state.set_entry_changes(path, changes) {
+            self.state.save()?;
+        }
+        Ok(())
+    }
+
+    /// Accept the current desktop entries and icons of an integrated
+    /// AppImage as intended, so `check` no longer reports them; returns false
+    /// if it isn't integrated
+    pub fn adopt_changes(&mut self, path: &Path) -> Result<bool, DaemonError> {
+        let Some(checksums) = self.state.get_by_path(path).map(integrity::checksums) else {
+            return Ok(false);
+        };
+        self.state.set_checksums(path, checksums);
+        self.state.save()?;
+        Ok(true)
+    }
+
+    /// Pin or unpin an integrated AppImage; returns false if it isn't integrated
+    pub fn set_pinned(&mut self, path: &Path, pinned: bool) -> Result<bool, DaemonError> {
+        let found = self.state.set_pinned(path, pinned);
+        if found {
+            self.state.save()?;
+        }
+        Ok(found)
+    }
+
+    /// Install a copy of `icon` instead of an integrated AppImage's own icon,
+    /// now and on every later re-integration, or go back to its own icon with
+    /// `None`. Returns false if it isn't integrated.
+    pub fn set_icon(&mut self, path: &Path, icon: Option<&Path>) -> Result<bool, DaemonError> {
+        let Some(app) = self.state.get_by_path(path).cloned() else {
+            return Ok(false);
+        };
+        if self.dry_run {
+            info!("Dry run: would change the icon of {:?}", path);
+            return Ok(true);
+        }
 
-        Ok(())
+        let custom_icon = match icon {
+            Some(icon) => Some(store_custom_icon(&app.identifier, icon)?),
+            None => None,
+        };
+        if let Some(old) = &app.custom_icon
+            && Some(old) != custom_icon.as_ref()
+            && let Err(e) = fs::remove_file(old)
+        {
+            warn!("Failed to remove custom icon {:?}: {}", old, e);
+        }
+        self.state.set_custom_icon(path, custom_icon);
+        self.state.save()?;
+        self.reintegrate(path)?;
+        Ok(true)
     }
 
-    /// Handle a file system event
-    fn handle_event(&mut self, event: FileEvent) -> Result<(), DaemonError> {
-        match event {
-            FileEvent::Created(ref path) => {
-                debug!("File created: {:?}", path);
-                if appimage::is_appimage(path) {
-                    // Check if file is complete before integrating
-                    match appimage::is_appimage_complete(path) {
-                        Ok(true) => {
-                            info!("New complete AppImage detected: {:?}", path);
-                            self.integrate(path)?;
-                        }
-                        Ok(false) => {
-                            debug!("AppImage incomplete, re-queuing: {:?}", path);
-                            // Re-queue for later check
-                            self.pending_events
-                                .insert(path.clone(), (event, Instant::now()));
-                        }
-                        Err(e) => {
-                            warn!("Could not verify completeness for {:?}: {}", path, e);
-                            // Try integration anyway (fallback to previous behavior)
-                            info!("New AppImage detected (unverified): {:?}", path);
-                            self.integrate(path)?;
-                        }
-                    }
-                }
+    /// Record or clear the failure entry for an integration attempt
+    fn track_result(
+        &mut self,
+        path: &Path,
+        result: Result<(), DaemonError>,
+    ) -> Result<(), DaemonError> {
+        let changed = match &result {
+            Ok(()) => self.state.clear_failure(path).is_some(),
+            // Retried by the event loop until the limit is reached
+            Err(e)
+                if e.is_busy()
+                    && self.busy_retries.get(path).copied().unwrap_or(0) < BUSY_RETRY_LIMIT =>
+            {
+                false
             }
-
-            FileEvent::Deleted(path) => {
-                debug!("File deleted: {:?}", path);
-                if self.state.is_integrated(&path) {
-                    info!("Integrated AppImage deleted: {:?}", path);
-                    self.unintegrate(&path)?;
+            Err(e) => {
+                let failure = self.state.record_failure(
+                    path,
+                    &e.to_string(),
+                    self.config.integration.max_retries,
+                );
+                match failure.next_retry_at {
+                    Some(at) => warn!(
+                        "Integration of {:?} failed (attempt {}), retrying in {}s",
+                        path,
+                        failure.attempts,
+                        at.saturating_sub(failure.last_failed_at)
+                    ),
+                    None => warn!(
+                        "Integration of {:?} failed {} times, giving up",
+                        path, failure.attempts
+                    ),
                 }
+                true
             }
+        };
 
-            FileEvent::Moved { from, to } => {
-                debug!("File moved: {:?} -> {:?}", from, to);
-                if self.state.is_integrated(&from) {
-                    info!("Integrated AppImage moved: {:?} -> {:?}", from, to);
-                    self.handle_move(&from, &to)?;
-                } else if appimage::is_appimage(&to) {
-                    // Moved in from outside watched dirs
-                    info!("AppImage moved into watched directory: {:?}", to);
-                    self.integrate(&to)?;
-                }
-            }
+        if changed && let Err(e) = self.state.save() {
+            warn!("Failed to save state: {}", e);
+        }
+        result
+    }
 
-            FileEvent::Modified(ref path) => {
-                debug!("File modified: {:?}", path);
-                // For now, we don't re-integrate on modification
-                // Could be extended to detect significant changes
+    /// Retry failed integrations whose backoff has expired
+    pub fn retry_failed(&mut self) {
+        for path in self.state.due_retries(state::current_timestamp()) {
+            if !path.exists() || self.state.is_integrated(&path) {
+                self.state.clear_failure(&path);
+                continue;
             }
+            info!("Retrying integration: {:?}", path);
+            // Failures are recorded (and logged) by integrate itself
+            let _ = self.integrate(&path);
         }
-
-        Ok(())
     }
 
-    /// Integrate an AppImage
-    pub fn integrate(&mut self, path: &Path) -> Result<(), DaemonError> {
-        let identifier = appimage::generate_identifier(path);
+    /// Integrate an AppImage without failure bookkeeping.
+    ///
+    /// With `previous`, this is an update of that entry: its identifier and
+    /// integration time are kept and an "updated" notification is sent.
+    fn try_integrate(
+        &mut self,
+        path: &Path,
+        previous: Option<IntegratedAppImage>,
+    ) -> Result<(), DaemonError> {
+        let identifier = match &previous {
+            Some(prev) => prev.identifier.clone(),
+            None => appimage::generate_identifier(path),
+        };
 
-        // Check if already integrated
-        if self.state.get(&identifier).is_some() {
+        // Check if already integrated, possibly under another spelling of the path
+        if previous.is_none()
+            && (self.state.get(&identifier).is_some() || self.state.is_integrated(path))
+        {
             debug!("AppImage already integrated: {:?}", path);
             return Ok(());
         }
+        if previous.is_none() && self.record_alias(path)? {
+            return Ok(());
+        }
 
+        let _span = info_span!("integrate", path = %path.display()).entered();
         info!("Integrating AppImage: {:?}", path);
-
-        // Create temporary directory for extraction
-        let temp_dir = TempDir::new()?;
+        let started = Instant::now();
+
+        let dirs = [
+            extraction::extraction_root(),
+            self.config.icon_directory(),
+            self.config.desktop_directory(),
+        ];
+        self.check_disk_space(path, &dirs, 0)?;
+        let mtime_ns = state::modified_ns(path);
+        let content_hash = appimage::content_hash(path)?;
+
+        // Extract into a directory of its own, removed when done
+        let temp_dir = extraction::extraction_dir(&identifier)?;
         let extract_dir = temp_dir.path();
 
         // Extract metadata
         let info = appimage::extract_metadata(path, extract_dir)?;
-
-        // Find the best icon
-        let icon_path = appimage::select_best_icon(&info.icon_files);
+        let extracted = Instant::now();
+
+        // Keys trusted on first use are recorded per app, which is only known
+        // from the bundled metadata
+        let app = info.app_id.clone().or_else(|| {
+            let desktop_file = info.desktop_file.as_deref()?;
+            Some(desktop_file.file_stem()?.to_string_lossy().into_owned())
+        });
+        self.check_signature(path, app.as_deref())?;
+
+        // Find the best icon, unless the user chose one
+        let custom_icon = previous
+            .as_ref()
+            .and_then(|prev| prev.custom_icon.clone())
+            .filter(|icon| icon.exists());
+        let icon_path = custom_icon
+            .as_ref()
+            .or_else(|| appimage::select_best_icon(&info.icon_files));
 
         // Install icon if available
-        let installed_icon = if let Some(src_icon) = icon_path {
-            match self.install_icon(src_icon, &identifier) {
-                Ok(installed) => Some(installed),
-                Err(e) => {
-                    warn!("Failed to install icon: {}", e);
-                    None
-                }
-            }
-        } else {
-            None
-        };
+        let icon_paths = self.install_icons(icon_path, &identifier);
+        let installed_icon = icon_paths.first().cloned();
+        let icons_done = Instant::now();
 
         // Install desktop entry
         let desktop_file = info
@@ -341,63 +1626,354 @@ impl Daemon {
             .as_ref()
             .ok_or(crate::appimage::AppImageError::NoDesktopFile)?;
 
+        let mut overrides = Overrides::load().unwrap_or_else(|e| {
+            warn!("Failed to load desktop entry overrides: {}", e);
+            Overrides::default()
+        });
+        self.follow_overrides(
+            &mut overrides,
+            &identifier,
+            &content_hash,
+            previous.is_none(),
+        );
+        let launcher = if self.config.integration.track_launches {
+            let launcher = desktop::tracking_launcher();
+            if launcher.is_none() {
+                warn!("appimage-auto binary not found, not tracking launches");
+            }
+            launcher
+        } else {
+            None
+        };
+        let duplicate = if self.config.integration.detect_duplicates {
+            find_native_duplicate(desktop_file)
+        } else {
+            None
+        };
+        let name_suffix = (duplicate.is_some() && self.config.integration.suffix_duplicates)
+            .then(duplicates::name_suffix);
+        let name = overrides
+            .get(&identifier)
+            .and_then(|o| o.name.clone())
+            .or(info.name);
+        let profile = self
+            .config
+            .app_profile(&identifier, name.as_deref())
+            .cloned();
         let desktop_path = desktop::install_desktop_entry(
             desktop_file,
             path,
             installed_icon.as_deref(),
             &identifier,
             &self.config.desktop_directory(),
+            &desktop::EntryCustomizations {
+                categories: Some(&self.config.categories),
+                name_suffix,
+                overrides: overrides.get(&identifier),
+                launcher: launcher.as_deref(),
+                profile: profile.as_ref(),
+                app_id: info.app_id.as_deref(),
+                comment: info.summary.as_deref(),
+                translations: Some(&info.translations),
+                without_actions: !self.config.integration.desktop_actions,
+            },
         )?;
+        let desktop_copies =
+            copy_desktop_entry(&desktop_path, &self.config.extra_desktop_directories());
 
         // Update desktop database
         if self.config.integration.update_database {
-            desktop::update_desktop_database(&self.config.desktop_directory())?;
+            update_desktop_databases(&self.config)?;
         }
 
+        let finished = Instant::now();
+        let timing = IntegrationTiming {
+            extract_ms: millis(extracted - started),
+            icon_ms: millis(icons_done - extracted),
+            desktop_ms: millis(finished - icons_done),
+            total_ms: millis(finished - started),
+        };
+
         // Record in state
-        let icon_paths = installed_icon.map(|p| vec![p]).unwrap_or_default();
-        let entry = state::create_entry(
-            identifier,
+        let kind = match previous {
+            Some(_) => HistoryKind::Updated,
+            None => HistoryKind::Integrated,
+        };
+        self.record_history(HistoryEntry::new(kind, &identifier, name.as_deref(), path));
+        let mut entry = state::create_entry(
+            identifier.clone(),
             path.to_path_buf(),
             desktop_path,
             icon_paths.clone(),
-            info.name.clone(),
+            name.clone(),
         );
+        entry.timing = Some(timing);
+        entry.content_hash = Some(content_hash);
+        entry.mtime_ns = mtime_ns;
+        entry.version = info.version.clone();
+        entry.links = info.links.clone();
+        entry.native_duplicate = duplicate;
+        entry.pinned = profile.as_ref().is_some_and(|p| p.pinned);
+        entry.symlink_target = appimage::symlink_target(path);
+        entry.link_path = self.install_link(name.as_deref(), path);
+        entry.desktop_copies = desktop_copies;
+        entry.sizes = Some(entry.measure_disk_usage());
+        entry.checksums = integrity::checksums(&entry);
+        if let Some(profile) = profile.as_ref().filter(|p| p.sandbox) {
+            write_apparmor_template(&entry, profile);
+        }
+        let origin = appimage::download_origin(path);
+        entry.origin_url = origin.url;
+        entry.referrer_url = origin.referrer;
+        if let Some(prev) = &previous {
+            entry.integrated_at = prev.integrated_at;
+            entry.launch_count = prev.launch_count;
+            entry.last_launched = prev.last_launched;
+            entry.pinned |= prev.pinned;
+            entry.custom_icon = custom_icon;
+            entry.autostart_entries = prev.autostart_entries.clone();
+            // Aliases of a replaced file no longer reach this one
+            let id = appimage::file_id(path);
+            entry.aliases = prev
+                .aliases
+                .iter()
+                .filter(|alias| appimage::file_id(alias) == id)
+                .cloned()
+                .collect();
+            // Copying or moving across filesystems can drop the attributes
+            if entry.origin_url.is_none() {
+                entry.origin_url = prev.origin_url.clone();
+                entry.referrer_url = prev.referrer_url.clone();
+            }
+        }
+        // The symlink takes over the launcher of its target, which becomes
+        // an alias
+        if let Some(target) = &entry.symlink_target
+            && let Some(replaced) = self.state.remove_by_path(target)
+        {
+            info!("{:?} is integrated through symlink {:?}", target, path);
+            self.cleanup_integration(&replaced)?;
+            entry.aliases.push(replaced.appimage_path);
+            entry
+                .aliases
+                .extend(replaced.aliases.into_iter().filter(|a| a != path));
+        }
+        self.state.remove_alias(path);
         self.state.add(entry);
+        self.state.remove_awaiting(path);
         self.state.save()?;
 
         // Send notification
         if self.config.notifications.enabled && self.config.notifications.on_integrate {
-            let name = info.name.as_deref().unwrap_or("AppImage");
+            let name = name.as_deref().unwrap_or("AppImage");
             let icon = icon_paths.first().map(|p| p.as_path());
-            crate::notifications::send(crate::notifications::integrated(name, path, icon));
+            let event = match previous {
+                Some(_) => {
+                    notifications::updated(&identifier, name, info.version.as_deref(), path, icon)
+                }
+                None => notifications::integrated(&identifier, name, path, icon),
+            };
+            self.notify(event);
         }
 
-        info!("Successfully integrated: {:?}", path);
+        info!(
+            "Successfully integrated: {:?} in {}ms (extract {}ms, icon {}ms, desktop {}ms)",
+            path, timing.total_ms, timing.extract_ms, timing.icon_ms, timing.desktop_ms
+        );
         Ok(())
     }
 
+    /// Install an AppImage's icon and its copies in further icon themes
+    /// (`integration.icon_dirs`); the icon in the main theme comes first
+    fn install_icons(&self, src_icon: Option<&PathBuf>, identifier: &str) -> Vec<PathBuf> {
+        let Some(src_icon) = src_icon.filter(|_| self.config.integration.install_icons) else {
+            return Vec::new();
+        };
+        match self.install_icon(&self.config.icon_directory(), src_icon, identifier) {
+            Ok(installed) => {
+                let mut icon_paths = vec![installed];
+                for dir in self.config.extra_icon_directories() {
+                    match self.install_icon(&dir, src_icon, identifier) {
+                        Ok(installed) => icon_paths.push(installed),
+                        Err(e) => warn!("Failed to install icon into {:?}: {}", dir, e),
+                    }
+                }
+                icon_paths
+            }
+            Err(e) => {
+                warn!("Failed to install icon: {}", e);
+                Vec::new()
+            }
+        }
+    }
+
+    /// Take over a desktop entry written by hand for an AppImage: the
+    /// AppImage its Exec runs gets a state entry and its icons are installed,
+    /// so it is updated, moved and removed like any other integration.
+    ///
+    /// The entry stays where it is. Returns the AppImage's path.
+    pub fn adopt(&mut self, desktop_path: &Path) -> Result<PathBuf, DaemonError> {
+        let mut entry = desktop::DesktopEntry::parse(desktop_path)?;
+        let path = entry
+            .exec_program()
+            .ok_or_else(|| desktop::DesktopError::MissingField("Exec".to_string()))?;
+        if !appimage::is_appimage(&path) {
+            return Err(appimage::AppImageError::NotAppImage(path.display().to_string()).into());
+        }
+        if self.state.is_integrated(&path) {
+            return Err(DaemonError::AlreadyIntegrated(path));
+        }
+
+        info!("Adopting {:?} for {:?}", desktop_path, path);
+        let identifier = appimage::generate_identifier(&path);
+        let mtime_ns = state::modified_ns(&path);
+        let content_hash = appimage::content_hash(&path)?;
+        let temp_dir = extraction::extraction_dir(&identifier)?;
+        let info = appimage::extract_metadata(&path, temp_dir.path())?;
+
+        let icon = appimage::select_best_icon(&info.icon_files);
+        let icon_paths = self.install_icons(icon, &identifier);
+        if let Some(icon) = icon_paths.first() {
+            entry.set_icon(&icon.display().to_string());
+        }
+        if let Some(summary) = &info.summary {
+            entry.ensure_comment(summary);
+        }
+        entry.add_appstream_translations(&info.translations, info.summary.as_deref());
+        entry.set_appimage_identifier(&identifier);
+        entry.write(desktop_path)?;
+        if self.config.integration.update_database {
+            update_desktop_databases(&self.config)?;
+        }
+
+        let name = entry.name().map(str::to_string).or(info.name);
+        self.record_history(HistoryEntry::new(
+            HistoryKind::Integrated,
+            &identifier,
+            name.as_deref(),
+            &path,
+        ));
+        let mut adopted = state::create_entry(
+            identifier,
+            path.clone(),
+            desktop_path.to_path_buf(),
+            icon_paths,
+            name,
+        );
+        adopted.content_hash = Some(content_hash);
+        adopted.mtime_ns = mtime_ns;
+        adopted.version = info.version;
+        adopted.links = info.links;
+        adopted.symlink_target = appimage::symlink_target(&path);
+        adopted.sizes = Some(adopted.measure_disk_usage());
+        adopted.checksums = integrity::checksums(&adopted);
+        let origin = appimage::download_origin(&path);
+        adopted.origin_url = origin.url;
+        adopted.referrer_url = origin.referrer;
+        self.state.add(adopted);
+        self.state.clear_failure(&path);
+        self.state.remove_awaiting(&path);
+        self.state.save()?;
+        Ok(path)
+    }
+
     /// Unintegrate an AppImage
     pub fn unintegrate(&mut self, path: &Path) -> Result<(), DaemonError> {
+        if self.dry_run {
+            if self.state.remove_by_path(path).is_some() {
+                info!("Dry run: would unintegrate {:?}", path);
+            }
+            return Ok(());
+        }
         if let Some(info) = self.state.remove_by_path(path) {
             // Send notification before cleanup
             if self.config.notifications.enabled && self.config.notifications.on_unintegrate {
                 let name = info.name.as_deref().unwrap_or("AppImage");
-                crate::notifications::send(crate::notifications::unintegrated(
-                    name,
-                    &info.appimage_path,
-                ));
+                self.notify(notifications::unintegrated(name, &info.appimage_path));
             }
 
             self.cleanup_integration(&info)?;
+            if let Some(icon) = &info.custom_icon
+                && let Err(e) = fs::remove_file(icon)
+            {
+                warn!("Failed to remove custom icon {:?}: {}", icon, e);
+            }
+            // Kept, in case the file turns up again elsewhere
+            if let Some(hash) = &info.content_hash {
+                record_overrides_hash(&info.identifier, hash);
+            }
             self.state.save()?;
+            self.record_history(HistoryEntry::new(
+                HistoryKind::Unintegrated,
+                &info.identifier,
+                info.name.as_deref(),
+                &info.appimage_path,
+            ));
             info!("Successfully unintegrated: {:?}", path);
         }
         Ok(())
     }
 
+    /// Remove the integration for an AppImage, optionally deleting the file.
+    ///
+    /// The state entry is removed before the file so a running daemon sees
+    /// the deletion as already handled.
+    pub fn remove(&mut self, path: &Path, delete_file: bool) -> Result<(), DaemonError> {
+        self.unintegrate(path)?;
+        if delete_file && !self.dry_run {
+            fs::remove_file(path)?;
+            info!("Deleted AppImage: {:?}", path);
+        }
+        Ok(())
+    }
+
+    /// Record which AppImage contents the overrides of `identifier` apply
+    /// to, first moving them over from an integration left behind by the
+    /// same file if this is a `fresh` one.
+    ///
+    /// A file moved while the daemon wasn't watching, or out of the watched
+    /// directories and back, is integrated afresh under a new identifier.
+    fn follow_overrides(
+        &self,
+        overrides: &mut Overrides,
+        identifier: &str,
+        hash: &str,
+        fresh: bool,
+    ) {
+        let mut changed = false;
+        if fresh && overrides.get(identifier).is_none() {
+            let left_behind = overrides
+                .identifiers_for_hash(hash)
+                .find(|old| {
+                    self.state
+                        .get(old)
+                        .is_none_or(|info| !info.appimage_path.exists())
+                })
+                .map(str::to_string);
+            if let Some(old) = left_behind
+                && overrides.rename(&old, identifier)
+            {
+                info!("Moved desktop entry overrides of {} to {}", old, identifier);
+                changed = true;
+            }
+        }
+        changed |= overrides.set_content_hash(identifier, hash);
+        if changed
+            && !self.dry_run
+            && let Err(e) = overrides.save()
+        {
+            warn!("Failed to save desktop entry overrides: {}", e);
+        }
+    }
+
     /// Handle an AppImage move within watched directories
     fn handle_move(&mut self, from: &Path, to: &Path) -> Result<(), DaemonError> {
+        if self.dry_run {
+            if self.state.update_path(from, to).is_some() {
+                info!("Dry run: would move integration {:?} -> {:?}", from, to);
+            }
+            return Ok(());
+        }
         // Update state
         if let Some(info) = self.state.update_path(from, to) {
             // Update the desktop file to point to new location
@@ -405,22 +1981,171 @@ impl Daemon {
             entry.set_exec(to);
             entry.set_try_exec(to);
             entry.write(&info.desktop_path)?;
+            for copy in &info.desktop_copies {
+                if let Err(e) = entry.write(copy) {
+                    warn!("Failed to update desktop entry copy {:?}: {}", copy, e);
+                }
+            }
+
+            if let Some(profile) = self
+                .config
+                .app_profile(&info.identifier, info.name.as_deref())
+                .filter(|p| p.sandbox)
+            {
+                write_apparmor_template(info, profile);
+            }
+
+            if let Some(link) = &info.link_path
+                && let Some(dir) = link.parent()
+                && let Some(link_name) = link.file_name()
+                && let Err(e) = links::install(dir, &link_name.to_string_lossy(), to)
+            {
+                warn!("Failed to update launch symlink {:?}: {}", link, e);
+            }
 
             // Update desktop database
             if self.config.integration.update_database {
-                desktop::update_desktop_database(&self.config.desktop_directory())?;
+                update_desktop_databases(&self.config)?;
             }
 
+            let event = HistoryEntry::new(
+                HistoryKind::Moved,
+                &info.identifier,
+                info.name.as_deref(),
+                to,
+            );
+            let checksums = integrity::checksums(info);
+            self.record_history(event);
+            self.state.set_checksums(to, checksums);
+            self.state.set_last_error(to, None);
             self.state.save()?;
             info!("Updated desktop entry for moved AppImage: {:?}", to);
         }
         Ok(())
     }
 
+    /// Fail before anything is written if one of `dirs` has less free space
+    /// than `integration.min_free_space_mb` plus `extra` bytes, notifying once
+    /// until there is enough again
+    fn check_disk_space(
+        &mut self,
+        path: &Path,
+        dirs: &[PathBuf],
+        extra: u64,
+    ) -> Result<(), DaemonError> {
+        let min_free_mb = self.config.integration.min_free_space_mb;
+        if min_free_mb == 0 {
+            return Ok(());
+        }
+        let required = min_free_mb.saturating_mul(1_000_000).saturating_add(extra);
+        let Some(low) = disk_space::check(dirs.iter().map(PathBuf::as_path), required) else {
+            self.low_space_notified = false;
+            return Ok(());
+        };
+        if !self.low_space_notified && self.config.notifications.enabled {
+            self.low_space_notified = true;
+            self.notify(notifications::low_disk_space(path, &low));
+        }
+        Err(DaemonError::LowDiskSpace(low))
+    }
+
+    /// Verify an AppImage of `app` as `signatures.verify` asks. With
+    /// `trust_on_first_use`, a key outside the trust store is accepted if it
+    /// is the one recorded for the app, or if none is recorded yet.
+    fn check_signature(&self, path: &Path, app: Option<&str>) -> Result<(), DaemonError> {
+        let policy = self.config.signatures.verify;
+        if policy == SignaturePolicy::Off {
+            return Ok(());
+        }
+
+        let verification = match self.verify_signature(path, app) {
+            Ok(verification) => verification,
+            Err(e) => return signature_error(policy, path, e),
+        };
+        let problem = match verification {
+            Verification::Trusted { fingerprint } => {
+                debug!("{:?} is signed by trusted key {}", path, fingerprint);
+                return Ok(());
+            }
+            Verification::Unsigned if policy == SignaturePolicy::Warn => return Ok(()),
+            Verification::Unsigned => "not signed".to_string(),
+            Verification::UnknownKey { key_id } => format!("signed by untrusted key {}", key_id),
+            Verification::ExpiredKey { fingerprint } => {
+                format!("signed by expired key {}", fingerprint)
+            }
+            Verification::RevokedKey { fingerprint } => {
+                format!("signed by revoked key {}", fingerprint)
+            }
+            Verification::Bad => "signed, but the signature does not match".to_string(),
+        };
+        if policy == SignaturePolicy::Require {
+            return Err(DaemonError::Untrusted(path.to_path_buf(), problem));
+        }
+        warn!("{:?} is {}", path, problem);
+        Ok(())
+    }
+
+    /// Verify an AppImage, trusting the key of `app` on first use if asked
+    fn verify_signature(
+        &self,
+        path: &Path,
+        app: Option<&str>,
+    ) -> Result<Verification, DaemonError> {
+        let mut verification = signature::verify(path)?;
+        if let Verification::UnknownKey { .. } = verification
+            && self.config.signatures.trust_on_first_use
+            && let Some(app) = app
+            && let Some(key) = signature::embedded_key(path)?
+        {
+            verification = match signature::verify_with_key(path, key.as_bytes())? {
+                Verification::Trusted { fingerprint } => match signature::app_key(app)? {
+                    None => {
+                        info!("Trusting the signing key of {} on first use", app);
+                        signature::record_app_key(app, &fingerprint)?;
+                        Verification::Trusted { fingerprint }
+                    }
+                    Some(known) if known == fingerprint => Verification::Trusted { fingerprint },
+                    Some(known) => {
+                        warn!(
+                            "{:?} is signed by {}, but {} was signed by {} before",
+                            path, fingerprint, app, known
+                        );
+                        Verification::UnknownKey {
+                            key_id: fingerprint,
+                        }
+                    }
+                },
+                other => other,
+            };
+        }
+        Ok(verification)
+    }
+
+    /// Create the launch symlink for an AppImage if `link_dir` is set
+    fn install_link(&self, name: Option<&str>, path: &Path) -> Option<PathBuf> {
+        let dir = self.config.integration.link_dir.as_ref()?;
+        let link_name = links::link_name(name, path)?;
+        links::install(Path::new(dir), &link_name, path)
+            .inspect_err(|e| warn!("Failed to create launch symlink for {:?}: {}", path, e))
+            .ok()
+            .flatten()
+    }
+
     /// Clean up integration files (desktop entry and icons)
     fn cleanup_integration(&self, info: &IntegratedAppImage) -> Result<(), DaemonError> {
         // Remove desktop file
         desktop::remove_desktop_entry(&info.desktop_path)?;
+        for copy in &info.desktop_copies {
+            if let Err(e) = desktop::remove_desktop_entry(copy) {
+                warn!("Failed to remove desktop entry copy {:?}: {}", copy, e);
+            }
+        }
+
+        if let Some(link) = &info.link_path
+            && let Err(e) = links::remove(link, &info.appimage_path)
+        {
+            warn!("Failed to remove launch symlink {:?}: {}", link, e);
+        }
 
         // Remove icons
         for icon_path in &info.icon_paths {
@@ -433,16 +2158,53 @@ impl Daemon {
 
         // Update desktop database
         if self.config.integration.update_database {
-            desktop::update_desktop_database(&self.config.desktop_directory())?;
+            update_desktop_databases(&self.config)?;
         }
 
         Ok(())
     }
 
-    /// Install an icon to the appropriate location
-    fn install_icon(&self, src: &Path, identifier: &str) -> Result<PathBuf, DaemonError> {
-        let icon_base = self.config.icon_directory();
+    /// Remove the files of an earlier integration that re-integrating didn't
+    /// write again, like an icon of another size or format
+    fn cleanup_replaced(&self, old: &IntegratedAppImage) {
+        let Some(new) = self.state.get(&old.identifier) else {
+            return;
+        };
+        let desktop_entries = std::iter::once(&old.desktop_path).chain(&old.desktop_copies);
+        for entry in
+            desktop_entries.filter(|e| **e != new.desktop_path && !new.desktop_copies.contains(e))
+        {
+            if let Err(e) = desktop::remove_desktop_entry(entry) {
+                warn!("Failed to remove old desktop entry {:?}: {}", entry, e);
+            }
+        }
+        if let Some(link) = &old.link_path
+            && new.link_path.as_ref() != Some(link)
+            && let Err(e) = links::remove(link, &old.appimage_path)
+        {
+            warn!("Failed to remove old launch symlink {:?}: {}", link, e);
+        }
+        for icon_path in old
+            .icon_paths
+            .iter()
+            .filter(|i| !new.icon_paths.contains(i))
+        {
+            if icon_path.exists()
+                && let Err(e) = fs::remove_file(icon_path)
+            {
+                warn!("Failed to remove old icon {:?}: {}", icon_path, e);
+            }
+        }
+    }
 
+    /// Install an icon to the appropriate location
+    #[instrument(level = "debug", skip(self))]
+    fn install_icon(
+        &self,
+        icon_base: &Path,
+        src: &Path,
+        identifier: &str,
+    ) -> Result<PathBuf, DaemonError> {
         // Determine icon size and format
         let (size, ext) = determine_icon_info(src);
 
@@ -481,6 +2243,114 @@ impl Daemon {
     }
 }
 
+/// Convert a duration to whole milliseconds
+/// Delay before retry number `attempt` (from 1) of a busy AppImage
+fn busy_retry_delay(attempt: u32) -> Duration {
+    BUSY_RETRY_DELAY
+        .saturating_mul(1 << attempt.saturating_sub(1).min(16))
+        .min(BUSY_RETRY_MAX_DELAY)
+}
+
+fn millis(d: Duration) -> u64 {
+    d.as_millis().try_into().unwrap_or(u64::MAX)
+}
+
+/// Look for a distro or Flatpak install of the app an embedded desktop entry describes
+fn find_native_duplicate(desktop_file: &Path) -> Option<duplicates::NativeDuplicate> {
+    let entry = desktop::DesktopEntry::parse(desktop_file).ok()?;
+    let desktop_id = desktop_file.file_name()?.to_string_lossy();
+    let duplicate =
+        duplicates::find_native(&entry, &desktop_id, &duplicates::native_application_dirs())?;
+    warn!(
+        "{} is also installed natively: {:?} ({})",
+        entry.name().unwrap_or(&desktop_id),
+        duplicate.desktop_path,
+        duplicate.reason.label()
+    );
+    Some(duplicate)
+}
+
+/// Copy an installed desktop entry into each of `dirs`, logging (not
+/// failing) on error. Returns the copies made.
+fn copy_desktop_entry(desktop_path: &Path, dirs: &[PathBuf]) -> Vec<PathBuf> {
+    let Some(file_name) = desktop_path.file_name() else {
+        return Vec::new();
+    };
+    dirs.iter()
+        .filter_map(|dir| {
+            let copy = dir.join(file_name);
+            fs::create_dir_all(dir)
+                .and_then(|()| fs::copy(desktop_path, &copy))
+                .inspect_err(|e| warn!("Failed to copy desktop entry to {:?}: {}", dir, e))
+                .ok()
+                .map(|_| copy)
+        })
+        .collect()
+}
+
+/// Handle a signature that could not be checked at all, e.g. because gpg
+/// is not installed: only a required signature fails the integration
+fn signature_error(
+    policy: SignaturePolicy,
+    path: &Path,
+    error: DaemonError,
+) -> Result<(), DaemonError> {
+    if policy == SignaturePolicy::Require {
+        return Err(error);
+    }
+    warn!("Could not check the signature of {:?}: {}", path, error);
+    Ok(())
+}
+
+/// Run update-desktop-database on the desktop entry directories
+fn update_desktop_databases(config: &Config) -> Result<(), desktop::DesktopError> {
+    desktop::update_desktop_database(&config.desktop_directory())?;
+    for dir in config.extra_desktop_directories() {
+        desktop::update_desktop_database(&dir)?;
+    }
+    Ok(())
+}
+
+/// Write the AppArmor profile template of a sandboxed app, logging (not
+/// failing) on error
+fn write_apparmor_template(entry: &IntegratedAppImage, profile: &AppProfile) {
+    if let Err(e) = apparmor::write_template(
+        &entry.identifier,
+        entry.name.as_deref(),
+        &entry.appimage_path,
+        profile.network,
+    ) {
+        warn!(
+            "Failed to write AppArmor profile for {:?}: {}",
+            entry.appimage_path, e
+        );
+    }
+}
+
+/// Icon formats icon themes can hold
+const ICON_EXTENSIONS: [&str; 3] = ["png", "svg", "xpm"];
+
+/// Copy an icon the user chose into the custom icon directory, where it
+/// outlives the file it came from
+fn store_custom_icon(identifier: &str, icon: &Path) -> Result<PathBuf, DaemonError> {
+    let ext = icon
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .filter(|ext| ICON_EXTENSIONS.contains(&ext.as_str()))
+        .ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("{:?} is not a PNG, SVG or XPM icon", icon),
+            )
+        })?;
+    let dir = State::custom_icon_dir()?;
+    fs::create_dir_all(&dir)?;
+    let dest = dir.join(format!("{}.{}", identifier, ext));
+    fs::copy(icon, &dest)?;
+    info!("Stored custom icon {:?} as {:?}", icon, dest);
+    Ok(dest)
+}
+
 /// Determine icon size and extension from path
 fn determine_icon_info(path: &Path) -> (u32, String) {
     let ext = path
@@ -525,6 +2395,7 @@ pub fn oneshot(config: Option<Config>) -> Result<(), DaemonError> {
     // Scan and integrate
     daemon.scan_existing()?;
     daemon.cleanup_orphaned()?;
+    daemon.cleanup_extractions();
 
     info!(
         "One-shot scan complete. Integrated {} AppImages.",
@@ -533,9 +2404,24 @@ pub fn oneshot(config: Option<Config>) -> Result<(), DaemonError> {
     Ok(())
 }
 
+/// Record the AppImage contents the overrides of `identifier` apply to, so
+/// they can follow the file to another identifier
+fn record_overrides_hash(identifier: &str, hash: &str) {
+    let result = Overrides::load().and_then(|mut overrides| {
+        if overrides.set_content_hash(identifier, hash) {
+            overrides.save()?;
+        }
+        Ok(())
+    });
+    if let Err(e) = result {
+        warn!("Failed to save desktop entry overrides: {}", e);
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::watcher::ChannelSource;
 
     #[test]
     fn test_determine_icon_info_png() {
@@ -543,7 +2429,7 @@ mod tests {
         let (size, ext) = determine_icon_info(path);
         assert_eq!(ext, "png");
         // Size detection might vary, just check it's reasonable
-        assert!(size >= 16 && size <= 512);
+        assert!((16..=512).contains(&size));
     }
 
     #[test]
@@ -552,4 +2438,317 @@ mod tests {
         let (_, ext) = determine_icon_info(path);
         assert_eq!(ext, "svg");
     }
+
+    #[test]
+    fn test_watch_directory_created_after_startup() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let later = temp_dir.path().join("Applications");
+
+        let mut config = Config::default();
+        config.watch.directories = vec![later.to_string_lossy().to_string()];
+        let mut daemon = Daemon::with_config(config).unwrap();
+
+        daemon.check_watches();
+        assert_eq!(daemon.unwatched_directories(), vec![later.clone()]);
+
+        fs::create_dir(&later).unwrap();
+        daemon.check_watches();
+        assert!(daemon.unwatched_directories().is_empty());
+        assert_eq!(daemon.watcher.watched_directories(), &[later]);
+    }
+
+    #[test]
+    fn test_synthetic_events_are_debounced() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("App.AppImage");
+
+        let mut config = Config::default();
+        config.watch.debounce_ms = 0;
+        let (source, events) = ChannelSource::new();
+        let mut daemon = Daemon::with_source(config, source).unwrap();
+
+        events.send(FileEvent::Created(path.clone())).unwrap();
+        events.send(FileEvent::Modified(path.clone())).unwrap();
+        while daemon.receive_event(Duration::ZERO).unwrap() {}
+        assert_eq!(daemon.pending_events.len(), 1);
+        assert!(matches!(
+            daemon.pending_events[&path].event,
+            FileEvent::Created(_)
+        ));
+
+        // Gone by the time it is handled: nothing to integrate
+        daemon.process_pending_events().unwrap();
+        assert!(daemon.pending_events.is_empty());
+        assert!(!daemon.state.is_integrated(&path));
+
+        drop(events);
+        assert!(daemon.receive_event(Duration::ZERO).is_err());
+    }
+
+    #[test]
+    fn test_replay_is_dry_run() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let from = temp_dir.path().join("App.AppImage");
+        let to = temp_dir.path().join("Renamed.AppImage");
+        let desktop_path = temp_dir.path().join("appimage-test123.desktop");
+        fs::write(&desktop_path, "[Desktop Entry]\n").unwrap();
+
+        let mut config = Config::default();
+        config.watch.debounce_ms = 0;
+        let (source, _events) = ChannelSource::new();
+        let mut daemon = Daemon::with_source(config, source).unwrap();
+        daemon.state = State::default();
+        daemon.state.add(state::create_entry(
+            "test123".to_string(),
+            from.clone(),
+            desktop_path.clone(),
+            vec![],
+            Some("Test App".to_string()),
+        ));
+        daemon.set_dry_run(true);
+
+        daemon
+            .replay(vec![
+                RecordedEvent {
+                    at_ms: 1000,
+                    event: FileEvent::Moved {
+                        from: from.clone(),
+                        to: to.clone(),
+                    },
+                },
+                RecordedEvent {
+                    at_ms: 1500,
+                    event: FileEvent::Deleted(to.clone()),
+                },
+            ])
+            .unwrap();
+
+        // Followed in memory, but the desktop entry is left alone
+        assert!(!daemon.state.is_integrated(&from));
+        assert!(!daemon.state.is_integrated(&to));
+        assert_eq!(
+            fs::read_to_string(&desktop_path).unwrap(),
+            "[Desktop Entry]\n"
+        );
+    }
+
+    #[test]
+    fn test_observe_mode() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let desktop_dir = temp_dir.path().join("applications");
+        let desktop_path = temp_dir.path().join("appimage-test123.desktop");
+        fs::write(&desktop_path, "[Desktop Entry]\n").unwrap();
+
+        let mut config = Config::default();
+        config.watch.directories = vec![temp_dir.path().display().to_string()];
+        config.integration.desktop_dir = desktop_dir.display().to_string();
+        let (source, _events) = ChannelSource::new();
+        let mut daemon = Daemon::with_source(config, source).unwrap();
+        daemon.state = State::default();
+        daemon.state.add(state::create_entry(
+            "test123".to_string(),
+            temp_dir.path().join("Gone.AppImage"),
+            desktop_path.clone(),
+            vec![],
+            None,
+        ));
+        daemon.set_dry_run(true);
+        daemon.init().unwrap();
+
+        // The orphan is dropped in memory only
+        assert_eq!(daemon.state.count(), 0);
+        assert!(desktop_path.exists());
+        assert!(!desktop_dir.exists());
+    }
+
+    #[test]
+    fn test_quarantine_dirs() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let downloads = temp_dir.path().join("Downloads");
+        let applications = temp_dir.path().join("Applications");
+        let download = downloads.join("App.AppImage");
+        fs::create_dir(&downloads).unwrap();
+        fs::write(&download, "AppImage").unwrap();
+
+        let mut config = Config::default();
+        config.notifications.enabled = false;
+        config.integration.quarantine_dirs = vec![downloads.to_string_lossy().to_string()];
+        config.integration.applications_dir = applications.to_string_lossy().to_string();
+        let (source, _events) = ChannelSource::new();
+        let mut daemon = Daemon::with_source(config, source).unwrap();
+        daemon.state = State::default();
+        daemon.state.set_read_only(true);
+
+        assert!(daemon.is_quarantined(&download));
+        assert!(!daemon.is_quarantined(&applications.join("App.AppImage")));
+        assert!(!daemon.discover(&download).unwrap());
+        assert_eq!(daemon.state.awaiting(), vec![&download]);
+        assert!(!daemon.state.is_integrated(&download));
+    }
+
+    #[test]
+    fn test_pending_event_max_delay() {
+        let start = Instant::now();
+        let debounce = Duration::from_secs(1);
+        let max_delay = Duration::from_secs(30);
+        let mut pending = PendingEvent::new(FileEvent::Created(PathBuf::from("/a")), start);
+
+        // Keeps being touched: never quiet long enough for the debounce
+        for secs in 1..30 {
+            pending.last_seen = start + Duration::from_millis(secs * 1000 - 500);
+            assert!(!pending.is_ready(start + Duration::from_secs(secs), debounce, max_delay));
+        }
+
+        // The cap forces it through
+        pending.last_seen = start + Duration::from_millis(29_900);
+        assert!(pending.is_ready(start + max_delay, debounce, max_delay));
+    }
+
+    #[test]
+    fn test_retry_busy() {
+        assert_eq!(busy_retry_delay(1), Duration::from_secs(1));
+        assert_eq!(busy_retry_delay(3), Duration::from_secs(4));
+        assert_eq!(busy_retry_delay(BUSY_RETRY_LIMIT), BUSY_RETRY_MAX_DELAY);
+
+        let (source, _events) = ChannelSource::new();
+        let mut daemon = Daemon::with_source(Config::default(), source).unwrap();
+        let path = PathBuf::from("/apps/App.AppImage");
+        let event = FileEvent::Created(path.clone());
+        assert!(DaemonError::Io(std::io::ErrorKind::ExecutableFileBusy.into()).is_busy());
+
+        assert!(daemon.retry_busy(&path, event.clone()));
+        let pending = &daemon.pending_events[&path];
+        let now = Instant::now();
+        assert!(!pending.is_ready(now, Duration::ZERO, Duration::ZERO));
+        assert!(pending.is_ready(now + BUSY_RETRY_DELAY, Duration::ZERO, Duration::ZERO));
+
+        // Left to the failure retries in the end
+        for _ in 1..BUSY_RETRY_LIMIT {
+            assert!(daemon.retry_busy(&path, event.clone()));
+        }
+        assert!(!daemon.retry_busy(&path, event));
+        assert!(!daemon.busy_retries.contains_key(&path));
+    }
+
+    #[test]
+    fn test_batch_ends_when_backlog_is_deleted() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let mut config = Config::default();
+        config.watch.debounce_ms = 0;
+        config.watch.batch_size = 1;
+        config.watch.inhibit_sleep = 2;
+        let (source, _events) = ChannelSource::new();
+        let mut daemon = Daemon::with_source(config, source).unwrap();
+        daemon.state = State::default();
+        daemon.state.set_read_only(true);
+
+        let paths: Vec<_> = (0..3)
+            .map(|i| temp_dir.path().join(format!("App{}.AppImage", i)))
+            .collect();
+        for path in &paths {
+            fs::write(path, b"").unwrap();
+            daemon.queue_event(FileEvent::Created(path.clone()));
+        }
+        daemon.process_pending_events().unwrap();
+        assert!(daemon.batching);
+        assert_eq!(daemon.pending_events.len(), 2);
+
+        for path in &paths[1..] {
+            fs::remove_file(path).unwrap();
+            daemon.queue_event(FileEvent::Deleted(path.clone()));
+        }
+        assert!(daemon.pending_events.is_empty());
+        daemon.process_pending_events().unwrap();
+        assert!(!daemon.batching);
+        assert!(daemon.inhibitor.is_none());
+    }
+
+    #[test]
+    fn test_record_error() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let from = temp_dir.path().join("App.AppImage");
+        let to = temp_dir.path().join("Renamed.AppImage");
+
+        let (source, _events) = ChannelSource::new();
+        let mut daemon = Daemon::with_source(Config::default(), source).unwrap();
+        daemon.state = State::default();
+        daemon.state.set_read_only(true);
+        daemon.state.add(state::create_entry(
+            "test123".to_string(),
+            from.clone(),
+            temp_dir.path().join("missing.desktop"),
+            vec![],
+            None,
+        ));
+
+        // Rewriting the missing desktop entry fails after the path moved
+        daemon.queue_event(FileEvent::Moved {
+            from,
+            to: to.clone(),
+        });
+        let error = daemon.state.get_by_path(&to).unwrap().last_error.clone();
+        assert!(error.is_some_and(|e| !e.message.is_empty()));
+
+        assert!(daemon.state.set_last_error(&to, None));
+        assert!(!daemon.state.set_last_error(&to, None));
+    }
+
+    #[test]
+    fn test_signature_without_gpg() {
+        let missing_gpg = || {
+            DaemonError::from(crate::signature::SignatureError::Io(std::io::Error::from(
+                std::io::ErrorKind::NotFound,
+            )))
+        };
+        let path = Path::new("/apps/Signed.AppImage");
+        assert!(signature_error(SignaturePolicy::Warn, path, missing_gpg()).is_ok());
+        assert!(matches!(
+            signature_error(SignaturePolicy::Require, path, missing_gpg()),
+            Err(DaemonError::Signature(_))
+        ));
+    }
+
+    #[test]
+    fn test_store_custom_icon_rejects_other_files() {
+        let error = store_custom_icon("test123", Path::new("/tmp/photo.jpg")).unwrap_err();
+        assert!(error.to_string().contains("not a PNG, SVG or XPM icon"));
+    }
+
+    #[test]
+    fn test_rescan_delay() {
+        let interval = Duration::from_secs(600);
+        assert_eq!(rescan_delay(interval, 0), Duration::from_secs(540));
+        assert_eq!(rescan_delay(interval, 120_000), Duration::from_secs(660));
+        for seed in [1, 59_999, u64::MAX] {
+            let delay = rescan_delay(interval, seed);
+            assert!(delay >= Duration::from_secs(540) && delay <= Duration::from_secs(660));
+        }
+
+        let (source, _events) = ChannelSource::new();
+        let mut daemon = Daemon::with_source(Config::default(), source).unwrap();
+        assert!(daemon.next_rescan().is_none());
+        daemon.config.watch.rescan_interval = 600;
+        assert!(daemon.next_rescan().is_some());
+    }
+
+    #[test]
+    fn test_resync() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let (source, _events) = ChannelSource::new();
+        let mut daemon = Daemon::with_source(Config::default(), source).unwrap();
+        daemon.state = State::default();
+        daemon.state.add(state::create_entry(
+            "test123".to_string(),
+            temp_dir.path().join("Gone.AppImage"),
+            temp_dir.path().join("appimage-test123.desktop"),
+            vec![],
+            None,
+        ));
+        daemon.set_dry_run(true);
+
+        // The deletion was among the dropped events
+        daemon.queue_event(FileEvent::Resync(vec![temp_dir.path().to_path_buf()]));
+        assert_eq!(daemon.state.count(), 0);
+        assert!(daemon.pending_events.is_empty());
+    }
 }
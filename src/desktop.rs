@@ -1,6 +1,10 @@
 //! Desktop entry file handling according to freedesktop.org specification.
 
-use std::collections::HashMap;
+use crate::appstream::Translations;
+use crate::config::{AppProfile, CategoriesConfig};
+use crate::overrides::DesktopOverrides;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
 use std::fs;
 use std::io::{BufRead, BufReader, Write};
 use std::path::{Path, PathBuf};
@@ -101,11 +105,25 @@ impl DesktopEntry {
         self.entries.get("Exec").map(|s| s.as_str())
     }
 
+    /// Program the Exec command runs, past an `env` prefix and its
+    /// variables, with quotes removed
+    pub fn exec_program(&self) -> Option<PathBuf> {
+        exec_words(self.exec()?)
+            .into_iter()
+            .find(|word| word != "env" && !word.contains('='))
+            .map(PathBuf::from)
+    }
+
     /// Get the Icon name
     pub fn icon(&self) -> Option<&str> {
         self.entries.get("Icon").map(|s| s.as_str())
     }
 
+    /// Get the application version declared by the AppImage (`X-AppImage-Version`)
+    pub fn version(&self) -> Option<&str> {
+        self.entries.get("X-AppImage-Version").map(|s| s.as_str())
+    }
+
     /// Get the entry Type
     pub fn entry_type(&self) -> Option<&str> {
         self.entries.get("Type").map(|s| s.as_str())
@@ -130,6 +148,15 @@ impl DesktopEntry {
         self.entries.insert("Exec".to_string(), new_exec);
     }
 
+    /// Append text to the name and its translations (`Name[de]`, ...)
+    pub fn append_to_name(&mut self, suffix: &str) {
+        for (key, value) in self.entries.iter_mut() {
+            if key == "Name" || key.starts_with("Name[") {
+                value.push_str(suffix);
+            }
+        }
+    }
+
     /// Set the Icon to a specific path or name
     pub fn set_icon(&mut self, icon: &str) {
         self.entries.insert("Icon".to_string(), icon.to_string());
@@ -148,19 +175,83 @@ impl DesktopEntry {
             .map(|s| s.as_str())
     }
 
-    /// Add StartupWMClass if not present (helps with taskbar grouping)
-    pub fn ensure_startup_wm_class(&mut self) {
-        if !self.entries.contains_key("StartupWMClass")
-            && let Some(name) = self.name()
+    /// Add StartupWMClass if not present, so docks and taskbars group the
+    /// app's windows under this entry (on Wayland by the window's app id).
+    ///
+    /// Inferred from the app's own hints, most reliable first: its AppStream
+    /// `app_id`, a reverse-DNS desktop file name (the app id of GTK and Qt
+    /// apps), the binary from `Exec` (the `WM_CLASS` of most other toolkits,
+    /// such as Electron) and only then the sanitized display name. Apps with
+    /// a reverse-DNS id also get `StartupNotify=true` unless they say
+    /// otherwise, as GTK and Qt complete startup notification themselves.
+    /// Call before [`Self::set_exec`], which replaces the binary.
+    pub fn ensure_startup_wm_class(&mut self, app_id: Option<&str>) {
+        if self.entries.contains_key("StartupWMClass") {
+            return;
+        }
+
+        let desktop_id = self
+            .source_path
+            .as_deref()
+            .and_then(Path::file_stem)
+            .map(|stem| stem.to_string_lossy().to_string())
+            .filter(|stem| is_reverse_dns(stem));
+        let app_id = app_id.map(str::to_string).or(desktop_id);
+        if app_id.as_deref().is_some_and(is_reverse_dns) {
+            self.entries
+                .entry("StartupNotify".to_string())
+                .or_insert_with(|| "true".to_string());
+        }
+
+        let wm_class = app_id
+            .or_else(|| self.exec().and_then(exec_binary))
+            .or_else(|| {
+                self.name().map(|name| {
+                    name.chars()
+                        .filter(|c| c.is_alphanumeric() || *c == '-' || *c == '_')
+                        .collect()
+                })
+            })
+            .filter(|class| !class.is_empty());
+        if let Some(wm_class) = wm_class {
+            self.entries.insert("StartupWMClass".to_string(), wm_class);
+        }
+    }
+
+    /// Set the Comment (shown as tooltip and searched by menus) if the entry
+    /// has none or an empty one
+    pub fn ensure_comment(&mut self, comment: &str) {
+        if self
+            .entries
+            .get("Comment")
+            .is_none_or(|c| c.trim().is_empty())
         {
-            // Use a sanitized version of the name
-            let wm_class = name
-                .chars()
-                .filter(|c| c.is_alphanumeric() || *c == '-' || *c == '_')
-                .collect::<String>();
-            if !wm_class.is_empty() {
-                self.entries.insert("StartupWMClass".to_string(), wm_class);
-            }
+            self.entries
+                .insert("Comment".to_string(), comment.to_string());
+        }
+    }
+
+    /// Add the translations (`Name[de]`, ...) of a key the entry doesn't
+    /// translate into those languages itself
+    pub fn ensure_translations(&mut self, key: &str, translations: &BTreeMap<String, String>) {
+        for (lang, value) in translations {
+            self.entries
+                .entry(format!("{}[{}]", key, lang))
+                .or_insert_with(|| value.clone());
+        }
+    }
+
+    /// Add the AppStream translations of the name, and of the summary if it
+    /// is the entry's Comment (a translated summary would contradict a
+    /// Comment of the entry's own)
+    pub fn add_appstream_translations(
+        &mut self,
+        translations: &Translations,
+        summary: Option<&str>,
+    ) {
+        self.ensure_translations("Name", &translations.names);
+        if summary.is_some() && self.entries.get("Comment").map(String::as_str) == summary {
+            self.ensure_translations("Comment", &translations.summaries);
         }
     }
 
@@ -170,6 +261,67 @@ impl DesktopEntry {
             .insert("TryExec".to_string(), appimage_path.display().to_string());
     }
 
+    /// Route the Exec lines (including actions) through a launcher that
+    /// counts launches, e.g. `"<launcher>" track -- "<appimage>" %F`
+    pub fn set_launcher(&mut self, launcher: &Path) {
+        self.prefix_exec(&format!("\"{}\" {} --", launcher.display(), TRACK_COMMAND));
+    }
+
+    /// Undo [`Self::set_launcher`], running the AppImage directly again
+    pub fn remove_launcher(&mut self) {
+        let marker = format!(" {} -- ", TRACK_COMMAND);
+        for exec in self.execs_mut() {
+            if exec.starts_with('"')
+                && let Some(pos) = exec.find(&marker)
+            {
+                *exec = exec[pos + marker.len()..].to_string();
+            }
+        }
+    }
+
+    /// Point the Exec lines (including actions) and TryExec, written for the
+    /// AppImage at `from`, at `to`
+    pub fn relocate_appimage(&mut self, from: &Path, to: &Path) {
+        let old = format!("\"{}\"", from.display());
+        let new = format!("\"{}\"", to.display());
+        for exec in self.execs_mut() {
+            *exec = exec.replace(&old, &new);
+        }
+        let try_exec = self.entries.get("TryExec").map(Path::new);
+        if try_exec == Some(from) {
+            self.set_try_exec(to);
+        }
+    }
+
+    /// Put a command in front of the Exec lines (including actions)
+    pub fn prefix_exec(&mut self, prefix: &str) {
+        for exec in self.execs_mut() {
+            *exec = format!("{} {}", prefix, exec);
+        }
+    }
+
+    /// Pass extra arguments to the AppImage in the Exec lines (including
+    /// actions), before the entry's own
+    pub fn insert_exec_args(&mut self, appimage_path: &Path, args: &[String]) {
+        if args.is_empty() {
+            return;
+        }
+        let program = format!("\"{}\"", appimage_path.display());
+        let args: Vec<String> = args.iter().map(|arg| exec_arg(arg)).collect();
+        for exec in self.execs_mut() {
+            if let Some(rest) = exec.strip_prefix(&program) {
+                *exec = format!("{} {}{}", program, args.join(" "), rest);
+            }
+        }
+    }
+
+    /// Exec values of the entry and its actions
+    fn execs_mut(&mut self) -> impl Iterator<Item = &mut String> {
+        std::iter::once(&mut self.entries)
+            .chain(self.actions.values_mut())
+            .filter_map(|entries| entries.get_mut("Exec"))
+    }
+
     /// Update actions' Exec lines
     pub fn update_action_exec(&mut self, appimage_path: &Path) {
         for (_action_name, action_entries) in self.actions.iter_mut() {
@@ -185,6 +337,12 @@ impl DesktopEntry {
         }
     }
 
+    /// Remove the desktop actions and the `Actions` key listing them
+    pub fn remove_actions(&mut self) {
+        self.actions.clear();
+        self.entries.remove("Actions");
+    }
+
     /// Write the desktop entry to a file
     pub fn write(&self, path: &Path) -> Result<(), DesktopError> {
         let mut file = fs::File::create(path)?;
@@ -247,11 +405,154 @@ fn extract_exec_args(exec: &str) -> String {
     }
 }
 
+/// Split an Exec line into words, undoing the double quoting and escapes of
+/// the desktop entry spec
+pub(crate) fn exec_words(exec: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut quoted = false;
+    let mut chars = exec.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => quoted = !quoted,
+            '\\' if quoted => word.extend(chars.next()),
+            c if c.is_whitespace() && !quoted => {
+                if !word.is_empty() {
+                    words.push(std::mem::take(&mut word));
+                }
+            }
+            c => word.push(c),
+        }
+    }
+    if !word.is_empty() {
+        words.push(word);
+    }
+    words
+}
+
+/// Name of the binary an Exec line runs, unless it is the generic `AppRun`
+fn exec_binary(exec: &str) -> Option<String> {
+    let program = exec
+        .split_whitespace()
+        .find(|word| *word != "env" && !word.contains('='))?
+        .trim_matches(['"', '\'']);
+    let binary = Path::new(program).file_name()?.to_string_lossy();
+    (!binary.eq_ignore_ascii_case("AppRun") && !binary.starts_with('%')).then(|| binary.to_string())
+}
+
+/// Whether an id is in reverse-DNS form, e.g. `org.kde.krita`
+fn is_reverse_dns(id: &str) -> bool {
+    let parts: Vec<&str> = id.split('.').collect();
+    parts.len() >= 3
+        && parts.iter().all(|part| {
+            !part.is_empty()
+                && part
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        })
+}
+
+/// Quote an argument for an Exec line where needed, as the desktop entry
+/// spec describes, including the escaping of backslashes the file format
+/// applies on top
+fn exec_arg(arg: &str) -> String {
+    const RESERVED: &[char] = &[
+        ' ', '\t', '"', '\'', '\\', '>', '<', '~', '|', '&', ';', '$', '*', '?', '#', '(', ')', '`',
+    ];
+
+    let arg = arg.replace('%', "%%");
+    let quoted = if arg.is_empty() || arg.contains(RESERVED) {
+        let mut quoted = String::from("\"");
+        for c in arg.chars() {
+            if matches!(c, '"' | '`' | '$' | '\\') {
+                quoted.push('\\');
+            }
+            quoted.push(c);
+        }
+        quoted.push('"');
+        quoted
+    } else {
+        arg
+    };
+    quoted.replace('\\', "\\\\")
+}
+
+/// Command the Exec lines of an app are run through for its launch
+/// settings: `env` for its environment and `firejail` for the sandbox
+fn launch_prefix(profile: &AppProfile) -> Option<String> {
+    let mut env: Vec<String> = profile
+        .env
+        .iter()
+        .map(|(key, value)| exec_arg(&format!("{}={}", key, value)))
+        .collect();
+    if profile.extract_and_run {
+        env.push("APPIMAGE_EXTRACT_AND_RUN=1".to_string());
+    }
+
+    let mut prefix = Vec::new();
+    if !env.is_empty() {
+        prefix.push("env".to_string());
+        prefix.extend(env);
+    }
+    if profile.sandbox {
+        prefix.extend(["firejail".to_string(), "--appimage".to_string()]);
+        if !profile.network {
+            prefix.push("--net=none".to_string());
+        }
+    }
+    (!prefix.is_empty()).then(|| prefix.join(" "))
+}
+
+/// System icon theme directories (`/usr/local` for FreeBSD ports), whose
+/// icons are referenced by name
+const SYSTEM_ICON_DIRS: &[&str] = &["/usr/share/icons", "/usr/local/share/icons"];
+
+/// CLI subcommand that counts a launch and then runs the AppImage
+pub const TRACK_COMMAND: &str = "track";
+
+/// Find the `appimage-auto` binary to launch tracked apps through: the
+/// running binary, or one next to it (e.g. when running the GUI). None
+/// inside a Flatpak sandbox, where the binary isn't visible to the host.
+pub fn tracking_launcher() -> Option<PathBuf> {
+    if crate::flatpak::is_sandboxed() {
+        return None;
+    }
+    let exe = std::env::current_exe().ok()?;
+    if exe.file_name().is_some_and(|name| name == "appimage-auto") {
+        return Some(exe);
+    }
+    let sibling = exe.with_file_name("appimage-auto");
+    sibling.is_file().then_some(sibling)
+}
+
 /// Generate a desktop file name for an integrated AppImage
 pub fn generate_desktop_filename(identifier: &str) -> String {
     format!("appimage-{}.desktop", identifier)
 }
 
+/// Changes made to an AppImage's own desktop entry when installing it
+#[derive(Debug, Clone, Copy, Default)]
+pub struct EntryCustomizations<'a> {
+    /// Category rewrites from the config
+    pub categories: Option<&'a CategoriesConfig>,
+    /// Appended to the name, including its translations
+    pub name_suffix: Option<&'a str>,
+    /// The user's edits for this app (applied last, so they win)
+    pub overrides: Option<&'a DesktopOverrides>,
+    /// Launcher counting launches (see [`DesktopEntry::set_launcher`])
+    pub launcher: Option<&'a Path>,
+    /// The app's launch settings from the config
+    pub profile: Option<&'a AppProfile>,
+    /// App id from the AppImage's AppStream metainfo
+    pub app_id: Option<&'a str>,
+    /// Comment for entries without one (the AppStream summary)
+    pub comment: Option<&'a str>,
+    /// Translated names and summaries from the AppImage's AppStream metainfo
+    pub translations: Option<&'a Translations>,
+    /// Leave out the desktop actions
+    pub without_actions: bool,
+}
+
 /// Install a desktop entry for an AppImage
 pub fn install_desktop_entry(
     source_desktop: &Path,
@@ -259,22 +560,50 @@ pub fn install_desktop_entry(
     icon_path: Option<&Path>,
     identifier: &str,
     desktop_dir: &Path,
+    customizations: &EntryCustomizations,
 ) -> Result<PathBuf, DesktopError> {
     // Parse the original desktop file
     let mut entry = DesktopEntry::parse(source_desktop)?;
 
     // Modify for our purposes
+    entry.ensure_startup_wm_class(customizations.app_id);
+    if let Some(comment) = customizations.comment {
+        entry.ensure_comment(comment);
+    }
+    if let Some(translations) = customizations.translations {
+        entry.add_appstream_translations(translations, customizations.comment);
+    }
     entry.set_exec(appimage_path);
     entry.set_try_exec(appimage_path);
     entry.set_appimage_identifier(identifier);
-    entry.ensure_startup_wm_class();
     entry.update_action_exec(appimage_path);
+    if customizations.without_actions {
+        entry.remove_actions();
+    }
+    if let Some(profile) = customizations.profile {
+        entry.insert_exec_args(appimage_path, &profile.args);
+        if profile.hidden {
+            entry
+                .entries
+                .insert("NoDisplay".to_string(), "true".to_string());
+        }
+    }
+    // firejail has to start the AppImage itself
+    let sandboxed = customizations.profile.is_some_and(|p| p.sandbox);
+    if let Some(launcher) = customizations.launcher
+        && !sandboxed
+    {
+        entry.set_launcher(launcher);
+    }
+    if let Some(prefix) = customizations.profile.and_then(launch_prefix) {
+        entry.prefix_exec(&prefix);
+    }
 
     // Set icon if provided
     if let Some(icon) = icon_path {
         // Use the icon name without path if it's in a standard location,
         // otherwise use full path
-        let icon_str = if icon.starts_with("/usr/share/icons")
+        let icon_str = if SYSTEM_ICON_DIRS.iter().any(|dir| icon.starts_with(dir))
             || icon.to_string_lossy().contains("/.local/share/icons/")
         {
             // Extract just the icon name for theme lookup
@@ -287,6 +616,29 @@ pub fn install_desktop_entry(
         entry.set_icon(&icon_str);
     }
 
+    if let Some(categories) = customizations.categories {
+        let file_name = appimage_path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let current = entry.entries.get("Categories").cloned().unwrap_or_default();
+        let remapped = categories.remap(&current, entry.name().unwrap_or(""), &file_name);
+        if remapped.is_empty() {
+            entry.entries.remove("Categories");
+        } else {
+            entry.entries.insert("Categories".to_string(), remapped);
+        }
+    }
+
+    if let Some(suffix) = customizations.name_suffix {
+        entry.append_to_name(suffix);
+    }
+
+    // User edits win over the AppImage's own values
+    if let Some(overrides) = customizations.overrides {
+        overrides.apply(&mut entry);
+    }
+
     // Ensure desktop directory exists
     fs::create_dir_all(desktop_dir)?;
 
@@ -309,9 +661,7 @@ pub fn remove_desktop_entry(desktop_path: &Path) -> Result<(), DesktopError> {
 
 /// Update the desktop database
 pub fn update_desktop_database(desktop_dir: &Path) -> Result<(), DesktopError> {
-    use std::process::Command;
-
-    let output = Command::new("update-desktop-database")
+    let output = crate::flatpak::host_command("update-desktop-database", &[])
         .arg(desktop_dir)
         .output();
 
@@ -333,6 +683,80 @@ pub fn update_desktop_database(desktop_dir: &Path) -> Result<(), DesktopError> {
     Ok(())
 }
 
+/// A key that differs between two versions of a desktop entry
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EntryChange {
+    /// Group the key is in, e.g. `Desktop Entry` or `Desktop Action new-window`
+    pub group: String,
+    pub key: String,
+    /// Value before, `None` if the key was added
+    pub old: Option<String>,
+    /// Value after, `None` if the key was removed
+    pub new: Option<String>,
+}
+
+impl std::fmt::Display for EntryChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.group != "Desktop Entry" {
+            write!(f, "[{}] ", self.group)?;
+        }
+        match (&self.old, &self.new) {
+            (Some(old), Some(new)) => write!(f, "{}: {} -> {}", self.key, old, new),
+            (None, Some(new)) => write!(f, "+{}={}", self.key, new),
+            (Some(old), None) => write!(f, "-{}={}", self.key, old),
+            (None, None) => write!(f, "{}", self.key),
+        }
+    }
+}
+
+/// Keys added, removed or changed between two desktop entry files, by group
+/// and key (the order keys are written in doesn't matter)
+pub fn diff_entries(old: &str, new: &str) -> Vec<EntryChange> {
+    let old = key_values(old);
+    let mut new = key_values(new);
+
+    let mut changes = Vec::new();
+    for ((group, key), old_value) in old {
+        let new_value = new.remove(&(group.clone(), key.clone()));
+        if new_value.as_ref() != Some(&old_value) {
+            changes.push(EntryChange {
+                group,
+                key,
+                old: Some(old_value),
+                new: new_value,
+            });
+        }
+    }
+    changes.extend(new.into_iter().map(|((group, key), value)| EntryChange {
+        group,
+        key,
+        old: None,
+        new: Some(value),
+    }));
+    changes.sort_by(|a, b| (&a.group, &a.key).cmp(&(&b.group, &b.key)));
+    changes
+}
+
+/// Values of a desktop entry file by group and key
+fn key_values(contents: &str) -> BTreeMap<(String, String), String> {
+    let mut values = BTreeMap::new();
+    let mut group = String::new();
+    for line in contents.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            group = name.to_string();
+        } else if let Some((key, value)) = line.split_once('=') {
+            values.insert(
+                (group.clone(), key.trim().to_string()),
+                value.trim().to_string(),
+            );
+        }
+    }
+    values
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -345,10 +769,248 @@ mod tests {
         assert_eq!(extract_exec_args("/path/to/app arg1 arg2"), "arg1 arg2");
     }
 
+    #[test]
+    fn test_exec_program() {
+        let program = |exec: &str| {
+            DesktopEntry {
+                entries: HashMap::from([("Exec".to_string(), exec.to_string())]),
+                actions: HashMap::new(),
+                source_path: None,
+            }
+            .exec_program()
+        };
+        assert_eq!(
+            program("\"/home/me/My Apps/App.AppImage\" %U"),
+            Some(PathBuf::from("/home/me/My Apps/App.AppImage"))
+        );
+        assert_eq!(
+            program("env GDK_SCALE=2 /opt/App.AppImage --no-sandbox"),
+            Some(PathBuf::from("/opt/App.AppImage"))
+        );
+        assert_eq!(program(""), None);
+    }
+
     #[test]
     fn test_generate_desktop_filename() {
         let id = "abc123def456";
         let filename = generate_desktop_filename(id);
         assert_eq!(filename, "appimage-abc123def456.desktop");
     }
+
+    #[test]
+    fn test_diff_entries() {
+        let old = "[Desktop Entry]\nName=App\nExec=/a/App-1.AppImage %U\nComment=Old\n\n\
+                   [Desktop Action new]\nName=New Window\n";
+        let new = "[Desktop Entry]\nExec=/a/App-2.AppImage %U\nName=App\nKeywords=edit;\n\n\
+                   [Desktop Action new]\nName=New Window\n";
+        let changes: Vec<String> = diff_entries(old, new)
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(
+            changes,
+            [
+                "-Comment=Old",
+                "Exec: /a/App-1.AppImage %U -> /a/App-2.AppImage %U",
+                "+Keywords=edit;",
+            ]
+        );
+        assert!(diff_entries(old, old).is_empty());
+
+        let changes = diff_entries("[Desktop Action new]\nName=New\n", "");
+        assert_eq!(changes[0].to_string(), "[Desktop Action new] -Name=New");
+    }
+
+    #[test]
+    fn test_ensure_startup_wm_class() {
+        let entry = |path: &str, exec: &str| DesktopEntry {
+            entries: HashMap::from([
+                ("Name".to_string(), "My App!".to_string()),
+                ("Exec".to_string(), exec.to_string()),
+            ]),
+            actions: HashMap::new(),
+            source_path: Some(PathBuf::from(path)),
+        };
+        let inferred = |mut entry: DesktopEntry, app_id: Option<&str>| {
+            entry.ensure_startup_wm_class(app_id);
+            (
+                entry.entries.get("StartupWMClass").cloned(),
+                entry.entries.get("StartupNotify").cloned(),
+            )
+        };
+
+        assert_eq!(
+            inferred(entry("/x/krita.desktop", "krita %F"), Some("org.kde.krita")),
+            (Some("org.kde.krita".to_string()), Some("true".to_string()))
+        );
+        assert_eq!(
+            inferred(
+                entry("/x/org.gnome.Maps.desktop", "gapplication launch"),
+                None
+            ),
+            (Some("org.gnome.Maps".to_string()), Some("true".to_string()))
+        );
+        assert_eq!(
+            inferred(
+                entry("/x/obsidian.desktop", "env A=1 \"obsidian\" %U"),
+                None
+            ),
+            (Some("obsidian".to_string()), None)
+        );
+        assert_eq!(
+            inferred(entry("/x/app.desktop", "AppRun %F"), None),
+            (Some("MyApp".to_string()), None)
+        );
+
+        // The app's own values are kept
+        let mut declared = entry("/x/org.kde.krita.desktop", "krita");
+        declared
+            .entries
+            .insert("StartupWMClass".to_string(), "Krita".to_string());
+        declared
+            .entries
+            .insert("StartupNotify".to_string(), "false".to_string());
+        assert_eq!(
+            inferred(declared, None),
+            (Some("Krita".to_string()), Some("false".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_set_launcher() {
+        let mut entry = DesktopEntry {
+            entries: HashMap::from([("Exec".to_string(), "app %F".to_string())]),
+            actions: HashMap::from([(
+                "Desktop Action new-window".to_string(),
+                HashMap::from([("Exec".to_string(), "app --new-window".to_string())]),
+            )]),
+            source_path: None,
+        };
+        let appimage = Path::new("/apps/App.AppImage");
+        entry.set_exec(appimage);
+        entry.update_action_exec(appimage);
+        entry.set_launcher(Path::new("/usr/bin/appimage-auto"));
+
+        assert_eq!(
+            entry.exec(),
+            Some("\"/usr/bin/appimage-auto\" track -- \"/apps/App.AppImage\" %F")
+        );
+        assert_eq!(
+            entry.actions["Desktop Action new-window"]["Exec"],
+            "\"/usr/bin/appimage-auto\" track -- \"/apps/App.AppImage\" --new-window"
+        );
+
+        entry.remove_launcher();
+        entry.set_try_exec(appimage);
+        entry.relocate_appimage(appimage, Path::new("/mnt/apps/App.AppImage"));
+        assert_eq!(entry.exec(), Some("\"/mnt/apps/App.AppImage\" %F"));
+        assert_eq!(
+            entry.actions["Desktop Action new-window"]["Exec"],
+            "\"/mnt/apps/App.AppImage\" --new-window"
+        );
+        assert_eq!(entry.entries["TryExec"], "/mnt/apps/App.AppImage");
+    }
+
+    #[test]
+    fn test_ensure_comment() {
+        let mut entry = DesktopEntry {
+            entries: HashMap::from([("Comment".to_string(), " ".to_string())]),
+            actions: HashMap::new(),
+            source_path: None,
+        };
+        entry.ensure_comment("Digital painting");
+        assert_eq!(entry.entries["Comment"], "Digital painting");
+        entry.ensure_comment("Other");
+        assert_eq!(entry.entries["Comment"], "Digital painting");
+    }
+
+    #[test]
+    fn test_add_appstream_translations() {
+        let translations = Translations {
+            names: BTreeMap::from([
+                ("de".to_string(), "Malen".to_string()),
+                ("fr".to_string(), "Peindre".to_string()),
+            ]),
+            summaries: BTreeMap::from([("de".to_string(), "Digitales Malen".to_string())]),
+        };
+        let mut entry = DesktopEntry {
+            entries: HashMap::from([
+                ("Name".to_string(), "Paint".to_string()),
+                ("Name[fr]".to_string(), "Peinture".to_string()),
+                ("Comment".to_string(), "Digital painting".to_string()),
+            ]),
+            actions: HashMap::new(),
+            source_path: None,
+        };
+        entry.add_appstream_translations(&translations, Some("Draw things"));
+        assert_eq!(entry.entries["Name[de]"], "Malen");
+        assert_eq!(entry.entries["Name[fr]"], "Peinture");
+        assert!(!entry.entries.contains_key("Comment[de]"));
+
+        entry.add_appstream_translations(&translations, Some("Digital painting"));
+        assert_eq!(entry.entries["Comment[de]"], "Digitales Malen");
+    }
+
+    #[test]
+    fn test_launch_profile() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let source = temp.path().join("app.desktop");
+        fs::write(
+            &source,
+            "[Desktop Entry]\nType=Application\nName=App\nExec=app %F\n",
+        )
+        .unwrap();
+        let appimage = Path::new("/apps/App.AppImage");
+        let launcher = Path::new("/usr/bin/appimage-auto");
+
+        let mut profile = AppProfile {
+            env: [("GDK_SCALE".to_string(), "2".to_string())].into(),
+            args: vec!["--profile".to_string(), "My Work".to_string()],
+            extract_and_run: true,
+            hidden: true,
+            ..Default::default()
+        };
+        let install = |profile: &AppProfile| {
+            let path = install_desktop_entry(
+                &source,
+                appimage,
+                None,
+                "abc",
+                temp.path(),
+                &EntryCustomizations {
+                    launcher: Some(launcher),
+                    profile: Some(profile),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+            DesktopEntry::parse(&path).unwrap()
+        };
+
+        let entry = install(&profile);
+        assert_eq!(
+            entry.exec(),
+            Some(
+                "env GDK_SCALE=2 APPIMAGE_EXTRACT_AND_RUN=1 \"/usr/bin/appimage-auto\" track -- \
+                 \"/apps/App.AppImage\" --profile \"My Work\" %F"
+            )
+        );
+        assert_eq!(entry.entries["NoDisplay"], "true");
+
+        profile.env.clear();
+        profile.extract_and_run = false;
+        profile.sandbox = true;
+        assert_eq!(
+            install(&profile).exec(),
+            Some("firejail --appimage \"/apps/App.AppImage\" --profile \"My Work\" %F")
+        );
+        profile.network = false;
+        assert_eq!(
+            install(&profile).exec(),
+            Some("firejail --appimage --net=none \"/apps/App.AppImage\" --profile \"My Work\" %F")
+        );
+
+        assert_eq!(exec_arg("100%"), "100%%");
+        assert_eq!(exec_arg("$HOME"), "\"\\\\$HOME\"");
+    }
 }
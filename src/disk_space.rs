@@ -0,0 +1,86 @@
+//! Free space checks before writing.
+//!
+//! Integrating extracts files into the cache directory and writes icons and
+//! desktop entries; extracting an AppImage from an archive writes a copy as
+//! large as the archive. Running out of space halfway leaves truncated icons
+//! and AppImages behind, so the daemon checks the directories it is about to
+//! write to first and skips the AppImage with a notification when one has
+//! less than `integration.min_free_space_mb` left.
+
+use crate::format;
+use std::ffi::CString;
+use std::fmt;
+use std::io;
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+
+/// A directory without the space an operation needs
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LowSpace {
+    pub dir: PathBuf,
+    /// Bytes available
+    pub available: u64,
+    /// Bytes needed
+    pub required: u64,
+}
+
+impl fmt::Display for LowSpace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "only {} free in {}, {} needed",
+            format::human_size(self.available),
+            self.dir.display(),
+            format::human_size(self.required)
+        )
+    }
+}
+
+/// Bytes available to unprivileged users on the file system holding `path`.
+/// For a path that doesn't exist yet, its nearest existing ancestor is used.
+pub fn available(path: &Path) -> io::Result<u64> {
+    let existing = path
+        .ancestors()
+        .find(|dir| dir.exists())
+        .unwrap_or(Path::new("/"));
+    let c_path = CString::new(existing.as_os_str().as_bytes())?;
+
+    // SAFETY: c_path is NUL-terminated and stat is a valid statvfs to fill
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(c_path.as_ptr(), &mut stat) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    // Both are u64 on 64-bit Linux but narrower on some other targets
+    #[allow(clippy::useless_conversion)]
+    Ok(u64::from(stat.f_bavail) * u64::from(stat.f_frsize))
+}
+
+/// The first of `dirs` with less than `required` bytes available. Directories
+/// whose file system can't be queried are skipped.
+pub fn check<'a>(dirs: impl IntoIterator<Item = &'a Path>, required: u64) -> Option<LowSpace> {
+    dirs.into_iter().find_map(|dir| {
+        let available = available(dir).ok()?;
+        (available < required).then(|| LowSpace {
+            dir: dir.to_path_buf(),
+            available,
+            required,
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let missing = dir.path().join("not/created/yet");
+        assert!(available(&missing).unwrap() > 0);
+
+        assert_eq!(check([dir.path(), missing.as_path()], 0), None);
+        let low = check([missing.as_path()], u64::MAX).unwrap();
+        assert_eq!(low.dir, missing);
+        assert!(low.to_string().starts_with("only "));
+    }
+}
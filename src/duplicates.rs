@@ -0,0 +1,220 @@
+//! Detection of AppImages that duplicate natively installed apps.
+//!
+//! An AppImage of an app that is also installed from the distribution or
+//! Flatpak shows up twice in the application menu under the same name. The
+//! AppImage's embedded desktop entry is compared against the desktop entries
+//! in the system and Flatpak application directories by desktop file ID,
+//! `StartupWMClass` and `Name`.
+
+use crate::desktop::DesktopEntry;
+use crate::i18n::tr;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use tracing::debug;
+
+/// Suffix added to the menu name of duplicates when enabled
+pub fn name_suffix() -> &'static str {
+    tr(" (AppImage)")
+}
+
+/// What a native desktop entry has in common with the AppImage's
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MatchReason {
+    /// Same desktop file name
+    DesktopId,
+    /// Same `StartupWMClass`
+    WmClass,
+    /// Same `Name`
+    Name,
+}
+
+impl MatchReason {
+    /// Human-readable description
+    pub fn label(self) -> &'static str {
+        match self {
+            MatchReason::DesktopId => "same desktop ID",
+            MatchReason::WmClass => "same window class",
+            MatchReason::Name => "same name",
+        }
+    }
+}
+
+/// A natively installed app the AppImage duplicates
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NativeDuplicate {
+    /// Desktop entry of the native app
+    pub desktop_path: PathBuf,
+    /// Strongest reason the entries were matched
+    pub reason: MatchReason,
+}
+
+/// Application directories of system packages and Flatpak.
+///
+/// Follows `XDG_DATA_DIRS` (defaulting to `/usr/local/share:/usr/share`) and
+/// adds the system and user Flatpak exports. The user's own
+/// `~/.local/share/applications` is left out, as that is where integrated
+/// AppImages go.
+pub fn native_application_dirs() -> Vec<PathBuf> {
+    let data_dirs = std::env::var("XDG_DATA_DIRS")
+        .ok()
+        .filter(|dirs| !dirs.is_empty())
+        .unwrap_or_else(|| "/usr/local/share:/usr/share".to_string());
+    let mut dirs: Vec<PathBuf> = data_dirs
+        .split(':')
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .collect();
+    dirs.push(PathBuf::from("/var/lib/flatpak/exports/share"));
+    if let Some(base) = directories::BaseDirs::new() {
+        dirs.push(base.data_dir().join("flatpak/exports/share"));
+    }
+
+    let mut apps: Vec<PathBuf> = Vec::new();
+    for dir in dirs {
+        let dir = dir.join("applications");
+        if !apps.contains(&dir) {
+            apps.push(dir);
+        }
+    }
+    apps
+}
+
+/// Find a native app in `dirs` that the AppImage's embedded desktop entry
+/// (installed as `desktop_id`) duplicates.
+///
+/// Entries of other AppImages and hidden entries are ignored. When several
+/// entries match, the one with the strongest reason wins.
+pub fn find_native(
+    entry: &DesktopEntry,
+    desktop_id: &str,
+    dirs: &[PathBuf],
+) -> Option<NativeDuplicate> {
+    let name = entry.name().map(str::to_lowercase);
+    let wm_class = entry
+        .entries
+        .get("StartupWMClass")
+        .map(|c| c.to_lowercase());
+
+    let mut best: Option<NativeDuplicate> = None;
+    for path in dirs.iter().flat_map(|dir| desktop_files(dir)) {
+        let Ok(native) = DesktopEntry::parse(&path) else {
+            continue;
+        };
+        if native.appimage_identifier().is_some() || is_hidden(&native) {
+            continue;
+        }
+
+        let native_wm_class = native
+            .entries
+            .get("StartupWMClass")
+            .map(|c| c.to_lowercase());
+        let reason = if path.file_name().is_some_and(|n| n == desktop_id) {
+            MatchReason::DesktopId
+        } else if wm_class.is_some() && native_wm_class == wm_class {
+            MatchReason::WmClass
+        } else if name.is_some() && native.name().map(str::to_lowercase) == name {
+            MatchReason::Name
+        } else {
+            continue;
+        };
+
+        debug!("{} duplicates {:?} ({})", desktop_id, path, reason.label());
+        if best.as_ref().is_none_or(|b| reason < b.reason) {
+            best = Some(NativeDuplicate {
+                desktop_path: path,
+                reason,
+            });
+        }
+    }
+    best
+}
+
+/// Whether an entry is not shown in menus
+fn is_hidden(entry: &DesktopEntry) -> bool {
+    ["NoDisplay", "Hidden"]
+        .iter()
+        .any(|key| entry.entries.get(*key).is_some_and(|v| v == "true"))
+}
+
+/// Desktop entries directly inside a directory, sorted
+fn desktop_files(dir: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut paths: Vec<PathBuf> = entries
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| p.extension().is_some_and(|ext| ext == "desktop"))
+        .collect();
+    paths.sort();
+    paths
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(dir: &Path, file: &str, content: &str) -> PathBuf {
+        let path = dir.join(file);
+        fs::write(&path, content).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_find_native() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let system = temp.path().join("system");
+        let flatpak = temp.path().join("flatpak");
+        fs::create_dir_all(&system).unwrap();
+        fs::create_dir_all(&flatpak).unwrap();
+        let dirs = [system.clone(), flatpak.clone()];
+
+        let by_name = write(
+            &system,
+            "krita-distro.desktop",
+            "[Desktop Entry]\nType=Application\nName=Krita\nExec=krita\n",
+        );
+        let by_wm_class = write(
+            &flatpak,
+            "org.kde.krita.desktop",
+            "[Desktop Entry]\nType=Application\nName=Krita (Flatpak)\nStartupWMClass=Krita\n",
+        );
+        write(
+            &system,
+            "hidden.desktop",
+            "[Desktop Entry]\nType=Application\nName=Gimp\nNoDisplay=true\n",
+        );
+        let appimage = write(
+            temp.path(),
+            "appimage.desktop",
+            "[Desktop Entry]\nType=Application\nName=Krita\nStartupWMClass=krita\n",
+        );
+        let entry = DesktopEntry::parse(&appimage).unwrap();
+
+        // The window class is a stronger match than the name
+        assert_eq!(
+            find_native(&entry, "krita.desktop", &dirs),
+            Some(NativeDuplicate {
+                desktop_path: by_wm_class,
+                reason: MatchReason::WmClass,
+            })
+        );
+        assert_eq!(
+            find_native(&entry, "krita-distro.desktop", &dirs),
+            Some(NativeDuplicate {
+                desktop_path: by_name,
+                reason: MatchReason::DesktopId,
+            })
+        );
+
+        let gimp = write(
+            temp.path(),
+            "gimp.desktop",
+            "[Desktop Entry]\nType=Application\nName=Gimp\n",
+        );
+        let gimp = DesktopEntry::parse(&gimp).unwrap();
+        assert_eq!(find_native(&gimp, "gimp.desktop", &dirs), None);
+    }
+}
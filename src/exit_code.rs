@@ -0,0 +1,127 @@
+//! Exit statuses of the `appimage-auto` command by kind of failure, so
+//! scripts and the GUI can tell them apart.
+
+use crate::appimage::AppImageError;
+use crate::control::ControlError;
+use crate::state::StateError;
+use std::error::Error;
+use std::io;
+
+/// Exit status of the command line tool
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExitCode {
+    Success,
+    /// Any failure without a code of its own
+    Failure,
+    /// The file is not an AppImage
+    NotAppImage,
+    /// The AppImage could not be extracted
+    ExtractionFailed,
+    /// The AppImage has no desktop entry
+    NoDesktopFile,
+    /// The state file is locked or can't be written
+    StateLocked,
+    /// The daemon is not running
+    DaemonNotRunning,
+}
+
+impl ExitCode {
+    /// All exit codes, in order of their number
+    pub const ALL: [ExitCode; 7] = [
+        ExitCode::Success,
+        ExitCode::Failure,
+        ExitCode::NotAppImage,
+        ExitCode::ExtractionFailed,
+        ExitCode::NoDesktopFile,
+        ExitCode::StateLocked,
+        ExitCode::DaemonNotRunning,
+    ];
+
+    /// The process exit status (2 is left to usage errors)
+    pub fn code(self) -> i32 {
+        match self {
+            ExitCode::Success => 0,
+            ExitCode::Failure => 1,
+            ExitCode::NotAppImage => 3,
+            ExitCode::ExtractionFailed => 4,
+            ExitCode::NoDesktopFile => 5,
+            ExitCode::StateLocked => 6,
+            ExitCode::DaemonNotRunning => 7,
+        }
+    }
+
+    /// The exit code with a process exit status
+    pub fn from_code(code: i32) -> Option<Self> {
+        Self::ALL.into_iter().find(|c| c.code() == code)
+    }
+
+    /// The exit code for an error, from the first error in its chain of
+    /// sources that has a code of its own
+    pub fn of(error: &(dyn Error + 'static)) -> Self {
+        let mut current = Some(error);
+        while let Some(error) = current {
+            if let Some(code) = Self::of_single(error) {
+                return code;
+            }
+            current = error.source();
+        }
+        ExitCode::Failure
+    }
+
+    fn of_single(error: &(dyn Error + 'static)) -> Option<Self> {
+        if let Some(e) = error.downcast_ref::<AppImageError>() {
+            return match e {
+                AppImageError::NotAppImage(_) => Some(ExitCode::NotAppImage),
+                AppImageError::ExtractionFailed(_) | AppImageError::Squashfs(_) => {
+                    Some(ExitCode::ExtractionFailed)
+                }
+                AppImageError::NoDesktopFile => Some(ExitCode::NoDesktopFile),
+                _ => None,
+            };
+        }
+        if let Some(StateError::Io(e)) = error.downcast_ref::<StateError>() {
+            let locked = matches!(
+                e.kind(),
+                io::ErrorKind::WouldBlock
+                    | io::ErrorKind::ResourceBusy
+                    | io::ErrorKind::PermissionDenied
+                    | io::ErrorKind::ReadOnlyFilesystem
+            );
+            return locked.then_some(ExitCode::StateLocked);
+        }
+        if error.downcast_ref::<ControlError>().is_some() {
+            return Some(ExitCode::DaemonNotRunning);
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::daemon::DaemonError;
+
+    #[test]
+    fn test_exit_code_of() {
+        let not_appimage = DaemonError::AppImage(AppImageError::NotAppImage("x".to_string()));
+        assert_eq!(ExitCode::of(&not_appimage), ExitCode::NotAppImage);
+        let no_desktop = DaemonError::AppImage(AppImageError::NoDesktopFile);
+        assert_eq!(ExitCode::of(&no_desktop), ExitCode::NoDesktopFile);
+
+        let read_only = StateError::Io(io::ErrorKind::PermissionDenied.into());
+        assert_eq!(
+            ExitCode::of(&DaemonError::State(read_only)),
+            ExitCode::StateLocked
+        );
+        let missing = StateError::Io(io::ErrorKind::NotFound.into());
+        assert_eq!(ExitCode::of(&missing), ExitCode::Failure);
+
+        let boxed: Box<dyn Error> = Box::new(ControlError::NoRuntimeDir);
+        assert_eq!(ExitCode::of(boxed.as_ref()), ExitCode::DaemonNotRunning);
+
+        for code in ExitCode::ALL {
+            assert_eq!(ExitCode::from_code(code.code()), Some(code));
+        }
+        assert_eq!(ExitCode::from_code(2), None);
+    }
+}
@@ -0,0 +1,161 @@
+//! Export of the generated menu to another prefix.
+//!
+//! `appimage-auto export-menu <prefix>` copies the desktop entries and icons
+//! of all integrated AppImages to `<prefix>/share/applications` and
+//! `<prefix>/share/icons/hicolor`, for sessions that don't share the home
+//! directory: XRDP and thin clients, chroots, or images built from an
+//! overlay. The entries refer to their icons by theme name, so they are
+//! found wherever the prefix ends up as long as its `share` directory is in
+//! `XDG_DATA_DIRS`, and run the AppImages directly rather than through
+//! launch tracking, optionally from another directory.
+
+use crate::backup;
+use crate::desktop::{DesktopEntry, DesktopError};
+use crate::state::State;
+use std::fs;
+use std::path::Path;
+use thiserror::Error;
+use tracing::warn;
+
+/// Directory of desktop entries below the prefix
+const DESKTOP_DIR: &str = "share/applications";
+
+/// Icon theme directory below the prefix
+const ICON_DIR: &str = "share/icons/hicolor";
+
+#[derive(Error, Debug)]
+pub enum ExportError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Desktop entry error: {0}")]
+    Desktop(#[from] DesktopError),
+}
+
+/// What an export covered
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ExportSummary {
+    /// Desktop entries written
+    pub apps: usize,
+    /// Icons copied
+    pub icons: usize,
+}
+
+/// Copy the desktop entries and icons of all integrated AppImages below
+/// `prefix`. With `appimage_dir`, the entries run the AppImages from there
+/// instead of their current directories.
+pub fn export_menu(
+    state: &State,
+    icon_dir: &Path,
+    prefix: &Path,
+    appimage_dir: Option<&Path>,
+) -> Result<ExportSummary, ExportError> {
+    let desktop_dir = prefix.join(DESKTOP_DIR);
+    let theme_dir = prefix.join(ICON_DIR);
+    fs::create_dir_all(&desktop_dir)?;
+
+    let mut summary = ExportSummary::default();
+    for app in state.all() {
+        let Some(file_name) = app.desktop_path.file_name() else {
+            continue;
+        };
+        let mut entry = match DesktopEntry::parse(&app.desktop_path) {
+            Ok(entry) => entry,
+            Err(e) => {
+                warn!("Not exporting {:?}: {}", app.desktop_path, e);
+                continue;
+            }
+        };
+
+        // The launcher counting launches only exists on this machine
+        entry.remove_launcher();
+        if let Some(dir) = appimage_dir
+            && let Some(name) = app.appimage_path.file_name()
+        {
+            entry.relocate_appimage(&app.appimage_path, &dir.join(name));
+        }
+
+        // The first icon is the one in the main icon directory
+        if let Some(icon) = app.icon_paths.first().filter(|icon| icon.is_file())
+            && let Some(below) = backup::icon_below(icon, icon_dir)
+        {
+            let dest = theme_dir.join(below);
+            if let Some(parent) = dest.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::copy(icon, &dest)?;
+            summary.icons += 1;
+
+            if entry
+                .icon()
+                .is_some_and(|value| Path::new(value).is_absolute())
+                && let Some(name) = icon.file_stem()
+            {
+                entry.set_icon(&name.to_string_lossy());
+            }
+        }
+
+        entry.write(&desktop_dir.join(file_name))?;
+        summary.apps += 1;
+    }
+
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::create_entry;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_export_menu() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let icon_dir = temp.path().join("icons");
+        let prefix = temp.path().join("export");
+        fs::create_dir_all(icon_dir.join("64x64/apps")).unwrap();
+
+        let desktop = temp.path().join("appimage-abc.desktop");
+        let icon = icon_dir.join("64x64/apps/appimage-abc.png");
+        fs::write(
+            &desktop,
+            format!(
+                "[Desktop Entry]\nType=Application\nName=App\nIcon={}\n\
+                 Exec=\"/usr/bin/appimage-auto\" track -- \"/home/user/Apps/App.AppImage\" %F\n\
+                 TryExec=/home/user/Apps/App.AppImage\n",
+                icon.display()
+            ),
+        )
+        .unwrap();
+        fs::write(&icon, b"png").unwrap();
+
+        let mut state = State::default();
+        state.add(create_entry(
+            "abc".to_string(),
+            PathBuf::from("/home/user/Apps/App.AppImage"),
+            desktop,
+            vec![icon],
+            Some("App".to_string()),
+        ));
+        state.add(create_entry(
+            "def".to_string(),
+            PathBuf::from("/home/user/Apps/Gone.AppImage"),
+            temp.path().join("appimage-def.desktop"),
+            vec![],
+            None,
+        ));
+
+        let summary =
+            export_menu(&state, &icon_dir, &prefix, Some(Path::new("/srv/apps"))).unwrap();
+        assert_eq!(summary, ExportSummary { apps: 1, icons: 1 });
+
+        let exported =
+            DesktopEntry::parse(&prefix.join("share/applications/appimage-abc.desktop")).unwrap();
+        assert_eq!(exported.exec(), Some("\"/srv/apps/App.AppImage\" %F"));
+        assert_eq!(exported.entries["TryExec"], "/srv/apps/App.AppImage");
+        assert_eq!(exported.icon(), Some("appimage-abc"));
+        assert_eq!(
+            fs::read(prefix.join("share/icons/hicolor/64x64/apps/appimage-abc.png")).unwrap(),
+            b"png"
+        );
+    }
+}
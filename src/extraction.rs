@@ -0,0 +1,113 @@
+//! Directories AppImages are extracted into.
+//!
+//! Each integration gets its own directory under the cache directory
+//! (`~/.cache/appimage-auto/extract/<identifier>-<random>`), so integrations
+//! running at the same time (the daemon and the CLI, or parallel workers)
+//! never share one. It is removed when dropped; directories left behind by
+//! a killed process are removed by [`cleanup_stale`] once they are older
+//! than `integration.extract_ttl_secs`.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use tempfile::TempDir;
+use tracing::{debug, warn};
+
+/// Directory the extraction directories are made in: `extract` in the
+/// cache directory, or in the system temp directory without one
+pub fn extraction_root() -> PathBuf {
+    match directories::ProjectDirs::from("", "", "appimage-auto") {
+        Some(dirs) => dirs.cache_dir().join("extract"),
+        None => std::env::temp_dir().join("appimage-auto-extract"),
+    }
+}
+
+/// A new extraction directory for the AppImage `identifier`, removed when
+/// dropped
+pub fn extraction_dir(identifier: &str) -> io::Result<TempDir> {
+    create_in(&extraction_root(), identifier)
+}
+
+/// Remove extraction directories older than `ttl`, returning how many
+pub fn cleanup_stale(ttl: Duration) -> usize {
+    cleanup_in(&extraction_root(), ttl, SystemTime::now())
+}
+
+fn create_in(root: &Path, identifier: &str) -> io::Result<TempDir> {
+    fs::create_dir_all(root)?;
+    tempfile::Builder::new()
+        .prefix(&format!("{}-", identifier))
+        .tempdir_in(root)
+}
+
+fn cleanup_in(root: &Path, ttl: Duration, now: SystemTime) -> usize {
+    let Ok(entries) = fs::read_dir(root) else {
+        return 0;
+    };
+
+    let mut removed = 0;
+    for entry in entries.filter_map(|e| e.ok()) {
+        let path = entry.path();
+        let stale = entry
+            .metadata()
+            .and_then(|m| m.modified())
+            .is_ok_and(|modified| modified + ttl <= now);
+        if !stale {
+            continue;
+        }
+        match fs::remove_dir_all(&path) {
+            Ok(()) => {
+                debug!("Removed stale extraction directory {:?}", path);
+                removed += 1;
+            }
+            Err(e) => warn!("Failed to remove stale extraction {:?}: {}", path, e),
+        }
+    }
+    removed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extraction_dirs_are_namespaced() {
+        let root = tempfile::TempDir::new().unwrap();
+        let first = create_in(root.path(), "0123abcd").unwrap();
+        let second = create_in(root.path(), "0123abcd").unwrap();
+
+        assert_ne!(first.path(), second.path());
+        assert_eq!(first.path().parent(), Some(root.path()));
+        assert!(
+            first
+                .path()
+                .file_name()
+                .unwrap()
+                .to_string_lossy()
+                .starts_with("0123abcd-")
+        );
+
+        let path = first.path().to_path_buf();
+        drop(first);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_cleanup_stale() {
+        let root = tempfile::TempDir::new().unwrap();
+        let left_behind = create_in(root.path(), "0123abcd").unwrap().keep();
+        fs::create_dir_all(left_behind.join("squashfs-root/usr")).unwrap();
+
+        let ttl = Duration::from_secs(3600);
+        assert_eq!(cleanup_in(root.path(), ttl, SystemTime::now()), 0);
+        assert!(left_behind.exists());
+
+        let later = SystemTime::now() + ttl;
+        assert_eq!(cleanup_in(root.path(), ttl, later), 1);
+        assert!(!left_behind.exists());
+
+        // Nothing to clean up before anything was extracted
+        assert_eq!(cleanup_in(&root.path().join("missing"), ttl, later), 0);
+    }
+}
@@ -0,0 +1,108 @@
+//! Running inside a Flatpak sandbox.
+//!
+//! The settings app can be shipped as a Flatpak and still manage the host's
+//! integrations. Inside the sandbox the XDG base directories point into
+//! `~/.var/app/<id>`, so [`use_host_directories`] points them back at the
+//! host's, where the daemon keeps its config and state and desktop
+//! environments look for desktop entries and icons. Programs that act on the
+//! host session (`systemctl`, `journalctl`, `update-desktop-database` and the
+//! AppImages themselves) are started there through `flatpak-spawn --host`,
+//! which needs the `--talk-name=org.freedesktop.Flatpak` permission.
+
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// File Flatpak places at the root of every sandbox
+const INFO_FILE: &str = "/.flatpak-info";
+
+/// Base directory variables and their defaults below the home directory
+const HOST_DIRECTORIES: [(&str, &str); 3] = [
+    ("XDG_CONFIG_HOME", ".config"),
+    ("XDG_DATA_HOME", ".local/share"),
+    ("XDG_STATE_HOME", ".local/state"),
+];
+
+/// Whether this process runs inside a Flatpak sandbox
+pub fn is_sandboxed() -> bool {
+    Path::new(INFO_FILE).exists()
+}
+
+/// The Flatpak application ID when sandboxed
+pub fn app_id() -> Option<String> {
+    std::env::var("FLATPAK_ID").ok().filter(|id| !id.is_empty())
+}
+
+/// Point the XDG base directories at the host's when sandboxed. Flatpak keeps
+/// the host's values in `HOST_XDG_*`; without them the defaults below the
+/// home directory are used.
+///
+/// Must be called at the start of `main`, before any threads are started.
+pub fn use_host_directories() {
+    if !is_sandboxed() {
+        return;
+    }
+    let Some(home) = std::env::var_os("HOME").map(PathBuf::from) else {
+        return;
+    };
+    for (var, default) in HOST_DIRECTORIES {
+        let dir = std::env::var_os(format!("HOST_{}", var))
+            .map(PathBuf::from)
+            .unwrap_or_else(|| home.join(default));
+        // SAFETY: only called at the start of main, while the process is
+        // still single-threaded
+        unsafe { std::env::set_var(var, dir) };
+    }
+}
+
+/// A command running `program` on the host: through `flatpak-spawn --host`
+/// when sandboxed, directly otherwise. Environment variables for the program
+/// go in `env`, since `flatpak-spawn` doesn't pass its own environment on.
+pub fn host_command(program: impl AsRef<OsStr>, env: &[(&str, &str)]) -> Command {
+    wrap(program.as_ref(), env, is_sandboxed())
+}
+
+fn wrap(program: &OsStr, env: &[(&str, &str)], sandboxed: bool) -> Command {
+    if !sandboxed {
+        let mut command = Command::new(program);
+        command.envs(env.iter().copied());
+        return command;
+    }
+    let mut command = Command::new("flatpak-spawn");
+    command.arg("--host");
+    for (key, value) in env {
+        command.arg(format!("--env={}={}", key, value));
+    }
+    command.arg(program);
+    command
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wrap() {
+        let env = [("APPIMAGE_EXTRACT_AND_RUN", "1")];
+
+        let direct = wrap(OsStr::new("/apps/App.AppImage"), &env, false);
+        assert_eq!(direct.get_program(), "/apps/App.AppImage");
+        assert_eq!(direct.get_args().count(), 0);
+        assert!(direct.get_envs().any(|(key, value)| {
+            key == "APPIMAGE_EXTRACT_AND_RUN" && value == Some(OsStr::new("1"))
+        }));
+
+        let spawned = wrap(OsStr::new("/apps/App.AppImage"), &env, true);
+        assert_eq!(spawned.get_program(), "flatpak-spawn");
+        let args: Vec<_> = spawned.get_args().collect();
+        assert_eq!(
+            args,
+            [
+                "--host",
+                "--env=APPIMAGE_EXTRACT_AND_RUN=1",
+                "/apps/App.AppImage"
+            ]
+        );
+        assert_eq!(spawned.get_envs().count(), 0);
+    }
+}
@@ -0,0 +1,142 @@
+//! Human-readable formatting of sizes, ages and tables for display.
+
+/// Seconds in a day
+const DAY_SECS: u64 = 24 * 60 * 60;
+
+/// Format a byte count with decimal (SI) units, e.g. `"124.5 MB"`
+pub fn human_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["kB", "MB", "GB", "TB"];
+
+    if bytes < 1000 {
+        return format!("{} bytes", bytes);
+    }
+
+    let mut value = bytes as f64 / 1000.0;
+    let mut unit = UNITS[0];
+    for next in &UNITS[1..] {
+        if value < 1000.0 {
+            break;
+        }
+        value /= 1000.0;
+        unit = next;
+    }
+    format!("{:.1} {}", value, unit)
+}
+
+/// Describe how long ago a Unix timestamp was, in whole days
+pub fn days_ago(timestamp: u64, now: u64) -> String {
+    match now.saturating_sub(timestamp) / DAY_SECS {
+        0 => "today".to_string(),
+        1 => "yesterday".to_string(),
+        days => format!("{} days ago", days),
+    }
+}
+
+/// Describe how often and when an app was last launched
+pub fn launches(count: u64, last: Option<u64>, now: u64) -> String {
+    match (count, last) {
+        (0, _) | (_, None) => "never launched".to_string(),
+        (1, Some(last)) => format!("launched once, {}", days_ago(last, now)),
+        (count, Some(last)) => format!("launched {} times, last {}", count, days_ago(last, now)),
+    }
+}
+
+/// Lay out rows as left-aligned columns under a header
+pub fn table(header: &[&str], rows: &[Vec<String>]) -> String {
+    let mut widths: Vec<usize> = header.iter().map(|h| h.chars().count()).collect();
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    let header: Vec<String> = header.iter().map(|h| h.to_string()).collect();
+    let mut out = String::new();
+    for row in std::iter::once(&header).chain(rows) {
+        let line: Vec<String> = row
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!("{:width$}", cell, width = width))
+            .collect();
+        out.push_str(line.join("  ").trim_end());
+        out.push('\n');
+    }
+    out
+}
+
+/// Format fields as one CSV line, quoting fields that need it
+pub fn csv_row<S: AsRef<str>>(fields: &[S]) -> String {
+    let fields: Vec<String> = fields
+        .iter()
+        .map(|field| {
+            let field = field.as_ref();
+            if field.contains([',', '"', '\n', '\r']) {
+                format!("\"{}\"", field.replace('"', "\"\""))
+            } else {
+                field.to_string()
+            }
+        })
+        .collect();
+    fields.join(",")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_human_size() {
+        assert_eq!(human_size(512), "512 bytes");
+        assert_eq!(human_size(1500), "1.5 kB");
+        assert_eq!(human_size(124_500_000), "124.5 MB");
+        assert_eq!(human_size(2_000_000_000), "2.0 GB");
+    }
+
+    #[test]
+    fn test_days_ago() {
+        let now = 100 * DAY_SECS;
+        assert_eq!(days_ago(now - 60, now), "today");
+        assert_eq!(days_ago(now - DAY_SECS, now), "yesterday");
+        assert_eq!(days_ago(now - 12 * DAY_SECS, now), "12 days ago");
+        assert_eq!(days_ago(now + 60, now), "today");
+    }
+
+    #[test]
+    fn test_launches() {
+        let now = 100 * DAY_SECS;
+        assert_eq!(launches(0, None, now), "never launched");
+        assert_eq!(launches(1, Some(now), now), "launched once, today");
+        assert_eq!(
+            launches(7, Some(now - 3 * DAY_SECS), now),
+            "launched 7 times, last 3 days ago"
+        );
+    }
+
+    #[test]
+    fn test_table_and_csv() {
+        let rows = vec![
+            vec![
+                "Krita".to_string(),
+                "5.2".to_string(),
+                "/apps/krita".to_string(),
+            ],
+            vec![
+                "Inkscape".to_string(),
+                String::new(),
+                "/apps/ink".to_string(),
+            ],
+        ];
+        assert_eq!(
+            table(&["NAME", "VERSION", "PATH"], &rows),
+            "NAME      VERSION  PATH\n\
+             Krita     5.2      /apps/krita\n\
+             Inkscape           /apps/ink\n"
+        );
+
+        assert_eq!(csv_row(&["a", "b c", ""]), "a,b c,");
+        assert_eq!(
+            csv_row(&["x,y", "say \"hi\""]),
+            "\"x,y\",\"say \"\"hi\"\"\""
+        );
+    }
+}
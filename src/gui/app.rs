@@ -1,9 +1,15 @@
 //! Main application component for the GUI.
 
 use super::app_list_page::{AppListPage, AppListPageMsg, AppListPageOutput};
-use super::dialogs;
+use super::dialogs::{self, OpenChoice};
 use super::settings_page::{SettingsPage, SettingsPageMsg, SettingsPageOutput};
+use super::setup_assistant::{SetupAssistant, SetupAssistantOutput};
 use super::status_page::{StatusPage, StatusPageMsg, StatusPageOutput};
+use crate::appimage;
+use crate::config::Config;
+use crate::exit_code::ExitCode;
+use crate::i18n::{tr, tr_args};
+use crate::notifications;
 use relm4::adw::prelude::*;
 use relm4::gtk::{self, gio};
 use relm4::prelude::*;
@@ -21,6 +27,8 @@ pub struct AppModel {
     settings_page: Controller<SettingsPage>,
     /// View stack for tab switching.
     view_stack: adw::ViewStack,
+    /// First-run setup assistant, while open.
+    setup_assistant: Option<Controller<SetupAssistant>>,
 }
 
 /// Messages for the main application.
@@ -34,8 +42,16 @@ pub enum AppMsg {
     IntegrateAppImage,
     /// Handle AppImage file selected.
     AppImageSelected(PathBuf),
+    /// Handle an AppImage opened from the file manager.
+    OpenAppImage(PathBuf),
+    /// Act on an opened AppImage.
+    OpenChosen(PathBuf, OpenChoice),
+    /// Show the details of an integrated app by identifier.
+    ShowApp(String),
     /// Refresh all pages.
     RefreshAll,
+    /// Show the first-run setup assistant.
+    ShowSetupAssistant,
     /// Show directory chooser for settings.
     ShowDirectoryChooser,
     /// Directory selected for settings.
@@ -57,7 +73,7 @@ impl SimpleComponent for AppModel {
     view! {
         #[root]
         adw::ApplicationWindow {
-            set_title: Some("AppImage Auto Settings"),
+            set_title: Some(tr("AppImage Auto Settings")),
             set_default_width: 700,
             set_default_height: 700,
 
@@ -85,6 +101,13 @@ impl SimpleComponent for AppModel {
         root: Self::Root,
         sender: ComponentSender<Self>,
     ) -> ComponentParts<Self> {
+        // Loading the config below creates it, so check for a first run first
+        let first_run = Config::config_path().is_ok_and(|path| !path.exists());
+
+        // Apply the saved color scheme before the window is shown
+        let theme = Config::load().map(|c| c.gui.theme).unwrap_or_default();
+        SettingsPage::apply_theme(theme);
+
         // Create child components
         let status_page = StatusPage::builder()
             .launch(())
@@ -103,6 +126,7 @@ impl SimpleComponent for AppModel {
             app_list_page,
             settings_page,
             view_stack: adw::ViewStack::new(),
+            setup_assistant: None,
         };
 
         let widgets = view_output!();
@@ -114,13 +138,13 @@ impl SimpleComponent for AppModel {
         let apps_page_widget = model.app_list_page.widget().clone();
         let settings_page_widget = model.settings_page.widget().clone();
 
-        let status_stack_page = widgets.view_stack.add_titled(&status_page_widget, Some("status"), "Overview");
+        let status_stack_page = widgets.view_stack.add_titled(&status_page_widget, Some("status"), tr("Overview"));
         status_stack_page.set_icon_name(Some("go-home-symbolic"));
 
-        let apps_stack_page = widgets.view_stack.add_titled(&apps_page_widget, Some("apps"), "Apps");
+        let apps_stack_page = widgets.view_stack.add_titled(&apps_page_widget, Some("apps"), tr("Apps"));
         apps_stack_page.set_icon_name(Some("application-x-executable-symbolic"));
 
-        let settings_stack_page = widgets.view_stack.add_titled(&settings_page_widget, Some("settings"), "Settings");
+        let settings_stack_page = widgets.view_stack.add_titled(&settings_page_widget, Some("settings"), tr("Settings"));
         settings_stack_page.set_icon_name(Some("emblem-system-symbolic"));
 
         // Set up actions
@@ -151,6 +175,27 @@ impl SimpleComponent for AppModel {
 
         app.add_action_entries([integrate_action, refresh_action, about_action]);
 
+        // Files opened with the GUI, e.g. double-clicking an AppImage
+        let sender_clone = sender.clone();
+        app.connect_open(move |app, files, _| {
+            // "open" is emitted instead of "activate", so show the window here
+            if let Some(window) = app.active_window() {
+                window.present();
+            }
+            for file in files {
+                // Clicked integration notifications open the app's details
+                if let Some(identifier) = notifications::parse_details_uri(&file.uri()) {
+                    sender_clone.input(AppMsg::ShowApp(identifier.to_string()));
+                } else if let Some(path) = file.path() {
+                    sender_clone.input(AppMsg::OpenAppImage(path));
+                }
+            }
+        });
+
+        if first_run {
+            sender.input(AppMsg::ShowSetupAssistant);
+        }
+
         ComponentParts { model, widgets }
     }
 
@@ -177,8 +222,10 @@ impl SimpleComponent for AppModel {
                 let app = relm4::main_adw_application();
                 if let Some(window) = app.active_window() {
                     let sender_clone = sender.input_sender().clone();
-                    dialogs::show_appimage_chooser(&window, move |path| {
-                        sender_clone.emit(AppMsg::AppImageSelected(path));
+                    dialogs::show_appimage_chooser(&window, move |paths| {
+                        for path in paths {
+                            sender_clone.emit(AppMsg::AppImageSelected(path));
+                        }
                     });
                 }
             }
@@ -186,24 +233,89 @@ impl SimpleComponent for AppModel {
                 let path_str = path.to_string_lossy().to_string();
                 match Command::new("appimage-auto")
                     .args(["integrate", &path_str])
-                    .spawn()
+                    .status()
                 {
-                    Ok(mut child) => {
-                        let _ = child.wait();
-                        sender.input(AppMsg::ShowToast("AppImage integrated".to_string()));
+                    Ok(status) if status.success() => {
+                        sender.input(AppMsg::ShowToast(tr("AppImage integrated").to_string()));
                         self.app_list_page.emit(AppListPageMsg::Reload);
                         self.status_page.emit(StatusPageMsg::Refresh);
                     }
+                    Ok(status) => {
+                        let name = path.display();
+                        let message = match status.code().and_then(ExitCode::from_code) {
+                            Some(ExitCode::NotAppImage) => {
+                                tr_args("{} is not an AppImage", &[&name])
+                            }
+                            Some(ExitCode::ExtractionFailed) => {
+                                tr_args("Could not extract {}", &[&name])
+                            }
+                            Some(ExitCode::NoDesktopFile) => {
+                                tr_args("{} has no desktop entry", &[&name])
+                            }
+                            _ => tr_args("Failed to integrate: {}", &[&name]),
+                        };
+                        sender.input(AppMsg::ShowToast(message));
+                    }
                     Err(e) => {
-                        sender.input(AppMsg::ShowToast(format!("Failed to integrate: {}", e)));
+                        sender.input(AppMsg::ShowToast(tr_args("Failed to integrate: {}", &[&e])));
                     }
                 }
             }
+            AppMsg::OpenAppImage(path) => {
+                if !appimage::is_appimage(&path) {
+                    sender.input(AppMsg::ShowToast(tr_args(
+                        "{} is not an AppImage",
+                        &[&path.display()],
+                    )));
+                    return;
+                }
+                let app = relm4::main_adw_application();
+                if let Some(window) = app.active_window() {
+                    let name = path
+                        .file_name()
+                        .map(|n| n.to_string_lossy().to_string())
+                        .unwrap_or_default();
+                    let sender_clone = sender.input_sender().clone();
+                    dialogs::show_open_appimage(&window, &name, move |choice| {
+                        sender_clone.emit(AppMsg::OpenChosen(path.clone(), choice));
+                    });
+                }
+            }
+            AppMsg::OpenChosen(path, choice) => {
+                if choice != OpenChoice::Integrate
+                    && let Err(e) = run_appimage(&path)
+                {
+                    sender.input(AppMsg::ShowToast(tr_args("Failed to run: {}", &[&e])));
+                    return;
+                }
+                if choice != OpenChoice::RunOnce {
+                    sender.input(AppMsg::AppImageSelected(path));
+                }
+            }
+            AppMsg::ShowApp(identifier) => {
+                self.view_stack.set_visible_child_name("apps");
+                self.app_list_page.emit(AppListPageMsg::Reload);
+                self.app_list_page.emit(AppListPageMsg::ShowApp(identifier));
+            }
             AppMsg::RefreshAll => {
                 self.status_page.emit(StatusPageMsg::Refresh);
                 self.app_list_page.emit(AppListPageMsg::Reload);
                 self.settings_page.emit(SettingsPageMsg::Reload);
             }
+            AppMsg::ShowSetupAssistant => {
+                let mut builder = SetupAssistant::builder();
+                if let Some(window) = relm4::main_adw_application().active_window() {
+                    builder = builder.transient_for(&window);
+                }
+                let assistant = builder
+                    .launch(())
+                    .forward(sender.input_sender(), |output| match output {
+                        SetupAssistantOutput::Done => AppMsg::RefreshAll,
+                        SetupAssistantOutput::ShowToast(msg) => AppMsg::ShowToast(msg),
+                    });
+                assistant.widget().present();
+                self.setup_assistant = Some(assistant);
+            }
             AppMsg::ShowDirectoryChooser => {
                 let app = relm4::main_adw_application();
                 if let Some(window) = app.active_window() {
@@ -220,6 +332,9 @@ impl SimpleComponent for AppModel {
                 StatusPageOutput::NavigateTo(page) => {
                     self.view_stack.set_visible_child_name(&page);
                 }
+                StatusPageOutput::ShowToast(msg) => {
+                    sender.input(AppMsg::ShowToast(msg));
+                }
             },
             AppMsg::AppListPageOutput(output) => match output {
                 AppListPageOutput::ShowToast(msg) => {
@@ -238,17 +353,25 @@ impl SimpleComponent for AppModel {
     }
 }
 
+/// Launch an AppImage without integrating it.
+fn run_appimage(path: &std::path::Path) -> Result<(), appimage::AppImageError> {
+    let mut child = appimage::run_once(path, std::iter::empty::<&str>())?;
+    // Reap the process when it exits
+    std::thread::spawn(move || child.wait());
+    Ok(())
+}
+
 /// Show the about dialog.
 fn show_about_dialog() {
     let dialog = adw::AboutWindow::builder()
-        .application_name("AppImage Auto Settings")
+        .application_name(tr("AppImage Auto Settings"))
         .application_icon("appimage-auto")
         .developer_name("Caleb")
         .version(env!("CARGO_PKG_VERSION"))
         .website("https://github.com/youruser/appimage-auto")
         .issue_url("https://github.com/youruser/appimage-auto/issues")
         .license_type(gtk::License::MitX11)
-        .comments("Configure automatic AppImage integration")
+        .comments(tr("Configure automatic AppImage integration"))
         .build();
 
     let app = relm4::main_adw_application();
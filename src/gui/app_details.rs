@@ -0,0 +1,614 @@
+//! App details window with an inline desktop entry and launch settings editor.
+
+use super::dialogs;
+use crate::appstream;
+use crate::autostart;
+use crate::config::{AppProfile, Config};
+use crate::desktop::DesktopEntry;
+use crate::format;
+use crate::i18n::{tr, tr_args};
+use crate::overrides::{DesktopOverrides, Overrides};
+use crate::state::{self, IntegratedAppImage};
+use relm4::adw;
+use relm4::adw::prelude::*;
+use relm4::gtk;
+use relm4::prelude::*;
+use relm4::{ComponentParts, ComponentSender};
+use std::path::PathBuf;
+use std::process::Command;
+
+/// Editable desktop entry fields.
+#[derive(Debug, Clone, Default, PartialEq)]
+struct EntryFields {
+    name: String,
+    comment: String,
+    categories: String,
+    keywords: String,
+    terminal: bool,
+}
+
+impl EntryFields {
+    /// Read the fields from an installed desktop entry.
+    fn from_entry(entry: &DesktopEntry) -> Self {
+        let get = |key: &str| entry.entries.get(key).cloned().unwrap_or_default();
+        Self {
+            name: get("Name"),
+            comment: get("Comment"),
+            categories: get("Categories"),
+            keywords: get("Keywords"),
+            terminal: get("Terminal") == "true",
+        }
+    }
+}
+
+/// The app details window model.
+pub struct AppDetails {
+    /// The integrated AppImage being shown.
+    app: IntegratedAppImage,
+    /// Values in the installed desktop entry.
+    installed: EntryFields,
+    /// Values currently in the editor.
+    edited: EntryFields,
+    /// Whether the app has any overrides saved.
+    has_overrides: bool,
+    /// Config key of the app's launch settings (identifier, unless saved
+    /// under its name).
+    profile_key: String,
+    /// Launch settings in the config.
+    saved_profile: AppProfile,
+    /// Launch settings currently in the editor.
+    profile: AppProfile,
+    /// The details window, closed after saving.
+    window: adw::Window,
+}
+
+/// Messages for the app details window.
+#[derive(Debug)]
+pub enum AppDetailsMsg {
+    SetName(String),
+    SetComment(String),
+    SetCategories(String),
+    SetKeywords(String),
+    SetTerminal(bool),
+    SetSandbox(bool),
+    SetNetwork(bool),
+    SetExtractAndRun(bool),
+    SetHidden(bool),
+    SetPinned(bool),
+    SetArgs(String),
+    SetEnv(String),
+    /// Save changed fields as overrides and the launch settings.
+    Save,
+    /// Drop all overrides and launch settings for the app.
+    Reset,
+    /// Open a project link of the given AppStream type in the browser.
+    OpenLink(&'static str),
+    /// Re-integrate the app after its last update failed.
+    Retry,
+    /// Pick a custom icon file.
+    ChooseIcon,
+    /// Use the picked icon file instead of the AppImage's own icon.
+    IconSelected(PathBuf),
+    /// Go back to the AppImage's own icon.
+    ResetIcon,
+}
+
+/// Output messages from the app details window.
+#[derive(Debug)]
+pub enum AppDetailsOutput {
+    /// Overrides or launch settings changed; the AppImage needs re-integrating.
+    OverridesChanged(PathBuf),
+    /// Request to show a toast message.
+    ShowToast(String),
+    /// Re-integrate an app whose last update failed.
+    Retry(PathBuf),
+    /// Use a custom icon for an AppImage, or its own icon again with `None`.
+    SetIcon(PathBuf, Option<PathBuf>),
+}
+
+#[relm4::component(pub)]
+impl SimpleComponent for AppDetails {
+    type Init = IntegratedAppImage;
+    type Input = AppDetailsMsg;
+    type Output = AppDetailsOutput;
+
+    view! {
+        #[root]
+        adw::Window {
+            set_title: Some(&model.edited.name),
+            set_modal: true,
+            set_default_width: 500,
+            set_default_height: 600,
+
+            adw::ToolbarView {
+                add_top_bar = &adw::HeaderBar {
+                    pack_start = &gtk::Button {
+                        set_label: tr("Reset"),
+                        set_tooltip_text: Some(tr("Discard all edits and use the AppImage's own values")),
+                        #[watch]
+                        set_sensitive: model.has_overrides || !model.saved_profile.is_empty(),
+                        connect_clicked => AppDetailsMsg::Reset,
+                    },
+
+                    pack_end = &gtk::Button {
+                        set_label: tr("Save"),
+                        add_css_class: "suggested-action",
+                        #[watch]
+                        set_sensitive: model.edited != model.installed
+                            || model.profile != model.saved_profile,
+                        connect_clicked => AppDetailsMsg::Save,
+                    },
+                },
+
+                add_top_bar = &adw::Banner {
+                    set_title: &model.error_title(),
+                    set_button_label: Some(tr("Retry")),
+                    set_revealed: model.app.last_error.is_some(),
+                    connect_button_clicked => AppDetailsMsg::Retry,
+                },
+
+                #[wrap(Some)]
+                set_content = &adw::PreferencesPage {
+                    add = &adw::PreferencesGroup {
+                        set_title: tr("AppImage"),
+
+                        adw::ActionRow {
+                            set_title: tr("Path"),
+                            set_subtitle: &model.app.appimage_path.display().to_string(),
+                            set_subtitle_selectable: true,
+                        },
+
+                        adw::ActionRow {
+                            set_title: tr("Version"),
+                            set_subtitle: model.app.version.as_deref().unwrap_or("Unknown"),
+                        },
+
+                        adw::ActionRow {
+                            set_title: tr("Size"),
+                            set_subtitle: &format::human_size(model.app.file_size()),
+                        },
+
+                        adw::ActionRow {
+                            set_title: tr("Also Installed Natively"),
+                            set_subtitle: &model.app.native_duplicate.as_ref()
+                                .map(|d| format!("{} ({})", d.desktop_path.display(), d.reason.label()))
+                                .unwrap_or_default(),
+                            set_visible: model.app.native_duplicate.is_some(),
+                        },
+
+                        adw::ActionRow {
+                            set_title: tr("Source"),
+                            set_subtitle: model.app.origin_url.as_deref().unwrap_or(""),
+                            set_subtitle_selectable: true,
+                            set_tooltip_text: model.app.referrer_url.as_deref(),
+                            set_visible: model.app.origin_url.is_some(),
+                        },
+
+                        adw::ActionRow {
+                            set_title: tr("Added to Autostart"),
+                            set_subtitle: &model.autostart_summary(),
+                            set_subtitle_selectable: true,
+                            set_visible: !model.app.autostart_entries.is_empty(),
+                            add_suffix = &gtk::Image {
+                                set_icon_name: Some("dialog-warning-symbolic"),
+                                set_tooltip_text: Some(tr("The app added itself to autostart")),
+                            },
+                        },
+
+                        adw::ActionRow {
+                            set_title: tr("Usage"),
+                            set_subtitle: &format::launches(
+                                model.app.launch_count,
+                                model.app.last_launched,
+                                state::current_timestamp(),
+                            ),
+                        },
+                    },
+
+                    add = &adw::PreferencesGroup {
+                        set_title: tr("Project"),
+                        set_visible: model.link(appstream::HOMEPAGE).is_some()
+                            || model.link(appstream::BUGTRACKER).is_some(),
+
+                        adw::ActionRow {
+                            set_title: tr("Open Website"),
+                            set_subtitle: model.link(appstream::HOMEPAGE).unwrap_or(""),
+                            set_activatable: true,
+                            set_visible: model.link(appstream::HOMEPAGE).is_some(),
+                            add_suffix = &gtk::Image {
+                                set_icon_name: Some("adw-external-link-symbolic"),
+                            },
+                            connect_activated => AppDetailsMsg::OpenLink(appstream::HOMEPAGE),
+                        },
+
+                        adw::ActionRow {
+                            set_title: tr("Report Issue"),
+                            set_subtitle: model.link(appstream::BUGTRACKER).unwrap_or(""),
+                            set_activatable: true,
+                            set_visible: model.link(appstream::BUGTRACKER).is_some(),
+                            add_suffix = &gtk::Image {
+                                set_icon_name: Some("adw-external-link-symbolic"),
+                            },
+                            connect_activated => AppDetailsMsg::OpenLink(appstream::BUGTRACKER),
+                        },
+                    },
+
+                    add = &adw::PreferencesGroup {
+                        set_title: tr("Last Update"),
+                        set_description: Some(tr("How the desktop entry changed when the AppImage was last re-integrated")),
+                        set_visible: !model.app.entry_changes.is_empty(),
+
+                        gtk::Label {
+                            set_label: &model.app.entry_changes.iter()
+                                .map(ToString::to_string)
+                                .collect::<Vec<_>>()
+                                .join("\n"),
+                            set_xalign: 0.0,
+                            set_wrap: true,
+                            set_selectable: true,
+                            add_css_class: "monospace",
+                        },
+                    },
+
+                    add = &adw::PreferencesGroup {
+                        set_title: tr("Icon"),
+                        set_description: Some(tr("A custom icon is kept when the AppImage is re-integrated or updated")),
+
+                        adw::ActionRow {
+                            set_title: if model.app.custom_icon.is_some() {
+                                tr("Custom icon")
+                            } else {
+                                tr("The AppImage's own icon")
+                            },
+                            add_prefix = &gtk::Image {
+                                set_pixel_size: 48,
+                                set_from_file: model.app.icon_paths.first(),
+                            },
+                            add_suffix = &gtk::Button {
+                                set_label: tr("Reset"),
+                                set_valign: gtk::Align::Center,
+                                set_visible: model.app.custom_icon.is_some(),
+                                connect_clicked => AppDetailsMsg::ResetIcon,
+                            },
+                            add_suffix = &gtk::Button {
+                                set_label: tr("Choose…"),
+                                set_valign: gtk::Align::Center,
+                                connect_clicked => AppDetailsMsg::ChooseIcon,
+                            },
+                        },
+                    },
+
+                    add = &adw::PreferencesGroup {
+                        set_title: tr("Desktop Entry"),
+                        set_description: Some(tr("Edits are kept when the AppImage is re-integrated or updated")),
+
+                        adw::EntryRow {
+                            set_title: tr("Name"),
+                            set_text: &model.edited.name,
+                            connect_changed[sender] => move |row| {
+                                sender.input(AppDetailsMsg::SetName(row.text().to_string()));
+                            },
+                        },
+
+                        adw::EntryRow {
+                            set_title: tr("Comment"),
+                            set_text: &model.edited.comment,
+                            connect_changed[sender] => move |row| {
+                                sender.input(AppDetailsMsg::SetComment(row.text().to_string()));
+                            },
+                        },
+
+                        adw::EntryRow {
+                            set_title: tr("Categories"),
+                            set_text: &model.edited.categories,
+                            connect_changed[sender] => move |row| {
+                                sender.input(AppDetailsMsg::SetCategories(row.text().to_string()));
+                            },
+                        },
+
+                        adw::EntryRow {
+                            set_title: tr("Keywords"),
+                            set_text: &model.edited.keywords,
+                            connect_changed[sender] => move |row| {
+                                sender.input(AppDetailsMsg::SetKeywords(row.text().to_string()));
+                            },
+                        },
+
+                        adw::SwitchRow {
+                            set_title: tr("Run in Terminal"),
+                            set_active: model.edited.terminal,
+                            connect_active_notify[sender] => move |row| {
+                                sender.input(AppDetailsMsg::SetTerminal(row.is_active()));
+                            },
+                        },
+                    },
+
+                    add = &adw::PreferencesGroup {
+                        set_title: tr("Launch"),
+                        set_description: Some(tr("Stored in the config file and applied when the AppImage is integrated")),
+
+                        adw::EntryRow {
+                            set_title: tr("Arguments"),
+                            set_text: &model.profile.args.join(" "),
+                            connect_changed[sender] => move |row| {
+                                sender.input(AppDetailsMsg::SetArgs(row.text().to_string()));
+                            },
+                        },
+
+                        adw::EntryRow {
+                            set_title: tr("Environment (NAME=value, separated by spaces)"),
+                            set_text: &format_env(&model.profile),
+                            connect_changed[sender] => move |row| {
+                                sender.input(AppDetailsMsg::SetEnv(row.text().to_string()));
+                            },
+                        },
+
+                        adw::SwitchRow {
+                            set_title: tr("Sandbox"),
+                            set_subtitle: tr("Run with firejail; launches are not counted"),
+                            set_active: model.profile.sandbox,
+                            connect_active_notify[sender] => move |row| {
+                                sender.input(AppDetailsMsg::SetSandbox(row.is_active()));
+                            },
+                        },
+
+                        adw::SwitchRow {
+                            set_title: tr("Network"),
+                            set_subtitle: tr("Allow network access in the sandbox"),
+                            set_active: model.profile.network,
+                            connect_active_notify[sender] => move |row| {
+                                sender.input(AppDetailsMsg::SetNetwork(row.is_active()));
+                            },
+                        },
+
+                        adw::SwitchRow {
+                            set_title: tr("Extract and Run"),
+                            set_subtitle: tr("Run without mounting the AppImage with FUSE"),
+                            set_active: model.profile.extract_and_run,
+                            connect_active_notify[sender] => move |row| {
+                                sender.input(AppDetailsMsg::SetExtractAndRun(row.is_active()));
+                            },
+                        },
+
+                        adw::SwitchRow {
+                            set_title: tr("Hidden"),
+                            set_subtitle: tr("Leave out of the application menu"),
+                            set_active: model.profile.hidden,
+                            connect_active_notify[sender] => move |row| {
+                                sender.input(AppDetailsMsg::SetHidden(row.is_active()));
+                            },
+                        },
+
+                        adw::SwitchRow {
+                            set_title: tr("Pinned"),
+                            set_subtitle: tr("Keep the integration when the file goes missing"),
+                            set_active: model.profile.pinned,
+                            connect_active_notify[sender] => move |row| {
+                                sender.input(AppDetailsMsg::SetPinned(row.is_active()));
+                            },
+                        },
+                    },
+                },
+            },
+        }
+    }
+
+    fn init(
+        app: Self::Init,
+        root: Self::Root,
+        sender: ComponentSender<Self>,
+    ) -> ComponentParts<Self> {
+        let installed = DesktopEntry::parse(&app.desktop_path)
+            .map(|entry| EntryFields::from_entry(&entry))
+            .unwrap_or_default();
+        let has_overrides = Overrides::load()
+            .map(|o| o.get(&app.identifier).is_some())
+            .unwrap_or(false);
+        let config = Config::load().unwrap_or_default();
+        let profile_key = match &app.name {
+            Some(name)
+                if !config.apps.contains_key(&app.identifier) && config.apps.contains_key(name) =>
+            {
+                name.clone()
+            }
+            _ => app.identifier.clone(),
+        };
+        let saved_profile = config.apps.get(&profile_key).cloned().unwrap_or_default();
+
+        let model = Self {
+            app,
+            edited: installed.clone(),
+            installed,
+            has_overrides,
+            profile_key,
+            profile: saved_profile.clone(),
+            saved_profile,
+            window: root.clone(),
+        };
+
+        let widgets = view_output!();
+
+        ComponentParts { model, widgets }
+    }
+
+    fn update(&mut self, msg: Self::Input, sender: ComponentSender<Self>) {
+        match msg {
+            AppDetailsMsg::SetName(name) => self.edited.name = name,
+            AppDetailsMsg::SetComment(comment) => self.edited.comment = comment,
+            AppDetailsMsg::SetCategories(categories) => self.edited.categories = categories,
+            AppDetailsMsg::SetKeywords(keywords) => self.edited.keywords = keywords,
+            AppDetailsMsg::SetTerminal(terminal) => self.edited.terminal = terminal,
+            AppDetailsMsg::SetSandbox(sandbox) => self.profile.sandbox = sandbox,
+            AppDetailsMsg::SetNetwork(network) => self.profile.network = network,
+            AppDetailsMsg::SetExtractAndRun(extract) => self.profile.extract_and_run = extract,
+            AppDetailsMsg::SetHidden(hidden) => self.profile.hidden = hidden,
+            AppDetailsMsg::SetPinned(pinned) => self.profile.pinned = pinned,
+            AppDetailsMsg::SetArgs(args) => {
+                self.profile.args = args.split_whitespace().map(str::to_string).collect();
+            }
+            AppDetailsMsg::SetEnv(env) => {
+                self.profile.env = env
+                    .split_whitespace()
+                    .filter_map(|pair| pair.split_once('='))
+                    .filter(|(name, _)| !name.is_empty())
+                    .map(|(name, value)| (name.to_string(), value.to_string()))
+                    .collect();
+            }
+            AppDetailsMsg::Save => {
+                let result = self.save_overrides(|overrides| {
+                    // Only fields changed in the editor become (or replace) overrides
+                    let (installed, edited) = (&self.installed, &self.edited);
+                    if edited.name != installed.name {
+                        overrides.name = Some(edited.name.clone());
+                    }
+                    if edited.comment != installed.comment {
+                        overrides.comment = Some(edited.comment.clone());
+                    }
+                    if edited.categories != installed.categories {
+                        overrides.categories = Some(edited.categories.clone());
+                    }
+                    if edited.keywords != installed.keywords {
+                        overrides.keywords = Some(edited.keywords.clone());
+                    }
+                    if edited.terminal != installed.terminal {
+                        overrides.terminal = Some(edited.terminal);
+                    }
+                });
+                let result = result
+                    .map_err(|e| e.to_string())
+                    .and_then(|()| self.save_profile(self.profile.clone()));
+                self.finish(result, &sender);
+            }
+            AppDetailsMsg::Reset => {
+                let result = self
+                    .save_overrides(|overrides| *overrides = DesktopOverrides::default())
+                    .map_err(|e| e.to_string())
+                    .and_then(|()| self.save_profile(AppProfile::default()));
+                self.finish(result, &sender);
+            }
+            AppDetailsMsg::OpenLink(kind) => {
+                if let Some(url) = self.link(kind) {
+                    let _ = Command::new("xdg-open").arg(url).spawn();
+                }
+            }
+            AppDetailsMsg::Retry => {
+                sender
+                    .output(AppDetailsOutput::Retry(self.app.appimage_path.clone()))
+                    .unwrap();
+                self.window.close();
+            }
+            AppDetailsMsg::ChooseIcon => {
+                let input = sender.input_sender().clone();
+                dialogs::show_icon_chooser(&self.window, move |icon| {
+                    input.emit(AppDetailsMsg::IconSelected(icon));
+                });
+            }
+            AppDetailsMsg::IconSelected(icon) => {
+                sender
+                    .output(AppDetailsOutput::SetIcon(
+                        self.app.appimage_path.clone(),
+                        Some(icon),
+                    ))
+                    .unwrap();
+                self.window.close();
+            }
+            AppDetailsMsg::ResetIcon => {
+                sender
+                    .output(AppDetailsOutput::SetIcon(self.app.appimage_path.clone(), None))
+                    .unwrap();
+                self.window.close();
+            }
+        }
+    }
+}
+
+impl AppDetails {
+    /// The app's project link of the given AppStream type.
+    fn link(&self, kind: &str) -> Option<&str> {
+        self.app.links.get(kind).map(String::as_str)
+    }
+
+    /// Banner text for the app's last integration error.
+    fn error_title(&self) -> String {
+        self.app
+            .last_error
+            .as_ref()
+            .map(|error| {
+                let when = format::days_ago(error.at, state::current_timestamp());
+                tr_args("Last update failed {}: {}", &[&when, &error.message])
+            })
+            .unwrap_or_default()
+    }
+
+    /// The autostart entries the app added, marking disabled ones.
+    fn autostart_summary(&self) -> String {
+        self.app
+            .autostart_entries
+            .iter()
+            .map(|entry| {
+                let path = entry.display().to_string();
+                if autostart::is_blocked(entry) {
+                    tr_args("{} (disabled)", &[&path])
+                } else {
+                    path
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// Update this app's overrides and write them to disk.
+    fn save_overrides(
+        &self,
+        change: impl FnOnce(&mut DesktopOverrides),
+    ) -> Result<(), crate::overrides::OverridesError> {
+        let mut all = Overrides::load()?;
+        let mut overrides = all.get(&self.app.identifier).cloned().unwrap_or_default();
+        change(&mut overrides);
+        all.set(&self.app.identifier, overrides);
+        all.save()
+    }
+
+    /// Write the app's launch settings to the config if they changed.
+    fn save_profile(&self, profile: AppProfile) -> Result<(), String> {
+        if profile == self.saved_profile {
+            return Ok(());
+        }
+        let mut config = Config::load().map_err(|e| e.to_string())?;
+        config.set_app_profile(&self.profile_key, profile);
+        config.save().map_err(|e| e.to_string())
+    }
+
+    /// Report the result and close the window on success.
+    fn finish(&self, result: Result<(), String>, sender: &ComponentSender<Self>) {
+        match result {
+            Ok(()) => {
+                sender
+                    .output(AppDetailsOutput::OverridesChanged(
+                        self.app.appimage_path.clone(),
+                    ))
+                    .unwrap();
+                self.window.close();
+            }
+            Err(e) => {
+                sender
+                    .output(AppDetailsOutput::ShowToast(format!(
+                        "Failed to save edits: {}",
+                        e
+                    )))
+                    .unwrap();
+            }
+        }
+    }
+}
+
+/// Environment variables of launch settings as edited: `NAME=value` pairs
+/// separated by spaces
+fn format_env(profile: &AppProfile) -> String {
+    profile
+        .env
+        .iter()
+        .map(|(name, value)| format!("{}={}", name, value))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
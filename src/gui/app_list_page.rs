@@ -1,13 +1,21 @@
 //! AppImage list page component.
 
-use super::app_row::{AppImageRow, AppImageRowOutput};
-use crate::state::{IntegratedAppImage, State};
+use super::app_details::{AppDetails, AppDetailsOutput};
+use super::app_row::{AppImageRow, AppImageRowMsg, AppImageRowOutput};
+use super::awaiting_row::{AwaitingRow, AwaitingRowOutput};
+use super::dialogs::{self, RemoveChoice};
+use super::failed_row::{FailedRow, FailedRowOutput};
+use crate::client::Client;
+use crate::config::{AppSort, Config};
+use crate::daemon::Daemon;
+use crate::i18n::{tr, tr_args};
+use crate::state::{self, FailedIntegration, IntegratedAppImage, State};
 use relm4::adw::prelude::*;
 use relm4::factory::{DynamicIndex, FactoryVecDeque};
-use relm4::gtk;
+use relm4::gtk::{self, gio};
 use relm4::prelude::*;
 use relm4::{adw, ComponentParts, ComponentSender, RelmWidgetExt};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::process::Command;
 
 /// The app list page model.
@@ -16,6 +24,20 @@ pub struct AppListPage {
     app_rows: FactoryVecDeque<AppImageRow>,
     /// Count of integrated apps.
     app_count: usize,
+    /// Factory for failed integration rows.
+    failed_rows: FactoryVecDeque<FailedRow>,
+    /// Count of failed integrations.
+    failed_count: usize,
+    /// Factory for AppImages awaiting approval.
+    awaiting_rows: FactoryVecDeque<AwaitingRow>,
+    /// Count of AppImages awaiting approval.
+    awaiting_count: usize,
+    /// Whether rows show check buttons for bulk actions.
+    selection_mode: bool,
+    /// Current sort order.
+    sort: AppSort,
+    /// Open app details window, if any.
+    details: Option<Controller<AppDetails>>,
 }
 
 /// Messages for the app list page.
@@ -23,10 +45,49 @@ pub struct AppListPage {
 pub enum AppListPageMsg {
     /// Reload the app list from state.
     Reload,
-    /// Remove an app by factory index.
+    /// Ask before removing an app by factory index.
     RemoveApp(DynamicIndex),
+    /// Remove apps after confirmation, optionally deleting the files.
+    RemoveConfirmed(Vec<PathBuf>, RemoveChoice),
+    /// Ask for a folder to scan once.
+    ScanFolder,
+    /// Integrate AppImages in a folder without watching it.
+    FolderSelected(PathBuf),
+    /// A folder scan finished, with the names of the integrated apps and the
+    /// number of AppImages now awaiting approval.
+    FolderScanned(PathBuf, Result<(Vec<String>, usize), String>),
+    /// Change the sort order by menu index.
+    SetSort(u32),
+    /// Enter or leave selection mode.
+    SetSelectionMode(bool),
+    /// Ask before removing all selected apps.
+    RemoveSelected,
+    /// Re-integrate all selected apps from scratch.
+    ReintegrateSelected,
+    /// Pin all selected apps, or unpin them if all are already pinned.
+    TogglePinSelected,
     /// Open a file location in the file manager.
     OpenLocation(PathBuf),
+    /// Show the details window for an app by factory index.
+    ShowDetails(DynamicIndex),
+    /// Show the details window for an app by identifier.
+    ShowApp(String),
+    /// Desktop entry overrides changed; re-integrate to apply them.
+    OverridesChanged(PathBuf),
+    /// Forward a toast message.
+    ShowToast(String),
+    /// Open a generated .desktop file in the default text editor.
+    EditDesktopFile(PathBuf),
+    /// Show an installed icon in the file manager.
+    RevealIcon(PathBuf),
+    /// Retry a failed integration.
+    Retry(PathBuf),
+    /// Re-integrate an app whose last update failed.
+    Reintegrate(PathBuf),
+    /// Integrate an AppImage held for approval.
+    Approve(PathBuf),
+    /// Use a custom icon for an AppImage, or its own icon again with `None`.
+    SetIcon(PathBuf, Option<PathBuf>),
 }
 
 /// Output messages from the app list page.
@@ -50,14 +111,42 @@ impl SimpleComponent for AppListPage {
             adw::HeaderBar {
                 #[wrap(Some)]
                 set_title_widget = &adw::WindowTitle {
-                    set_title: "Integrated Apps",
+                    set_title: tr("Integrated Apps"),
                 },
 
                 pack_start = &gtk::Button {
                     set_icon_name: "view-refresh-symbolic",
-                    set_tooltip_text: Some("Refresh list"),
+                    set_tooltip_text: Some(tr("Refresh list")),
                     connect_clicked => AppListPageMsg::Reload,
                 },
+
+                pack_start = &gtk::Button {
+                    set_icon_name: "folder-saved-search-symbolic",
+                    set_tooltip_text: Some(tr("Scan a folder now (without watching it)")),
+                    connect_clicked => AppListPageMsg::ScanFolder,
+                },
+
+                pack_end = &gtk::DropDown::from_strings(&AppSort::ALL.map(AppSort::label)) {
+                    set_tooltip_text: Some(tr("Sort by")),
+                    set_valign: gtk::Align::Center,
+                    #[watch]
+                    set_selected: AppSort::ALL.iter().position(|s| *s == model.sort).unwrap_or(0) as u32,
+                    connect_selected_notify[sender] => move |dropdown| {
+                        sender.input(AppListPageMsg::SetSort(dropdown.selected()));
+                    },
+                },
+
+                pack_end = &gtk::ToggleButton {
+                    set_icon_name: "selection-mode-symbolic",
+                    set_tooltip_text: Some(tr("Select apps")),
+                    #[watch]
+                    set_sensitive: model.app_count > 0,
+                    #[watch]
+                    set_active: model.selection_mode,
+                    connect_toggled[sender] => move |button| {
+                        sender.input(AppListPageMsg::SetSelectionMode(button.is_active()));
+                    },
+                },
             },
 
             gtk::ScrolledWindow {
@@ -68,33 +157,101 @@ impl SimpleComponent for AppListPage {
                     set_maximum_size: 600,
                     set_margin_all: 12,
 
-                    if model.app_count == 0 {
-                        adw::StatusPage {
-                            set_icon_name: Some("application-x-executable-symbolic"),
-                            set_title: "No Integrated Apps",
-                            set_description: Some("AppImages you integrate will appear here.\nDrop an AppImage into a watched directory to get started."),
-                        }
-                    } else {
+                    gtk::Box {
+                        set_orientation: gtk::Orientation::Vertical,
+                        set_spacing: 24,
+
                         gtk::Box {
                             set_orientation: gtk::Orientation::Vertical,
                             set_spacing: 12,
+                            #[watch]
+                            set_visible: model.awaiting_count > 0,
 
                             gtk::Label {
                                 #[watch]
-                                set_label: &format!("{} integrated app{}", model.app_count, if model.app_count == 1 { "" } else { "s" }),
+                                set_label: &tr_args("{} awaiting approval", &[&model.awaiting_count]),
                                 set_halign: gtk::Align::Start,
-                                add_css_class: "dim-label",
+                                add_css_class: "heading",
                             },
 
                             #[local_ref]
-                            app_list_box -> gtk::ListBox {
+                            awaiting_list_box -> gtk::ListBox {
                                 set_selection_mode: gtk::SelectionMode::None,
                                 add_css_class: "boxed-list",
                             },
+                        },
+
+                        gtk::Box {
+                            set_orientation: gtk::Orientation::Vertical,
+                            set_spacing: 12,
+                            #[watch]
+                            set_visible: model.failed_count > 0,
+
+                            gtk::Label {
+                                #[watch]
+                                set_label: &if model.failed_count == 1 { tr("1 failed integration").to_string() } else { tr_args("{} failed integrations", &[&model.failed_count]) },
+                                set_halign: gtk::Align::Start,
+                                add_css_class: "heading",
+                            },
+
+                            #[local_ref]
+                            failed_list_box -> gtk::ListBox {
+                                set_selection_mode: gtk::SelectionMode::None,
+                                add_css_class: "boxed-list",
+                            },
+                        },
+
+                        if model.app_count == 0 {
+                            adw::StatusPage {
+                                set_icon_name: Some("application-x-executable-symbolic"),
+                                set_title: tr("No Integrated Apps"),
+                                set_description: Some(tr("AppImages you integrate will appear here.\nDrop an AppImage into a watched directory to get started.")),
+                            }
+                        } else {
+                            gtk::Box {
+                                set_orientation: gtk::Orientation::Vertical,
+                                set_spacing: 12,
+
+                                gtk::Label {
+                                    #[watch]
+                                    set_label: &if model.app_count == 1 { tr("1 integrated app").to_string() } else { tr_args("{} integrated apps", &[&model.app_count]) },
+                                    set_halign: gtk::Align::Start,
+                                    add_css_class: "dim-label",
+                                },
+
+                                #[local_ref]
+                                app_list_box -> gtk::ListBox {
+                                    set_selection_mode: gtk::SelectionMode::None,
+                                    add_css_class: "boxed-list",
+                                },
+                            }
                         }
                     }
                 }
-            }
+            },
+
+            gtk::ActionBar {
+                #[watch]
+                set_revealed: model.selection_mode,
+
+                pack_start = &gtk::Button {
+                    set_label: tr("Re-integrate"),
+                    set_tooltip_text: Some(tr("Integrate the selected apps again from scratch")),
+                    connect_clicked => AppListPageMsg::ReintegrateSelected,
+                },
+
+                pack_start = &gtk::Button {
+                    set_label: tr("Pin"),
+                    set_tooltip_text: Some(tr("Pin or unpin the selected apps")),
+                    connect_clicked => AppListPageMsg::TogglePinSelected,
+                },
+
+                pack_end = &gtk::Button {
+                    set_label: tr("Remove"),
+                    add_css_class: "destructive-action",
+                    connect_clicked => AppListPageMsg::RemoveSelected,
+                },
+            },
         }
     }
 
@@ -108,14 +265,39 @@ impl SimpleComponent for AppListPage {
             .forward(sender.input_sender(), |output| match output {
                 AppImageRowOutput::Remove(index) => AppListPageMsg::RemoveApp(index),
                 AppImageRowOutput::OpenLocation(path) => AppListPageMsg::OpenLocation(path),
+                AppImageRowOutput::EditDesktopFile(path) => AppListPageMsg::EditDesktopFile(path),
+                AppImageRowOutput::RevealIcon(path) => AppListPageMsg::RevealIcon(path),
+                AppImageRowOutput::ShowDetails(index) => AppListPageMsg::ShowDetails(index),
+                AppImageRowOutput::Retry(path) => AppListPageMsg::Reintegrate(path),
+            });
+
+        let failed_rows = FactoryVecDeque::builder()
+            .launch(gtk::ListBox::default())
+            .forward(sender.input_sender(), |output| match output {
+                FailedRowOutput::Retry(path) => AppListPageMsg::Retry(path),
+            });
+
+        let awaiting_rows = FactoryVecDeque::builder()
+            .launch(gtk::ListBox::default())
+            .forward(sender.input_sender(), |output| match output {
+                AwaitingRowOutput::Approve(path) => AppListPageMsg::Approve(path),
             });
 
         let model = Self {
             app_rows,
             app_count: 0,
+            failed_rows,
+            failed_count: 0,
+            awaiting_rows,
+            awaiting_count: 0,
+            selection_mode: false,
+            sort: Config::load().map(|c| c.gui.sort).unwrap_or_default(),
+            details: None,
         };
 
         let app_list_box = model.app_rows.widget();
+        let failed_list_box = model.failed_rows.widget();
+        let awaiting_list_box = model.awaiting_rows.widget();
         let widgets = view_output!();
 
         // Initial load
@@ -132,56 +314,341 @@ impl SimpleComponent for AppListPage {
             AppListPageMsg::RemoveApp(index) => {
                 if let Some(row) = self.app_rows.get(index.current_index()) {
                     let path = row.appimage_path.clone();
-                    let path_str = path.to_string_lossy().to_string();
-
-                    // Spawn CLI to remove integration
-                    match Command::new("appimage-auto")
-                        .args(["remove", &path_str])
-                        .spawn()
-                    {
-                        Ok(mut child) => {
-                            // Wait for completion
-                            let _ = child.wait();
-                            sender.input(AppListPageMsg::Reload);
-                            sender
-                                .output(AppListPageOutput::ShowToast(
-                                    "Integration removed".to_string(),
-                                ))
-                                .unwrap();
-                        }
-                        Err(e) => {
-                            sender
-                                .output(AppListPageOutput::ShowToast(format!(
-                                    "Failed to remove: {}",
-                                    e
-                                )))
-                                .unwrap();
+                    let app = relm4::main_adw_application();
+                    if let Some(window) = app.active_window() {
+                        let input = sender.input_sender().clone();
+                        dialogs::show_remove_confirmation(&window, &row.name, move |choice| {
+                            input.emit(AppListPageMsg::RemoveConfirmed(vec![path.clone()], choice));
+                        });
+                    }
+                }
+            }
+            AppListPageMsg::RemoveConfirmed(paths, choice) => {
+                // Through the daemon, or in-process when it isn't running
+                let input = sender.input_sender().clone();
+                relm4::spawn(async move {
+                    let failed = match Client::connect().await {
+                        Ok(client) => {
+                            let mut failed = 0;
+                            for path in &paths {
+                                let result = match choice {
+                                    RemoveChoice::Unintegrate => client.remove(path).await,
+                                    RemoveChoice::Delete => client.remove_and_delete(path).await,
+                                };
+                                if result.is_err() {
+                                    failed += 1;
+                                }
+                            }
+                            Ok(failed)
                         }
+                        Err(e) => Err(e.to_string()),
+                    };
+                    input.emit(AppListPageMsg::Reload);
+                    input.emit(AppListPageMsg::ShowToast(removed_message(
+                        paths.len(),
+                        choice,
+                        failed,
+                    )));
+                });
+                self.selection_mode = false;
+            }
+            AppListPageMsg::ScanFolder => {
+                if let Some(window) = relm4::main_adw_application().active_window() {
+                    let input = sender.input_sender().clone();
+                    dialogs::show_directory_chooser(&window, move |path| {
+                        input.emit(AppListPageMsg::FolderSelected(path));
+                    });
+                }
+            }
+            AppListPageMsg::FolderSelected(folder) => {
+                // Scanning a large folder or a slow drive takes a while
+                let input = sender.input_sender().clone();
+                relm4::spawn_blocking(move || {
+                    let result = scan_folder(&folder);
+                    input.emit(AppListPageMsg::FolderScanned(folder, result));
+                });
+            }
+            AppListPageMsg::FolderScanned(folder, result) => match result {
+                Ok((names, awaiting)) => {
+                    if let Some(window) = relm4::main_adw_application().active_window() {
+                        dialogs::show_scan_results(&window, &folder, &names, awaiting);
                     }
+                    sender.input(AppListPageMsg::Reload);
+                }
+                Err(e) => {
+                    sender
+                        .output(AppListPageOutput::ShowToast(tr_args(
+                            "Scan failed: {}",
+                            &[&e],
+                        )))
+                        .unwrap();
+                }
+            },
+            AppListPageMsg::SetSort(index) => {
+                let Some(&sort) = AppSort::ALL.get(index as usize) else {
+                    return;
+                };
+                if sort == self.sort {
+                    return;
+                }
+                self.sort = sort;
+                self.reload_apps();
+
+                // Persist only the sort order so other settings are not clobbered
+                let result = Config::load().and_then(|mut config| {
+                    config.gui.sort = sort;
+                    config.save()
+                });
+                if let Err(e) = result {
+                    sender
+                        .output(AppListPageOutput::ShowToast(tr_args(
+                            "Failed to save config: {}",
+                            &[&e],
+                        )))
+                        .unwrap();
                 }
             }
+            AppListPageMsg::SetSelectionMode(enabled) => {
+                if self.selection_mode != enabled {
+                    self.selection_mode = enabled;
+                    self.app_rows.broadcast(AppImageRowMsg::SetSelectionMode(enabled));
+                }
+            }
+            AppListPageMsg::RemoveSelected => {
+                let paths = self.selected_paths();
+                if paths.is_empty() {
+                    return;
+                }
+                let label = if paths.len() == 1 {
+                    self.app_rows.iter().find(|r| r.selected).map(|r| r.name.clone()).unwrap_or_default()
+                } else {
+                    tr_args("{} apps", &[&paths.len()])
+                };
+                let app = relm4::main_adw_application();
+                if let Some(window) = app.active_window() {
+                    let input = sender.input_sender().clone();
+                    dialogs::show_remove_confirmation(&window, &label, move |choice| {
+                        input.emit(AppListPageMsg::RemoveConfirmed(paths.clone(), choice));
+                    });
+                }
+            }
+            AppListPageMsg::ReintegrateSelected => {
+                let paths = self.selected_paths();
+                if paths.is_empty() {
+                    return;
+                }
+
+                let input = sender.input_sender().clone();
+                relm4::spawn(async move {
+                    let failed = match Client::connect().await {
+                        Ok(client) => {
+                            let mut failed = 0;
+                            for path in &paths {
+                                if client.reintegrate(path).await.is_err() {
+                                    failed += 1;
+                                }
+                            }
+                            failed
+                        }
+                        Err(_) => paths.len(),
+                    };
+                    let message = if failed == 0 && paths.len() == 1 {
+                        tr("App re-integrated").to_string()
+                    } else if failed == 0 {
+                        tr_args("{} apps re-integrated", &[&paths.len()])
+                    } else {
+                        tr_args(
+                            "Failed to re-integrate {} of {} apps",
+                            &[&failed, &paths.len()],
+                        )
+                    };
+                    input.emit(AppListPageMsg::Reload);
+                    input.emit(AppListPageMsg::ShowToast(message));
+                });
+                self.selection_mode = false;
+            }
+            AppListPageMsg::TogglePinSelected => {
+                let selected: Vec<_> = self.app_rows.iter().filter(|r| r.selected).collect();
+                if selected.is_empty() {
+                    return;
+                }
+
+                // Pin all unless every selected app is already pinned
+                let pin = !selected.iter().all(|r| r.pinned);
+                let paths: Vec<PathBuf> = selected.iter().map(|r| r.appimage_path.clone()).collect();
+                let command = if pin { "pin" } else { "unpin" };
+
+                let message = match Command::new("appimage-auto").arg(command).args(&paths).status() {
+                    Ok(status) if status.success() => match (pin, paths.len()) {
+                        (true, 1) => tr("App pinned").to_string(),
+                        (true, n) => tr_args("{} apps pinned", &[&n]),
+                        (false, 1) => tr("App unpinned").to_string(),
+                        (false, n) => tr_args("{} apps unpinned", &[&n]),
+                    },
+                    Ok(_) if pin => tr("Failed to pin apps").to_string(),
+                    Ok(_) => tr("Failed to unpin apps").to_string(),
+                    Err(e) if pin => tr_args("Failed to pin: {}", &[&e]),
+                    Err(e) => tr_args("Failed to unpin: {}", &[&e]),
+                };
+                self.selection_mode = false;
+                sender.input(AppListPageMsg::Reload);
+                sender
+                    .output(AppListPageOutput::ShowToast(message))
+                    .unwrap();
+            }
             AppListPageMsg::OpenLocation(path) => {
                 // Open file manager at location
                 let _ = Command::new("xdg-open").arg(&path).spawn();
             }
+            AppListPageMsg::ShowDetails(index) => {
+                let Some(row) = self.app_rows.get(index.current_index()) else {
+                    return;
+                };
+                if let Some(app) = State::load()
+                    .ok()
+                    .and_then(|state| state.get_by_path(&row.appimage_path).cloned())
+                {
+                    self.show_details(app, &sender);
+                }
+            }
+            AppListPageMsg::ShowApp(identifier) => {
+                match State::load()
+                    .ok()
+                    .and_then(|state| state.get(&identifier).cloned())
+                {
+                    Some(app) => self.show_details(app, &sender),
+                    None => sender.input(AppListPageMsg::ShowToast(
+                        tr("App is no longer integrated").to_string(),
+                    )),
+                }
+            }
+            AppListPageMsg::OverridesChanged(path) => {
+                // Spawn CLI to rewrite the desktop entry with the overrides applied
+                let message = match Command::new("appimage-auto")
+                    .arg("integrate")
+                    .arg("--force")
+                    .arg(&path)
+                    .status()
+                {
+                    Ok(status) if status.success() => tr("Desktop entry updated").to_string(),
+                    Ok(_) => tr("Edits saved, but re-integration failed").to_string(),
+                    Err(e) => tr_args("Edits saved, but failed to re-integrate: {}", &[&e]),
+                };
+                sender.input(AppListPageMsg::Reload);
+                sender
+                    .output(AppListPageOutput::ShowToast(message))
+                    .unwrap();
+            }
+            AppListPageMsg::ShowToast(message) => {
+                sender
+                    .output(AppListPageOutput::ShowToast(message))
+                    .unwrap();
+            }
+            AppListPageMsg::EditDesktopFile(path) => {
+                if let Err(message) = open_in_text_editor(&path) {
+                    sender
+                        .output(AppListPageOutput::ShowToast(message))
+                        .unwrap();
+                }
+            }
+            AppListPageMsg::RevealIcon(path) => {
+                reveal_in_file_manager(&path);
+            }
+            AppListPageMsg::Reintegrate(path) => {
+                // Spawn CLI to integrate from scratch; a fresh entry has no error
+                let message = match Command::new("appimage-auto")
+                    .arg("integrate")
+                    .arg("--force")
+                    .arg(&path)
+                    .status()
+                {
+                    Ok(status) if status.success() => tr("AppImage integrated").to_string(),
+                    Ok(_) => tr("Integration failed").to_string(),
+                    Err(e) => tr_args("Failed to integrate: {}", &[&e]),
+                };
+                sender.input(AppListPageMsg::Reload);
+                sender
+                    .output(AppListPageOutput::ShowToast(message))
+                    .unwrap();
+            }
+            AppListPageMsg::SetIcon(path, icon) => {
+                // Spawn CLI to store the icon and re-integrate with it
+                let mut command = Command::new("appimage-auto");
+                command.arg("set-icon").arg(&path);
+                match &icon {
+                    Some(icon) => command.arg(icon),
+                    None => command.arg("--reset"),
+                };
+                let message = match command.status() {
+                    Ok(status) if status.success() => tr("Icon changed").to_string(),
+                    Ok(_) => tr("Failed to change the icon").to_string(),
+                    Err(e) => tr_args("Failed to change the icon: {}", &[&e]),
+                };
+                sender.input(AppListPageMsg::Reload);
+                sender
+                    .output(AppListPageOutput::ShowToast(message))
+                    .unwrap();
+            }
+            AppListPageMsg::Retry(path) | AppListPageMsg::Approve(path) => {
+                // Integrating clears the failure or approval record
+                let input = sender.input_sender().clone();
+                relm4::spawn(async move {
+                    let result = match Client::connect().await {
+                        Ok(client) => client.integrate(&path).await,
+                        Err(e) => Err(e),
+                    };
+                    let message = match result {
+                        Ok(()) => tr("AppImage integrated").to_string(),
+                        Err(e) => tr_args("Failed to integrate: {}", &[&e]),
+                    };
+                    input.emit(AppListPageMsg::Reload);
+                    input.emit(AppListPageMsg::ShowToast(message));
+                });
+            }
         }
     }
 }
 
 impl AppListPage {
+    /// Open the details window for an app, replacing an open one.
+    fn show_details(&mut self, app: IntegratedAppImage, sender: &ComponentSender<Self>) {
+        let mut builder = AppDetails::builder();
+        if let Some(window) = relm4::main_adw_application().active_window() {
+            builder = builder.transient_for(&window);
+        }
+        let details = builder
+            .launch(app)
+            .forward(sender.input_sender(), |output| match output {
+                AppDetailsOutput::OverridesChanged(path) => AppListPageMsg::OverridesChanged(path),
+                AppDetailsOutput::ShowToast(message) => AppListPageMsg::ShowToast(message),
+                AppDetailsOutput::Retry(path) => AppListPageMsg::Reintegrate(path),
+                AppDetailsOutput::SetIcon(path, icon) => AppListPageMsg::SetIcon(path, icon),
+            });
+        details.widget().present();
+        self.details = Some(details);
+    }
+
+    /// AppImage paths of the rows selected for a bulk action.
+    fn selected_paths(&self) -> Vec<PathBuf> {
+        self.app_rows
+            .iter()
+            .filter(|row| row.selected)
+            .map(|row| row.appimage_path.clone())
+            .collect()
+    }
+
     /// Reload the app list from state.
     fn reload_apps(&mut self) {
+        let state = State::load().ok();
+        self.reload_failures(state.as_ref());
+        self.reload_awaiting(state.as_ref());
+
         let mut guard = self.app_rows.guard();
         guard.clear();
 
-        if let Ok(state) = State::load() {
+        if let Some(state) = state {
             let mut apps: Vec<IntegratedAppImage> = state.all().cloned().collect();
-            // Sort by name
-            apps.sort_by(|a, b| {
-                let name_a = a.name.as_deref().unwrap_or("");
-                let name_b = b.name.as_deref().unwrap_or("");
-                name_a.to_lowercase().cmp(&name_b.to_lowercase())
-            });
+            state::sort_apps(&mut apps, self.sort);
 
             self.app_count = apps.len();
             for app in apps {
@@ -190,5 +657,125 @@ impl AppListPage {
         } else {
             self.app_count = 0;
         }
+        drop(guard);
+
+        // New rows start outside selection mode
+        if self.app_count == 0 {
+            self.selection_mode = false;
+        } else if self.selection_mode {
+            self.app_rows.broadcast(AppImageRowMsg::SetSelectionMode(true));
+        }
+    }
+
+    /// Reload the AppImages awaiting approval from state.
+    fn reload_awaiting(&mut self, state: Option<&State>) {
+        let mut guard = self.awaiting_rows.guard();
+        guard.clear();
+
+        let awaiting: Vec<(PathBuf, u64)> = state
+            .map(|s| {
+                s.awaiting()
+                    .into_iter()
+                    .map(|path| (path.clone(), s.awaiting_approval[path]))
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        self.awaiting_count = awaiting.len();
+        for entry in awaiting {
+            guard.push_back(entry);
+        }
+    }
+
+    /// Reload the failed integrations list from state.
+    fn reload_failures(&mut self, state: Option<&State>) {
+        let mut guard = self.failed_rows.guard();
+        guard.clear();
+
+        let mut failures: Vec<FailedIntegration> = state
+            .map(|s| s.failures().cloned().collect())
+            .unwrap_or_default();
+        failures.sort_by(|a, b| a.appimage_path.cmp(&b.appimage_path));
+
+        self.failed_count = failures.len();
+        for failure in failures {
+            guard.push_back(failure);
+        }
+    }
+}
+
+/// Integrate the AppImages in a folder, returning the names of the
+/// integrated apps and how many more AppImages await approval.
+fn scan_folder(folder: &Path) -> Result<(Vec<String>, usize), String> {
+    let config = Config::load().map_err(|e| e.to_string())?;
+    let mut daemon = Daemon::with_config(config).map_err(|e| e.to_string())?;
+    let awaiting_before = daemon.state().awaiting().len();
+    let integrated = daemon.scan_directories(&[folder.to_path_buf()], |_, _, _| {});
+    let names = integrated
+        .iter()
+        .map(|path| {
+            daemon
+                .state()
+                .get_by_path(path)
+                .and_then(|app| app.name.clone())
+                .unwrap_or_else(|| path.display().to_string())
+        })
+        .collect();
+    let awaiting = daemon
+        .state()
+        .awaiting()
+        .len()
+        .saturating_sub(awaiting_before);
+    Ok((names, awaiting))
+}
+
+/// Toast after removing `total` integrations, `failed` of which could not
+/// be removed (or the error that stopped removing them).
+fn removed_message(total: usize, choice: RemoveChoice, failed: Result<usize, String>) -> String {
+    match failed {
+        Err(e) => tr_args("Failed to remove: {}", &[&e]),
+        Ok(failed) if failed > 0 => {
+            tr_args("Failed to remove {} of {} integrations", &[&failed, &total])
+        }
+        Ok(_) if total > 1 => match choice {
+            RemoveChoice::Unintegrate => tr_args("{} integrations removed", &[&total]),
+            RemoveChoice::Delete => tr_args("{} integrations removed and files deleted", &[&total]),
+        },
+        Ok(_) => match choice {
+            RemoveChoice::Unintegrate => tr("Integration removed").to_string(),
+            RemoveChoice::Delete => tr("Integration removed and file deleted").to_string(),
+        },
+    }
+}
+
+/// Open a file with the default text editor.
+///
+/// `xdg-open` cannot be used for .desktop files: it would launch the app.
+fn open_in_text_editor(path: &Path) -> Result<(), String> {
+    let editor = gio::AppInfo::default_for_type("text/plain", false)
+        .ok_or_else(|| tr("No text editor found").to_string())?;
+    editor
+        .launch(&[gio::File::for_path(path)], gio::AppLaunchContext::NONE)
+        .map_err(|e| tr_args("Failed to open editor: {}", &[&e]))
+}
+
+/// Select a file in the file manager, falling back to opening its directory.
+fn reveal_in_file_manager(path: &Path) {
+    let uri = gio::File::for_path(path).uri();
+    let shown = Command::new("dbus-send")
+        .args([
+            "--session",
+            "--dest=org.freedesktop.FileManager1",
+            "--type=method_call",
+            "/org/freedesktop/FileManager1",
+            "org.freedesktop.FileManager1.ShowItems",
+        ])
+        .arg(format!("array:string:{}", uri))
+        .arg("string:")
+        .status()
+        .is_ok_and(|status| status.success());
+
+    if !shown && let Some(parent) = path.parent() {
+        let _ = Command::new("xdg-open").arg(parent).spawn();
     }
 }
@@ -1,6 +1,8 @@
 //! AppImage row factory component for the app list.
 
-use crate::state::IntegratedAppImage;
+use crate::format;
+use crate::i18n::{tr, tr_args};
+use crate::state::{self, IntegratedAppImage};
 use relm4::adw::prelude::*;
 use relm4::factory::{DynamicIndex, FactoryComponent, FactorySender};
 use relm4::gtk;
@@ -16,14 +18,36 @@ pub struct AppImageRow {
     pub name: String,
     /// Path to the AppImage file.
     pub appimage_path: PathBuf,
+    /// Path to the generated .desktop file.
+    pub desktop_path: PathBuf,
+    /// First installed icon that still exists.
+    pub icon_path: Option<PathBuf>,
     /// Whether the AppImage file still exists.
     pub exists: bool,
+    /// Size of the AppImage file and how long ago it was integrated.
+    pub details: String,
+    /// Whether the integration is pinned.
+    pub pinned: bool,
+    /// Whether the app added itself to autostart.
+    pub autostart: bool,
+    /// Whether the list is in selection mode.
+    pub selection_mode: bool,
+    /// Whether the row is selected for a bulk action.
+    pub selected: bool,
+    /// Message of the last integration error, if the last update failed.
+    pub error: Option<String>,
 }
 
 /// Messages for the AppImage row.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum AppImageRowMsg {
     OpenLocation,
+    EditDesktopFile,
+    RevealIcon,
+    Retry,
+    /// Show or hide the selection check button (clears the selection).
+    SetSelectionMode(bool),
+    SetSelected(bool),
 }
 
 /// Output messages from the AppImage row.
@@ -31,6 +55,14 @@ pub enum AppImageRowMsg {
 pub enum AppImageRowOutput {
     Remove(DynamicIndex),
     OpenLocation(PathBuf),
+    /// Show the details window for the row.
+    ShowDetails(DynamicIndex),
+    /// Open a .desktop file in a text editor.
+    EditDesktopFile(PathBuf),
+    /// Show an icon file in the file manager.
+    RevealIcon(PathBuf),
+    /// Re-integrate an AppImage whose last update failed.
+    Retry(PathBuf),
 }
 
 #[relm4::factory(pub)]
@@ -47,6 +79,20 @@ impl FactoryComponent for AppImageRow {
             set_title: &self.name,
             set_subtitle: &self.appimage_path.display().to_string(),
             set_activatable: true,
+            connect_activated[sender, index] => move |_| {
+                sender.output(AppImageRowOutput::ShowDetails(index.clone())).unwrap();
+            },
+
+            add_prefix = &gtk::CheckButton {
+                #[watch]
+                set_visible: self.selection_mode,
+                #[watch]
+                set_active: self.selected,
+                set_valign: gtk::Align::Center,
+                connect_toggled[sender] => move |check| {
+                    sender.input(AppImageRowMsg::SetSelected(check.is_active()));
+                },
+            },
 
             add_prefix = &gtk::Image {
                 set_icon_name: Some(if self.exists { "application-x-executable-symbolic" } else { "dialog-warning-symbolic" }),
@@ -56,19 +102,68 @@ impl FactoryComponent for AppImageRow {
                 set_spacing: 6,
                 set_valign: gtk::Align::Center,
 
+                gtk::Label {
+                    set_label: &self.details,
+                    add_css_class: "dim-label",
+                    add_css_class: "caption",
+                },
+
+                gtk::Button {
+                    set_icon_name: "dialog-warning-symbolic",
+                    add_css_class: "flat",
+                    add_css_class: "warning",
+                    set_tooltip_text: self.error.as_deref(),
+                    set_visible: self.error.is_some(),
+                    connect_clicked[sender] => move |_| {
+                        sender.input(AppImageRowMsg::Retry);
+                    },
+                },
+
+                gtk::Image {
+                    set_icon_name: Some("system-run-symbolic"),
+                    set_tooltip_text: Some(tr("Starts on login: the app added itself to autostart")),
+                    set_visible: self.autostart,
+                },
+
+                gtk::Image {
+                    set_icon_name: Some("view-pin-symbolic"),
+                    set_tooltip_text: Some(tr("Pinned: kept when the file goes missing")),
+                    #[watch]
+                    set_visible: self.pinned,
+                },
+
                 gtk::Button {
                     set_icon_name: "folder-open-symbolic",
                     add_css_class: "flat",
-                    set_tooltip_text: Some("Open file location"),
+                    set_tooltip_text: Some(tr("Open file location")),
                     connect_clicked[sender] => move |_| {
                         sender.input(AppImageRowMsg::OpenLocation);
                     },
                 },
 
+                gtk::Button {
+                    set_icon_name: "document-edit-symbolic",
+                    add_css_class: "flat",
+                    set_tooltip_text: Some(tr("Edit desktop entry")),
+                    connect_clicked[sender] => move |_| {
+                        sender.input(AppImageRowMsg::EditDesktopFile);
+                    },
+                },
+
+                gtk::Button {
+                    set_icon_name: "image-x-generic-symbolic",
+                    add_css_class: "flat",
+                    set_tooltip_text: Some(tr("Show installed icon")),
+                    set_sensitive: self.icon_path.is_some(),
+                    connect_clicked[sender] => move |_| {
+                        sender.input(AppImageRowMsg::RevealIcon);
+                    },
+                },
+
                 gtk::Button {
                     set_icon_name: "user-trash-symbolic",
                     add_css_class: "flat",
-                    set_tooltip_text: Some("Remove integration"),
+                    set_tooltip_text: Some(tr("Remove integration")),
                     connect_clicked[sender, index] => move |_| {
                         sender.output(AppImageRowOutput::Remove(index.clone())).unwrap();
                     },
@@ -83,14 +178,38 @@ impl FactoryComponent for AppImageRow {
             info.appimage_path
                 .file_name()
                 .map(|n| n.to_string_lossy().to_string())
-                .unwrap_or_else(|| "Unknown".to_string())
+                .unwrap_or_else(|| tr("Unknown").to_string())
         });
 
+        let now = state::current_timestamp();
+        let age = format::days_ago(info.integrated_at, now);
+        let mut details = if exists {
+            format!("{} · integrated {}", format::human_size(info.file_size()), age)
+        } else {
+            format!("integrated {}", age)
+        };
+        if let Some(last) = info.last_launched {
+            details.push_str(&format!(" · used {}", format::days_ago(last, now)));
+        }
+
+        let icon_path = info.icon_paths.into_iter().find(|p| p.exists());
+        let error = info
+            .last_error
+            .map(|e| tr_args("Last update failed: {}. Click to retry.", &[&e.message]));
+
         Self {
             identifier: info.identifier,
             name,
             appimage_path: info.appimage_path,
+            desktop_path: info.desktop_path,
+            icon_path,
             exists,
+            details,
+            pinned: info.pinned,
+            autostart: !info.autostart_entries.is_empty(),
+            selection_mode: false,
+            selected: false,
+            error,
         }
     }
 
@@ -103,6 +222,30 @@ impl FactoryComponent for AppImageRow {
                         .unwrap();
                 }
             }
+            AppImageRowMsg::Retry => {
+                sender
+                    .output(AppImageRowOutput::Retry(self.appimage_path.clone()))
+                    .unwrap();
+            }
+            AppImageRowMsg::EditDesktopFile => {
+                sender
+                    .output(AppImageRowOutput::EditDesktopFile(self.desktop_path.clone()))
+                    .unwrap();
+            }
+            AppImageRowMsg::RevealIcon => {
+                if let Some(icon) = &self.icon_path {
+                    sender
+                        .output(AppImageRowOutput::RevealIcon(icon.clone()))
+                        .unwrap();
+                }
+            }
+            AppImageRowMsg::SetSelectionMode(enabled) => {
+                self.selection_mode = enabled;
+                self.selected = false;
+            }
+            AppImageRowMsg::SetSelected(selected) => {
+                self.selected = selected;
+            }
         }
     }
 }
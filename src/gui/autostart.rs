@@ -1,5 +1,6 @@
 //! Autostart helpers for managing XDG autostart entries.
 
+use crate::i18n;
 use std::fs;
 use std::io::{self, ErrorKind};
 use std::path::PathBuf;
@@ -32,7 +33,10 @@ pub fn set_autostart(enabled: bool) -> io::Result<()> {
 
         // Write the autostart desktop entry
         let desktop_content = include_str!("../../autostart/appimage-auto.desktop");
-        fs::write(&autostart_file, desktop_content)?;
+        fs::write(
+            &autostart_file,
+            i18n::localize_desktop_entry(desktop_content),
+        )?;
     } else if autostart_file.exists() {
         fs::remove_file(&autostart_file)?;
     }
@@ -0,0 +1,82 @@
+//! Row factory component for AppImages awaiting approval.
+
+use crate::format;
+use crate::i18n::tr;
+use crate::state;
+use relm4::adw;
+use relm4::adw::prelude::*;
+use relm4::factory::{DynamicIndex, FactoryComponent, FactorySender};
+use relm4::gtk;
+use std::path::PathBuf;
+
+/// A single AppImage held for approval.
+#[derive(Debug)]
+pub struct AwaitingRow {
+    /// Path to the AppImage file.
+    pub appimage_path: PathBuf,
+    /// File name shown as the title.
+    pub name: String,
+    /// Location and when it was found, shown as the subtitle.
+    pub summary: String,
+}
+
+/// Output messages from the awaiting approval row.
+#[derive(Debug)]
+pub enum AwaitingRowOutput {
+    Approve(PathBuf),
+}
+
+#[relm4::factory(pub)]
+impl FactoryComponent for AwaitingRow {
+    /// AppImage path and when it was found.
+    type Init = (PathBuf, u64);
+    type Input = ();
+    type Output = AwaitingRowOutput;
+    type CommandOutput = ();
+    type ParentWidget = gtk::ListBox;
+
+    view! {
+        #[root]
+        adw::ActionRow {
+            set_title: &self.name,
+            set_subtitle: &self.summary,
+
+            add_prefix = &gtk::Image {
+                set_icon_name: Some("dialog-question-symbolic"),
+            },
+
+            add_suffix = &gtk::Button {
+                set_label: tr("Integrate"),
+                set_valign: gtk::Align::Center,
+                add_css_class: "suggested-action",
+                set_tooltip_text: Some(tr("Add this AppImage to the application menu")),
+                connect_clicked[sender, path = self.appimage_path.clone()] => move |_| {
+                    sender.output(AwaitingRowOutput::Approve(path.clone())).unwrap();
+                },
+            },
+        }
+    }
+
+    fn init_model(init: Self::Init, _index: &DynamicIndex, _sender: FactorySender<Self>) -> Self {
+        let (appimage_path, found_at) = init;
+        let name = appimage_path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_else(|| tr("Unknown").to_string());
+        let dir = appimage_path
+            .parent()
+            .map(|p| p.display().to_string())
+            .unwrap_or_default();
+        let summary = format!(
+            "{} · found {}",
+            dir,
+            format::days_ago(found_at, state::current_timestamp())
+        );
+
+        Self {
+            appimage_path,
+            name,
+            summary,
+        }
+    }
+}
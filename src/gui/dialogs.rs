@@ -1,48 +1,51 @@
-//! File chooser dialogs for the GUI.
+//! File chooser and confirmation dialogs for the GUI.
+//!
+//! The file choosers use `gtk::FileDialog`, which goes through the XDG
+//! desktop portal when one is available, so they work inside a Flatpak
+//! sandbox and on systems that only offer portal file choosers.
 
+use crate::i18n::{tr, tr_args};
+use relm4::adw;
+use relm4::adw::prelude::{MessageDialogExt, MessageDialogExtManual};
 use relm4::gtk::glib;
 use relm4::gtk::{self, gio, prelude::*};
 use std::path::PathBuf;
 
-/// Show a file chooser dialog for selecting an AppImage file.
+/// Show a file chooser dialog for selecting one or more AppImage files.
 pub fn show_appimage_chooser<F>(parent: &impl IsA<gtk::Window>, callback: F)
 where
-    F: Fn(PathBuf) + 'static,
+    F: Fn(Vec<PathBuf>) + 'static,
 {
-    let dialog = gtk::FileChooserNative::builder()
-        .title("Select AppImage")
+    let dialog = gtk::FileDialog::builder()
+        .title(tr("Select AppImages"))
         .modal(true)
-        .transient_for(parent)
-        .action(gtk::FileChooserAction::Open)
-        .accept_label("Select")
-        .cancel_label("Cancel")
+        .accept_label(tr("Select"))
         .build();
 
     // Create filter for AppImage files
     let filter = gtk::FileFilter::new();
-    filter.set_name(Some("AppImage Files"));
+    filter.set_name(Some(tr("AppImage Files")));
     filter.add_pattern("*.AppImage");
     filter.add_pattern("*.appimage");
-    dialog.add_filter(&filter);
-    dialog.set_filter(&filter);
+    set_filter(&dialog, &filter);
 
     // Set initial folder to Downloads if it exists
     if let Some(downloads) = glib::user_special_dir(glib::UserDirectory::Downloads) {
-        let file = gio::File::for_path(&downloads);
-        let _ = dialog.set_current_folder(Some(&file));
+        dialog.set_initial_folder(Some(&gio::File::for_path(&downloads)));
     }
 
-    dialog.connect_response(move |dialog, response| {
-        if response == gtk::ResponseType::Accept {
-            if let Some(file) = dialog.file() {
-                if let Some(path) = file.path() {
-                    callback(path);
-                }
+    dialog.open_multiple(Some(parent), gio::Cancellable::NONE, move |result| {
+        if let Ok(files) = result {
+            let paths: Vec<PathBuf> = files
+                .iter::<gio::File>()
+                .flatten()
+                .filter_map(|file| file.path())
+                .collect();
+            if !paths.is_empty() {
+                callback(paths);
             }
         }
     });
-
-    dialog.show();
 }
 
 /// Show a folder chooser dialog for selecting a watch directory.
@@ -50,29 +53,173 @@ pub fn show_directory_chooser<F>(parent: &impl IsA<gtk::Window>, callback: F)
 where
     F: Fn(PathBuf) + 'static,
 {
-    let dialog = gtk::FileChooserNative::builder()
-        .title("Select Watch Directory")
+    let dialog = gtk::FileDialog::builder()
+        .title(tr("Select Watch Directory"))
         .modal(true)
-        .transient_for(parent)
-        .action(gtk::FileChooserAction::SelectFolder)
-        .accept_label("Select")
-        .cancel_label("Cancel")
+        .accept_label(tr("Select"))
         .build();
 
     // Set initial folder to home directory
-    let home = glib::home_dir();
-    let file = gio::File::for_path(&home);
-    let _ = dialog.set_current_folder(Some(&file));
-
-    dialog.connect_response(move |dialog, response| {
-        if response == gtk::ResponseType::Accept {
-            if let Some(file) = dialog.file() {
-                if let Some(path) = file.path() {
-                    callback(path);
-                }
-            }
+    dialog.set_initial_folder(Some(&gio::File::for_path(glib::home_dir())));
+
+    dialog.select_folder(Some(parent), gio::Cancellable::NONE, move |result| {
+        if let Some(path) = result.ok().and_then(|folder| folder.path()) {
+            callback(path);
         }
     });
+}
+
+/// Show a file chooser dialog for selecting a custom app icon.
+pub fn show_icon_chooser<F>(parent: &impl IsA<gtk::Window>, callback: F)
+where
+    F: Fn(PathBuf) + 'static,
+{
+    let dialog = gtk::FileDialog::builder()
+        .title(tr("Select Icon"))
+        .modal(true)
+        .accept_label(tr("Select"))
+        .build();
+
+    // Formats an icon theme can hold
+    let filter = gtk::FileFilter::new();
+    filter.set_name(Some(tr("Icons (PNG, SVG, XPM)")));
+    filter.add_suffix("png");
+    filter.add_suffix("svg");
+    filter.add_suffix("xpm");
+    set_filter(&dialog, &filter);
+
+    if let Some(pictures) = glib::user_special_dir(glib::UserDirectory::Pictures) {
+        dialog.set_initial_folder(Some(&gio::File::for_path(&pictures)));
+    }
+
+    dialog.open(Some(parent), gio::Cancellable::NONE, move |result| {
+        if let Some(path) = result.ok().and_then(|file| file.path()) {
+            callback(path);
+        }
+    });
+}
+
+/// Offer only files matching `filter` in a file dialog.
+fn set_filter(dialog: &gtk::FileDialog, filter: &gtk::FileFilter) {
+    let filters = gio::ListStore::new::<gtk::FileFilter>();
+    filters.append(filter);
+    dialog.set_filters(Some(&filters));
+    dialog.set_default_filter(Some(filter));
+}
+
+/// What the user chose when removing an integration.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RemoveChoice {
+    /// Remove the menu entry, keep the AppImage file.
+    Unintegrate,
+    /// Remove the menu entry and delete the AppImage file.
+    Delete,
+}
+
+/// Ask whether to remove an integration, and whether to delete the file too.
+///
+/// The callback is only invoked if the user did not cancel.
+pub fn show_remove_confirmation<F>(parent: &impl IsA<gtk::Window>, name: &str, callback: F)
+where
+    F: Fn(RemoveChoice) + 'static,
+{
+    let dialog = adw::MessageDialog::new(
+        Some(parent),
+        Some(&tr_args("Remove {}?", &[&name])),
+        Some(tr(
+            "The menu entry will be removed. You can also delete the AppImage file itself.",
+        )),
+    );
+    dialog.add_responses(&[
+        ("cancel", tr("Cancel")),
+        ("remove", tr("Remove Integration")),
+        ("delete", tr("Remove and Delete File")),
+    ]);
+    dialog.set_response_appearance("delete", adw::ResponseAppearance::Destructive);
+    dialog.set_default_response(Some("remove"));
+    dialog.set_close_response("cancel");
+
+    dialog.connect_response(None, move |_, response| match response {
+        "remove" => callback(RemoveChoice::Unintegrate),
+        "delete" => callback(RemoveChoice::Delete),
+        _ => {}
+    });
+
+    dialog.present();
+}
+
+/// What to do with an AppImage opened from the file manager.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OpenChoice {
+    /// Add it to the application menu.
+    Integrate,
+    /// Launch it without integrating.
+    RunOnce,
+    /// Launch it and add it to the application menu.
+    RunAndIntegrate,
+}
+
+/// Ask what to do with an AppImage opened from the file manager.
+///
+/// The callback is only invoked if the user did not cancel.
+pub fn show_open_appimage<F>(parent: &impl IsA<gtk::Window>, name: &str, callback: F)
+where
+    F: Fn(OpenChoice) + 'static,
+{
+    let dialog = adw::MessageDialog::new(
+        Some(parent),
+        Some(&tr_args("Open {}?", &[&name])),
+        Some(tr(
+            "Add it to the application menu, or just run it this time.",
+        )),
+    );
+    dialog.add_responses(&[
+        ("cancel", tr("Cancel")),
+        ("run", tr("Run Once")),
+        ("integrate", tr("Integrate")),
+        ("both", tr("Run and Integrate")),
+    ]);
+    dialog.set_response_appearance("both", adw::ResponseAppearance::Suggested);
+    dialog.set_default_response(Some("both"));
+    dialog.set_close_response("cancel");
+
+    dialog.connect_response(None, move |_, response| match response {
+        "run" => callback(OpenChoice::RunOnce),
+        "integrate" => callback(OpenChoice::Integrate),
+        "both" => callback(OpenChoice::RunAndIntegrate),
+        _ => {}
+    });
+
+    dialog.present();
+}
+
+/// Show the result of a one-shot folder scan.
+pub fn show_scan_results(
+    parent: &impl IsA<gtk::Window>,
+    folder: &std::path::Path,
+    integrated: &[String],
+    awaiting: usize,
+) {
+    let heading = match integrated.len() {
+        0 => tr("No New AppImages").to_string(),
+        1 => tr("1 AppImage Integrated").to_string(),
+        n => tr_args("{} AppImages Integrated", &[&n]),
+    };
+    let mut body = if integrated.is_empty() {
+        tr_args("Nothing new to integrate in {}.", &[&folder.display()])
+    } else {
+        integrated.join("\n")
+    };
+    if awaiting > 0 {
+        body.push_str(&format!(
+            "\n\n{} AppImage{} awaiting approval.",
+            awaiting,
+            if awaiting == 1 { "" } else { "s" }
+        ));
+    }
 
-    dialog.show();
+    let dialog = adw::MessageDialog::new(Some(parent), Some(&heading), Some(&body));
+    dialog.add_responses(&[("close", tr("Close"))]);
+    dialog.set_close_response("close");
+    dialog.present();
 }
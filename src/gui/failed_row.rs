@@ -0,0 +1,83 @@
+//! Failed integration row factory component for the app list.
+
+use crate::i18n::tr;
+use crate::state::FailedIntegration;
+use relm4::adw;
+use relm4::adw::prelude::*;
+use relm4::factory::{DynamicIndex, FactoryComponent, FactorySender};
+use relm4::gtk;
+use std::path::PathBuf;
+
+/// A single failed integration in the list.
+#[derive(Debug)]
+pub struct FailedRow {
+    /// Path to the AppImage file.
+    pub appimage_path: PathBuf,
+    /// File name shown as the title.
+    pub name: String,
+    /// Summary of the failure shown as the subtitle.
+    pub summary: String,
+}
+
+/// Output messages from the failed integration row.
+#[derive(Debug)]
+pub enum FailedRowOutput {
+    Retry(PathBuf),
+}
+
+#[relm4::factory(pub)]
+impl FactoryComponent for FailedRow {
+    type Init = FailedIntegration;
+    type Input = ();
+    type Output = FailedRowOutput;
+    type CommandOutput = ();
+    type ParentWidget = gtk::ListBox;
+
+    view! {
+        #[root]
+        adw::ActionRow {
+            set_title: &self.name,
+            set_subtitle: &self.summary,
+            set_subtitle_lines: 2,
+
+            add_prefix = &gtk::Image {
+                set_icon_name: Some("dialog-error-symbolic"),
+            },
+
+            add_suffix = &gtk::Button {
+                set_label: tr("Retry"),
+                set_valign: gtk::Align::Center,
+                set_tooltip_text: Some(tr("Try integrating again")),
+                connect_clicked[sender, path = self.appimage_path.clone()] => move |_| {
+                    sender.output(FailedRowOutput::Retry(path.clone())).unwrap();
+                },
+            },
+        }
+    }
+
+    fn init_model(info: Self::Init, _index: &DynamicIndex, _sender: FactorySender<Self>) -> Self {
+        let name = info
+            .appimage_path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_else(|| tr("Unknown").to_string());
+        let retry = if info.next_retry_at.is_some() {
+            "will retry"
+        } else {
+            "gave up"
+        };
+        let summary = format!(
+            "{} ({} attempt{}, {})",
+            info.reason,
+            info.attempts,
+            if info.attempts == 1 { "" } else { "s" },
+            retry
+        );
+
+        Self {
+            appimage_path: info.appimage_path,
+            name,
+            summary,
+        }
+    }
+}
@@ -4,11 +4,15 @@
 //! watch directories, and daemon settings using Relm4 and libadwaita.
 
 mod app;
+mod app_details;
 mod app_list_page;
 mod app_row;
 mod autostart;
+mod awaiting_row;
 mod dialogs;
+mod failed_row;
 mod settings_page;
+mod setup_assistant;
 mod status_page;
 mod watch_dir_row;
 
@@ -2,14 +2,16 @@
 
 use super::autostart;
 use super::watch_dir_row::{WatchDirRow, WatchDirRowOutput};
-use crate::config::Config;
+use crate::config::{Config, Theme};
+use crate::i18n::{tr, tr_args};
+use crate::state::State;
 use relm4::adw::prelude::*;
 use relm4::factory::{DynamicIndex, FactoryVecDeque};
 use relm4::gtk::glib;
 use relm4::gtk;
 use relm4::prelude::*;
 use relm4::{adw, ComponentParts, ComponentSender, RelmWidgetExt};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 /// The settings page model.
 pub struct SettingsPage {
@@ -44,8 +46,14 @@ pub enum SettingsPageMsg {
     ToggleAutostart(bool),
     /// Toggle scan on startup.
     ToggleScanOnStartup(bool),
+    /// Toggle launch tracking.
+    ToggleTrackLaunches(bool),
+    /// Toggle the name suffix of native duplicates.
+    ToggleSuffixDuplicates(bool),
     /// Set debounce delay.
     SetDebounceMs(f64),
+    /// Set the color scheme by menu index.
+    SetTheme(u32),
 }
 
 /// Output messages from the settings page.
@@ -71,7 +79,7 @@ impl SimpleComponent for SettingsPage {
             adw::HeaderBar {
                 #[wrap(Some)]
                 set_title_widget = &adw::WindowTitle {
-                    set_title: "Settings",
+                    set_title: tr("Settings"),
                 },
             },
 
@@ -89,14 +97,14 @@ impl SimpleComponent for SettingsPage {
 
                         // Watch Directories Section
                         adw::PreferencesGroup {
-                            set_title: "Watch Directories",
-                            set_description: Some("Directories to monitor for AppImages"),
+                            set_title: tr("Watch Directories"),
+                            set_description: Some(tr("Directories to monitor for AppImages")),
 
                             #[wrap(Some)]
                             set_header_suffix = &gtk::Button {
                                 set_icon_name: "list-add-symbolic",
                                 add_css_class: "flat",
-                                set_tooltip_text: Some("Add watch directory"),
+                                set_tooltip_text: Some(tr("Add watch directory")),
                                 connect_clicked[sender] => move |_| {
                                     sender.output(SettingsPageOutput::ShowDirectoryChooser).unwrap();
                                 },
@@ -111,12 +119,12 @@ impl SimpleComponent for SettingsPage {
 
                         // Notifications Section
                         adw::PreferencesGroup {
-                            set_title: "Notifications",
-                            set_description: Some("Desktop notification settings"),
+                            set_title: tr("Notifications"),
+                            set_description: Some(tr("Desktop notification settings")),
 
                             adw::ActionRow {
-                                set_title: "Enable Notifications",
-                                set_subtitle: "Show desktop notifications for integration events",
+                                set_title: tr("Enable Notifications"),
+                                set_subtitle: tr("Show desktop notifications for integration events"),
 
                                 add_suffix = &gtk::Switch {
                                     set_valign: gtk::Align::Center,
@@ -130,8 +138,8 @@ impl SimpleComponent for SettingsPage {
                             },
 
                             adw::ActionRow {
-                                set_title: "Notify on Integration",
-                                set_subtitle: "Show notification when an AppImage is integrated",
+                                set_title: tr("Notify on Integration"),
+                                set_subtitle: tr("Show notification when an AppImage is integrated"),
                                 #[watch]
                                 set_sensitive: model.config.notifications.enabled,
 
@@ -147,8 +155,8 @@ impl SimpleComponent for SettingsPage {
                             },
 
                             adw::ActionRow {
-                                set_title: "Notify on Removal",
-                                set_subtitle: "Show notification when integration is removed",
+                                set_title: tr("Notify on Removal"),
+                                set_subtitle: tr("Show notification when integration is removed"),
                                 #[watch]
                                 set_sensitive: model.config.notifications.enabled,
 
@@ -166,12 +174,12 @@ impl SimpleComponent for SettingsPage {
 
                         // Daemon Settings Section
                         adw::PreferencesGroup {
-                            set_title: "Daemon",
-                            set_description: Some("Daemon behavior settings"),
+                            set_title: tr("Daemon"),
+                            set_description: Some(tr("Daemon behavior settings")),
 
                             adw::ActionRow {
-                                set_title: "Scan on Startup",
-                                set_subtitle: "Integrate existing AppImages when daemon starts",
+                                set_title: tr("Scan on Startup"),
+                                set_subtitle: tr("Integrate existing AppImages when daemon starts"),
 
                                 add_suffix = &gtk::Switch {
                                     set_valign: gtk::Align::Center,
@@ -184,9 +192,39 @@ impl SimpleComponent for SettingsPage {
                                 },
                             },
 
+                            adw::ActionRow {
+                                set_title: tr("Track Launches"),
+                                set_subtitle: tr("Count launches of newly integrated apps to find unused ones"),
+
+                                add_suffix = &gtk::Switch {
+                                    set_valign: gtk::Align::Center,
+                                    #[watch]
+                                    set_active: model.config.integration.track_launches,
+                                    connect_state_set[sender] => move |_, state| {
+                                        sender.input(SettingsPageMsg::ToggleTrackLaunches(state));
+                                        glib::Propagation::Proceed
+                                    },
+                                },
+                            },
+
+                            adw::ActionRow {
+                                set_title: tr("Mark Duplicates"),
+                                set_subtitle: tr("Add \"(AppImage)\" to the menu name of apps also installed natively"),
+
+                                add_suffix = &gtk::Switch {
+                                    set_valign: gtk::Align::Center,
+                                    #[watch]
+                                    set_active: model.config.integration.suffix_duplicates,
+                                    connect_state_set[sender] => move |_, state| {
+                                        sender.input(SettingsPageMsg::ToggleSuffixDuplicates(state));
+                                        glib::Propagation::Proceed
+                                    },
+                                },
+                            },
+
                             adw::ComboRow {
-                                set_title: "Log Level",
-                                set_subtitle: "Verbosity of daemon logging",
+                                set_title: tr("Log Level"),
+                                set_subtitle: tr("Verbosity of daemon logging"),
                                 set_model: Some(&gtk::StringList::new(&["error", "warn", "info", "debug", "trace"])),
                                 #[watch]
                                 set_selected: match model.config.logging.level.as_str() {
@@ -203,8 +241,8 @@ impl SimpleComponent for SettingsPage {
                             },
 
                             adw::ActionRow {
-                                set_title: "Debounce Delay (ms)",
-                                set_subtitle: "Wait time before processing file events",
+                                set_title: tr("Debounce Delay (ms)"),
+                                set_subtitle: tr("Wait time before processing file events"),
 
                                 add_suffix = &gtk::SpinButton::with_range(100.0, 10000.0, 100.0) {
                                     set_valign: gtk::Align::Center,
@@ -219,12 +257,12 @@ impl SimpleComponent for SettingsPage {
 
                         // Autostart Section
                         adw::PreferencesGroup {
-                            set_title: "Startup",
-                            set_description: Some("Automatic startup settings"),
+                            set_title: tr("Startup"),
+                            set_description: Some(tr("Automatic startup settings")),
 
                             adw::ActionRow {
-                                set_title: "Start on Login",
-                                set_subtitle: "Automatically start daemon when you log in (XDG autostart)",
+                                set_title: tr("Start on Login"),
+                                set_subtitle: tr("Automatically start daemon when you log in (XDG autostart)"),
 
                                 add_suffix = &gtk::Switch {
                                     set_valign: gtk::Align::Center,
@@ -237,6 +275,22 @@ impl SimpleComponent for SettingsPage {
                                 },
                             },
                         },
+
+                        // Appearance Section
+                        adw::PreferencesGroup {
+                            set_title: tr("Appearance"),
+
+                            adw::ComboRow {
+                                set_title: tr("Style"),
+                                set_subtitle: tr("Color scheme of this settings app"),
+                                set_model: Some(&gtk::StringList::new(&Theme::ALL.map(Theme::label))),
+                                #[watch]
+                                set_selected: Theme::ALL.iter().position(|t| *t == model.config.gui.theme).unwrap_or(0) as u32,
+                                connect_selected_notify[sender] => move |row| {
+                                    sender.input(SettingsPageMsg::SetTheme(row.selected()));
+                                },
+                            },
+                        },
                     }
                 }
             }
@@ -340,9 +394,9 @@ impl SimpleComponent for SettingsPage {
                     Ok(()) => {
                         self.autostart_enabled = enabled;
                         let msg = if enabled {
-                            "Autostart enabled"
+                            tr("Autostart enabled")
                         } else {
-                            "Autostart disabled"
+                            tr("Autostart disabled")
                         };
                         sender
                             .output(SettingsPageOutput::ShowToast(msg.to_string()))
@@ -350,9 +404,9 @@ impl SimpleComponent for SettingsPage {
                     }
                     Err(e) => {
                         sender
-                            .output(SettingsPageOutput::ShowToast(format!(
+                            .output(SettingsPageOutput::ShowToast(tr_args(
                                 "Failed to set autostart: {}",
-                                e
+                                &[&e],
                             )))
                             .unwrap();
                         // Revert the UI toggle
@@ -364,10 +418,28 @@ impl SimpleComponent for SettingsPage {
                 self.config.integration.scan_on_startup = enabled;
                 self.save_config(&sender);
             }
+            SettingsPageMsg::ToggleTrackLaunches(enabled) => {
+                self.config.integration.track_launches = enabled;
+                self.save_config(&sender);
+            }
+            SettingsPageMsg::ToggleSuffixDuplicates(enabled) => {
+                self.config.integration.suffix_duplicates = enabled;
+                self.save_config(&sender);
+            }
             SettingsPageMsg::SetDebounceMs(ms) => {
                 self.config.watch.debounce_ms = ms as u64;
                 self.save_config(&sender);
             }
+            SettingsPageMsg::SetTheme(index) => {
+                let Some(&theme) = Theme::ALL.get(index as usize) else {
+                    return;
+                };
+                if theme != self.config.gui.theme {
+                    self.config.gui.theme = theme;
+                    Self::apply_theme(theme);
+                    self.save_config(&sender);
+                }
+            }
         }
     }
 }
@@ -375,21 +447,42 @@ impl SimpleComponent for SettingsPage {
 impl SettingsPage {
     /// Reload watch directories from config.
     fn reload_watch_dirs(&mut self) {
+        let state = State::load().unwrap_or_default();
         let mut guard = self.watch_dirs.guard();
         guard.clear();
 
         for dir in &self.config.watch.directories {
-            guard.push_back(dir.clone());
+            let expanded = shellexpand::tilde(dir);
+            let counts = state.directory_counts(Path::new(expanded.as_ref()));
+            guard.push_back((dir.clone(), counts));
         }
     }
 
+    /// Apply a color scheme to the running application.
+    pub fn apply_theme(theme: Theme) {
+        let scheme = match theme {
+            Theme::System => adw::ColorScheme::Default,
+            Theme::Light => adw::ColorScheme::ForceLight,
+            Theme::Dark => adw::ColorScheme::ForceDark,
+        };
+        adw::StyleManager::default().set_color_scheme(scheme);
+    }
+
     /// Save config to file.
     fn save_config(&self, sender: &ComponentSender<Self>) {
-        if let Err(e) = self.config.save() {
+        // The app list page saves its sort order and the details window the
+        // launch settings on their own; keep what is on disk
+        let mut config = self.config.clone();
+        if let Ok(on_disk) = Config::load() {
+            config.gui.sort = on_disk.gui.sort;
+            config.apps = on_disk.apps;
+        }
+
+        if let Err(e) = config.save() {
             sender
-                .output(SettingsPageOutput::ShowToast(format!(
+                .output(SettingsPageOutput::ShowToast(tr_args(
                     "Failed to save config: {}",
-                    e
+                    &[&e],
                 )))
                 .unwrap();
         }
@@ -0,0 +1,406 @@
+//! First-run setup assistant.
+//!
+//! Shown when no config file exists yet: picks watch directories, the
+//! integration mode and autostart, then runs the initial scan with progress.
+
+use super::autostart;
+use super::dialogs;
+use crate::config::{Config, IntegrationMode};
+use crate::daemon::Daemon;
+use crate::i18n::{tr, tr_args};
+use relm4::adw;
+use relm4::adw::prelude::*;
+use relm4::gtk;
+use relm4::prelude::*;
+use relm4::{ComponentParts, ComponentSender, RelmWidgetExt};
+use std::path::PathBuf;
+use tracing::warn;
+
+/// Directories offered in addition to the default watch directories.
+const EXTRA_SUGGESTIONS: &[&str] = &["~/AppImages", "~/Apps", "~/bin"];
+
+/// The setup assistant model.
+pub struct SetupAssistant {
+    /// Candidate watch directories and whether each is selected.
+    dirs: Vec<(String, bool)>,
+    /// List showing the candidate directories.
+    dir_list: gtk::ListBox,
+    /// Hold new AppImages for approval instead of integrating them.
+    approval: bool,
+    /// Start the daemon on login.
+    autostart: bool,
+    /// Whether the initial scan has started.
+    scanning: bool,
+    /// Whether the initial scan has finished.
+    finished: bool,
+    /// Scan progress, 0.0 to 1.0.
+    progress: f64,
+    /// Scan progress or result text.
+    status: String,
+    /// The assistant window.
+    window: adw::Window,
+}
+
+/// Messages for the setup assistant.
+#[derive(Debug)]
+pub enum SetupAssistantMsg {
+    ToggleDir(usize, bool),
+    /// Ask for another directory to watch.
+    AddDir,
+    DirSelected(PathBuf),
+    SetApproval(bool),
+    SetAutostart(bool),
+    /// Save the configuration and run the initial scan.
+    Start,
+    Close,
+}
+
+/// Progress reported by the background scan.
+#[derive(Debug)]
+pub enum ScanProgress {
+    Checking {
+        done: usize,
+        total: usize,
+        name: String,
+    },
+    Finished {
+        integrated: usize,
+        awaiting: usize,
+    },
+    Failed(String),
+}
+
+/// Output messages from the setup assistant.
+#[derive(Debug)]
+pub enum SetupAssistantOutput {
+    /// Setup finished; pages should reload.
+    Done,
+    /// Show a toast message.
+    ShowToast(String),
+}
+
+#[relm4::component(pub)]
+impl Component for SetupAssistant {
+    type Init = ();
+    type Input = SetupAssistantMsg;
+    type Output = SetupAssistantOutput;
+    type CommandOutput = ScanProgress;
+
+    view! {
+        #[root]
+        adw::Window {
+            set_title: Some(tr("Welcome to AppImage Auto")),
+            set_modal: true,
+            set_default_width: 500,
+            set_default_height: 640,
+
+            adw::ToolbarView {
+                add_top_bar = &adw::HeaderBar {
+                    #[wrap(Some)]
+                    set_title_widget = &adw::WindowTitle {
+                        set_title: tr("Setup"),
+                    },
+                },
+
+                #[wrap(Some)]
+                set_content = &gtk::Stack {
+                    set_transition_type: gtk::StackTransitionType::SlideLeft,
+                    #[watch]
+                    set_visible_child_name: if model.scanning { "scan" } else { "setup" },
+
+                    add_named[Some("setup")] = &adw::PreferencesPage {
+                        add = &adw::PreferencesGroup {
+                            set_title: tr("Watch Directories"),
+                            set_description: Some(tr("AppImages saved to these folders are picked up automatically")),
+
+                            #[wrap(Some)]
+                            set_header_suffix = &gtk::Button {
+                                set_icon_name: "list-add-symbolic",
+                                add_css_class: "flat",
+                                set_tooltip_text: Some(tr("Add directory")),
+                                connect_clicked => SetupAssistantMsg::AddDir,
+                            },
+
+                            #[local_ref]
+                            dir_list -> gtk::ListBox {
+                                set_selection_mode: gtk::SelectionMode::None,
+                                add_css_class: "boxed-list",
+                            },
+                        },
+
+                        add = &adw::PreferencesGroup {
+                            set_title: tr("New AppImages"),
+
+                            #[name(automatic_check)]
+                            gtk::CheckButton {
+                                set_label: Some(tr("Integrate automatically")),
+                                set_active: !model.approval,
+                                connect_toggled[sender] => move |check| {
+                                    if check.is_active() {
+                                        sender.input(SetupAssistantMsg::SetApproval(false));
+                                    }
+                                },
+                            },
+
+                            gtk::CheckButton {
+                                set_label: Some(tr("Ask before integrating (notify and wait for approval)")),
+                                set_group: Some(&automatic_check),
+                                set_active: model.approval,
+                                connect_toggled[sender] => move |check| {
+                                    if check.is_active() {
+                                        sender.input(SetupAssistantMsg::SetApproval(true));
+                                    }
+                                },
+                            },
+                        },
+
+                        add = &adw::PreferencesGroup {
+                            adw::SwitchRow {
+                                set_title: tr("Start on Login"),
+                                set_subtitle: tr("Run the daemon in the background after you log in"),
+                                set_active: model.autostart,
+                                connect_active_notify[sender] => move |row| {
+                                    sender.input(SetupAssistantMsg::SetAutostart(row.is_active()));
+                                },
+                            },
+                        },
+
+                        add = &adw::PreferencesGroup {
+                            gtk::Button {
+                                set_label: tr("Get Started"),
+                                set_halign: gtk::Align::Center,
+                                add_css_class: "pill",
+                                add_css_class: "suggested-action",
+                                #[watch]
+                                set_sensitive: model.dirs.iter().any(|(_, selected)| *selected),
+                                connect_clicked => SetupAssistantMsg::Start,
+                            },
+                        },
+                    },
+
+                    add_named[Some("scan")] = &gtk::Box {
+                        set_orientation: gtk::Orientation::Vertical,
+                        set_valign: gtk::Align::Center,
+                        set_spacing: 18,
+                        set_margin_all: 24,
+
+                        gtk::Label {
+                            set_label: tr("Looking for AppImages"),
+                            add_css_class: "title-2",
+                        },
+
+                        gtk::ProgressBar {
+                            #[watch]
+                            set_fraction: model.progress,
+                        },
+
+                        gtk::Label {
+                            #[watch]
+                            set_label: &model.status,
+                            set_wrap: true,
+                            set_justify: gtk::Justification::Center,
+                            add_css_class: "dim-label",
+                        },
+
+                        gtk::Button {
+                            set_label: tr("Done"),
+                            set_halign: gtk::Align::Center,
+                            add_css_class: "pill",
+                            add_css_class: "suggested-action",
+                            #[watch]
+                            set_visible: model.finished,
+                            connect_clicked => SetupAssistantMsg::Close,
+                        },
+                    },
+                },
+            },
+        }
+    }
+
+    fn init(
+        _init: Self::Init,
+        root: Self::Root,
+        sender: ComponentSender<Self>,
+    ) -> ComponentParts<Self> {
+        let defaults = Config::default().watch.directories;
+        let mut dirs: Vec<(String, bool)> = defaults.into_iter().map(|d| (d, true)).collect();
+        for extra in EXTRA_SUGGESTIONS {
+            let exists = PathBuf::from(shellexpand::tilde(extra).as_ref()).is_dir();
+            if exists {
+                dirs.push((extra.to_string(), true));
+            }
+        }
+
+        let model = Self {
+            dirs,
+            dir_list: gtk::ListBox::default(),
+            approval: false,
+            autostart: true,
+            scanning: false,
+            finished: false,
+            progress: 0.0,
+            status: String::new(),
+            window: root.clone(),
+        };
+
+        for (index, (dir, selected)) in model.dirs.iter().enumerate() {
+            model.add_dir_row(index, dir, *selected, &sender);
+        }
+
+        let dir_list = &model.dir_list;
+        let widgets = view_output!();
+
+        ComponentParts { model, widgets }
+    }
+
+    fn update(&mut self, msg: Self::Input, sender: ComponentSender<Self>, _root: &Self::Root) {
+        match msg {
+            SetupAssistantMsg::ToggleDir(index, selected) => {
+                if let Some(dir) = self.dirs.get_mut(index) {
+                    dir.1 = selected;
+                }
+            }
+            SetupAssistantMsg::AddDir => {
+                let input = sender.input_sender().clone();
+                dialogs::show_directory_chooser(&self.window, move |path| {
+                    input.emit(SetupAssistantMsg::DirSelected(path));
+                });
+            }
+            SetupAssistantMsg::DirSelected(path) => {
+                let dir = path.to_string_lossy().to_string();
+                if !self.dirs.iter().any(|(d, _)| *d == dir) {
+                    self.add_dir_row(self.dirs.len(), &dir, true, &sender);
+                    self.dirs.push((dir, true));
+                }
+            }
+            SetupAssistantMsg::SetApproval(approval) => self.approval = approval,
+            SetupAssistantMsg::SetAutostart(enabled) => self.autostart = enabled,
+            SetupAssistantMsg::Start => self.start(&sender),
+            SetupAssistantMsg::Close => {
+                self.window.close();
+                let _ = sender.output(SetupAssistantOutput::Done);
+            }
+        }
+    }
+
+    fn update_cmd(
+        &mut self,
+        progress: Self::CommandOutput,
+        _sender: ComponentSender<Self>,
+        _root: &Self::Root,
+    ) {
+        match progress {
+            ScanProgress::Checking { done, total, name } => {
+                self.progress = done as f64 / total.max(1) as f64;
+                self.status = tr_args("Checking {} ({} of {})", &[&name, &(done + 1), &total]);
+            }
+            ScanProgress::Finished {
+                integrated,
+                awaiting,
+            } => {
+                self.progress = 1.0;
+                self.finished = true;
+                self.status = match (integrated, awaiting) {
+                    (0, 0) => {
+                        "No AppImages found yet. New downloads will be picked up automatically."
+                            .to_string()
+                    }
+                    (0, a) => format!(
+                        "{} AppImage{} awaiting your approval in the Apps tab.",
+                        a,
+                        if a == 1 { "" } else { "s" }
+                    ),
+                    (i, _) => format!(
+                        "{} AppImage{} added to the application menu.",
+                        i,
+                        if i == 1 { "" } else { "s" }
+                    ),
+                };
+            }
+            ScanProgress::Failed(error) => {
+                self.finished = true;
+                self.status = tr_args("The initial scan failed: {}", &[&error]);
+            }
+        }
+    }
+}
+
+impl SetupAssistant {
+    /// Add a check row for a candidate directory.
+    fn add_dir_row(&self, index: usize, dir: &str, selected: bool, sender: &ComponentSender<Self>) {
+        let check = gtk::CheckButton::builder()
+            .active(selected)
+            .valign(gtk::Align::Center)
+            .build();
+        let input = sender.input_sender().clone();
+        check.connect_toggled(move |check| {
+            input.emit(SetupAssistantMsg::ToggleDir(index, check.is_active()));
+        });
+
+        let exists = PathBuf::from(shellexpand::tilde(dir).as_ref()).is_dir();
+        let row = adw::ActionRow::builder()
+            .title(dir)
+            .subtitle(if exists {
+                ""
+            } else {
+                "Will be watched once created"
+            })
+            .activatable_widget(&check)
+            .build();
+        row.add_prefix(&check);
+        self.dir_list.append(&row);
+    }
+
+    /// Save the configuration, set up autostart and run the initial scan.
+    fn start(&mut self, sender: &ComponentSender<Self>) {
+        let mut config = Config::default();
+        config.watch.directories = self
+            .dirs
+            .iter()
+            .filter(|(_, selected)| *selected)
+            .map(|(dir, _)| dir.clone())
+            .collect();
+        config.integration.mode = if self.approval {
+            IntegrationMode::Approval
+        } else {
+            IntegrationMode::Automatic
+        };
+        if let Err(e) = config.save() {
+            self.scanning = true;
+            self.finished = true;
+            self.status = tr_args("Failed to save config: {}", &[&e]);
+            return;
+        }
+        if let Err(e) = autostart::set_autostart(self.autostart) {
+            warn!("Failed to set autostart: {}", e);
+            let _ = sender.output(SetupAssistantOutput::ShowToast(tr_args(
+                "Failed to set autostart: {}",
+                &[&e],
+            )));
+        }
+
+        self.scanning = true;
+        self.status = tr("Starting…").to_string();
+        sender.spawn_command(move |out| {
+            let dirs = config.watch_directories();
+            let result = Daemon::with_config(config).map(|mut daemon| {
+                let integrated = daemon.scan_directories(&dirs, |done, total, path| {
+                    let name = path
+                        .file_name()
+                        .map(|n| n.to_string_lossy().to_string())
+                        .unwrap_or_default();
+                    let _ = out.send(ScanProgress::Checking { done, total, name });
+                });
+                (integrated.len(), daemon.state().awaiting().len())
+            });
+
+            let _ = out.send(match result {
+                Ok((integrated, awaiting)) => ScanProgress::Finished {
+                    integrated,
+                    awaiting,
+                },
+                Err(e) => ScanProgress::Failed(e.to_string()),
+            });
+        });
+    }
+}
@@ -1,24 +1,43 @@
 //! Status page component showing overview information.
 
+use super::watch_dir_row::counts_label;
+use crate::client::Client;
 use crate::config::Config;
-use crate::state::State;
+use crate::control::{self, ControlCommand, ControlStatus};
+use crate::flatpak;
+use crate::format;
+use crate::i18n::{tr, tr_args};
+use crate::service;
+use crate::state::{DiskUsage, IntegratedAppImage, State};
 use relm4::adw::prelude::*;
 use relm4::gtk;
 use relm4::prelude::*;
 use relm4::{adw, ComponentParts, ComponentSender, RelmWidgetExt};
 use std::path::PathBuf;
-use std::process::Command;
+use std::process::{Child, Command};
+
+/// How many of the largest apps the storage section lists
+const STORAGE_LARGEST_APPS: usize = 3;
+
+/// How often to try subscribing to a daemon that was just started
+const SUBSCRIBE_ATTEMPTS: u32 = 5;
 
 /// The status page model.
 pub struct StatusPage {
     /// Daemon running status.
     daemon_running: bool,
+    /// Daemon started by this page because there is no systemd unit.
+    daemon_child: Option<Child>,
     /// Number of integrated apps (for heading display).
     integrated_count: usize,
     /// Number of watch directories (for heading display).
     watch_dir_count: usize,
+    /// Total disk space of the integrations (for heading display).
+    storage_total: u64,
     /// ListBox for integrated app rows.
     apps_list: gtk::ListBox,
+    /// ListBox for the disk space breakdown.
+    storage_list: gtk::ListBox,
     /// ListBox for watch directory rows.
     dirs_list: gtk::ListBox,
 }
@@ -28,10 +47,18 @@ pub struct StatusPage {
 pub enum StatusPageMsg {
     /// Refresh status information.
     Refresh,
+    /// Integrated apps fetched through the client for a refresh.
+    AppsLoaded(Result<Vec<IntegratedAppImage>, String>),
     /// Navigate to apps page.
     NavigateToApps,
     /// Navigate to settings page.
     NavigateToSettings,
+    /// Start the daemon.
+    StartDaemon,
+    /// Stop the daemon.
+    StopDaemon,
+    /// Restart the daemon.
+    RestartDaemon,
 }
 
 /// Output messages from the status page.
@@ -39,6 +66,8 @@ pub enum StatusPageMsg {
 pub enum StatusPageOutput {
     /// Navigate to a page by tag.
     NavigateTo(String),
+    /// Show a toast message.
+    ShowToast(String),
 }
 
 #[relm4::component(pub)]
@@ -55,12 +84,12 @@ impl SimpleComponent for StatusPage {
             adw::HeaderBar {
                 #[wrap(Some)]
                 set_title_widget = &adw::WindowTitle {
-                    set_title: "Overview",
+                    set_title: tr("Overview"),
                 },
 
                 pack_start = &gtk::Button {
                     set_icon_name: "view-refresh-symbolic",
-                    set_tooltip_text: Some("Refresh"),
+                    set_tooltip_text: Some(tr("Refresh")),
                     connect_clicked => StatusPageMsg::Refresh,
                 },
             },
@@ -92,18 +121,50 @@ impl SimpleComponent for StatusPage {
                             },
 
                             gtk::Label {
-                                set_label: "AppImage Auto",
+                                set_label: tr("AppImage Auto"),
                                 add_css_class: "title-1",
                             },
 
                             gtk::Label {
                                 #[watch]
-                                set_label: &format!(
+                                set_label: &tr_args(
                                     "Daemon: {}",
-                                    if model.daemon_running { "Running" } else { "Stopped" }
+                                    &[&if model.daemon_running { tr("Running") } else { tr("Stopped") }],
                                 ),
                                 add_css_class: "dim-label",
                             },
+
+                            gtk::Box {
+                                set_orientation: gtk::Orientation::Horizontal,
+                                set_spacing: 6,
+                                set_halign: gtk::Align::Center,
+                                set_margin_top: 6,
+
+                                gtk::Button {
+                                    set_label: tr("Start"),
+                                    add_css_class: "suggested-action",
+                                    add_css_class: "pill",
+                                    #[watch]
+                                    set_visible: !model.daemon_running,
+                                    connect_clicked => StatusPageMsg::StartDaemon,
+                                },
+
+                                gtk::Button {
+                                    set_label: tr("Stop"),
+                                    add_css_class: "pill",
+                                    #[watch]
+                                    set_visible: model.daemon_running,
+                                    connect_clicked => StatusPageMsg::StopDaemon,
+                                },
+
+                                gtk::Button {
+                                    set_label: tr("Restart"),
+                                    add_css_class: "pill",
+                                    #[watch]
+                                    set_visible: model.daemon_running,
+                                    connect_clicked => StatusPageMsg::RestartDaemon,
+                                },
+                            },
                         },
 
                         // Integrated Apps section
@@ -116,9 +177,9 @@ impl SimpleComponent for StatusPage {
 
                                 gtk::Label {
                                     #[watch]
-                                    set_label: &format!(
+                                    set_label: &tr_args(
                                         "Integrated Apps ({})",
-                                        model.integrated_count
+                                        &[&model.integrated_count],
                                     ),
                                     set_halign: gtk::Align::Start,
                                     set_hexpand: true,
@@ -126,7 +187,7 @@ impl SimpleComponent for StatusPage {
                                 },
 
                                 gtk::Button {
-                                    set_label: "View All",
+                                    set_label: tr("View All"),
                                     add_css_class: "flat",
                                     set_valign: gtk::Align::Center,
                                     connect_clicked => StatusPageMsg::NavigateToApps,
@@ -140,6 +201,28 @@ impl SimpleComponent for StatusPage {
                             },
                         },
 
+                        // Storage section
+                        gtk::Box {
+                            set_orientation: gtk::Orientation::Vertical,
+                            set_spacing: 12,
+
+                            gtk::Label {
+                                #[watch]
+                                set_label: &tr_args(
+                                    "Storage ({})",
+                                    &[&format::human_size(model.storage_total)],
+                                ),
+                                set_halign: gtk::Align::Start,
+                                add_css_class: "heading",
+                            },
+
+                            #[local_ref]
+                            storage_list_box -> gtk::ListBox {
+                                set_selection_mode: gtk::SelectionMode::None,
+                                add_css_class: "boxed-list",
+                            },
+                        },
+
                         // Watch Directories section
                         gtk::Box {
                             set_orientation: gtk::Orientation::Vertical,
@@ -150,9 +233,9 @@ impl SimpleComponent for StatusPage {
 
                                 gtk::Label {
                                     #[watch]
-                                    set_label: &format!(
+                                    set_label: &tr_args(
                                         "Watched Directories ({})",
-                                        model.watch_dir_count
+                                        &[&model.watch_dir_count],
                                     ),
                                     set_halign: gtk::Align::Start,
                                     set_hexpand: true,
@@ -160,7 +243,7 @@ impl SimpleComponent for StatusPage {
                                 },
 
                                 gtk::Button {
-                                    set_label: "Settings",
+                                    set_label: tr("Settings"),
                                     add_css_class: "flat",
                                     set_valign: gtk::Align::Center,
                                     connect_clicked => StatusPageMsg::NavigateToSettings,
@@ -185,22 +268,28 @@ impl SimpleComponent for StatusPage {
         sender: ComponentSender<Self>,
     ) -> ComponentParts<Self> {
         let apps_list = gtk::ListBox::new();
+        let storage_list = gtk::ListBox::new();
         let dirs_list = gtk::ListBox::new();
 
         let model = Self {
             daemon_running: false,
+            daemon_child: None,
             integrated_count: 0,
             watch_dir_count: 0,
+            storage_total: 0,
             apps_list: apps_list.clone(),
+            storage_list: storage_list.clone(),
             dirs_list: dirs_list.clone(),
         };
 
         let apps_list_box = &model.apps_list;
+        let storage_list_box = &model.storage_list;
         let dirs_list_box = &model.dirs_list;
         let widgets = view_output!();
 
         // Initial refresh
         sender.input(StatusPageMsg::Refresh);
+        follow_events(&sender);
 
         ComponentParts { model, widgets }
     }
@@ -208,7 +297,10 @@ impl SimpleComponent for StatusPage {
     fn update(&mut self, msg: Self::Input, sender: ComponentSender<Self>) {
         match msg {
             StatusPageMsg::Refresh => {
-                self.refresh_status();
+                load_apps(&sender);
+            }
+            StatusPageMsg::AppsLoaded(apps) => {
+                self.refresh_status(apps);
             }
             StatusPageMsg::NavigateToApps => {
                 sender
@@ -220,17 +312,54 @@ impl SimpleComponent for StatusPage {
                     .output(StatusPageOutput::NavigateTo("settings".to_string()))
                     .unwrap();
             }
+            StatusPageMsg::StartDaemon => {
+                let result = self.start_daemon();
+                if result.is_ok() {
+                    follow_events(&sender);
+                }
+                self.report(&sender, result, tr("Daemon started"));
+            }
+            StatusPageMsg::StopDaemon => {
+                let result = self.stop_daemon();
+                self.report(&sender, result, tr("Daemon stopped"));
+            }
+            StatusPageMsg::RestartDaemon => {
+                let result = self.stop_daemon().and_then(|()| self.start_daemon());
+                if result.is_ok() {
+                    follow_events(&sender);
+                }
+                self.report(&sender, result, tr("Daemon restarted"));
+            }
         }
     }
 }
 
 impl StatusPage {
-    fn refresh_status(&mut self) {
+    fn refresh_status(&mut self, apps: Result<Vec<IntegratedAppImage>, String>) {
         clear_list(&self.apps_list);
+        clear_list(&self.storage_list);
         clear_list(&self.dirs_list);
 
+        // From the daemon, or the state file when it isn't running
+        let state = match apps {
+            Ok(apps) => {
+                let mut state = State::default();
+                for app in apps {
+                    state.add(app);
+                }
+                Some(state)
+            }
+            Err(e) => {
+                add_placeholder(
+                    &self.apps_list,
+                    &tr_args("Failed to load integrated apps: {}", &[&e]),
+                );
+                None
+            }
+        };
+
         // Load and populate integrated apps
-        if let Ok(state) = State::load() {
+        if let Some(state) = &state {
             let mut apps: Vec<_> = state.all().cloned().collect();
             apps.sort_by(|a, b| {
                 let name_a = a.name.as_deref().unwrap_or("");
@@ -241,14 +370,14 @@ impl StatusPage {
             self.integrated_count = apps.len();
 
             if apps.is_empty() {
-                add_placeholder(&self.apps_list, "No integrated apps");
+                add_placeholder(&self.apps_list, tr("No integrated apps"));
             } else {
                 for app in &apps {
                     let name = app.name.clone().unwrap_or_else(|| {
                         app.appimage_path
                             .file_name()
                             .map(|n| n.to_string_lossy().to_string())
-                            .unwrap_or_else(|| "Unknown".to_string())
+                            .unwrap_or_else(|| tr("Unknown").to_string())
                     });
                     let exists = app.appimage_path.exists();
                     let icon = if exists {
@@ -266,15 +395,16 @@ impl StatusPage {
             }
         } else {
             self.integrated_count = 0;
-            add_placeholder(&self.apps_list, "No integrated apps");
         }
 
+        self.refresh_storage(state.as_ref());
+
         // Load and populate watch directories
         if let Ok(config) = Config::load() {
             self.watch_dir_count = config.watch.directories.len();
 
             if config.watch.directories.is_empty() {
-                add_placeholder(&self.dirs_list, "No watched directories");
+                add_placeholder(&self.dirs_list, tr("No watched directories"));
             } else {
                 for dir in &config.watch.directories {
                     let expanded = shellexpand::tilde(dir);
@@ -286,10 +416,21 @@ impl StatusPage {
                         "dialog-warning-symbolic"
                     };
 
+                    let counts = state
+                        .as_ref()
+                        .map(|s| s.directory_counts(&expanded_path))
+                        .unwrap_or_default();
+
                     let row = adw::ActionRow::new();
                     row.set_title(dir);
                     if dir != expanded.as_ref() {
-                        row.set_subtitle(&expanded_path.display().to_string());
+                        row.set_subtitle(&format!(
+                            "{} — {}",
+                            expanded_path.display(),
+                            counts_label(counts)
+                        ));
+                    } else {
+                        row.set_subtitle(&counts_label(counts));
                     }
                     row.add_prefix(&gtk::Image::from_icon_name(icon));
                     self.dirs_list.append(&row);
@@ -297,11 +438,142 @@ impl StatusPage {
             }
         } else {
             self.watch_dir_count = 0;
-            add_placeholder(&self.dirs_list, "No watched directories");
+            add_placeholder(&self.dirs_list, tr("No watched directories"));
         }
 
+        // Reap a managed daemon that has exited
+        if let Some(child) = &mut self.daemon_child
+            && !matches!(child.try_wait(), Ok(None))
+        {
+            self.daemon_child = None;
+        }
         self.daemon_running = is_daemon_running();
     }
+
+    /// Show the disk space of the AppImages and their icons and desktop
+    /// entries, and the largest apps.
+    fn refresh_storage(&mut self, state: Option<&State>) {
+        let mut apps: Vec<_> = state
+            .map(|s| s.all().map(|app| (app, app.disk_usage())).collect())
+            .unwrap_or_default();
+        let total: DiskUsage = apps.iter().map(|(_, usage)| *usage).sum();
+        self.storage_total = total.total();
+
+        if apps.is_empty() {
+            add_placeholder(&self.storage_list, tr("No integrated apps"));
+            return;
+        }
+
+        for (title, bytes) in [
+            (tr("AppImages"), total.appimage),
+            (tr("Icons"), total.icons),
+            (tr("Desktop entries"), total.desktop),
+        ] {
+            let row = adw::ActionRow::new();
+            row.set_title(title);
+            row.add_suffix(&gtk::Label::new(Some(&format::human_size(bytes))));
+            self.storage_list.append(&row);
+        }
+
+        apps.sort_by_key(|(_, usage)| std::cmp::Reverse(usage.total()));
+        for (app, usage) in apps.iter().take(STORAGE_LARGEST_APPS) {
+            let row = adw::ActionRow::new();
+            row.set_title(&crate::resolve::display_name(app));
+            row.set_subtitle(&tr_args(
+                "Icons and desktop entry: {}",
+                &[&format::human_size(usage.artifacts())],
+            ));
+            row.add_prefix(&gtk::Image::from_icon_name("drive-harddisk-symbolic"));
+            row.add_suffix(&gtk::Label::new(Some(&format::human_size(usage.total()))));
+            self.storage_list.append(&row);
+        }
+    }
+
+    /// Start the systemd unit, or run the daemon as a child process when
+    /// there is no unit.
+    fn start_daemon(&mut self) -> Result<(), String> {
+        if service::unit_path().is_ok_and(|path| path.exists()) {
+            return service::start().map_err(|e| e.to_string());
+        }
+        let child = Command::new("appimage-auto")
+            .arg("daemon")
+            .spawn()
+            .map_err(|e| e.to_string())?;
+        self.daemon_child = Some(child);
+        Ok(())
+    }
+
+    /// Stop the systemd unit, the managed child process, or a daemon started
+    /// some other way (e.g. from a terminal).
+    fn stop_daemon(&mut self) -> Result<(), String> {
+        if let Some(mut child) = self.daemon_child.take() {
+            child.kill().map_err(|e| e.to_string())?;
+            child.wait().map_err(|e| e.to_string())?;
+            return Ok(());
+        }
+        if service::status().is_ok_and(|status| status.active) {
+            return service::stop().map_err(|e| e.to_string());
+        }
+
+        let status = control::send(ControlCommand::Status).map_err(|e| e.to_string())?;
+        let status: ControlStatus = serde_json::from_value(status).map_err(|e| e.to_string())?;
+        // SAFETY: kill() has no memory safety requirements
+        if unsafe { libc::kill(status.pid as libc::pid_t, libc::SIGTERM) } != 0 {
+            return Err(std::io::Error::last_os_error().to_string());
+        }
+        // Give the daemon a moment to exit before the status is refreshed
+        std::thread::sleep(std::time::Duration::from_millis(300));
+        Ok(())
+    }
+
+    /// Refresh after a daemon action and report its outcome.
+    fn report(
+        &mut self,
+        sender: &ComponentSender<Self>,
+        result: Result<(), String>,
+        success: &str,
+    ) {
+        let message = match result {
+            Ok(()) => success.to_string(),
+            Err(e) => tr_args("Failed to control the daemon: {}", &[&e]),
+        };
+        load_apps(sender);
+        sender.output(StatusPageOutput::ShowToast(message)).unwrap();
+    }
+}
+
+/// Fetch the integrated apps through the client and refresh with them
+fn load_apps(sender: &ComponentSender<StatusPage>) {
+    let input = sender.input_sender().clone();
+    relm4::spawn(async move {
+        let apps = match Client::connect().await {
+            Ok(client) => client.list().await,
+            Err(e) => Err(e),
+        };
+        input.emit(StatusPageMsg::AppsLoaded(apps.map_err(|e| e.to_string())));
+    });
+}
+
+/// Refresh whenever the running daemon reports an integration change, until
+/// it stops
+fn follow_events(sender: &ComponentSender<StatusPage>) {
+    let input = sender.input_sender().clone();
+    relm4::spawn(async move {
+        // A daemon that was just started may not be listening yet
+        for _ in 0..SUBSCRIBE_ATTEMPTS {
+            let events = match Client::connect().await {
+                Ok(client) => client.subscribe().await,
+                Err(e) => Err(e),
+            };
+            if let Ok(mut events) = events {
+                while let Ok(Some(_)) = events.next().await {
+                    input.emit(StatusPageMsg::Refresh);
+                }
+                return;
+            }
+            tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+        }
+    });
 }
 
 fn clear_list(list: &gtk::ListBox) {
@@ -320,7 +592,7 @@ fn add_placeholder(list: &gtk::ListBox, title: &str) {
 /// Check if the daemon is running.
 fn is_daemon_running() -> bool {
     // Try systemctl first
-    if let Ok(output) = Command::new("systemctl")
+    if let Ok(output) = flatpak::host_command("systemctl", &[])
         .args(["--user", "is-active", "appimage-auto"])
         .output()
     {
@@ -333,7 +605,7 @@ fn is_daemon_running() -> bool {
     }
 
     // Fall back to pgrep
-    if let Ok(output) = Command::new("pgrep")
+    if let Ok(output) = flatpak::host_command("pgrep", &[])
         .args(["-f", "appimage-auto daemon"])
         .output()
     {
@@ -1,5 +1,7 @@
 //! Watch directory row factory component.
 
+use crate::i18n::tr;
+use crate::state::DirectoryCounts;
 use relm4::adw::prelude::*;
 use relm4::factory::{DynamicIndex, FactoryComponent, FactorySender};
 use relm4::gtk;
@@ -15,6 +17,8 @@ pub struct WatchDirRow {
     pub expanded_path: PathBuf,
     /// Whether the directory exists.
     pub exists: bool,
+    /// AppImages currently in the directory.
+    pub counts: DirectoryCounts,
 }
 
 /// Output messages from the watch directory row.
@@ -25,7 +29,7 @@ pub enum WatchDirRowOutput {
 
 #[relm4::factory(pub)]
 impl FactoryComponent for WatchDirRow {
-    type Init = String;
+    type Init = (String, DirectoryCounts);
     type Input = ();
     type Output = WatchDirRowOutput;
     type CommandOutput = ();
@@ -35,7 +39,11 @@ impl FactoryComponent for WatchDirRow {
         #[root]
         adw::ActionRow {
             set_title: &self.path,
-            set_subtitle: &self.expanded_path.display().to_string(),
+            set_subtitle: &format!(
+                "{} — {}",
+                self.expanded_path.display(),
+                counts_label(self.counts)
+            ),
 
             add_prefix = &gtk::Image {
                 set_icon_name: Some(if self.exists { "folder-symbolic" } else { "dialog-warning-symbolic" }),
@@ -45,7 +53,7 @@ impl FactoryComponent for WatchDirRow {
                 set_icon_name: "user-trash-symbolic",
                 set_valign: gtk::Align::Center,
                 add_css_class: "flat",
-                set_tooltip_text: Some("Remove watch directory"),
+                set_tooltip_text: Some(tr("Remove watch directory")),
                 connect_clicked[sender, index] => move |_| {
                     sender.output(WatchDirRowOutput::Remove(index.clone())).unwrap();
                 },
@@ -53,7 +61,11 @@ impl FactoryComponent for WatchDirRow {
         }
     }
 
-    fn init_model(path: Self::Init, _index: &DynamicIndex, _sender: FactorySender<Self>) -> Self {
+    fn init_model(
+        (path, counts): Self::Init,
+        _index: &DynamicIndex,
+        _sender: FactorySender<Self>,
+    ) -> Self {
         let expanded_path = PathBuf::from(shellexpand::tilde(&path).as_ref());
         let exists = expanded_path.exists();
 
@@ -61,6 +73,19 @@ impl FactoryComponent for WatchDirRow {
             path,
             expanded_path,
             exists,
+            counts,
         }
     }
 }
+
+/// Describe directory counts, e.g. "3 integrated, 1 not integrated".
+pub fn counts_label(counts: DirectoryCounts) -> String {
+    if counts.unintegrated == 0 {
+        format!("{} integrated", counts.integrated)
+    } else {
+        format!(
+            "{} integrated, {} not integrated",
+            counts.integrated, counts.unintegrated
+        )
+    }
+}
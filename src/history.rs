@@ -0,0 +1,193 @@
+//! Persistent history of integration events.
+//!
+//! Events are appended as JSON lines next to the state file so that other
+//! processes (the CLI, the HTTP API, the GUI) can read them without talking
+//! to the daemon.
+
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+use tracing::warn;
+
+/// Maximum number of entries kept in the history file
+const MAX_ENTRIES: usize = 1000;
+
+#[derive(Error, Debug)]
+pub enum HistoryError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("JSON error: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("No data directory found")]
+    NoDataDir,
+}
+
+/// Kind of recorded event
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HistoryKind {
+    Integrated,
+    Unintegrated,
+    Moved,
+    Updated,
+}
+
+/// A single history entry
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    /// Unix timestamp of the event
+    pub timestamp: u64,
+    pub kind: HistoryKind,
+    pub identifier: String,
+    pub name: Option<String>,
+    /// AppImage path (destination path for moves)
+    pub path: PathBuf,
+}
+
+impl HistoryEntry {
+    /// Create an entry stamped with the current time
+    pub fn new(kind: HistoryKind, identifier: &str, name: Option<&str>, path: &Path) -> Self {
+        Self {
+            timestamp: crate::state::current_timestamp(),
+            kind,
+            identifier: identifier.to_string(),
+            name: name.map(str::to_string),
+            path: path.to_path_buf(),
+        }
+    }
+}
+
+/// Get the default history file path
+pub fn history_path() -> Result<PathBuf, HistoryError> {
+    let dirs =
+        directories::ProjectDirs::from("", "", "appimage-auto").ok_or(HistoryError::NoDataDir)?;
+    Ok(dirs.data_dir().join("history.jsonl"))
+}
+
+/// Append an entry to the default history file, logging (not failing) on error
+pub fn record(entry: HistoryEntry) {
+    let result = history_path().and_then(|path| record_to(&path, &entry));
+    if let Err(e) = result {
+        warn!("Failed to record history: {}", e);
+    }
+}
+
+/// Append an entry to a specific history file
+pub fn record_to(path: &Path, entry: &HistoryEntry) -> Result<(), HistoryError> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    writeln!(file, "{}", serde_json::to_string(entry)?)?;
+    drop(file);
+
+    // Keep the file bounded
+    let entries = load_from(path, usize::MAX)?;
+    if entries.len() > MAX_ENTRIES {
+        let keep = &entries[entries.len() - MAX_ENTRIES / 2..];
+        let mut content = String::new();
+        for e in keep {
+            content.push_str(&serde_json::to_string(e)?);
+            content.push('\n');
+        }
+        fs::write(path, content)?;
+    }
+
+    Ok(())
+}
+
+/// Load the most recent `limit` entries from the default history file (oldest first)
+pub fn load_recent(limit: usize) -> Result<Vec<HistoryEntry>, HistoryError> {
+    let path = history_path()?;
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    load_from(&path, limit)
+}
+
+/// Load the most recent `limit` entries from a specific file (oldest first)
+///
+/// Lines that fail to parse are skipped.
+pub fn load_from(path: &Path, limit: usize) -> Result<Vec<HistoryEntry>, HistoryError> {
+    let file = fs::File::open(path)?;
+    let mut entries: Vec<HistoryEntry> = BufReader::new(file)
+        .lines()
+        .map_while(Result::ok)
+        .filter_map(|line| serde_json::from_str(&line).ok())
+        .collect();
+
+    if entries.len() > limit {
+        entries.drain(..entries.len() - limit);
+    }
+    Ok(entries)
+}
+
+/// Rewrite the entries of `from` in a history file as entries of `to`, for
+/// an AppImage whose identifier changed. Returns how many were rewritten.
+pub fn rename_identifier(path: &Path, from: &str, to: &str) -> Result<usize, HistoryError> {
+    if !path.exists() {
+        return Ok(0);
+    }
+    let mut entries = load_from(path, usize::MAX)?;
+    let mut renamed = 0;
+    for entry in entries.iter_mut().filter(|e| e.identifier == from) {
+        entry.identifier = to.to_string();
+        renamed += 1;
+    }
+    if renamed > 0 {
+        let mut content = String::new();
+        for e in &entries {
+            content.push_str(&serde_json::to_string(e)?);
+            content.push('\n');
+        }
+        fs::write(path, content)?;
+    }
+    Ok(renamed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_record_and_load() {
+        let temp = TempDir::new().unwrap();
+        let path = temp.path().join("history.jsonl");
+
+        for i in 0..3 {
+            let entry = HistoryEntry::new(
+                HistoryKind::Integrated,
+                &format!("id{}", i),
+                Some("App"),
+                Path::new("/tmp/App.AppImage"),
+            );
+            record_to(&path, &entry).unwrap();
+        }
+
+        let all = load_from(&path, 10).unwrap();
+        assert_eq!(all.len(), 3);
+
+        let recent = load_from(&path, 2).unwrap();
+        assert_eq!(recent.len(), 2);
+        assert_eq!(recent[0].identifier, "id1");
+        assert_eq!(recent[1].identifier, "id2");
+    }
+
+    #[test]
+    fn test_history_is_bounded() {
+        let temp = TempDir::new().unwrap();
+        let path = temp.path().join("history.jsonl");
+        let entry = HistoryEntry::new(HistoryKind::Moved, "x", None, Path::new("/a"));
+
+        for _ in 0..=MAX_ENTRIES {
+            record_to(&path, &entry).unwrap();
+        }
+
+        let all = load_from(&path, usize::MAX).unwrap();
+        assert!(all.len() <= MAX_ENTRIES);
+    }
+}
@@ -0,0 +1,285 @@
+//! Local read-only HTTP status API (optional feature).
+//!
+//! Serves JSON on `127.0.0.1` only, so status bars and dashboards can show
+//! integration info without linking the crate:
+//!
+//! - `GET /status`  — counts and watched directories
+//! - `GET /list`    — all integrated AppImages
+//! - `GET /history` — recent integration events (`?limit=N`, default 50)
+//!
+//! Status and list come from the running daemon's own config and state.
+//! Requests whose `Host` header is not `127.0.0.1:<port>` or
+//! `localhost:<port>` are refused, so web pages can't read the API through
+//! DNS rebinding.
+
+use crate::config::Config;
+use crate::history;
+use crate::state::State;
+use serde_json::json;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{Ipv4Addr, TcpListener, TcpStream};
+use std::path::Path;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+use std::time::Duration;
+use tracing::{debug, info, warn};
+
+/// Default number of history entries returned
+const DEFAULT_HISTORY_LIMIT: usize = 50;
+
+/// How long to wait for the daemon loop to answer
+const REPLY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Headers read before giving up on finding `Host`
+const MAX_HEADERS: usize = 100;
+
+/// What the daemon loop is asked for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ApiQuery {
+    Status,
+    List,
+}
+
+/// A request waiting for the daemon loop to answer it from its own config
+/// and state
+pub struct ApiRequest {
+    pub query: ApiQuery,
+    reply: Sender<serde_json::Value>,
+}
+
+impl ApiRequest {
+    /// Send the answer back to the waiting client
+    pub fn respond(self, config: &Config, state: &State) {
+        let value = match self.query {
+            ApiQuery::Status => status_json(config, state),
+            ApiQuery::List => list_json(state),
+        };
+        let _ = self.reply.send(value);
+    }
+}
+
+/// Start the API server on a background thread.
+///
+/// Returns the receiving end of the request channel, to be polled by the
+/// daemon loop.
+pub fn spawn(port: u16) -> std::io::Result<Receiver<ApiRequest>> {
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, port))?;
+    let port = listener.local_addr()?.port();
+    info!("HTTP status API listening on http://127.0.0.1:{}", port);
+
+    let (tx, rx) = mpsc::channel();
+    thread::Builder::new()
+        .name("http-api".to_string())
+        .spawn(move || {
+            for stream in listener.incoming() {
+                match stream {
+                    Ok(stream) => {
+                        if let Err(e) = handle_connection(stream, port, &tx) {
+                            debug!("HTTP API connection error: {}", e);
+                        }
+                    }
+                    Err(e) => warn!("HTTP API accept failed: {}", e),
+                }
+            }
+        })?;
+    Ok(rx)
+}
+
+/// Serve a single request
+fn handle_connection(
+    mut stream: TcpStream,
+    port: u16,
+    requests: &Sender<ApiRequest>,
+) -> std::io::Result<()> {
+    stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+
+    let mut reader = BufReader::new(&stream);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    let host = read_host(&mut reader)?;
+
+    let (status, body) = match parse_request_line(&request_line) {
+        // Pages on other sites can reach 127.0.0.1 by rebinding their
+        // own host name to it, but can't change the Host they send
+        _ if !allowed_host(host.as_deref(), port) => (403, json!({ "error": "forbidden" })),
+        Some(("GET", target)) => route(target, requests),
+        Some(_) => (405, json!({ "error": "method not allowed" })),
+        None => (400, json!({ "error": "bad request" })),
+    };
+
+    let body = body.to_string();
+    let reason = match status {
+        200 => "OK",
+        400 => "Bad Request",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        503 => "Service Unavailable",
+        _ => "Internal Server Error",
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        reason,
+        body.len(),
+        body
+    )?;
+    stream.flush()
+}
+
+/// Split a request line into method and target
+fn parse_request_line(line: &str) -> Option<(&str, &str)> {
+    let mut parts = line.split_whitespace();
+    let method = parts.next()?;
+    let target = parts.next()?;
+    parts
+        .next()?
+        .starts_with("HTTP/")
+        .then_some((method, target))
+}
+
+/// Read the request headers up to the blank line, returning the `Host` one
+fn read_host(reader: &mut impl BufRead) -> std::io::Result<Option<String>> {
+    let mut host = None;
+    for _ in 0..MAX_HEADERS {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 || line.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':')
+            && name.eq_ignore_ascii_case("host")
+        {
+            host = Some(value.trim().to_string());
+        }
+    }
+    Ok(host)
+}
+
+/// Whether the `Host` header names this server on the loopback address
+fn allowed_host(host: Option<&str>, port: u16) -> bool {
+    let Some(host) = host else {
+        return false;
+    };
+    [format!("127.0.0.1:{}", port), format!("localhost:{}", port)]
+        .iter()
+        .any(|allowed| host.eq_ignore_ascii_case(allowed))
+}
+
+/// Dispatch a request target to its handler
+fn route(target: &str, requests: &Sender<ApiRequest>) -> (u16, serde_json::Value) {
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+
+    let query = match path {
+        "/status" => ApiQuery::Status,
+        "/list" => ApiQuery::List,
+        "/history" => {
+            let limit = query_param(query, "limit")
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(DEFAULT_HISTORY_LIMIT);
+            return match history_json(limit) {
+                Ok(body) => (200, body),
+                Err(e) => (500, json!({ "error": e.to_string() })),
+            };
+        }
+        _ => return (404, json!({ "error": "not found" })),
+    };
+
+    let (reply, answer) = mpsc::channel();
+    let _ = requests.send(ApiRequest { query, reply });
+    match answer.recv_timeout(REPLY_TIMEOUT) {
+        Ok(body) => (200, body),
+        Err(_) => (503, json!({ "error": "daemon did not respond" })),
+    }
+}
+
+/// Look up a query string parameter
+fn query_param<'a>(query: &'a str, key: &str) -> Option<&'a str> {
+    query
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .find(|(k, _)| *k == key)
+        .map(|(_, v)| v)
+}
+
+fn status_json(config: &Config, state: &State) -> serde_json::Value {
+    let directories: Vec<_> = config
+        .watch
+        .directories
+        .iter()
+        .map(|dir| {
+            let expanded = shellexpand::tilde(dir);
+            json!({
+                "path": dir,
+                "exists": Path::new(expanded.as_ref()).exists(),
+            })
+        })
+        .collect();
+
+    json!({
+        "version": env!("CARGO_PKG_VERSION"),
+        "integrated": state.count(),
+        "orphaned": state.find_orphaned().len(),
+        "watch_directories": directories,
+    })
+}
+
+fn list_json(state: &State) -> serde_json::Value {
+    let mut apps: Vec<_> = state.all().collect();
+    apps.sort_by_key(|a| a.name.as_deref().unwrap_or("").to_lowercase());
+    serde_json::to_value(apps).unwrap_or_default()
+}
+
+fn history_json(limit: usize) -> Result<serde_json::Value, Box<dyn std::error::Error>> {
+    Ok(serde_json::to_value(history::load_recent(limit)?)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_request_line() {
+        assert_eq!(
+            parse_request_line("GET /status HTTP/1.1\r\n"),
+            Some(("GET", "/status"))
+        );
+        assert_eq!(parse_request_line("GET /status\r\n"), None);
+        assert_eq!(parse_request_line(""), None);
+    }
+
+    #[test]
+    fn test_query_param() {
+        assert_eq!(query_param("limit=5&x=1", "limit"), Some("5"));
+        assert_eq!(query_param("x=1", "limit"), None);
+    }
+
+    #[test]
+    fn test_allowed_host() {
+        assert!(allowed_host(Some("127.0.0.1:7474"), 7474));
+        assert!(allowed_host(Some("LOCALHOST:7474"), 7474));
+        assert!(!allowed_host(Some("localhost:8080"), 7474));
+        assert!(!allowed_host(Some("evil.example:7474"), 7474));
+        assert!(!allowed_host(None, 7474));
+
+        let mut headers = "User-Agent: x\r\nhost: localhost:7474\r\n\r\nbody".as_bytes();
+        assert_eq!(
+            read_host(&mut headers).unwrap().as_deref(),
+            Some("localhost:7474")
+        );
+    }
+
+    #[test]
+    fn test_route() {
+        let (requests, queries) = mpsc::channel();
+        assert_eq!(route("/nope", &requests).0, 404);
+
+        let daemon = thread::spawn(move || {
+            let request: ApiRequest = queries.recv().unwrap();
+            assert_eq!(request.query, ApiQuery::List);
+            request.respond(&Config::default(), &State::default());
+        });
+        assert_eq!(route("/list", &requests), (200, json!([])));
+        daemon.join().unwrap();
+    }
+}
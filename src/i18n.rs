@@ -0,0 +1,284 @@
+//! Translation of user-facing strings.
+//!
+//! Translations are gettext `.po` files in `po/`, compiled into the binary so
+//! nothing has to be installed next to it. The language is picked from
+//! `LANGUAGE`, `LC_ALL`, `LC_MESSAGES` and `LANG`, like gettext does, and
+//! strings without a translation stay in English.
+//!
+//! To add a language, copy `po/appimage-auto.pot` to `po/<lang>.po`, fill in
+//! the `msgstr`s and add the file to [`CATALOGS`]. `just pot` refreshes the
+//! template after strings change.
+
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::sync::OnceLock;
+
+/// Bundled translations: language code and `.po` file contents
+const CATALOGS: &[(&str, &str)] = &[("de", include_str!("../po/de.po"))];
+
+/// Desktop entry keys that get translated variants
+const DESKTOP_KEYS: [&str; 4] = ["Name", "GenericName", "Comment", "Keywords"];
+
+/// Translations of one language, from original to translated string
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Catalog {
+    messages: HashMap<String, String>,
+}
+
+impl Catalog {
+    /// Parse the contents of a `.po` file.
+    ///
+    /// Untranslated and fuzzy entries are skipped, as is the header.
+    pub fn parse(po: &str) -> Self {
+        let mut messages = HashMap::new();
+        let mut msgid = String::new();
+        let mut msgstr = String::new();
+        let mut fuzzy = false;
+        // Which string continuation lines belong to
+        let mut target: Option<bool> = None;
+
+        let mut flush = |msgid: &mut String, msgstr: &mut String, fuzzy: &mut bool| {
+            if !msgid.is_empty() && !msgstr.is_empty() && !*fuzzy {
+                messages.insert(std::mem::take(msgid), std::mem::take(msgstr));
+            }
+            msgid.clear();
+            msgstr.clear();
+            *fuzzy = false;
+        };
+
+        for line in po.lines().map(str::trim) {
+            if let Some(flags) = line.strip_prefix("#,") {
+                flush(&mut msgid, &mut msgstr, &mut fuzzy);
+                target = None;
+                fuzzy = flags.split(',').any(|f| f.trim() == "fuzzy");
+            } else if let Some(rest) = line.strip_prefix("msgid ") {
+                if target == Some(false) {
+                    flush(&mut msgid, &mut msgstr, &mut fuzzy);
+                }
+                msgid = unquote(rest);
+                target = Some(true);
+            } else if let Some(rest) = line.strip_prefix("msgstr ") {
+                msgstr = unquote(rest);
+                target = Some(false);
+            } else if line.starts_with('"') {
+                match target {
+                    Some(true) => msgid.push_str(&unquote(line)),
+                    Some(false) => msgstr.push_str(&unquote(line)),
+                    None => {}
+                }
+            }
+        }
+        flush(&mut msgid, &mut msgstr, &mut fuzzy);
+
+        Self { messages }
+    }
+
+    /// Translation of a string, if there is one
+    pub fn get(&self, msgid: &str) -> Option<&str> {
+        self.messages.get(msgid).map(String::as_str)
+    }
+}
+
+/// Translate a string into the user's language
+pub fn tr(msgid: &'static str) -> &'static str {
+    active()
+        .and_then(|(_, catalog)| catalog.get(msgid))
+        .unwrap_or(msgid)
+}
+
+/// Translate a string and fill its `{}` placeholders with `args`, in order
+pub fn tr_args(msgid: &'static str, args: &[&dyn Display]) -> String {
+    let mut out = String::new();
+    let mut args = args.iter();
+    let mut parts = tr(msgid).split("{}");
+    if let Some(first) = parts.next() {
+        out.push_str(first);
+    }
+    for part in parts {
+        if let Some(arg) = args.next() {
+            out.push_str(&arg.to_string());
+        }
+        out.push_str(part);
+    }
+    out
+}
+
+/// Language of the translations in use, if the user's language has any
+pub fn language() -> Option<&'static str> {
+    active().map(|(lang, _)| *lang)
+}
+
+/// Add translated `Name[lang]=`, `Comment[lang]=` etc. lines for every
+/// bundled language to a desktop entry written by appimage-auto itself
+pub fn localize_desktop_entry(content: &str) -> String {
+    let catalogs = catalogs();
+    let mut out = String::with_capacity(content.len());
+    for line in content.lines() {
+        out.push_str(line);
+        out.push('\n');
+
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+        if !DESKTOP_KEYS.contains(&key) {
+            continue;
+        }
+        for (lang, catalog) in catalogs {
+            if let Some(translated) = catalog.get(value) {
+                out.push_str(&format!("{}[{}]={}\n", key, lang, translated));
+            }
+        }
+    }
+    out
+}
+
+/// Candidate languages from the environment, most preferred first, e.g.
+/// `de_AT.UTF-8` gives `de_AT` then `de`
+pub fn requested_languages(
+    language: Option<&str>,
+    lc_all: Option<&str>,
+    lc_messages: Option<&str>,
+    lang: Option<&str>,
+) -> Vec<String> {
+    // LANGUAGE is a priority list, but (as in gettext) only used with a locale
+    let locale = [lc_all, lc_messages, lang]
+        .into_iter()
+        .flatten()
+        .find(|l| !l.is_empty());
+    let Some(locale) = locale.filter(|l| *l != "C" && *l != "POSIX") else {
+        return Vec::new();
+    };
+
+    let list = language.filter(|l| !l.is_empty()).unwrap_or(locale);
+    let mut languages = Vec::new();
+    for entry in list.split(':') {
+        // Drop `.UTF-8` and `@euro`
+        let entry = entry.split(['.', '@']).next().unwrap_or("");
+        for candidate in [entry, entry.split('_').next().unwrap_or("")] {
+            if !candidate.is_empty() && !languages.iter().any(|l| l == candidate) {
+                languages.push(candidate.to_string());
+            }
+        }
+    }
+    languages
+}
+
+/// All bundled catalogs, parsed once
+fn catalogs() -> &'static [(&'static str, Catalog)] {
+    static CATALOGS_PARSED: OnceLock<Vec<(&'static str, Catalog)>> = OnceLock::new();
+    CATALOGS_PARSED.get_or_init(|| {
+        CATALOGS
+            .iter()
+            .map(|(lang, po)| (*lang, Catalog::parse(po)))
+            .collect()
+    })
+}
+
+/// The catalog for the user's language
+fn active() -> Option<&'static (&'static str, Catalog)> {
+    // Tests compare against the English strings
+    if cfg!(test) {
+        return None;
+    }
+
+    static ACTIVE: OnceLock<Option<usize>> = OnceLock::new();
+    let index = ACTIVE.get_or_init(|| {
+        let var = |name| std::env::var(name).ok();
+        let requested = requested_languages(
+            var("LANGUAGE").as_deref(),
+            var("LC_ALL").as_deref(),
+            var("LC_MESSAGES").as_deref(),
+            var("LANG").as_deref(),
+        );
+        requested
+            .iter()
+            .find_map(|lang| catalogs().iter().position(|(l, _)| l == lang))
+    });
+    index.map(|i| &catalogs()[i])
+}
+
+/// Decode a quoted `.po` string
+fn unquote(s: &str) -> String {
+    let s = s.trim();
+    let s = s.strip_prefix('"').unwrap_or(s);
+    let s = s.strip_suffix('"').unwrap_or(s);
+
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => out.push('\n'),
+            Some('t') => out.push('\t'),
+            Some(other) => out.push(other),
+            None => {}
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_po() {
+        let catalog = Catalog::parse(
+            r#"
+msgid ""
+msgstr ""
+"Content-Type: text/plain; charset=UTF-8\n"
+
+#: src/notifications.rs
+msgid "{} integrated"
+msgstr "{} integriert"
+
+msgid ""
+"Ready in application menu\n"
+"{}"
+msgstr "Bereit im Anwendungsmenü\n{}"
+
+#, fuzzy
+msgid "Settings"
+msgstr "Einstellungen?"
+
+msgid "Untranslated"
+msgstr ""
+"#,
+        );
+
+        assert_eq!(catalog.get("{} integrated"), Some("{} integriert"));
+        assert_eq!(
+            catalog.get("Ready in application menu\n{}"),
+            Some("Bereit im Anwendungsmenü\n{}")
+        );
+        assert_eq!(catalog.get("Settings"), None);
+        assert_eq!(catalog.get("Untranslated"), None);
+        assert_eq!(catalog.get(""), None);
+
+        // Every bundled catalog parses to something
+        assert!(catalogs().iter().all(|(_, c)| !c.messages.is_empty()));
+        assert_eq!(
+            tr_args("Failed to re-integrate {} of {} apps", &[&1, &"2"]),
+            "Failed to re-integrate 1 of 2 apps"
+        );
+    }
+
+    #[test]
+    fn test_requested_languages() {
+        assert_eq!(
+            requested_languages(None, None, None, Some("de_AT.UTF-8")),
+            ["de_AT", "de"]
+        );
+        assert_eq!(
+            requested_languages(Some("fr:pt_BR"), Some("en_US.UTF-8"), None, None),
+            ["fr", "pt_BR", "pt"]
+        );
+        // LC_ALL wins over LANG, and the C locale means no translation
+        assert!(requested_languages(Some("de"), Some("C"), None, Some("de_DE")).is_empty());
+        assert!(requested_languages(None, None, None, None).is_empty());
+    }
+}
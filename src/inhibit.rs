@@ -0,0 +1,65 @@
+//! Keeping the machine awake during long batches.
+//!
+//! While a large burst of AppImages is integrated (the first scan of a full
+//! directory, a mass update, a resync after suspend), the daemon takes a
+//! logind inhibitor lock through `systemd-inhibit`, so idle suspend doesn't
+//! interrupt it halfway. The lock is held for as long as the child process
+//! runs and released when the [`Inhibitor`] is dropped. Closing the lid still
+//! suspends, as logind ignores inhibitors for the lid switch by default.
+
+use std::io;
+use std::process::{Child, Command, Stdio};
+
+/// A held inhibitor lock, released on drop
+#[derive(Debug)]
+pub struct Inhibitor {
+    child: Child,
+}
+
+impl Inhibitor {
+    /// Block idle and sleep, giving `why` as the reason shown by
+    /// `systemd-inhibit --list` and desktop shutdown dialogs
+    pub fn acquire(why: &str) -> io::Result<Self> {
+        let child = command(why)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()?;
+        Ok(Self { child })
+    }
+}
+
+impl Drop for Inhibitor {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+fn command(why: &str) -> Command {
+    let mut command = Command::new("systemd-inhibit");
+    command.args([
+        "--what=idle:sleep",
+        "--mode=block",
+        "--who=appimage-auto",
+        &format!("--why={}", why),
+        "sleep",
+        "infinity",
+    ]);
+    command
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::OsStr;
+
+    #[test]
+    fn test_command() {
+        let command = command("Integrating 12 AppImages");
+        assert_eq!(command.get_program(), "systemd-inhibit");
+        let args: Vec<_> = command.get_args().collect();
+        assert!(args.contains(&OsStr::new("--why=Integrating 12 AppImages")));
+        assert_eq!(args[args.len() - 2..], ["sleep", "infinity"]);
+    }
+}
@@ -0,0 +1,97 @@
+//! Checking that the files of an integration are as they were written.
+//!
+//! The MD5 of the desktop entry, its copies and the icons is recorded in the
+//! state whenever they are written. `appimage-auto check` compares them with
+//! the files on disk to find ones edited by hand, overwritten by other tools
+//! or deleted.
+
+use crate::state::IntegratedAppImage;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// A file of an integration that is no longer as it was written
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Drift {
+    Modified(PathBuf),
+    Missing(PathBuf),
+}
+
+impl fmt::Display for Drift {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Drift::Modified(path) => write!(f, "modified: {:?}", path),
+            Drift::Missing(path) => write!(f, "missing:  {:?}", path),
+        }
+    }
+}
+
+/// MD5 of a file's contents, as a hex string
+pub fn checksum(path: &Path) -> io::Result<String> {
+    Ok(format!("{:x}", md5::compute(fs::read(path)?)))
+}
+
+/// Checksums of the desktop entry, its copies and the icons of an app, as
+/// they are on disk now
+pub fn checksums(app: &IntegratedAppImage) -> BTreeMap<PathBuf, String> {
+    std::iter::once(&app.desktop_path)
+        .chain(&app.desktop_copies)
+        .chain(&app.icon_paths)
+        .filter_map(|path| Some((path.clone(), checksum(path).ok()?)))
+        .collect()
+}
+
+/// Files whose contents differ from the recorded checksums.
+///
+/// Apps integrated before checksums were recorded have none to compare.
+pub fn check(app: &IntegratedAppImage) -> Vec<Drift> {
+    app.checksums
+        .iter()
+        .filter_map(|(path, recorded)| match checksum(path) {
+            Ok(current) if current == *recorded => None,
+            Ok(_) => Some(Drift::Modified(path.clone())),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Some(Drift::Missing(path.clone())),
+            Err(_) => Some(Drift::Modified(path.clone())),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::create_entry;
+
+    #[test]
+    fn test_check() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let desktop = temp.path().join("appimage-0123.desktop");
+        let icon = temp.path().join("appimage-0123.png");
+        fs::write(&desktop, "[Desktop Entry]\nName=App\n").unwrap();
+        fs::write(&icon, b"png").unwrap();
+
+        let mut app = create_entry(
+            "0123".to_string(),
+            PathBuf::from("/apps/App.AppImage"),
+            desktop.clone(),
+            vec![icon.clone()],
+            None,
+        );
+        assert!(check(&app).is_empty());
+        app.checksums = checksums(&app);
+        assert_eq!(app.checksums.len(), 2);
+        assert!(check(&app).is_empty());
+
+        fs::write(&desktop, "[Desktop Entry]\nName=Edited\n").unwrap();
+        fs::remove_file(&icon).unwrap();
+        assert_eq!(
+            check(&app),
+            [Drift::Modified(desktop.clone()), Drift::Missing(icon)]
+        );
+
+        // Adopting the changes records the files as they are now
+        app.checksums = checksums(&app);
+        assert!(check(&app).is_empty());
+    }
+}
@@ -3,17 +3,58 @@
 //! This library provides the core functionality for automatically integrating
 //! AppImages into the Linux desktop environment.
 
+pub mod apparmor;
 pub mod appimage;
+pub mod appstream;
+pub mod archive;
+pub mod audit;
+pub mod autostart;
+pub mod backup;
+pub mod client;
+pub mod compare;
+pub mod completeness;
 pub mod config;
+pub mod control;
 pub mod daemon;
 pub mod desktop;
+pub mod disk_space;
+pub mod duplicates;
+pub mod exit_code;
+pub mod export;
+pub mod extraction;
+pub mod flatpak;
+pub mod format;
+pub mod history;
+pub mod i18n;
+pub mod inhibit;
+pub mod integrity;
+pub mod links;
+pub mod logs;
+pub mod maintenance;
+pub mod migrate;
 pub mod notifications;
+pub mod overrides;
+pub mod replay;
+pub mod resolve;
+pub mod service;
+pub mod setup;
+pub mod signature;
+pub mod squashfs;
 pub mod state;
 pub mod watcher;
 
 #[cfg(feature = "gui")]
 pub mod gui;
 
+#[cfg(feature = "http-api")]
+pub mod http_api;
+
+#[cfg(feature = "python")]
+pub mod python;
+
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+
 pub use config::Config;
 pub use daemon::Daemon;
 pub use state::State;
@@ -0,0 +1,128 @@
+//! Launch symlinks for integrated AppImages.
+//!
+//! With `integration.link_dir` set, each integrated AppImage gets a symlink
+//! named after the app in that directory, so it can be started from a shell
+//! (e.g. `~/.local/bin/obsidian`) where there is no application menu.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use tracing::{debug, info, warn};
+
+/// Command name for an app: its name (or file name without version and
+/// extension) lowercased, with spaces as dashes
+pub fn link_name(name: Option<&str>, appimage_path: &Path) -> Option<String> {
+    let stem = appimage_path.file_stem()?.to_string_lossy();
+    let base = match name.filter(|n| !n.trim().is_empty()) {
+        Some(name) => name.to_string(),
+        // App-1.2.3-x86_64 -> App
+        None => stem
+            .split(['-', '_'])
+            .take_while(|part| !part.starts_with(|c: char| c.is_ascii_digit()))
+            .collect::<Vec<_>>()
+            .join("-"),
+    };
+
+    let command: String = base
+        .trim()
+        .to_lowercase()
+        .chars()
+        .map(|c| if c.is_whitespace() { '-' } else { c })
+        .filter(|c| c.is_alphanumeric() || matches!(c, '-' | '_' | '.'))
+        .collect();
+    (!command.is_empty() && !command.starts_with('.')).then_some(command)
+}
+
+/// Create (or update) a launch symlink in `dir` pointing at the AppImage.
+///
+/// Existing files that aren't symlinks are left alone; returns the path of
+/// the symlink if one is in place.
+pub fn install(dir: &Path, name: &str, appimage_path: &Path) -> io::Result<Option<PathBuf>> {
+    fs::create_dir_all(dir)?;
+    let link = dir.join(name);
+
+    match fs::symlink_metadata(&link) {
+        Ok(metadata) if !metadata.file_type().is_symlink() => {
+            warn!("Not replacing {:?} with a launch symlink", link);
+            return Ok(None);
+        }
+        Ok(_) => {
+            if fs::read_link(&link)? == appimage_path {
+                return Ok(Some(link));
+            }
+            fs::remove_file(&link)?;
+        }
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+        Err(e) => return Err(e),
+    }
+
+    std::os::unix::fs::symlink(appimage_path, &link)?;
+    info!("Created launch symlink {:?} -> {:?}", link, appimage_path);
+    Ok(Some(link))
+}
+
+/// Remove a launch symlink, unless it has been replaced by something else
+/// (e.g. a link to another app of the same name)
+pub fn remove(link: &Path, appimage_path: &Path) -> io::Result<()> {
+    match fs::symlink_metadata(link) {
+        Ok(metadata)
+            if metadata.file_type().is_symlink() && fs::read_link(link)? == appimage_path =>
+        {
+            fs::remove_file(link)?;
+            debug!("Removed launch symlink {:?}", link);
+            Ok(())
+        }
+        Ok(_) => Ok(()),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_link_name() {
+        let path = Path::new("/apps/Obsidian-1.5.3-x86_64.AppImage");
+        assert_eq!(
+            link_name(Some("Obsidian"), path).as_deref(),
+            Some("obsidian")
+        );
+        assert_eq!(
+            link_name(Some("Visual Studio Code"), path).as_deref(),
+            Some("visual-studio-code")
+        );
+        assert_eq!(link_name(None, path).as_deref(), Some("obsidian"));
+        assert_eq!(
+            link_name(Some("  "), Path::new("/apps/Krita_5.2.AppImage")).as_deref(),
+            Some("krita")
+        );
+        assert_eq!(link_name(Some("../.."), path), None);
+    }
+
+    #[test]
+    fn test_install_and_remove() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let bin = temp_dir.path().join("bin");
+        let first = temp_dir.path().join("App-1.AppImage");
+        let second = temp_dir.path().join("App-2.AppImage");
+
+        let link = install(&bin, "app", &first).unwrap().unwrap();
+        assert_eq!(fs::read_link(&link).unwrap(), first);
+        install(&bin, "app", &second).unwrap();
+        assert_eq!(fs::read_link(&link).unwrap(), second);
+
+        // Taken over by the second app
+        remove(&link, &first).unwrap();
+        assert!(fs::symlink_metadata(&link).is_ok());
+        remove(&link, &second).unwrap();
+        assert!(fs::symlink_metadata(&link).is_err());
+
+        // Files of the user's own are kept
+        fs::write(&link, "#!/bin/sh").unwrap();
+        assert_eq!(install(&bin, "app", &first).unwrap(), None);
+        remove(&link, &first).unwrap();
+        assert!(link.is_file());
+    }
+}
@@ -0,0 +1,361 @@
+//! Finding and reading the daemon's log.
+//!
+//! The daemon logs to stderr, which ends up in the user journal when it runs
+//! as a systemd unit or is started by the desktop session, and also to
+//! `logging.file` when one is configured. Log lines start with a UTC
+//! timestamp and the level, e.g. `2024-05-01T12:00:00.123456Z  INFO ...`.
+
+use crate::config::Config;
+use crate::flatpak;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::PathBuf;
+use std::process::Stdio;
+use std::time::Duration;
+use thiserror::Error;
+
+/// Identifier the daemon's lines carry in the journal (its binary name)
+pub const SYSLOG_IDENTIFIER: &str = "appimage-auto";
+
+/// How often a followed log file is checked for new lines
+const FOLLOW_INTERVAL: Duration = Duration::from_millis(500);
+
+#[derive(Error, Debug)]
+pub enum LogsError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("journalctl not found; set logging.file in the config to log to a file")]
+    NoJournal,
+    #[error("Invalid time {0:?}; use e.g. \"30m\", \"2h\", \"1d\" or \"2024-05-01 14:30\"")]
+    InvalidSince(String),
+}
+
+/// Where the daemon's log ends up
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LogSource {
+    /// The user journal
+    Journal,
+    /// `logging.file`
+    File(PathBuf),
+}
+
+impl LogSource {
+    /// Log destination configured in `config`
+    pub fn from_config(config: &Config) -> Self {
+        match &config.logging.file {
+            Some(file) => Self::File(PathBuf::from(shellexpand::tilde(file).as_ref())),
+            None => Self::Journal,
+        }
+    }
+}
+
+/// Print the log through `emit`, line by line, optionally only from `since`
+/// (seconds since the epoch) and waiting for new lines if `follow` is set
+pub fn stream(
+    source: &LogSource,
+    since: Option<u64>,
+    follow: bool,
+    emit: impl FnMut(&str),
+) -> Result<(), LogsError> {
+    match source {
+        LogSource::Journal => stream_journal(since, follow, emit),
+        LogSource::File(path) => stream_file(path, since, follow, emit),
+    }
+}
+
+/// Parse a `--since` value: a duration back from `now` ("90s", "30m", "2h",
+/// "1d", "1w") or a local date and time ("2024-05-01", "2024-05-01 14:30")
+pub fn parse_since(spec: &str, now: u64) -> Result<u64, LogsError> {
+    let invalid = || LogsError::InvalidSince(spec.to_string());
+    let spec = spec.trim();
+
+    let digits = spec
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(spec.len());
+    if digits > 0 && !spec[digits..].contains('-') {
+        let count: u64 = spec[..digits].parse().map_err(|_| invalid())?;
+        let unit = match spec[digits..].trim() {
+            "s" | "sec" => 1,
+            "m" | "min" => 60,
+            "h" => 3600,
+            "d" => 86400,
+            "w" => 7 * 86400,
+            _ => return Err(invalid()),
+        };
+        return Ok(now.saturating_sub(count.saturating_mul(unit)));
+    }
+
+    let (date, time) = spec.split_once([' ', 'T']).unwrap_or((spec, "00:00"));
+    let date: Vec<i32> = date
+        .split('-')
+        .map(str::parse)
+        .collect::<Result<_, _>>()
+        .map_err(|_| invalid())?;
+    let time: Vec<i32> = time
+        .split(':')
+        .map(str::parse)
+        .collect::<Result<_, _>>()
+        .map_err(|_| invalid())?;
+    match (date.as_slice(), time.as_slice()) {
+        (&[year, month, day], &[hour, minute]) => {
+            local_timestamp(year, month, day, hour, minute, 0)
+        }
+        (&[year, month, day], &[hour, minute, second]) => {
+            local_timestamp(year, month, day, hour, minute, second)
+        }
+        _ => None,
+    }
+    .ok_or_else(invalid)
+}
+
+/// Color the level of a log line for a terminal, replacing any colors it
+/// was logged with
+pub fn colorize(line: &str) -> String {
+    let line = strip_ansi(line);
+    let level = line.split_whitespace().take(3).find_map(|word| {
+        let color = match word {
+            "ERROR" => "31",
+            "WARN" => "33",
+            "INFO" => "32",
+            "DEBUG" => "34",
+            "TRACE" => "35",
+            _ => return None,
+        };
+        Some((word, color))
+    });
+    match level {
+        Some((word, color)) => {
+            let at = line.find(word).unwrap_or_default();
+            format!(
+                "{}\x1b[{}m{}\x1b[0m{}",
+                &line[..at],
+                color,
+                word,
+                &line[at + word.len()..]
+            )
+        }
+        None => line,
+    }
+}
+
+/// Seconds since the epoch as `YYYY-MM-DDTHH:MM:SS` in UTC, the start of
+/// the daemon's log lines
+pub fn utc_timestamp(secs: u64) -> String {
+    let days = (secs / 86400) as i64;
+    let rest = secs % 86400;
+
+    // Civil date from days since 1970-01-01 (Howard Hinnant's algorithm)
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
+        year,
+        month,
+        day,
+        rest / 3600,
+        rest % 3600 / 60,
+        rest % 60
+    )
+}
+
+/// Stream the daemon's lines from the user journal
+fn stream_journal(
+    since: Option<u64>,
+    follow: bool,
+    mut emit: impl FnMut(&str),
+) -> Result<(), LogsError> {
+    let mut command = flatpak::host_command("journalctl", &[]);
+    command.args([
+        "--user",
+        "--identifier",
+        SYSLOG_IDENTIFIER,
+        "--output",
+        "cat",
+        "--no-pager",
+    ]);
+    if let Some(since) = since {
+        command.arg(format!("--since=@{}", since));
+    }
+    if follow {
+        command.arg("--follow");
+    }
+
+    let mut child = match command.stdout(Stdio::piped()).spawn() {
+        Ok(child) => child,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Err(LogsError::NoJournal),
+        Err(e) => return Err(e.into()),
+    };
+    if let Some(stdout) = child.stdout.take() {
+        for line in BufReader::new(stdout).lines() {
+            emit(&line?);
+        }
+    }
+    child.wait()?;
+    Ok(())
+}
+
+/// Stream a log file, waiting for lines appended to it (or it being
+/// truncated by log rotation) when following
+fn stream_file(
+    path: &std::path::Path,
+    since: Option<u64>,
+    follow: bool,
+    mut emit: impl FnMut(&str),
+) -> Result<(), LogsError> {
+    let since = since.map(utc_timestamp);
+    // Lines without a timestamp continue the previous one
+    let mut showing = since.is_none();
+    let mut reader = BufReader::new(File::open(path)?);
+    let mut position = 0u64;
+    let mut line = String::new();
+
+    loop {
+        let read = reader.read_line(&mut line)?;
+        position += read as u64;
+        if line.ends_with('\n') || (read == 0 && !line.is_empty() && !follow) {
+            if let Some(since) = &since
+                && let Some(stamp) = line.get(..19)
+                && stamp.as_bytes()[10] == b'T'
+            {
+                showing = stamp >= since.as_str();
+            }
+            if showing {
+                emit(line.trim_end_matches('\n'));
+            }
+            line.clear();
+            continue;
+        }
+        if read > 0 {
+            // The rest of a partly written line
+            continue;
+        }
+        if !follow {
+            return Ok(());
+        }
+
+        std::thread::sleep(FOLLOW_INTERVAL);
+        if std::fs::metadata(path).is_ok_and(|metadata| metadata.len() < position) {
+            reader = BufReader::new(File::open(path)?);
+            position = 0;
+            line.clear();
+        }
+    }
+}
+
+/// Remove terminal escape sequences from a line
+fn strip_ansi(line: &str) -> String {
+    let mut stripped = String::with_capacity(line.len());
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            // CSI sequences end with a letter
+            for c in chars.by_ref() {
+                if c.is_ascii_alphabetic() {
+                    break;
+                }
+            }
+        } else {
+            stripped.push(c);
+        }
+    }
+    stripped
+}
+
+/// Seconds since the epoch of a local date and time
+fn local_timestamp(
+    year: i32,
+    month: i32,
+    day: i32,
+    hour: i32,
+    minute: i32,
+    second: i32,
+) -> Option<u64> {
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) || !(0..24).contains(&hour) {
+        return None;
+    }
+    // SAFETY: tm is plain data; mktime only reads and normalizes it
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+    tm.tm_year = year - 1900;
+    tm.tm_mon = month - 1;
+    tm.tm_mday = day;
+    tm.tm_hour = hour;
+    tm.tm_min = minute;
+    tm.tm_sec = second;
+    tm.tm_isdst = -1;
+    let secs = unsafe { libc::mktime(&mut tm) };
+    u64::try_from(secs).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_since() {
+        let now = 1_700_000_000;
+        assert_eq!(parse_since("90s", now).unwrap(), now - 90);
+        assert_eq!(parse_since("30m", now).unwrap(), now - 1800);
+        assert_eq!(parse_since("2h", now).unwrap(), now - 7200);
+        assert_eq!(parse_since("1d", now).unwrap(), now - 86400);
+        assert!(parse_since("2024-05-01 14:30", now).is_ok());
+        assert!(parse_since("2024-05-01", now).is_ok());
+        assert!(parse_since("soon", now).is_err());
+        assert!(parse_since("2y", now).is_err());
+        assert!(parse_since("2024-13-01", now).is_err());
+
+        assert_eq!(utc_timestamp(0), "1970-01-01T00:00:00");
+        assert_eq!(utc_timestamp(1_700_000_000), "2023-11-14T22:13:20");
+    }
+
+    #[test]
+    fn test_colorize() {
+        assert_eq!(
+            colorize("2024-05-01T12:00:00.000000Z  WARN Failed to install icon"),
+            "2024-05-01T12:00:00.000000Z  \x1b[33mWARN\x1b[0m Failed to install icon"
+        );
+        assert_eq!(
+            colorize("2024-05-01T12:00:00.000000Z \x1b[32m INFO\x1b[0m Integrating"),
+            "2024-05-01T12:00:00.000000Z  \x1b[32mINFO\x1b[0m Integrating"
+        );
+        assert_eq!(
+            colorize("    at src/daemon.rs:42"),
+            "    at src/daemon.rs:42"
+        );
+    }
+
+    #[test]
+    fn test_stream_file_since() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("daemon.log");
+        std::fs::write(
+            &path,
+            "2023-11-14T22:00:00.000000Z  INFO old\n\
+             2023-11-14T22:13:20.000000Z  INFO new\n\
+             continued\n\
+             2023-11-14T22:20:00.000000Z ERROR newer",
+        )
+        .unwrap();
+
+        let mut lines = Vec::new();
+        stream(&LogSource::File(path), Some(1_700_000_000), false, |line| {
+            lines.push(line.to_string())
+        })
+        .unwrap();
+        assert_eq!(
+            lines,
+            [
+                "2023-11-14T22:13:20.000000Z  INFO new",
+                "continued",
+                "2023-11-14T22:20:00.000000Z ERROR newer"
+            ]
+        );
+    }
+}
@@ -0,0 +1,326 @@
+//! Maintenance of installed integration files.
+//!
+//! Finds desktop entries and icons named after an AppImage identifier
+//! (`appimage-<md5>.desktop`, `appimage-<md5>.png`, ...) that the state no
+//! longer knows about, e.g. after a crash or from older versions, and
+//! rebuilds a lost state file from the installed desktop entries.
+
+use crate::appimage;
+use crate::desktop::DesktopEntry;
+use crate::state::{self, State};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+use tracing::{debug, info};
+
+/// Length of an AppImage identifier (MD5 hex digest)
+const IDENTIFIER_LEN: usize = 32;
+
+/// Integration files not referenced by the state
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Leftovers {
+    pub desktop_files: Vec<PathBuf>,
+    pub icons: Vec<PathBuf>,
+}
+
+impl Leftovers {
+    /// Whether nothing was found
+    pub fn is_empty(&self) -> bool {
+        self.desktop_files.is_empty() && self.icons.is_empty()
+    }
+
+    /// Number of files found
+    pub fn len(&self) -> usize {
+        self.desktop_files.len() + self.icons.len()
+    }
+
+    /// All files found, desktop entries first
+    pub fn paths(&self) -> impl Iterator<Item = &PathBuf> {
+        self.desktop_files.iter().chain(&self.icons)
+    }
+}
+
+/// Find desktop entries and icons for identifiers that are not in the state.
+///
+/// Files of integrated AppImages are kept even if the state no longer lists
+/// that exact path, so only whole integrations that were lost are reported.
+/// Icons are looked for in `<icon_dir>/<size>/apps` of each icon directory.
+pub fn find_leftovers(
+    state: &State,
+    desktop_dirs: &[PathBuf],
+    icon_dirs: &[PathBuf],
+) -> io::Result<Leftovers> {
+    let unknown = |path: &Path| {
+        path.file_name()
+            .and_then(|name| leftover_identifier(&name.to_string_lossy()))
+            .is_some_and(|id| state.get(&id).is_none())
+    };
+
+    let mut leftovers = Leftovers::default();
+    for dir in desktop_dirs {
+        leftovers.desktop_files.extend(
+            files_in(dir)?.into_iter().filter(|path| {
+                path.extension().is_some_and(|ext| ext == "desktop") && unknown(path)
+            }),
+        );
+    }
+
+    for dir in icon_dirs {
+        leftovers.icons.extend(
+            installed_icons(dir)?
+                .into_iter()
+                .filter(|(id, _)| state.get(id).is_none())
+                .map(|(_, path)| path),
+        );
+    }
+
+    Ok(leftovers)
+}
+
+/// Reconstruct a state from the desktop entries in `desktop_dir`.
+///
+/// Every entry carrying `X-AppImage-Identifier` becomes an integration, with
+/// the AppImage path taken from `TryExec` (or the quoted `Exec` program), its
+/// icons found by identifier in `icon_dir`, and the entry's modification time
+/// as integration time. Pins, timings and failures cannot be recovered.
+pub fn rebuild_state(desktop_dir: &Path, icon_dir: &Path) -> io::Result<State> {
+    let icons = installed_icons(icon_dir)?;
+    let mut state = State::default();
+
+    for path in files_in(desktop_dir)? {
+        if path.extension().is_none_or(|ext| ext != "desktop") {
+            continue;
+        }
+        let entry = match DesktopEntry::parse(&path) {
+            Ok(entry) => entry,
+            Err(e) => {
+                debug!("Skipping {:?}: {}", path, e);
+                continue;
+            }
+        };
+        let (Some(identifier), Some(appimage_path)) =
+            (entry.appimage_identifier(), appimage_path(&entry))
+        else {
+            continue;
+        };
+
+        let modified = fs::metadata(&path)
+            .and_then(|m| m.modified())
+            .ok()
+            .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+            .map_or_else(state::current_timestamp, |d| d.as_secs());
+        let icon_paths = icons
+            .iter()
+            .filter(|(id, _)| id == identifier)
+            .map(|(_, icon)| icon.clone())
+            .collect();
+
+        let mut info = state::create_entry(
+            identifier.to_string(),
+            appimage_path,
+            path.clone(),
+            icon_paths,
+            entry.name().map(str::to_string),
+        );
+        info.integrated_at = modified;
+        info.updated_at = modified;
+        info.version = entry.version().map(str::to_string);
+        // Without a hash the next refresh would treat the file as updated
+        info.content_hash = appimage::content_hash(&info.appimage_path).ok();
+        let origin = appimage::download_origin(&info.appimage_path);
+        info.origin_url = origin.url;
+        info.referrer_url = origin.referrer;
+
+        info!("Recovered {:?} from {:?}", info.appimage_path, path);
+        state.add(info);
+    }
+
+    Ok(state)
+}
+
+/// Remove leftover files, returning the paths that could not be removed
+pub fn remove_leftovers(leftovers: &Leftovers) -> Vec<(PathBuf, io::Error)> {
+    let mut failed = Vec::new();
+    for path in leftovers.paths() {
+        match fs::remove_file(path) {
+            Ok(()) => info!("Removed leftover {:?}", path),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => failed.push((path.clone(), e)),
+        }
+    }
+    failed
+}
+
+/// Path of the AppImage an installed desktop entry launches
+fn appimage_path(entry: &DesktopEntry) -> Option<PathBuf> {
+    if let Some(try_exec) = entry.entries.get("TryExec") {
+        return Some(PathBuf::from(try_exec));
+    }
+    // Exec is written as `"<path>" <args>`
+    let exec = entry.exec()?.strip_prefix('"')?;
+    let (path, _args) = exec.split_once('"')?;
+    Some(PathBuf::from(path))
+}
+
+/// Icons named after an identifier in `<icon_dir>/<size>/apps`, with their identifiers
+fn installed_icons(icon_dir: &Path) -> io::Result<Vec<(String, PathBuf)>> {
+    let mut icons = Vec::new();
+    for size_dir in files_in(icon_dir)? {
+        let apps = size_dir.join("apps");
+        if !apps.is_dir() {
+            continue;
+        }
+        for path in files_in(&apps)? {
+            let id = path
+                .file_name()
+                .and_then(|name| leftover_identifier(&name.to_string_lossy()));
+            if let Some(id) = id {
+                icons.push((id, path));
+            }
+        }
+    }
+    icons.sort_by(|a, b| a.1.cmp(&b.1));
+    Ok(icons)
+}
+
+/// Extract the identifier from an `appimage-<identifier>.<ext>` file name
+fn leftover_identifier(file_name: &str) -> Option<String> {
+    let (stem, _ext) = file_name.strip_prefix("appimage-")?.split_once('.')?;
+    (stem.len() == IDENTIFIER_LEN && stem.chars().all(|c| c.is_ascii_hexdigit()))
+        .then(|| stem.to_string())
+}
+
+/// Sorted entries of a directory (empty if it does not exist)
+fn files_in(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+    let mut paths: Vec<PathBuf> = entries.filter_map(|e| e.ok()).map(|e| e.path()).collect();
+    paths.sort();
+    Ok(paths)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::create_entry;
+
+    #[test]
+    fn test_find_and_remove_leftovers() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let desktop_dir = temp.path().join("applications");
+        let copies_dir = temp.path().join("menu");
+        let desktop_dirs = [desktop_dir.clone(), copies_dir.clone()];
+        let icon_dir = temp.path().join("icons");
+        let theme_dir = temp.path().join("theme");
+        let icon_dirs = [icon_dir.clone(), theme_dir.clone()];
+        let apps_256 = icon_dir.join("256x256/apps");
+        let theme_48 = theme_dir.join("48x48/apps");
+        fs::create_dir_all(&desktop_dir).unwrap();
+        fs::create_dir_all(&copies_dir).unwrap();
+        fs::create_dir_all(&apps_256).unwrap();
+        fs::create_dir_all(&theme_48).unwrap();
+
+        let known = "0123456789abcdef0123456789abcdef";
+        let lost = "fedcba9876543210fedcba9876543210";
+        let mut state = State::default();
+        state.add(create_entry(
+            known.to_string(),
+            PathBuf::from("/apps/Known.AppImage"),
+            desktop_dir.join(format!("appimage-{}.desktop", known)),
+            vec![],
+            None,
+        ));
+
+        for file in [
+            desktop_dir.join(format!("appimage-{}.desktop", known)),
+            desktop_dir.join(format!("appimage-{}.desktop", lost)),
+            // Not ours, despite the prefix
+            desktop_dir.join("appimage-auto-gui.desktop"),
+            desktop_dir.join("firefox.desktop"),
+            copies_dir.join(format!("appimage-{}.desktop", known)),
+            copies_dir.join(format!("appimage-{}.desktop", lost)),
+            apps_256.join(format!("appimage-{}.png", known)),
+            apps_256.join(format!("appimage-{}.png", lost)),
+            apps_256.join("appimage-auto.png"),
+            theme_48.join(format!("appimage-{}.png", known)),
+            theme_48.join(format!("appimage-{}.png", lost)),
+        ] {
+            fs::write(file, b"").unwrap();
+        }
+
+        let leftovers = find_leftovers(&state, &desktop_dirs, &icon_dirs).unwrap();
+        assert_eq!(
+            leftovers,
+            Leftovers {
+                desktop_files: vec![
+                    desktop_dir.join(format!("appimage-{}.desktop", lost)),
+                    copies_dir.join(format!("appimage-{}.desktop", lost)),
+                ],
+                icons: vec![
+                    apps_256.join(format!("appimage-{}.png", lost)),
+                    theme_48.join(format!("appimage-{}.png", lost)),
+                ],
+            }
+        );
+
+        assert!(remove_leftovers(&leftovers).is_empty());
+        assert!(
+            find_leftovers(&state, &desktop_dirs, &icon_dirs)
+                .unwrap()
+                .is_empty()
+        );
+        assert!(desktop_dir.join("appimage-auto-gui.desktop").exists());
+        assert!(apps_256.join(format!("appimage-{}.png", known)).exists());
+    }
+
+    #[test]
+    fn test_rebuild_state() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let desktop_dir = temp.path().join("applications");
+        let icon_dir = temp.path().join("icons");
+        let scalable = icon_dir.join("scalable/apps");
+        fs::create_dir_all(&desktop_dir).unwrap();
+        fs::create_dir_all(&scalable).unwrap();
+
+        let appimage = temp.path().join("Krita.AppImage");
+        fs::write(&appimage, b"krita").unwrap();
+        let id = "0123456789abcdef0123456789abcdef";
+        let icon = scalable.join(format!("appimage-{}.svg", id));
+        fs::write(&icon, b"").unwrap();
+
+        let desktop = desktop_dir.join(format!("appimage-{}.desktop", id));
+        fs::write(
+            &desktop,
+            format!(
+                "[Desktop Entry]\nType=Application\nName=Krita\nExec=\"{}\" %F\n\
+                 X-AppImage-Identifier={}\nX-AppImage-Version=5.2\n",
+                appimage.display(),
+                id
+            ),
+        )
+        .unwrap();
+        // Entries not created by appimage-auto are ignored
+        fs::write(
+            desktop_dir.join("firefox.desktop"),
+            "[Desktop Entry]\nType=Application\nName=Firefox\nExec=firefox\n",
+        )
+        .unwrap();
+
+        let state = rebuild_state(&desktop_dir, &icon_dir).unwrap();
+        assert_eq!(state.count(), 1);
+        let info = state.get(id).unwrap();
+        assert_eq!(info.appimage_path, appimage);
+        assert_eq!(info.desktop_path, desktop);
+        assert_eq!(info.icon_paths, [icon]);
+        assert_eq!(info.name.as_deref(), Some("Krita"));
+        assert_eq!(info.version.as_deref(), Some("5.2"));
+        assert_eq!(
+            info.content_hash,
+            Some(format!("{:x}", md5::compute(b"krita")))
+        );
+    }
+}
@@ -0,0 +1,322 @@
+//! Upgrading the state file and generated artifacts.
+//!
+//! The state file records the format version it was written with. When the
+//! daemon starts on a file older than [`STATE_VERSION`], the migrations
+//! between the two are applied in order: each can rewrite entries and rename
+//! or delete the desktop entries and icons they point to, for when the
+//! identifier or file name scheme changes. The old file is copied to
+//! `state.json.v<version>.bak` first, and what was changed is appended to
+//! `migrations.log` next to it.
+//!
+//! Migrations work on the loaded [`State`], so fields are still only ever
+//! added with `#[serde(default)]`, keeping old files readable. Overrides and
+//! history entries follow an AppImage whose identifier changes.
+
+use crate::history::{self, HistoryError};
+use crate::overrides::{Overrides, OverridesError};
+use crate::state::{self, IntegratedAppImage, STATE_VERSION, State, StateError, current_timestamp};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+use tracing::{info, warn};
+
+#[derive(Error, Debug)]
+pub enum MigrationError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("State error: {0}")]
+    State(#[from] StateError),
+    #[error("Overrides error: {0}")]
+    Overrides(#[from] OverridesError),
+    #[error("History error: {0}")]
+    History(#[from] HistoryError),
+}
+
+/// Files keyed by AppImage identifier, besides the state
+struct Files {
+    overrides: PathBuf,
+    history: PathBuf,
+}
+
+/// An upgrade of the state to a format version
+struct Migration {
+    /// Version the state has afterwards
+    version: u32,
+    description: &'static str,
+    run: fn(&mut State, &Files, &mut Vec<String>) -> Result<(), MigrationError>,
+}
+
+const MIGRATIONS: &[Migration] = &[Migration {
+    version: 1,
+    description: "Merge entries for the same AppImage reached through different paths",
+    run: merge_duplicates,
+}];
+
+/// What a migration run changed
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MigrationReport {
+    /// Version of the state before
+    pub from: u32,
+    /// Version of the state after
+    pub to: u32,
+    /// Copy of the state file before migrating
+    pub backup: PathBuf,
+    /// Migrations applied, with the changes each made
+    pub applied: Vec<(&'static str, Vec<String>)>,
+}
+
+impl fmt::Display for MigrationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Migrated state from version {} to {} (backup: {})",
+            self.from,
+            self.to,
+            self.backup.display()
+        )?;
+        for (description, changes) in &self.applied {
+            writeln!(f, "  {}", description)?;
+            for change in changes {
+                writeln!(f, "    {}", change)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Migrate the state file at the default location, see [`migrate_file`]
+pub fn migrate() -> Result<Option<MigrationReport>, MigrationError> {
+    migrate_file(&State::state_path()?, &Overrides::overrides_path()?)
+}
+
+/// Bring the state file at `path` and its artifacts up to [`STATE_VERSION`],
+/// along with the overrides at `overrides` and the history next to `path`.
+///
+/// Returns `None` when there was nothing to migrate.
+pub fn migrate_file(
+    path: &Path,
+    overrides: &Path,
+) -> Result<Option<MigrationReport>, MigrationError> {
+    if !path.exists() {
+        return Ok(None);
+    }
+    let mut state = State::load_from(path)?;
+    let from = state.version();
+    if from > STATE_VERSION {
+        warn!(
+            "State file {:?} is from a newer release (version {}), not migrating",
+            path, from
+        );
+        return Ok(None);
+    }
+    if from == STATE_VERSION {
+        return Ok(None);
+    }
+
+    let backup = path.with_extension(format!("json.v{}.bak", from));
+    fs::copy(path, &backup)?;
+
+    let files = Files {
+        overrides: overrides.to_path_buf(),
+        history: path.with_file_name("history.jsonl"),
+    };
+    let mut report = MigrationReport {
+        from,
+        to: STATE_VERSION,
+        backup,
+        applied: Vec::new(),
+    };
+    for migration in MIGRATIONS.iter().filter(|m| m.version > from) {
+        info!(
+            "Migrating state to version {}: {}",
+            migration.version, migration.description
+        );
+        let mut changes = Vec::new();
+        (migration.run)(&mut state, &files, &mut changes)?;
+        report.applied.push((migration.description, changes));
+    }
+    state.set_version(STATE_VERSION);
+    state.save_to(path)?;
+
+    append_log(&path.with_file_name("migrations.log"), &report)?;
+    Ok(Some(report))
+}
+
+fn append_log(log: &Path, report: &MigrationReport) -> io::Result<()> {
+    let mut file = fs::OpenOptions::new().create(true).append(true).open(log)?;
+    write!(file, "[{}] {}", current_timestamp(), report)
+}
+
+/// Version 1: paths are looked up by their normalized spelling. Older states
+/// can hold several entries for one file, integrated through a symlinked
+/// directory and directly, say; the first integrated one is kept, and the
+/// launchers and icons of the others are deleted. Their overrides move to
+/// the kept entry unless it has its own, and their history is rewritten.
+fn merge_duplicates(
+    state: &mut State,
+    files: &Files,
+    changes: &mut Vec<String>,
+) -> Result<(), MigrationError> {
+    let mut overrides = if files.overrides.exists() {
+        Overrides::load_from(&files.overrides)?
+    } else {
+        Overrides::default()
+    };
+    let mut overrides_changed = false;
+
+    let mut by_file: BTreeMap<PathBuf, Vec<IntegratedAppImage>> = BTreeMap::new();
+    for info in state.all() {
+        by_file
+            .entry(state::normalize_path(&info.appimage_path))
+            .or_default()
+            .push(info.clone());
+    }
+
+    for mut entries in by_file.into_values().filter(|entries| entries.len() > 1) {
+        entries.sort_by_key(|info| info.integrated_at);
+        let mut kept = entries.remove(0);
+        for duplicate in entries {
+            state.remove(&duplicate.identifier);
+            kept.launch_count += duplicate.launch_count;
+            kept.last_launched = kept.last_launched.max(duplicate.last_launched);
+            kept.pinned |= duplicate.pinned;
+
+            let artifacts = std::iter::once(&duplicate.desktop_path)
+                .chain(&duplicate.desktop_copies)
+                .chain(&duplicate.icon_paths)
+                .chain(&duplicate.link_path);
+            for artifact in artifacts {
+                if kept.desktop_path == *artifact || kept.icon_paths.contains(artifact) {
+                    continue;
+                }
+                match fs::remove_file(artifact) {
+                    Ok(()) => changes.push(format!("Removed {}", artifact.display())),
+                    Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+                    Err(e) => return Err(e.into()),
+                }
+            }
+            changes.push(format!(
+                "Merged {} into {}",
+                duplicate.appimage_path.display(),
+                kept.appimage_path.display()
+            ));
+
+            if overrides.rename(&duplicate.identifier, &kept.identifier) {
+                overrides_changed = true;
+                changes.push(format!(
+                    "Moved overrides of {} to {}",
+                    duplicate.identifier, kept.identifier
+                ));
+            } else if overrides.get(&duplicate.identifier).is_some() {
+                changes.push(format!(
+                    "Left overrides of {} in place, {} has its own",
+                    duplicate.identifier, kept.identifier
+                ));
+            }
+            let renamed = history::rename_identifier(
+                &files.history,
+                &duplicate.identifier,
+                &kept.identifier,
+            )?;
+            if renamed > 0 {
+                changes.push(format!(
+                    "Moved {} history entries of {} to {}",
+                    renamed, duplicate.identifier, kept.identifier
+                ));
+            }
+        }
+        state.add(kept);
+    }
+    if overrides_changed {
+        overrides.save_to(&files.overrides)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::create_entry;
+
+    #[test]
+    fn test_merge_duplicates() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let apps = temp_dir.path().join("apps");
+        let link = temp_dir.path().join("link");
+        fs::create_dir(&apps).unwrap();
+        std::os::unix::fs::symlink(&apps, &link).unwrap();
+
+        let mut state = State::default();
+        for (id, dir, integrated_at) in [("direct", &apps, 1), ("linked", &link, 2)] {
+            let desktop = temp_dir.path().join(format!("appimage-{}.desktop", id));
+            fs::write(&desktop, "[Desktop Entry]\n").unwrap();
+            let mut entry = create_entry(
+                id.to_string(),
+                dir.join("App.AppImage"),
+                desktop,
+                vec![],
+                Some("App".to_string()),
+            );
+            entry.integrated_at = integrated_at;
+            entry.launch_count = 2;
+            state.add(entry);
+        }
+        // Written before the format was versioned
+        let path = temp_dir.path().join("state.json");
+        let mut json: serde_json::Value = serde_json::to_value(&state).unwrap();
+        json.as_object_mut().unwrap().remove("version");
+        fs::write(&path, json.to_string()).unwrap();
+
+        // Saving keeps the version the file was loaded with
+        let mut loaded = State::load_from(&path).unwrap();
+        loaded.save_to(&path).unwrap();
+        assert_eq!(State::load_from(&path).unwrap().version(), 0);
+
+        let overrides_path = temp_dir.path().join("overrides.toml");
+        let mut overrides = Overrides::default();
+        overrides.set(
+            "linked",
+            crate::overrides::DesktopOverrides {
+                name: Some("Renamed".to_string()),
+                ..Default::default()
+            },
+        );
+        overrides.save_to(&overrides_path).unwrap();
+        let history_path = temp_dir.path().join("history.jsonl");
+        let integrated = history::HistoryEntry::new(
+            history::HistoryKind::Integrated,
+            "linked",
+            Some("App"),
+            &link.join("App.AppImage"),
+        );
+        history::record_to(&history_path, &integrated).unwrap();
+
+        let report = migrate_file(&path, &overrides_path).unwrap().unwrap();
+        assert_eq!((report.from, report.to), (0, STATE_VERSION));
+        assert!(report.backup.exists());
+        assert_eq!(report.applied[0].1.len(), 4);
+        assert!(report.to_string().contains("Merged "));
+        assert!(temp_dir.path().join("migrations.log").exists());
+
+        let migrated = State::load_from(&path).unwrap();
+        assert_eq!(migrated.version(), STATE_VERSION);
+        assert_eq!(migrated.count(), 1);
+        assert_eq!(migrated.get("direct").unwrap().launch_count, 4);
+        assert!(temp_dir.path().join("appimage-direct.desktop").exists());
+        assert!(!temp_dir.path().join("appimage-linked.desktop").exists());
+
+        let overrides = Overrides::load_from(&overrides_path).unwrap();
+        assert!(overrides.get("linked").is_none());
+        assert_eq!(
+            overrides.get("direct").unwrap().name.as_deref(),
+            Some("Renamed")
+        );
+        let history = history::load_from(&history_path, 10).unwrap();
+        assert_eq!(history[0].identifier, "direct");
+
+        assert_eq!(migrate_file(&path, &overrides_path).unwrap(), None);
+    }
+}
@@ -1,70 +1,775 @@
-//! Desktop notification support (optional feature).
+//! Desktop notifications.
+//!
+//! Sent with one of three backends (`notifications.backend`): the
+//! `notify-rust` crate (the `notifications` feature), direct calls to the
+//! `org.freedesktop.Notifications` D-Bus service through `gdbus`, or a
+//! command such as `dunstify`.
+//!
+//! Clicks on D-Bus notifications are watched for with one `gdbus monitor`
+//! shared by all notifications with a button. It runs while any of them
+//! waits for a click, each for up to [`ACTION_TIMEOUT`].
 
-use std::path::Path;
-
-#[cfg(feature = "notifications")]
+use crate::config::{NotificationBackend, NotificationConfig};
+use crate::disk_space::LowSpace;
+use crate::i18n::{tr, tr_args};
+use std::collections::BTreeMap;
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use std::time::{Duration, Instant};
 use tracing::warn;
-#[cfg(not(feature = "notifications"))]
-use tracing::debug;
 
 /// Events that can trigger a desktop notification.
 pub enum NotificationEvent {
     /// An AppImage was successfully integrated.
     Integrated {
+        identifier: String,
+        name: String,
+        path: String,
+        icon: Option<String>,
+    },
+    /// An AppImage was replaced in place by a different build.
+    Updated {
+        identifier: String,
         name: String,
+        version: Option<String>,
         path: String,
         icon: Option<String>,
     },
     /// An AppImage was unintegrated (removed from menu).
     Unintegrated { name: String, path: String },
+    /// A new AppImage was found in approval mode and awaits integration.
+    AwaitingApproval { name: String, path: String },
+    /// A new AppImage was found in a quarantine directory and is integrated
+    /// once moved into `destination`.
+    Quarantined {
+        name: String,
+        path: String,
+        destination: String,
+    },
+    /// An AppImage was skipped because a directory it needs is short of
+    /// free space.
+    LowDiskSpace {
+        name: String,
+        dir: String,
+        available: String,
+    },
+    /// Several events coalesced into one notification.
+    Summary {
+        integrated: Vec<String>,
+        removed: Vec<String>,
+        awaiting: Vec<String>,
+    },
 }
 
-/// Send a desktop notification for an event.
-#[cfg(feature = "notifications")]
-pub fn send(event: NotificationEvent) {
-    use notify_rust::Notification;
-
-    let result = match &event {
-        NotificationEvent::Integrated { name, path, icon } => {
-            let mut n = Notification::new();
-            n.appname("AppImage Auto")
-                .summary(&format!("{} integrated", name))
-                .body(&format!("Ready in application menu\n{}", path));
-            if let Some(i) = icon {
-                n.icon(i);
-            } else {
-                n.icon("appimage-auto");
+/// Number of names listed in a summary notification body.
+const SUMMARY_NAMES: usize = 5;
+
+/// File name of the GUI binary, started when a notification is clicked.
+const GUI_BINARY: &str = "appimage-auto-gui";
+
+/// Prefix of the URI that makes the GUI show an app's details page.
+const DETAILS_URI_PREFIX: &str = "appimage-auto://app/";
+
+/// Application name notifications are sent under.
+const APP_NAME: &str = "AppImage Auto";
+
+/// How long a click on a D-Bus notification's button is waited for.
+pub const ACTION_TIMEOUT: Duration = Duration::from_secs(30 * 60);
+
+/// How long the shared `gdbus monitor` is kept with nothing to wait for.
+const MONITOR_GRACE: Duration = Duration::from_secs(10);
+
+/// Destination arguments of `gdbus` for the notification service.
+const DBUS_DEST: [&str; 4] = [
+    "--dest",
+    "org.freedesktop.Notifications",
+    "--object-path",
+    "/org/freedesktop/Notifications",
+];
+
+/// The `gdbus monitor` shared by D-Bus notifications waiting for a click
+struct Monitor {
+    /// Actions by notification id, with when to stop waiting for them
+    waiters: BTreeMap<u32, (Action, Instant)>,
+    /// Whether the monitor process is running
+    running: bool,
+    /// When a notification last needed it
+    last_used: Option<Instant>,
+}
+
+static MONITOR: Mutex<Monitor> = Mutex::new(Monitor {
+    waiters: BTreeMap::new(),
+    running: false,
+    last_used: None,
+});
+
+fn monitor() -> MutexGuard<'static, Monitor> {
+    MONITOR.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// What clicking a notification (or one of its buttons) does
+enum Action {
+    /// Open the GUI on an app's details page
+    ShowDetails(PathBuf, String),
+    /// Move an AppImage into a directory
+    Move(PathBuf, PathBuf),
+}
+
+impl Action {
+    /// Key the action is sent with and reported back under when invoked
+    fn key(&self) -> &'static str {
+        match self {
+            Self::ShowDetails(..) => "default",
+            Self::Move(..) => "move",
+        }
+    }
+
+    fn label(&self) -> &'static str {
+        match self {
+            Self::ShowDetails(..) => tr("Show details"),
+            Self::Move(..) => tr("Move & Integrate"),
+        }
+    }
+
+    fn run(&self) {
+        match self {
+            Self::ShowDetails(gui, identifier) => show_details(gui, identifier),
+            // The daemon integrates it when it sees the move
+            Self::Move(path, dir) => {
+                if let Err(e) = move_into(path, dir) {
+                    warn!("Failed to move {:?} to {:?}: {}", path, dir, e);
+                }
             }
-            n.show()
         }
-        NotificationEvent::Unintegrated { name, path } => Notification::new()
-            .appname("AppImage Auto")
-            .summary(&format!("{} removed", name))
-            .body(path)
-            .icon("appimage-auto")
-            .show(),
-    };
+    }
+}
+
+/// A notification as shown, whatever the backend
+struct Message {
+    summary: String,
+    body: String,
+    icon: String,
+    action: Option<Action>,
+}
+
+impl Message {
+    fn new(event: NotificationEvent) -> Self {
+        let message = |summary: String, body: String| Self {
+            summary,
+            body,
+            icon: "appimage-auto".to_string(),
+            action: None,
+        };
+
+        match event {
+            NotificationEvent::Integrated {
+                identifier,
+                name,
+                path,
+                icon,
+            } => Self {
+                icon: icon.unwrap_or_else(|| "appimage-auto".to_string()),
+                // Clicking an integration notification opens the app in the GUI
+                action: gui_binary().map(|gui| Action::ShowDetails(gui, identifier)),
+                ..message(
+                    tr_args("{} integrated", &[&name]),
+                    tr_args("Ready in application menu\n{}", &[&path]),
+                )
+            },
+            NotificationEvent::Updated {
+                identifier,
+                name,
+                version,
+                path,
+                icon,
+            } => {
+                let summary = match version {
+                    Some(v) => tr_args("{} updated to version {}", &[&name, &v]),
+                    None => tr_args("{} updated", &[&name]),
+                };
+                Self {
+                    icon: icon.unwrap_or_else(|| "appimage-auto".to_string()),
+                    action: gui_binary().map(|gui| Action::ShowDetails(gui, identifier)),
+                    ..message(summary, path)
+                }
+            }
+            NotificationEvent::Unintegrated { name, path } => {
+                message(tr_args("{} removed", &[&name]), path)
+            }
+            NotificationEvent::AwaitingApproval { name, path } => message(
+                tr_args("New AppImage: {}", &[&name]),
+                tr_args(
+                    "Waiting for approval\nIntegrate with: appimage-auto integrate \"{}\"",
+                    &[&path],
+                ),
+            ),
+            NotificationEvent::Quarantined {
+                name,
+                path,
+                destination,
+            } => Self {
+                action: Some(Action::Move(path.into(), PathBuf::from(&destination))),
+                ..message(
+                    tr_args("New AppImage: {}", &[&name]),
+                    tr_args("Integrated once moved to {}", &[&destination]),
+                )
+            },
+            NotificationEvent::LowDiskSpace {
+                name,
+                dir,
+                available,
+            } => message(
+                tr_args("Not enough disk space for {}", &[&name]),
+                tr_args("Only {} free in {}", &[&available, &dir]),
+            ),
+            NotificationEvent::Summary {
+                integrated,
+                removed,
+                awaiting,
+            } => message(
+                summary_title(integrated.len(), removed.len(), awaiting.len()),
+                summary_body(integrated.iter().chain(&removed).chain(&awaiting)),
+            ),
+        }
+    }
+}
 
+/// Send a desktop notification for an event with the configured backend.
+pub fn send(event: NotificationEvent, config: &NotificationConfig) {
+    let message = Message::new(event);
+    let result = match config.backend {
+        NotificationBackend::NotifyRust => send_notify_rust(message),
+        NotificationBackend::Dbus => send_dbus(message),
+        NotificationBackend::Command => send_command(message, &config.command),
+    };
     if let Err(e) = result {
         warn!("Notification failed: {}", e);
     }
 }
 
-/// Send a desktop notification for an event (no-op when feature disabled).
+/// Send a notification with the `notify-rust` crate
+#[cfg(feature = "notifications")]
+fn send_notify_rust(message: Message) -> Result<(), String> {
+    let mut n = notify_rust::Notification::new();
+    n.appname(APP_NAME)
+        .summary(&message.summary)
+        .body(&message.body)
+        .icon(&message.icon);
+    if let Some(action) = &message.action {
+        n.action(action.key(), action.label());
+    }
+    let handle = n.show().map_err(|e| e.to_string())?;
+
+    // Waiting blocks until the notification is clicked or closed
+    if let Some(action) = message.action {
+        std::thread::spawn(move || {
+            handle.wait_for_action(|key| {
+                if key == action.key() {
+                    action.run();
+                }
+            });
+        });
+    }
+    Ok(())
+}
+
+/// Without the `notifications` feature there is no `notify-rust` backend
 #[cfg(not(feature = "notifications"))]
-pub fn send(_event: NotificationEvent) {
-    debug!("Notifications disabled at compile time");
+fn send_notify_rust(_message: Message) -> Result<(), String> {
+    tracing::debug!("Built without notify-rust, not sending notification");
+    Ok(())
+}
+
+/// Send a notification by calling `org.freedesktop.Notifications` with
+/// `gdbus`, watching its signals for a click on the action
+fn send_dbus(message: Message) -> Result<(), String> {
+    let failed = |e: std::io::Error| format!("gdbus: {}", e);
+
+    // Listen before sending, so a quick click isn't missed
+    if message.action.is_some() {
+        start_monitor(&mut monitor()).map_err(failed)?;
+    }
+
+    let actions = match &message.action {
+        Some(action) => format!(
+            "[{}, {}]",
+            gvariant_string(action.key()),
+            gvariant_string(action.label())
+        ),
+        None => "@as []".to_string(),
+    };
+    let output = Command::new("gdbus")
+        .args(["call", "--session"])
+        .args(DBUS_DEST)
+        .args(["--method", "org.freedesktop.Notifications.Notify"])
+        .args([
+            gvariant_string(APP_NAME),
+            "uint32 0".to_string(),
+            gvariant_string(&message.icon),
+            gvariant_string(&message.summary),
+            gvariant_string(&message.body),
+            actions,
+            "@a{sv} {}".to_string(),
+            "int32 -1".to_string(),
+        ])
+        .output()
+        .map_err(failed)?;
+    let Some(id) = parse_notification_id(&String::from_utf8_lossy(&output.stdout)) else {
+        return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
+    };
+
+    if let Some(action) = message.action {
+        let mut monitor = monitor();
+        // Stopped meanwhile after a long call
+        start_monitor(&mut monitor).map_err(failed)?;
+        monitor
+            .waiters
+            .insert(id, (action, Instant::now() + ACTION_TIMEOUT));
+    }
+    Ok(())
+}
+
+/// Start the shared `gdbus monitor` unless it is running, and keep it from
+/// stopping for [`MONITOR_GRACE`]
+fn start_monitor(monitor: &mut Monitor) -> std::io::Result<()> {
+    monitor.last_used = Some(Instant::now());
+    if monitor.running {
+        return Ok(());
+    }
+    let mut child = Command::new("gdbus")
+        .args(["monitor", "--session"])
+        .args(DBUS_DEST)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()?;
+    let stdout = child.stdout.take().expect("stdout is piped");
+
+    let (tx, lines) = mpsc::channel();
+    std::thread::spawn(move || {
+        for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+            if tx.send(line).is_err() {
+                break;
+            }
+        }
+    });
+    std::thread::spawn(move || dispatch_signals(child, lines));
+    monitor.running = true;
+    Ok(())
+}
+
+/// Run the actions of clicked notifications from the monitor's output,
+/// until none is waiting any more
+fn dispatch_signals(mut child: Child, lines: Receiver<String>) {
+    loop {
+        let timeout = monitor()
+            .waiters
+            .values()
+            .map(|(_, until)| until.saturating_duration_since(Instant::now()))
+            .min()
+            .unwrap_or(MONITOR_GRACE);
+        match lines.recv_timeout(timeout) {
+            Ok(line) => {
+                let Some((id, signal)) = parse_signal(&line) else {
+                    continue;
+                };
+                let mut monitor = monitor();
+                let action = match signal {
+                    Signal::ActionInvoked(key)
+                        if monitor
+                            .waiters
+                            .get(&id)
+                            .is_some_and(|(action, _)| action.key() == key) =>
+                    {
+                        monitor.waiters.remove(&id)
+                    }
+                    Signal::Closed => {
+                        monitor.waiters.remove(&id);
+                        None
+                    }
+                    _ => None,
+                };
+                drop(monitor);
+                if let Some((action, _)) = action {
+                    action.run();
+                }
+            }
+            Err(RecvTimeoutError::Timeout) => {
+                let mut monitor = monitor();
+                let now = Instant::now();
+                monitor.waiters.retain(|_, (_, until)| *until > now);
+                if monitor.waiters.is_empty()
+                    && monitor
+                        .last_used
+                        .is_none_or(|at| at.elapsed() >= MONITOR_GRACE)
+                {
+                    monitor.running = false;
+                    break;
+                }
+            }
+            Err(RecvTimeoutError::Disconnected) => {
+                warn!("gdbus monitor exited, no longer waiting for notification clicks");
+                let mut monitor = monitor();
+                monitor.waiters.clear();
+                monitor.running = false;
+                break;
+            }
+        }
+    }
+    let _ = child.kill();
+    let _ = child.wait();
+}
+
+/// Send a notification by running `command` with the summary and body as
+/// its last two arguments (and the icon in `APPIMAGE_AUTO_ICON`).
+///
+/// For a notification with a button, `APPIMAGE_AUTO_ACTION` and
+/// `APPIMAGE_AUTO_ACTION_LABEL` are set too, and the command printing the
+/// action (as `dunstify -A` does when it is clicked) triggers it.
+fn send_command(message: Message, command: &[String]) -> Result<(), String> {
+    let Some((program, args)) = command.split_first() else {
+        return Err("notifications.command is empty".to_string());
+    };
+    let mut command = Command::new(program);
+    command
+        .args(args)
+        .arg(&message.summary)
+        .arg(&message.body)
+        .env("APPIMAGE_AUTO_ICON", &message.icon)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped());
+    if let Some(action) = &message.action {
+        command
+            .env("APPIMAGE_AUTO_ACTION", action.key())
+            .env("APPIMAGE_AUTO_ACTION_LABEL", action.label());
+    }
+    let child = command.spawn().map_err(|e| format!("{}: {}", program, e))?;
+
+    // Commands like `dunstify -A` block until the notification is closed
+    let program = program.clone();
+    std::thread::spawn(move || match child.wait_with_output() {
+        Ok(output) if !output.status.success() => {
+            warn!("{} exited with {}", program, output.status);
+        }
+        Ok(output) => {
+            let printed = String::from_utf8_lossy(&output.stdout);
+            if let Some(action) = message.action
+                && printed.trim() == action.key()
+            {
+                action.run();
+            }
+        }
+        Err(e) => warn!("{} failed: {}", program, e),
+    });
+    Ok(())
+}
+
+/// A string literal in GVariant text format, as `gdbus call` parses its
+/// arguments
+fn gvariant_string(s: &str) -> String {
+    let mut quoted = String::with_capacity(s.len() + 2);
+    quoted.push('\'');
+    for c in s.chars() {
+        match c {
+            '\'' | '\\' => {
+                quoted.push('\\');
+                quoted.push(c);
+            }
+            '\n' => quoted.push_str("\\n"),
+            _ => quoted.push(c),
+        }
+    }
+    quoted.push('\'');
+    quoted
+}
+
+/// Id of a new notification in `gdbus call` output, e.g. `(uint32 7,)`
+fn parse_notification_id(output: &str) -> Option<u32> {
+    output
+        .trim()
+        .strip_prefix("(uint32 ")?
+        .strip_suffix(",)")?
+        .parse()
+        .ok()
+}
+
+/// A signal about a sent notification
+#[derive(Debug, PartialEq, Eq)]
+enum Signal {
+    ActionInvoked(String),
+    Closed,
+}
+
+/// The notification id and signal in a line of `gdbus monitor` output,
+/// e.g. `/org/freedesktop/Notifications: org.freedesktop.Notifications.ActionInvoked (uint32 7, 'move')`
+fn parse_signal(line: &str) -> Option<(u32, Signal)> {
+    let (_, signal) = line.split_once("org.freedesktop.Notifications.")?;
+    let (name, args) = signal.split_once(" (")?;
+    let (id, args) = args.strip_prefix("uint32 ")?.split_once(", ")?;
+    let id = id.parse().ok()?;
+    match name {
+        "ActionInvoked" => {
+            let key = args.strip_prefix('\'')?.strip_suffix("')")?;
+            Some((id, Signal::ActionInvoked(key.to_string())))
+        }
+        "NotificationClosed" => Some((id, Signal::Closed)),
+        _ => None,
+    }
+}
+
+/// Start the GUI (or bring an open one to the front) showing an app's
+/// details page
+fn show_details(gui: &Path, identifier: &str) {
+    if let Err(e) = Command::new(gui).arg(details_uri(identifier)).spawn() {
+        warn!("Failed to start {:?}: {}", gui, e);
+    }
+}
+
+/// The GUI binary next to the running one, or else on `PATH`
+fn gui_binary() -> Option<PathBuf> {
+    let sibling = std::env::current_exe().ok()?.with_file_name(GUI_BINARY);
+    if sibling.is_file() {
+        return Some(sibling);
+    }
+    std::env::split_paths(&std::env::var_os("PATH")?)
+        .map(|dir| dir.join(GUI_BINARY))
+        .find(|path| path.is_file())
+}
+
+/// Move a file into `dir` under its own name, never replacing a file
+fn move_into(path: &Path, dir: &Path) -> std::io::Result<PathBuf> {
+    let dest = dir.join(path.file_name().unwrap_or_default());
+    if dest.exists() {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::AlreadyExists,
+            format!("{} already exists", dest.display()),
+        ));
+    }
+    std::fs::create_dir_all(dir)?;
+    if std::fs::rename(path, &dest).is_err() {
+        // Different file systems
+        std::fs::copy(path, &dest)?;
+        std::fs::remove_file(path)?;
+    }
+    Ok(dest)
+}
+
+/// URI that makes the GUI show an app's details page when opened with it,
+/// e.g. `appimage-auto-gui appimage-auto://app/<identifier>`.
+pub fn details_uri(identifier: &str) -> String {
+    format!("{}{}", DETAILS_URI_PREFIX, identifier)
+}
+
+/// Identifier of the app a details page URI refers to.
+pub fn parse_details_uri(uri: &str) -> Option<&str> {
+    uri.strip_prefix(DETAILS_URI_PREFIX)
+        .map(|identifier| identifier.trim_end_matches('/'))
+        .filter(|identifier| {
+            !identifier.is_empty() && identifier.chars().all(|c| c.is_ascii_alphanumeric())
+        })
 }
 
 /// Create an integration notification event.
-pub fn integrated(name: &str, path: &Path, icon: Option<&Path>) -> NotificationEvent {
+pub fn integrated(
+    identifier: &str,
+    name: &str,
+    path: &Path,
+    icon: Option<&Path>,
+) -> NotificationEvent {
     NotificationEvent::Integrated {
+        identifier: identifier.to_string(),
         name: name.to_string(),
         path: path.display().to_string(),
         icon: icon.map(|p| p.display().to_string()),
     }
 }
 
+/// A daily window in which notifications are held back, e.g. `22:00-08:00`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QuietHours {
+    /// Minutes after midnight the window starts
+    start: u32,
+    /// Minutes after midnight the window ends (the next day if before `start`)
+    end: u32,
+}
+
+impl QuietHours {
+    /// Parse a window as `HH:MM-HH:MM`
+    pub fn parse(s: &str) -> Option<Self> {
+        let minutes = |time: &str| {
+            let (hours, minutes) = time.trim().split_once(':')?;
+            let (hours, minutes): (u32, u32) = (hours.parse().ok()?, minutes.parse().ok()?);
+            (hours < 24 && minutes < 60).then_some(hours * 60 + minutes)
+        };
+        let (start, end) = s.split_once('-')?;
+        Some(Self {
+            start: minutes(start)?,
+            end: minutes(end)?,
+        })
+    }
+
+    /// Whether `minute` (after midnight) falls in the window
+    pub fn contains(&self, minute: u32) -> bool {
+        if self.start <= self.end {
+            (self.start..self.end).contains(&minute)
+        } else {
+            minute >= self.start || minute < self.end
+        }
+    }
+
+    /// Whether it is quiet hours now, in local time
+    pub fn is_now(&self) -> bool {
+        self.contains(local_minute_of_day())
+    }
+}
+
+/// Minutes since local midnight
+fn local_minute_of_day() -> u32 {
+    let now = libc::time_t::try_from(crate::state::current_timestamp()).unwrap_or_default();
+    // SAFETY: tm is plain data that localtime_r fills in
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+    if unsafe { libc::localtime_r(&now, &mut tm) }.is_null() {
+        return 0;
+    }
+    (tm.tm_hour * 60 + tm.tm_min) as u32
+}
+
+/// Coalesce a burst of events into a single summary when there are more
+/// than `threshold` of them.
+pub fn coalesce(events: Vec<NotificationEvent>, threshold: usize) -> Vec<NotificationEvent> {
+    if events.len() <= threshold {
+        return events;
+    }
+
+    let mut integrated = Vec::new();
+    let mut removed = Vec::new();
+    let mut awaiting = Vec::new();
+    // Warnings aren't summarized
+    let mut warnings = Vec::new();
+    for event in events {
+        match event {
+            NotificationEvent::Integrated { name, .. }
+            | NotificationEvent::Updated { name, .. } => integrated.push(name),
+            NotificationEvent::Unintegrated { name, .. } => removed.push(name),
+            NotificationEvent::AwaitingApproval { name, .. }
+            | NotificationEvent::Quarantined { name, .. } => awaiting.push(name),
+            NotificationEvent::Summary {
+                integrated: i,
+                removed: r,
+                awaiting: a,
+            } => {
+                integrated.extend(i);
+                removed.extend(r);
+                awaiting.extend(a);
+            }
+            NotificationEvent::LowDiskSpace { .. } => warnings.push(event),
+        }
+    }
+    if integrated.is_empty() && removed.is_empty() && awaiting.is_empty() {
+        return warnings;
+    }
+    let mut coalesced = vec![NotificationEvent::Summary {
+        integrated,
+        removed,
+        awaiting,
+    }];
+    coalesced.extend(warnings);
+    coalesced
+}
+
+/// Title for a summary notification, e.g. "12 AppImages integrated, 1 removed".
+fn summary_title(integrated: usize, removed: usize, awaiting: usize) -> String {
+    let counts = [
+        (integrated, tr("integrated")),
+        (removed, tr("removed")),
+        (awaiting, tr("awaiting approval")),
+    ];
+    let mut parts = counts.iter().filter(|(n, _)| *n > 0);
+
+    // The noun follows the first count only: "12 AppImages integrated, 1 removed"
+    let (first, what) = parts.next().unwrap_or(&counts[0]);
+    let noun = if *first == 1 {
+        tr("AppImage")
+    } else {
+        tr("AppImages")
+    };
+    let mut title = format!("{} {} {}", first, noun, what);
+    for (n, what) in parts {
+        title.push_str(&format!(", {} {}", n, what));
+    }
+    title
+}
+
+/// Body for a summary notification: the first few names, then a count.
+fn summary_body<'a>(names: impl Iterator<Item = &'a String>) -> String {
+    let names: Vec<_> = names.collect();
+    let mut body = names
+        .iter()
+        .take(SUMMARY_NAMES)
+        .map(|s| s.as_str())
+        .collect::<Vec<_>>()
+        .join(", ");
+    if names.len() > SUMMARY_NAMES {
+        body.push_str(&tr_args(" and {} more", &[&(names.len() - SUMMARY_NAMES)]));
+    }
+    body
+}
+
+/// Create an update notification event.
+pub fn updated(
+    identifier: &str,
+    name: &str,
+    version: Option<&str>,
+    path: &Path,
+    icon: Option<&Path>,
+) -> NotificationEvent {
+    NotificationEvent::Updated {
+        identifier: identifier.to_string(),
+        name: name.to_string(),
+        version: version.map(str::to_string),
+        path: path.display().to_string(),
+        icon: icon.map(|p| p.display().to_string()),
+    }
+}
+
+/// Create a notification event for an AppImage held for approval.
+pub fn awaiting_approval(path: &Path) -> NotificationEvent {
+    NotificationEvent::AwaitingApproval {
+        name: path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_else(|| path.display().to_string()),
+        path: path.display().to_string(),
+    }
+}
+
+/// Create a notification event for an AppImage held in a quarantine
+/// directory until it is moved into `destination`.
+pub fn quarantined(path: &Path, destination: &Path) -> NotificationEvent {
+    NotificationEvent::Quarantined {
+        name: path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_else(|| path.display().to_string()),
+        path: path.display().to_string(),
+        destination: destination.display().to_string(),
+    }
+}
+
+/// Create a notification event for an AppImage skipped for lack of space.
+pub fn low_disk_space(path: &Path, low: &LowSpace) -> NotificationEvent {
+    NotificationEvent::LowDiskSpace {
+        name: path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_else(|| path.display().to_string()),
+        dir: low.dir.display().to_string(),
+        available: crate::format::human_size(low.available),
+    }
+}
+
 /// Create an unintegration notification event.
 pub fn unintegrated(name: &str, path: &Path) -> NotificationEvent {
     NotificationEvent::Unintegrated {
@@ -72,3 +777,142 @@ pub fn unintegrated(name: &str, path: &Path) -> NotificationEvent {
         path: path.display().to_string(),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_coalesce_below_threshold() {
+        let events = vec![integrated("0123456789abcdef", "A", Path::new("/a"), None)];
+        let out = coalesce(events, 3);
+        assert!(matches!(
+            out.as_slice(),
+            [NotificationEvent::Integrated { .. }]
+        ));
+    }
+
+    #[test]
+    fn test_coalesce_burst() {
+        let mut events: Vec<_> = (0..8)
+            .map(|i| integrated(&i.to_string(), &format!("App{}", i), Path::new("/a"), None))
+            .collect();
+        events.push(unintegrated("Old", Path::new("/b")));
+
+        let out = coalesce(events, 3);
+        let [
+            NotificationEvent::Summary {
+                integrated,
+                removed,
+                ..
+            },
+        ] = out.as_slice()
+        else {
+            panic!("expected a single summary");
+        };
+        assert_eq!(integrated.len(), 8);
+        assert_eq!(removed, &vec!["Old".to_string()]);
+        assert_eq!(summary_title(8, 1, 0), "8 AppImages integrated, 1 removed");
+        assert_eq!(summary_title(0, 0, 1), "1 AppImage awaiting approval");
+        assert_eq!(
+            summary_body(integrated.iter()),
+            "App0, App1, App2, App3, App4 and 3 more"
+        );
+    }
+
+    #[test]
+    fn test_details_uri() {
+        let uri = details_uri("0123456789abcdef");
+        assert_eq!(uri, "appimage-auto://app/0123456789abcdef");
+        assert_eq!(parse_details_uri(&uri), Some("0123456789abcdef"));
+        assert_eq!(parse_details_uri("appimage-auto://app/"), None);
+        assert_eq!(parse_details_uri("appimage-auto://app/../x"), None);
+        assert_eq!(parse_details_uri("file:///home/user/App.AppImage"), None);
+    }
+
+    #[test]
+    fn test_move_into() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let download = temp_dir.path().join("App.AppImage");
+        let applications = temp_dir.path().join("Applications");
+        std::fs::write(&download, "AppImage").unwrap();
+
+        let moved = move_into(&download, &applications).unwrap();
+        assert_eq!(moved, applications.join("App.AppImage"));
+        assert!(moved.exists() && !download.exists());
+
+        // Never replaces a file of the same name
+        std::fs::write(&download, "AppImage").unwrap();
+        assert!(move_into(&download, &applications).is_err());
+        assert!(download.exists());
+    }
+
+    #[test]
+    fn test_gdbus() {
+        assert_eq!(gvariant_string("It's\nC:\\"), "'It\\'s\\nC:\\\\'");
+        assert_eq!(parse_notification_id("(uint32 42,)\n"), Some(42));
+        assert_eq!(parse_notification_id(""), None);
+
+        let signal = |name: &str, args: &str| {
+            format!(
+                "/org/freedesktop/Notifications: org.freedesktop.Notifications.{} ({})",
+                name, args
+            )
+        };
+        assert_eq!(
+            parse_signal(&signal("ActionInvoked", "uint32 42, 'move'")),
+            Some((42, Signal::ActionInvoked("move".to_string())))
+        );
+        assert_eq!(
+            parse_signal(&signal("NotificationClosed", "uint32 42, uint32 2")),
+            Some((42, Signal::Closed))
+        );
+        assert_eq!(parse_signal(&signal("Notify", "'app', uint32 0")), None);
+    }
+
+    #[test]
+    fn test_command_backend() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let download = temp_dir.path().join("App.AppImage");
+        let applications = temp_dir.path().join("Applications");
+        std::fs::write(&download, "AppImage").unwrap();
+
+        // Clicks the button right away
+        let config = NotificationConfig {
+            backend: NotificationBackend::Command,
+            command: vec![
+                "sh".to_string(),
+                "-c".to_string(),
+                "echo \"$APPIMAGE_AUTO_ACTION\"".to_string(),
+            ],
+            ..NotificationConfig::default()
+        };
+        send(quarantined(&download, &applications), &config);
+
+        let moved = applications.join("App.AppImage");
+        for _ in 0..100 {
+            if moved.exists() {
+                break;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(50));
+        }
+        assert!(moved.exists() && !download.exists());
+    }
+
+    #[test]
+    fn test_quiet_hours() {
+        let night = QuietHours::parse("22:00-08:00").unwrap();
+        assert!(night.contains(23 * 60));
+        assert!(night.contains(0));
+        assert!(night.contains(7 * 60 + 59));
+        assert!(!night.contains(8 * 60));
+        assert!(!night.contains(12 * 60));
+
+        let lunch = QuietHours::parse("12:00 - 13:30").unwrap();
+        assert!(lunch.contains(13 * 60));
+        assert!(!lunch.contains(13 * 60 + 30));
+
+        assert_eq!(QuietHours::parse("22:00"), None);
+        assert_eq!(QuietHours::parse("24:00-08:00"), None);
+    }
+}
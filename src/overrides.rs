@@ -0,0 +1,286 @@
+//! User overrides for generated desktop entries.
+//!
+//! Overrides are stored per AppImage identifier in
+//! `~/.config/appimage-auto/overrides.toml` and applied every time a desktop
+//! entry is written, so manual edits survive re-integration and in-place
+//! updates:
+//!
+//! ```toml
+//! [apps.0123456789abcdef]
+//! name = "Editor"
+//! categories = "Development;TextEditor;"
+//! terminal = false
+//! ```
+//!
+//! Identifiers derive from the path an AppImage was first integrated at.
+//! Moves the watcher sees keep the identifier, but a file moved while the
+//! daemon wasn't watching is integrated afresh under a new one, so the
+//! contents hash of the AppImage is recorded with its overrides too
+//! (`content_hash`) and they follow the file to its new identifier.
+
+use crate::desktop::DesktopEntry;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum OverridesError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Failed to parse overrides file: {0}")]
+    Parse(#[from] toml::de::Error),
+    #[error("Failed to serialize overrides: {0}")]
+    Serialize(#[from] toml::ser::Error),
+    #[error("No config directory found")]
+    NoConfigDir,
+}
+
+/// Replacement values for one desktop entry; unset fields keep the AppImage's own value
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DesktopOverrides {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+    /// Semicolon-separated list, as in the desktop entry
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub categories: Option<String>,
+    /// Semicolon-separated list, as in the desktop entry
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub keywords: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub terminal: Option<bool>,
+    /// MD5 of the AppImage contents these were last applied to; not an
+    /// override itself
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub content_hash: Option<String>,
+}
+
+impl DesktopOverrides {
+    /// Whether no field is overridden
+    pub fn is_empty(&self) -> bool {
+        Self {
+            content_hash: None,
+            ..self.clone()
+        } == Self::default()
+    }
+
+    /// Apply the overridden fields to a desktop entry.
+    ///
+    /// Localized variants (`Name[de]`, ...) of an overridden key are dropped,
+    /// otherwise they would still win in non-English sessions.
+    pub fn apply(&self, entry: &mut DesktopEntry) {
+        let strings = [
+            ("Name", self.name.clone()),
+            ("Comment", self.comment.clone()),
+            ("Categories", self.categories.as_deref().map(normalize_list)),
+            ("Keywords", self.keywords.as_deref().map(normalize_list)),
+            ("Terminal", self.terminal.map(|t| t.to_string())),
+        ];
+
+        for (key, value) in strings {
+            let Some(value) = value else {
+                continue;
+            };
+            let localized = format!("{}[", key);
+            entry.entries.retain(|k, _| !k.starts_with(&localized));
+            entry.entries.insert(key.to_string(), value);
+        }
+    }
+}
+
+/// All overrides, keyed by AppImage identifier
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Overrides {
+    pub apps: HashMap<String, DesktopOverrides>,
+}
+
+impl Overrides {
+    /// Load overrides from the default location (empty if the file does not exist)
+    pub fn load() -> Result<Self, OverridesError> {
+        let path = Self::overrides_path()?;
+        if path.exists() {
+            Self::load_from(&path)
+        } else {
+            Ok(Self::default())
+        }
+    }
+
+    /// Load overrides from a specific path
+    pub fn load_from(path: &Path) -> Result<Self, OverridesError> {
+        let content = fs::read_to_string(path)?;
+        Ok(toml::from_str(&content)?)
+    }
+
+    /// Save overrides to the default location
+    pub fn save(&self) -> Result<(), OverridesError> {
+        self.save_to(&Self::overrides_path()?)
+    }
+
+    /// Save overrides to a specific path
+    pub fn save_to(&self, path: &Path) -> Result<(), OverridesError> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, toml::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// Get the default overrides file path
+    pub fn overrides_path() -> Result<PathBuf, OverridesError> {
+        let dirs = directories::ProjectDirs::from("", "", "appimage-auto")
+            .ok_or(OverridesError::NoConfigDir)?;
+        Ok(dirs.config_dir().join("overrides.toml"))
+    }
+
+    /// Get the overrides for an AppImage
+    pub fn get(&self, identifier: &str) -> Option<&DesktopOverrides> {
+        self.apps.get(identifier)
+    }
+
+    /// Set the overrides for an AppImage; empty overrides remove the entry
+    pub fn set(&mut self, identifier: &str, overrides: DesktopOverrides) {
+        if overrides.is_empty() {
+            self.apps.remove(identifier);
+        } else {
+            self.apps.insert(identifier.to_string(), overrides);
+        }
+    }
+
+    /// Record the contents hash of the AppImage the overrides of
+    /// `identifier` apply to; returns false if it has none or the hash was
+    /// already recorded
+    pub fn set_content_hash(&mut self, identifier: &str, hash: &str) -> bool {
+        match self.apps.get_mut(identifier) {
+            Some(overrides) if overrides.content_hash.as_deref() != Some(hash) => {
+                overrides.content_hash = Some(hash.to_string());
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Identifiers with overrides recorded for the AppImage contents `hash`
+    pub fn identifiers_for_hash<'a>(&'a self, hash: &'a str) -> impl Iterator<Item = &'a str> {
+        self.apps
+            .iter()
+            .filter(move |(_, o)| o.content_hash.as_deref() == Some(hash))
+            .map(|(identifier, _)| identifier.as_str())
+    }
+
+    /// Move the overrides of `from` to `to`, for an AppImage whose
+    /// identifier changed. Nothing is moved if `to` has overrides of its
+    /// own; returns whether anything was.
+    pub fn rename(&mut self, from: &str, to: &str) -> bool {
+        if from == to || self.apps.contains_key(to) {
+            return false;
+        }
+        match self.apps.remove(from) {
+            Some(overrides) => {
+                self.apps.insert(to.to_string(), overrides);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// Normalize a user-typed list (`"Graphics, Viewer"`) to desktop entry form (`"Graphics;Viewer;"`)
+fn normalize_list(value: &str) -> String {
+    value
+        .split([';', ','])
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(|item| format!("{};", item))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_apply_overrides() {
+        let mut entry = DesktopEntry {
+            entries: HashMap::from([
+                ("Name".to_string(), "App".to_string()),
+                ("Name[de]".to_string(), "Anwendung".to_string()),
+                ("Comment".to_string(), "Original".to_string()),
+            ]),
+            actions: HashMap::new(),
+            source_path: None,
+        };
+
+        let overrides = DesktopOverrides {
+            name: Some("My App".to_string()),
+            categories: Some("Graphics, Viewer".to_string()),
+            terminal: Some(true),
+            ..Default::default()
+        };
+        overrides.apply(&mut entry);
+
+        assert_eq!(entry.name(), Some("My App"));
+        assert!(!entry.entries.contains_key("Name[de]"));
+        assert_eq!(entry.entries["Comment"], "Original");
+        assert_eq!(entry.entries["Categories"], "Graphics;Viewer;");
+        assert_eq!(entry.entries["Terminal"], "true");
+    }
+
+    #[test]
+    fn test_overrides_round_trip() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let path = temp.path().join("overrides.toml");
+
+        let mut overrides = Overrides::default();
+        overrides.set(
+            "abc",
+            DesktopOverrides {
+                comment: Some("Edited".to_string()),
+                ..Default::default()
+            },
+        );
+        overrides.set("empty", DesktopOverrides::default());
+        overrides.save_to(&path).unwrap();
+
+        let loaded = Overrides::load_from(&path).unwrap();
+        assert_eq!(loaded.apps.len(), 1);
+        assert_eq!(
+            loaded.get("abc").and_then(|o| o.comment.as_deref()),
+            Some("Edited")
+        );
+    }
+
+    #[test]
+    fn test_follow_content_hash() {
+        let mut overrides = Overrides::default();
+        overrides.set(
+            "old",
+            DesktopOverrides {
+                terminal: Some(true),
+                ..Default::default()
+            },
+        );
+        assert!(!overrides.set_content_hash("other", "hash"));
+        assert!(overrides.set_content_hash("old", "hash"));
+        assert!(!overrides.set_content_hash("old", "hash"));
+        assert_eq!(
+            overrides.identifiers_for_hash("hash").collect::<Vec<_>>(),
+            ["old"]
+        );
+
+        assert!(overrides.rename("old", "new"));
+        assert_eq!(overrides.get("new").unwrap().terminal, Some(true));
+        assert!(overrides.get("old").is_none());
+
+        // A recorded hash alone is no override
+        let hash_only = DesktopOverrides {
+            content_hash: Some("hash".to_string()),
+            ..Default::default()
+        };
+        assert!(hash_only.is_empty());
+    }
+}
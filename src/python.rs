@@ -0,0 +1,300 @@
+//! Python bindings (optional feature).
+//!
+//! Exposes `Config`, `State` and the integration service (`Integrator`) as the
+//! `appimage_auto` Python module so bulk operations and reports can be scripted.
+//! Build with `maturin develop --features python`.
+
+use crate::appimage;
+use crate::config::Config;
+use crate::daemon::Daemon;
+use crate::state::{IntegratedAppImage, State};
+use pyo3::create_exception;
+use pyo3::exceptions::PyException;
+use pyo3::prelude::*;
+use std::path::PathBuf;
+
+create_exception!(appimage_auto, AppImageAutoError, PyException);
+
+/// Convert any library error into the module's Python exception
+fn to_py_err(e: impl std::fmt::Display) -> PyErr {
+    AppImageAutoError::new_err(e.to_string())
+}
+
+/// Daemon configuration
+#[pyclass(name = "Config", from_py_object)]
+#[derive(Clone)]
+pub struct PyConfig {
+    inner: Config,
+}
+
+#[pymethods]
+impl PyConfig {
+    /// Default configuration (not read from disk)
+    #[new]
+    fn new() -> Self {
+        Self {
+            inner: Config::default(),
+        }
+    }
+
+    /// Load configuration from the default location
+    #[staticmethod]
+    fn load() -> PyResult<Self> {
+        Config::load()
+            .map(|inner| Self { inner })
+            .map_err(to_py_err)
+    }
+
+    /// Load configuration from a specific file
+    #[staticmethod]
+    fn load_from(path: PathBuf) -> PyResult<Self> {
+        Config::load_from(&path)
+            .map(|inner| Self { inner })
+            .map_err(to_py_err)
+    }
+
+    /// Save configuration to the default location
+    fn save(&self) -> PyResult<()> {
+        self.inner.save().map_err(to_py_err)
+    }
+
+    /// Watch directories as written in the config (unexpanded)
+    #[getter]
+    fn watch_directories(&self) -> Vec<String> {
+        self.inner.watch.directories.clone()
+    }
+
+    #[setter]
+    fn set_watch_directories(&mut self, directories: Vec<String>) {
+        self.inner.watch.directories = directories;
+    }
+
+    /// Expanded directory for .desktop files
+    #[getter]
+    fn desktop_dir(&self) -> PathBuf {
+        self.inner.desktop_directory()
+    }
+
+    /// Expanded directory for icons
+    #[getter]
+    fn icon_dir(&self) -> PathBuf {
+        self.inner.icon_directory()
+    }
+
+    /// Render the configuration as TOML
+    fn to_toml(&self) -> PyResult<String> {
+        toml::to_string_pretty(&self.inner).map_err(to_py_err)
+    }
+
+    fn __repr__(&self) -> String {
+        format!("Config(watch_directories={:?})", self.inner.watch.directories)
+    }
+}
+
+/// A single integrated AppImage record
+#[pyclass(name = "IntegratedAppImage", frozen)]
+pub struct PyIntegratedAppImage {
+    inner: IntegratedAppImage,
+}
+
+#[pymethods]
+impl PyIntegratedAppImage {
+    #[getter]
+    fn identifier(&self) -> &str {
+        &self.inner.identifier
+    }
+
+    #[getter]
+    fn name(&self) -> Option<&str> {
+        self.inner.name.as_deref()
+    }
+
+    #[getter]
+    fn appimage_path(&self) -> PathBuf {
+        self.inner.appimage_path.clone()
+    }
+
+    #[getter]
+    fn desktop_path(&self) -> PathBuf {
+        self.inner.desktop_path.clone()
+    }
+
+    #[getter]
+    fn icon_paths(&self) -> Vec<PathBuf> {
+        self.inner.icon_paths.clone()
+    }
+
+    #[getter]
+    fn integrated_at(&self) -> u64 {
+        self.inner.integrated_at
+    }
+
+    #[getter]
+    fn updated_at(&self) -> u64 {
+        self.inner.updated_at
+    }
+
+    /// Whether the AppImage file still exists on disk
+    fn exists(&self) -> bool {
+        self.inner.appimage_path.exists()
+    }
+
+    /// Serialize the record as a JSON string
+    fn to_json(&self) -> PyResult<String> {
+        serde_json::to_string(&self.inner).map_err(to_py_err)
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "IntegratedAppImage(name={:?}, path={:?})",
+            self.inner.name, self.inner.appimage_path
+        )
+    }
+}
+
+impl From<&IntegratedAppImage> for PyIntegratedAppImage {
+    fn from(info: &IntegratedAppImage) -> Self {
+        Self {
+            inner: info.clone(),
+        }
+    }
+}
+
+/// Snapshot of the integration state file
+#[pyclass(name = "State")]
+pub struct PyState {
+    inner: State,
+}
+
+#[pymethods]
+impl PyState {
+    /// Load state from the default location
+    #[staticmethod]
+    fn load() -> PyResult<Self> {
+        State::load().map(|inner| Self { inner }).map_err(to_py_err)
+    }
+
+    /// Re-read the state file from disk
+    fn reload(&mut self) -> PyResult<()> {
+        self.inner.reload().map_err(to_py_err)
+    }
+
+    /// All integrated AppImages
+    fn all(&self) -> Vec<PyIntegratedAppImage> {
+        self.inner.all().map(PyIntegratedAppImage::from).collect()
+    }
+
+    /// Integrated AppImages whose file no longer exists
+    fn orphaned(&self) -> Vec<PyIntegratedAppImage> {
+        self.inner
+            .find_orphaned()
+            .into_iter()
+            .map(PyIntegratedAppImage::from)
+            .collect()
+    }
+
+    /// Integrated AppImages located in a directory
+    fn in_directory(&self, dir: PathBuf) -> Vec<PyIntegratedAppImage> {
+        self.inner
+            .find_in_directory(&dir)
+            .into_iter()
+            .map(PyIntegratedAppImage::from)
+            .collect()
+    }
+
+    /// Look up an entry by identifier
+    fn get(&self, identifier: &str) -> Option<PyIntegratedAppImage> {
+        self.inner.get(identifier).map(PyIntegratedAppImage::from)
+    }
+
+    /// Look up an entry by AppImage path
+    fn get_by_path(&self, path: PathBuf) -> Option<PyIntegratedAppImage> {
+        self.inner.get_by_path(&path).map(PyIntegratedAppImage::from)
+    }
+
+    fn is_integrated(&self, path: PathBuf) -> bool {
+        self.inner.is_integrated(&path)
+    }
+
+    fn __len__(&self) -> usize {
+        self.inner.count()
+    }
+}
+
+/// Integration service: integrates and removes AppImages like the daemon does
+#[pyclass(name = "Integrator", unsendable)]
+pub struct PyIntegrator {
+    daemon: Daemon,
+}
+
+#[pymethods]
+impl PyIntegrator {
+    /// Create an integrator using the given config, or the default config file
+    #[new]
+    #[pyo3(signature = (config = None))]
+    fn new(config: Option<PyConfig>) -> PyResult<Self> {
+        let daemon = match config {
+            Some(c) => Daemon::with_config(c.inner),
+            None => Daemon::new(),
+        }
+        .map_err(to_py_err)?;
+        Ok(Self { daemon })
+    }
+
+    /// Integrate a single AppImage
+    fn integrate(&mut self, path: PathBuf) -> PyResult<()> {
+        if !appimage::is_appimage(&path) {
+            return Err(to_py_err(format!("Not a valid AppImage: {:?}", path)));
+        }
+        self.daemon.integrate(&path).map_err(to_py_err)
+    }
+
+    /// Remove the integration for an AppImage
+    fn unintegrate(&mut self, path: PathBuf) -> PyResult<()> {
+        self.daemon.unintegrate(&path).map_err(to_py_err)
+    }
+
+    /// Integrate every AppImage directly inside `directory`.
+    ///
+    /// Returns the paths that were newly integrated.
+    fn integrate_directory(&mut self, directory: PathBuf) -> PyResult<Vec<PathBuf>> {
+        let mut integrated = Vec::new();
+        for path in appimage::scan_directory(&directory).map_err(to_py_err)? {
+            if self.daemon.state().is_integrated(&path) {
+                continue;
+            }
+            self.daemon.integrate(&path).map_err(to_py_err)?;
+            integrated.push(path);
+        }
+        Ok(integrated)
+    }
+
+    /// Remove entries whose AppImage no longer exists
+    fn cleanup_orphaned(&mut self) -> PyResult<()> {
+        self.daemon.cleanup_orphaned().map_err(to_py_err)
+    }
+
+    /// Current state as seen by this integrator
+    fn state(&self) -> PyState {
+        PyState {
+            inner: self.daemon.state().clone(),
+        }
+    }
+}
+
+/// Check whether a file is an AppImage (magic byte check)
+#[pyfunction]
+fn is_appimage(path: PathBuf) -> bool {
+    appimage::is_appimage(&path)
+}
+
+#[pymodule]
+fn appimage_auto(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add("AppImageAutoError", m.py().get_type::<AppImageAutoError>())?;
+    m.add_class::<PyConfig>()?;
+    m.add_class::<PyState>()?;
+    m.add_class::<PyIntegratedAppImage>()?;
+    m.add_class::<PyIntegrator>()?;
+    m.add_function(wrap_pyfunction!(is_appimage, m)?)?;
+    Ok(())
+}
@@ -0,0 +1,134 @@
+//! Recording watcher events and reading them back.
+//!
+//! With `logging.record_events` set, the daemon appends every event it gets
+//! from the watcher to that file, one JSON object per line:
+//!
+//! ```text
+//! {"at_ms":1714564800123,"event":{"created":"/home/user/Downloads/App.AppImage"}}
+//! {"at_ms":1714564800456,"event":{"moved":{"from":"/home/user/Downloads/App.AppImage","to":"/home/user/Applications/App.AppImage"}}}
+//! ```
+//!
+//! `appimage-auto replay` feeds such a file back through the daemon's event
+//! handling in dry-run mode (see [`crate::Daemon::replay`]), to reproduce a
+//! reported integration bug.
+
+use crate::watcher::FileEvent;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum ReplayError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("JSON error: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("Line {0}: {1}")]
+    Parse(usize, serde_json::Error),
+}
+
+/// A watcher event and when it arrived
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordedEvent {
+    /// Milliseconds since the Unix epoch
+    pub at_ms: u64,
+    pub event: FileEvent,
+}
+
+/// Appends watcher events to a file
+pub struct EventRecorder {
+    file: File,
+}
+
+impl EventRecorder {
+    /// Open `path` for appending, creating it and its directory if needed
+    pub fn open(path: &Path) -> Result<Self, ReplayError> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self { file })
+    }
+
+    /// Append an event, stamped with the current time
+    pub fn record(&mut self, event: &FileEvent) -> Result<(), ReplayError> {
+        let at_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or_default();
+        let recorded = RecordedEvent {
+            at_ms,
+            event: event.clone(),
+        };
+        let line = serde_json::to_string(&recorded)?;
+        writeln!(self.file, "{}", line)?;
+        Ok(())
+    }
+}
+
+/// Read the events recorded in a file, skipping blank lines
+pub fn read_events(path: &Path) -> Result<Vec<RecordedEvent>, ReplayError> {
+    parse_events(&fs::read_to_string(path)?)
+}
+
+/// Parse recorded events, one JSON object per line
+fn parse_events(content: &str) -> Result<Vec<RecordedEvent>, ReplayError> {
+    content
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(index, line)| {
+            serde_json::from_str(line).map_err(|e| ReplayError::Parse(index + 1, e))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_record_and_read_events() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("debug").join("events.jsonl");
+
+        let mut recorder = EventRecorder::open(&path).unwrap();
+        recorder
+            .record(&FileEvent::Created(PathBuf::from("/apps/App.AppImage")))
+            .unwrap();
+        recorder
+            .record(&FileEvent::Moved {
+                from: PathBuf::from("/apps/App.AppImage"),
+                to: PathBuf::from("/apps/Other.AppImage"),
+            })
+            .unwrap();
+
+        let events = read_events(&path).unwrap();
+        assert_eq!(events.len(), 2);
+        assert!(events[0].at_ms <= events[1].at_ms);
+        assert!(
+            matches!(&events[0].event, FileEvent::Created(p) if p == Path::new("/apps/App.AppImage"))
+        );
+        assert!(
+            matches!(&events[1].event, FileEvent::Moved { to, .. } if to == Path::new("/apps/Other.AppImage"))
+        );
+    }
+
+    #[test]
+    fn test_parse_events() {
+        let events = parse_events(
+            "{\"at_ms\":1,\"event\":{\"deleted\":\"/apps/App.AppImage\"}}\n\n\
+             {\"at_ms\":2,\"event\":{\"modified\":\"/apps/App.AppImage\"}}\n",
+        )
+        .unwrap();
+        assert_eq!(events.len(), 2);
+        assert!(matches!(events[1].event, FileEvent::Modified(_)));
+
+        let err = parse_events("{\"at_ms\":1,\"event\":{\"renamed\":\"/x\"}}").unwrap_err();
+        assert!(err.to_string().starts_with("Line 1:"));
+    }
+}
@@ -0,0 +1,179 @@
+//! Resolve user-supplied names to integrated AppImages.
+//!
+//! CLI commands accept an AppImage path, an identifier prefix, the app name
+//! or the file name, whole or in part. Matches are tried from most to least
+//! specific and only the best kind of match is kept, so `firefox` picks the
+//! app named "Firefox" rather than also matching "Firefox Developer Edition".
+
+use crate::state::{IntegratedAppImage, State};
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+/// Shortest identifier prefix accepted, to avoid matching short names as hex
+const MIN_IDENTIFIER_PREFIX: usize = 4;
+
+#[derive(Error, Debug)]
+pub enum ResolveError {
+    #[error("No integrated AppImage matches '{0}'")]
+    NotFound(String),
+    #[error("'{query}' matches {} integrated AppImages", .matches.len())]
+    Ambiguous {
+        query: String,
+        /// Paths of the matching AppImages
+        matches: Vec<PathBuf>,
+    },
+}
+
+/// How well an entry matches a query; lower is better
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum MatchKind {
+    Path,
+    Identifier,
+    ExactName,
+    PartialName,
+}
+
+/// Find the entries matching a query, keeping only the best kind of match.
+///
+/// Results are sorted by name.
+pub fn matches<'a>(state: &'a State, query: &str) -> Vec<&'a IntegratedAppImage> {
+    let query = query.trim();
+    if query.is_empty() {
+        return Vec::new();
+    }
+
+    let mut found: Vec<(MatchKind, &IntegratedAppImage)> = state
+        .all()
+        .filter_map(|app| match_kind(app, query).map(|kind| (kind, app)))
+        .collect();
+    let Some(best) = found.iter().map(|(kind, _)| *kind).min() else {
+        return Vec::new();
+    };
+    found.retain(|(kind, _)| *kind == best);
+
+    let mut apps: Vec<&IntegratedAppImage> = found.into_iter().map(|(_, app)| app).collect();
+    apps.sort_by_cached_key(|app| (display_name(app).to_lowercase(), app.appimage_path.clone()));
+    apps
+}
+
+/// Resolve a query to exactly one entry
+pub fn resolve<'a>(state: &'a State, query: &str) -> Result<&'a IntegratedAppImage, ResolveError> {
+    let mut apps = matches(state, query);
+    match apps.len() {
+        0 => Err(ResolveError::NotFound(query.to_string())),
+        1 => Ok(apps.remove(0)),
+        _ => Err(ResolveError::Ambiguous {
+            query: query.to_string(),
+            matches: apps.iter().map(|app| app.appimage_path.clone()).collect(),
+        }),
+    }
+}
+
+/// Name shown for an entry: the app name, or the file name if it has none
+pub fn display_name(app: &IntegratedAppImage) -> String {
+    app.name
+        .clone()
+        .unwrap_or_else(|| file_name(&app.appimage_path))
+}
+
+/// Classify how an entry matches a (trimmed, non-empty) query
+fn match_kind(app: &IntegratedAppImage, query: &str) -> Option<MatchKind> {
+    let query_path = Path::new(query);
+    if app.appimage_path == query_path
+        || query_path
+            .canonicalize()
+            .is_ok_and(|path| path == app.appimage_path)
+    {
+        return Some(MatchKind::Path);
+    }
+
+    let query = query.to_lowercase();
+    if app.identifier == query
+        || (query.len() >= MIN_IDENTIFIER_PREFIX && app.identifier.starts_with(&query))
+    {
+        return Some(MatchKind::Identifier);
+    }
+
+    let name = app.name.as_deref().unwrap_or("").to_lowercase();
+    let file_name = file_name(&app.appimage_path).to_lowercase();
+    let file_stem = app
+        .appimage_path
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    if name == query || file_name == query || file_stem == query {
+        return Some(MatchKind::ExactName);
+    }
+    if name.contains(&query) || file_name.contains(&query) {
+        return Some(MatchKind::PartialName);
+    }
+
+    None
+}
+
+/// File name of a path as a string
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::create_entry;
+
+    fn state() -> State {
+        let mut state = State::default();
+        for (id, path, name) in [
+            ("0a1b2c3d", "/apps/Firefox.AppImage", "Firefox"),
+            (
+                "9f8e7d6c",
+                "/apps/firefox-dev.AppImage",
+                "Firefox Developer Edition",
+            ),
+            ("5a5a5a5a", "/apps/krita-5.2.AppImage", "Krita"),
+        ] {
+            state.add(create_entry(
+                id.to_string(),
+                PathBuf::from(path),
+                PathBuf::new(),
+                vec![],
+                Some(name.to_string()),
+            ));
+        }
+        state
+    }
+
+    #[test]
+    fn test_resolve() {
+        let state = state();
+        let id = |query: &str| resolve(&state, query).map(|app| app.identifier.as_str());
+
+        assert_eq!(id("/apps/krita-5.2.AppImage").unwrap(), "5a5a5a5a");
+        assert_eq!(id("9F8E").unwrap(), "9f8e7d6c");
+        // An exact name beats partial matches of other entries
+        assert_eq!(id("firefox").unwrap(), "0a1b2c3d");
+        assert_eq!(id("krita-5.2").unwrap(), "5a5a5a5a");
+        assert_eq!(id("developer").unwrap(), "9f8e7d6c");
+        assert!(matches!(id("gimp"), Err(ResolveError::NotFound(_))));
+        // Too short to be taken as an identifier prefix
+        assert!(matches!(id("0a"), Err(ResolveError::NotFound(_))));
+    }
+
+    #[test]
+    fn test_resolve_ambiguous() {
+        let state = state();
+        match resolve(&state, "fire") {
+            Err(ResolveError::Ambiguous { matches, .. }) => assert_eq!(
+                matches,
+                [
+                    PathBuf::from("/apps/Firefox.AppImage"),
+                    PathBuf::from("/apps/firefox-dev.AppImage"),
+                ]
+            ),
+            other => panic!("expected ambiguous match, got {:?}", other),
+        }
+        assert_eq!(super::matches(&state, "  ").len(), 0);
+    }
+}
@@ -0,0 +1,201 @@
+//! systemd user service management.
+//!
+//! Writes the daemon's user unit with the path of the running binary, then
+//! drives `systemctl --user` to enable, disable and inspect it.
+
+use crate::flatpak;
+use std::fs;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+use tracing::{debug, info};
+
+/// Name of the user unit
+pub const UNIT_NAME: &str = "appimage-auto.service";
+
+/// Name of the socket unit starting the daemon on demand
+pub const SOCKET_UNIT_NAME: &str = "appimage-auto.socket";
+
+#[derive(Error, Debug)]
+pub enum ServiceError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("No data directory found")]
+    NoDataDir,
+    #[error("systemctl {0} failed: {1}")]
+    Systemctl(String, String),
+}
+
+/// Installation and runtime status of the user unit
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServiceStatus {
+    /// Unit file exists
+    pub installed: bool,
+    /// Unit is enabled (`systemctl --user is-enabled`)
+    pub enabled: bool,
+    /// Unit is running (`systemctl --user is-active`)
+    pub active: bool,
+}
+
+/// Get the path of the user unit file
+pub fn unit_path() -> Result<PathBuf, ServiceError> {
+    let dirs = directories::BaseDirs::new().ok_or(ServiceError::NoDataDir)?;
+    Ok(dirs.data_dir().join("systemd/user").join(UNIT_NAME))
+}
+
+/// Render the unit file for a daemon binary at `exec`. Inside a Flatpak
+/// sandbox the unit runs the daemon through `flatpak run` instead, since the
+/// binary's path only exists in the sandbox.
+pub fn render_unit(exec: &Path) -> String {
+    let flatpak_id = if flatpak::is_sandboxed() {
+        flatpak::app_id()
+    } else {
+        None
+    };
+    format!(
+        "\
+[Unit]
+Description=AppImage Auto-Integration Daemon
+Documentation=https://github.com/cjrh/appimage-auto
+After=graphical-session.target
+
+[Service]
+Type=simple
+ExecStart={}
+Restart=on-failure
+RestartSec=5
+
+# Security hardening
+NoNewPrivileges=true
+ProtectSystem=full
+PrivateTmp=true
+ProtectKernelTunables=true
+ProtectKernelModules=true
+ProtectControlGroups=true
+RestrictRealtime=true
+LockPersonality=true
+
+[Install]
+WantedBy=default.target
+",
+        exec_start(exec, flatpak_id.as_deref())
+    )
+}
+
+/// The daemon's command line: the binary at `exec`, or the command of the
+/// Flatpak app `flatpak_id`
+fn exec_start(exec: &Path, flatpak_id: Option<&str>) -> String {
+    match flatpak_id {
+        Some(id) => format!("/usr/bin/flatpak run --command=appimage-auto {} daemon", id),
+        None => format!(
+            "\"{}\" daemon",
+            exec.display().to_string().replace('"', "\\\"")
+        ),
+    }
+}
+
+/// Write the unit for `exec`, then enable and start it
+pub fn install(exec: &Path) -> Result<PathBuf, ServiceError> {
+    let path = unit_path()?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(&path, render_unit(exec))?;
+    info!("Wrote {:?}", path);
+
+    systemctl(&["daemon-reload"])?;
+    systemctl(&["enable", "--now", UNIT_NAME])?;
+    Ok(path)
+}
+
+/// Stop and disable the unit, then remove its file
+pub fn uninstall() -> Result<(), ServiceError> {
+    let path = unit_path()?;
+    if !path.exists() {
+        return Ok(());
+    }
+
+    // The unit may already be stopped or disabled
+    if let Err(e) = systemctl(&["disable", "--now", UNIT_NAME]) {
+        debug!("{}", e);
+    }
+    fs::remove_file(&path)?;
+    info!("Removed {:?}", path);
+    systemctl(&["daemon-reload"])
+}
+
+/// Start the unit
+pub fn start() -> Result<(), ServiceError> {
+    systemctl(&["start", UNIT_NAME])
+}
+
+/// Stop the unit
+pub fn stop() -> Result<(), ServiceError> {
+    systemctl(&["stop", UNIT_NAME])
+}
+
+/// Restart the unit, starting it if it isn't running
+pub fn restart() -> Result<(), ServiceError> {
+    systemctl(&["restart", UNIT_NAME])
+}
+
+/// Query the unit's installation and runtime status
+pub fn status() -> Result<ServiceStatus, ServiceError> {
+    Ok(ServiceStatus {
+        installed: unit_path()?.exists(),
+        enabled: systemctl(&["is-enabled", "--quiet", UNIT_NAME]).is_ok(),
+        active: systemctl(&["is-active", "--quiet", UNIT_NAME]).is_ok(),
+    })
+}
+
+/// Whether the socket unit is listening, so connecting to the control socket
+/// would start the daemon
+pub fn socket_active() -> bool {
+    systemctl(&["is-active", "--quiet", SOCKET_UNIT_NAME]).is_ok()
+}
+
+/// Run `systemctl --user` with the given arguments
+fn systemctl(args: &[&str]) -> Result<(), ServiceError> {
+    let output = flatpak::host_command("systemctl", &[])
+        .arg("--user")
+        .args(args)
+        .output()?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(ServiceError::Systemctl(
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unit_path() {
+        let path = unit_path().unwrap();
+        assert!(path.ends_with("systemd/user/appimage-auto.service"));
+    }
+
+    #[test]
+    fn test_render_unit() {
+        let unit = render_unit(Path::new("/home/me/my apps/appimage-auto"));
+        assert!(unit.contains("ExecStart=\"/home/me/my apps/appimage-auto\" daemon\n"));
+        assert!(unit.contains("Restart=on-failure"));
+        assert!(unit.contains("NoNewPrivileges=true"));
+        assert!(unit.contains("WantedBy=default.target"));
+    }
+
+    #[test]
+    fn test_exec_start_flatpak() {
+        assert_eq!(
+            exec_start(
+                Path::new("/app/bin/appimage-auto"),
+                Some("io.github.appimage-auto.settings")
+            ),
+            "/usr/bin/flatpak run --command=appimage-auto io.github.appimage-auto.settings daemon"
+        );
+    }
+}
@@ -0,0 +1,473 @@
+//! Desktop setup for appimage-auto itself.
+//!
+//! Registers the AppImage MIME types with shared-mime-info and makes the GUI
+//! the default handler for them, so AppImages opened from a file manager end
+//! up in the GUI's open dialog. Also adds an "Integrate AppImage" entry to the
+//! context menus of Dolphin and Nautilus, and bookmarks directories created
+//! for AppImages in the file manager sidebar.
+
+use crate::i18n;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use thiserror::Error;
+use tracing::{debug, info};
+
+/// MIME types of type 2 and type 1 AppImages
+pub const MIME_TYPES: [&str; 2] = ["application/vnd.appimage", "application/x-iso9660-appimage"];
+
+/// Desktop file ID of the GUI
+pub const GUI_DESKTOP_ID: &str = "appimage-auto-gui.desktop";
+
+/// Name of the shared-mime-info package file
+const MIME_PACKAGE_NAME: &str = "appimage-auto.xml";
+
+/// The GUI desktop entry shipped with the sources
+const GUI_DESKTOP_ENTRY: &str = include_str!("../desktop/appimage-auto-gui.desktop");
+
+/// The Dolphin service menu shipped with the sources
+const SERVICE_MENU_ENTRY: &str = include_str!("../desktop/appimage-auto-integrate.desktop");
+
+/// Dolphin service menu, relative to the data directory
+const SERVICE_MENU: &str = "kio/servicemenus/appimage-auto-integrate.desktop";
+
+/// Nautilus script, relative to the data directory; the file name is the menu label
+const NAUTILUS_SCRIPT: &str = "nautilus/scripts/Integrate AppImage";
+
+/// GTK file manager bookmarks, relative to the config directory
+const GTK_BOOKMARKS: &str = "gtk-3.0/bookmarks";
+
+/// Section of `mimeapps.list` holding the default handlers
+const DEFAULT_APPLICATIONS: &str = "[Default Applications]";
+
+/// shared-mime-info package; type 2 AppImages carry `AI\x02` and type 1
+/// `AI\x01` at offset 8 of the ELF header
+const MIME_PACKAGE: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<mime-info xmlns="http://www.freedesktop.org/standards/shared-mime-info">
+  <mime-type type="application/vnd.appimage">
+    <comment>AppImage application bundle</comment>
+    <sub-class-of type="application/x-executable"/>
+    <generic-icon name="application-x-executable"/>
+    <magic priority="80">
+      <match type="string" value="\x7fELF" offset="0">
+        <match type="string" value="AI\x02" offset="8"/>
+      </match>
+    </magic>
+    <glob pattern="*.appimage"/>
+  </mime-type>
+  <mime-type type="application/x-iso9660-appimage">
+    <comment>AppImage application bundle (type 1)</comment>
+    <sub-class-of type="application/x-executable"/>
+    <generic-icon name="application-x-executable"/>
+    <magic priority="80">
+      <match type="string" value="\x7fELF" offset="0">
+        <match type="string" value="AI\x01" offset="8"/>
+      </match>
+    </magic>
+  </mime-type>
+</mime-info>
+"#;
+
+#[derive(Error, Debug)]
+pub enum SetupError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("No data directory found")]
+    NoDataDir,
+    #[error("{0} failed: {1}")]
+    Command(String, String),
+}
+
+/// What `install_mime` set up
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MimeSetup {
+    /// Path of the installed shared-mime-info package
+    pub package: PathBuf,
+    /// Whether the GUI was made the default handler
+    pub associated: bool,
+}
+
+/// Get the path of the shared-mime-info package file
+pub fn mime_package_path() -> Result<PathBuf, SetupError> {
+    Ok(data_dir()?.join("mime/packages").join(MIME_PACKAGE_NAME))
+}
+
+/// Get the path of the GUI desktop entry
+pub fn gui_desktop_path() -> Result<PathBuf, SetupError> {
+    Ok(data_dir()?.join("applications").join(GUI_DESKTOP_ID))
+}
+
+/// Render the GUI desktop entry for a GUI binary at `exec`
+pub fn render_gui_desktop_entry(exec: &Path) -> String {
+    let entry = GUI_DESKTOP_ENTRY.replace(
+        "Exec=appimage-auto-gui",
+        &format!(
+            "Exec=\"{}\"",
+            exec.display().to_string().replace('"', "\\\"")
+        ),
+    );
+    i18n::localize_desktop_entry(&entry)
+}
+
+/// Register the AppImage MIME types and, if the GUI is available, make it the
+/// default handler.
+///
+/// An existing GUI desktop entry is left as is; one is only written when
+/// missing and `gui_exec` is given.
+pub fn install_mime(gui_exec: Option<&Path>) -> Result<MimeSetup, SetupError> {
+    let package = mime_package_path()?;
+    if let Some(parent) = package.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(&package, MIME_PACKAGE)?;
+    info!("Wrote {:?}", package);
+    update_mime_database()?;
+
+    let desktop = gui_desktop_path()?;
+    if !desktop.exists()
+        && let Some(exec) = gui_exec
+    {
+        if let Some(parent) = desktop.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&desktop, render_gui_desktop_entry(exec))?;
+        info!("Wrote {:?}", desktop);
+    }
+
+    let associated = desktop.exists();
+    if associated {
+        update_mimeapps(true)?;
+        // Only speeds up lookups, so a missing tool is not an error
+        if let Some(dir) = desktop.parent()
+            && let Err(e) = run("update-desktop-database", &[dir.as_os_str()])
+        {
+            debug!("{}", e);
+        }
+    }
+
+    Ok(MimeSetup {
+        package,
+        associated,
+    })
+}
+
+/// Remove the MIME registration and the GUI's default handler association
+pub fn uninstall_mime() -> Result<(), SetupError> {
+    update_mimeapps(false)?;
+
+    let package = mime_package_path()?;
+    if package.exists() {
+        fs::remove_file(&package)?;
+        info!("Removed {:?}", package);
+        update_mime_database()?;
+    }
+    Ok(())
+}
+
+/// Install the Dolphin service menu and Nautilus script that integrate the
+/// selected AppImages by running the CLI at `cli`.
+///
+/// Returns the installed files.
+pub fn install_file_manager(cli: &Path) -> Result<Vec<PathBuf>, SetupError> {
+    let data = data_dir()?;
+    let files = [
+        (data.join(SERVICE_MENU), render_service_menu(cli)),
+        (data.join(NAUTILUS_SCRIPT), render_nautilus_script(cli)),
+    ];
+
+    let mut installed = Vec::new();
+    for (path, content) in files {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&path, content)?;
+        // Dolphin only runs service menus that are executable
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755))?;
+        info!("Wrote {:?}", path);
+        installed.push(path);
+    }
+    Ok(installed)
+}
+
+/// Remove the file manager context menu entries.
+///
+/// Returns the files that were removed.
+pub fn uninstall_file_manager() -> Result<Vec<PathBuf>, SetupError> {
+    let data = data_dir()?;
+    let mut removed = Vec::new();
+    for path in [data.join(SERVICE_MENU), data.join(NAUTILUS_SCRIPT)] {
+        match fs::remove_file(&path) {
+            Ok(()) => {
+                info!("Removed {:?}", path);
+                removed.push(path);
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Ok(removed)
+}
+
+/// Add a directory to the GTK bookmarks shown in the sidebar of Nautilus,
+/// Thunar and the file chooser.
+///
+/// Returns whether a bookmark was added (false if it already existed).
+pub fn add_bookmark(dir: &Path) -> Result<bool, SetupError> {
+    let dirs = directories::BaseDirs::new().ok_or(SetupError::NoDataDir)?;
+    let path = dirs.config_dir().join(GTK_BOOKMARKS);
+    let content = match fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e.into()),
+    };
+
+    let Some(updated) = add_bookmark_line(&content, dir) else {
+        return Ok(false);
+    };
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(&path, updated)?;
+    info!("Bookmarked {:?} in {:?}", dir, path);
+    Ok(true)
+}
+
+/// Append a bookmark for `dir` to the contents of a GTK bookmarks file, or
+/// None if it is already bookmarked
+pub fn add_bookmark_line(content: &str, dir: &Path) -> Option<String> {
+    let uri = file_uri(dir);
+    if content
+        .lines()
+        .any(|line| line.split(' ').next() == Some(uri.as_str()))
+    {
+        return None;
+    }
+    let mut updated = content.to_string();
+    if !updated.is_empty() && !updated.ends_with('\n') {
+        updated.push('\n');
+    }
+    updated.push_str(&uri);
+    updated.push('\n');
+    Some(updated)
+}
+
+/// `file://` URI of an absolute path, percent-encoding all but unreserved
+/// characters and slashes
+fn file_uri(path: &Path) -> String {
+    let mut uri = String::from("file://");
+    for byte in path.as_os_str().as_encoded_bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => {
+                uri.push(*byte as char)
+            }
+            _ => uri.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    uri
+}
+
+/// Render the Dolphin service menu for the CLI at `cli`
+pub fn render_service_menu(cli: &Path) -> String {
+    let entry = SERVICE_MENU_ENTRY.replace(
+        "Exec=appimage-auto",
+        &format!(
+            "Exec=\"{}\"",
+            cli.display().to_string().replace('"', "\\\"")
+        ),
+    );
+    i18n::localize_desktop_entry(&entry)
+}
+
+/// Render the Nautilus script for the CLI at `cli`
+pub fn render_nautilus_script(cli: &Path) -> String {
+    format!(
+        "#!/bin/sh\n\
+         # Installed by `appimage-auto setup file-manager`\n\
+         set -f\n\
+         IFS='\n'\n\
+         for path in $NAUTILUS_SCRIPT_SELECTED_FILE_PATHS; do\n    \
+             '{}' integrate \"$path\"\n\
+         done\n",
+        cli.display().to_string().replace('\'', "'\\''")
+    )
+}
+
+/// Set (or, with `set == false`, remove) `desktop_id` as the default handler
+/// for `mime_types` in the contents of a `mimeapps.list` file.
+///
+/// Removing only drops entries that name `desktop_id` first, so handlers the
+/// user picked later are kept.
+pub fn update_default_handlers(
+    content: &str,
+    desktop_id: &str,
+    mime_types: &[&str],
+    set: bool,
+) -> String {
+    let mut lines: Vec<String> = content.lines().map(str::to_string).collect();
+
+    let header = match lines.iter().position(|l| l.trim() == DEFAULT_APPLICATIONS) {
+        Some(index) => index,
+        None if !set => return content.to_string(),
+        None => {
+            if lines.last().is_some_and(|l| !l.trim().is_empty()) {
+                lines.push(String::new());
+            }
+            lines.push(DEFAULT_APPLICATIONS.to_string());
+            lines.len() - 1
+        }
+    };
+    let end = lines[header + 1..]
+        .iter()
+        .position(|l| l.trim_start().starts_with('['))
+        .map_or(lines.len(), |offset| header + 1 + offset);
+
+    let mut section: Vec<String> = lines.drain(header + 1..end).collect();
+    section.retain(|line| {
+        let Some((key, value)) = line.split_once('=') else {
+            return true;
+        };
+        if !mime_types.contains(&key.trim()) {
+            return true;
+        }
+        !set && value.trim().split(';').next() != Some(desktop_id)
+    });
+    // Keep blank lines separating the next section at the end
+    let trailing = section
+        .iter()
+        .rev()
+        .take_while(|l| l.trim().is_empty())
+        .count();
+    let at = section.len() - trailing;
+    if set {
+        for (i, mime_type) in mime_types.iter().enumerate() {
+            section.insert(at + i, format!("{}={};", mime_type, desktop_id));
+        }
+    }
+    lines.splice(header + 1..header + 1, section);
+
+    let mut result = lines.join("\n");
+    result.push('\n');
+    result
+}
+
+/// Update the user's `mimeapps.list` for the GUI
+fn update_mimeapps(set: bool) -> Result<(), SetupError> {
+    let dirs = directories::BaseDirs::new().ok_or(SetupError::NoDataDir)?;
+    let path = dirs.config_dir().join("mimeapps.list");
+    let content = match fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            if !set {
+                return Ok(());
+            }
+            String::new()
+        }
+        Err(e) => return Err(e.into()),
+    };
+
+    let updated = update_default_handlers(&content, GUI_DESKTOP_ID, &MIME_TYPES, set);
+    if updated != content {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&path, updated)?;
+        info!("Updated {:?}", path);
+    }
+    Ok(())
+}
+
+/// Rebuild the user's MIME database after changing a package
+fn update_mime_database() -> Result<(), SetupError> {
+    let dir = data_dir()?.join("mime");
+    run("update-mime-database", &[dir.as_os_str()])
+}
+
+/// Run a command, turning a failure into an error with its stderr
+fn run(program: &str, args: &[&std::ffi::OsStr]) -> Result<(), SetupError> {
+    let output = Command::new(program).args(args).output()?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(SetupError::Command(
+            program.to_string(),
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        ))
+    }
+}
+
+/// Get the user's data directory (`~/.local/share`)
+fn data_dir() -> Result<PathBuf, SetupError> {
+    let dirs = directories::BaseDirs::new().ok_or(SetupError::NoDataDir)?;
+    Ok(dirs.data_dir().to_path_buf())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_gui_desktop_entry() {
+        let entry = render_gui_desktop_entry(Path::new("/home/me/bin/appimage-auto-gui"));
+        assert!(entry.contains("Exec=\"/home/me/bin/appimage-auto-gui\" %F\n"));
+        assert!(
+            entry.contains("MimeType=application/vnd.appimage;application/x-iso9660-appimage;")
+        );
+    }
+
+    #[test]
+    fn test_render_file_manager_entries() {
+        let cli = Path::new("/home/me/bin/appimage-auto");
+        let menu = render_service_menu(cli);
+        assert!(menu.contains("Exec=\"/home/me/bin/appimage-auto\" integrate %f\n"));
+        assert!(menu.contains("MimeType=application/vnd.appimage;application/x-iso9660-appimage;"));
+
+        let script = render_nautilus_script(Path::new("/home/me/it's/appimage-auto"));
+        assert!(script.starts_with("#!/bin/sh\n"));
+        assert!(script.contains("IFS='\n'\n"));
+        assert!(script.contains("    '/home/me/it'\\''s/appimage-auto' integrate \"$path\"\n"));
+    }
+
+    #[test]
+    fn test_add_bookmark_line() {
+        let dir = Path::new("/home/me/My Apps");
+        let added = add_bookmark_line("file:///home/me/Music Music", dir).unwrap();
+        assert_eq!(
+            added,
+            "file:///home/me/Music Music\nfile:///home/me/My%20Apps\n"
+        );
+        assert_eq!(add_bookmark_line(&added, dir), None);
+        assert_eq!(
+            add_bookmark_line("", Path::new("/home/me/Applications")).unwrap(),
+            "file:///home/me/Applications\n"
+        );
+    }
+
+    #[test]
+    fn test_update_default_handlers() {
+        let types = ["a/b", "c/d"];
+        let content = "[Default Applications]\na/b=other.desktop;\ntext/plain=edit.desktop;\n\n[Added Associations]\nx/y=z.desktop;\n";
+
+        let set = update_default_handlers(content, "gui.desktop", &types, true);
+        assert_eq!(
+            set,
+            "[Default Applications]\ntext/plain=edit.desktop;\na/b=gui.desktop;\nc/d=gui.desktop;\n\n[Added Associations]\nx/y=z.desktop;\n"
+        );
+        // Setting again changes nothing
+        assert_eq!(
+            update_default_handlers(&set, "gui.desktop", &types, true),
+            set
+        );
+
+        let unset = update_default_handlers(&set, "gui.desktop", &types, false);
+        assert_eq!(
+            unset,
+            "[Default Applications]\ntext/plain=edit.desktop;\n\n[Added Associations]\nx/y=z.desktop;\n"
+        );
+
+        assert_eq!(
+            update_default_handlers("", "gui.desktop", &types, true),
+            "[Default Applications]\na/b=gui.desktop;\nc/d=gui.desktop;\n"
+        );
+    }
+}
@@ -0,0 +1,463 @@
+//! AppImage signature verification and the trust store.
+//!
+//! Type 2 AppImages can be signed with `appimagetool --sign`, which embeds a
+//! detached GPG signature in the `.sha256_sig` ELF section (and the public
+//! key in `.sig_key`). The signature covers the SHA-256 hex digest of the
+//! file with both sections zeroed. Signatures are checked with `gpg` against
+//! the trust store, a keyring in its own GnuPG home
+//! (`~/.local/share/appimage-auto/gnupg`) kept apart from the user's keys.
+//!
+//! Keys trusted on first use never go into the trust store, where they would
+//! be trusted for every app. The signature is checked against the key
+//! embedded in the AppImage in a throwaway keyring instead, and the key's
+//! fingerprint is recorded for that app only (in `app-keys.json`); later
+//! releases of the app must be signed with the same key.
+
+use crate::appimage;
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+use thiserror::Error;
+use tracing::{debug, info};
+
+#[derive(Error, Debug)]
+pub enum SignatureError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("AppImage error: {0}")]
+    AppImage(#[from] appimage::AppImageError),
+    #[error("JSON error: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("No data directory found")]
+    NoDataDir,
+    #[error("{0} failed: {1}")]
+    Command(String, String),
+    #[error("No key found for {0}")]
+    KeyNotFound(String),
+    #[error("{0} matches several keys: {1}")]
+    AmbiguousKey(String, String),
+}
+
+/// Outcome of checking an AppImage's signature
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Verification {
+    /// Has no signature
+    Unsigned,
+    /// Good signature by a trusted key
+    Trusted { fingerprint: String },
+    /// Signed by a key that is not in the trust store
+    UnknownKey { key_id: String },
+    /// Signed by a key that has expired
+    ExpiredKey { fingerprint: String },
+    /// Signed by a key that has been revoked
+    RevokedKey { fingerprint: String },
+    /// The signature does not match the file
+    Bad,
+}
+
+/// A key in the trust store
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TrustedKey {
+    pub fingerprint: String,
+    /// Primary user ID, e.g. `Jane Doe <jane@example.org>`
+    pub user_id: Option<String>,
+}
+
+/// GnuPG home of the trust store
+pub fn trust_store_dir() -> Result<PathBuf, SignatureError> {
+    Ok(data_dir()?.join("gnupg"))
+}
+
+fn data_dir() -> Result<PathBuf, SignatureError> {
+    let dirs =
+        directories::ProjectDirs::from("", "", "appimage-auto").ok_or(SignatureError::NoDataDir)?;
+    Ok(dirs.data_dir().to_path_buf())
+}
+
+/// Check an AppImage's signature against the trust store
+pub fn verify(path: &Path) -> Result<Verification, SignatureError> {
+    verify_in(gpg()?, path)
+}
+
+/// Check an AppImage's signature against its own embedded key only, in a
+/// throwaway keyring. A `Trusted` result just means the key made the
+/// signature; whether to trust the key is up to the caller.
+pub fn verify_with_key(path: &Path, key: &[u8]) -> Result<Verification, SignatureError> {
+    let home = tempfile::TempDir::new()?;
+    import_into(gpg_in(home.path()), key)?;
+    verify_in(gpg_in(home.path()), path)
+}
+
+fn verify_in(mut gpg: Command, path: &Path) -> Result<Verification, SignatureError> {
+    let Some(signature) = appimage::embedded_signature(path)? else {
+        return Ok(Verification::Unsigned);
+    };
+
+    let temp_dir = tempfile::TempDir::new()?;
+    let signature_path = temp_dir.path().join("signature.asc");
+    let digest_path = temp_dir.path().join("digest");
+    fs::write(&signature_path, signature)?;
+    fs::write(&digest_path, signed_digest(path)?)?;
+
+    // A failed verification exits non-zero; the status lines tell why
+    let output = gpg
+        .args(["--status-fd", "1", "--verify"])
+        .arg(&signature_path)
+        .arg(&digest_path)
+        .output()?;
+    let verification = parse_verify_status(&String::from_utf8_lossy(&output.stdout));
+    debug!("Signature of {:?}: {:?}", path, verification);
+    Ok(verification)
+}
+
+/// Public key embedded in a signed AppImage, ASCII-armored
+pub fn embedded_key(path: &Path) -> Result<Option<String>, SignatureError> {
+    Ok(appimage::embedded_signing_key(path)?)
+}
+
+/// Add keys (ASCII-armored or binary) to the trust store, returning their
+/// fingerprints
+pub fn import_key(key: &[u8]) -> Result<Vec<String>, SignatureError> {
+    let fingerprints = import_into(gpg()?, key)?;
+    info!("Trusted keys: {}", fingerprints.join(", "));
+    Ok(fingerprints)
+}
+
+fn import_into(mut gpg: Command, key: &[u8]) -> Result<Vec<String>, SignatureError> {
+    let mut child = gpg
+        .args(["--status-fd", "1", "--import"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(key)?;
+    }
+    let output = checked("gpg --import", child.wait_with_output()?)?;
+
+    let mut fingerprints: Vec<String> = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| line.strip_prefix("[GNUPG:] IMPORT_OK "))
+        .filter_map(|rest| rest.split_whitespace().nth(1))
+        .map(str::to_string)
+        .collect();
+    fingerprints.dedup();
+    if fingerprints.is_empty() {
+        return Err(SignatureError::KeyNotFound("the given data".to_string()));
+    }
+    Ok(fingerprints)
+}
+
+/// Add a key from the user's own GnuPG keyring to the trust store
+pub fn import_user_key(key_id: &str) -> Result<Vec<String>, SignatureError> {
+    let output = checked(
+        "gpg --export",
+        Command::new("gpg")
+            .args(["--batch", "--armor", "--export", "--", key_id])
+            .output()?,
+    )?;
+    if output.stdout.is_empty() {
+        return Err(SignatureError::KeyNotFound(key_id.to_string()));
+    }
+    import_key(&output.stdout)
+}
+
+/// Keys in the trust store
+pub fn list_keys() -> Result<Vec<TrustedKey>, SignatureError> {
+    let output = checked(
+        "gpg --list-keys",
+        gpg()?.args(["--with-colons", "--list-keys"]).output()?,
+    )?;
+    Ok(parse_key_list(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// Remove a key from the trust store by fingerprint or key ID (a suffix of
+/// the fingerprint), returning the removed key
+pub fn remove_key(key_id: &str) -> Result<TrustedKey, SignatureError> {
+    let wanted = key_id.trim_start_matches("0x").to_uppercase();
+    let matches: Vec<TrustedKey> = list_keys()?
+        .into_iter()
+        .filter(|key| !wanted.is_empty() && key.fingerprint.ends_with(&wanted))
+        .collect();
+    let key = match <[TrustedKey; 1]>::try_from(matches) {
+        Ok([key]) => key,
+        Err(matches) if matches.is_empty() => {
+            return Err(SignatureError::KeyNotFound(key_id.to_string()));
+        }
+        Err(matches) => {
+            let fingerprints: Vec<_> = matches.into_iter().map(|k| k.fingerprint).collect();
+            return Err(SignatureError::AmbiguousKey(
+                key_id.to_string(),
+                fingerprints.join(", "),
+            ));
+        }
+    };
+
+    checked(
+        "gpg --delete-keys",
+        gpg()?
+            .args(["--yes", "--delete-keys", "--", &key.fingerprint])
+            .output()?,
+    )?;
+    info!("Removed key {} from the trust store", key.fingerprint);
+    Ok(key)
+}
+
+/// Keys trusted on first use, by app
+pub fn app_keys() -> Result<BTreeMap<String, String>, SignatureError> {
+    let path = app_keys_path()?;
+    if !path.exists() {
+        return Ok(BTreeMap::new());
+    }
+    Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+}
+
+/// Fingerprint of the key an app was trusted with on first use
+pub fn app_key(app: &str) -> Result<Option<String>, SignatureError> {
+    Ok(app_keys()?.remove(app))
+}
+
+/// Record the key an app is signed with, trusting it for that app only
+pub fn record_app_key(app: &str, fingerprint: &str) -> Result<(), SignatureError> {
+    let mut keys = app_keys()?;
+    keys.insert(app.to_string(), fingerprint.to_string());
+    let path = app_keys_path()?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, serde_json::to_string_pretty(&keys)?)?;
+    info!("Trusting key {} for {}", fingerprint, app);
+    Ok(())
+}
+
+/// Forget the key an app was trusted with on first use; returns whether one
+/// was recorded
+pub fn forget_app_key(app: &str) -> Result<bool, SignatureError> {
+    let mut keys = app_keys()?;
+    if keys.remove(app).is_none() {
+        return Ok(false);
+    }
+    fs::write(app_keys_path()?, serde_json::to_string_pretty(&keys)?)?;
+    Ok(true)
+}
+
+fn app_keys_path() -> Result<PathBuf, SignatureError> {
+    Ok(data_dir()?.join("app-keys.json"))
+}
+
+/// `gpg` working on the trust store
+fn gpg() -> Result<Command, SignatureError> {
+    let home = trust_store_dir()?;
+    if !home.exists() {
+        use std::os::unix::fs::DirBuilderExt;
+        fs::DirBuilder::new()
+            .recursive(true)
+            .mode(0o700)
+            .create(&home)?;
+    }
+    Ok(gpg_in(&home))
+}
+
+/// `gpg` working on the keyring in `home`
+fn gpg_in(home: &Path) -> Command {
+    let mut command = Command::new("gpg");
+    command
+        .arg("--homedir")
+        .arg(home)
+        .args(["--batch", "--no-tty", "--trust-model", "always"]);
+    command
+}
+
+/// Turn a failed command into an error with its stderr
+fn checked(command: &str, output: Output) -> Result<Output, SignatureError> {
+    if output.status.success() {
+        Ok(output)
+    } else {
+        Err(SignatureError::Command(
+            command.to_string(),
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        ))
+    }
+}
+
+/// SHA-256 hex digest the signature was made over: that of the file with
+/// the signature and key sections zeroed
+fn signed_digest(path: &Path) -> Result<String, SignatureError> {
+    let mut skip = Vec::new();
+    for name in [appimage::SIGNATURE_SECTION, appimage::KEY_SECTION] {
+        if let Some((offset, size)) = appimage::elf_section(path, name)? {
+            skip.push(offset..offset.saturating_add(size));
+        }
+    }
+
+    let mut child = Command::new("sha256sum")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    if let Some(mut stdin) = child.stdin.take() {
+        let mut file = File::open(path)?;
+        let mut buffer = vec![0u8; 64 * 1024];
+        let mut position = 0u64;
+        loop {
+            let n = file.read(&mut buffer)?;
+            if n == 0 {
+                break;
+            }
+            for (i, byte) in buffer[..n].iter_mut().enumerate() {
+                let at = position + i as u64;
+                if skip.iter().any(|range| range.contains(&at)) {
+                    *byte = 0;
+                }
+            }
+            stdin.write_all(&buffer[..n])?;
+            position += n as u64;
+        }
+    }
+    let output = checked("sha256sum", child.wait_with_output()?)?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    Ok(stdout
+        .split_whitespace()
+        .next()
+        .unwrap_or_default()
+        .to_string())
+}
+
+/// Outcome of `gpg --verify` from its `--status-fd` lines.
+///
+/// gpg still reports `VALIDSIG` for a signature by an expired or revoked
+/// key, next to `EXPKEYSIG` or `REVKEYSIG`, so all lines are read first.
+fn parse_verify_status(status: &str) -> Verification {
+    let mut valid = None;
+    let mut expired = false;
+    let mut revoked = false;
+    let mut unknown = None;
+    for line in status.lines() {
+        let mut fields = line.strip_prefix("[GNUPG:] ").unwrap_or("").split(' ');
+        match (fields.next(), fields.next()) {
+            (Some("VALIDSIG"), Some(fingerprint)) => valid = Some(fingerprint.to_string()),
+            (Some("BADSIG" | "EXPSIG"), _) => return Verification::Bad,
+            (Some("EXPKEYSIG"), _) => expired = true,
+            (Some("REVKEYSIG"), _) => revoked = true,
+            (Some("NO_PUBKEY"), Some(key_id)) => unknown = Some(key_id.to_string()),
+            _ => {}
+        }
+    }
+    match (valid, unknown) {
+        (Some(fingerprint), _) if revoked => Verification::RevokedKey { fingerprint },
+        (Some(fingerprint), _) if expired => Verification::ExpiredKey { fingerprint },
+        (Some(fingerprint), _) => Verification::Trusted { fingerprint },
+        (None, Some(key_id)) => Verification::UnknownKey { key_id },
+        (None, None) => Verification::Bad,
+    }
+}
+
+/// Keys from `gpg --with-colons --list-keys` output
+fn parse_key_list(listing: &str) -> Vec<TrustedKey> {
+    let mut keys: Vec<TrustedKey> = Vec::new();
+    // Subkeys have their own fpr records, which are skipped
+    let mut in_primary = false;
+    for line in listing.lines() {
+        let fields: Vec<&str> = line.split(':').collect();
+        match fields.first().copied() {
+            Some("pub") => in_primary = true,
+            Some("sub") => in_primary = false,
+            Some("fpr") if in_primary => {
+                if let Some(fingerprint) = fields.get(9) {
+                    keys.push(TrustedKey {
+                        fingerprint: fingerprint.to_string(),
+                        user_id: None,
+                    });
+                }
+                in_primary = false;
+            }
+            Some("uid") => {
+                if let Some(key) = keys.last_mut()
+                    && key.user_id.is_none()
+                {
+                    key.user_id = fields.get(9).map(|uid| uid.replace("\\x3a", ":"));
+                }
+            }
+            _ => {}
+        }
+    }
+    keys
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_verify_status() {
+        let good = "[GNUPG:] NEWSIG\n\
+                    [GNUPG:] GOODSIG 1234567890ABCDEF Jane Doe\n\
+                    [GNUPG:] VALIDSIG 0123456789ABCDEF0123456789ABCDEF01234567 2024-01-01\n";
+        assert_eq!(
+            parse_verify_status(good),
+            Verification::Trusted {
+                fingerprint: "0123456789ABCDEF0123456789ABCDEF01234567".to_string()
+            }
+        );
+
+        let unknown = "[GNUPG:] NEWSIG\n\
+                       [GNUPG:] ERRSIG 1234567890ABCDEF 1 10 00 1700000000 9\n\
+                       [GNUPG:] NO_PUBKEY 1234567890ABCDEF\n";
+        assert_eq!(
+            parse_verify_status(unknown),
+            Verification::UnknownKey {
+                key_id: "1234567890ABCDEF".to_string()
+            }
+        );
+
+        assert_eq!(
+            parse_verify_status("[GNUPG:] BADSIG 1234567890ABCDEF Jane Doe\n"),
+            Verification::Bad
+        );
+        assert_eq!(parse_verify_status(""), Verification::Bad);
+
+        let revoked = "[GNUPG:] NEWSIG\n\
+                       [GNUPG:] REVKEYSIG 1234567890ABCDEF Jane Doe\n\
+                       [GNUPG:] VALIDSIG 0123456789ABCDEF0123456789ABCDEF01234567 2024-01-01\n";
+        assert_eq!(
+            parse_verify_status(revoked),
+            Verification::RevokedKey {
+                fingerprint: "0123456789ABCDEF0123456789ABCDEF01234567".to_string()
+            }
+        );
+        let expired = revoked.replace("REVKEYSIG", "EXPKEYSIG");
+        assert!(matches!(
+            parse_verify_status(&expired),
+            Verification::ExpiredKey { .. }
+        ));
+    }
+
+    #[test]
+    fn test_parse_key_list() {
+        let listing = "\
+tru::1:1700000000:0:3:1:5
+pub:-:255:22:1234567890ABCDEF:1700000000:::-:::scESC::::::23::0:
+fpr:::::::::0123456789ABCDEF0123456789ABCDEF01234567:
+uid:-::::1700000000::HASH::Jane Doe <jane@example.org>::::::::::0:
+sub:-:255:18:FEDCBA0987654321:1700000000::::::e::::::23:
+fpr:::::::::FEDCBA0987654321FEDCBA0987654321FEDCBA09:
+pub:-:255:22:AAAAAAAAAAAAAAAA:1700000000:::-:::scESC::::::23::0:
+fpr:::::::::AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA:
+";
+        assert_eq!(
+            parse_key_list(listing),
+            [
+                TrustedKey {
+                    fingerprint: "0123456789ABCDEF0123456789ABCDEF01234567".to_string(),
+                    user_id: Some("Jane Doe <jane@example.org>".to_string()),
+                },
+                TrustedKey {
+                    fingerprint: "AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA".to_string(),
+                    user_id: None,
+                },
+            ]
+        );
+    }
+}
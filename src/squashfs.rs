@@ -0,0 +1,679 @@
+//! Reading SquashFS images natively.
+//!
+//! Lists the entries of an AppImage's SquashFS image and extracts selected
+//! ones without running the AppImage. Images compressed with gzip or zstd
+//! (or not at all) are read; for other compressors (xz, lzo, lz4) opening
+//! fails with [`SquashfsError::UnsupportedCompression`], so callers can fall
+//! back to the AppImage's own `--appimage-extract`.
+
+use flate2::read::ZlibDecoder;
+use ruzstd::decoding::StreamingDecoder;
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use thiserror::Error;
+
+/// Superblock magic ("hsqs" little-endian)
+const MAGIC: [u8; 4] = *b"hsqs";
+
+const SUPERBLOCK_SIZE: usize = 96;
+
+/// Largest (uncompressed) metadata block
+const METADATA_SIZE: usize = 8192;
+
+/// Set in a metadata block header when the block is stored uncompressed
+const METADATA_UNCOMPRESSED: u16 = 0x8000;
+
+/// Set in a data or fragment block size when the block is stored uncompressed
+const DATA_UNCOMPRESSED: u32 = 1 << 24;
+
+/// Fragment index of files without a fragment
+const NO_FRAGMENT: u32 = u32::MAX;
+
+/// Entries in a metadata block of the fragment table
+const FRAGMENTS_PER_BLOCK: u32 = 512;
+
+/// Directories nested deeper than this are not listed
+const MAX_DEPTH: usize = 64;
+
+/// Images with more entries than this are rejected
+const MAX_ENTRIES: usize = 1_000_000;
+
+#[derive(Error, Debug)]
+pub enum SquashfsError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Invalid SquashFS image: {0}")]
+    Invalid(&'static str),
+    #[error("Unsupported SquashFS compression: {0}")]
+    UnsupportedCompression(&'static str),
+}
+
+/// What an entry of an image is
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EntryKind {
+    Directory,
+    File,
+    /// A symbolic link, with its target
+    Symlink(String),
+    /// A device, FIFO or socket
+    Other,
+}
+
+/// A file, directory or link in an image
+#[derive(Debug, Clone)]
+pub struct Entry {
+    /// Path from the image root, e.g. `usr/share/icons/hicolor`
+    pub path: String,
+    pub kind: EntryKind,
+    /// Size of a file's contents
+    pub size: u64,
+    /// Where a file's contents are
+    data: Option<FileData>,
+}
+
+/// Location of a file's contents
+#[derive(Debug, Clone)]
+struct FileData {
+    blocks_start: u64,
+    block_sizes: Vec<u32>,
+    /// Fragment index and offset in it of the file's tail
+    fragment: Option<(u32, u32)>,
+}
+
+/// Location of a directory's listing
+#[derive(Debug, Clone, Copy)]
+struct Listing {
+    start_block: u64,
+    offset: usize,
+    size: usize,
+}
+
+/// A parsed inode
+enum Inode {
+    Directory(Listing),
+    File(u64, FileData),
+    Symlink(String),
+    Other,
+}
+
+/// A position in a metadata table: the block's position in the image and
+/// an offset in its contents
+#[derive(Debug, Clone, Copy)]
+struct Cursor {
+    block: u64,
+    offset: usize,
+}
+
+/// Compressors whose blocks can be read
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Compression {
+    /// zlib streams, despite the name
+    Gzip,
+    Zstd,
+}
+
+/// A SquashFS 4.0 image, possibly inside a larger file
+pub struct Squashfs {
+    file: File,
+    /// Where the image starts in the file
+    offset: u64,
+    compression: Compression,
+    block_size: u32,
+    root_inode: u64,
+    inode_table: u64,
+    directory_table: u64,
+    fragment_table: u64,
+    fragment_count: u32,
+    /// Metadata blocks read so far, by position: contents and the position
+    /// of the next block
+    metadata: HashMap<u64, (Vec<u8>, u64)>,
+}
+
+impl Squashfs {
+    /// Open the image starting at `offset` in the file at `path`
+    pub fn open(path: &Path, offset: u64) -> Result<Self, SquashfsError> {
+        let mut file = File::open(path)?;
+        let mut superblock = [0u8; SUPERBLOCK_SIZE];
+        file.seek(SeekFrom::Start(offset))?;
+        file.read_exact(&mut superblock)?;
+
+        if superblock[0..4] != MAGIC {
+            return Err(SquashfsError::Invalid("bad magic"));
+        }
+        let u16_at = |at: usize| u16::from_le_bytes([superblock[at], superblock[at + 1]]);
+        let u32_at = |at: usize| u32::from_le_bytes(superblock[at..at + 4].try_into().unwrap());
+        let u64_at = |at: usize| u64::from_le_bytes(superblock[at..at + 8].try_into().unwrap());
+
+        let block_size = u32_at(12);
+        let block_log = u16_at(22);
+        if u16_at(28) != 4 || !(12..=20).contains(&block_log) || block_size != 1 << block_log {
+            return Err(SquashfsError::Invalid("unsupported version or block size"));
+        }
+        let compression = match u16_at(20) {
+            1 => Compression::Gzip,
+            6 => Compression::Zstd,
+            2 => return Err(SquashfsError::UnsupportedCompression("lzma")),
+            3 => return Err(SquashfsError::UnsupportedCompression("lzo")),
+            4 => return Err(SquashfsError::UnsupportedCompression("xz")),
+            5 => return Err(SquashfsError::UnsupportedCompression("lz4")),
+            _ => return Err(SquashfsError::UnsupportedCompression("unknown")),
+        };
+
+        Ok(Self {
+            file,
+            offset,
+            compression,
+            block_size,
+            root_inode: u64_at(32),
+            inode_table: u64_at(64),
+            directory_table: u64_at(72),
+            fragment_table: u64_at(80),
+            fragment_count: u32_at(16),
+            metadata: HashMap::new(),
+        })
+    }
+
+    /// All entries of the image, parents before their contents
+    pub fn entries(&mut self) -> Result<Vec<Entry>, SquashfsError> {
+        let Inode::Directory(root) = self.inode(self.root_inode)? else {
+            return Err(SquashfsError::Invalid("root is not a directory"));
+        };
+        let mut entries = Vec::new();
+        self.list(root, "", 0, &mut entries)?;
+        // A crafted image could list a name twice, e.g. as a symlink and
+        // then as a file written through it
+        let mut paths = HashSet::new();
+        if !entries
+            .iter()
+            .all(|entry| paths.insert(entry.path.as_str()))
+        {
+            return Err(SquashfsError::Invalid("duplicate path"));
+        }
+        Ok(entries)
+    }
+
+    /// Write an entry to `dest`: create a directory, write a file's
+    /// contents, or make a symbolic link. Devices and the like are skipped.
+    pub fn extract(&mut self, entry: &Entry, dest: &Path) -> Result<(), SquashfsError> {
+        if let Some(parent) = dest.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        match &entry.kind {
+            EntryKind::Directory => fs::create_dir_all(dest)?,
+            EntryKind::Symlink(target) => {
+                if fs::symlink_metadata(dest).is_ok() {
+                    fs::remove_file(dest)?;
+                }
+                std::os::unix::fs::symlink(target, dest)?;
+            }
+            EntryKind::File => {
+                let contents = match &entry.data {
+                    Some(data) => self.read_file(entry.size, data)?,
+                    None => Vec::new(),
+                };
+                // Replace whatever is there instead of writing through a
+                // symlink
+                if fs::symlink_metadata(dest).is_ok() {
+                    fs::remove_file(dest)?;
+                }
+                File::options()
+                    .write(true)
+                    .create_new(true)
+                    .open(dest)?
+                    .write_all(&contents)?;
+            }
+            EntryKind::Other => {}
+        }
+        Ok(())
+    }
+
+    /// Add the entries of a directory (and below) to `entries`
+    fn list(
+        &mut self,
+        listing: Listing,
+        prefix: &str,
+        depth: usize,
+        entries: &mut Vec<Entry>,
+    ) -> Result<(), SquashfsError> {
+        // The size counts the implicit "." and ".." entries
+        let Some(size) = listing.size.checked_sub(3) else {
+            return Ok(());
+        };
+        let mut cursor = Cursor {
+            block: self.directory_table + listing.start_block,
+            offset: listing.offset,
+        };
+        let bytes = self.read_metadata(&mut cursor, size)?;
+
+        let mut rest = bytes.as_slice();
+        while !rest.is_empty() {
+            let header = take(&mut rest, 12)?;
+            let count = le_u32(header, 0) as usize + 1;
+            let start = u64::from(le_u32(header, 4));
+            if count > 256 {
+                return Err(SquashfsError::Invalid("directory header too large"));
+            }
+
+            for _ in 0..count {
+                let fields = take(&mut rest, 8)?;
+                let offset = u64::from(le_u16(fields, 0));
+                let name_size = usize::from(le_u16(fields, 6)) + 1;
+                let name = std::str::from_utf8(take(&mut rest, name_size)?)
+                    .map_err(|_| SquashfsError::Invalid("file name is not UTF-8"))?;
+                if name == "." || name == ".." || name.contains(['/', '\0']) {
+                    return Err(SquashfsError::Invalid("bad file name"));
+                }
+                if entries.len() >= MAX_ENTRIES {
+                    return Err(SquashfsError::Invalid("too many entries"));
+                }
+
+                let path = format!("{}{}", prefix, name);
+                match self.inode((start << 16) | offset)? {
+                    Inode::Directory(child) => {
+                        entries.push(Entry {
+                            path: path.clone(),
+                            kind: EntryKind::Directory,
+                            size: 0,
+                            data: None,
+                        });
+                        if depth < MAX_DEPTH {
+                            self.list(child, &format!("{}/", path), depth + 1, entries)?;
+                        }
+                    }
+                    Inode::File(size, data) => entries.push(Entry {
+                        path,
+                        kind: EntryKind::File,
+                        size,
+                        data: Some(data),
+                    }),
+                    Inode::Symlink(target) => entries.push(Entry {
+                        path,
+                        kind: EntryKind::Symlink(target),
+                        size: 0,
+                        data: None,
+                    }),
+                    Inode::Other => entries.push(Entry {
+                        path,
+                        kind: EntryKind::Other,
+                        size: 0,
+                        data: None,
+                    }),
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Parse the inode at `reference` (block position in the inode table
+    /// shifted left by 16, plus the offset in the block)
+    fn inode(&mut self, reference: u64) -> Result<Inode, SquashfsError> {
+        let mut cursor = Cursor {
+            block: self.inode_table + (reference >> 16),
+            offset: (reference & 0xFFFF) as usize,
+        };
+        let header = self.read_metadata(&mut cursor, 16)?;
+
+        match le_u16(&header, 0) {
+            // Basic directory
+            1 => {
+                let fields = self.read_metadata(&mut cursor, 16)?;
+                Ok(Inode::Directory(Listing {
+                    start_block: u64::from(le_u32(&fields, 0)),
+                    size: usize::from(le_u16(&fields, 8)),
+                    offset: usize::from(le_u16(&fields, 10)),
+                }))
+            }
+            // Extended directory
+            8 => {
+                let fields = self.read_metadata(&mut cursor, 24)?;
+                Ok(Inode::Directory(Listing {
+                    size: le_u32(&fields, 4) as usize,
+                    start_block: u64::from(le_u32(&fields, 8)),
+                    offset: usize::from(le_u16(&fields, 18)),
+                }))
+            }
+            // Basic file
+            2 => {
+                let fields = self.read_metadata(&mut cursor, 16)?;
+                let blocks_start = u64::from(le_u32(&fields, 0));
+                let fragment = (le_u32(&fields, 4), le_u32(&fields, 8));
+                let size = u64::from(le_u32(&fields, 12));
+                self.file_inode(&mut cursor, blocks_start, size, fragment)
+            }
+            // Extended file
+            9 => {
+                let fields = self.read_metadata(&mut cursor, 40)?;
+                let blocks_start = le_u64(&fields, 0);
+                let size = le_u64(&fields, 8);
+                let fragment = (le_u32(&fields, 28), le_u32(&fields, 32));
+                self.file_inode(&mut cursor, blocks_start, size, fragment)
+            }
+            // Basic and extended symlink
+            3 | 10 => {
+                let fields = self.read_metadata(&mut cursor, 8)?;
+                let target_size = le_u32(&fields, 4) as usize;
+                if target_size > 4096 {
+                    return Err(SquashfsError::Invalid("symlink target too long"));
+                }
+                let target = self.read_metadata(&mut cursor, target_size)?;
+                let target = String::from_utf8(target)
+                    .map_err(|_| SquashfsError::Invalid("symlink target is not UTF-8"))?;
+                Ok(Inode::Symlink(target))
+            }
+            4..=7 | 11..=14 => Ok(Inode::Other),
+            _ => Err(SquashfsError::Invalid("unknown inode type")),
+        }
+    }
+
+    /// Read the block sizes following a file inode
+    fn file_inode(
+        &mut self,
+        cursor: &mut Cursor,
+        blocks_start: u64,
+        size: u64,
+        (fragment_index, fragment_offset): (u32, u32),
+    ) -> Result<Inode, SquashfsError> {
+        let block_size = u64::from(self.block_size);
+        let (blocks, fragment) = if fragment_index == NO_FRAGMENT {
+            (size.div_ceil(block_size), None)
+        } else {
+            (size / block_size, Some((fragment_index, fragment_offset)))
+        };
+        if blocks > 1 << 20 {
+            return Err(SquashfsError::Invalid("file too large"));
+        }
+        let sizes = self.read_metadata(cursor, blocks as usize * 4)?;
+        let block_sizes = sizes.chunks(4).map(|b| le_u32(b, 0)).collect();
+
+        Ok(Inode::File(
+            size,
+            FileData {
+                blocks_start,
+                block_sizes,
+                fragment,
+            },
+        ))
+    }
+
+    /// Read a file's contents: its blocks, then the tail in its fragment
+    fn read_file(&mut self, size: u64, data: &FileData) -> Result<Vec<u8>, SquashfsError> {
+        let block_size = self.block_size as usize;
+        let mut contents = Vec::new();
+        let mut position = data.blocks_start;
+
+        for &stored in &data.block_sizes {
+            let wanted = (size as usize - contents.len()).min(block_size);
+            let on_disk = stored & !DATA_UNCOMPRESSED;
+            if on_disk == 0 {
+                // A sparse block
+                contents.resize(contents.len() + wanted, 0);
+                continue;
+            }
+            let block = self.read_block(position, stored)?;
+            position += u64::from(on_disk);
+            if block.len() < wanted {
+                return Err(SquashfsError::Invalid("short data block"));
+            }
+            contents.extend_from_slice(&block[..wanted]);
+        }
+
+        if let Some((index, offset)) = data.fragment {
+            let tail = size as usize - contents.len();
+            let (start, stored) = self.fragment(index)?;
+            let block = self.read_block(start, stored)?;
+            let offset = offset as usize;
+            let tail = block
+                .get(offset..offset + tail)
+                .ok_or(SquashfsError::Invalid("fragment too short"))?;
+            contents.extend_from_slice(tail);
+        }
+
+        if contents.len() as u64 != size {
+            return Err(SquashfsError::Invalid("file size mismatch"));
+        }
+        Ok(contents)
+    }
+
+    /// Position and stored size of a fragment block
+    fn fragment(&mut self, index: u32) -> Result<(u64, u32), SquashfsError> {
+        if index >= self.fragment_count {
+            return Err(SquashfsError::Invalid("fragment index out of range"));
+        }
+        let mut pointer = [0u8; 8];
+        let at = self.fragment_table + u64::from(index / FRAGMENTS_PER_BLOCK) * 8;
+        self.read_at(at, &mut pointer)?;
+
+        let mut cursor = Cursor {
+            block: u64::from_le_bytes(pointer),
+            offset: (index % FRAGMENTS_PER_BLOCK) as usize * 16,
+        };
+        let entry = self.read_metadata(&mut cursor, 16)?;
+        Ok((le_u64(&entry, 0), le_u32(&entry, 8)))
+    }
+
+    /// Read a data or fragment block stored at `position` with the size
+    /// (and uncompressed flag) `stored`
+    fn read_block(&mut self, position: u64, stored: u32) -> Result<Vec<u8>, SquashfsError> {
+        let on_disk = stored & !DATA_UNCOMPRESSED;
+        if on_disk > self.block_size {
+            return Err(SquashfsError::Invalid("data block too large"));
+        }
+        let mut block = vec![0u8; on_disk as usize];
+        self.read_at(position, &mut block)?;
+        if stored & DATA_UNCOMPRESSED != 0 {
+            Ok(block)
+        } else {
+            decompress(self.compression, &block, self.block_size as usize)
+        }
+    }
+
+    /// Read `len` bytes of a metadata table, advancing `cursor`
+    fn read_metadata(&mut self, cursor: &mut Cursor, len: usize) -> Result<Vec<u8>, SquashfsError> {
+        let mut bytes = Vec::with_capacity(len);
+        while bytes.len() < len {
+            self.load_metadata(cursor.block)?;
+            let (block, next) = &self.metadata[&cursor.block];
+            let available = block
+                .get(cursor.offset..)
+                .ok_or(SquashfsError::Invalid("metadata offset out of range"))?;
+            let wanted = (len - bytes.len()).min(available.len());
+            bytes.extend_from_slice(&available[..wanted]);
+            cursor.offset += wanted;
+            if cursor.offset == block.len() {
+                cursor.block = *next;
+                cursor.offset = 0;
+            }
+        }
+        Ok(bytes)
+    }
+
+    /// Read the metadata block at `position` into the cache
+    fn load_metadata(&mut self, position: u64) -> Result<(), SquashfsError> {
+        if self.metadata.contains_key(&position) {
+            return Ok(());
+        }
+        let mut header = [0u8; 2];
+        self.read_at(position, &mut header)?;
+        let header = u16::from_le_bytes(header);
+        let stored = usize::from(header & !METADATA_UNCOMPRESSED);
+        if stored == 0 || stored > METADATA_SIZE {
+            return Err(SquashfsError::Invalid("bad metadata block"));
+        }
+
+        let mut block = vec![0u8; stored];
+        self.read_at(position + 2, &mut block)?;
+        if header & METADATA_UNCOMPRESSED == 0 {
+            block = decompress(self.compression, &block, METADATA_SIZE)?;
+        }
+        if block.is_empty() {
+            return Err(SquashfsError::Invalid("empty metadata block"));
+        }
+        let next = position + 2 + stored as u64;
+        self.metadata.insert(position, (block, next));
+        Ok(())
+    }
+
+    /// Read bytes at a position in the image
+    fn read_at(&mut self, position: u64, buf: &mut [u8]) -> Result<(), SquashfsError> {
+        self.file.seek(SeekFrom::Start(self.offset + position))?;
+        self.file.read_exact(buf)?;
+        Ok(())
+    }
+}
+
+/// Split `n` bytes off the front of `bytes`
+fn take<'a>(bytes: &mut &'a [u8], n: usize) -> Result<&'a [u8], SquashfsError> {
+    if bytes.len() < n {
+        return Err(SquashfsError::Invalid("truncated directory listing"));
+    }
+    let (head, tail) = bytes.split_at(n);
+    *bytes = tail;
+    Ok(head)
+}
+
+fn le_u16(bytes: &[u8], at: usize) -> u16 {
+    u16::from_le_bytes([bytes[at], bytes[at + 1]])
+}
+
+fn le_u32(bytes: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes(bytes[at..at + 4].try_into().unwrap())
+}
+
+fn le_u64(bytes: &[u8], at: usize) -> u64 {
+    u64::from_le_bytes(bytes[at..at + 8].try_into().unwrap())
+}
+
+/// Decompress a block of at most `limit` bytes
+fn decompress(
+    compression: Compression,
+    data: &[u8],
+    limit: usize,
+) -> Result<Vec<u8>, SquashfsError> {
+    // One byte over the limit is read so that oversized blocks are noticed
+    let cap = limit as u64 + 1;
+    let mut output = Vec::new();
+    let read = match compression {
+        Compression::Gzip => ZlibDecoder::new(data).take(cap).read_to_end(&mut output),
+        Compression::Zstd => StreamingDecoder::new(data)
+            .map_err(|_| SquashfsError::Invalid("bad zstd frame"))?
+            .take(cap)
+            .read_to_end(&mut output),
+    };
+    if read.is_err() {
+        return Err(SquashfsError::Invalid("corrupt compressed block"));
+    }
+    if output.len() > limit {
+        return Err(SquashfsError::Invalid("block larger than expected"));
+    }
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::AppImageBuilder;
+
+    #[test]
+    fn test_decompress() {
+        let text = b"hello hello hello hello\n";
+        // zlib.compress(text)
+        let zlib = [
+            0x78, 0x9c, 0xcb, 0x48, 0xcd, 0xc9, 0xc9, 0x57, 0xc8, 0x40, 0x27, 0xb9, 0x00, 0x70,
+            0xbe, 0x08, 0xbb,
+        ];
+        assert_eq!(decompress(Compression::Gzip, &zlib, 100).unwrap(), text);
+        assert!(decompress(Compression::Gzip, &zlib, 10).is_err());
+
+        let mut corrupt = zlib;
+        corrupt[zlib.len() - 1] ^= 1;
+        assert!(decompress(Compression::Gzip, &corrupt, 100).is_err());
+
+        // zstd -19 --no-check
+        let zstd = [
+            0x28, 0xb5, 0x2f, 0xfd, 0x00, 0x68, 0x6d, 0x00, 0x00, 0x38, 0x68, 0x65, 0x6c, 0x6c,
+            0x6f, 0x20, 0x0a, 0x01, 0x00, 0x99, 0x4b, 0x11,
+        ];
+        assert_eq!(decompress(Compression::Zstd, &zstd, 100).unwrap(), text);
+        assert!(decompress(Compression::Zstd, &zstd, 10).is_err());
+        assert!(decompress(Compression::Zstd, &zlib, 100).is_err());
+    }
+
+    #[test]
+    fn test_read_image() {
+        let builder = AppImageBuilder::new("Test App")
+            .file("usr/share/icons/hicolor/256x256/apps/test-app.png", "png")
+            .file("usr/bin/test-app", vec![1u8; 300_000]);
+        let (dir, path) = builder.write_temp().unwrap();
+        let offset = crate::appimage::squashfs_offset(&path).unwrap().unwrap();
+        let mut image = Squashfs::open(&path, offset).unwrap();
+
+        let entries = image.entries().unwrap();
+        let paths: Vec<&str> = entries.iter().map(|e| e.path.as_str()).collect();
+        assert_eq!(
+            paths,
+            [
+                ".DirIcon",
+                "test-app.desktop",
+                "test-app.png",
+                "usr",
+                "usr/bin",
+                "usr/bin/test-app",
+                "usr/share",
+                "usr/share/icons",
+                "usr/share/icons/hicolor",
+                "usr/share/icons/hicolor/256x256",
+                "usr/share/icons/hicolor/256x256/apps",
+                "usr/share/icons/hicolor/256x256/apps/test-app.png",
+            ]
+        );
+
+        let binary = &entries[5];
+        assert_eq!(binary.kind, EntryKind::File);
+        assert_eq!(binary.size, 300_000);
+        let dest = dir.path().join("out/usr/bin/test-app");
+        image.extract(binary, &dest).unwrap();
+        assert_eq!(fs::read(&dest).unwrap(), vec![1u8; 300_000]);
+
+        image
+            .extract(&entries[1], &dir.path().join("out/test-app.desktop"))
+            .unwrap();
+        assert!(
+            fs::read_to_string(dir.path().join("out/test-app.desktop"))
+                .unwrap()
+                .starts_with("[Desktop Entry]\n")
+        );
+
+        // A file is written in place of a symlink, not through it
+        let outside = dir.path().join("outside");
+        fs::write(&outside, "keep").unwrap();
+        let link = dir.path().join("out/usr/bin/link");
+        std::os::unix::fs::symlink(&outside, &link).unwrap();
+        image.extract(binary, &link).unwrap();
+        assert_eq!(fs::read_to_string(&outside).unwrap(), "keep");
+        assert!(!fs::symlink_metadata(&link).unwrap().is_symlink());
+        assert_eq!(fs::read(&link).unwrap().len(), 300_000);
+    }
+
+    #[test]
+    fn test_duplicate_paths() {
+        let builder = AppImageBuilder::new("Test App")
+            .file("usr/bin/tool-a", "a")
+            .file("usr/bin/tool-b", "b");
+        let mut bytes = builder.build();
+        // Rename tool-b to tool-a in the directory table, the last mention
+        let at = bytes.windows(6).rposition(|w| w == b"tool-b").unwrap();
+        bytes[at + 5] = b'a';
+        let (dir, _) = builder.write_temp().unwrap();
+        let path = dir.path().join("crafted.AppImage");
+        fs::write(&path, &bytes).unwrap();
+
+        let offset = crate::appimage::squashfs_offset(&path).unwrap().unwrap();
+        let mut image = Squashfs::open(&path, offset).unwrap();
+        assert!(matches!(
+            image.entries(),
+            Err(SquashfsError::Invalid("duplicate path"))
+        ));
+    }
+}
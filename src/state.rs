@@ -1,12 +1,28 @@
 //! State management for tracking integrated AppImages.
+//!
+//! The same file can be reached through several spellings of its path: via a
+//! symlinked or bind-mounted directory, with a trailing slash or `.`
+//! components, or with its name in decomposed Unicode (as some file managers
+//! and archives on macOS write it). Paths are looked up by a normalized key so
+//! these all find the same entry instead of creating duplicates. The paths
+//! themselves are stored as given, since they name the file on disk.
 
+use crate::config::AppSort;
+use crate::desktop::EntryChange;
+use crate::duplicates::NativeDuplicate;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashMap};
 use std::fs;
 use std::path::{Path, PathBuf};
-use std::time::SystemTime;
+use std::time::{SystemTime, UNIX_EPOCH};
 use thiserror::Error;
 use tracing::{debug, info};
+use unicode_normalization::UnicodeNormalization;
+
+/// Version of the state file format written by this release, see
+/// [`crate::migrate`]
+pub const STATE_VERSION: u32 = 1;
 
 #[derive(Error, Debug)]
 pub enum StateError {
@@ -35,19 +51,249 @@ pub struct IntegratedAppImage {
     pub integrated_at: u64,
     /// When the entry was last updated
     pub updated_at: u64,
+    /// How long the last integration took
+    #[serde(default)]
+    pub timing: Option<IntegrationTiming>,
+    /// MD5 of the AppImage contents when last integrated (detects in-place updates)
+    #[serde(default)]
+    pub content_hash: Option<String>,
+    /// Version from the embedded desktop entry
+    #[serde(default)]
+    pub version: Option<String>,
+    /// Pinned entries are kept when the AppImage goes missing
+    #[serde(default)]
+    pub pinned: bool,
+    /// How often the app was launched through appimage-auto
+    #[serde(default)]
+    pub launch_count: u64,
+    /// When the app was last launched through appimage-auto
+    #[serde(default)]
+    pub last_launched: Option<u64>,
+    /// URL the AppImage was downloaded from, if the browser recorded it
+    #[serde(default)]
+    pub origin_url: Option<String>,
+    /// Page the download was started from, if the browser recorded it
+    #[serde(default)]
+    pub referrer_url: Option<String>,
+    /// Project links from the AppStream metainfo, by type (`homepage`, `bugtracker`, ...)
+    #[serde(default)]
+    pub links: BTreeMap<String, String>,
+    /// Natively installed app this AppImage duplicates, if any was found
+    #[serde(default)]
+    pub native_duplicate: Option<NativeDuplicate>,
+    /// Resolved target when the AppImage path is a symlink
+    #[serde(default)]
+    pub symlink_target: Option<PathBuf>,
+    /// Other paths (hardlinks, symlinks) reaching the same file, which share
+    /// this integration instead of getting launchers of their own
+    #[serde(default)]
+    pub aliases: Vec<PathBuf>,
+    /// Launch symlink in `integration.link_dir`, if one was made
+    #[serde(default)]
+    pub link_path: Option<PathBuf>,
+    /// Sizes of the AppImage and its artifacts when last integrated
+    #[serde(default)]
+    pub sizes: Option<DiskUsage>,
+    /// How the desktop entry changed when it was last re-integrated
+    #[serde(default)]
+    pub entry_changes: Vec<EntryChange>,
+    /// Copies of the desktop entry in `integration.desktop_dirs`
+    #[serde(default)]
+    pub desktop_copies: Vec<PathBuf>,
+    /// MD5 of the desktop entries and icons as they were written, to detect
+    /// later changes (see [`crate::integrity`])
+    #[serde(default)]
+    pub checksums: BTreeMap<PathBuf, String>,
+    /// Last error while updating this integration, until it succeeds again
+    #[serde(default)]
+    pub last_error: Option<IntegrationError>,
+    /// Icon the user chose with `set-icon`, kept in [`State::custom_icon_dir`]
+    /// and installed instead of the AppImage's own on every integration
+    #[serde(default)]
+    pub custom_icon: Option<PathBuf>,
+    /// Entries in `~/.config/autostart` that start the app on login, found
+    /// after it created them (see [`crate::autostart`])
+    #[serde(default)]
+    pub autostart_entries: Vec<PathBuf>,
+    /// Modification time of the AppImage in nanoseconds when its contents
+    /// were hashed, to tell cheaply whether it may have changed since
+    #[serde(default)]
+    pub mtime_ns: Option<u64>,
+}
+
+impl IntegratedAppImage {
+    /// Size of the AppImage file in bytes (0 if it is missing)
+    pub fn file_size(&self) -> u64 {
+        fs::metadata(&self.appimage_path)
+            .map(|m| m.len())
+            .unwrap_or(0)
+    }
+
+    /// Modification time of the AppImage file as a Unix timestamp, if it exists
+    pub fn file_modified(&self) -> Option<u64> {
+        fs::metadata(&self.appimage_path)
+            .and_then(|m| m.modified())
+            .ok()
+            .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+            .map(|d| d.as_secs())
+    }
+
+    /// Whether the AppImage's size or modification time differs from when it
+    /// was integrated. Also true when they weren't recorded.
+    pub fn changed_on_disk(&self) -> bool {
+        let Some(sizes) = self.sizes else {
+            return true;
+        };
+        self.mtime_ns.is_none()
+            || self.mtime_ns != modified_ns(&self.appimage_path)
+            || sizes.appimage != self.file_size()
+    }
+
+    /// Measure the disk space of the AppImage, its icons and desktop entry
+    pub fn measure_disk_usage(&self) -> DiskUsage {
+        let size = |path: &Path| fs::metadata(path).map(|m| m.len()).unwrap_or(0);
+        DiskUsage {
+            appimage: self.file_size(),
+            icons: self.icon_paths.iter().map(|p| size(p)).sum(),
+            desktop: size(&self.desktop_path)
+                + self.desktop_copies.iter().map(|p| size(p)).sum::<u64>(),
+        }
+    }
+
+    /// Disk space recorded at integration, measured now for entries
+    /// integrated before sizes were recorded
+    pub fn disk_usage(&self) -> DiskUsage {
+        self.sizes.unwrap_or_else(|| self.measure_disk_usage())
+    }
+
+    /// When the app was last integrated, launched or modified on disk
+    pub fn last_activity(&self) -> u64 {
+        self.last_launched
+            .into_iter()
+            .chain(self.file_modified())
+            .fold(self.integrated_at, u64::max)
+    }
+}
+
+/// Breakdown of how long an integration took, in milliseconds
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct IntegrationTiming {
+    /// Extracting metadata from the AppImage
+    pub extract_ms: u64,
+    /// Selecting and installing the icon
+    pub icon_ms: u64,
+    /// Writing the desktop entry and updating the database
+    pub desktop_ms: u64,
+    /// Whole integration
+    pub total_ms: u64,
+}
+
+/// Disk space taken by an integration, in bytes
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DiskUsage {
+    /// The AppImage file
+    pub appimage: u64,
+    /// Installed icons
+    pub icons: u64,
+    /// The desktop entry
+    pub desktop: u64,
+}
+
+impl DiskUsage {
+    /// Space taken by the icons and desktop entry
+    pub fn artifacts(&self) -> u64 {
+        self.icons + self.desktop
+    }
+
+    pub fn total(&self) -> u64 {
+        self.appimage + self.artifacts()
+    }
+}
+
+impl std::iter::Sum for DiskUsage {
+    fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+        iter.fold(Self::default(), |total, usage| Self {
+            appimage: total.appimage + usage.appimage,
+            icons: total.icons + usage.icons,
+            desktop: total.desktop + usage.desktop,
+        })
+    }
+}
+
+/// An error while updating an integrated AppImage
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IntegrationError {
+    /// Error message
+    pub message: String,
+    /// When the error occurred
+    pub at: u64,
+}
+
+impl IntegrationError {
+    /// An error that occurred now
+    pub fn new(message: impl Into<String>) -> Self {
+        Self {
+            message: message.into(),
+            at: current_timestamp(),
+        }
+    }
+}
+
+/// First retry delay after a failed integration, in seconds
+const RETRY_BASE_SECS: u64 = 30;
+
+/// Longest delay between retries, in seconds
+const RETRY_MAX_SECS: u64 = 3600;
+
+/// An AppImage whose integration failed
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FailedIntegration {
+    /// Path to the AppImage file
+    pub appimage_path: PathBuf,
+    /// Error message from the last attempt
+    pub reason: String,
+    /// Number of failed attempts so far
+    pub attempts: u32,
+    /// When the first attempt failed
+    pub first_failed_at: u64,
+    /// When the last attempt failed
+    pub last_failed_at: u64,
+    /// When the next automatic retry is due (`None` once retries are exhausted)
+    pub next_retry_at: Option<u64>,
+}
+
+/// How many AppImages in a directory are integrated
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DirectoryCounts {
+    pub integrated: usize,
+    pub unintegrated: usize,
 }
 
 /// State storage for the daemon
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct State {
+    /// Format version of the file this was loaded from (0 for files written
+    /// before the format was versioned)
+    #[serde(default)]
+    version: u32,
     /// Map from identifier to integrated AppImage info
     pub integrated: HashMap<String, IntegratedAppImage>,
-    /// Map from AppImage path to identifier (for quick lookup)
+    /// AppImages whose integration failed, keyed by path
+    #[serde(default)]
+    pub failed: HashMap<PathBuf, FailedIntegration>,
+    /// AppImages found in approval mode, waiting to be integrated (path to when found)
+    #[serde(default)]
+    pub awaiting_approval: HashMap<PathBuf, u64>,
+    /// Map from normalized AppImage path to identifier (for quick lookup),
+    /// see [`path_keys`]
     #[serde(skip)]
     path_index: HashMap<PathBuf, String>,
     /// Last known mtime of the state file on disk (to detect external changes)
     #[serde(skip)]
     last_saved_mtime: Option<SystemTime>,
+    /// Keep changes in memory only (for dry runs)
+    #[serde(skip)]
+    read_only: bool,
 }
 
 impl State {
@@ -58,7 +304,10 @@ impl State {
         if state_path.exists() {
             Self::load_from(&state_path)
         } else {
-            Ok(State::default())
+            Ok(State {
+                version: STATE_VERSION,
+                ..State::default()
+            })
         }
     }
 
@@ -73,19 +322,41 @@ impl State {
 
     /// Save state to the default location
     pub fn save(&mut self) -> Result<(), StateError> {
-        let state_path = Self::state_path()?;
+        self.save_to(&Self::state_path()?)
+    }
 
+    /// Save state to a specific path
+    pub fn save_to(&mut self, state_path: &Path) -> Result<(), StateError> {
+        if self.read_only {
+            debug!("State is read-only, not saving");
+            return Ok(());
+        }
         if let Some(parent) = state_path.parent() {
             fs::create_dir_all(parent)?;
         }
 
         let content = serde_json::to_string_pretty(self)?;
-        fs::write(&state_path, content)?;
-        self.last_saved_mtime = fs::metadata(&state_path).ok().and_then(|m| m.modified().ok());
+        fs::write(state_path, content)?;
+        self.last_saved_mtime = fs::metadata(state_path).ok().and_then(|m| m.modified().ok());
         debug!("Saved state to {:?}", state_path);
         Ok(())
     }
 
+    /// Format version of the file the state was loaded from
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    /// Set the format version written on the next save, once migrated
+    pub(crate) fn set_version(&mut self, version: u32) {
+        self.version = version;
+    }
+
+    /// Keep further changes in memory only, never writing them to disk
+    pub fn set_read_only(&mut self, read_only: bool) {
+        self.read_only = read_only;
+    }
+
     /// Check if the state file on disk has been modified externally.
     /// Returns true if the file mtime differs from the last known save.
     pub fn modified_externally(&self) -> bool {
@@ -102,7 +373,10 @@ impl State {
         if state_path.exists() {
             let reloaded = Self::load_from(&state_path)?;
             info!("Reloaded state from disk ({} entries)", reloaded.count());
+            self.version = reloaded.version;
             self.integrated = reloaded.integrated;
+            self.failed = reloaded.failed;
+            self.awaiting_approval = reloaded.awaiting_approval;
             self.path_index = reloaded.path_index;
             self.last_saved_mtime = reloaded.last_saved_mtime;
         }
@@ -116,41 +390,44 @@ impl State {
         Ok(dirs.data_dir().join("state.json"))
     }
 
+    /// Directory holding the icons users chose for their apps
+    pub fn custom_icon_dir() -> Result<PathBuf, StateError> {
+        Ok(Self::state_path()?.with_file_name("custom-icons"))
+    }
+
     /// Rebuild the path index from the integrated map
     fn rebuild_index(&mut self) {
         self.path_index.clear();
         for (id, info) in &self.integrated {
-            self.path_index
-                .insert(info.appimage_path.clone(), id.clone());
+            for key in path_keys(&info.appimage_path) {
+                self.path_index.insert(key, id.clone());
+            }
         }
     }
 
     /// Add or update an integrated AppImage
     pub fn add(&mut self, info: IntegratedAppImage) {
         let id = info.identifier.clone();
-        let path = info.appimage_path.clone();
+        let keys = path_keys(&info.appimage_path);
 
         self.integrated.insert(id.clone(), info);
-        self.path_index.insert(path, id);
+        self.path_index.retain(|_, other| *other != id);
+        for key in keys {
+            self.path_index.insert(key, id.clone());
+        }
     }
 
     /// Remove an integrated AppImage by identifier
     pub fn remove(&mut self, identifier: &str) -> Option<IntegratedAppImage> {
-        if let Some(info) = self.integrated.remove(identifier) {
-            self.path_index.remove(&info.appimage_path);
-            Some(info)
-        } else {
-            None
-        }
+        let info = self.integrated.remove(identifier)?;
+        self.path_index.retain(|_, id| id != identifier);
+        Some(info)
     }
 
     /// Remove an integrated AppImage by path
     pub fn remove_by_path(&mut self, path: &Path) -> Option<IntegratedAppImage> {
-        if let Some(id) = self.path_index.remove(path) {
-            self.integrated.remove(&id)
-        } else {
-            None
-        }
+        let id = lookup(&self.path_index, path)?.clone();
+        self.remove(&id)
     }
 
     /// Get an integrated AppImage by identifier
@@ -160,27 +437,165 @@ impl State {
 
     /// Get an integrated AppImage by path
     pub fn get_by_path(&self, path: &Path) -> Option<&IntegratedAppImage> {
-        self.path_index
-            .get(path)
-            .and_then(|id| self.integrated.get(id))
+        lookup(&self.path_index, path).and_then(|id| self.integrated.get(id))
     }
 
     /// Check if a path is integrated
     pub fn is_integrated(&self, path: &Path) -> bool {
-        self.path_index.contains_key(path)
+        lookup(&self.path_index, path).is_some()
     }
 
     /// Update the path of an integrated AppImage (for move handling)
     pub fn update_path(&mut self, old_path: &Path, new_path: &Path) -> Option<&IntegratedAppImage> {
-        if let Some(id) = self.path_index.remove(old_path)
-            && let Some(info) = self.integrated.get_mut(&id)
-        {
-            info.appimage_path = new_path.to_path_buf();
-            info.updated_at = current_timestamp();
-            self.path_index.insert(new_path.to_path_buf(), id.clone());
-            return self.integrated.get(&id);
+        let id = lookup(&self.path_index, old_path)?.clone();
+        let info = self.integrated.get_mut(&id)?;
+        info.appimage_path = new_path.to_path_buf();
+        info.updated_at = current_timestamp();
+        self.path_index.retain(|_, other| *other != id);
+        for key in path_keys(new_path) {
+            self.path_index.insert(key, id.clone());
+        }
+        self.integrated.get(&id)
+    }
+
+    /// Record another path reaching an integrated AppImage's file; returns
+    /// false if it was already recorded
+    pub fn add_alias(&mut self, identifier: &str, path: &Path) -> bool {
+        match self.integrated.get_mut(identifier) {
+            Some(info) if !info.aliases.iter().any(|a| same_path(a, path)) => {
+                info.aliases.push(path.to_path_buf());
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Forget an alias; returns the identifier of the AppImage it belonged to
+    pub fn remove_alias(&mut self, path: &Path) -> Option<String> {
+        let info = self
+            .integrated
+            .values_mut()
+            .find(|info| info.aliases.iter().any(|a| same_path(a, path)))?;
+        info.aliases.retain(|a| !same_path(a, path));
+        Some(info.identifier.clone())
+    }
+
+    /// Check if a path is an alias of an integrated AppImage
+    pub fn is_alias(&self, path: &Path) -> bool {
+        self.integrated
+            .values()
+            .any(|info| info.aliases.iter().any(|a| same_path(a, path)))
+    }
+
+    /// Take the first alias of an AppImage that still exists out of its
+    /// aliases (dropping those that don't), for the integration to move to
+    /// when `path` is gone
+    pub fn promote_alias(&mut self, path: &Path) -> Option<PathBuf> {
+        let info = lookup(&self.path_index, path).and_then(|id| self.integrated.get_mut(id))?;
+        info.aliases.retain(|a| a.exists());
+        if info.aliases.is_empty() {
+            return None;
+        }
+        let alias = info.aliases.remove(0);
+        info.symlink_target = crate::appimage::symlink_target(&alias);
+        Some(alias)
+    }
+
+    /// Pin or unpin an AppImage by path; returns false if it isn't integrated
+    pub fn set_pinned(&mut self, path: &Path, pinned: bool) -> bool {
+        match lookup(&self.path_index, path).and_then(|id| self.integrated.get_mut(id)) {
+            Some(info) => {
+                info.pinned = pinned;
+                info.updated_at = current_timestamp();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Count a launch of an AppImage by path; returns false if it isn't integrated
+    pub fn record_launch(&mut self, path: &Path) -> bool {
+        match lookup(&self.path_index, path).and_then(|id| self.integrated.get_mut(id)) {
+            Some(info) => {
+                info.launch_count += 1;
+                info.last_launched = Some(current_timestamp());
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Record how an AppImage's desktop entry changed on re-integration
+    pub fn set_entry_changes(&mut self, path: &Path, changes: Vec<EntryChange>) -> bool {
+        match lookup(&self.path_index, path).and_then(|id| self.integrated.get_mut(id)) {
+            Some(info) => {
+                info.entry_changes = changes;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Record the modification time an AppImage had when its contents were
+    /// last hashed; returns whether it changed
+    pub fn set_mtime(&mut self, path: &Path, mtime_ns: Option<u64>) -> bool {
+        match lookup(&self.path_index, path).and_then(|id| self.integrated.get_mut(id)) {
+            Some(info) if info.mtime_ns != mtime_ns => {
+                info.mtime_ns = mtime_ns;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Record the checksums of an AppImage's desktop entries and icons
+    pub fn set_checksums(&mut self, path: &Path, checksums: BTreeMap<PathBuf, String>) -> bool {
+        match lookup(&self.path_index, path).and_then(|id| self.integrated.get_mut(id)) {
+            Some(info) => {
+                info.checksums = checksums;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Set or clear the custom icon of an AppImage by path; returns false if
+    /// it isn't integrated
+    pub fn set_custom_icon(&mut self, path: &Path, icon: Option<PathBuf>) -> bool {
+        match lookup(&self.path_index, path).and_then(|id| self.integrated.get_mut(id)) {
+            Some(info) => {
+                info.custom_icon = icon;
+                info.updated_at = current_timestamp();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Record the autostart entries that start an AppImage.
+    ///
+    /// Returns whether the recorded entries changed.
+    pub fn set_autostart_entries(&mut self, path: &Path, entries: Vec<PathBuf>) -> bool {
+        match lookup(&self.path_index, path).and_then(|id| self.integrated.get_mut(id)) {
+            Some(info) if info.autostart_entries != entries => {
+                info.autostart_entries = entries;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Record or clear the last error while updating an AppImage's integration.
+    ///
+    /// Returns whether the recorded error changed.
+    pub fn set_last_error(&mut self, path: &Path, error: Option<IntegrationError>) -> bool {
+        match lookup(&self.path_index, path).and_then(|id| self.integrated.get_mut(id)) {
+            Some(info) if info.last_error != error => {
+                info.last_error = error;
+                true
+            }
+            _ => false,
         }
-        None
     }
 
     /// Get all integrated AppImages
@@ -193,6 +608,96 @@ impl State {
         self.integrated.len()
     }
 
+    /// Get the `n` integrations that took longest, slowest first
+    pub fn slowest(&self, n: usize) -> Vec<&IntegratedAppImage> {
+        let mut timed: Vec<_> = self
+            .integrated
+            .values()
+            .filter(|info| info.timing.is_some())
+            .collect();
+        timed.sort_by_key(|info| Reverse(info.timing.map(|t| t.total_ms)));
+        timed.truncate(n);
+        timed
+    }
+
+    /// Record a failed integration attempt and schedule the next retry.
+    ///
+    /// The delay doubles with each attempt; once `max_retries` attempts have
+    /// failed no further retry is scheduled.
+    pub fn record_failure(
+        &mut self,
+        path: &Path,
+        reason: &str,
+        max_retries: u32,
+    ) -> &FailedIntegration {
+        let now = current_timestamp();
+        let key = matching_key(&self.failed, path).unwrap_or_else(|| clean(path));
+        let entry = self.failed.entry(key).or_insert_with(|| FailedIntegration {
+            appimage_path: clean(path),
+            reason: String::new(),
+            attempts: 0,
+            first_failed_at: now,
+            last_failed_at: now,
+            next_retry_at: None,
+        });
+
+        entry.attempts += 1;
+        entry.reason = reason.to_string();
+        entry.last_failed_at = now;
+        entry.next_retry_at = (entry.attempts < max_retries).then(|| {
+            let delay = RETRY_BASE_SECS
+                .saturating_mul(1 << (entry.attempts - 1).min(16))
+                .min(RETRY_MAX_SECS);
+            now + delay
+        });
+        entry
+    }
+
+    /// Forget a failed integration (after success, deletion or a manual retry)
+    pub fn clear_failure(&mut self, path: &Path) -> Option<FailedIntegration> {
+        let key = matching_key(&self.failed, path)?;
+        self.failed.remove(&key)
+    }
+
+    /// Get all failed integrations
+    pub fn failures(&self) -> impl Iterator<Item = &FailedIntegration> {
+        self.failed.values()
+    }
+
+    /// Hold an AppImage for approval; returns false if it was already waiting
+    pub fn add_awaiting(&mut self, path: &Path) -> bool {
+        if matching_key(&self.awaiting_approval, path).is_some() {
+            return false;
+        }
+        self.awaiting_approval
+            .insert(clean(path), current_timestamp());
+        true
+    }
+
+    /// Stop holding an AppImage for approval; returns false if it wasn't waiting
+    pub fn remove_awaiting(&mut self, path: &Path) -> bool {
+        match matching_key(&self.awaiting_approval, path) {
+            Some(key) => self.awaiting_approval.remove(&key).is_some(),
+            None => false,
+        }
+    }
+
+    /// AppImages waiting for approval, sorted by path
+    pub fn awaiting(&self) -> Vec<&PathBuf> {
+        let mut paths: Vec<_> = self.awaiting_approval.keys().collect();
+        paths.sort();
+        paths
+    }
+
+    /// Paths whose next retry is due at or before `now`
+    pub fn due_retries(&self, now: u64) -> Vec<PathBuf> {
+        self.failed
+            .values()
+            .filter(|f| f.next_retry_at.is_some_and(|t| t <= now))
+            .map(|f| f.appimage_path.clone())
+            .collect()
+    }
+
     /// Find AppImages that no longer exist on disk
     pub fn find_orphaned(&self) -> Vec<&IntegratedAppImage> {
         self.integrated
@@ -201,6 +706,19 @@ impl State {
             .collect()
     }
 
+    /// Count integrated and unintegrated AppImages currently in a directory
+    pub fn directory_counts(&self, dir: &Path) -> DirectoryCounts {
+        let files = crate::appimage::scan_directory(dir).unwrap_or_default();
+        let integrated = files
+            .iter()
+            .filter(|p| self.is_integrated(p) || self.is_alias(p))
+            .count();
+        DirectoryCounts {
+            integrated,
+            unintegrated: files.len() - integrated,
+        }
+    }
+
     /// Find AppImages in a specific directory
     pub fn find_in_directory(&self, dir: &Path) -> Vec<&IntegratedAppImage> {
         self.integrated
@@ -210,8 +728,78 @@ impl State {
     }
 }
 
+/// `path` without `.` components, repeated or trailing slashes
+fn clean(path: &Path) -> PathBuf {
+    path.components().collect()
+}
+
+/// `path` cleaned and with its name in Unicode NFC, so that precomposed and
+/// decomposed spellings of the same name compare equal
+fn lexical_key(path: &Path) -> PathBuf {
+    let cleaned = clean(path);
+    match cleaned.to_str() {
+        Some(s) => PathBuf::from(s.nfc().collect::<String>()),
+        None => cleaned,
+    }
+}
+
+/// The lexical key of `path` with symlinks in its directory resolved. The file
+/// itself isn't resolved, as symlinked AppImages are integrated under their
+/// own path. `None` when the directory doesn't exist.
+fn canonical_key(path: &Path) -> Option<PathBuf> {
+    let name = path.file_name()?;
+    let dir = fs::canonicalize(path.parent()?).ok()?;
+    Some(lexical_key(&dir.join(name)))
+}
+
+/// The keys a path is indexed under: the lexical key, and the canonical key
+/// when it differs. Both are kept so an entry is still found by its path
+/// after its directory has gone (when removing orphans, say).
+fn path_keys(path: &Path) -> Vec<PathBuf> {
+    let mut keys = vec![lexical_key(path)];
+    if let Some(canonical) = canonical_key(path)
+        && canonical != keys[0]
+    {
+        keys.push(canonical);
+    }
+    keys
+}
+
+/// The path of the file `path` names, the same for all spellings of it
+pub fn normalize_path(path: &Path) -> PathBuf {
+    canonical_key(path).unwrap_or_else(|| lexical_key(path))
+}
+
+/// Find the identifier indexed for any spelling of `path`
+fn lookup<'a>(index: &'a HashMap<PathBuf, String>, path: &Path) -> Option<&'a String> {
+    index
+        .get(&lexical_key(path))
+        .or_else(|| canonical_key(path).and_then(|key| index.get(&key)))
+}
+
+/// Whether two paths are spellings of the same path
+fn same_path(a: &Path, b: &Path) -> bool {
+    let b_keys = path_keys(b);
+    a == b || path_keys(a).iter().any(|key| b_keys.contains(key))
+}
+
+/// The key in a path-keyed map under which `path` is stored, in any spelling
+fn matching_key<V>(map: &HashMap<PathBuf, V>, path: &Path) -> Option<PathBuf> {
+    if map.contains_key(path) {
+        return Some(path.to_path_buf());
+    }
+    map.keys().find(|key| same_path(key, path)).cloned()
+}
+
+/// Modification time of a file in nanoseconds since the Unix epoch
+pub fn modified_ns(path: &Path) -> Option<u64> {
+    let modified = fs::metadata(path).and_then(|m| m.modified()).ok()?;
+    let since_epoch = modified.duration_since(UNIX_EPOCH).ok()?;
+    u64::try_from(since_epoch.as_nanos()).ok()
+}
+
 /// Get the current Unix timestamp
-fn current_timestamp() -> u64 {
+pub fn current_timestamp() -> u64 {
     use std::time::{SystemTime, UNIX_EPOCH};
     SystemTime::now()
         .duration_since(UNIX_EPOCH)
@@ -219,6 +807,65 @@ fn current_timestamp() -> u64 {
         .unwrap_or(0)
 }
 
+/// Criteria for narrowing down a list of integrated AppImages
+#[derive(Debug, Clone, Default)]
+pub struct AppFilter {
+    /// Only AppImages whose file is missing
+    pub missing: bool,
+    /// Only AppImages inside this directory
+    pub dir: Option<PathBuf>,
+    /// Only AppImages whose name or file name contains this (case-insensitive)
+    pub name: Option<String>,
+}
+
+impl AppFilter {
+    /// Whether an integrated AppImage passes all criteria
+    pub fn matches(&self, info: &IntegratedAppImage) -> bool {
+        if self.missing && info.appimage_path.exists() {
+            return false;
+        }
+        if let Some(dir) = &self.dir
+            && !info.appimage_path.starts_with(dir)
+        {
+            return false;
+        }
+        if let Some(name) = &self.name {
+            let name = name.to_lowercase();
+            let app_name = info.name.as_deref().unwrap_or("").to_lowercase();
+            let file_name = info
+                .appimage_path
+                .file_name()
+                .map(|n| n.to_string_lossy().to_lowercase())
+                .unwrap_or_default();
+            if !app_name.contains(&name) && !file_name.contains(&name) {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+/// Sort integrated AppImages in the given order.
+///
+/// Ties (and entries without a name) fall back to alphabetical order.
+pub fn sort_apps(apps: &mut [IntegratedAppImage], order: AppSort) {
+    let name = |info: &IntegratedAppImage| info.name.as_deref().unwrap_or("").to_lowercase();
+    apps.sort_by_cached_key(|info| name(info));
+
+    // Stable sorts keep the alphabetical order within equal keys
+    match order {
+        AppSort::Name => {}
+        AppSort::Integrated => apps.sort_by_key(|info| Reverse(info.integrated_at)),
+        AppSort::Updated => apps.sort_by_key(|info| Reverse(info.updated_at)),
+        AppSort::Size => apps.sort_by_cached_key(|info| Reverse(info.file_size())),
+        // Never launched sorts last
+        AppSort::LastUsed => apps.sort_by_key(|info| Reverse(info.last_launched)),
+        AppSort::Directory => {
+            apps.sort_by(|a, b| a.appimage_path.parent().cmp(&b.appimage_path.parent()))
+        }
+    }
+}
+
 /// Create a new IntegratedAppImage entry
 pub fn create_entry(
     identifier: String,
@@ -236,6 +883,27 @@ pub fn create_entry(
         name,
         integrated_at: now,
         updated_at: now,
+        timing: None,
+        content_hash: None,
+        version: None,
+        pinned: false,
+        launch_count: 0,
+        last_launched: None,
+        origin_url: None,
+        referrer_url: None,
+        links: BTreeMap::new(),
+        native_duplicate: None,
+        symlink_target: None,
+        aliases: Vec::new(),
+        link_path: None,
+        sizes: None,
+        entry_changes: Vec::new(),
+        desktop_copies: Vec::new(),
+        checksums: BTreeMap::new(),
+        last_error: None,
+        custom_icon: None,
+        autostart_entries: Vec::new(),
+        mtime_ns: None,
     }
 }
 
@@ -264,6 +932,47 @@ mod tests {
         assert_eq!(state.count(), 0);
     }
 
+    #[test]
+    fn test_record_launch() {
+        let mut state = State::default();
+        state.add(create_entry(
+            "test123".to_string(),
+            PathBuf::from("/home/user/test.AppImage"),
+            PathBuf::new(),
+            vec![],
+            None,
+        ));
+
+        assert!(state.record_launch(Path::new("/home/user/test.AppImage")));
+        assert!(state.record_launch(Path::new("/home/user/test.AppImage")));
+        assert!(!state.record_launch(Path::new("/home/user/other.AppImage")));
+
+        let info = state.get("test123").unwrap();
+        assert_eq!(info.launch_count, 2);
+        assert!(info.last_launched.is_some());
+    }
+
+    #[test]
+    fn test_last_activity() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let mut info = create_entry(
+            "test123".to_string(),
+            temp.path().join("missing.AppImage"),
+            PathBuf::new(),
+            vec![],
+            None,
+        );
+        info.integrated_at = 100;
+        assert_eq!(info.last_activity(), 100);
+        info.last_launched = Some(500);
+        assert_eq!(info.last_activity(), 500);
+
+        // A file modified now is more recent than either
+        info.appimage_path = temp.path().join("App.AppImage");
+        fs::write(&info.appimage_path, b"").unwrap();
+        assert!(info.last_activity() >= current_timestamp() - 60);
+    }
+
     #[test]
     fn test_state_update_path() {
         let mut state = State::default();
@@ -287,6 +996,150 @@ mod tests {
         assert!(state.is_integrated(Path::new("/home/user/Applications/test.AppImage")));
     }
 
+    #[test]
+    fn test_slowest() {
+        let mut state = State::default();
+
+        for (id, ms) in [("fast", 10), ("slow", 900), ("mid", 200)] {
+            let mut entry = create_entry(
+                id.to_string(),
+                PathBuf::from(format!("/home/user/{}.AppImage", id)),
+                PathBuf::from(format!("/tmp/appimage-{}.desktop", id)),
+                vec![],
+                None,
+            );
+            entry.timing = Some(IntegrationTiming {
+                total_ms: ms,
+                ..Default::default()
+            });
+            state.add(entry);
+        }
+
+        let slowest: Vec<_> = state
+            .slowest(2)
+            .iter()
+            .map(|i| i.identifier.as_str())
+            .collect();
+        assert_eq!(slowest, vec!["slow", "mid"]);
+    }
+
+    #[test]
+    fn test_sort_apps() {
+        let mut apps: Vec<_> = [
+            ("b", "/z/b.AppImage", 100),
+            ("a", "/y/a.AppImage", 300),
+            ("c", "/y/c.AppImage", 200),
+        ]
+        .into_iter()
+        .map(|(id, path, at)| {
+            let mut entry = create_entry(
+                id.to_string(),
+                PathBuf::from(path),
+                PathBuf::new(),
+                vec![],
+                Some(id.to_uppercase()),
+            );
+            entry.integrated_at = at;
+            entry
+        })
+        .collect();
+
+        let ids = |apps: &[IntegratedAppImage]| -> Vec<String> {
+            apps.iter().map(|a| a.identifier.clone()).collect()
+        };
+
+        sort_apps(&mut apps, AppSort::Name);
+        assert_eq!(ids(&apps), ["a", "b", "c"]);
+        sort_apps(&mut apps, AppSort::Integrated);
+        assert_eq!(ids(&apps), ["a", "c", "b"]);
+        sort_apps(&mut apps, AppSort::Directory);
+        assert_eq!(ids(&apps), ["a", "c", "b"]);
+    }
+
+    #[test]
+    fn test_app_filter() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let present = temp.path().join("Present.AppImage");
+        fs::write(&present, b"").unwrap();
+
+        let entry = |path: PathBuf, name: &str| {
+            create_entry(
+                name.to_string(),
+                path,
+                PathBuf::new(),
+                vec![],
+                Some(name.to_string()),
+            )
+        };
+        let kept = entry(present, "Krita");
+        let gone = entry(PathBuf::from("/gone/Old.AppImage"), "Old Editor");
+
+        let filter = AppFilter::default();
+        assert!(filter.matches(&kept) && filter.matches(&gone));
+
+        let missing = AppFilter {
+            missing: true,
+            ..Default::default()
+        };
+        assert!(!missing.matches(&kept) && missing.matches(&gone));
+
+        let dir = AppFilter {
+            dir: Some(temp.path().to_path_buf()),
+            ..Default::default()
+        };
+        assert!(dir.matches(&kept) && !dir.matches(&gone));
+
+        let name = AppFilter {
+            name: Some("KRI".to_string()),
+            ..Default::default()
+        };
+        assert!(name.matches(&kept) && !name.matches(&gone));
+        let file_name = AppFilter {
+            name: Some("old.app".to_string()),
+            ..Default::default()
+        };
+        assert!(!file_name.matches(&kept) && file_name.matches(&gone));
+    }
+
+    #[test]
+    fn test_awaiting_approval() {
+        let mut state = State::default();
+        let b = Path::new("/home/user/B.AppImage");
+        let a = Path::new("/home/user/A.AppImage");
+
+        assert!(state.add_awaiting(b));
+        assert!(state.add_awaiting(a));
+        assert!(!state.add_awaiting(a));
+        assert_eq!(state.awaiting(), vec![a, b]);
+
+        assert!(state.remove_awaiting(a));
+        assert!(!state.remove_awaiting(a));
+        assert_eq!(state.awaiting(), vec![b]);
+    }
+
+    #[test]
+    fn test_failure_backoff() {
+        let mut state = State::default();
+        let path = Path::new("/home/user/Broken.AppImage");
+
+        let first = state.record_failure(path, "locked", 3).clone();
+        assert_eq!(first.attempts, 1);
+        let first_delay = first.next_retry_at.unwrap() - first.last_failed_at;
+
+        let second = state.record_failure(path, "locked", 3).clone();
+        let second_delay = second.next_retry_at.unwrap() - second.last_failed_at;
+        assert_eq!(second_delay, first_delay * 2);
+
+        let third = state.record_failure(path, "still locked", 3).clone();
+        assert_eq!(third.attempts, 3);
+        assert_eq!(third.reason, "still locked");
+        assert!(third.next_retry_at.is_none());
+        assert!(state.due_retries(u64::MAX).is_empty());
+
+        assert!(state.clear_failure(path).is_some());
+        assert_eq!(state.failures().count(), 0);
+    }
+
     #[test]
     fn test_serialize_deserialize() {
         let mut state = State::default();
@@ -308,4 +1161,148 @@ mod tests {
         assert_eq!(loaded.count(), 1);
         assert!(loaded.is_integrated(Path::new("/home/user/test.AppImage")));
     }
+
+    #[test]
+    fn test_aliases() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("App.AppImage");
+        let gone = temp_dir.path().join("Gone.AppImage");
+        let link = temp_dir.path().join("Link.AppImage");
+        fs::write(&link, b"").unwrap();
+
+        let mut state = State::default();
+        state.add(create_entry(
+            "app".to_string(),
+            path.clone(),
+            PathBuf::from("/tmp/appimage-app.desktop"),
+            vec![],
+            None,
+        ));
+        assert!(state.add_alias("app", &gone));
+        assert!(state.add_alias("app", &link));
+        assert!(!state.add_alias("app", &link));
+        assert!(state.is_alias(&link));
+        assert!(!state.is_integrated(&link));
+
+        assert_eq!(state.remove_alias(&gone), Some("app".to_string()));
+        assert_eq!(state.remove_alias(&gone), None);
+
+        state.add_alias("app", &gone);
+        assert_eq!(state.promote_alias(&path), Some(link.clone()));
+        assert!(state.get("app").unwrap().aliases.is_empty());
+        assert_eq!(state.promote_alias(&path), None);
+    }
+
+    #[test]
+    fn test_path_spellings() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let apps = temp_dir.path().join("apps");
+        let link = temp_dir.path().join("link");
+        fs::create_dir(&apps).unwrap();
+        std::os::unix::fs::symlink(&apps, &link).unwrap();
+        let path = apps.join("Caf\u{e9}.AppImage");
+
+        let mut state = State::default();
+        state.add(create_entry(
+            "cafe".to_string(),
+            path.clone(),
+            PathBuf::from("/tmp/appimage-cafe.desktop"),
+            vec![],
+            None,
+        ));
+        assert!(state.is_integrated(&link.join("Caf\u{e9}.AppImage")));
+        assert!(state.is_integrated(&apps.join(".").join("Cafe\u{301}.AppImage")));
+        assert!(state.is_integrated(Path::new(&format!("{}/", path.display()))));
+        assert!(!state.is_integrated(&apps.join("Other.AppImage")));
+
+        // The stored path stays as given
+        let moved = link.join("Cafe\u{301}.AppImage");
+        state.update_path(&link.join("Caf\u{e9}.AppImage"), &moved);
+        assert_eq!(state.get("cafe").unwrap().appimage_path, moved);
+        assert!(state.is_integrated(&path));
+
+        assert!(state.add_awaiting(&path));
+        assert!(!state.add_awaiting(&link.join("Caf\u{e9}.AppImage")));
+        assert!(state.remove_awaiting(&moved));
+
+        // Found by its lexical key once the directory is gone
+        fs::remove_file(&link).unwrap();
+        assert!(state.remove_by_path(&moved).is_some());
+        assert_eq!(state.count(), 0);
+        assert!(state.path_index.is_empty());
+    }
+
+    #[test]
+    fn test_disk_usage() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let file = |name: &str, len: usize| {
+            let path = temp_dir.path().join(name);
+            fs::write(&path, vec![0u8; len]).unwrap();
+            path
+        };
+        let mut entry = create_entry(
+            "app".to_string(),
+            file("App.AppImage", 1000),
+            file("appimage-app.desktop", 10),
+            vec![file("app.png", 100), file("app.svg", 200)],
+            None,
+        );
+
+        let measured = entry.measure_disk_usage();
+        assert_eq!(
+            measured,
+            DiskUsage {
+                appimage: 1000,
+                icons: 300,
+                desktop: 10
+            }
+        );
+        assert_eq!(measured.total(), 1310);
+
+        // Recorded sizes win over the files as they are now
+        entry.sizes = Some(DiskUsage {
+            appimage: 5,
+            ..measured
+        });
+        assert_eq!(entry.disk_usage().total(), 315);
+        let total: DiskUsage = [measured, entry.disk_usage()].into_iter().sum();
+        assert_eq!(total.appimage, 1005);
+        assert_eq!(total.artifacts(), 620);
+    }
+
+    #[test]
+    fn test_directory_counts() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let appimage = |name: &str| {
+            let path = temp_dir.path().join(name);
+            // ELF header with the type 2 AppImage magic at offset 8, padded
+            // past the smallest size an AppImage can have
+            let mut bytes = vec![0x7F, b'E', b'L', b'F', 2, 1, 1, 0, b'A', b'I', 2];
+            bytes.resize(4096, 0);
+            fs::write(&path, bytes).unwrap();
+            path
+        };
+        let integrated = appimage("One.AppImage");
+        appimage("Two.AppImage");
+        appimage("Three.AppImage.crdownload");
+        fs::write(temp_dir.path().join("notes.txt"), "hi").unwrap();
+
+        let mut state = State::default();
+        state.add(create_entry(
+            "one".to_string(),
+            integrated,
+            PathBuf::from("/tmp/appimage-one.desktop"),
+            vec![],
+            None,
+        ));
+
+        let counts = state.directory_counts(temp_dir.path());
+        assert_eq!(
+            counts,
+            DirectoryCounts {
+                integrated: 1,
+                unintegrated: 1
+            }
+        );
+    }
 }
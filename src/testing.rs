@@ -0,0 +1,567 @@
+//! Synthetic AppImages for tests (feature `testing`).
+//!
+//! [`AppImageBuilder`] writes a minimal type 2 AppImage: a small x86-64 ELF
+//! runtime carrying the AppImage magic, followed by an uncompressed SquashFS
+//! image with a desktop file and icon. The runtime hands over to `/bin/sh`,
+//! which answers `--appimage-extract [pattern]` by writing the same files
+//! into `squashfs-root` (and otherwise prints its arguments), so the whole
+//! pipeline from detection to integration runs without binary fixtures or
+//! SquashFS tools. Files should be small: they are also carried in the
+//! runtime's script.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use tempfile::TempDir;
+
+/// A 1x1 PNG, the default icon
+pub const ICON_PNG: &[u8] = &[
+    0x89, 0x50, 0x4e, 0x47, 0x0d, 0x0a, 0x1a, 0x0a, 0x00, 0x00, 0x00, 0x0d, 0x49, 0x48, 0x44, 0x52,
+    0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x01, 0x08, 0x06, 0x00, 0x00, 0x00, 0x1f, 0x15, 0xc4,
+    0x89, 0x00, 0x00, 0x00, 0x0d, 0x49, 0x44, 0x41, 0x54, 0x78, 0x9c, 0x63, 0xb0, 0x6a, 0x38, 0xf0,
+    0x1f, 0x00, 0x04, 0xec, 0x02, 0x7a, 0x28, 0xf2, 0xd8, 0xd8, 0x00, 0x00, 0x00, 0x00, 0x49, 0x45,
+    0x4e, 0x44, 0xae, 0x42, 0x60, 0x82,
+];
+
+/// Where the runtime is loaded
+const LOAD_ADDRESS: u64 = 0x40_0000;
+
+/// Size of the ELF header and the one program header before the code
+const CODE_OFFSET: usize = 64 + 56;
+
+/// x86-64 code that runs `/bin/sh -c <script> <argv...>`, expecting the two
+/// strings and the script right behind it:
+///
+/// ```text
+/// mov rcx, [rsp]              ; argc
+/// lea rdx, [rsp+rcx*8+16]     ; envp
+/// lea rdi, [rip+sh]
+/// mov [rsp-16], rdi           ; argv becomes sh, -c, script, argv...
+/// lea rax, [rip+dash_c]
+/// mov [rsp-8], rax
+/// lea rax, [rip+script]
+/// mov [rsp], rax
+/// lea rsi, [rsp-16]
+/// mov eax, 59                 ; execve
+/// syscall
+/// mov edi, 127
+/// mov eax, 60                 ; exit
+/// syscall
+/// ```
+const RUNTIME_CODE: &[u8] = &[
+    0x48, 0x8b, 0x0c, 0x24, 0x48, 0x8d, 0x54, 0xcc, 0x10, 0x48, 0x8d, 0x3d, 0x34, 0x00, 0x00, 0x00,
+    0x48, 0x89, 0x7c, 0x24, 0xf0, 0x48, 0x8d, 0x05, 0x30, 0x00, 0x00, 0x00, 0x48, 0x89, 0x44, 0x24,
+    0xf8, 0x48, 0x8d, 0x05, 0x27, 0x00, 0x00, 0x00, 0x48, 0x89, 0x04, 0x24, 0x48, 0x8d, 0x74, 0x24,
+    0xf0, 0xb8, 0x3b, 0x00, 0x00, 0x00, 0x0f, 0x05, 0xbf, 0x7f, 0x00, 0x00, 0x00, 0xb8, 0x3c, 0x00,
+    0x00, 0x00, 0x0f, 0x05,
+];
+
+/// Builds synthetic AppImages
+#[derive(Debug, Clone)]
+pub struct AppImageBuilder {
+    name: String,
+    version: Option<String>,
+    desktop_entry: Option<String>,
+    icon: bool,
+    files: BTreeMap<String, Vec<u8>>,
+}
+
+impl AppImageBuilder {
+    /// An AppImage of the app `name`, with a generated desktop file and icon
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            version: None,
+            desktop_entry: None,
+            icon: true,
+            files: BTreeMap::new(),
+        }
+    }
+
+    /// Set `X-AppImage-Version` in the generated desktop file
+    pub fn version(mut self, version: &str) -> Self {
+        self.version = Some(version.to_string());
+        self
+    }
+
+    /// Use `contents` as the desktop file instead of the generated one
+    pub fn desktop_entry(mut self, contents: &str) -> Self {
+        self.desktop_entry = Some(contents.to_string());
+        self
+    }
+
+    /// Leave out the icon (and `.DirIcon`)
+    pub fn without_icon(mut self) -> Self {
+        self.icon = false;
+        self
+    }
+
+    /// Add a file at `path` in the image, e.g. `usr/share/metainfo/app.appdata.xml`
+    pub fn file(mut self, path: &str, contents: impl Into<Vec<u8>>) -> Self {
+        self.files.insert(path.to_string(), contents.into());
+        self
+    }
+
+    /// File name the AppImage is written under by [`Self::write_temp`]
+    pub fn file_name(&self) -> String {
+        format!("{}.AppImage", self.name.replace(['/', ' '], "_"))
+    }
+
+    /// The AppImage's contents
+    pub fn build(&self) -> Vec<u8> {
+        let files = self.image_files();
+        let mut appimage = runtime(&extract_script(&files));
+        appimage.extend(squashfs(&files));
+        appimage
+    }
+
+    /// Write the AppImage to `path` and make it executable
+    pub fn write(&self, path: &Path) -> io::Result<()> {
+        use std::os::unix::fs::PermissionsExt;
+
+        fs::write(path, self.build())?;
+        fs::set_permissions(path, fs::Permissions::from_mode(0o755))
+    }
+
+    /// Write the AppImage into a new temporary directory, returning the
+    /// directory (removed when dropped) and the AppImage's path
+    pub fn write_temp(&self) -> io::Result<(TempDir, PathBuf)> {
+        let dir = TempDir::new()?;
+        let path = dir.path().join(self.file_name());
+        self.write(&path)?;
+        Ok((dir, path))
+    }
+
+    /// Files in the image, by path
+    fn image_files(&self) -> BTreeMap<String, Vec<u8>> {
+        let id = self
+            .name
+            .to_lowercase()
+            .replace(|c: char| !c.is_ascii_alphanumeric(), "-");
+        let desktop_entry = self.desktop_entry.clone().unwrap_or_else(|| {
+            let mut entry = format!(
+                "[Desktop Entry]\nType=Application\nName={}\nExec={}\nIcon={}\nCategories=Utility;\n",
+                self.name, id, id
+            );
+            if let Some(version) = &self.version {
+                entry.push_str(&format!("X-AppImage-Version={}\n", version));
+            }
+            entry
+        });
+
+        let mut files = BTreeMap::new();
+        files.insert(format!("{}.desktop", id), desktop_entry.into_bytes());
+        if self.icon {
+            files.insert(format!("{}.png", id), ICON_PNG.to_vec());
+            files.insert(".DirIcon".to_string(), ICON_PNG.to_vec());
+        }
+        files.extend(self.files.clone());
+        files
+    }
+}
+
+/// Shell script that writes the files matching an `--appimage-extract`
+/// pattern into `squashfs-root`, like the real runtime
+fn extract_script(files: &BTreeMap<String, Vec<u8>>) -> String {
+    let mut script = String::from(
+        "if [ \"$1\" = --appimage-extract ]; then\n\
+         pattern=${2:-*}\n\
+         x() {\n\
+         case $1 in $pattern) ;; *) return ;; esac\n\
+         mkdir -p \"squashfs-root/$(dirname \"$1\")\"\n\
+         printf \"$2\" > \"squashfs-root/$1\"\n\
+         echo \"squashfs-root/$1\"\n\
+         }\n",
+    );
+    for (path, contents) in files {
+        assert!(
+            !path.is_empty() && !path.contains(['\'', '\\']),
+            "unsupported path {:?}",
+            path
+        );
+        script.push_str(&format!("x '{}' '{}'\n", path, printf_escape(contents)));
+    }
+    script.push_str("exit 0\nfi\necho \"$0\" \"$@\"\n");
+    script
+}
+
+/// Escape bytes for a `printf` format in single quotes
+fn printf_escape(bytes: &[u8]) -> String {
+    let mut escaped = String::new();
+    for &b in bytes {
+        if b.is_ascii_alphanumeric() || b" .,:;=_/+-[]".contains(&b) {
+            escaped.push(char::from(b));
+        } else {
+            escaped.push_str(&format!("\\{:03o}", b));
+        }
+    }
+    escaped
+}
+
+/// ELF runtime running `script`, with the AppImage type 2 magic and a
+/// section header table (the SquashFS image starts where it ends)
+fn runtime(script: &str) -> Vec<u8> {
+    let mut elf = vec![0u8; CODE_OFFSET];
+    elf.extend(RUNTIME_CODE);
+    elf.extend(b"/bin/sh\0-c\0");
+    elf.extend(script.as_bytes());
+    elf.push(0);
+
+    let names_offset = elf.len();
+    elf.extend(b"\0.shstrtab\0");
+    elf.resize(elf.len().next_multiple_of(8), 0);
+    let section_headers = elf.len();
+    let size = section_headers + 2 * 64;
+
+    // ELF header: 64-bit little-endian x86-64 executable, the AppImage magic
+    // in the padding of e_ident
+    elf[0..16].copy_from_slice(b"\x7fELF\x02\x01\x01\0AI\x02\0\0\0\0\0");
+    put(&mut elf, 0x10, &2u16.to_le_bytes());
+    put(&mut elf, 0x12, &62u16.to_le_bytes());
+    put(&mut elf, 0x14, &1u32.to_le_bytes());
+    put(
+        &mut elf,
+        0x18,
+        &(LOAD_ADDRESS + CODE_OFFSET as u64).to_le_bytes(),
+    );
+    put(&mut elf, 0x20, &64u64.to_le_bytes());
+    put(&mut elf, 0x28, &(section_headers as u64).to_le_bytes());
+    put(&mut elf, 0x34, &64u16.to_le_bytes());
+    put(&mut elf, 0x36, &56u16.to_le_bytes());
+    put(&mut elf, 0x38, &1u16.to_le_bytes());
+    put(&mut elf, 0x3A, &64u16.to_le_bytes());
+    put(&mut elf, 0x3C, &2u16.to_le_bytes());
+    put(&mut elf, 0x3E, &1u16.to_le_bytes());
+
+    // One readable and executable PT_LOAD segment
+    put(&mut elf, 0x40, &1u32.to_le_bytes());
+    put(&mut elf, 0x44, &5u32.to_le_bytes());
+    put(&mut elf, 0x50, &LOAD_ADDRESS.to_le_bytes());
+    put(&mut elf, 0x58, &LOAD_ADDRESS.to_le_bytes());
+    put(&mut elf, 0x60, &(size as u64).to_le_bytes());
+    put(&mut elf, 0x68, &(size as u64).to_le_bytes());
+    put(&mut elf, 0x70, &0x1000u64.to_le_bytes());
+
+    // Section headers: the null section and .shstrtab
+    elf.resize(size, 0);
+    let shstrtab = section_headers + 64;
+    put(&mut elf, shstrtab, &1u32.to_le_bytes());
+    put(&mut elf, shstrtab + 0x04, &3u32.to_le_bytes());
+    put(
+        &mut elf,
+        shstrtab + 0x18,
+        &(names_offset as u64).to_le_bytes(),
+    );
+    put(&mut elf, shstrtab + 0x20, &11u64.to_le_bytes());
+    put(&mut elf, shstrtab + 0x30, &1u64.to_le_bytes());
+    elf
+}
+
+/// Copy `bytes` into `buf` at `at`
+fn put(buf: &mut [u8], at: usize, bytes: &[u8]) {
+    buf[at..at + bytes.len()].copy_from_slice(bytes);
+}
+
+const SQUASHFS_BLOCK_LOG: u16 = 17;
+const SQUASHFS_METADATA_SIZE: usize = 8192;
+/// Set in a block size when the block is stored uncompressed
+const SQUASHFS_UNCOMPRESSED_BLOCK: u32 = 1 << 24;
+/// Uncompressed inodes, data and fragments, no fragments, no xattrs
+const SQUASHFS_FLAGS: u16 = 0x0001 | 0x0002 | 0x0008 | 0x0010 | 0x0200;
+
+/// A file or directory in a SquashFS image
+enum Node {
+    File(Vec<u8>),
+    Dir(BTreeMap<String, Node>),
+}
+
+impl Node {
+    /// Number of inodes below a directory
+    fn count(&self) -> u32 {
+        match self {
+            Node::File(_) => 0,
+            Node::Dir(entries) => entries.values().map(|node| 1 + node.count()).sum(),
+        }
+    }
+}
+
+/// Metadata blocks (inode or directory table), stored uncompressed
+#[derive(Default)]
+struct Metadata {
+    blocks: Vec<u8>,
+    current: Vec<u8>,
+}
+
+impl Metadata {
+    /// Start of the current block in the table, and offset in it
+    fn position(&self) -> (u32, u16) {
+        (self.blocks.len() as u32, self.current.len() as u16)
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &b in bytes {
+            self.current.push(b);
+            if self.current.len() == SQUASHFS_METADATA_SIZE {
+                self.flush();
+            }
+        }
+    }
+
+    fn flush(&mut self) {
+        let header = self.current.len() as u16 | 0x8000;
+        self.blocks.extend(header.to_le_bytes());
+        self.blocks.append(&mut self.current);
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if !self.current.is_empty() {
+            self.flush();
+        }
+        self.blocks
+    }
+}
+
+/// Writes a SquashFS image, files before the inodes of their directory
+struct SquashfsWriter {
+    image: Vec<u8>,
+    inodes: Metadata,
+    directories: Metadata,
+    next_inode: u32,
+}
+
+impl SquashfsWriter {
+    /// Write a file's data and inode, returning the inode's position
+    fn file(&mut self, contents: &[u8], number: u32) -> (u32, u16) {
+        let start = self.image.len() as u32;
+        let block_size = 1usize << SQUASHFS_BLOCK_LOG;
+        let blocks: Vec<u32> = contents
+            .chunks(block_size)
+            .map(|block| block.len() as u32 | SQUASHFS_UNCOMPRESSED_BLOCK)
+            .collect();
+        self.image.extend(contents);
+
+        let position = self.inodes.position();
+        self.inodes.write(&inode_header(2, 0o644, number));
+        self.inodes.write(&start.to_le_bytes());
+        self.inodes.write(&u32::MAX.to_le_bytes());
+        self.inodes.write(&0u32.to_le_bytes());
+        self.inodes.write(&(contents.len() as u32).to_le_bytes());
+        for block in blocks {
+            self.inodes.write(&block.to_le_bytes());
+        }
+        position
+    }
+
+    /// Write a directory's entries, listing and inode, returning the
+    /// inode's position
+    fn dir(&mut self, entries: &BTreeMap<String, Node>, number: u32, parent: u32) -> (u32, u16) {
+        let numbers: Vec<u32> = entries
+            .keys()
+            .map(|_| {
+                self.next_inode += 1;
+                self.next_inode
+            })
+            .collect();
+        let mut children = Vec::new();
+        for ((name, node), child) in entries.iter().zip(numbers) {
+            let (kind, position) = match node {
+                Node::File(contents) => (2u16, self.file(contents, child)),
+                Node::Dir(entries) => (1, self.dir(entries, child, number)),
+            };
+            children.push((name, kind, child, position));
+        }
+
+        // Entries sharing an inode block go under one header, at most 256
+        let listing = self.directories.position();
+        let mut size = 0;
+        let mut index = 0;
+        while index < children.len() {
+            let block = children[index].3.0;
+            let group: Vec<_> = children[index..]
+                .iter()
+                .take_while(|child| child.3.0 == block)
+                .take(256)
+                .collect();
+            let base = group[0].2;
+            self.directories
+                .write(&(group.len() as u32 - 1).to_le_bytes());
+            self.directories.write(&block.to_le_bytes());
+            self.directories.write(&base.to_le_bytes());
+            size += 12;
+            for (name, kind, child, (_, offset)) in &group {
+                self.directories.write(&offset.to_le_bytes());
+                self.directories
+                    .write(&((child - base) as u16).to_le_bytes());
+                self.directories.write(&kind.to_le_bytes());
+                self.directories
+                    .write(&(name.len() as u16 - 1).to_le_bytes());
+                self.directories.write(name.as_bytes());
+                size += 8 + name.len();
+            }
+            index += group.len();
+        }
+
+        let subdirectories = entries
+            .values()
+            .filter(|node| matches!(node, Node::Dir(_)))
+            .count() as u32;
+        let position = self.inodes.position();
+        self.inodes.write(&inode_header(1, 0o755, number));
+        self.inodes.write(&listing.0.to_le_bytes());
+        self.inodes.write(&(2 + subdirectories).to_le_bytes());
+        // The size counts the implicit "." and ".." entries
+        self.inodes.write(&(size as u16 + 3).to_le_bytes());
+        self.inodes.write(&listing.1.to_le_bytes());
+        self.inodes.write(&parent.to_le_bytes());
+        position
+    }
+}
+
+/// Common header of an inode
+fn inode_header(kind: u16, mode: u16, number: u32) -> Vec<u8> {
+    let mut header = Vec::with_capacity(16);
+    header.extend(kind.to_le_bytes());
+    header.extend(mode.to_le_bytes());
+    // uid and gid index 0, mtime 0
+    header.extend([0u8; 8]);
+    header.extend(number.to_le_bytes());
+    header
+}
+
+/// An uncompressed SquashFS 4.0 image of `files`, padded to 4 KiB
+fn squashfs(files: &BTreeMap<String, Vec<u8>>) -> Vec<u8> {
+    let mut root = BTreeMap::new();
+    for (path, contents) in files {
+        let mut dir = &mut root;
+        let mut components: Vec<&str> = path.split('/').filter(|c| !c.is_empty()).collect();
+        let name = components.pop().unwrap_or_default();
+        for component in components {
+            let node = dir
+                .entry(component.to_string())
+                .or_insert_with(|| Node::Dir(BTreeMap::new()));
+            let Node::Dir(entries) = node else {
+                panic!("{:?} is both a file and a directory", component);
+            };
+            dir = entries;
+        }
+        dir.insert(name.to_string(), Node::File(contents.clone()));
+    }
+    let root = Node::Dir(root);
+    let inode_count = 1 + root.count();
+
+    let mut writer = SquashfsWriter {
+        image: vec![0u8; 96],
+        inodes: Metadata::default(),
+        directories: Metadata::default(),
+        next_inode: 1,
+    };
+    let Node::Dir(entries) = &root else {
+        unreachable!()
+    };
+    let root_inode = writer.dir(entries, 1, inode_count + 1);
+
+    let SquashfsWriter {
+        mut image,
+        inodes,
+        directories,
+        ..
+    } = writer;
+    let inode_table = image.len() as u64;
+    image.extend(inodes.finish());
+    let directory_table = image.len() as u64;
+    image.extend(directories.finish());
+    // One id (root), in a metadata block, and the index pointing at it
+    let ids = image.len() as u64;
+    image.extend([0x04, 0x80, 0, 0, 0, 0]);
+    let id_table = image.len() as u64;
+    image.extend(ids.to_le_bytes());
+    let bytes_used = image.len() as u64;
+
+    let root_inode = (u64::from(root_inode.0) << 16) | u64::from(root_inode.1);
+    let superblock = &mut image[..96];
+    put(superblock, 0, b"hsqs");
+    put(superblock, 4, &inode_count.to_le_bytes());
+    put(superblock, 12, &(1u32 << SQUASHFS_BLOCK_LOG).to_le_bytes());
+    // gzip, though nothing is compressed
+    put(superblock, 20, &1u16.to_le_bytes());
+    put(superblock, 22, &SQUASHFS_BLOCK_LOG.to_le_bytes());
+    put(superblock, 24, &SQUASHFS_FLAGS.to_le_bytes());
+    put(superblock, 26, &1u16.to_le_bytes());
+    put(superblock, 28, &4u16.to_le_bytes());
+    put(superblock, 32, &root_inode.to_le_bytes());
+    put(superblock, 40, &bytes_used.to_le_bytes());
+    put(superblock, 48, &id_table.to_le_bytes());
+    put(superblock, 56, &u64::MAX.to_le_bytes());
+    put(superblock, 64, &inode_table.to_le_bytes());
+    put(superblock, 72, &directory_table.to_le_bytes());
+    put(superblock, 80, &u64::MAX.to_le_bytes());
+    put(superblock, 88, &u64::MAX.to_le_bytes());
+
+    image.resize(image.len().next_multiple_of(4096), 0);
+    image
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::appimage::{self, AppImageType};
+
+    #[test]
+    fn test_synthetic_appimage() {
+        let builder = AppImageBuilder::new("Test App").version("1.2.3");
+        let (dir, path) = builder.write_temp().unwrap();
+        assert_eq!(path, dir.path().join("Test_App.AppImage"));
+
+        assert!(appimage::is_appimage(&path));
+        assert_eq!(
+            appimage::get_appimage_type(&path).unwrap(),
+            AppImageType::Type2
+        );
+        assert!(appimage::is_appimage_complete(&path).unwrap());
+        assert_eq!(
+            appimage::elf_section(&path, ".shstrtab")
+                .unwrap()
+                .map(|s| s.1),
+            Some(11)
+        );
+
+        // Cut short, like a download in progress
+        let bytes = builder.build();
+        let partial = dir.path().join("partial.AppImage");
+        fs::write(&partial, &bytes[..bytes.len() - 5000]).unwrap();
+        assert!(!appimage::is_appimage_complete(&partial).unwrap());
+    }
+
+    #[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+    #[test]
+    fn test_synthetic_appimage_extracts() {
+        let builder = AppImageBuilder::new("Test App")
+            .version("1.2.3")
+            .file(
+                "usr/share/metainfo/test-app.appdata.xml",
+                "<component><url type=\"homepage\">https://example.org</url></component>\n",
+            )
+            .file(
+                "usr/share/icons/hicolor/256x256/apps/test-app.png",
+                ICON_PNG,
+            );
+        let (dir, path) = builder.write_temp().unwrap();
+
+        let info = appimage::extract_metadata(&path, dir.path()).unwrap();
+        assert_eq!(info.name.as_deref(), Some("test-app"));
+        assert_eq!(info.version.as_deref(), Some("1.2.3"));
+        assert_eq!(info.icon_files.len(), 2);
+        assert_eq!(
+            info.links.get("homepage").map(String::as_str),
+            Some("https://example.org")
+        );
+
+        let output = std::process::Command::new(&path)
+            .arg("--hello")
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        assert!(String::from_utf8_lossy(&output.stdout).ends_with(" --hello\n"));
+    }
+}
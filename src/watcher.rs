@@ -1,15 +1,42 @@
-//! File system watcher using inotify via the `notify` crate.
+//! File system watcher using the `notify` crate.
+//!
+//! The backend is the platform's native one: inotify on Linux, kqueue on
+//! FreeBSD. kqueue reports renames only under the old name (the new name
+//! shows up as a created file), so moves arrive as a deletion and a creation.
 
 use notify::{
     Config, Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher,
     event::{CreateKind, ModifyKind, RemoveKind, RenameMode},
 };
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::os::unix::fs::MetadataExt;
 use std::path::{Path, PathBuf};
-use std::sync::mpsc::{self, Receiver};
+use std::sync::mpsc::{self, Receiver, Sender};
 use std::time::Duration;
 use thiserror::Error;
-use tracing::{debug, error, info};
+use tracing::{debug, error, info, warn};
+
+/// Name of the native watcher backend, for logging
+#[cfg(target_os = "linux")]
+pub const BACKEND: &str = "inotify";
+/// Name of the native watcher backend, for logging
+#[cfg(any(
+    target_os = "freebsd",
+    target_os = "openbsd",
+    target_os = "netbsd",
+    target_os = "dragonfly"
+))]
+pub const BACKEND: &str = "kqueue";
+/// Name of the native watcher backend, for logging
+#[cfg(not(any(
+    target_os = "linux",
+    target_os = "freebsd",
+    target_os = "openbsd",
+    target_os = "netbsd",
+    target_os = "dragonfly"
+)))]
+pub const BACKEND: &str = "native";
 
 #[derive(Error, Debug)]
 pub enum WatcherError {
@@ -22,7 +49,8 @@ pub enum WatcherError {
 }
 
 /// Events emitted by the file watcher
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum FileEvent {
     /// A new file was created or moved into a watched directory
     Created(PathBuf),
@@ -32,6 +60,31 @@ pub enum FileEvent {
     Moved { from: PathBuf, to: PathBuf },
     /// A file was modified
     Modified(PathBuf),
+    /// The kernel dropped events (e.g. inotify queue overflow), so these
+    /// watched directories need scanning again
+    Resync(Vec<PathBuf>),
+}
+
+/// A source of file events for the daemon.
+///
+/// [`FileWatcher`] reports changes in the file system. Tests and embedders
+/// can feed synthetic events through a [`ChannelSource`], or implement this
+/// for events from elsewhere (e.g. a network sync client).
+pub trait EventSource: Send {
+    /// Start reporting events for files directly in a directory
+    fn watch(&mut self, path: &Path) -> Result<(), WatcherError>;
+
+    /// Directories events are reported for
+    fn watched_directories(&self) -> &[PathBuf];
+
+    /// Get the next event, waiting at most `timeout`
+    fn next_event_timeout(&mut self, timeout: Duration) -> Result<Option<FileEvent>, WatcherError>;
+
+    /// Re-establish watches on directories that were deleted and recreated,
+    /// returning them. Sources without such watches have none.
+    fn rearm_stale(&mut self) -> Vec<PathBuf> {
+        Vec::new()
+    }
 }
 
 /// File system watcher that monitors directories for changes
@@ -39,6 +92,8 @@ pub struct FileWatcher {
     watcher: RecommendedWatcher,
     receiver: Receiver<Result<Event, notify::Error>>,
     watched_dirs: Vec<PathBuf>,
+    /// Identity (device, inode) of each watched directory when its watch was set up
+    dir_ids: HashMap<PathBuf, Option<(u64, u64)>>,
     /// Track rename events to match FROM and TO
     pending_renames: HashMap<u64, (PathBuf, std::time::Instant)>,
 }
@@ -54,11 +109,13 @@ impl FileWatcher {
             },
             Config::default().with_poll_interval(Duration::from_secs(2)),
         )?;
+        debug!("Using {} file watcher", BACKEND);
 
         Ok(Self {
             watcher,
             receiver: rx,
             watched_dirs: Vec::new(),
+            dir_ids: HashMap::new(),
             pending_renames: HashMap::new(),
         })
     }
@@ -72,6 +129,7 @@ impl FileWatcher {
         // Watch non-recursively - we only care about direct children
         self.watcher.watch(path, RecursiveMode::NonRecursive)?;
         self.watched_dirs.push(path.to_path_buf());
+        self.dir_ids.insert(path.to_path_buf(), dir_identity(path));
         info!("Watching directory: {:?}", path);
         Ok(())
     }
@@ -80,15 +138,70 @@ impl FileWatcher {
     pub fn unwatch(&mut self, path: &Path) -> Result<(), WatcherError> {
         self.watcher.unwatch(path)?;
         self.watched_dirs.retain(|p| p != path);
+        self.dir_ids.remove(path);
         info!("Stopped watching directory: {:?}", path);
         Ok(())
     }
 
+    /// Re-establish watches on directories that were deleted and recreated.
+    ///
+    /// inotify and kqueue watches die with the directory they were set on, so a watched
+    /// directory whose inode has changed since its watch was added is watched
+    /// again. Returns the directories that were re-armed; a directory that is
+    /// currently missing, or was reported removed, is re-armed once it
+    /// reappears, even under its old inode number.
+    pub fn rearm_stale(&mut self) -> Vec<PathBuf> {
+        let mut rearmed = Vec::new();
+
+        for dir in self.watched_dirs.clone() {
+            let current = dir_identity(&dir);
+            if current.is_none() {
+                self.dir_ids.insert(dir, None);
+                continue;
+            }
+            if current == self.dir_ids.get(&dir).copied().flatten() {
+                continue;
+            }
+
+            // The old watch is usually gone already
+            let _ = self.watcher.unwatch(&dir);
+            match self.watcher.watch(&dir, RecursiveMode::NonRecursive) {
+                Ok(()) => {
+                    info!("Re-armed watch on recreated directory: {:?}", dir);
+                    self.dir_ids.insert(dir.clone(), current);
+                    rearmed.push(dir);
+                }
+                Err(e) => warn!("Failed to re-arm watch on {:?}: {}", dir, e),
+            }
+        }
+
+        rearmed
+    }
+
     /// Check if a path is within any watched directory
     pub fn is_in_watched_dir(&self, path: &Path) -> bool {
+        self.watched_path(path).is_some()
+    }
+
+    /// A path directly in a watched directory, spelled below the directory as
+    /// it was watched; `None` for paths elsewhere.
+    ///
+    /// Events may name a watched directory differently than it was added:
+    /// through a symlink, a bind mount or the merged directory of an overlay
+    /// in a container. A parent that is the same directory (device and inode)
+    /// as a watched one counts, so the daemon sees one spelling per file and
+    /// its state lookups match.
+    pub fn watched_path(&self, path: &Path) -> Option<PathBuf> {
+        let parent = path.parent()?;
+        if self.watched_dirs.iter().any(|dir| dir == parent) {
+            return Some(path.to_path_buf());
+        }
+        let name = path.file_name()?;
+        let identity = dir_identity(parent)?;
         self.watched_dirs
             .iter()
-            .any(|dir| path.parent() == Some(dir.as_path()))
+            .find(|dir| self.dir_ids.get(*dir).copied().flatten() == Some(identity))
+            .map(|dir| dir.join(name))
     }
 
     /// Get the next file event (blocking)
@@ -132,24 +245,42 @@ impl FileWatcher {
 
     /// Process a raw notify event into our FileEvent type
     fn process_event(&mut self, event: Event) -> Option<FileEvent> {
+        self.translate_event(event)
+            .and_then(collapse_partial_download)
+    }
+
+    /// Translate a raw notify event, matching rename halves
+    fn translate_event(&mut self, event: Event) -> Option<FileEvent> {
         debug!("Raw event: {:?}", event);
 
+        if event.need_rescan() {
+            let dirs = self.resync_dirs(&event.paths);
+            warn!("File watcher dropped events, rescanning {:?}", dirs);
+            return Some(FileEvent::Resync(dirs));
+        }
+
+        // A watched directory itself was removed: its watch is gone, and a
+        // recreated directory may get the same inode number back
+        if matches!(event.kind, EventKind::Remove(_)) {
+            for path in &event.paths {
+                if let Some(id) = self.dir_ids.get_mut(path) {
+                    *id = None;
+                }
+            }
+        }
+
         match event.kind {
             // File created
             EventKind::Create(CreateKind::File) => {
-                if let Some(path) = event.paths.first()
-                    && self.is_in_watched_dir(path)
-                {
-                    return Some(FileEvent::Created(path.clone()));
+                if let Some(path) = self.first_watched_path(&event) {
+                    return Some(FileEvent::Created(path));
                 }
             }
 
             // File removed
             EventKind::Remove(RemoveKind::File) => {
-                if let Some(path) = event.paths.first()
-                    && self.is_in_watched_dir(path)
-                {
-                    return Some(FileEvent::Deleted(path.clone()));
+                if let Some(path) = self.first_watched_path(&event) {
+                    return Some(FileEvent::Deleted(path));
                 }
             }
 
@@ -163,8 +294,8 @@ impl FileWatcher {
                             .insert(tracker as u64, (path.clone(), std::time::Instant::now()));
                     } else {
                         // No cookie, treat as deletion
-                        if self.is_in_watched_dir(path) {
-                            return Some(FileEvent::Deleted(path.clone()));
+                        if let Some(path) = self.watched_path(path) {
+                            return Some(FileEvent::Deleted(path));
                         }
                     }
                 }
@@ -177,90 +308,89 @@ impl FileWatcher {
                     if let Some(tracker) = event.attrs.tracker()
                         && let Some((from_path, _)) = self.pending_renames.remove(&(tracker as u64))
                     {
-                        let from_watched = self.is_in_watched_dir(&from_path);
-                        let to_watched = self.is_in_watched_dir(to_path);
+                        let from_watched = self.watched_path(&from_path);
+                        let to_watched = self.watched_path(to_path);
 
                         match (from_watched, to_watched) {
-                            (true, true) => {
+                            (Some(from), Some(to)) => {
                                 // Moved within watched directories
-                                return Some(FileEvent::Moved {
-                                    from: from_path,
-                                    to: to_path.clone(),
-                                });
+                                return Some(FileEvent::Moved { from, to });
                             }
-                            (true, false) => {
+                            (Some(from), None) => {
                                 // Moved out of watched directory
-                                return Some(FileEvent::Deleted(from_path));
+                                return Some(FileEvent::Deleted(from));
                             }
-                            (false, true) => {
+                            (None, Some(to)) => {
                                 // Moved into watched directory
-                                return Some(FileEvent::Created(to_path.clone()));
+                                return Some(FileEvent::Created(to));
                             }
-                            (false, false) => {
+                            (None, None) => {
                                 // Neither in watched dirs, ignore
                             }
                         }
                     }
 
                     // No matching FROM, treat as creation if in watched dir
-                    if self.is_in_watched_dir(to_path) {
-                        return Some(FileEvent::Created(to_path.clone()));
+                    if let Some(to_path) = self.watched_path(to_path) {
+                        return Some(FileEvent::Created(to_path));
                     }
                 }
             }
 
+            // Rename reported under the old name only (kqueue); the new name
+            // arrives as a created file
+            EventKind::Modify(ModifyKind::Name(RenameMode::Any)) => {
+                if let Some(path) = self.first_watched_path(&event)
+                    && !path.exists()
+                {
+                    return Some(FileEvent::Deleted(path));
+                }
+            }
+
             // Both FROM and TO in single event
             EventKind::Modify(ModifyKind::Name(RenameMode::Both)) => {
                 if event.paths.len() >= 2 {
                     let from_path = &event.paths[0];
                     let to_path = &event.paths[1];
 
-                    let from_watched = self.is_in_watched_dir(from_path);
-                    let to_watched = self.is_in_watched_dir(to_path);
+                    let from_watched = self.watched_path(from_path);
+                    let to_watched = self.watched_path(to_path);
 
                     match (from_watched, to_watched) {
-                        (true, true) => {
-                            return Some(FileEvent::Moved {
-                                from: from_path.clone(),
-                                to: to_path.clone(),
-                            });
+                        (Some(from), Some(to)) => {
+                            return Some(FileEvent::Moved { from, to });
                         }
-                        (true, false) => {
-                            return Some(FileEvent::Deleted(from_path.clone()));
+                        (Some(from), None) => {
+                            return Some(FileEvent::Deleted(from));
                         }
-                        (false, true) => {
-                            return Some(FileEvent::Created(to_path.clone()));
+                        (None, Some(to)) => {
+                            return Some(FileEvent::Created(to));
                         }
-                        (false, false) => {}
+                        (None, None) => {}
                     }
                 }
             }
 
             // File modified (content changed)
             EventKind::Modify(ModifyKind::Data(_)) => {
-                if let Some(path) = event.paths.first()
-                    && self.is_in_watched_dir(path)
-                {
-                    return Some(FileEvent::Modified(path.clone()));
+                if let Some(path) = self.first_watched_path(&event) {
+                    return Some(FileEvent::Modified(path));
                 }
             }
 
             // Catch-all for other create events (e.g., CreateKind::Any)
             EventKind::Create(_) => {
-                if let Some(path) = event.paths.first()
-                    && self.is_in_watched_dir(path)
+                if let Some(path) = self.first_watched_path(&event)
                     && path.is_file()
                 {
-                    return Some(FileEvent::Created(path.clone()));
+                    return Some(FileEvent::Created(path));
                 }
             }
 
             // Catch-all for other remove events
             EventKind::Remove(_) => {
-                if let Some(path) = event.paths.first()
-                    && self.is_in_watched_dir(path)
-                {
-                    return Some(FileEvent::Deleted(path.clone()));
+                if let Some(path) = self.first_watched_path(&event) {
+                    return Some(FileEvent::Deleted(path));
                 }
             }
 
@@ -272,15 +402,152 @@ impl FileWatcher {
         None
     }
 
+    /// The event's first path, if it is in a watched directory (see
+    /// [`Self::watched_path`])
+    fn first_watched_path(&self, event: &Event) -> Option<PathBuf> {
+        self.watched_path(event.paths.first()?)
+    }
+
+    /// Watched directories affected by a rescan signal: those of its paths,
+    /// or all of them when the backend doesn't say (inotify overflow)
+    fn resync_dirs(&self, paths: &[PathBuf]) -> Vec<PathBuf> {
+        let mut dirs: Vec<PathBuf> = Vec::new();
+        for path in paths {
+            let dir = if self.watched_dirs.contains(path) {
+                path.clone()
+            } else if let Some(parent) = self
+                .watched_path(path)
+                .and_then(|path| path.parent().map(Path::to_path_buf))
+            {
+                parent
+            } else {
+                continue;
+            };
+            if !dirs.contains(&dir) {
+                dirs.push(dir);
+            }
+        }
+        if dirs.is_empty() {
+            dirs = self.watched_dirs.clone();
+        }
+        dirs
+    }
+
     /// Get list of watched directories
     pub fn watched_directories(&self) -> &[PathBuf] {
         &self.watched_dirs
     }
 }
 
+impl EventSource for FileWatcher {
+    fn watch(&mut self, path: &Path) -> Result<(), WatcherError> {
+        FileWatcher::watch(self, path)
+    }
+
+    fn watched_directories(&self) -> &[PathBuf] {
+        FileWatcher::watched_directories(self)
+    }
+
+    fn next_event_timeout(&mut self, timeout: Duration) -> Result<Option<FileEvent>, WatcherError> {
+        FileWatcher::next_event_timeout(self, timeout)
+    }
+
+    fn rearm_stale(&mut self) -> Vec<PathBuf> {
+        FileWatcher::rearm_stale(self)
+    }
+}
+
+/// Event source fed with events sent through a channel
+pub struct ChannelSource {
+    receiver: Receiver<FileEvent>,
+    watched_dirs: Vec<PathBuf>,
+}
+
+impl ChannelSource {
+    /// Create a source and the sender to feed it with
+    pub fn new() -> (Self, Sender<FileEvent>) {
+        let (tx, rx) = mpsc::channel();
+        let source = Self {
+            receiver: rx,
+            watched_dirs: Vec::new(),
+        };
+        (source, tx)
+    }
+}
+
+impl EventSource for ChannelSource {
+    fn watch(&mut self, path: &Path) -> Result<(), WatcherError> {
+        if !self.watched_dirs.iter().any(|dir| dir == path) {
+            self.watched_dirs.push(path.to_path_buf());
+        }
+        Ok(())
+    }
+
+    fn watched_directories(&self) -> &[PathBuf] {
+        &self.watched_dirs
+    }
+
+    fn next_event_timeout(&mut self, timeout: Duration) -> Result<Option<FileEvent>, WatcherError> {
+        match self.receiver.recv_timeout(timeout) {
+            Ok(event) => Ok(Some(event)),
+            Err(mpsc::RecvTimeoutError::Timeout) => Ok(None),
+            Err(mpsc::RecvTimeoutError::Disconnected) => {
+                Err(WatcherError::Receive(mpsc::RecvError))
+            }
+        }
+    }
+}
+
+/// File extensions browsers use while a download is in progress
+const PARTIAL_DOWNLOAD_EXTENSIONS: &[&str] = &["crdownload", "part", "partial", "download"];
+
+/// Check whether a path is an in-progress browser download
+/// (e.g. `foo.AppImage.crdownload` from Chrome, `foo.AppImage.part` from Firefox)
+pub fn is_partial_download(path: &Path) -> bool {
+    path.extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+        .is_some_and(|ext| PARTIAL_DOWNLOAD_EXTENSIONS.contains(&ext.as_str()))
+}
+
+/// Hide in-progress browser downloads from the daemon.
+///
+/// Events on partial files are dropped, and the final rename
+/// (`foo.AppImage.crdownload -> foo.AppImage`) becomes a single `Created`
+/// for the finished file rather than a move of an untracked path.
+fn collapse_partial_download(event: FileEvent) -> Option<FileEvent> {
+    match event {
+        FileEvent::Created(ref path)
+        | FileEvent::Deleted(ref path)
+        | FileEvent::Modified(ref path)
+            if is_partial_download(path) =>
+        {
+            debug!("Ignoring partial download: {:?}", path);
+            None
+        }
+        FileEvent::Moved { from, to } => {
+            match (is_partial_download(&from), is_partial_download(&to)) {
+                (true, true) => None,
+                (true, false) => Some(FileEvent::Created(to)),
+                (false, true) => Some(FileEvent::Deleted(from)),
+                (false, false) => Some(FileEvent::Moved { from, to }),
+            }
+        }
+        event => Some(event),
+    }
+}
+
+/// Device and inode of a directory, if it exists
+fn dir_identity(path: &Path) -> Option<(u64, u64)> {
+    std::fs::metadata(path)
+        .ok()
+        .filter(|m| m.is_dir())
+        .map(|m| (m.dev(), m.ino()))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use notify::event::Flag;
     use tempfile::TempDir;
 
     #[test]
@@ -324,4 +591,129 @@ mod tests {
         // File in different directory should return false
         assert!(!watcher.is_in_watched_dir(Path::new("/tmp/other/test.AppImage")));
     }
+
+    #[test]
+    fn test_rearm_recreated_directory() {
+        let temp_dir = TempDir::new().unwrap();
+        let watched = temp_dir.path().join("Downloads");
+        std::fs::create_dir(&watched).unwrap();
+
+        let mut watcher = FileWatcher::new().unwrap();
+        watcher.watch(&watched).unwrap();
+        assert!(watcher.rearm_stale().is_empty());
+
+        // Removed: nothing to re-arm until it comes back, likely with the
+        // freed inode number
+        std::fs::remove_dir(&watched).unwrap();
+        assert!(watcher.rearm_stale().is_empty());
+        std::fs::create_dir(&watched).unwrap();
+        assert_eq!(watcher.rearm_stale(), vec![watched.clone()]);
+        assert!(watcher.rearm_stale().is_empty());
+
+        // Removed and recreated between checks: the removal event counts
+        std::fs::remove_dir(&watched).unwrap();
+        std::fs::create_dir(&watched).unwrap();
+        let removed = Event::new(EventKind::Remove(RemoveKind::Folder)).add_path(watched.clone());
+        assert!(watcher.translate_event(removed).is_none());
+        assert_eq!(watcher.rearm_stale(), vec![watched.clone()]);
+        assert!(watcher.rearm_stale().is_empty());
+    }
+
+    #[test]
+    fn test_symlinked_watch_dir() {
+        let temp_dir = TempDir::new().unwrap();
+        let real = temp_dir.path().join("data/Applications");
+        let link = temp_dir.path().join("Applications");
+        std::fs::create_dir_all(&real).unwrap();
+        std::os::unix::fs::symlink(&real, &link).unwrap();
+
+        let mut watcher = FileWatcher::new().unwrap();
+        watcher.watch(&link).unwrap();
+
+        // Events naming the real directory are reported below the watched one
+        let app = link.join("App.AppImage");
+        assert_eq!(
+            watcher.watched_path(&real.join("App.AppImage")),
+            Some(app.clone())
+        );
+        assert_eq!(watcher.watched_path(&app), Some(app.clone()));
+        assert_eq!(watcher.watched_path(&real.join("sub/App.AppImage")), None);
+
+        let event = Event::new(EventKind::Modify(ModifyKind::Name(RenameMode::Both)))
+            .add_path(real.join("Old.AppImage"))
+            .add_path(real.join("App.AppImage"));
+        assert!(matches!(
+            watcher.translate_event(event),
+            Some(FileEvent::Moved { from, to })
+                if from == link.join("Old.AppImage") && to == app
+        ));
+
+        // And the other way round: the real directory is watched, events come
+        // through the link
+        let mut watcher = FileWatcher::new().unwrap();
+        watcher.watch(&real).unwrap();
+        let event = Event::new(EventKind::Remove(RemoveKind::File)).add_path(app);
+        assert!(matches!(
+            watcher.translate_event(event),
+            Some(FileEvent::Deleted(path)) if path == real.join("App.AppImage")
+        ));
+    }
+
+    #[test]
+    fn test_rename_without_new_name_becomes_deleted() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut watcher = FileWatcher::new().unwrap();
+        watcher.watch(temp_dir.path()).unwrap();
+
+        let gone = temp_dir.path().join("App.AppImage");
+        let event =
+            Event::new(EventKind::Modify(ModifyKind::Name(RenameMode::Any))).add_path(gone.clone());
+        assert!(matches!(
+            watcher.translate_event(event),
+            Some(FileEvent::Deleted(path)) if path == gone
+        ));
+    }
+
+    #[test]
+    fn test_overflow_becomes_resync() {
+        let first = TempDir::new().unwrap();
+        let second = TempDir::new().unwrap();
+        let mut watcher = FileWatcher::new().unwrap();
+        watcher.watch(first.path()).unwrap();
+        watcher.watch(second.path()).unwrap();
+
+        // inotify overflow names no paths, so every directory is rescanned
+        let overflow = Event::new(EventKind::Other).set_flag(Flag::Rescan);
+        assert!(matches!(
+            watcher.translate_event(overflow),
+            Some(FileEvent::Resync(dirs)) if dirs == [first.path(), second.path()]
+        ));
+
+        let file = second.path().join("App.AppImage");
+        let rescan = Event::new(EventKind::Any)
+            .add_path(file)
+            .set_flag(Flag::Rescan);
+        assert!(matches!(
+            watcher.translate_event(rescan),
+            Some(FileEvent::Resync(dirs)) if dirs == [second.path()]
+        ));
+    }
+
+    #[test]
+    fn test_partial_download_rename_becomes_created() {
+        let finished = PathBuf::from("/home/user/Downloads/App.AppImage");
+        let partial = PathBuf::from("/home/user/Downloads/App.AppImage.crdownload");
+        assert!(is_partial_download(&partial));
+        assert!(is_partial_download(Path::new("/tmp/App.AppImage.PART")));
+        assert!(!is_partial_download(&finished));
+
+        assert!(collapse_partial_download(FileEvent::Created(partial.clone())).is_none());
+        assert!(collapse_partial_download(FileEvent::Deleted(partial.clone())).is_none());
+
+        let renamed = collapse_partial_download(FileEvent::Moved {
+            from: partial,
+            to: finished.clone(),
+        });
+        assert!(matches!(renamed, Some(FileEvent::Created(p)) if p == finished));
+    }
 }
@@ -0,0 +1,170 @@
+//! The daemon's whole pipeline on synthetic AppImages: from a watcher event
+//! to a menu entry, and back, and taking over a hand-written menu entry.
+//!
+//! Their runtime is x86-64 Linux code.
+#![cfg(all(target_os = "linux", target_arch = "x86_64"))]
+
+use appimage_auto::integrity;
+use appimage_auto::overrides::{DesktopOverrides, Overrides};
+use appimage_auto::state::State;
+use appimage_auto::testing::AppImageBuilder;
+use appimage_auto::watcher::{ChannelSource, FileEvent};
+use appimage_auto::{Config, Daemon};
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+use tempfile::TempDir;
+
+/// A daemon with everything (state, menu entries, control socket) kept
+/// inside `home`, fed with events through the returned sender
+fn daemon(home: &Path) -> (Daemon, std::sync::mpsc::Sender<FileEvent>) {
+    // SAFETY: the only test in this binary, so nothing else reads the
+    // environment concurrently
+    unsafe {
+        std::env::set_var("HOME", home);
+        std::env::set_var("XDG_DATA_HOME", home.join("data"));
+        std::env::set_var("XDG_CONFIG_HOME", home.join("config"));
+        std::env::set_var("XDG_RUNTIME_DIR", home);
+    }
+
+    let mut config = Config::default();
+    config.watch.directories = vec![home.join("Applications").display().to_string()];
+    config.watch.debounce_ms = 0;
+    config.integration.desktop_dir = home.join("applications").display().to_string();
+    config.integration.desktop_dirs = vec![home.join("menu").display().to_string()];
+    config.integration.icon_dir = home.join("icons").display().to_string();
+    config.integration.icon_dirs = vec![home.join("theme").display().to_string()];
+    config.integration.update_database = false;
+    config.notifications.enabled = false;
+
+    let (source, events) = ChannelSource::new();
+    let mut daemon = Daemon::with_source(config, source).unwrap();
+    daemon.set_exit_idle(Some(Duration::from_millis(500)));
+    (daemon, events)
+}
+
+#[test]
+fn test_integrate_and_unintegrate() {
+    let home = TempDir::new().unwrap();
+    let apps = home.path().join("Applications");
+    fs::create_dir_all(&apps).unwrap();
+    let (mut daemon, events) = daemon(home.path());
+    daemon.init().unwrap();
+
+    let path = apps.join("Test_App.AppImage");
+    AppImageBuilder::new("Test App")
+        .version("1.2.3")
+        .write(&path)
+        .unwrap();
+    events.send(FileEvent::Created(path.clone())).unwrap();
+    daemon.run().unwrap();
+
+    let app = daemon.state().get_by_path(&path).expect("integrated");
+    assert_eq!(app.name.as_deref(), Some("test-app"));
+    assert_eq!(app.version.as_deref(), Some("1.2.3"));
+    assert_eq!(app.icon_paths.len(), 2);
+    assert!(app.icon_paths[1].starts_with(home.path().join("theme")));
+    let icon_paths = app.icon_paths.clone();
+    assert_eq!(app.checksums.len(), 4);
+    assert!(integrity::check(app).is_empty());
+    let entry = fs::read_to_string(&app.desktop_path).unwrap();
+    assert!(entry.contains(&format!("TryExec={}", path.display())));
+    let desktop_path = app.desktop_path.clone();
+    let copy = home
+        .path()
+        .join("menu")
+        .join(desktop_path.file_name().unwrap());
+    assert_eq!(app.desktop_copies.as_slice(), std::slice::from_ref(&copy));
+    assert_eq!(fs::read_to_string(&copy).unwrap(), entry);
+
+    // Autostart entries the app created stay known across an update, so
+    // they aren't reported (or blocked) as new again
+    let autostart = home.path().join("config/autostart/test-app.desktop");
+    let mut state = State::load().unwrap();
+    assert!(state.set_autostart_entries(&path, vec![autostart.clone()]));
+    state.save().unwrap();
+    daemon.run().unwrap();
+    AppImageBuilder::new("Test App")
+        .version("1.2.4")
+        .write(&path)
+        .unwrap();
+    events.send(FileEvent::Modified(path.clone())).unwrap();
+    daemon.run().unwrap();
+    let app = daemon.state().get_by_path(&path).expect("updated");
+    assert_eq!(app.version.as_deref(), Some("1.2.4"));
+    assert_eq!(app.autostart_entries, [autostart]);
+    let entry = fs::read_to_string(&desktop_path).unwrap();
+
+    // A broken update leaves the working launcher in place
+    AppImageBuilder::new("Test App")
+        .desktop_entry("[Desktop Entry]\nType=Application\n")
+        .write(&path)
+        .unwrap();
+    events.send(FileEvent::Modified(path.clone())).unwrap();
+    daemon.run().unwrap();
+    let app = daemon.state().get_by_path(&path).expect("still integrated");
+    assert_eq!(app.version.as_deref(), Some("1.2.4"));
+    assert_eq!(fs::read_to_string(&desktop_path).unwrap(), entry);
+
+    fs::remove_file(&path).unwrap();
+    events.send(FileEvent::Deleted(path.clone())).unwrap();
+    daemon.run().unwrap();
+
+    assert!(!daemon.state().is_integrated(&path));
+    assert!(!desktop_path.exists());
+    assert!(!copy.exists());
+    assert!(icon_paths.iter().all(|icon| !icon.exists()));
+
+    // Overrides follow a file moved without the watcher seeing it
+    AppImageBuilder::new("Test App").write(&path).unwrap();
+    events.send(FileEvent::Created(path.clone())).unwrap();
+    daemon.run().unwrap();
+    let identifier = daemon
+        .state()
+        .get_by_path(&path)
+        .unwrap()
+        .identifier
+        .clone();
+    let mut overrides = Overrides::default();
+    overrides.set(
+        &identifier,
+        DesktopOverrides {
+            name: Some("Edited".to_string()),
+            ..Default::default()
+        },
+    );
+    overrides.save().unwrap();
+    let renamed = apps.join("Renamed.AppImage");
+    fs::rename(&path, &renamed).unwrap();
+    events.send(FileEvent::Deleted(path.clone())).unwrap();
+    events.send(FileEvent::Created(renamed.clone())).unwrap();
+    daemon.run().unwrap();
+    let app = daemon.state().get_by_path(&renamed).expect("integrated");
+    assert_ne!(app.identifier, identifier);
+    assert!(
+        fs::read_to_string(&app.desktop_path)
+            .unwrap()
+            .contains("\nName=Edited\n")
+    );
+    assert!(Overrides::load().unwrap().get(&identifier).is_none());
+
+    // A menu entry written by hand is taken over where it is
+    let other = home.path().join("Other.AppImage");
+    AppImageBuilder::new("Other").write(&other).unwrap();
+    let handwritten = home.path().join("applications").join("other.desktop");
+    fs::write(
+        &handwritten,
+        format!(
+            "[Desktop Entry]\nType=Application\nName=Other\nExec=\"{}\" %U\n",
+            other.display()
+        ),
+    )
+    .unwrap();
+    assert_eq!(daemon.adopt(&handwritten).unwrap(), other);
+    let app = daemon.state().get_by_path(&other).expect("adopted");
+    assert_eq!(app.desktop_path, handwritten);
+    assert_eq!(app.icon_paths.len(), 2);
+    let entry = fs::read_to_string(&handwritten).unwrap();
+    assert!(entry.contains(&format!("Icon={}", app.icon_paths[0].display())));
+    assert!(daemon.adopt(&handwritten).is_err());
+}
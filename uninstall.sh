@@ -26,14 +26,23 @@ if [[ $EUID -eq 0 ]]; then
     exit 1
 fi
 
-# --- Stop and disable systemd service ---
-info "Stopping and disabling systemd service..."
-systemctl --user stop appimage-auto.service 2>/dev/null || true
-systemctl --user disable appimage-auto.service 2>/dev/null || true
+# --- Stop and disable systemd units ---
+info "Stopping and disabling systemd units..."
+for unit in appimage-auto.socket appimage-auto-scan.timer appimage-auto.service; do
+    systemctl --user stop "$unit" 2>/dev/null || true
+    systemctl --user disable "$unit" 2>/dev/null || true
+done
 pkill -x appimage-auto 2>/dev/null || true
 pkill -x appimage-auto-gui 2>/dev/null || true
 sleep 0.5
 
+# --- Remove MIME registration and context menus ---
+if [[ -x "$HOME/.local/bin/appimage-auto" ]]; then
+    info "Removing AppImage MIME registration and context menus..."
+    "$HOME/.local/bin/appimage-auto" setup mime --remove >/dev/null 2>&1 || true
+    "$HOME/.local/bin/appimage-auto" setup file-manager --remove >/dev/null 2>&1 || true
+fi
+
 # --- Remove installed files ---
 info "Removing installed files..."
 
@@ -41,6 +50,9 @@ files=(
     "$HOME/.local/bin/appimage-auto"
     "$HOME/.local/bin/appimage-auto-gui"
     "$HOME/.local/share/systemd/user/appimage-auto.service"
+    "$HOME/.local/share/systemd/user/appimage-auto.socket"
+    "$HOME/.local/share/systemd/user/appimage-auto-scan.service"
+    "$HOME/.local/share/systemd/user/appimage-auto-scan.timer"
     "$HOME/.local/share/applications/appimage-auto-gui.desktop"
     "$HOME/.local/share/icons/hicolor/256x256/apps/appimage-auto.png"
 )